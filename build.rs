@@ -0,0 +1,65 @@
+//! Embeds the git commit and the build date for BuildInfo, as the GAMEDEVJAM_GIT_HASH and
+//! GAMEDEVJAM_BUILD_DATE environment variables. Either is left out when it can't be had, e.g.
+//! building from a source tarball without git. The date is that of the commit being built
+//! rather than every rebuild's, unless SOURCE_DATE_EPOCH sets it.
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    if let Some(hash) = git_hash() {
+        println!("cargo:rustc-env=GAMEDEVJAM_GIT_HASH={}", hash);
+    }
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs())
+        });
+    if let Some(seconds) = seconds {
+        println!(
+            "cargo:rustc-env=GAMEDEVJAM_BUILD_DATE={}",
+            utc_date(seconds)
+        );
+    }
+}
+
+fn git_hash() -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    let hash = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    (output.status.success() && !hash.is_empty()).then_some(hash)
+}
+
+/// `seconds` since the Unix epoch as e.g. "2024-06-30 18:05 UTC"
+fn utc_date(seconds: u64) -> String {
+    let (hour, minute) = ((seconds % 86_400) / 3_600, (seconds % 3_600) / 60);
+    // days to a civil date, after Howard Hinnant's civil_from_days
+    let z = (seconds / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year, month, day, hour, minute
+    )
+}
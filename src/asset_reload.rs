@@ -0,0 +1,503 @@
+//! Reloading assets by hand in dev builds, for the web where there's no file watcher: F6, or the
+//! reload_assets console command, requests the asset manifests, the sound manifests and the
+//! current map again. The manifests are then applied again, patching AnimationResource and
+//! SoundResource in place, and the map respawns around a player who stays where they are. A file
+//! that fails to reload leaves what was there, and once everything has answered the console lists
+//! what changed.
+//!
+//! Browsers may answer from their cache, so on the web the default asset source is a
+//! CacheBustingReader: once assets have been reloaded, every file it fetches carries a
+//! `?reload=<n>` query string. Natively the files are read from disk as usual.
+
+use crate::console::{Console, LineKind, RegisterConsoleCommand};
+use crate::gfx::AnimationResource;
+use crate::helpers::tiled::{CurrentMap, MapLoaded, ReloadMap, TiledMap};
+use crate::manifest::{patch_animations, AssetManifest, AssetManifests};
+use crate::sound::{patch_sounds, SoundManifest, SoundManifests, SoundResource};
+use bevy::{
+    asset::{LoadState, UntypedAssetId},
+    ecs::system::SystemParam,
+    prelude::*,
+};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+/// The key reloading assets
+pub const RELOAD_KEY: KeyCode = KeyCode::F6;
+/// Seconds a reload waits for its files before listing them as not answering
+pub const RELOAD_TIMEOUT: f64 = 10.0;
+
+/// Requests the manifests and the current map again
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct ReloadAssets;
+
+///
+/// ReloadGeneration
+///
+/// How many times assets have been reloaded, which the CacheBustingReader puts in its URLs
+#[derive(Resource, Debug, Default, Clone)]
+pub struct ReloadGeneration(Arc<AtomicU32>);
+
+impl ReloadGeneration {
+    pub fn get(&self) -> u32 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn bump(&self) -> u32 {
+        self.0.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+/// A file a reload is waiting on, with what it held before
+enum Waiting {
+    Manifest {
+        handle: Handle<AssetManifest>,
+        old: AssetManifest,
+    },
+    Sounds {
+        handle: Handle<SoundManifest>,
+        old: SoundManifest,
+    },
+    Map {
+        path: String,
+        handle: Option<Handle<TiledMap>>,
+    },
+}
+
+impl Waiting {
+    fn path(&self) -> String {
+        let path = match self {
+            Waiting::Manifest { handle, .. } => handle.path(),
+            Waiting::Sounds { handle, .. } => handle.path(),
+            Waiting::Map { path, .. } => return path.clone(),
+        };
+        path.map_or_else(|| "?".to_string(), ToString::to_string)
+    }
+
+    fn id(&self) -> Option<UntypedAssetId> {
+        match self {
+            Waiting::Manifest { handle, .. } => Some(handle.id().untyped()),
+            Waiting::Sounds { handle, .. } => Some(handle.id().untyped()),
+            Waiting::Map { handle, .. } => handle.as_ref().map(|handle| handle.id().untyped()),
+        }
+    }
+}
+
+///
+/// AssetReload
+///
+/// The reload in progress: the files not back yet, and a line for each that is
+#[derive(Resource, Default)]
+pub struct AssetReload {
+    started: f64,
+    waiting: Vec<Waiting>,
+    report: Vec<String>,
+    /// the files that had failed to load before, whose failure says nothing about this reload
+    failed_before: Vec<UntypedAssetId>,
+}
+
+impl AssetReload {
+    pub fn is_running(&self) -> bool {
+        !self.waiting.is_empty()
+    }
+}
+
+///
+/// AssetReloadPlugin
+///
+/// ReloadAssets, the reload key and the reload_assets console command. Needs the
+/// AssetManifestPlugin and the SoundPlugin, and has to come before the AssetPlugin, since on the
+/// web it replaces the default asset source.
+/// * asset_path: the AssetPlugin's file_path
+pub struct AssetReloadPlugin {
+    pub asset_path: String,
+}
+
+impl Plugin for AssetReloadPlugin {
+    fn build(&self, app: &mut App) {
+        let generation = ReloadGeneration::default();
+        #[cfg(target_arch = "wasm32")]
+        web::register_source(app, &self.asset_path, generation.clone());
+
+        app.insert_resource(generation)
+            .init_resource::<AssetReload>()
+            .add_event::<ReloadAssets>()
+            .add_event::<ReloadMap>()
+            .add_event::<MapLoaded>()
+            .register_console_command(
+                "reload_assets",
+                "Reloads the manifests and the current map",
+                |_, world| {
+                    world.send_event(ReloadAssets);
+                    Ok(String::new())
+                },
+            )
+            .add_systems(
+                Update,
+                (
+                    reload_on_key,
+                    start_reload.run_if(on_event::<ReloadAssets>()),
+                    finish_reload,
+                )
+                    .chain(),
+            );
+    }
+}
+
+///
+/// reload_on_key: Bevy system
+///
+/// Sends ReloadAssets on RELOAD_KEY
+pub fn reload_on_key(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut reload: EventWriter<ReloadAssets>,
+) {
+    if keys.is_some_and(|keys| keys.just_pressed(RELOAD_KEY)) {
+        reload.send(ReloadAssets);
+    }
+}
+
+///
+/// start_reload: Bevy system
+///
+/// Handles ReloadAssets: bumps the ReloadGeneration and reloads the applied manifests and the
+/// current map, noting what each held so finish_reload can tell what changed
+#[allow(clippy::too_many_arguments)]
+pub fn start_reload(
+    mut events: EventReader<ReloadAssets>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time<Real>>,
+    generation: Res<ReloadGeneration>,
+    mut reload: ResMut<AssetReload>,
+    targets: ReloadTargets,
+    current: Res<CurrentMap>,
+    map_query: Query<&Handle<TiledMap>>,
+    mut reload_map: EventWriter<ReloadMap>,
+    mut console: Option<ResMut<Console>>,
+) {
+    events.clear();
+    if reload.is_running() {
+        print(
+            &mut console,
+            LineKind::Error,
+            "Assets are already reloading",
+        );
+        return;
+    }
+
+    let generation = generation.bump();
+    reload.started = time.elapsed_seconds_f64();
+    reload.report.clear();
+    reload.failed_before.clear();
+    for handle in targets.manifests.applied() {
+        let (Some(path), Some(old)) = (handle.path(), targets.manifest_assets.get(handle)) else {
+            continue;
+        };
+        asset_server.reload(path.clone());
+        reload.waiting.push(Waiting::Manifest {
+            handle: handle.clone(),
+            old: old.clone(),
+        });
+    }
+    for handle in targets.sound_manifests.applied() {
+        let (Some(path), Some(old)) = (handle.path(), targets.sound_manifest_assets.get(handle))
+        else {
+            continue;
+        };
+        asset_server.reload(path.clone());
+        reload.waiting.push(Waiting::Sounds {
+            handle: handle.clone(),
+            old: old.clone(),
+        });
+    }
+    if let Some(path) = current.path() {
+        reload_map.send(ReloadMap);
+        reload.waiting.push(Waiting::Map {
+            path: path.to_string(),
+            // worlds have their maps on the members
+            handle: current
+                .entity()
+                .and_then(|entity| map_query.get(entity).ok())
+                .cloned(),
+        });
+    }
+
+    let failed_before: Vec<UntypedAssetId> = reload
+        .waiting
+        .iter()
+        .filter_map(Waiting::id)
+        .filter(|id| failed(&asset_server, *id))
+        .collect();
+    reload.failed_before = failed_before;
+
+    let text = format!(
+        "Reloading {} files (reload {})",
+        reload.waiting.len(),
+        generation
+    );
+    info!("{}", text);
+    print(&mut console, LineKind::Output, &text);
+}
+
+///
+/// ReloadTargets: Bevy system parameter
+///
+/// The manifests a reload requests again, and the resources it patches once they're back
+#[derive(SystemParam)]
+pub struct ReloadTargets<'w> {
+    asset_server: Res<'w, AssetServer>,
+    manifests: Res<'w, AssetManifests>,
+    manifest_assets: Res<'w, Assets<AssetManifest>>,
+    layouts: ResMut<'w, Assets<TextureAtlasLayout>>,
+    animations: ResMut<'w, AnimationResource>,
+    sound_manifests: ResMut<'w, SoundManifests>,
+    sound_manifest_assets: Res<'w, Assets<SoundManifest>>,
+    sounds: ResMut<'w, SoundResource>,
+}
+
+///
+/// finish_reload: Bevy system
+///
+/// Patches in what the files of the reload in progress brought back, and once they're all in,
+/// or RELOAD_TIMEOUT has passed, prints what changed
+pub fn finish_reload(
+    mut reload: ResMut<AssetReload>,
+    time: Res<Time<Real>>,
+    mut manifest_events: EventReader<AssetEvent<AssetManifest>>,
+    mut sound_events: EventReader<AssetEvent<SoundManifest>>,
+    mut loaded: EventReader<MapLoaded>,
+    mut targets: ReloadTargets,
+    mut console: Option<ResMut<Console>>,
+) {
+    let modified_manifests: Vec<AssetId<AssetManifest>> = manifest_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let modified_sounds: Vec<AssetId<SoundManifest>> = sound_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    let map_respawned = loaded.read().any(|event| event.reloaded);
+    if !reload.is_running() {
+        return;
+    }
+
+    let reload = &mut *reload;
+    let asset_server = targets.asset_server.clone();
+    let failed_before = &reload.failed_before;
+    let failed_now = |waiting: &Waiting| {
+        waiting
+            .id()
+            .is_some_and(|id| failed(&asset_server, id) && !failed_before.contains(&id))
+    };
+    let mut report = Vec::new();
+    reload.waiting.retain(|waiting| {
+        let path = waiting.path();
+        let line = match waiting {
+            Waiting::Manifest { handle, old } => {
+                if modified_manifests.contains(&handle.id()) {
+                    let Some(new) = targets.manifest_assets.get(handle) else {
+                        return true;
+                    };
+                    let new = new.clone();
+                    changes_line(
+                        &path,
+                        patch_animations(
+                            &targets.asset_server,
+                            &mut targets.layouts,
+                            &mut targets.animations,
+                            handle.path(),
+                            old,
+                            &new,
+                        ),
+                    )
+                } else if failed_now(waiting) {
+                    format!("{} failed to reload and was left as it was", path)
+                } else {
+                    return true;
+                }
+            }
+            Waiting::Sounds { handle, old } => {
+                if modified_sounds.contains(&handle.id()) {
+                    let Some(new) = targets.sound_manifest_assets.get(handle) else {
+                        return true;
+                    };
+                    let new = new.clone();
+                    changes_line(
+                        &path,
+                        patch_sounds(
+                            &targets.asset_server,
+                            &mut targets.sounds,
+                            &mut targets.sound_manifests,
+                            old,
+                            &new,
+                        ),
+                    )
+                } else if failed_now(waiting) {
+                    format!("{} failed to reload and was left as it was", path)
+                } else {
+                    return true;
+                }
+            }
+            Waiting::Map { .. } => {
+                if map_respawned {
+                    format!("{}: respawned", path)
+                } else if failed_now(waiting) {
+                    format!("{} failed to reload and was left as it was", path)
+                } else {
+                    return true;
+                }
+            }
+        };
+        report.push(line);
+        false
+    });
+    reload.report.extend(report);
+
+    if time.elapsed_seconds_f64() - reload.started >= RELOAD_TIMEOUT {
+        for waiting in reload.waiting.drain(..) {
+            reload
+                .report
+                .push(format!("{} didn't answer", waiting.path()));
+        }
+    }
+    if reload.is_running() {
+        return;
+    }
+    let text = format!("Reloaded assets:\n{}", reload.report.join("\n"));
+    info!("{}", text);
+    print(&mut console, LineKind::Output, &text);
+}
+
+fn failed(asset_server: &AssetServer, id: UntypedAssetId) -> bool {
+    matches!(asset_server.get_load_state(id), Some(LoadState::Failed))
+}
+
+fn changes_line(path: &str, changes: Vec<String>) -> String {
+    match changes.is_empty() {
+        true => format!("{}: no changes", path),
+        false => format!("{}: {}", path, changes.join(", ")),
+    }
+}
+
+fn print(console: &mut Option<ResMut<Console>>, kind: LineKind, text: &str) {
+    if let Some(console) = console.as_mut() {
+        console.print(kind, text);
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use super::ReloadGeneration;
+    use bevy::{
+        asset::io::{
+            AssetReader, AssetReaderError, AssetSource, AssetSourceId, PathStream, Reader,
+            VecReader,
+        },
+        prelude::*,
+        utils::BoxedFuture,
+    };
+    use std::path::{Path, PathBuf};
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+
+    /// Makes the default asset source a CacheBustingReader over `asset_path`
+    pub(super) fn register_source(app: &mut App, asset_path: &str, generation: ReloadGeneration) {
+        let root = PathBuf::from(asset_path);
+        app.register_asset_source(
+            AssetSourceId::Default,
+            AssetSource::build().with_reader(move || {
+                Box::new(CacheBustingReader {
+                    root: root.clone(),
+                    generation: generation.clone(),
+                })
+            }),
+        );
+    }
+
+    /// Fetches assets like bevy's own web reader, adding `?reload=<n>` to the URLs once they've
+    /// been reloaded so the browser's cache doesn't answer
+    struct CacheBustingReader {
+        root: PathBuf,
+        generation: ReloadGeneration,
+    }
+
+    impl CacheBustingReader {
+        async fn fetch(&self, path: &Path) -> Result<Vec<u8>, AssetReaderError> {
+            let mut url = self.root.join(path).to_string_lossy().replace('\\', "/");
+            let generation = self.generation.get();
+            if generation > 0 {
+                url = format!("{}?reload={}", url, generation);
+            }
+            let response = request(&url).await.map_err(|e| {
+                let message = e
+                    .as_string()
+                    .unwrap_or_else(|| format!("fetching {} failed: {:?}", url, e));
+                AssetReaderError::Io(std::io::Error::new(std::io::ErrorKind::Other, message).into())
+            })?;
+            response.ok_or_else(|| AssetReaderError::NotFound(path.to_path_buf()))
+        }
+    }
+
+    /// The body of `url`, None if there's nothing there
+    async fn request(url: &str) -> Result<Option<Vec<u8>>, JsValue> {
+        let window = web_sys::window().ok_or("there's no window to fetch from")?;
+        let response: web_sys::Response = JsFuture::from(window.fetch_with_str(url))
+            .await?
+            .dyn_into()?;
+        match response.status() {
+            404 => Ok(None),
+            _ if !response.ok() => Err(format!("{} answered {}", url, response.status()).into()),
+            _ => {
+                let body = JsFuture::from(response.array_buffer()?).await?;
+                Ok(Some(js_sys::Uint8Array::new(&body).to_vec()))
+            }
+        }
+    }
+
+    impl AssetReader for CacheBustingReader {
+        fn read<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+            Box::pin(async move {
+                let reader: Box<Reader> = Box::new(VecReader::new(self.fetch(path).await?));
+                Ok(reader)
+            })
+        }
+
+        fn read_meta<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+            Box::pin(async move {
+                let mut meta = path.as_os_str().to_owned();
+                meta.push(".meta");
+                let bytes = self.fetch(Path::new(&meta)).await?;
+                let reader: Box<Reader> = Box::new(VecReader::new(bytes));
+                Ok(reader)
+            })
+        }
+
+        fn read_directory<'a>(
+            &'a self,
+            path: &'a Path,
+        ) -> BoxedFuture<'a, Result<Box<PathStream>, AssetReaderError>> {
+            // http can't list directories, nor can bevy's own web reader
+            Box::pin(async move { Err(AssetReaderError::NotFound(path.to_path_buf())) })
+        }
+
+        fn is_directory<'a>(
+            &'a self,
+            _path: &'a Path,
+        ) -> BoxedFuture<'a, Result<bool, AssetReaderError>> {
+            Box::pin(async move { Ok(false) })
+        }
+    }
+}
@@ -0,0 +1,418 @@
+//! The game and its hosting page talking to each other.
+//!
+//! The page listens to events: a BridgeEvent called "level_complete" is
+//! dispatched on `window` as a CustomEvent of type "game:level_complete", with the payload as
+//! its `detail`:
+//!
+//! ```js
+//! window.addEventListener("game:level_complete", (event) => showShareDialog(event.detail));
+//! ```
+//!
+//! The game sends level_complete, game_over, score_changed and save_written on its own, see
+//! BridgePlugin. Gameplay code can send any other with an `EventWriter<BridgeEvent>`. Natively
+//! the events are logged instead.
+//!
+//! And the page sends PageCommands with `send_command`, e.g.
+//! `send_command('{"type": "load_map", "path": "level3.tmx"}')`. They queue up until the game
+//! reads them at the start of its next frame, so commands sent before start() wait for the game
+//! to run.
+
+use crate::helpers::tiled::{CurrentMap, LoadMap};
+use crate::options::StartOptions;
+use crate::replay::SaveReplay;
+use crate::save::{GameProgress, GameSaved};
+use crate::sound::{AudioChannel, SetMuted, SetVolume};
+use crate::state::{AppState, ChangeState};
+use crate::timestep::SetFixedRate;
+use bevy::prelude::*;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use thiserror::Error;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsValue;
+
+/// Prefix of the page's event types, so they don't clash with the DOM's own
+pub const BRIDGE_EVENT_PREFIX: &str = "game:";
+
+///
+/// BridgeEvent
+///
+/// An event for the page
+/// * name: the event type without BRIDGE_EVENT_PREFIX, in snake_case
+/// * payload: the CustomEvent's `detail`, null for none
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct BridgeEvent {
+    pub name: String,
+    pub payload: Value,
+}
+
+impl BridgeEvent {
+    pub fn new(name: impl Into<String>, payload: Value) -> Self {
+        BridgeEvent {
+            name: name.into(),
+            payload,
+        }
+    }
+
+    /// An event without a payload
+    pub fn named(name: impl Into<String>) -> Self {
+        BridgeEvent::new(name, Value::Null)
+    }
+
+    /// The type of the DOM event, e.g. "game:level_complete"
+    pub fn event_type(&self) -> String {
+        format!("{}{}", BRIDGE_EVENT_PREFIX, self.name)
+    }
+}
+
+/// Sent by gameplay code when the player finishes the current level. The page gets a
+/// level_complete with the map, score and play time.
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct LevelComplete;
+
+///
+/// PageCommand
+///
+/// What the page can ask of the running game, as JSON tagged with its snake_case `type`:
+/// * set_muted `{ "muted": true }`: mutes or unmutes all sound
+/// * set_volume `{ "channel": "music", "volume": 0.5 }`: "music", "sfx" or "ambient", 0 to 1
+/// * load_map `{ "path": "level3.tmx", "spawn": "west" }`: in game, switches to the map, spawn
+///   being optional. Anywhere else it becomes the map new games start on, and from the main
+///   menu the game starts.
+/// * grant_item `{ "item": "golden_key" }`: adds the item to GameProgress::inventory
+/// * trigger_event `{ "name": "open_gates" }`: sends PageTrigger, for gameplay code to react to
+/// * set_fixed_rate `{ "hz": 30 }`: changes how often FixedUpdate runs, for experimenting
+/// * save_replay `{}`: writes the input recorded so far to storage, when recording
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum PageCommand {
+    SetMuted {
+        muted: bool,
+    },
+    SetVolume {
+        channel: AudioChannel,
+        volume: f32,
+    },
+    LoadMap {
+        path: String,
+        #[serde(default)]
+        spawn: Option<String>,
+    },
+    GrantItem {
+        item: String,
+    },
+    TriggerEvent {
+        name: String,
+    },
+    SetFixedRate {
+        hz: f64,
+    },
+    SaveReplay,
+}
+
+#[derive(Error, Debug)]
+pub enum CommandError {
+    #[error("invalid command: {0}")]
+    Parse(#[from] serde_json::Error),
+}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CommandError> for JsValue {
+    fn from(e: CommandError) -> Self {
+        js_sys::Error::new(&e.to_string()).into()
+    }
+}
+
+/// Sent for the page's trigger_event commands
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct PageTrigger {
+    pub name: String,
+}
+
+///
+/// CommandChannel
+///
+/// Where PageCommands wait for the game. Clones share the queue.
+#[derive(Resource, Debug, Clone)]
+pub struct CommandChannel {
+    sender: Sender<PageCommand>,
+    receiver: Arc<Mutex<Receiver<PageCommand>>>,
+}
+
+impl Default for CommandChannel {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        CommandChannel {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+        }
+    }
+}
+
+impl CommandChannel {
+    /// The channel send_command sends to, which outlives the games reading it
+    pub fn global() -> CommandChannel {
+        static GLOBAL: OnceLock<CommandChannel> = OnceLock::new();
+        GLOBAL.get_or_init(CommandChannel::default).clone()
+    }
+
+    pub fn send(&self, command: PageCommand) {
+        // can't fail, self holds the receiver
+        let _ = self.sender.send(command);
+    }
+
+    /// Sends the PageCommand in `json`, or says what's wrong with it
+    pub fn send_json(&self, json: &str) -> Result<(), CommandError> {
+        self.send(serde_json::from_str(json)?);
+        Ok(())
+    }
+
+    /// Takes the commands sent so far
+    pub fn drain(&self) -> Vec<PageCommand> {
+        match self.receiver.lock() {
+            Ok(receiver) => receiver.try_iter().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Sends the PageCommand in `json` to the game, running or not yet started
+pub fn send_command(json: &str) -> Result<(), CommandError> {
+    CommandChannel::global().send_json(json)
+}
+
+///
+/// BridgePlugin
+///
+/// Sends BridgeEvents to the page and runs the PageCommands of `commands`, by default those
+/// sent with send_command. The built in events:
+/// * level_complete `{ map, score, play_time }`: on LevelComplete
+/// * game_over `{ score, play_time }`: entering AppState::GameOver
+/// * score_changed `{ score, previous }`: when GameProgress::score changes
+/// * save_written `{ map }`: when SaveGame has written the save
+#[derive(Debug, Clone)]
+pub struct BridgePlugin {
+    pub commands: CommandChannel,
+}
+
+impl Default for BridgePlugin {
+    fn default() -> Self {
+        BridgePlugin {
+            commands: CommandChannel::global(),
+        }
+    }
+}
+
+impl Plugin for BridgePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BridgeEvent>()
+            .add_event::<LevelComplete>()
+            .add_event::<GameSaved>()
+            .add_event::<PageTrigger>()
+            .add_event::<LoadMap>()
+            .add_event::<SetMuted>()
+            .add_event::<SetVolume>()
+            .add_event::<ChangeState>()
+            .add_event::<SetFixedRate>()
+            .add_event::<SaveReplay>()
+            .insert_resource(self.commands.clone())
+            .add_systems(First, run_page_commands)
+            .add_systems(OnEnter(AppState::GameOver), emit_game_over)
+            .add_systems(
+                PostUpdate,
+                (
+                    emit_level_complete,
+                    emit_score_changed,
+                    emit_save_written,
+                    dispatch_bridge_events,
+                )
+                    .chain(),
+            );
+    }
+}
+
+///
+/// run_page_commands: Bevy system
+///
+/// Sends the events for the PageCommands sent since last frame
+#[allow(clippy::too_many_arguments)]
+pub fn run_page_commands(
+    channel: Res<CommandChannel>,
+    state: Option<Res<State<AppState>>>,
+    mut options: Option<ResMut<StartOptions>>,
+    mut progress: Option<ResMut<GameProgress>>,
+    mut set_muted: EventWriter<SetMuted>,
+    mut set_volume: EventWriter<SetVolume>,
+    mut load_map: EventWriter<LoadMap>,
+    mut change_state: EventWriter<ChangeState>,
+    mut triggers: EventWriter<PageTrigger>,
+    mut set_fixed_rate: EventWriter<SetFixedRate>,
+    mut save_replay: EventWriter<SaveReplay>,
+) {
+    for command in channel.drain() {
+        debug!("Page command: {:?}", command);
+        match command {
+            PageCommand::SetMuted { muted } => {
+                set_muted.send(SetMuted(muted));
+            }
+            PageCommand::SetVolume { channel, volume } => {
+                set_volume.send(SetVolume { channel, volume });
+            }
+            PageCommand::LoadMap { path, spawn } => {
+                let state = state.as_ref().map(|state| *state.get());
+                if state == Some(AppState::InGame) {
+                    let mut load = LoadMap::new(path);
+                    if let Some(spawn) = spawn {
+                        load = load.with_spawn(spawn);
+                    }
+                    load_map.send(load);
+                } else {
+                    // start_game loads it, there's no spawn to pick for a new game
+                    if let Some(options) = options.as_mut() {
+                        options.map = path;
+                    }
+                    if state == Some(AppState::MainMenu) {
+                        change_state.send(ChangeState(AppState::InGame));
+                    }
+                }
+            }
+            PageCommand::GrantItem { item } => match progress.as_mut() {
+                Some(progress) => progress.inventory.push(item),
+                None => warn!("No GameProgress to grant {} to", item),
+            },
+            PageCommand::TriggerEvent { name } => {
+                triggers.send(PageTrigger { name });
+            }
+            PageCommand::SetFixedRate { hz } => {
+                set_fixed_rate.send(SetFixedRate(hz));
+            }
+            PageCommand::SaveReplay => {
+                save_replay.send(SaveReplay);
+            }
+        }
+    }
+}
+
+fn progress_payload(progress: Option<&GameProgress>) -> (u64, f64) {
+    progress.map_or((0, 0.0), |progress| (progress.score, progress.play_time))
+}
+
+///
+/// emit_level_complete: Bevy system
+///
+/// Sends level_complete for LevelComplete
+pub fn emit_level_complete(
+    mut completed: EventReader<LevelComplete>,
+    current: Option<Res<CurrentMap>>,
+    progress: Option<Res<GameProgress>>,
+    mut bridge: EventWriter<BridgeEvent>,
+) {
+    for _ in completed.read() {
+        let (score, play_time) = progress_payload(progress.as_deref());
+        bridge.send(BridgeEvent::new(
+            "level_complete",
+            json!({
+                "map": current.as_ref().and_then(|current| current.path()),
+                "score": score,
+                "play_time": play_time,
+            }),
+        ));
+    }
+}
+
+///
+/// emit_game_over: Bevy system
+///
+/// Sends game_over
+pub fn emit_game_over(progress: Option<Res<GameProgress>>, mut bridge: EventWriter<BridgeEvent>) {
+    let (score, play_time) = progress_payload(progress.as_deref());
+    bridge.send(BridgeEvent::new(
+        "game_over",
+        json!({ "score": score, "play_time": play_time }),
+    ));
+}
+
+///
+/// emit_score_changed: Bevy system
+///
+/// Sends score_changed when GameProgress::score isn't what it was last frame. The score the game
+/// starts with isn't a change.
+pub fn emit_score_changed(
+    progress: Option<Res<GameProgress>>,
+    mut last_score: Local<Option<u64>>,
+    mut bridge: EventWriter<BridgeEvent>,
+) {
+    let Some(progress) = progress else {
+        return;
+    };
+    if !progress.is_changed() {
+        return;
+    }
+    let previous = last_score.replace(progress.score);
+    match previous {
+        Some(previous) if previous != progress.score => {
+            bridge.send(BridgeEvent::new(
+                "score_changed",
+                json!({ "score": progress.score, "previous": previous }),
+            ));
+        }
+        _ => {}
+    }
+}
+
+///
+/// emit_save_written: Bevy system
+///
+/// Sends save_written for GameSaved
+pub fn emit_save_written(mut saved: EventReader<GameSaved>, mut bridge: EventWriter<BridgeEvent>) {
+    for event in saved.read() {
+        bridge.send(BridgeEvent::new(
+            "save_written",
+            json!({ "map": event.map }),
+        ));
+    }
+}
+
+///
+/// dispatch_bridge_events: Bevy system
+///
+/// Dispatches BridgeEvents on the page's window
+#[cfg(target_arch = "wasm32")]
+pub fn dispatch_bridge_events(mut events: EventReader<BridgeEvent>) {
+    let Some(window) = web_sys::window() else {
+        events.clear();
+        return;
+    };
+    for event in events.read() {
+        // through JSON, as serde-wasm-bindgen would make JS Maps of objects
+        let detail = js_sys::JSON::parse(&event.payload.to_string()).unwrap_or_default();
+        let event_type = event.event_type();
+        // initCustomEvent rather than a CustomEventInit, whose setters changed across web-sys
+        // releases
+        let dispatched = web_sys::CustomEvent::new(&event_type).and_then(|dom_event| {
+            dom_event.init_custom_event_with_can_bubble_and_cancelable_and_detail(
+                &event_type,
+                false,
+                false,
+                &detail,
+            );
+            window.dispatch_event(&dom_event)
+        });
+        if let Err(e) = dispatched {
+            warn!("Could not dispatch {}: {:?}", event_type, e);
+        }
+    }
+}
+
+///
+/// dispatch_bridge_events: Bevy system
+///
+/// Logs BridgeEvents, there being no page to send them to
+#[cfg(not(target_arch = "wasm32"))]
+pub fn dispatch_bridge_events(mut events: EventReader<BridgeEvent>) {
+    for event in events.read() {
+        info!("Bridge event {}: {}", event.event_type(), event.payload);
+    }
+}
@@ -0,0 +1,102 @@
+//! Which build is running, for bug reports and telemetry: the crate version, and the git commit
+//! and build date that build.rs embeds when it can. It's logged at startup, shown on the main
+//! menu and the diagnostics overlay, sent with crash reports and leaderboard scores, and the page
+//! can ask for it with `get_version()`.
+
+use crate::locale::LocalizedText;
+use crate::state::AppState;
+use bevy::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+///
+/// BuildInfo
+///
+/// * version: the crate version, e.g. "0.1.0"
+/// * git_hash: short hash of the commit built, None without git
+/// * built: when it was built, e.g. "2024-06-30 18:05 UTC", None if unknown
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub version: String,
+    pub git_hash: Option<String>,
+    pub built: Option<String>,
+}
+
+impl BuildInfo {
+    /// This build's
+    pub fn current() -> Self {
+        BuildInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: option_env!("GAMEDEVJAM_GIT_HASH").map(String::from),
+            built: option_env!("GAMEDEVJAM_BUILD_DATE").map(String::from),
+        }
+    }
+}
+
+impl Default for BuildInfo {
+    fn default() -> Self {
+        BuildInfo::current()
+    }
+}
+
+/// e.g. "v0.1.0 3303029 (2024-06-30 18:05 UTC)"
+impl fmt::Display for BuildInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.version)?;
+        if let Some(hash) = &self.git_hash {
+            write!(f, " {}", hash)?;
+        }
+        if let Some(built) = &self.built {
+            write!(f, " ({})", built)?;
+        }
+        Ok(())
+    }
+}
+
+/// Marks the version shown in a corner of the main menu
+#[derive(Component, Debug)]
+pub struct VersionLabel;
+
+/// BuildInfo, logged at startup and shown on the main menu
+pub struct BuildInfoPlugin;
+
+impl Plugin for BuildInfoPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BuildInfo>()
+            .add_systems(Startup, log_build_info)
+            .add_systems(OnEnter(AppState::MainMenu), spawn_version_label)
+            .add_systems(OnExit(AppState::MainMenu), despawn_version_label);
+    }
+}
+
+fn log_build_info(build: Res<BuildInfo>) {
+    info!("Build: {}", *build);
+}
+
+fn spawn_version_label(mut commands: Commands, build: Res<BuildInfo>) {
+    commands.spawn((
+        VersionLabel,
+        LocalizedText::new("menu.build").with_arg("build", build.to_string()),
+        TextBundle::from_section(
+            build.to_string(),
+            TextStyle {
+                font_size: 12.0,
+                color: Color::rgba(1.0, 1.0, 1.0, 0.6),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            right: Val::Px(8.0),
+            bottom: Val::Px(8.0),
+            ..default()
+        }),
+    ));
+}
+
+fn despawn_version_label(mut commands: Commands, label_query: Query<Entity, With<VersionLabel>>) {
+    for entity in label_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,225 @@
+//! Fitting the canvas to its parent element on the web, with the `fitToParent` start option, so
+//! the game can be embedded in a responsive layout without CSS scaling blurring it.
+//!
+//! A ResizeObserver watches the parent's size in CSS pixels, and the devicePixelRatio is checked
+//! every frame, which picks up a window dragged to a screen of another density. Once a new size
+//! has held for RESIZE_DEBOUNCE, the window's logical size becomes the parent's and its scale
+//! factor the devicePixelRatio, so the backbuffer has exactly as many pixels as the screen area
+//! it covers. The camera's ScalingMode decides how much of the world that shows; UI is laid out
+//! in CSS pixels, so text keeps its size on high-DPI phones.
+//!
+//! The parent has to get its size from the page's CSS rather than from the canvas inside it, or
+//! the two grow each other.
+//!
+//! Going fullscreen resizes the window through CanvasFit too, on any page.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+/// Seconds a new size has to hold before the window follows it, so dragging a window edge
+/// doesn't resize the backbuffer every frame
+pub const RESIZE_DEBOUNCE: f64 = 0.15;
+
+///
+/// CanvasSize
+///
+/// * width, height: the parent element's size in CSS pixels
+/// * scale_factor: the devicePixelRatio, physical pixels per CSS pixel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CanvasSize {
+    pub width: f32,
+    pub height: f32,
+    pub scale_factor: f32,
+}
+
+impl CanvasSize {
+    /// Size of the backbuffer in physical pixels
+    pub fn physical(&self) -> UVec2 {
+        (Vec2::new(self.width, self.height) * self.scale_factor)
+            .round()
+            .as_uvec2()
+    }
+}
+
+///
+/// CanvasFit
+///
+/// The latest size observed for the canvas, and the one the window was last given
+#[derive(Resource, Debug, Default)]
+pub struct CanvasFit {
+    observed: Option<CanvasSize>,
+    observed_at: f64,
+    applied: Option<CanvasSize>,
+}
+
+impl CanvasFit {
+    /// Records the size seen at `now`, in seconds of real time. Sizes with no area, like that of
+    /// a hidden parent, are ignored.
+    pub fn observe(&mut self, size: CanvasSize, now: f64) {
+        if size.width < 1.0 || size.height < 1.0 || size.scale_factor <= 0.0 {
+            return;
+        }
+        if self.observed != Some(size) {
+            self.observed = Some(size);
+            self.observed_at = now;
+        }
+    }
+
+    /// The observed size, once it has held for RESIZE_DEBOUNCE and if it hasn't been applied yet
+    pub fn settled(&mut self, now: f64) -> Option<CanvasSize> {
+        let observed = self.observed?;
+        // against when it settles, since now - observed_at can round to just under RESIZE_DEBOUNCE
+        if self.applied == Some(observed) || now < self.observed_at + RESIZE_DEBOUNCE {
+            return None;
+        }
+        self.applied = Some(observed);
+        Some(observed)
+    }
+}
+
+///
+/// CanvasFitPlugin
+///
+/// Resizes the primary window to the CanvasFit size, and with observe_parent, on the web, keeps
+/// that the size of its canvas's parent element. Fullscreen goes through it too.
+#[derive(Default)]
+pub struct CanvasFitPlugin {
+    pub observe_parent: bool,
+}
+
+impl Plugin for CanvasFitPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CanvasFit>()
+            .add_systems(PreUpdate, fit_canvas);
+
+        #[cfg(target_arch = "wasm32")]
+        if self.observe_parent {
+            app.init_non_send_resource::<page::ParentObserver>()
+                .add_systems(PreUpdate, page::observe_canvas_parent.before(fit_canvas));
+        }
+    }
+}
+
+///
+/// fit_canvas: Bevy system
+///
+/// Gives the primary window the settled CanvasFit size
+pub fn fit_canvas(
+    time: Res<Time<Real>>,
+    mut fit: ResMut<CanvasFit>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let Some(size) = fit.settled(time.elapsed_seconds_f64()) else {
+        return;
+    };
+    for mut window in window_query.iter_mut() {
+        window
+            .resolution
+            .set_scale_factor_override(Some(size.scale_factor));
+        window.resolution.set(size.width, size.height);
+    }
+    debug!(
+        "Canvas fitted to {}x{} at {}x, {} physical",
+        size.width,
+        size.height,
+        size.scale_factor,
+        size.physical()
+    );
+}
+
+#[cfg(target_arch = "wasm32")]
+mod page {
+    use super::{CanvasFit, CanvasSize};
+    use crate::fullscreen::Fullscreen;
+    use bevy::{prelude::*, window::PrimaryWindow};
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use wasm_bindgen::{closure::Closure, JsCast};
+
+    /// The ResizeObserver on the canvas's parent, once the canvas is on the page
+    #[derive(Default)]
+    pub(super) struct ParentObserver {
+        observing: Option<Observing>,
+    }
+
+    struct Observing {
+        observer: web_sys::ResizeObserver,
+        // called by the observer for as long as it's kept
+        _callback: Closure<dyn FnMut(js_sys::Array)>,
+        size: Rc<Cell<Option<(f32, f32)>>>,
+    }
+
+    impl Drop for Observing {
+        fn drop(&mut self) {
+            self.observer.disconnect();
+        }
+    }
+
+    impl Observing {
+        /// Observes the parent of the canvas matching `selector`, or of the first canvas
+        fn start(selector: Option<&str>) -> Option<Self> {
+            let document = web_sys::window()?.document()?;
+            let canvas = document
+                .query_selector(selector.unwrap_or("canvas"))
+                .ok()
+                .flatten()?;
+            let parent = canvas.parent_element()?;
+
+            let size = Rc::new(Cell::new(None));
+            let seen = size.clone();
+            let callback =
+                Closure::<dyn FnMut(js_sys::Array)>::new(move |entries: js_sys::Array| {
+                    let Ok(entry) = entries.get(0).dyn_into::<web_sys::ResizeObserverEntry>()
+                    else {
+                        return;
+                    };
+                    let rect = entry.content_rect();
+                    seen.set(Some((rect.width() as f32, rect.height() as f32)));
+                });
+            let observer = web_sys::ResizeObserver::new(callback.as_ref().unchecked_ref()).ok()?;
+            // it reports the current size straight away
+            observer.observe(&parent);
+            Some(Observing {
+                observer,
+                _callback: callback,
+                size,
+            })
+        }
+    }
+
+    ///
+    /// observe_canvas_parent: Bevy system
+    ///
+    /// Records the parent's size and the devicePixelRatio in CanvasFit, starting to observe the
+    /// parent once the canvas is on the page. While the canvas is fullscreen it's the screen's
+    /// size that counts, see the fullscreen module.
+    pub(super) fn observe_canvas_parent(
+        mut parent: NonSendMut<ParentObserver>,
+        time: Res<Time<Real>>,
+        mut fit: ResMut<CanvasFit>,
+        fullscreen: Option<Res<Fullscreen>>,
+        window_query: Query<&Window, With<PrimaryWindow>>,
+    ) {
+        if fullscreen.map_or(false, |fullscreen| fullscreen.is_active()) {
+            return;
+        }
+        if parent.observing.is_none() {
+            let selector = window_query
+                .get_single()
+                .ok()
+                .and_then(|window| window.canvas.as_deref());
+            parent.observing = Observing::start(selector);
+        }
+        let Some((width, height)) = parent.observing.as_ref().and_then(|o| o.size.get()) else {
+            return;
+        };
+        let scale_factor = web_sys::window().map_or(1.0, |window| window.device_pixel_ratio());
+        fit.observe(
+            CanvasSize {
+                width,
+                height,
+                scale_factor: scale_factor as f32,
+            },
+            time.elapsed_seconds_f64(),
+        );
+    }
+}
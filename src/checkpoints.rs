@@ -0,0 +1,357 @@
+//! Checkpoints: Tiled objects of type "checkpoint" are trigger regions, on any layer, that the
+//! player comes back to on dying. The first time the player walks into one it lights up, showing
+//! the last frame of the CHECKPOINT_ANIMATION clip instead of the first, plays CHECKPOINT_SOUND
+//! and becomes the active checkpoint, kept in GameProgress::checkpoint and so in saves, with an
+//! autosave. Walking into an earlier checkpoint leaves the active one be, unless it's marked
+//! `reactivate`: earlier being one that's lit already, or one of lower `order` on the same map.
+//!
+//! When the player dies with an active checkpoint, the music gives way to DEATH_STINGER, the
+//! player plays its death animation, the screen fades out and the player is spawned again there
+//! with full health, then the screen fades back in. By default the checkpoint's map is loaded
+//! again, so the room's enemies and hazards start over; RespawnSettings::reset_room turns that
+//! off for checkpoints on the current map. PlayerRespawned is sent, the camera snaps onto the
+//! player and music paused for the stinger resumes. With no active checkpoint, dying is game
+//! over.
+//!
+//! Object properties:
+//! * order: where it comes in the map's checkpoints, lower ones being earlier
+//! * reactivate: walking into it makes it the active checkpoint even if it's an earlier one
+
+use crate::gfx::{Animation, AnimationResource, MainCamera, ScreenFade};
+use crate::health::Died;
+use crate::helpers::tiled::{
+    place_at_spawn_point, CurrentMap, LoadMap, MapLoaded, RegisterTiledObject, TiledObject,
+    TriggerEntered, TriggerRegion,
+};
+use crate::player::{spawn_player, Player};
+use crate::save::{GameProgress, RequestAutosave, SavedCheckpoint};
+use crate::sound::{PlaySFX, PlayStinger, ResumeMusic};
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+/// Object type of checkpoints
+pub const CHECKPOINT_TYPE: &str = "checkpoint";
+/// The AnimationResource animation checkpoints are drawn with: its first frame while unlit and
+/// its last once lit
+pub const CHECKPOINT_ANIMATION: &str = "checkpoint";
+/// Sound played where a checkpoint lights up
+pub const CHECKPOINT_SOUND: &str = "checkpoint";
+/// Stinger replacing the music when the player dies
+pub const DEATH_STINGER: &str = "death";
+/// Seconds to fade out and back in on respawning
+pub const RESPAWN_FADE: f32 = 0.4;
+
+/// On checkpoint objects, from their properties
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub order: Option<i32>,
+    pub reactivate: bool,
+}
+
+/// Sent when the player is spawned again at the active checkpoint, at `position`
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct PlayerRespawned {
+    pub entity: Entity,
+    pub position: Vec2,
+}
+
+///
+/// RespawnSettings
+///
+/// How the player comes back at a checkpoint
+/// * reset_room: load the checkpoint's map again, so its enemies and hazards start over; the
+///   checkpoint's map is always loaded when the player died on another one
+/// * fade: seconds to fade out and back in
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct RespawnSettings {
+    pub reset_room: bool,
+    pub fade: f32,
+}
+
+impl Default for RespawnSettings {
+    fn default() -> Self {
+        RespawnSettings {
+            reset_room: true,
+            fade: RESPAWN_FADE,
+        }
+    }
+}
+
+///
+/// Respawn
+///
+/// Where bringing the player back is at
+/// * Dying: playing the player's death animation
+/// * FadingOut: fading out, to spawn the player again, or load its checkpoint's map
+/// * Loading: LoadMap sent for the checkpoint's map, waiting for MapLoaded
+/// * FadingIn: the player is back
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub enum Respawn {
+    #[default]
+    Idle,
+    Dying(SavedCheckpoint),
+    FadingOut(SavedCheckpoint),
+    Loading(SavedCheckpoint),
+    FadingIn,
+}
+
+impl Respawn {
+    pub fn is_idle(&self) -> bool {
+        matches!(self, Respawn::Idle)
+    }
+}
+
+/// Checkpoints from Tiled and bringing the player back at them
+pub struct CheckpointsPlugin;
+
+impl Plugin for CheckpointsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameProgress>()
+            .init_resource::<CurrentMap>()
+            .init_resource::<RespawnSettings>()
+            .init_resource::<Respawn>()
+            .add_event::<PlayerRespawned>()
+            .add_event::<Died>()
+            .add_event::<TriggerEntered>()
+            .add_event::<MapLoaded>()
+            .add_event::<LoadMap>()
+            .add_event::<RequestAutosave>()
+            .add_event::<PlaySFX>()
+            .add_event::<PlayStinger>()
+            .add_event::<ResumeMusic>()
+            .register_trigger_object(CHECKPOINT_TYPE, spawn_checkpoint)
+            .add_systems(
+                Update,
+                (
+                    (activate_checkpoints, dress_checkpoints, show_checkpoints).chain(),
+                    (
+                        start_respawn,
+                        advance_respawn.after(place_at_spawn_point),
+                        snap_to_respawn,
+                    )
+                        .chain(),
+                ),
+            );
+    }
+}
+
+fn spawn_checkpoint(entity: &mut EntityCommands, object: &TiledObject) {
+    entity.insert(Checkpoint {
+        order: object.get_int("order"),
+        reactivate: object.get_bool("reactivate").unwrap_or(false),
+    });
+}
+
+///
+/// activate_checkpoints: Bevy system
+///
+/// Lights the checkpoints the player walks into and makes them the active checkpoint, unless
+/// they're earlier than it
+pub fn activate_checkpoints(
+    mut entered: EventReader<TriggerEntered>,
+    mut progress: ResMut<GameProgress>,
+    current: Res<CurrentMap>,
+    checkpoint_query: Query<(&Checkpoint, &TiledObject, &TriggerRegion)>,
+    player_query: Query<(), With<Player>>,
+    mut autosave: EventWriter<RequestAutosave>,
+    mut sfx: EventWriter<PlaySFX>,
+) {
+    for event in entered.read() {
+        if !player_query.contains(event.entity) {
+            continue;
+        }
+        let Ok((checkpoint, object, region)) = checkpoint_query.get(event.region) else {
+            continue;
+        };
+        let Some(map) = current.path() else {
+            continue;
+        };
+        let position = region.rect.center();
+        let lit = progress.has_lit(map, object.id);
+        if !lit {
+            progress.light(map, object.id);
+            sfx.send(PlaySFX::at(CHECKPOINT_SOUND, position));
+        }
+
+        let (active, earlier) = match &progress.checkpoint {
+            Some(active) if active.map == map => (
+                active.id == object.id,
+                lit || checkpoint
+                    .order
+                    .zip(active.order)
+                    .is_some_and(|(order, active)| order < active),
+            ),
+            _ => (false, lit),
+        };
+        if active || (earlier && !checkpoint.reactivate) {
+            continue;
+        }
+        progress.checkpoint = Some(SavedCheckpoint {
+            map: map.to_string(),
+            id: object.id,
+            position,
+            order: checkpoint.order,
+        });
+        autosave.send(RequestAutosave);
+    }
+}
+
+///
+/// dress_checkpoints: Bevy system
+///
+/// Gives new checkpoints the sprite of the CHECKPOINT_ANIMATION clip, if there is one
+pub fn dress_checkpoints(
+    mut commands: Commands,
+    animations: Option<Res<AnimationResource>>,
+    checkpoint_query: Query<Entity, Added<Checkpoint>>,
+) {
+    let Some(clip) = animations.and_then(|animations| animations.get(CHECKPOINT_ANIMATION)) else {
+        return;
+    };
+    for entity in checkpoint_query.iter() {
+        commands.entity(entity).insert((
+            Sprite::default(),
+            TextureAtlas {
+                layout: clip.atlas().clone(),
+                index: clip.frame(),
+            },
+            clip.texture().clone(),
+        ));
+    }
+}
+
+///
+/// show_checkpoints: Bevy system
+///
+/// Shows each checkpoint's frame of the CHECKPOINT_ANIMATION clip for whether it's lit
+pub fn show_checkpoints(
+    progress: Res<GameProgress>,
+    current: Res<CurrentMap>,
+    animations: Option<Res<AnimationResource>>,
+    mut checkpoint_query: Query<(&TiledObject, &mut TextureAtlas), With<Checkpoint>>,
+) {
+    let (Some(clip), Some(map)) = (
+        animations.and_then(|animations| animations.get(CHECKPOINT_ANIMATION)),
+        current.path(),
+    ) else {
+        return;
+    };
+    let frames = clip.frames();
+    for (object, mut atlas) in checkpoint_query.iter_mut() {
+        let frame = if progress.has_lit(map, object.id) {
+            frames.last()
+        } else {
+            frames.first()
+        };
+        let Some(&index) = frame else {
+            continue;
+        };
+        if atlas.index != index {
+            atlas.index = index;
+        }
+    }
+}
+
+///
+/// start_respawn: Bevy system
+///
+/// Starts bringing the player back when it dies with an active checkpoint
+pub fn start_respawn(
+    mut deaths: EventReader<Died>,
+    progress: Res<GameProgress>,
+    player_query: Query<(), With<Player>>,
+    mut respawn: ResMut<Respawn>,
+    mut stingers: EventWriter<PlayStinger>,
+) {
+    let died = deaths
+        .read()
+        .any(|death| player_query.contains(death.entity));
+    let Some(checkpoint) = progress.checkpoint.as_ref().filter(|_| died) else {
+        return;
+    };
+    if !respawn.is_idle() {
+        return;
+    }
+    stingers.send(PlayStinger::new(DEATH_STINGER).replacing());
+    *respawn = Respawn::Dying(checkpoint.clone());
+}
+
+///
+/// advance_respawn: Bevy system
+///
+/// Moves the Respawn along as the death animation, the fade and the checkpoint's map finish
+#[allow(clippy::too_many_arguments)]
+pub fn advance_respawn(
+    mut commands: Commands,
+    mut loaded: EventReader<MapLoaded>,
+    settings: Res<RespawnSettings>,
+    current: Res<CurrentMap>,
+    animations: Option<Res<AnimationResource>>,
+    mut progress: ResMut<GameProgress>,
+    mut respawn: ResMut<Respawn>,
+    mut fade: Option<ResMut<ScreenFade>>,
+    player_query: Query<(Entity, Has<Animation>), With<Player>>,
+    mut load_map: EventWriter<LoadMap>,
+    mut respawned: EventWriter<PlayerRespawned>,
+) {
+    let loaded: Vec<String> = loaded
+        .read()
+        .filter(|event| !event.reloaded)
+        .map(|event| event.path.clone())
+        .collect();
+    let faded = fade.as_ref().is_none_or(|fade| fade.is_done());
+
+    let position = match respawn.clone() {
+        Respawn::Dying(checkpoint) if player_query.iter().all(|(_, animating)| !animating) => {
+            if let Some(fade) = fade.as_mut() {
+                fade.fade_out(settings.fade);
+            }
+            *respawn = Respawn::FadingOut(checkpoint);
+            return;
+        }
+        Respawn::FadingOut(checkpoint) if faded => {
+            for (player, _) in player_query.iter() {
+                commands.entity(player).despawn_recursive();
+            }
+            if settings.reset_room || current.path() != Some(checkpoint.map.as_str()) {
+                load_map.send(LoadMap::new(checkpoint.map.clone()));
+                *respawn = Respawn::Loading(checkpoint);
+                return;
+            }
+            checkpoint.position
+        }
+        Respawn::Loading(checkpoint) if loaded.contains(&checkpoint.map) => checkpoint.position,
+        Respawn::FadingIn if faded => {
+            *respawn = Respawn::Idle;
+            return;
+        }
+        _ => return,
+    };
+
+    progress.health = progress.max_health;
+    let empty = AnimationResource::new();
+    let animations = animations.as_deref().unwrap_or(&empty);
+    let entity = spawn_player(&mut commands, animations, position);
+    respawned.send(PlayerRespawned { entity, position });
+    if let Some(fade) = fade.as_mut() {
+        fade.fade_in(settings.fade);
+    }
+    *respawn = Respawn::FadingIn;
+}
+
+///
+/// snap_to_respawn: Bevy system
+///
+/// Snaps the MainCamera onto a respawned player and resumes the music the death stinger paused
+pub fn snap_to_respawn(
+    mut respawned: EventReader<PlayerRespawned>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+    mut resume: EventWriter<ResumeMusic>,
+) {
+    let Some(event) = respawned.read().last() else {
+        return;
+    };
+    for mut camera in camera_query.iter_mut() {
+        camera.translation = event.position.extend(camera.translation.z);
+    }
+    resume.send(ResumeMusic);
+}
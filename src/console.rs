@@ -0,0 +1,554 @@
+//! A quake-style console for dev builds: backtick opens and closes it, and commands like
+//! `load_map level3.tmx` or `set_volume music 0.2` send the same events the game does, so they
+//! go down the same code paths. The plugins register their own commands with
+//! register_console_command, and `help` lists them.
+//!
+//! While it's open the console has the keyboard: the keys it reads are cleared from
+//! `ButtonInput<KeyCode>` before ActionState is read from it, so typing doesn't move the player
+//! or pause the game. Up and down go through the commands entered before, Escape closes it.
+
+use crate::input::ActionSet;
+use bevy::{input::InputSystem, prelude::*, window::ReceivedCharacter};
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The key opening and closing the console
+pub const CONSOLE_KEY: KeyCode = KeyCode::Backquote;
+/// Lines of output kept, the oldest going first
+const MAX_LINES: usize = 200;
+/// Lines of output shown
+const SHOWN_LINES: usize = 16;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleError {
+    #[error("missing {name}, usage: {usage}")]
+    Missing { name: &'static str, usage: String },
+    #[error("{name} can't be {value}, usage: {usage}")]
+    Invalid {
+        name: &'static str,
+        value: String,
+        usage: String,
+    },
+    #[error("unknown command {name}{}", did_you_mean(.suggestions))]
+    Unknown {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    #[error("{0}")]
+    Failed(String),
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => ", try help".to_string(),
+        [only] => format!(", did you mean {}?", only),
+        _ => format!(", did you mean one of {}?", suggestions.join(", ")),
+    }
+}
+
+///
+/// ConsoleArgs
+///
+/// The words after a command's name, taken in order by its arguments
+pub struct ConsoleArgs<'a> {
+    words: &'a [&'a str],
+    next: usize,
+    usage: &'a str,
+}
+
+impl<'a> ConsoleArgs<'a> {
+    pub fn new(words: &'a [&'a str], usage: &'a str) -> Self {
+        ConsoleArgs {
+            words,
+            next: 0,
+            usage,
+        }
+    }
+
+    /// The next argument, called `name` in errors
+    pub fn required<T: FromStr>(&mut self, name: &'static str) -> Result<T, ConsoleError> {
+        self.optional(name)?.ok_or_else(|| ConsoleError::Missing {
+            name,
+            usage: self.usage.to_string(),
+        })
+    }
+
+    /// The next argument if there's one left
+    pub fn optional<T: FromStr>(&mut self, name: &'static str) -> Result<Option<T>, ConsoleError> {
+        let Some(word) = self.words.get(self.next) else {
+            return Ok(None);
+        };
+        self.next += 1;
+        word.parse().map(Some).map_err(|_| ConsoleError::Invalid {
+            name,
+            value: word.to_string(),
+            usage: self.usage.to_string(),
+        })
+    }
+
+    /// The arguments no one took
+    pub fn rest(&self) -> &[&'a str] {
+        &self.words[self.next.min(self.words.len())..]
+    }
+}
+
+type RunCommand =
+    Box<dyn Fn(&mut ConsoleArgs, &mut World) -> Result<String, ConsoleError> + Send + Sync>;
+
+struct ConsoleCommand {
+    usage: String,
+    help: String,
+    run: RunCommand,
+}
+
+///
+/// ConsoleCommands
+///
+/// The commands the console knows, by name. `help` is built in.
+#[derive(Resource, Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<String, ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    /// Adds the command `usage` starts with, replacing one of the same name
+    pub fn register(
+        &mut self,
+        usage: impl Into<String>,
+        help: impl Into<String>,
+        run: impl Fn(&mut ConsoleArgs, &mut World) -> Result<String, ConsoleError>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        let usage = usage.into();
+        let name = usage
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        self.commands.insert(
+            name,
+            ConsoleCommand {
+                usage,
+                help: help.into(),
+                run: Box::new(run),
+            },
+        );
+    }
+
+    /// The names of the commands, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        std::iter::once("help").chain(self.commands.keys().map(String::as_str))
+    }
+
+    /// Runs `line`, returning what the command printed
+    pub fn run(&self, line: &str, world: &mut World) -> Result<String, ConsoleError> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((name, words)) = words.split_first() else {
+            return Ok(String::new());
+        };
+        if *name == "help" {
+            return self.help(words.first().copied());
+        }
+        let Some(command) = self.commands.get(*name) else {
+            return Err(ConsoleError::Unknown {
+                name: name.to_string(),
+                suggestions: self.suggestions(name),
+            });
+        };
+        let mut args = ConsoleArgs::new(words, &command.usage);
+        let output = (command.run)(&mut args, world)?;
+        match args.rest() {
+            [] => Ok(output),
+            rest => Ok(format!("{} (ignored {})", output, rest.join(" "))
+                .trim_start()
+                .to_string()),
+        }
+    }
+
+    fn help(&self, name: Option<&str>) -> Result<String, ConsoleError> {
+        let Some(name) = name else {
+            let lines: Vec<String> = std::iter::once("help [command]: lists the commands")
+                .map(str::to_string)
+                .chain(
+                    self.commands
+                        .values()
+                        .map(|command| format!("{}: {}", command.usage, command.help)),
+                )
+                .collect();
+            return Ok(lines.join("\n"));
+        };
+        match self.commands.get(name) {
+            Some(command) => Ok(format!("{}: {}", command.usage, command.help)),
+            None if name == "help" => Ok("help [command]: lists the commands".to_string()),
+            None => Err(ConsoleError::Unknown {
+                name: name.to_string(),
+                suggestions: self.suggestions(name),
+            }),
+        }
+    }
+
+    /// The commands `name` could have meant: those it's a few typos away from or the start of,
+    /// closest first
+    pub fn suggestions(&self, name: &str) -> Vec<String> {
+        let allowed = (name.chars().count() / 3).max(1);
+        let mut close: Vec<(usize, &str)> = self
+            .names()
+            .filter_map(|known| {
+                let distance = edit_distance(name, known);
+                (distance <= allowed || (!name.is_empty() && known.starts_with(name)))
+                    .then_some((distance, known))
+            })
+            .collect();
+        close.sort();
+        close
+            .into_iter()
+            .take(3)
+            .map(|(_, known)| known.to_string())
+            .collect()
+    }
+}
+
+/// How many characters have to be added, removed or changed to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if a_char == *b_char {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+pub trait RegisterConsoleCommand {
+    /// Adds a console command: `usage` is its name and arguments, e.g. "give <item> [count]",
+    /// and `run` takes the arguments from its ConsoleArgs and returns what to print, e.g.
+    /// `app.register_console_command("hurt <amount>", "Hurts the player", |args, world| ...)`
+    fn register_console_command(
+        &mut self,
+        usage: impl Into<String>,
+        help: impl Into<String>,
+        run: impl Fn(&mut ConsoleArgs, &mut World) -> Result<String, ConsoleError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterConsoleCommand for App {
+    fn register_console_command(
+        &mut self,
+        usage: impl Into<String>,
+        help: impl Into<String>,
+        run: impl Fn(&mut ConsoleArgs, &mut World) -> Result<String, ConsoleError>
+            + Send
+            + Sync
+            + 'static,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .register(usage, help, run);
+        self
+    }
+}
+
+/// Whether a line of output is what was typed, what a command printed or an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Input,
+    Output,
+    Error,
+}
+
+///
+/// Console
+///
+/// Whether the console is open, what's being typed, the commands entered before and the output
+#[derive(Resource, Debug, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    history: Vec<String>,
+    /// the history entry shown, while going through it
+    browsing: Option<usize>,
+    lines: VecDeque<(LineKind, String)>,
+    pending: Vec<String>,
+}
+
+impl Console {
+    /// What's being typed
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    /// The commands entered, oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// The output, oldest first
+    pub fn lines(&self) -> impl Iterator<Item = (LineKind, &str)> {
+        self.lines.iter().map(|(kind, text)| (*kind, text.as_str()))
+    }
+
+    /// Runs `line` as if it had been typed, in the next frame's Update
+    pub fn submit(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        if line.trim().is_empty() {
+            return;
+        }
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+        self.browsing = None;
+        self.pending.push(line);
+    }
+
+    pub fn print(&mut self, kind: LineKind, text: &str) {
+        for line in text.lines() {
+            self.lines.push_back((kind, line.to_string()));
+        }
+        while self.lines.len() > MAX_LINES {
+            self.lines.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Shows the command entered before the one shown, going back through the history
+    pub fn history_back(&mut self) {
+        let index = match self.browsing {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+        self.browsing = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// Shows the command entered after the one shown, and an empty line after the last
+    pub fn history_forward(&mut self) {
+        let Some(index) = self.browsing else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.browsing = Some(index + 1);
+            self.input = self.history[index + 1].clone();
+        } else {
+            self.browsing = None;
+            self.input.clear();
+        }
+    }
+}
+
+/// The console's panel
+#[derive(Component, Debug)]
+pub struct ConsoleRoot;
+
+/// The text listing the console's output
+#[derive(Component, Debug)]
+pub struct ConsoleOutput;
+
+/// The text showing what's being typed
+#[derive(Component, Debug)]
+pub struct ConsoleInput;
+
+/// The console, with `help` and `clear`
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<ConsoleCommands>()
+            .add_event::<ReceivedCharacter>()
+            .register_console_command("clear", "Clears the console", |_, world| {
+                world.resource_mut::<Console>().clear();
+                Ok(String::new())
+            })
+            .add_systems(Startup, spawn_console)
+            .add_systems(
+                PreUpdate,
+                type_in_console.after(InputSystem).before(ActionSet),
+            )
+            .add_systems(Update, (run_console_commands, update_console_ui).chain());
+    }
+}
+
+fn spawn_console(mut commands: Commands) {
+    let style = TextStyle {
+        font_size: 14.0,
+        color: Color::WHITE,
+        ..default()
+    };
+    commands
+        .spawn((
+            ConsoleRoot,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(0.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(45.0),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+                visibility: Visibility::Hidden,
+                // over the game's UI, under the toasts
+                z_index: ZIndex::Global(200),
+                ..default()
+            },
+        ))
+        .with_children(|console| {
+            console.spawn((ConsoleOutput, TextBundle::default()));
+            console.spawn((
+                ConsoleInput,
+                TextBundle::from_section("> ", style).with_style(Style {
+                    margin: UiRect::top(Val::Px(4.0)),
+                    ..default()
+                }),
+            ));
+        });
+}
+
+///
+/// type_in_console: Bevy system
+///
+/// Opens and closes the console, and while it's open types into it, taking the keys from the
+/// rest of the game
+pub fn type_in_console(
+    mut console: ResMut<Console>,
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Option<ResMut<ButtonInput<KeyCode>>>,
+) {
+    let Some(mut keys) = keys else {
+        characters.clear();
+        return;
+    };
+    if keys.just_pressed(CONSOLE_KEY) {
+        console.open = !console.open;
+        characters.clear();
+        keys.reset_all();
+        return;
+    }
+    if !console.open {
+        characters.clear();
+        return;
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        console.open = false;
+    } else if keys.just_pressed(KeyCode::Enter) || keys.just_pressed(KeyCode::NumpadEnter) {
+        let line = std::mem::take(&mut console.input);
+        console.submit(line);
+    } else if keys.just_pressed(KeyCode::Backspace) {
+        console.input.pop();
+    } else if keys.just_pressed(KeyCode::ArrowUp) {
+        console.history_back();
+    } else if keys.just_pressed(KeyCode::ArrowDown) {
+        console.history_forward();
+    }
+    for event in characters.read() {
+        let typed = event.char.chars().filter(|c| !c.is_control() && *c != '`');
+        console.input.extend(typed);
+    }
+    keys.reset_all();
+}
+
+///
+/// run_console_commands: Bevy system
+///
+/// Runs the lines submitted to the Console, printing what they return
+pub fn run_console_commands(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<Console>().pending);
+    if pending.is_empty() {
+        return;
+    }
+    world.resource_scope(|world, commands: Mut<ConsoleCommands>| {
+        for line in pending {
+            world
+                .resource_mut::<Console>()
+                .print(LineKind::Input, &format!("> {}", line));
+            let result = commands.run(&line, world);
+            let mut console = world.resource_mut::<Console>();
+            match result {
+                Ok(output) => console.print(LineKind::Output, &output),
+                Err(e) => console.print(LineKind::Error, &e.to_string()),
+            }
+        }
+    });
+}
+
+type ConsoleTexts<'w, 's> = ParamSet<
+    'w,
+    's,
+    (
+        Query<'static, 'static, &'static mut Text, With<ConsoleOutput>>,
+        Query<'static, 'static, &'static mut Text, With<ConsoleInput>>,
+    ),
+>;
+
+///
+/// update_console_ui: Bevy system
+///
+/// Shows or hides the console, and its output and input
+pub fn update_console_ui(
+    console: Res<Console>,
+    mut root_query: Query<&mut Visibility, With<ConsoleRoot>>,
+    mut text_queries: ConsoleTexts,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    let visibility = if console.open {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for mut root in root_query.iter_mut() {
+        root.set_if_neq(visibility);
+    }
+
+    let skip = console.lines.len().saturating_sub(SHOWN_LINES);
+    let sections: Vec<TextSection> = console
+        .lines()
+        .skip(skip)
+        .map(|(kind, text)| {
+            let color = match kind {
+                LineKind::Input => Color::GRAY,
+                LineKind::Output => Color::WHITE,
+                LineKind::Error => Color::rgb(1.0, 0.45, 0.4),
+            };
+            TextSection::new(
+                format!("{}\n", text),
+                TextStyle {
+                    font_size: 14.0,
+                    color,
+                    ..default()
+                },
+            )
+        })
+        .collect();
+    for mut text in text_queries.p0().iter_mut() {
+        text.sections = sections.clone();
+    }
+    for mut text in text_queries.p1().iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            section.value = format!("> {}_", console.input);
+        }
+    }
+}
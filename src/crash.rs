@@ -0,0 +1,267 @@
+//! What happens when the game panics on the web: instead of a frozen canvas, the page shows that
+//! the game crashed with a button to reload it, and if the page set `crashReportUrl` in the start
+//! options a CrashReport is POSTed there.
+//!
+//! A report only holds game state: the panic message and where it happened, the BuildInfo, the
+//! GameRng seed, and the last LOG_RING_LINES lines this crate logged. Nothing about the page,
+//! the browser or the player goes in. Without a report URL nothing is recorded or sent.
+
+use crate::build_info::BuildInfo;
+use bevy::log::{
+    tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer,
+    },
+    BoxedSubscriber,
+};
+use bevy::utils::tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Number of log lines kept for a CrashReport
+pub const LOG_RING_LINES: usize = 50;
+
+///
+/// LogRing
+///
+/// The latest log lines, oldest first, up to LOG_RING_LINES of them
+#[derive(Debug, Default, Clone)]
+pub struct LogRing {
+    lines: VecDeque<String>,
+}
+
+impl LogRing {
+    pub const fn new() -> Self {
+        LogRing {
+            lines: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: String) {
+        if self.lines.len() == LOG_RING_LINES {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.iter().cloned().collect()
+    }
+}
+
+static LOG_RING: Mutex<LogRing> = Mutex::new(LogRing::new());
+static OVERLAY_TEXT: Mutex<Option<(String, String)>> = Mutex::new(None);
+static REPORTING: Mutex<Option<CrashReporting>> = Mutex::new(None);
+
+/// Where reports go and the run they're about, set by `configure`
+#[derive(Debug, Clone)]
+struct CrashReporting {
+    url: String,
+    seed: u64,
+}
+
+/// Turns crash reports on for a run seeded with `seed`, POSTing them to `url`, or off when `url`
+/// is None
+pub fn configure(url: Option<String>, seed: u64) {
+    if let Ok(mut reporting) = REPORTING.lock() {
+        *reporting = url.map(|url| CrashReporting { url, seed });
+    }
+}
+
+/// Sets the overlay's message and the text of its reload button, for the active language
+pub fn set_overlay_text(message: String, reload: String) {
+    if let Ok(mut text) = OVERLAY_TEXT.lock() {
+        *text = Some((message, reload));
+    }
+}
+
+/// The URL to send the report of a panic saying `message` at `location` to, and the report, or
+/// None when reports are off. The locks are only tried, as the panic may have happened while one
+/// was held.
+pub fn crash_report(
+    message: impl Into<String>,
+    location: Option<String>,
+) -> Option<(String, CrashReport)> {
+    let reporting = REPORTING.try_lock().ok()?.clone()?;
+    let log = LOG_RING
+        .try_lock()
+        .map(|ring| ring.lines())
+        .unwrap_or_default();
+    let report = CrashReport::new(message, location, reporting.seed, log);
+    Some((reporting.url, report))
+}
+
+///
+/// CrashReport
+///
+/// What's POSTed, as JSON, when the game panics
+/// * message: what the panic said
+/// * location: the file, line and column it happened at, if known
+/// * build: the BuildInfo of the build that crashed
+/// * seed: the GameRng seed of the run, to replay it
+/// * log: the last lines the game logged, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrashReport {
+    pub message: String,
+    pub location: Option<String>,
+    pub build: BuildInfo,
+    pub seed: u64,
+    pub log: Vec<String>,
+}
+
+impl CrashReport {
+    pub fn new(
+        message: impl Into<String>,
+        location: Option<String>,
+        seed: u64,
+        log: Vec<String>,
+    ) -> Self {
+        CrashReport {
+            message: message.into(),
+            location,
+            build: BuildInfo::current(),
+            seed,
+            log,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+/// Records this crate's log events in the LogRing, for the LogPlugin's update_subscriber
+pub fn with_log_ring(subscriber: BoxedSubscriber) -> BoxedSubscriber {
+    Box::new(subscriber.with(LogRingLayer))
+}
+
+/// The tracing layer behind with_log_ring. Only events from this crate are kept, so what bevy
+/// and the browser's graphics stack log about the machine stays out of reports.
+pub struct LogRingLayer;
+
+impl<S: Subscriber> Layer<S> for LogRingLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if !metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            return;
+        }
+        let mut message = String::new();
+        event.record(&mut MessageVisitor(&mut message));
+        let line = format!("{} {}: {}", metadata.level(), metadata.target(), message);
+        if let Ok(mut ring) = LOG_RING.lock() {
+            ring.push(line);
+        }
+    }
+}
+
+struct MessageVisitor<'a>(&'a mut String);
+
+impl Visit for MessageVisitor<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.0, "{:?}", value);
+        }
+    }
+}
+
+/// Handles a panic: shows the crash overlay, and sends a report if reports are on. Called from
+/// the panic hook, so it mustn't panic itself.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn on_panic(message: String, location: Option<String>) {
+    page::show_overlay();
+    if let Some((url, report)) = crash_report(message, location) {
+        page::post(&url, report.to_json());
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod page {
+    use js_sys::Reflect;
+    use wasm_bindgen::{closure::Closure, JsCast, JsValue};
+
+    const OVERLAY_ID: &str = "game-crashed";
+    const OVERLAY_STYLE: &str = "position:fixed;inset:0;z-index:2147483647;display:flex;\
+        flex-direction:column;align-items:center;justify-content:center;gap:1em;\
+        background:rgba(0,0,0,0.85);color:#fff;font:16px sans-serif;text-align:center";
+    const BUTTON_STYLE: &str = "padding:0.5em 1.5em;font:inherit;cursor:pointer";
+
+    /// Lays the crash message over the page, once
+    pub(super) fn show_overlay() {
+        if let Err(e) = try_show_overlay() {
+            web_sys::console::error_2(&"Could not show the crash message:".into(), &e);
+        }
+    }
+
+    fn try_show_overlay() -> Result<(), JsValue> {
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("there's no document")?;
+        if document.get_element_by_id(OVERLAY_ID).is_some() {
+            return Ok(());
+        }
+        let body = document.body().ok_or("the page has no body")?;
+
+        let overlay = document.create_element("div")?;
+        overlay.set_id(OVERLAY_ID);
+        overlay.set_attribute("style", OVERLAY_STYLE)?;
+        overlay.set_attribute("role", "alert")?;
+
+        // in English if the game never got to set it, or panicked setting it
+        let (message, reload) = super::OVERLAY_TEXT
+            .try_lock()
+            .ok()
+            .and_then(|text| text.clone())
+            .unwrap_or_else(|| {
+                (
+                    "The game crashed. Sorry! Reloading the page starts it again.".to_string(),
+                    "Reload".to_string(),
+                )
+            });
+        let text = document.create_element("p")?;
+        text.set_text_content(Some(&message));
+        let button = document.create_element("button")?;
+        button.set_text_content(Some(&reload));
+        button.set_attribute("style", BUTTON_STYLE)?;
+        let reload = Closure::<dyn FnMut()>::new(|| {
+            if let Some(window) = web_sys::window() {
+                let _ = window.location().reload();
+            }
+        });
+        button.add_event_listener_with_callback("click", reload.as_ref().unchecked_ref())?;
+        // the page keeps it for as long as it's open
+        reload.forget();
+
+        overlay.append_child(&text)?;
+        overlay.append_child(&button)?;
+        body.append_child(&overlay)?;
+        Ok(())
+    }
+
+    /// POSTs `body` to `url`, without waiting for the answer: the game can't run any more code
+    /// once the panic has unwound
+    pub(super) fn post(url: &str, body: String) {
+        if let Err(e) = try_post(url, body) {
+            web_sys::console::error_2(&"Could not send the crash report:".into(), &e);
+        }
+    }
+
+    fn try_post(url: &str, body: String) -> Result<(), JsValue> {
+        let window = web_sys::window().ok_or("there's no window to fetch from")?;
+        // a plain object rather than RequestInit, whose setters changed across web-sys releases
+        let init = js_sys::Object::new();
+        let headers = js_sys::Object::new();
+        Reflect::set(&headers, &"Content-Type".into(), &"application/json".into())?;
+        Reflect::set(&init, &"method".into(), &"POST".into())?;
+        Reflect::set(&init, &"headers".into(), &headers)?;
+        Reflect::set(&init, &"body".into(), &body.into())?;
+        // so the report still goes out if the player reloads straight away
+        Reflect::set(&init, &"keepalive".into(), &true.into())?;
+        let _ = window.fetch_with_str_and_init(url, init.unchecked_ref());
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+//! A pixel-art cursor in place of the OS arrow. The cursor sheet has a cell for each
+//! CursorVariant in a row: Default; Hover while pointing at a Pickable, going by the picking
+//! events; and Crosshair while Aiming is on. Each variant's hotspot, the texel that's on the
+//! point clicked, is set in CursorSettings.
+//!
+//! The hardware cursor is only hidden once the sheet has loaded, so a missing sheet leaves the
+//! OS one. The sprite is hidden while the cursor is outside the window or the pointer is locked,
+//! and the custom cursor is off altogether while the touch controls are, giving the OS cursor
+//! back.
+//!
+//! Texels are drawn a whole number of physical pixels across, the scale factor rounded, at a
+//! position snapped to the physical pixel grid, and the sheet is sampled nearest, so the cursor
+//! stays crisp at any scale factor.
+
+use crate::input::TouchControls;
+use crate::picking::{PointerEntered, PointerExited};
+use crate::pointer::PointerLock;
+use bevy::{
+    prelude::*,
+    render::texture::{ImageLoaderSettings, ImageSampler},
+    utils::HashMap,
+    window::PrimaryWindow,
+};
+
+/// Which cursor is shown, its cell in the sheet
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CursorVariant {
+    #[default]
+    Default,
+    Hover,
+    Crosshair,
+}
+
+impl CursorVariant {
+    pub const ALL: [CursorVariant; 3] = [
+        CursorVariant::Default,
+        CursorVariant::Hover,
+        CursorVariant::Crosshair,
+    ];
+
+    fn index(self) -> usize {
+        match self {
+            CursorVariant::Default => 0,
+            CursorVariant::Hover => 1,
+            CursorVariant::Crosshair => 2,
+        }
+    }
+}
+
+///
+/// CursorSettings
+///
+/// * sheet: path of the cursor sheet, relative to the assets directory
+/// * cell_size: the size of a variant's cell in the sheet, in texels
+/// * hotspots: the texel of each variant on the point clicked, from the top left of its cell.
+///   The top left when unset.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct CursorSettings {
+    pub sheet: String,
+    pub cell_size: Vec2,
+    pub hotspots: HashMap<CursorVariant, Vec2>,
+}
+
+impl Default for CursorSettings {
+    fn default() -> Self {
+        CursorSettings {
+            sheet: "graphics/cursor.png".to_string(),
+            cell_size: Vec2::splat(16.0),
+            hotspots: vec![
+                (CursorVariant::Default, Vec2::new(1.0, 1.0)),
+                (CursorVariant::Hover, Vec2::new(5.0, 1.0)),
+                (CursorVariant::Crosshair, Vec2::new(8.0, 8.0)),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+impl CursorSettings {
+    pub fn hotspot(&self, variant: CursorVariant) -> Vec2 {
+        self.hotspots.get(&variant).copied().unwrap_or_default()
+    }
+}
+
+///
+/// Aiming
+///
+/// On while the player aims, showing the crosshair
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Aiming(pub bool);
+
+///
+/// CursorState
+///
+/// The custom cursor's variant, and the Pickable it's over from the picking events, if any
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CursorState {
+    pub variant: CursorVariant,
+    hovered: Option<Entity>,
+}
+
+/// Marks the node drawing the cursor
+#[derive(Component, Debug)]
+pub struct CustomCursor;
+
+/// The cursor loaded from CursorSettings::sheet
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CursorSettings>()
+            .init_resource::<CursorState>()
+            .init_resource::<Aiming>()
+            .add_event::<PointerEntered>()
+            .add_event::<PointerExited>()
+            .add_systems(Startup, spawn_cursor)
+            .add_systems(Update, (choose_cursor_variant, update_cursor).chain());
+    }
+}
+
+/// Spawns the cursor's node, hidden until there's a cursor to show
+fn spawn_cursor(
+    mut commands: Commands,
+    settings: Res<CursorSettings>,
+    asset_server: Res<AssetServer>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+) {
+    let sheet: Handle<Image> = asset_server.load_with_settings(
+        settings.sheet.clone(),
+        |image: &mut ImageLoaderSettings| {
+            image.sampler = ImageSampler::nearest();
+        },
+    );
+    let layout = layouts.add(TextureAtlasLayout::from_grid(
+        settings.cell_size,
+        CursorVariant::ALL.len(),
+        1,
+        None,
+        None,
+    ));
+    commands.spawn((
+        CustomCursor,
+        AtlasImageBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            },
+            image: UiImage::new(sheet),
+            texture_atlas: TextureAtlas { layout, index: 0 },
+            visibility: Visibility::Hidden,
+            // over everything
+            z_index: ZIndex::Global(i32::MAX),
+            ..default()
+        },
+    ));
+}
+
+///
+/// choose_cursor_variant: Bevy system
+///
+/// Shows the crosshair while Aiming, or the hover cursor while over a Pickable
+pub fn choose_cursor_variant(
+    mut entered: EventReader<PointerEntered>,
+    mut exited: EventReader<PointerExited>,
+    aiming: Res<Aiming>,
+    mut state: ResMut<CursorState>,
+) {
+    let mut hovered = state.hovered;
+    for event in exited.read() {
+        if hovered == Some(event.entity) {
+            hovered = None;
+        }
+    }
+    if let Some(event) = entered.read().last() {
+        hovered = Some(event.entity);
+    }
+    let variant = if aiming.0 {
+        CursorVariant::Crosshair
+    } else if hovered.is_some() {
+        CursorVariant::Hover
+    } else {
+        CursorVariant::Default
+    };
+    if state.hovered != hovered || state.variant != variant {
+        state.hovered = hovered;
+        state.variant = variant;
+    }
+}
+
+///
+/// update_cursor: Bevy system
+///
+/// Puts the cursor's variant under the window's cursor, hiding the hardware cursor while the
+/// custom one can be shown
+#[allow(clippy::type_complexity)]
+pub fn update_cursor(
+    settings: Res<CursorSettings>,
+    state: Res<CursorState>,
+    images: Res<Assets<Image>>,
+    touch: Option<Res<TouchControls>>,
+    lock: Option<Res<PointerLock>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    mut cursor_query: Query<
+        (&UiImage, &mut TextureAtlas, &mut Style, &mut Visibility),
+        With<CustomCursor>,
+    >,
+) {
+    let Ok(mut window) = window_query.get_single_mut() else {
+        return;
+    };
+    let touching = touch.is_some_and(|touch| touch.enabled);
+    let locked = lock.is_some_and(|lock| lock.is_locked());
+    for (image, mut atlas, mut style, mut visibility) in cursor_query.iter_mut() {
+        let custom = !touching && images.contains(&image.texture);
+        // pointer lock hides the cursor itself
+        if !locked && window.cursor.visible == custom {
+            window.cursor.visible = !custom;
+        }
+
+        let position = window.cursor_position().filter(|_| custom && !locked);
+        let wanted = if position.is_some() {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+        let Some(position) = position else {
+            continue;
+        };
+
+        if atlas.index != state.variant.index() {
+            atlas.index = state.variant.index();
+        }
+        let scale_factor = window.scale_factor();
+        // logical pixels per texel, a whole number of physical ones
+        let texel = scale_factor.round().max(1.0) / scale_factor;
+        let snap = |value: f32| (value * scale_factor).round() / scale_factor;
+        let top_left = position - settings.hotspot(state.variant) * texel;
+        let size = settings.cell_size * texel;
+        let wanted = (
+            Val::Px(snap(top_left.x)),
+            Val::Px(snap(top_left.y)),
+            Val::Px(size.x),
+            Val::Px(size.y),
+        );
+        if (style.left, style.top, style.width, style.height) != wanted {
+            (style.left, style.top, style.width, style.height) = wanted;
+        }
+    }
+}
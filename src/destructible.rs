@@ -0,0 +1,155 @@
+//! Breakable tiles: cracked walls and crates with an `hp` tile property.
+//!
+//! Tile properties:
+//! * hp: hits the tile takes to break; tiles without it can't be damaged
+//! * breaks_into: tile id to leave behind when it breaks, removed when unset
+//! * cracked_at, cracked_tile: once damaged down to `cracked_at` hp, the tile is swapped for
+//!   `cracked_tile`, keeping the damage it took. The cracked tile can crack further the same way.
+//! * debris: the AnimationResource animation played where it breaks, DEBRIS_ANIMATION if unset
+//! * break_sound: the sound it breaks with, BREAK_SOUND if unset
+
+use crate::gfx::AnimationResource;
+use crate::helpers::tiled::{
+    apply_tile_edits, SetTile, TileLookup, TileProperties, TiledLayer, TilesetTile,
+};
+use crate::sound::PlaySFX;
+use crate::state::GameplaySet;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+/// Animation played where a tile breaks when it doesn't name its own
+pub const DEBRIS_ANIMATION: &str = "debris";
+/// Sound played when a tile breaks when it doesn't name its own
+pub const BREAK_SOUND: &str = "tile_break";
+
+pub struct DestructiblePlugin;
+
+impl Plugin for DestructiblePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<DamageTile>()
+            .add_event::<TileDestroyed>()
+            .add_event::<PlaySFX>()
+            .add_systems(
+                Update,
+                damage_tiles.before(apply_tile_edits).in_set(GameplaySet),
+            );
+    }
+}
+
+/// Damages the first tile at `pos` with an `hp` property, on whichever layer it's on
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageTile {
+    pub pos: TilePos,
+    pub amount: i32,
+}
+
+impl DamageTile {
+    pub fn new(pos: TilePos, amount: i32) -> Self {
+        DamageTile { pos, amount }
+    }
+}
+
+/// Sent when a tile breaks, e.g. to drop loot. `tile_id` is the tile that broke, before its
+/// replacement.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileDestroyed {
+    pub pos: TilePos,
+    pub tile_id: u32,
+}
+
+/// The hp left on a damaged tile
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileHealth(pub i32);
+
+///
+/// damage_tiles: Bevy system
+///
+/// Handles DamageTile, swapping tiles through SetTile as they crack and break
+#[allow(clippy::too_many_arguments)]
+pub fn damage_tiles(
+    mut commands: Commands,
+    mut damage_events: EventReader<DamageTile>,
+    mut set_tile: EventWriter<SetTile>,
+    mut destroyed: EventWriter<TileDestroyed>,
+    mut sfx: EventWriter<PlaySFX>,
+    animations: Option<Res<AnimationResource>>,
+    lookup: Res<TileLookup>,
+    tilemap_query: Query<(&TiledLayer, &TileStorage, &Transform)>,
+    tile_query: Query<(&TileProperties, &TilesetTile, Option<&TileHealth>)>,
+) {
+    // all of a frame's hits on each tile, so they add up before the tile is swapped
+    let mut hits: Vec<(Entity, TilePos, i32)> = Vec::new();
+    for event in damage_events.read() {
+        let target = tilemap_query
+            .iter()
+            .filter_map(|(_, storage, _)| storage.get(&event.pos))
+            .find(|tile| {
+                tile_query
+                    .get(*tile)
+                    .is_ok_and(|(properties, ..)| properties.get_int("hp").is_some())
+            });
+        let Some(tile) = target else {
+            continue;
+        };
+        match hits.iter_mut().find(|(hit, ..)| *hit == tile) {
+            Some((_, _, amount)) => *amount += event.amount,
+            None => hits.push((tile, event.pos, event.amount)),
+        }
+    }
+
+    for (tile, pos, amount) in hits {
+        let (properties, tileset_tile, health) = tile_query.get(tile).unwrap();
+        let Some((layer, _, layer_transform)) = tilemap_query
+            .iter()
+            .find(|(_, storage, _)| storage.get(&pos) == Some(tile))
+        else {
+            continue;
+        };
+        let hp = health.map_or_else(|| properties.get_int("hp").unwrap_or(0), |health| health.0);
+        let hp = hp - amount;
+
+        if hp > 0 {
+            commands.entity(tile).insert(TileHealth(hp));
+            let cracked = properties.get_int("cracked_at").is_some_and(|at| hp <= at);
+            if let (true, Some(cracked_tile)) = (cracked, properties.get_int("cracked_tile")) {
+                set_tile.send(SetTile::new(layer.path.clone(), pos, cracked_tile as u32));
+            }
+            continue;
+        }
+
+        commands.entity(tile).remove::<TileHealth>();
+        set_tile.send(match properties.get_int("breaks_into") {
+            Some(replacement) => SetTile::new(layer.path.clone(), pos, replacement as u32),
+            None => SetTile::remove(layer.path.clone(), pos),
+        });
+        destroyed.send(TileDestroyed {
+            pos,
+            tile_id: tileset_tile.0,
+        });
+
+        let position = lookup.tile_to_world(pos);
+        let sound = properties.get_string("break_sound").unwrap_or(BREAK_SOUND);
+        sfx.send(PlaySFX::at(sound, position));
+        let debris = properties.get_string("debris").unwrap_or(DEBRIS_ANIMATION);
+        if let Some(animation) = animations
+            .as_ref()
+            .and_then(|animations| animations.get(debris))
+        {
+            commands.spawn((
+                SpriteSheetBundle {
+                    texture: animation.texture().clone(),
+                    atlas: TextureAtlas {
+                        layout: animation.atlas().clone(),
+                        index: animation.frame(),
+                    },
+                    // just above the layer
+                    transform: Transform::from_translation(
+                        position.extend(layer_transform.translation.z + 0.5),
+                    ),
+                    ..default()
+                },
+                animation,
+            ));
+        }
+    }
+}
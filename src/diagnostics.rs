@@ -0,0 +1,369 @@
+//! The diagnostics overlay, for when "it's laggy": FPS with its average and 1% low over the last
+//! FRAME_HISTORY frames, a graph of the latest frame times, the entity and audio entity counts,
+//! the AppState, MemoryStats, how well the entity pools are reused and the BuildInfo. F3 or the
+//! debug start option shows it. With the `profiling` feature it also lists the slowest systems of
+//! SystemTimings.
+//!
+//! It's in every build but hidden by default, and costs next to nothing hidden: only bevy's
+//! frame time and entity count diagnostics keep running.
+
+use crate::build_info::BuildInfo;
+use crate::gfx::{BurstParticle, FloatingText};
+use crate::memory::{mib, MemoryStats};
+use crate::pool::{Pool, PoolKind};
+use crate::profiling::SystemTimings;
+use crate::projectiles::Projectile;
+use crate::sound::OneShotSfx;
+use crate::state::AppState;
+use bevy::diagnostic::{
+    Diagnostic, DiagnosticPath, Diagnostics, DiagnosticsPlugin, DiagnosticsStore,
+    EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin, RegisterDiagnostic,
+};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+/// Key showing or hiding the overlay
+pub const DIAGNOSTICS_KEY: KeyCode = KeyCode::F3;
+/// Number of frames the average and 1% low are over
+pub const FRAME_HISTORY: usize = 600;
+/// Number of frames in the graph
+const GRAPH_BARS: usize = 60;
+/// Height of the graph in pixels, a frame of GRAPH_MAX_MS reaching the top
+const GRAPH_HEIGHT: f32 = 40.0;
+const GRAPH_MAX_MS: f32 = 50.0;
+/// Number of systems from SystemTimings listed
+const SLOWEST_SYSTEMS: usize = 5;
+
+/// Entities playing or about to play a sound
+pub const AUDIO_ENTITY_COUNT: DiagnosticPath = DiagnosticPath::const_new("audio_entity_count");
+
+///
+/// DiagnosticsOverlay
+///
+/// Whether the diagnostics overlay is showing
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiagnosticsOverlay(pub bool);
+
+///
+/// FrameHistory
+///
+/// The real time the last FRAME_HISTORY frames took in milliseconds, oldest first, recorded while
+/// the overlay shows
+#[derive(Resource, Debug, Default)]
+pub struct FrameHistory {
+    frame_times: VecDeque<f32>,
+}
+
+impl FrameHistory {
+    pub fn push(&mut self, frame_time: f32) {
+        if self.frame_times.len() == FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    pub fn frame_times(&self) -> impl DoubleEndedIterator<Item = f32> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    /// Frames per second over the whole history
+    pub fn average_fps(&self) -> Option<f32> {
+        let total: f32 = self.frame_times.iter().sum();
+        (total > 0.0).then(|| self.frame_times.len() as f32 * 1000.0 / total)
+    }
+
+    /// Frames per second over the slowest 1% of the history, at least one frame
+    pub fn one_percent_low(&self) -> Option<f32> {
+        let mut slowest: Vec<f32> = self.frame_times.iter().copied().collect();
+        slowest.sort_by(|a, b| b.total_cmp(a));
+        slowest.truncate((slowest.len() / 100).max(1));
+        let total: f32 = slowest.iter().sum();
+        (total > 0.0).then(|| slowest.len() as f32 * 1000.0 / total)
+    }
+}
+
+/// Marks the overlay's root node
+#[derive(Component)]
+pub struct DiagnosticsOverlayRoot;
+
+/// Marks the overlay's text
+#[derive(Component)]
+pub struct DiagnosticsText;
+
+/// A bar of the frame time graph, 0 being the oldest frame
+#[derive(Component)]
+pub struct FrameGraphBar(usize);
+
+/// The diagnostics overlay, and bevy's diagnostics plugins it reads if they're not there yet
+pub struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<DiagnosticsPlugin>() {
+            app.add_plugins(DiagnosticsPlugin);
+        }
+        if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+            app.add_plugins(FrameTimeDiagnosticsPlugin);
+        }
+        if !app.is_plugin_added::<EntityCountDiagnosticsPlugin>() {
+            app.add_plugins(EntityCountDiagnosticsPlugin);
+        }
+        app.register_diagnostic(Diagnostic::new(AUDIO_ENTITY_COUNT).with_max_history_length(20))
+            .init_resource::<DiagnosticsOverlay>()
+            .init_resource::<FrameHistory>()
+            .add_systems(Startup, spawn_diagnostics_overlay)
+            .add_systems(
+                Update,
+                (
+                    toggle_diagnostics_overlay,
+                    show_diagnostics_overlay,
+                    (
+                        record_frame_time,
+                        measure_audio_entities,
+                        update_diagnostics_text,
+                        update_frame_graph,
+                    )
+                        .run_if(overlay_shown),
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn overlay_shown(overlay: Res<DiagnosticsOverlay>) -> bool {
+    overlay.0
+}
+
+pub fn spawn_diagnostics_overlay(mut commands: Commands) {
+    commands
+        .spawn((
+            DiagnosticsOverlayRoot,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(4.0),
+                    left: Val::Px(4.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                // a backing panel, so the text reads on bright scenes as well as dark ones
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                visibility: Visibility::Hidden,
+                z_index: ZIndex::Global(i32::MAX),
+                ..default()
+            },
+        ))
+        .with_children(|overlay| {
+            overlay.spawn((
+                DiagnosticsText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 14.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+            ));
+            overlay
+                .spawn(NodeBundle {
+                    style: Style {
+                        height: Val::Px(GRAPH_HEIGHT),
+                        align_items: AlignItems::FlexEnd,
+                        column_gap: Val::Px(1.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|graph| {
+                    for index in 0..GRAPH_BARS {
+                        graph.spawn((
+                            FrameGraphBar(index),
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(2.0),
+                                    height: Val::Px(0.0),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                        ));
+                    }
+                });
+        });
+}
+
+///
+/// toggle_diagnostics_overlay: Bevy system
+///
+/// DIAGNOSTICS_KEY shows or hides the overlay
+pub fn toggle_diagnostics_overlay(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut overlay: ResMut<DiagnosticsOverlay>,
+) {
+    if keys.is_some_and(|keys| keys.just_pressed(DIAGNOSTICS_KEY)) {
+        overlay.0 = !overlay.0;
+    }
+}
+
+///
+/// show_diagnostics_overlay: Bevy system
+///
+/// Shows the overlay while DiagnosticsOverlay is on. The history starts over each time, so it
+/// doesn't average in frames from before it was hidden.
+pub fn show_diagnostics_overlay(
+    overlay: Res<DiagnosticsOverlay>,
+    mut history: ResMut<FrameHistory>,
+    mut root_query: Query<&mut Visibility, With<DiagnosticsOverlayRoot>>,
+) {
+    if !overlay.is_changed() {
+        return;
+    }
+    for mut visibility in root_query.iter_mut() {
+        *visibility = if overlay.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    if overlay.0 {
+        *history = FrameHistory::default();
+    }
+}
+
+///
+/// record_frame_time: Bevy system
+///
+/// Adds the last frame's real time to the FrameHistory
+pub fn record_frame_time(time: Res<Time<Real>>, mut history: ResMut<FrameHistory>) {
+    history.push(time.delta_seconds() * 1000.0);
+}
+
+///
+/// measure_audio_entities: Bevy system
+///
+/// Counts the entities playing or about to play a sound, for AUDIO_ENTITY_COUNT
+pub fn measure_audio_entities(
+    mut diagnostics: Diagnostics,
+    audio_query: Query<(), With<Handle<AudioSource>>>,
+) {
+    diagnostics.add_measurement(&AUDIO_ENTITY_COUNT, || audio_query.iter().count() as f64);
+}
+
+fn format_value(value: Option<f64>, precision: usize) -> String {
+    value.map_or_else(
+        || "-".to_string(),
+        |value| format!("{:.*}", precision, value),
+    )
+}
+
+type OverlayPools<'w> = (
+    Option<Res<'w, Pool<BurstParticle>>>,
+    Option<Res<'w, Pool<FloatingText>>>,
+    Option<Res<'w, Pool<OneShotSfx>>>,
+    Option<Res<'w, Pool<Projectile>>>,
+);
+
+///
+/// update_diagnostics_text: Bevy system
+///
+/// Writes the FPS, frame time, counts, state, memory and slowest systems to the overlay
+pub fn update_diagnostics_text(
+    store: Res<DiagnosticsStore>,
+    history: Res<FrameHistory>,
+    (state, build): (Option<Res<State<AppState>>>, Option<Res<BuildInfo>>),
+    timings: Option<Res<SystemTimings>>,
+    memory: Option<Res<MemoryStats>>,
+    pools: OverlayPools,
+    mut text_query: Query<&mut Text, With<DiagnosticsText>>,
+) {
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+    let smoothed = |path| store.get(path).and_then(|diagnostic| diagnostic.smoothed());
+    let latest = |path| store.get(path).and_then(|diagnostic| diagnostic.value());
+
+    let mut overlay = format!(
+        "FPS {}  avg {}  1% low {}\nframe {} ms\n",
+        format_value(smoothed(&FrameTimeDiagnosticsPlugin::FPS), 0),
+        format_value(history.average_fps().map(f64::from), 1),
+        format_value(history.one_percent_low().map(f64::from), 1),
+        format_value(smoothed(&FrameTimeDiagnosticsPlugin::FRAME_TIME), 2),
+    );
+    let _ = write!(
+        overlay,
+        "entities {}  audio {}\nstate {}",
+        format_value(latest(&EntityCountDiagnosticsPlugin::ENTITY_COUNT), 0),
+        format_value(latest(&AUDIO_ENTITY_COUNT), 0),
+        state.map_or_else(|| "-".to_string(), |state| format!("{:?}", state.get())),
+    );
+    if let Some(memory) = memory {
+        let to_mib = |bytes: Option<usize>| format_value(bytes.map(|bytes| mib(bytes).into()), 1);
+        let _ = write!(overlay, "\nmemory {} MiB", to_mib(memory.linear_memory));
+        if memory.allocated.is_some() {
+            let _ = write!(
+                overlay,
+                "  allocated {} MiB (peak {}, {} allocations)",
+                to_mib(memory.allocated),
+                to_mib(memory.peak_allocated),
+                format_value(memory.allocations.map(|count| count as f64), 0),
+            );
+        }
+    }
+    write_pool(&mut overlay, pools.0.as_deref());
+    write_pool(&mut overlay, pools.1.as_deref());
+    write_pool(&mut overlay, pools.2.as_deref());
+    write_pool(&mut overlay, pools.3.as_deref());
+    if let Some(build) = build {
+        let _ = write!(overlay, "\nbuild {}", *build);
+    }
+    for (name, average) in timings
+        .iter()
+        .flat_map(|timings| timings.top(SLOWEST_SYSTEMS))
+    {
+        let _ = write!(overlay, "\n{:.2} ms {}", average, name);
+    }
+    text.sections[0].value = overlay;
+}
+
+/// A pool's line in the overlay: how many entities are free and how often spawns reuse one
+fn write_pool<K: PoolKind>(overlay: &mut String, pool: Option<&Pool<K>>) {
+    let Some(pool) = pool else {
+        return;
+    };
+    let stats = pool.stats();
+    let _ = write!(
+        overlay,
+        "\npool {}: {} free, {}% reused ({} spawned)",
+        K::NAME,
+        pool.free(),
+        format_value(stats.reuse_rate().map(|rate| rate as f64 * 100.0), 0),
+        stats.spawned,
+    );
+}
+
+///
+/// update_frame_graph: Bevy system
+///
+/// Sizes the graph's bars to the latest frame times, coloured by how far they are off 60 FPS
+pub fn update_frame_graph(
+    history: Res<FrameHistory>,
+    mut bar_query: Query<(&FrameGraphBar, &mut Style, &mut BackgroundColor)>,
+) {
+    let latest: Vec<f32> = history.frame_times().rev().take(GRAPH_BARS).collect();
+    for (bar, mut style, mut color) in bar_query.iter_mut() {
+        // the newest frame on the right
+        let frame_time = latest.get(GRAPH_BARS - 1 - bar.0).copied().unwrap_or(0.0);
+        style.height = Val::Px((frame_time / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT);
+        color.0 = if frame_time <= 1000.0 / 55.0 {
+            Color::GREEN
+        } else if frame_time <= 1000.0 / 30.0 {
+            Color::YELLOW
+        } else {
+            Color::RED
+        };
+    }
+}
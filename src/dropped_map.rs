@@ -0,0 +1,528 @@
+//! Playing a Tiled map dropped onto the game, for sharing levels: drop a .tmx file together with
+//! the tilesets and images it uses, or a .zip holding them, and it fades in in place of the
+//! current map, or a new game starts on it from the main menu. On the web the canvas takes the
+//! drop; natively the window does.
+//!
+//! The files are kept in memory and served by the DROPPED_SOURCE asset source, each drop in a
+//! directory of its own so the asset server doesn't hand back the last drop's map. Only the
+//! latest drop is kept. A drop is checked with validate_map_files before the current map is torn
+//! down, and what's wrong with it, missing tilesets and images especially, is shown in a toast.
+
+use crate::gfx::ScreenFade;
+use crate::helpers::tiled::{validate_map_files, LoadMap, MapLoaded, MapReport, TiledMap};
+use crate::locale::Locale;
+use crate::options::StartOptions;
+use crate::state::{AppState, ChangeState};
+use crate::t;
+use crate::toast::ShowToast;
+use bevy::{
+    asset::{
+        io::{AssetReader, AssetReaderError, AssetSource, PathStream, Reader, VecReader},
+        LoadState,
+    },
+    prelude::*,
+    utils::{BoxedFuture, HashMap},
+};
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use thiserror::Error;
+
+/// Name of the asset source dropped files are read from, as in "dropped://1/level.tmx"
+pub const DROPPED_SOURCE: &str = "dropped";
+/// Seconds to fade out and back in to a dropped map
+const DROP_FADE: f32 = 0.3;
+
+/// A file dropped onto the game. Files dropped in the same frame are one drop.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct DroppedFile {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+///
+/// DroppedFiles
+///
+/// The files of the latest drop, by their path in DROPPED_SOURCE. Clones share the files.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct DroppedFiles {
+    files: Arc<RwLock<HashMap<PathBuf, Arc<[u8]>>>>,
+    drops: u32,
+}
+
+impl DroppedFiles {
+    /// Replaces the files with those of `map`, returning the asset path of its map
+    pub fn replace(&mut self, map: DroppedMap) -> String {
+        self.drops += 1;
+        let dir = PathBuf::from(self.drops.to_string());
+        let files = map
+            .files
+            .into_iter()
+            .map(|(path, bytes)| (dir.join(path), Arc::from(bytes.into_boxed_slice())))
+            .collect();
+        if let Ok(mut shared) = self.files.write() {
+            *shared = files;
+        }
+        let path = dir.join(map.map).to_string_lossy().replace('\\', "/");
+        format!("{}://{}", DROPPED_SOURCE, path)
+    }
+
+    pub fn get(&self, path: &Path) -> Option<Arc<[u8]>> {
+        self.files.read().ok()?.get(path).cloned()
+    }
+}
+
+/// Serves DroppedFiles to the asset server
+struct DroppedFilesReader(DroppedFiles);
+
+impl AssetReader for DroppedFilesReader {
+    fn read<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+        Box::pin(async move {
+            let bytes = self
+                .0
+                .get(path)
+                .ok_or_else(|| AssetReaderError::NotFound(path.to_path_buf()))?;
+            let reader: Box<Reader> = Box::new(VecReader::new(bytes.to_vec()));
+            Ok(reader)
+        })
+    }
+
+    fn read_meta<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+        // dropped files never come with .meta files
+        Box::pin(async move { Err(AssetReaderError::NotFound(path.to_path_buf())) })
+    }
+
+    fn read_directory<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<PathStream>, AssetReaderError>> {
+        Box::pin(async move { Err(AssetReaderError::NotFound(path.to_path_buf())) })
+    }
+
+    fn is_directory<'a>(
+        &'a self,
+        _path: &'a Path,
+    ) -> BoxedFuture<'a, Result<bool, AssetReaderError>> {
+        Box::pin(async move { Ok(false) })
+    }
+}
+
+///
+/// DroppedMap
+///
+/// A drop that holds one map, by its path among the files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DroppedMap {
+    pub map: PathBuf,
+    pub files: HashMap<PathBuf, Vec<u8>>,
+}
+
+#[derive(Error, Debug)]
+pub enum DropError {
+    #[error("{name} isn't a zip file that can be read: {message}")]
+    Zip { name: String, message: String },
+    #[error("there's no .tmx map among {0}")]
+    NoMap(String),
+    #[error("drop one map at a time, not {}", .0.join(", "))]
+    SeveralMaps(Vec<String>),
+    #[error("{0}")]
+    Invalid(MapReport),
+}
+
+/// The map among `files`, with the files inside dropped .zip files taken out of them, once
+/// validate_map_files finds nothing wrong with it
+pub fn unpack_drop(files: Vec<DroppedFile>) -> Result<DroppedMap, DropError> {
+    let names = files
+        .iter()
+        .map(|file| file.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut unpacked = HashMap::default();
+    for file in files {
+        if file.name.to_lowercase().ends_with(".zip") {
+            unzip(file, &mut unpacked)?;
+        } else {
+            unpacked.insert(PathBuf::from(file.name), file.bytes);
+        }
+    }
+
+    let mut maps: Vec<&PathBuf> = unpacked
+        .keys()
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("tmx"))
+        })
+        .collect();
+    maps.sort();
+    let map = match maps.as_slice() {
+        [] => return Err(DropError::NoMap(names)),
+        [map] => (*map).clone(),
+        _ => {
+            let maps = maps.iter().map(|map| map.display().to_string()).collect();
+            return Err(DropError::SeveralMaps(maps));
+        }
+    };
+
+    let report = validate_map_files(&map, &unpacked);
+    if !report.is_ok() {
+        return Err(DropError::Invalid(report));
+    }
+    Ok(DroppedMap {
+        map,
+        files: unpacked,
+    })
+}
+
+fn unzip(file: DroppedFile, files: &mut HashMap<PathBuf, Vec<u8>>) -> Result<(), DropError> {
+    let error = |message: String| DropError::Zip {
+        name: file.name.clone(),
+        message,
+    };
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(&file.bytes)).map_err(|e| error(e.to_string()))?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(|e| error(e.to_string()))?;
+        // skipping paths that would leave the archive, and the resource forks macOS adds
+        let Some(path) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        if entry.is_dir() || path.starts_with("__MACOSX") {
+            continue;
+        }
+        let mut bytes = Vec::new();
+        entry
+            .read_to_end(&mut bytes)
+            .map_err(|e| error(e.to_string()))?;
+        files.insert(path, bytes);
+    }
+    Ok(())
+}
+
+///
+/// DroppedMapTransition
+///
+/// Where switching to a dropped map is at, like DoorTransition for doors
+/// * FadingOut: fading out while the dropped map loads
+/// * Loading: LoadMap sent, waiting for MapLoaded
+/// * FadingIn: on the dropped map, or back on the old one if it failed to load
+#[derive(Resource, Debug, Default)]
+pub enum DroppedMapTransition {
+    #[default]
+    Idle,
+    FadingOut {
+        name: String,
+        path: String,
+        map: Handle<TiledMap>,
+    },
+    Loading,
+    FadingIn,
+}
+
+///
+/// DroppedMapPlugin
+///
+/// Plays maps dropped onto the game. It registers DROPPED_SOURCE, so it has to be added before
+/// the AssetPlugin, that is before DefaultPlugins.
+pub struct DroppedMapPlugin;
+
+impl Plugin for DroppedMapPlugin {
+    fn build(&self, app: &mut App) {
+        let files = DroppedFiles::default();
+        let reader_files = files.clone();
+        app.register_asset_source(
+            DROPPED_SOURCE,
+            AssetSource::build()
+                .with_reader(move || Box::new(DroppedFilesReader(reader_files.clone()))),
+        )
+        .insert_resource(files)
+        .init_resource::<DroppedMapTransition>()
+        .add_event::<DroppedFile>()
+        // also added by ToastPlugin and TiledMapPlugin
+        .add_event::<ShowToast>()
+        .add_event::<LoadMap>()
+        .add_event::<MapLoaded>()
+        .add_event::<ChangeState>()
+        .add_systems(Update, (play_dropped_files, advance_dropped_map).chain());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_event::<FileDragAndDrop>()
+            .add_systems(PreUpdate, read_dropped_paths);
+
+        #[cfg(target_arch = "wasm32")]
+        app.init_non_send_resource::<page::DropListener>()
+            .add_systems(PreUpdate, (page::listen_for_drops, page::receive_drops));
+    }
+}
+
+///
+/// play_dropped_files: Bevy system
+///
+/// Plays the map dropped this frame: in game it fades in in place of the current one, elsewhere
+/// it becomes the map new games start on, and from the main menu one starts. A drop that can't
+/// be played gets a toast saying why.
+#[allow(clippy::too_many_arguments)]
+pub fn play_dropped_files(
+    mut events: EventReader<DroppedFile>,
+    mut dropped: ResMut<DroppedFiles>,
+    asset_server: Res<AssetServer>,
+    state: Option<Res<State<AppState>>>,
+    mut options: Option<ResMut<StartOptions>>,
+    locale: Option<Res<Locale>>,
+    mut change_state: EventWriter<ChangeState>,
+    mut transition: ResMut<DroppedMapTransition>,
+    fade: Option<ResMut<ScreenFade>>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let files: Vec<DroppedFile> = events.read().cloned().collect();
+    if files.is_empty() {
+        return;
+    }
+    let name = files
+        .iter()
+        .map(|file| file.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let map = match unpack_drop(files) {
+        Ok(map) => map,
+        Err(e) => {
+            let headline = match &locale {
+                Some(locale) => t!(locale, "drop.invalid", name = name),
+                None => format!("{} can't be played:", name),
+            };
+            toasts.send(ShowToast::error(format!("{}\n{}", headline, e)));
+            return;
+        }
+    };
+    let path = dropped.replace(map);
+    info!("Playing the dropped map {}", path);
+
+    let state = state.map(|state| *state.get());
+    if state == Some(AppState::InGame) {
+        if let Some(mut fade) = fade {
+            fade.fade_out(DROP_FADE);
+        }
+        // loaded ahead, so the current map stays if the dropped one fails
+        *transition = DroppedMapTransition::FadingOut {
+            name,
+            map: asset_server.load(path.clone()),
+            path,
+        };
+        return;
+    }
+    // start_game loads it
+    if let Some(options) = options.as_mut() {
+        options.map = path;
+    }
+    if state == Some(AppState::MainMenu) {
+        change_state.send(ChangeState(AppState::InGame));
+    }
+}
+
+///
+/// advance_dropped_map: Bevy system
+///
+/// Moves the DroppedMapTransition along as the fade finishes and the map loads
+pub fn advance_dropped_map(
+    mut loaded: EventReader<MapLoaded>,
+    mut load_map: EventWriter<LoadMap>,
+    asset_server: Res<AssetServer>,
+    mut transition: ResMut<DroppedMapTransition>,
+    mut fade: Option<ResMut<ScreenFade>>,
+    locale: Option<Res<Locale>>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let map_loaded = loaded.read().count() > 0;
+    let faded = fade.as_ref().is_none_or(|fade| fade.is_done());
+
+    let next = match &*transition {
+        DroppedMapTransition::FadingOut { name, path, map } => {
+            match asset_server.load_state(map.id()) {
+                LoadState::Failed => {
+                    error!("The dropped map {} failed to load", path);
+                    let text = match &locale {
+                        Some(locale) => t!(locale, "drop.load_failed", name = name),
+                        None => format!("{} failed to load, the log says why", name),
+                    };
+                    toasts.send(ShowToast::error(text));
+                    Some(DroppedMapTransition::FadingIn)
+                }
+                LoadState::Loaded if faded => {
+                    load_map.send(LoadMap::new(path.clone()));
+                    Some(DroppedMapTransition::Loading)
+                }
+                _ => None,
+            }
+        }
+        DroppedMapTransition::Loading if map_loaded => Some(DroppedMapTransition::FadingIn),
+        DroppedMapTransition::FadingIn if faded => Some(DroppedMapTransition::Idle),
+        _ => None,
+    };
+    let Some(next) = next else {
+        return;
+    };
+    if matches!(next, DroppedMapTransition::FadingIn) {
+        if let Some(fade) = fade.as_mut() {
+            fade.fade_in(DROP_FADE);
+        }
+    }
+    *transition = next;
+}
+
+///
+/// read_dropped_paths: Bevy system
+///
+/// Reads the files dropped onto the window natively, for play_dropped_files
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_dropped_paths(
+    mut events: EventReader<FileDragAndDrop>,
+    mut dropped: EventWriter<DroppedFile>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    for event in events.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = event else {
+            continue;
+        };
+        let name = path_buf
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match std::fs::read(path_buf) {
+            Ok(bytes) => {
+                dropped.send(DroppedFile { name, bytes });
+            }
+            Err(e) => {
+                toasts.send(ShowToast::error(format!("Could not read {}: {}", name, e)));
+            }
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod page {
+    use super::DroppedFile;
+    use bevy::{prelude::*, window::PrimaryWindow};
+    use std::sync::Mutex;
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+    /// Files read from drops, waiting for receive_drops
+    static DROPS: Mutex<Vec<DroppedFile>> = Mutex::new(Vec::new());
+
+    /// The canvas's drag and drop listeners, once the canvas is on the page
+    #[derive(Default)]
+    pub(super) struct DropListener {
+        listening: Option<Listening>,
+    }
+
+    struct Listening {
+        canvas: web_sys::Element,
+        on_dragover: Closure<dyn FnMut(web_sys::DragEvent)>,
+        on_drop: Closure<dyn FnMut(web_sys::DragEvent)>,
+    }
+
+    impl Drop for Listening {
+        fn drop(&mut self) {
+            let _ = self.canvas.remove_event_listener_with_callback(
+                "dragover",
+                self.on_dragover.as_ref().unchecked_ref(),
+            );
+            let _ = self
+                .canvas
+                .remove_event_listener_with_callback("drop", self.on_drop.as_ref().unchecked_ref());
+        }
+    }
+
+    impl Listening {
+        /// Listens for drops on the canvas matching `selector`, or the first canvas
+        fn start(selector: Option<&str>) -> Option<Self> {
+            let canvas = web_sys::window()?
+                .document()?
+                .query_selector(selector.unwrap_or("canvas"))
+                .ok()
+                .flatten()?;
+            let on_dragover =
+                Closure::<dyn FnMut(web_sys::DragEvent)>::new(|event: web_sys::DragEvent| {
+                    // or the browser won't let the file be dropped, and opens it instead
+                    event.prevent_default();
+                    if let Some(transfer) = event.data_transfer() {
+                        transfer.set_drop_effect("copy");
+                    }
+                });
+            let on_drop =
+                Closure::<dyn FnMut(web_sys::DragEvent)>::new(|event: web_sys::DragEvent| {
+                    event.prevent_default();
+                    let Some(list) = event.data_transfer().and_then(|transfer| transfer.files())
+                    else {
+                        return;
+                    };
+                    let files = (0..list.length())
+                        .filter_map(|index| list.get(index))
+                        .collect();
+                    spawn_local(read_files(files));
+                });
+            canvas
+                .add_event_listener_with_callback("dragover", on_dragover.as_ref().unchecked_ref())
+                .ok()?;
+            canvas
+                .add_event_listener_with_callback("drop", on_drop.as_ref().unchecked_ref())
+                .ok()?;
+            Some(Listening {
+                canvas,
+                on_dragover,
+                on_drop,
+            })
+        }
+    }
+
+    /// Reads the files of a drop, queueing them together once they're all read so they arrive
+    /// in the same frame
+    async fn read_files(files: Vec<web_sys::File>) {
+        let mut read = Vec::new();
+        for file in files {
+            match JsFuture::from(file.array_buffer()).await {
+                Ok(buffer) => read.push(DroppedFile {
+                    name: file.name(),
+                    bytes: js_sys::Uint8Array::new(&buffer).to_vec(),
+                }),
+                Err(e) => warn!("Could not read the dropped {}: {:?}", file.name(), e),
+            }
+        }
+        if let Ok(mut drops) = DROPS.lock() {
+            drops.extend(read);
+        }
+    }
+
+    ///
+    /// listen_for_drops: Bevy system
+    ///
+    /// Starts listening for drops once the canvas is on the page
+    pub(super) fn listen_for_drops(
+        mut listener: NonSendMut<DropListener>,
+        window_query: Query<&Window, With<PrimaryWindow>>,
+    ) {
+        if listener.listening.is_some() {
+            return;
+        }
+        let selector = window_query
+            .get_single()
+            .ok()
+            .and_then(|window| window.canvas.as_deref());
+        listener.listening = Listening::start(selector);
+    }
+
+    ///
+    /// receive_drops: Bevy system
+    ///
+    /// Sends DroppedFile for the files read since the last frame
+    pub(super) fn receive_drops(mut dropped: EventWriter<DroppedFile>) {
+        if let Ok(mut drops) = DROPS.try_lock() {
+            dropped.send_batch(drops.drain(..));
+        }
+    }
+}
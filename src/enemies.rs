@@ -0,0 +1,409 @@
+//! Enemies placed in Tiled: an object of type "enemy" spawns the EnemyArchetype registered for
+//! its `kind` with App::register_enemy where the object is, drawn with the `<kind>_idle` and
+//! `<kind>_walk` clips of the AnimationResource, and dying with `<kind>_death`, with the Health
+//! the archetype gives it. Enemies are parented to the map, so they go
+//! with it and come back as new on coming back to it.
+//!
+//! An enemy walks the path its `patrol` property points to: back and forth along a polyline, or
+//! round and round a polygon, turning to face each waypoint. On coming within its archetype's
+//! notice_radius of the player it chases them instead, with a ShowEmote of Emote::Alert, until
+//! they're LOSE_FACTOR times that away again, with one of Emote::Lost, and then heads back to
+//! its path. Without a path it stands still until it notices the player.
+//!
+//! Object properties:
+//! * kind: the name of its EnemyArchetype
+//! * patrol: an object property pointing to the polyline or polygon it walks
+//! * once: once it's died, it doesn't come back, saves included
+
+use crate::gfx::{AnimationController, AnimationResource, Emote, ShowEmote, SpriteLayer};
+use crate::health::{DeathAnimation, Died, Faction, Health, Invulnerability};
+use crate::helpers::tiled::{CurrentMap, ObjectShape, RegisterTiledObject, TiledObject};
+use crate::physics::{AabbCollider, Velocity};
+use crate::player::{Facing, Player, IDLE, WALK};
+use crate::save::GameProgress;
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::{ecs::system::EntityCommands, prelude::*, transform::TransformSystem, utils::HashMap};
+
+/// Object type spawning enemies
+pub const ENEMY_TYPE: &str = "enemy";
+/// How many times its notice_radius away the player has to get for a chasing enemy to give up
+pub const LOSE_FACTOR: f32 = 1.5;
+/// The enemy kind the game comes with, as EnemyArchetype::default()
+pub const SLIME: &str = "slime";
+/// How near an enemy has to get to a waypoint to head for the next one
+pub const WAYPOINT_REACH: f32 = 1.0;
+
+/// What an enemy of a kind is like
+/// * collider: the box it collides with walls as
+/// * health: its max Health
+/// * speed, chase_speed: how fast it patrols and chases, in world units a second
+/// * notice_radius: how near the player has to get for it to chase them
+/// * invulnerability: seconds it can't be hurt for after a hit, none by default, so rapid fire
+///   hits it every time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnemyArchetype {
+    pub collider: AabbCollider,
+    pub health: i32,
+    pub speed: f32,
+    pub chase_speed: f32,
+    pub notice_radius: f32,
+    pub invulnerability: f32,
+}
+
+impl Default for EnemyArchetype {
+    fn default() -> Self {
+        EnemyArchetype {
+            collider: AabbCollider::new(Vec2::new(5.0, 3.0)).with_offset(Vec2::new(0.0, -5.0)),
+            health: 3,
+            speed: 30.0,
+            chase_speed: 55.0,
+            notice_radius: 64.0,
+            invulnerability: 0.0,
+        }
+    }
+}
+
+///
+/// EnemyArchetypes
+///
+/// The EnemyArchetype of each enemy kind, see App::register_enemy
+#[derive(Resource, Debug, Default)]
+pub struct EnemyArchetypes(HashMap<String, EnemyArchetype>);
+
+impl EnemyArchetypes {
+    pub fn get(&self, kind: &str) -> Option<&EnemyArchetype> {
+        self.0.get(kind)
+    }
+}
+
+pub trait RegisterEnemy {
+    /// Makes "enemy" objects with `kind = "<kind>"` spawn an enemy like `archetype`
+    fn register_enemy(&mut self, kind: impl Into<String>, archetype: EnemyArchetype) -> &mut Self;
+}
+
+impl RegisterEnemy for App {
+    fn register_enemy(&mut self, kind: impl Into<String>, archetype: EnemyArchetype) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(EnemyArchetypes::default)
+            .0
+            .insert(kind.into(), archetype);
+        self
+    }
+}
+
+/// On an "enemy" object, the enemy it spawns once the map is in place
+/// * patrol: the id of the path object it walks
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct EnemySpawner {
+    pub kind: String,
+    pub patrol: Option<u32>,
+    pub once: bool,
+}
+
+/// An enemy of `kind`, as its archetype made it
+/// * object: the id of the object it was spawned from
+/// * chasing: whether it's after the player
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Enemy {
+    pub kind: String,
+    pub archetype: EnemyArchetype,
+    pub object: u32,
+    pub once: bool,
+    pub chasing: bool,
+}
+
+/// The waypoints an enemy walks, where its Transform is
+/// * looped: round and round, going from the last waypoint to the first, rather than back and
+///   forth
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Patrol {
+    pub waypoints: Vec<Vec2>,
+    pub looped: bool,
+    next: usize,
+    backwards: bool,
+}
+
+impl Patrol {
+    pub fn new(waypoints: Vec<Vec2>, looped: bool) -> Self {
+        Patrol {
+            waypoints,
+            looped,
+            next: 0,
+            backwards: false,
+        }
+    }
+
+    /// The waypoint it's heading for
+    pub fn target(&self) -> Option<Vec2> {
+        self.waypoints.get(self.next).copied()
+    }
+
+    fn advance(&mut self) {
+        let last = self.waypoints.len().saturating_sub(1);
+        if self.looped {
+            self.next = if self.next >= last { 0 } else { self.next + 1 };
+            return;
+        }
+        if self.next >= last {
+            self.backwards = true;
+        } else if self.next == 0 {
+            self.backwards = false;
+        }
+        self.next = if self.backwards {
+            self.next.saturating_sub(1)
+        } else {
+            (self.next + 1).min(last)
+        };
+    }
+}
+
+/// Enemies from Tiled, patrolling and chasing
+pub struct EnemiesPlugin;
+
+impl Plugin for EnemiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EnemyArchetypes>()
+            .init_resource::<GameProgress>()
+            .init_resource::<CurrentMap>()
+            .add_event::<Died>()
+            .add_event::<ShowEmote>()
+            .register_enemy(SLIME, EnemyArchetype::default())
+            .register_tiled_object(ENEMY_TYPE, |entity: &mut EntityCommands, object| {
+                if let Some(spawner) = enemy_spawner(object) {
+                    entity.insert(spawner);
+                }
+            })
+            .add_systems(
+                PostUpdate,
+                // where the objects are in the world
+                spawn_enemies.after(TransformSystem::TransformPropagate),
+            )
+            .add_systems(Update, record_defeated_enemies)
+            .add_systems(
+                FixedUpdate,
+                move_enemies.in_set(FixedSet::Gameplay).in_set(GameplaySet),
+            );
+    }
+}
+
+/// The EnemySpawner of an "enemy" object, if it has a kind
+pub fn enemy_spawner(object: &TiledObject) -> Option<EnemySpawner> {
+    let Some(kind) = object.get_string("kind") else {
+        warn!("The enemy {} ({}) has no kind", object.name, object.id);
+        return None;
+    };
+    Some(EnemySpawner {
+        kind: kind.to_string(),
+        patrol: object.get_object("patrol"),
+        once: object.get_bool("once").unwrap_or(false),
+    })
+}
+
+/// The waypoints of a path object, looped for a polygon, as placed by `transform`. None for
+/// other shapes.
+pub fn patrol_from_path(path: &TiledObject, transform: &GlobalTransform) -> Option<Patrol> {
+    let (points, looped) = match &path.shape {
+        ObjectShape::Polyline { points } => (points, false),
+        ObjectShape::Polygon { points } => (points, true),
+        _ => return None,
+    };
+    let waypoints = points
+        .iter()
+        .map(|point| transform.transform_point(point.extend(0.0)).truncate())
+        .collect();
+    Some(Patrol::new(waypoints, looped))
+}
+
+///
+/// spawn_enemies: Bevy system
+///
+/// Spawns the enemies of new "enemy" objects, under the current map, leaving out `once` ones
+/// already defeated
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_enemies(
+    mut commands: Commands,
+    archetypes: Res<EnemyArchetypes>,
+    animations: Option<Res<AnimationResource>>,
+    progress: Res<GameProgress>,
+    current: Res<CurrentMap>,
+    spawner_query: Query<(&EnemySpawner, &TiledObject, &GlobalTransform), Added<EnemySpawner>>,
+    object_query: Query<(&TiledObject, &GlobalTransform)>,
+    map_query: Query<&GlobalTransform>,
+) {
+    let empty = AnimationResource::new();
+    let animations = animations.as_deref().unwrap_or(&empty);
+    for (spawner, object, transform) in spawner_query.iter() {
+        let defeated = current
+            .path()
+            .is_some_and(|map| progress.has_defeated(map, object.id));
+        if spawner.once && defeated {
+            continue;
+        }
+        let Some(archetype) = archetypes.get(&spawner.kind) else {
+            warn!("There's no enemy kind called {}", spawner.kind);
+            continue;
+        };
+
+        let map = current.entity().filter(|map| map_query.contains(*map));
+        // from the world to under the map
+        let to_map = map
+            .and_then(|map| map_query.get(map).ok())
+            .map_or(GlobalTransform::IDENTITY, |map| {
+                GlobalTransform::from(map.affine().inverse())
+            });
+
+        let patrol = spawner.patrol.and_then(|id| {
+            let path = object_query.iter().find(|(path, _)| path.id == id);
+            let patrol =
+                path.and_then(|(path, transform)| patrol_from_path(path, &(to_map * *transform)));
+            if patrol.is_none() {
+                warn!(
+                    "The patrol of the enemy {} ({}) isn't a polyline or polygon",
+                    object.name, object.id
+                );
+            }
+            patrol
+        });
+
+        let mut controller = AnimationController::new();
+        for (state, suffix) in [(IDLE, "idle"), (WALK, "walk")] {
+            let name = format!("{}_{}", spawner.kind, suffix);
+            match animations.get(&name) {
+                Some(clip) => controller = controller.with_clip(state, clip),
+                None => warn!("The {} enemy has no {} animation", spawner.kind, name),
+            }
+        }
+        let idle = animations.get(&format!("{}_idle", spawner.kind));
+        let position = (to_map * *transform)
+            .translation()
+            .truncate()
+            .extend(SpriteLayer::Actors.z());
+
+        let mut enemy = commands.spawn((
+            Enemy {
+                kind: spawner.kind.clone(),
+                archetype: *archetype,
+                object: object.id,
+                once: spawner.once,
+                chasing: false,
+            },
+            Velocity::default(),
+            archetype.collider,
+            Facing::default(),
+            controller,
+            SpriteSheetBundle {
+                texture: idle
+                    .as_ref()
+                    .map(|idle| idle.texture().clone())
+                    .unwrap_or_default(),
+                atlas: TextureAtlas {
+                    layout: idle
+                        .as_ref()
+                        .map(|idle| idle.atlas().clone())
+                        .unwrap_or_default(),
+                    index: idle.as_ref().map_or(0, |idle| idle.frame()),
+                },
+                transform: Transform::from_translation(position),
+                ..default()
+            },
+            Health::new(archetype.health),
+            DeathAnimation(format!("{}_death", spawner.kind)),
+            Faction::Enemy,
+            Name::new(format!("{} {}", spawner.kind, object.id)),
+        ));
+        if let Some(patrol) = patrol {
+            enemy.insert(patrol);
+        }
+        if archetype.invulnerability > 0.0 {
+            enemy.insert(Invulnerability::new(archetype.invulnerability));
+        }
+        if let Some(map) = map {
+            enemy.set_parent(map);
+        }
+    }
+}
+
+///
+/// move_enemies: Bevy system
+///
+/// Walks enemies along their patrols, or after the player once they notice them
+pub fn move_enemies(
+    player_query: Query<&Transform, With<Player>>,
+    mut enemy_query: Query<(
+        Entity,
+        &mut Enemy,
+        &mut Velocity,
+        &Transform,
+        Option<&mut Patrol>,
+    )>,
+    mut emotes: EventWriter<ShowEmote>,
+    time: Res<Time>,
+) {
+    let player = player_query
+        .iter()
+        .next()
+        .map(|transform| transform.translation.truncate());
+    let delta = time.delta_seconds();
+    for (entity, mut enemy, mut velocity, transform, patrol) in enemy_query.iter_mut() {
+        let position = transform.translation.truncate();
+        let distance = player.map_or(f32::INFINITY, |player| player.distance(position));
+        let notice = enemy.archetype.notice_radius;
+        if !enemy.chasing && distance <= notice {
+            enemy.chasing = true;
+            emotes.send(ShowEmote {
+                entity,
+                emote: Emote::Alert,
+            });
+        } else if enemy.chasing && distance > notice * LOSE_FACTOR {
+            enemy.chasing = false;
+            emotes.send(ShowEmote {
+                entity,
+                emote: Emote::Lost,
+            });
+        }
+
+        let (target, speed) = match (enemy.chasing, player, patrol) {
+            (true, Some(player), _) => (Some(player), enemy.archetype.chase_speed),
+            (false, _, Some(mut patrol)) => {
+                let reach = WAYPOINT_REACH.max(enemy.archetype.speed * delta);
+                if patrol
+                    .target()
+                    .is_some_and(|target| target.distance(position) <= reach)
+                {
+                    patrol.advance();
+                }
+                (patrol.target(), enemy.archetype.speed)
+            }
+            _ => (None, 0.0),
+        };
+        let wanted = target.map_or(Vec2::ZERO, |target| {
+            let offset = target - position;
+            // not past it in one step
+            offset.normalize_or_zero() * speed.min(offset.length() / delta.max(f32::EPSILON))
+        });
+        if velocity.0 != wanted {
+            velocity.0 = wanted;
+        }
+    }
+}
+
+///
+/// record_defeated_enemies: Bevy system
+///
+/// Remembers the `once` enemies that die in GameProgress
+pub fn record_defeated_enemies(
+    mut events: EventReader<Died>,
+    mut progress: ResMut<GameProgress>,
+    current: Res<CurrentMap>,
+    enemy_query: Query<&Enemy>,
+) {
+    for event in events.read() {
+        let Ok(enemy) = enemy_query.get(event.entity) else {
+            continue;
+        };
+        if let (true, Some(map)) = (enemy.once, current.path()) {
+            progress.defeat(map, enemy.object);
+        }
+    }
+}
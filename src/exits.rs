@@ -0,0 +1,150 @@
+//! Level exits: Tiled objects of type "exit" are trigger regions, on any layer, that complete the
+//! level when the player walks into one. LevelComplete is sent, which freezes the RunStats and
+//! puts up the results screen, VICTORY_STINGER plays over the music and GameplayInput goes off,
+//! so the player stands still behind the results. With a leaderboard_url in the StartOptions the
+//! run's score is submitted too, under SCORE_NAME.
+//!
+//! The exit reached is kept in ReachedExit for the results screen's Next Level button, which
+//! goes on to its `next_map`. Input comes back once a map loads, the next one or the same one
+//! restarted, but not hot reloaded, or on going back to the main menu.
+//!
+//! Object properties:
+//! * next_map: path of the map after this one, relative to the assets directory; without one
+//!   the level is the last, and the results only offer to play it again
+//! * next_spawn: the spawn point to start at there, DEFAULT_SPAWN when unset
+
+use crate::bridge::LevelComplete;
+use crate::health::Dying;
+use crate::helpers::tiled::{
+    ChangeMap, MapLoaded, RegisterTiledObject, TiledObject, TriggerEntered,
+};
+use crate::input::GameplayInput;
+use crate::leaderboard::SubmitScore;
+use crate::options::StartOptions;
+use crate::player::Player;
+use crate::run_stats::{finish_runs, RunStats};
+use crate::sound::PlayStinger;
+use crate::state::AppState;
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+/// Object type of level exits
+pub const EXIT_TYPE: &str = "exit";
+/// Stinger played over the music on reaching an exit
+pub const VICTORY_STINGER: &str = "victory";
+/// The name scores are submitted under, the game not asking for one
+pub const SCORE_NAME: &str = "player";
+
+/// On exit objects, from their properties
+#[derive(Component, Debug, Default, Clone, PartialEq, Eq)]
+pub struct Exit {
+    pub next_map: Option<String>,
+    pub next_spawn: Option<String>,
+}
+
+impl Exit {
+    /// The way to the next level, None for the last one
+    pub fn next_level(&self) -> Option<ChangeMap> {
+        let change = ChangeMap::new(self.next_map.clone()?);
+        Some(match &self.next_spawn {
+            Some(spawn) => change.with_spawn(spawn.clone()),
+            None => change,
+        })
+    }
+}
+
+///
+/// ReachedExit
+///
+/// The exit the player reached on the current map, None until then and again once a map loads
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReachedExit(pub Option<Exit>);
+
+/// Level exits from Tiled and completing the level at them
+pub struct ExitsPlugin;
+
+impl Plugin for ExitsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReachedExit>()
+            .init_resource::<RunStats>()
+            .init_resource::<GameplayInput>()
+            .add_event::<TriggerEntered>()
+            .add_event::<MapLoaded>()
+            .add_event::<LevelComplete>()
+            .add_event::<PlayStinger>()
+            .add_event::<SubmitScore>()
+            .register_trigger_object(EXIT_TYPE, spawn_exit)
+            .add_systems(OnEnter(AppState::MainMenu), leave_exit)
+            .add_systems(
+                Update,
+                (reach_exits.before(finish_runs), leave_exit_on_load),
+            );
+    }
+}
+
+fn spawn_exit(entity: &mut EntityCommands, object: &TiledObject) {
+    entity.insert(Exit {
+        next_map: object.get_string("next_map").map(str::to_string),
+        next_spawn: object.get_string("next_spawn").map(str::to_string),
+    });
+}
+
+///
+/// reach_exits: Bevy system
+///
+/// Completes the level when the player walks into an exit, once per map
+#[allow(clippy::too_many_arguments)]
+pub fn reach_exits(
+    mut entered: EventReader<TriggerEntered>,
+    exit_query: Query<&Exit>,
+    player_query: Query<(), (With<Player>, Without<Dying>)>,
+    stats: Res<RunStats>,
+    options: Option<Res<StartOptions>>,
+    mut reached: ResMut<ReachedExit>,
+    mut input: ResMut<GameplayInput>,
+    mut completed: EventWriter<LevelComplete>,
+    mut stingers: EventWriter<PlayStinger>,
+    mut submit: EventWriter<SubmitScore>,
+) {
+    let exit = entered
+        .read()
+        .filter(|event| player_query.contains(event.entity))
+        .find_map(|event| exit_query.get(event.region).ok());
+    let Some(exit) = exit.filter(|_| reached.0.is_none()) else {
+        return;
+    };
+    reached.0 = Some(exit.clone());
+    input.0 = false;
+    completed.send(LevelComplete);
+    stingers.send(PlayStinger::new(VICTORY_STINGER));
+    let configured = options.is_some_and(|options| options.leaderboard_url.is_some());
+    if configured {
+        submit.send(SubmitScore {
+            name: SCORE_NAME.to_string(),
+            score: stats.score,
+        });
+    }
+}
+
+///
+/// leave_exit: Bevy system
+///
+/// Forgets the exit reached, giving the player their input back
+pub fn leave_exit(mut reached: ResMut<ReachedExit>, mut input: ResMut<GameplayInput>) {
+    if reached.0.take().is_some() {
+        input.0 = true;
+    }
+}
+
+///
+/// leave_exit_on_load: Bevy system
+///
+/// leave_exit once a map loads, hot reloads aside
+pub fn leave_exit_on_load(
+    mut loaded: EventReader<MapLoaded>,
+    reached: ResMut<ReachedExit>,
+    input: ResMut<GameplayInput>,
+) {
+    if loaded.read().filter(|event| !event.reloaded).count() > 0 {
+        leave_exit(reached, input);
+    }
+}
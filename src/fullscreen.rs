@@ -0,0 +1,244 @@
+//! Letting the game take over the screen: the keys of Action::ToggleFullscreen, F11 by default,
+//! send ToggleFullscreen, and the page can call `set_fullscreen(true)`. On the web that's the
+//! Fullscreen API on the canvas; natively the window goes borderless fullscreen.
+//!
+//! Fullscreen follows what the browser or the window actually did, checked every frame, so a
+//! request the browser refuses or leaving browser fullscreen with Escape can't leave it wrong. On
+//! the web the window is resized through CanvasFit like any other resize, to the screen on the
+//! way in and back on the way out, so the camera framing follows; natively winit resizes it.
+
+use crate::canvas::fit_canvas;
+#[cfg(target_arch = "wasm32")]
+use crate::canvas::{CanvasFit, CanvasSize};
+use crate::input::{Action, ActionState};
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::window::WindowMode;
+use bevy::{prelude::*, window::PrimaryWindow};
+
+///
+/// Fullscreen
+///
+/// Whether the game is fullscreen, for the settings menu to show
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Fullscreen {
+    active: bool,
+}
+
+impl Fullscreen {
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+}
+
+/// Leaves fullscreen if the game is fullscreen, and goes fullscreen if it isn't. Browsers only
+/// allow going fullscreen shortly after a click or key press.
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct ToggleFullscreen;
+
+/// Fullscreen, ToggleFullscreen and its keys
+pub struct FullscreenPlugin;
+
+impl Plugin for FullscreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Fullscreen>()
+            .add_event::<ToggleFullscreen>()
+            .add_systems(PreUpdate, sync_fullscreen.before(fit_canvas))
+            .add_systems(
+                Update,
+                (
+                    toggle_fullscreen_on_key,
+                    toggle_fullscreen.run_if(on_event::<ToggleFullscreen>()),
+                )
+                    .chain(),
+            );
+    }
+}
+
+///
+/// toggle_fullscreen_on_key: Bevy system
+///
+/// Sends ToggleFullscreen when Action::ToggleFullscreen is pressed, F11 without InputPlugin
+pub fn toggle_fullscreen_on_key(
+    actions: Option<Res<ActionState>>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut events: EventWriter<ToggleFullscreen>,
+) {
+    let pressed = match (actions, keys) {
+        (Some(actions), _) => actions.just_pressed(Action::ToggleFullscreen),
+        (None, Some(keys)) => keys.just_pressed(KeyCode::F11),
+        (None, None) => false,
+    };
+    if pressed {
+        events.send(ToggleFullscreen);
+    }
+}
+
+///
+/// toggle_fullscreen: Bevy system
+///
+/// Handles ToggleFullscreen, asking the browser for it on the web. Toggling twice in a frame does
+/// nothing.
+#[cfg(target_arch = "wasm32")]
+pub fn toggle_fullscreen(
+    mut events: EventReader<ToggleFullscreen>,
+    fullscreen: Res<Fullscreen>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let toggled = events.read().fold(false, |toggled, _| !toggled);
+    if !toggled {
+        return;
+    }
+    if let Ok(window) = window_query.get_single() {
+        page::request(!fullscreen.active, window.canvas.as_deref());
+    }
+}
+
+///
+/// toggle_fullscreen: Bevy system
+///
+/// Handles ToggleFullscreen, switching the window's mode natively. Toggling twice in a frame does
+/// nothing.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn toggle_fullscreen(
+    mut events: EventReader<ToggleFullscreen>,
+    fullscreen: Res<Fullscreen>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let toggled = events.read().fold(false, |toggled, _| !toggled);
+    if !toggled {
+        return;
+    }
+    for mut window in window_query.iter_mut() {
+        window.mode = if fullscreen.active {
+            WindowMode::Windowed
+        } else {
+            WindowMode::BorderlessFullscreen
+        };
+    }
+}
+
+///
+/// sync_fullscreen: Bevy system
+///
+/// Keeps Fullscreen what the browser says, and the window the size of the screen while the
+/// canvas is fullscreen, then the size it had before
+#[cfg(target_arch = "wasm32")]
+pub fn sync_fullscreen(
+    mut fullscreen: ResMut<Fullscreen>,
+    time: Res<Time<Real>>,
+    fit: Option<ResMut<CanvasFit>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut windowed: Local<Option<CanvasSize>>,
+) {
+    let window = window_query.get_single().ok();
+    let screen = page::fullscreen_size(window.and_then(|window| window.canvas.as_deref()));
+    let entering = screen.is_some() && !fullscreen.active;
+    set_active(&mut fullscreen, screen.is_some());
+
+    let Some(mut fit) = fit else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    match screen {
+        Some(size) => {
+            if entering {
+                *windowed = window.map(|window| CanvasSize {
+                    width: window.width(),
+                    height: window.height(),
+                    scale_factor: window.scale_factor(),
+                });
+            }
+            fit.observe(size, now);
+        }
+        None => {
+            if let Some(size) = windowed.take() {
+                fit.observe(size, now);
+            }
+        }
+    }
+}
+
+///
+/// sync_fullscreen: Bevy system
+///
+/// Keeps Fullscreen what the window's mode is natively
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sync_fullscreen(
+    mut fullscreen: ResMut<Fullscreen>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let active = window_query
+        .get_single()
+        .is_ok_and(|window| window.mode != WindowMode::Windowed);
+    set_active(&mut fullscreen, active);
+}
+
+fn set_active(fullscreen: &mut ResMut<Fullscreen>, active: bool) {
+    if fullscreen.active != active {
+        fullscreen.active = active;
+        info!("{} fullscreen", if active { "Entered" } else { "Left" });
+    }
+}
+
+/// Makes the game fullscreen or leaves fullscreen, for set_fullscreen(). It has to run while the
+/// page handles a click or key press, as it does, for the browser to allow going fullscreen.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn request(fullscreen: bool, selector: Option<&str>) {
+    page::request(fullscreen, selector);
+}
+
+#[cfg(target_arch = "wasm32")]
+mod page {
+    use crate::canvas::CanvasSize;
+    use crate::utils::call_method;
+    use bevy::prelude::*;
+
+    fn document() -> Option<web_sys::Document> {
+        web_sys::window()?.document()
+    }
+
+    /// The size of the screen, if the canvas matching `selector`, or the first canvas, is
+    /// fullscreen
+    pub(super) fn fullscreen_size(selector: Option<&str>) -> Option<CanvasSize> {
+        let element = document()?.fullscreen_element()?;
+        if !element
+            .matches(selector.unwrap_or("canvas"))
+            .unwrap_or(false)
+        {
+            // the page made something else fullscreen
+            return None;
+        }
+        let scale_factor = web_sys::window()?.device_pixel_ratio() as f32;
+        Some(CanvasSize {
+            width: element.client_width() as f32,
+            height: element.client_height() as f32,
+            scale_factor,
+        })
+    }
+
+    /// Asks the browser to make the canvas matching `selector`, or the first canvas, fullscreen,
+    /// or to leave fullscreen
+    pub(super) fn request(fullscreen: bool, selector: Option<&str>) {
+        let Some(document) = document() else {
+            return;
+        };
+        let is_fullscreen = document.fullscreen_element().is_some();
+        if !fullscreen {
+            if is_fullscreen {
+                call_method(&document, "exitFullscreen");
+            }
+            return;
+        }
+        if is_fullscreen {
+            return;
+        }
+        match document
+            .query_selector(selector.unwrap_or("canvas"))
+            .ok()
+            .flatten()
+        {
+            Some(canvas) => call_method(&canvas, "requestFullscreen"),
+            None => warn!("There's no canvas to make fullscreen"),
+        }
+    }
+}
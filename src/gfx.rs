@@ -1,4 +1,14 @@
-use bevy::{prelude::*, render::camera::ScalingMode::WindowSize, window::PrimaryWindow};
+use crate::helpers::tiled::TiledImageLayer;
+use crate::locale::Locale;
+use crate::pool::{InPool, Pool, PoolKind};
+use crate::settings::{Settings, SettingsChanged};
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::{
+    ecs::system::EntityCommands, prelude::*, render::camera::ScalingMode::WindowSize,
+    window::PrimaryWindow,
+};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::Duration;
 
@@ -6,28 +16,236 @@ pub struct GFXPlugin;
 
 impl Plugin for GFXPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, spawn_camera)
+        app.init_resource::<ScreenFade>()
+            .init_resource::<GraphicsSettings>()
+            .add_event::<SettingsChanged>()
+            .add_event::<SpawnBurst>()
+            .add_event::<SpawnFloatingText>()
+            .add_event::<ShowEmote>()
+            .init_resource::<ZoomLimits>()
+            .init_resource::<CameraTarget>()
+            .add_event::<SetZoom>()
+            .init_resource::<CameraShake>()
+            .add_event::<ShakeCamera>()
+            .add_systems(Startup, (spawn_camera, spawn_screen_fade))
+            .add_systems(PreUpdate, apply_graphics_settings)
             .add_systems(
-                FixedUpdate,
+                Update,
                 (
-                    update_animations,
+                    update_screen_fade,
+                    apply_graphics_quality,
+                    apply_color_filter,
                     update_sprite_scaling,
+                    apply_zoom.run_if(on_event::<SetZoom>()),
+                    spawn_bursts.run_if(on_event::<SpawnBurst>()),
+                    show_emotes
+                        .run_if(on_event::<ShowEmote>())
+                        .before(spawn_floating_texts),
+                    spawn_floating_texts.run_if(on_event::<SpawnFloatingText>()),
+                    update_floating_texts
+                        .after(spawn_floating_texts)
+                        .in_set(GameplaySet),
+                    (follow_camera_target, shake_camera)
+                        .chain()
+                        .in_set(GameplaySet),
+                    update_flash_tints.in_set(GameplaySet),
                 ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (apply_animation_states, update_animations)
+                    .chain()
+                    .in_set(FixedSet::PostPhysics)
+                    .in_set(GameplaySet),
             );
     }
 }
 
+///
+/// Quality
+///
+/// How much the renderer smooths edges: Low turns antialiasing off for slow machines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Quality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+impl Quality {
+    pub fn msaa(self) -> Msaa {
+        match self {
+            Quality::Low => Msaa::Off,
+            Quality::Medium => Msaa::Sample2,
+            Quality::High => Msaa::Sample4,
+        }
+    }
+}
+
+///
+/// ColorFilter
+///
+/// Tints the whole screen to keep colors apart for players with color blindness
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorFilter {
+    #[default]
+    None,
+    Protanopia,
+    Deuteranopia,
+    Tritanopia,
+}
+
+impl ColorFilter {
+    /// The tint laid over the screen, None for no filter
+    pub fn tint(self) -> Option<Color> {
+        match self {
+            ColorFilter::None => None,
+            ColorFilter::Protanopia => Some(Color::rgba(0.0, 0.4, 1.0, 0.12)),
+            ColorFilter::Deuteranopia => Some(Color::rgba(1.0, 0.0, 0.6, 0.12)),
+            ColorFilter::Tritanopia => Some(Color::rgba(1.0, 0.2, 0.0, 0.12)),
+        }
+    }
+}
+
+///
+/// GraphicsSettings
+///
+/// The graphics part of Settings
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub quality: Quality,
+    pub color_filter: ColorFilter,
+}
+
+///
+/// apply_graphics_settings: Bevy system
+///
+/// Takes the graphics options from Settings when they change
+pub fn apply_graphics_settings(
+    mut events: EventReader<SettingsChanged>,
+    settings: Option<Res<Settings>>,
+    mut graphics: ResMut<GraphicsSettings>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    if let Some(settings) = settings.filter(|settings| settings.graphics != *graphics) {
+        *graphics = settings.graphics.clone();
+    }
+}
+
+///
+/// apply_graphics_quality: Bevy system
+///
+/// Sets antialiasing from GraphicsSettings::quality
+pub fn apply_graphics_quality(graphics: Res<GraphicsSettings>, msaa: Option<ResMut<Msaa>>) {
+    let Some(mut msaa) = msaa else {
+        return;
+    };
+    let wanted = graphics.quality.msaa();
+    if *msaa != wanted {
+        *msaa = wanted;
+    }
+}
+
+/// Screen-wide tint of GraphicsSettings::color_filter
+#[derive(Component)]
+pub struct ColorFilterOverlay;
+
+///
+/// apply_color_filter: Bevy system
+///
+/// Keeps a ColorFilterOverlay over the screen matching GraphicsSettings::color_filter
+pub fn apply_color_filter(
+    mut commands: Commands,
+    graphics: Res<GraphicsSettings>,
+    overlay_query: Query<Entity, With<ColorFilterOverlay>>,
+) {
+    if !graphics.is_changed() {
+        return;
+    }
+    for overlay in overlay_query.iter() {
+        commands.entity(overlay).despawn_recursive();
+    }
+    let Some(tint) = graphics.color_filter.tint() else {
+        return;
+    };
+    commands.spawn((
+        ColorFilterOverlay,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: tint.into(),
+            // under the screen fade
+            z_index: ZIndex::Global(i32::MAX - 1),
+            ..default()
+        },
+    ));
+}
+
 /// Important: this is the sprite size before window scaling is applied
 pub const SPRITE_SIZE: f32 = 1.0;
 
+///
+/// SpriteLayer
+///
+/// Depth bands, bottom to top. Actors go at `SpriteLayer::Actors.z()`; map layers are put in a
+/// band with LayerDepths or a `z_band` property, and keep their drawing order within it.
+/// * Background: map layers without a band, at their index in drawing order
+/// * BelowActors: what actors walk over
+/// * Actors: the player, NPCs and enemies
+/// * AboveActors: what actors walk under, e.g. canopies and roofs
+/// * Overlay: above everything in the world, e.g. weather
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SpriteLayer {
+    Background,
+    BelowActors,
+    Actors,
+    AboveActors,
+    Overlay,
+}
+
+impl SpriteLayer {
+    /// Leaves room for 100 map layers in each band
+    pub fn z(self) -> f32 {
+        match self {
+            SpriteLayer::Background => 0.0,
+            SpriteLayer::BelowActors => 100.0,
+            SpriteLayer::Actors => 200.0,
+            SpriteLayer::AboveActors => 300.0,
+            SpriteLayer::Overlay => 400.0,
+        }
+    }
+
+    /// The band called `name` in snake case, e.g. "above_actors"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "background" => Some(SpriteLayer::Background),
+            "below_actors" => Some(SpriteLayer::BelowActors),
+            "actors" => Some(SpriteLayer::Actors),
+            "above_actors" => Some(SpriteLayer::AboveActors),
+            "overlay" => Some(SpriteLayer::Overlay),
+            _ => None,
+        }
+    }
+}
+
 ///
 /// update_sprite_scaling: Bevy system
 ///
 /// Updates sprite scaling for each Sprite if the window changes
 pub fn update_sprite_scaling(
-    mut sprites_query: Query<&mut Sprite>,
+    // image layers are sized from the map
+    mut sprites_query: Query<&mut Sprite, Without<TiledImageLayer>>,
     window_query: Query<&Window, (With<PrimaryWindow>, Changed<Window>)>,
 ) {
+    let _span = crate::profiling::span("update_sprite_scaling");
     if window_query.is_empty() {
         return;
     }
@@ -45,17 +263,19 @@ pub fn update_sprite_scaling(
 /// * Once: plays once and stops on the last frame
 /// * Repeat: loops indefinitely
 /// * Despawn: despawns the entity on completion
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum AnimationType {
     Once,
     Repeat,
     Despawn,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone)]
 pub struct Animation {
     index: usize,
-    atlas: Handle<TextureAtlas>,
+    atlas: Handle<TextureAtlasLayout>,
+    // the sheet the atlas cuts up, for animations spawned on their own sprite
+    texture: Handle<Image>,
     frames: Vec<usize>,
     timer: Timer,
     animation_type: AnimationType,
@@ -64,7 +284,7 @@ pub struct Animation {
 
 impl Animation {
     pub fn new(
-        atlas: Handle<TextureAtlas>,
+        atlas: Handle<TextureAtlasLayout>,
         frames: Vec<usize>,
         frame_time: f32,
         animation_type: AnimationType,
@@ -72,6 +292,7 @@ impl Animation {
         Animation {
             index: 0,
             atlas,
+            texture: Handle::default(),
             frames,
             timer: Timer::from_seconds(frame_time, TimerMode::Once),
             animation_type,
@@ -79,6 +300,20 @@ impl Animation {
         }
     }
 
+    /// Sets the sprite sheet the animation's atlas is laid over
+    pub fn with_texture(mut self, texture: Handle<Image>) -> Self {
+        self.texture = texture;
+        self
+    }
+
+    pub fn atlas(&self) -> &Handle<TextureAtlasLayout> {
+        &self.atlas
+    }
+
+    pub fn texture(&self) -> &Handle<Image> {
+        &self.texture
+    }
+
     fn advance_frame(&mut self) {
         if self.animation_type.eq(&AnimationType::Repeat) {
             self.index = (self.index + 1) % self.frames.len();
@@ -104,6 +339,16 @@ impl Animation {
         self.frames[self.index].clone()
     }
 
+    /// Index of the frame currently shown
+    pub fn frame(&self) -> usize {
+        self.frames[self.index]
+    }
+
+    /// Indices of every frame, in order
+    pub fn frames(&self) -> &[usize] {
+        &self.frames
+    }
+
     pub fn get_type(&self) -> AnimationType {
         self.animation_type.clone()
     }
@@ -134,14 +379,27 @@ impl AnimationResource {
     pub fn get(&self, name: &str) -> Option<Animation> {
         self.map.get(name).cloned()
     }
+
+    /// Remove an Animation, returning it
+    pub fn remove(&mut self, name: &str) -> Option<Animation> {
+        self.map.remove(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Animation)> {
+        self.map
+            .iter()
+            .map(|(name, animation)| (name.as_str(), animation))
+    }
 }
 
 pub fn update_animations(
     mut commands: Commands,
     time: Res<Time>,
-    mut query: Query<(Entity, &mut TextureAtlas, &mut Animation)>,
+    mut pool: Option<ResMut<Pool<BurstParticle>>>,
+    mut query: Query<(Entity, &mut TextureAtlas, &mut Animation, Has<BurstParticle>)>,
 ) {
-    for (entity, mut sprite, mut animation) in query.iter_mut() {
+    let _span = crate::profiling::span("update_animations");
+    for (entity, mut sprite, mut animation, pooled) in query.iter_mut() {
         let next_index = animation.tick(time.delta_seconds());
         if next_index.ne(&sprite.index) {
             sprite.index = next_index;
@@ -152,26 +410,468 @@ pub fn update_animations(
                 AnimationType::Once => {
                     commands.entity(entity).remove::<Animation>();
                 }
-                AnimationType::Despawn => {
-                    commands.entity(entity).despawn();
-                }
+                AnimationType::Despawn => match pool.as_deref_mut().filter(|_| pooled) {
+                    Some(pool) => pool.release(&mut commands, entity),
+                    None => commands.entity(entity).despawn(),
+                },
                 _ => {}
             }
         }
     }
 }
 
+///
+/// AnimationController
+///
+/// Named clips for an entity's Animation, e.g. "idle" and "walk", switched with set_state. A clip
+/// starts over when its state is switched to, and carries on when the state is set again.
+#[derive(Component, Clone, Default)]
+pub struct AnimationController {
+    clips: HashMap<String, Animation>,
+    state: String,
+    switched: bool,
+}
+
+impl AnimationController {
+    pub fn new() -> Self {
+        AnimationController::default()
+    }
+
+    /// Adds the clip played in `state`. The first clip's state is the one to start in.
+    pub fn with_clip(mut self, state: impl Into<String>, clip: Animation) -> Self {
+        let state = state.into();
+        if self.clips.is_empty() {
+            self.state = state.clone();
+            self.switched = true;
+        }
+        self.clips.insert(state, clip);
+        self
+    }
+
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    /// Switches to `state`, if there's a clip for it
+    pub fn set_state(&mut self, state: &str) {
+        if self.state != state && self.clips.contains_key(state) {
+            self.state = state.to_string();
+            self.switched = true;
+        }
+    }
+}
+
+///
+/// apply_animation_states: Bevy system
+///
+/// Starts the clip of an AnimationController's state once it's switched to
+pub fn apply_animation_states(
+    mut commands: Commands,
+    mut query: Query<
+        (Entity, &mut AnimationController, &mut TextureAtlas, &mut Handle<Image>),
+        Changed<AnimationController>,
+    >,
+) {
+    for (entity, mut controller, mut atlas, mut texture) in query.iter_mut() {
+        if !controller.switched {
+            continue;
+        }
+        controller.bypass_change_detection().switched = false;
+        let Some(clip) = controller.clips.get(&controller.state).cloned() else {
+            continue;
+        };
+        atlas.layout = clip.atlas().clone();
+        atlas.index = clip.frame();
+        if *texture != *clip.texture() {
+            *texture = clip.texture().clone();
+        }
+        commands.entity(entity).insert(clip);
+    }
+}
+
+/// Marks a pooled particle: a sprite playing an AnimationType::Despawn animation, given back to
+/// the Pool<BurstParticle> when it finishes instead of being despawned
+#[derive(Debug, Default, Component)]
+pub struct BurstParticle;
+
+impl PoolKind for BurstParticle {
+    const NAME: &'static str = "particles";
+
+    fn reset(entity: &mut EntityCommands) {
+        entity.remove::<Animation>();
+    }
+}
+
+/// Spawns a sprite playing the AnimationResource animation called `animation` at `position`,
+/// e.g. a hit spark. The animation should be AnimationType::Despawn, so particles don't pile up.
+#[derive(Event, Debug, Clone)]
+pub struct SpawnBurst {
+    pub animation: String,
+    pub position: Vec2,
+}
+
+/// Drawn above the map's layers
+const BURST_Z: f32 = 50.0;
+
+///
+/// spawn_bursts: Bevy system
+///
+/// Handles SpawnBurst, on pooled entities when there's a Pool<BurstParticle>
+pub fn spawn_bursts(
+    mut commands: Commands,
+    mut events: EventReader<SpawnBurst>,
+    animations: Option<Res<AnimationResource>>,
+    mut pool: Option<ResMut<Pool<BurstParticle>>>,
+) {
+    for event in events.read() {
+        let Some(animation) = animations
+            .as_ref()
+            .and_then(|animations| animations.get(&event.animation))
+        else {
+            warn!("Animation not found: {}", event.animation);
+            continue;
+        };
+        let bundle = (
+            SpriteSheetBundle {
+                texture: animation.texture().clone(),
+                atlas: TextureAtlas {
+                    layout: animation.atlas().clone(),
+                    index: animation.frame(),
+                },
+                transform: Transform::from_translation(event.position.extend(BURST_Z)),
+                ..default()
+            },
+            animation,
+        );
+        match pool.as_deref_mut() {
+            Some(pool) => {
+                pool.spawn_pooled(&mut commands, bundle);
+            }
+            None => {
+                commands.spawn((bundle, BurstParticle));
+            }
+        }
+    }
+}
+
+/// How long floating text takes to rise FLOATING_TEXT_RISE world units and fade out, in seconds
+pub const FLOATING_TEXT_TIME: f32 = 0.8;
+pub const FLOATING_TEXT_RISE: f32 = 1.5;
+/// Font size of floating text in pixels
+const FLOATING_TEXT_SIZE: f32 = 16.0;
+/// Drawn above bursts
+const FLOATING_TEXT_Z: f32 = 60.0;
+
+/// Spawns text at `position` that rises and fades out, e.g. a damage number
+/// * text: what's shown, or the Locale key of what's shown when `args` is set
+/// * args: the values filling in the localized text's placeholders, None for text as it is
+#[derive(Event, Debug, Clone)]
+pub struct SpawnFloatingText {
+    pub text: String,
+    pub args: Option<Vec<(String, String)>>,
+    pub position: Vec2,
+    pub color: Color,
+}
+
+impl SpawnFloatingText {
+    pub fn new(text: impl Into<String>, position: Vec2) -> Self {
+        SpawnFloatingText {
+            text: text.into(),
+            args: None,
+            position,
+            color: Color::WHITE,
+        }
+    }
+
+    /// Shows the text of `key` in the active language
+    pub fn localized(key: impl Into<String>, position: Vec2) -> Self {
+        SpawnFloatingText {
+            args: Some(Vec::new()),
+            ..SpawnFloatingText::new(key, position)
+        }
+    }
+
+    /// Fills in the localized text's `{name}` with `value`
+    pub fn with_arg(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.args
+            .get_or_insert_with(Vec::new)
+            .push((name.into(), value.to_string()));
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// Marks floating text, with how long it has been showing. Pooled in a Pool<FloatingText>.
+#[derive(Debug, Default, Component)]
+pub struct FloatingText {
+    pub elapsed: f32,
+}
+
+impl PoolKind for FloatingText {
+    const NAME: &'static str = "floating text";
+
+    // the next spawn replaces its text and transform
+    fn reset(_entity: &mut EntityCommands) {}
+}
+
+///
+/// spawn_floating_texts: Bevy system
+///
+/// Handles SpawnFloatingText, on pooled entities when there's a Pool<FloatingText>
+pub fn spawn_floating_texts(
+    mut commands: Commands,
+    mut events: EventReader<SpawnFloatingText>,
+    locale: Option<Res<Locale>>,
+    mut pool: Option<ResMut<Pool<FloatingText>>>,
+) {
+    for event in events.read() {
+        let text = match (&event.args, &locale) {
+            (Some(args), Some(locale)) => locale.format(&event.text, args),
+            _ => event.text.clone(),
+        };
+        let bundle = Text2dBundle {
+            text: Text::from_section(
+                text,
+                TextStyle {
+                    font_size: FLOATING_TEXT_SIZE,
+                    color: event.color,
+                    ..default()
+                },
+            ),
+            // the font size is in pixels, and the camera shows 16 of them per unit
+            transform: Transform::from_translation(event.position.extend(FLOATING_TEXT_Z))
+                .with_scale(Vec3::splat(1.0 / 16.0)),
+            ..default()
+        };
+        match pool.as_deref_mut() {
+            Some(pool) => {
+                pool.spawn_pooled(&mut commands, bundle);
+            }
+            None => {
+                commands.spawn((bundle, FloatingText::default()));
+            }
+        }
+    }
+}
+
+///
+/// update_floating_texts: Bevy system
+///
+/// Raises and fades floating text, releasing it to its pool (or despawning it) once it's gone
+pub fn update_floating_texts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pool: Option<ResMut<Pool<FloatingText>>>,
+    mut text_query: Query<(Entity, &mut FloatingText, &mut Transform, &mut Text), Without<InPool>>,
+) {
+    for (entity, mut floating, mut transform, mut text) in text_query.iter_mut() {
+        floating.elapsed += time.delta_seconds();
+        transform.translation.y += FLOATING_TEXT_RISE / FLOATING_TEXT_TIME * time.delta_seconds();
+        let t = (floating.elapsed / FLOATING_TEXT_TIME).min(1.0);
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(1.0 - t);
+        }
+        if t >= 1.0 {
+            match pool.as_deref_mut() {
+                Some(pool) => pool.release(&mut commands, entity),
+                None => commands.entity(entity).despawn(),
+            }
+        }
+    }
+}
+
+/// Tints a sprite `color` for `duration` seconds, fading back to the colour it had, e.g. a hit
+/// flash. Removed once it's done.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct FlashTint {
+    pub color: Color,
+    pub duration: f32,
+    elapsed: f32,
+    // the sprite's own colour, once the flash has started
+    original: Option<Color>,
+}
+
+impl FlashTint {
+    pub fn new(color: Color, duration: f32) -> Self {
+        FlashTint {
+            color,
+            duration,
+            elapsed: 0.0,
+            original: None,
+        }
+    }
+
+    /// Starts the flash over, from the colour the sprite had before it
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+///
+/// update_flash_tints: Bevy system
+///
+/// Fades FlashTint sprites back to their colour, and puts it back exactly when done
+pub fn update_flash_tints(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut FlashTint, &mut Sprite)>,
+) {
+    for (entity, mut flash, mut sprite) in query.iter_mut() {
+        let original = *flash.original.get_or_insert(sprite.color);
+        flash.elapsed += time.delta_seconds();
+        let t = (flash.elapsed / flash.duration.max(f32::EPSILON)).min(1.0);
+        if t >= 1.0 {
+            sprite.color = original;
+            commands.entity(entity).remove::<FlashTint>();
+            continue;
+        }
+        let [r, g, b, a] = flash.color.as_rgba_f32();
+        let [or, og, ob, oa] = original.as_rgba_f32();
+        let mix = |from: f32, to: f32| from + (to - from) * t;
+        sprite.color = Color::rgba(mix(r, or), mix(g, og), mix(b, ob), mix(a, oa));
+    }
+}
+
+/// What a character's emote indicator shows
+/// * Alert: it noticed something, e.g. an enemy seeing the player
+/// * Lost: it lost track of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emote {
+    Alert,
+    Lost,
+}
+
+impl Emote {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Emote::Alert => "!",
+            Emote::Lost => "?",
+        }
+    }
+}
+
+/// How far above its entity an emote indicator pops up
+pub const EMOTE_OFFSET: f32 = 12.0;
+
+/// Pops `emote`'s indicator up over `entity`, as floating text
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShowEmote {
+    pub entity: Entity,
+    pub emote: Emote,
+}
+
+///
+/// show_emotes: Bevy system
+///
+/// Handles ShowEmote
+pub fn show_emotes(
+    mut events: EventReader<ShowEmote>,
+    transform_query: Query<&GlobalTransform>,
+    mut texts: EventWriter<SpawnFloatingText>,
+) {
+    for event in events.read() {
+        let Ok(transform) = transform_query.get(event.entity) else {
+            continue;
+        };
+        let position = transform.translation().truncate() + Vec2::Y * EMOTE_OFFSET;
+        texts.send(SpawnFloatingText::new(event.emote.symbol(), position));
+    }
+}
+
 #[derive(Debug, Component)]
 pub struct MainCamera {}
 
+/// Window pixels per game unit, at a zoom of 1
+pub const PIXELS_PER_UNIT: f32 = 16.0;
+
+///
+/// ZoomLimits
+///
+/// How far SetZoom takes the main camera, as its projection scale: above 1 shows more of the
+/// world, below 1 less
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct ZoomLimits {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for ZoomLimits {
+    fn default() -> Self {
+        ZoomLimits { min: 0.5, max: 2.0 }
+    }
+}
+
+/// Zooms the main camera, within ZoomLimits
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub enum SetZoom {
+    /// to this projection scale
+    To(f32),
+    /// multiplying the projection scale by this: below 1 zooms in
+    By(f32),
+}
+
+/// The part of the main camera's translation that comes from screen shake. Whatever shakes the
+/// camera keeps this up to date so systems following the camera can use its steady position.
+#[derive(Debug, Default, Component)]
+pub struct CameraShakeOffset(pub Vec2);
+
+/// Shakes the main camera by up to `strength` world units, dying down over `duration` seconds.
+/// One weaker than the shake going on leaves it be.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct ShakeCamera {
+    pub strength: f32,
+    pub duration: f32,
+}
+
+///
+/// CameraShake
+///
+/// The main camera's shake going on, if any, and the seconds it's been going
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct CameraShake {
+    pub shake: Option<ShakeCamera>,
+    pub elapsed: f32,
+}
+
+impl CameraShake {
+    /// How far it shakes the camera now, 0 once it's over
+    pub fn strength(&self) -> f32 {
+        match self.shake {
+            Some(shake) if self.elapsed < shake.duration => {
+                shake.strength * (1.0 - self.elapsed / shake.duration)
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// How fast a shake wobbles, in radians a second
+const SHAKE_FREQUENCY: f32 = 40.0;
+
+///
+/// CameraTarget
+///
+/// The entity the MainCamera follows, if any, e.g. the player
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CameraTarget(pub Option<Entity>);
+
+/// How quickly the camera catches up with its target: it covers 1 - e^-rate of the way left
+/// each second
+pub const CAMERA_FOLLOW_RATE: f32 = 8.0;
+
 pub fn spawn_camera(mut commands: Commands) {
     commands.spawn((
         MainCamera {},
+        CameraShakeOffset::default(),
         Camera2dBundle {
             projection: OrthographicProjection {
                 near: -1000.0,
                 far: 1000.0,
-                scaling_mode: WindowSize(16.0), // 16 pixels per game unit
+                scaling_mode: WindowSize(PIXELS_PER_UNIT),
                 ..default()
             },
             camera: Camera {
@@ -182,3 +882,182 @@ pub fn spawn_camera(mut commands: Commands) {
         },
     ));
 }
+
+///
+/// follow_camera_target: Bevy system
+///
+/// Eases the MainCamera towards the CameraTarget on game time, leaving its shake on top
+#[allow(clippy::type_complexity)]
+pub fn follow_camera_target(
+    time: Res<Time>,
+    target: Res<CameraTarget>,
+    target_query: Query<&GlobalTransform, Without<MainCamera>>,
+    mut camera_query: Query<(&mut Transform, Option<&CameraShakeOffset>), With<MainCamera>>,
+) {
+    let Some(goal) = target
+        .0
+        .and_then(|entity| target_query.get(entity).ok())
+        .map(|transform| transform.translation().truncate())
+    else {
+        return;
+    };
+    let t = 1.0 - (-CAMERA_FOLLOW_RATE * time.delta_seconds()).exp();
+    for (mut transform, shake) in camera_query.iter_mut() {
+        let shake = shake.map_or(Vec2::ZERO, |shake| shake.0);
+        let steady = transform.translation.truncate() - shake;
+        let moved = steady.lerp(goal, t) + shake;
+        if moved != transform.translation.truncate() {
+            transform.translation = moved.extend(transform.translation.z);
+        }
+    }
+}
+
+///
+/// shake_camera: Bevy system
+///
+/// Starts shakes from ShakeCamera and moves the MainCamera off its steady position by the one
+/// going on, on game time, keeping its CameraShakeOffset up to date
+pub fn shake_camera(
+    time: Res<Time>,
+    mut events: EventReader<ShakeCamera>,
+    mut shake: ResMut<CameraShake>,
+    mut camera_query: Query<(&mut Transform, &mut CameraShakeOffset), With<MainCamera>>,
+) {
+    for event in events.read() {
+        if event.strength >= shake.strength() && event.duration > 0.0 {
+            *shake = CameraShake {
+                shake: Some(*event),
+                elapsed: 0.0,
+            };
+        }
+    }
+    if shake.shake.is_none() {
+        return;
+    }
+    shake.elapsed += time.delta_seconds();
+    let strength = shake.strength();
+    if strength <= 0.0 {
+        shake.shake = None;
+    }
+    // two wobbles out of step, so it doesn't shake along a line
+    let t = shake.elapsed * SHAKE_FREQUENCY;
+    let offset = Vec2::new(t.sin(), (t * 1.37 + 1.0).sin()) * strength;
+    for (mut transform, mut current) in camera_query.iter_mut() {
+        transform.translation += (offset - current.0).extend(0.0);
+        current.0 = offset;
+    }
+}
+
+///
+/// apply_zoom: Bevy system
+///
+/// Sets the main camera's projection scale from SetZoom, kept within ZoomLimits
+pub fn apply_zoom(
+    mut events: EventReader<SetZoom>,
+    limits: Res<ZoomLimits>,
+    mut camera_query: Query<&mut OrthographicProjection, With<MainCamera>>,
+) {
+    let zooms: Vec<SetZoom> = events.read().copied().collect();
+    for mut projection in camera_query.iter_mut() {
+        let scale = zooms.iter().fold(projection.scale, |scale, zoom| match *zoom {
+            SetZoom::To(to) => to,
+            SetZoom::By(factor) => scale * factor,
+        });
+        let scale = scale.clamp(limits.min, limits.max);
+        if scale.is_finite() && projection.scale != scale {
+            projection.scale = scale;
+        }
+    }
+}
+
+///
+/// ScreenFade
+///
+/// A black overlay over everything for transitions: 0 is clear, 1 is black. Fades run on real
+/// time, so they keep going while the game is paused.
+#[derive(Resource, Debug, Default)]
+pub struct ScreenFade {
+    alpha: f32,
+    target: f32,
+    // alpha per second
+    speed: f32,
+}
+
+impl ScreenFade {
+    /// Fades to black over `seconds`
+    pub fn fade_out(&mut self, seconds: f32) {
+        self.fade_to(1.0, seconds);
+    }
+
+    /// Fades back to clear over `seconds`
+    pub fn fade_in(&mut self, seconds: f32) {
+        self.fade_to(0.0, seconds);
+    }
+
+    fn fade_to(&mut self, target: f32, seconds: f32) {
+        self.target = target;
+        if seconds <= 0.0 {
+            self.alpha = target;
+        } else {
+            self.speed = 1.0 / seconds;
+        }
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// Whether the last fade has finished
+    pub fn is_done(&self) -> bool {
+        self.alpha == self.target
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        let step = self.speed * delta;
+        self.alpha = if self.alpha < self.target {
+            (self.alpha + step).min(self.target)
+        } else {
+            (self.alpha - step).max(self.target)
+        };
+    }
+}
+
+/// Marks the UI node drawing the ScreenFade
+#[derive(Debug, Component)]
+pub struct ScreenFadeOverlay;
+
+pub fn spawn_screen_fade(mut commands: Commands) {
+    commands.spawn((
+        ScreenFadeOverlay,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                ..default()
+            },
+            background_color: Color::NONE.into(),
+            z_index: ZIndex::Global(i32::MAX),
+            ..default()
+        },
+    ));
+}
+
+///
+/// update_screen_fade: Bevy system
+///
+/// Advances the ScreenFade and shows it on the overlay
+pub fn update_screen_fade(
+    time: Res<Time<Real>>,
+    mut fade: ResMut<ScreenFade>,
+    mut overlay_query: Query<&mut BackgroundColor, With<ScreenFadeOverlay>>,
+) {
+    if !fade.is_done() {
+        fade.tick(time.delta_seconds());
+    }
+    for mut color in overlay_query.iter_mut() {
+        if color.0.a() != fade.alpha() {
+            color.0 = Color::rgba(0.0, 0.0, 0.0, fade.alpha());
+        }
+    }
+}
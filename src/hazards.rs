@@ -0,0 +1,172 @@
+//! Hazard tiles: spikes, lava and poison pools, tiles with a `damage` property. Each step,
+//! anything with an AabbCollider and Health overlapping one takes its Damage, then not again
+//! until `damage_interval` has gone by, so standing in lava hurts every so often rather than
+//! all at once. The player hitting one also plays the sound of its `hazard_type` and shakes the
+//! camera. Those with HazardImmune, e.g. enemies flying over spikes, aren't hurt.
+//!
+//! Tile properties:
+//! * damage: how much it hurts
+//! * damage_interval: seconds before it hurts the same thing again, DEFAULT_DAMAGE_INTERVAL if
+//!   unset
+//! * hazard_type: what it is, e.g. "spikes", and the sound it hurts the player with, HAZARD_SOUND
+//!   if unset
+//! * knockback: how hard it pushes what it hurts away from its centre, in world units a second;
+//!   SPIKE_KNOCKBACK for spikes and none for the rest if unset
+
+use crate::gfx::ShakeCamera;
+use crate::health::{Damage, Dying, Health};
+use crate::helpers::tiled::{TileLookup, TileProperties, TiledLayer};
+use crate::physics::AabbCollider;
+use crate::player::Player;
+use crate::sound::PlaySFX;
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+/// Seconds between a hazard's hurts when its tile doesn't say
+pub const DEFAULT_DAMAGE_INTERVAL: f32 = 0.5;
+/// The hazard_type of spikes, which knock back by default
+pub const SPIKES: &str = "spikes";
+/// How hard spikes push what they hurt when their tile doesn't say
+pub const SPIKE_KNOCKBACK: f32 = 160.0;
+/// Sound played when a hazard without a hazard_type hurts the player
+pub const HAZARD_SOUND: &str = "hazard";
+/// How hard and long hazards hurting the player shake the camera
+pub const HAZARD_SHAKE: ShakeCamera = ShakeCamera {
+    strength: 3.0,
+    duration: 0.25,
+};
+
+/// What a hazard tile does, from its properties
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hazard {
+    pub damage: i32,
+    pub interval: f32,
+    pub hazard_type: Option<String>,
+    pub knockback: f32,
+}
+
+impl Hazard {
+    /// The hazard of a tile with these properties, None if it isn't one
+    pub fn from_properties(properties: &TileProperties) -> Option<Self> {
+        let damage = properties.get_int("damage")?;
+        let hazard_type = properties.get_string("hazard_type").map(str::to_string);
+        let knockback = match hazard_type.as_deref() {
+            Some(SPIKES) => SPIKE_KNOCKBACK,
+            _ => 0.0,
+        };
+        Some(Hazard {
+            damage,
+            interval: properties
+                .get_float("damage_interval")
+                .unwrap_or(DEFAULT_DAMAGE_INTERVAL),
+            knockback: properties.get_float("knockback").unwrap_or(knockback),
+            hazard_type,
+        })
+    }
+
+    /// The sound it hurts the player with
+    pub fn sound(&self) -> &str {
+        self.hazard_type.as_deref().unwrap_or(HAZARD_SOUND)
+    }
+}
+
+/// Keeps hazard tiles from hurting an entity
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct HazardImmune;
+
+/// Seconds before hazards can hurt an entity again
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct HazardCooldown(pub f32);
+
+/// Hazard tiles and the damage they deal
+pub struct HazardsPlugin;
+
+impl Plugin for HazardsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<Damage>()
+            .add_event::<PlaySFX>()
+            .add_event::<ShakeCamera>()
+            .add_systems(
+                FixedUpdate,
+                hurt_on_hazards
+                    .in_set(FixedSet::PostPhysics)
+                    .in_set(GameplaySet),
+            );
+    }
+}
+
+///
+/// hurt_on_hazards: Bevy system
+///
+/// Damages what stands on hazard tiles, once every damage_interval, the worst hazard it touches
+/// counting
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn hurt_on_hazards(
+    mut commands: Commands,
+    time: Res<Time>,
+    lookup: Res<TileLookup>,
+    tilemap_query: Query<&TileStorage, With<TiledLayer>>,
+    tile_query: Query<&TileProperties>,
+    mut query: Query<
+        (
+            Entity,
+            &AabbCollider,
+            &GlobalTransform,
+            Option<&mut HazardCooldown>,
+            Has<Player>,
+        ),
+        (With<Health>, Without<HazardImmune>, Without<Dying>),
+    >,
+    mut damage: EventWriter<Damage>,
+    mut sfx: EventWriter<PlaySFX>,
+    mut shake: EventWriter<ShakeCamera>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, collider, transform, mut cooldown, player) in query.iter_mut() {
+        if let Some(cooldown) = cooldown.as_mut() {
+            cooldown.0 -= delta;
+            if cooldown.0 > 0.0 {
+                continue;
+            }
+        }
+        let position = transform.translation().truncate();
+        let worst = lookup
+            .tiles_in_rect(collider.rect(position))
+            .flat_map(|pos| {
+                tilemap_query
+                    .iter()
+                    .filter_map(move |storage| storage.get(&pos).map(|tile| (tile, pos)))
+            })
+            .filter_map(|(tile, pos)| {
+                let hazard = Hazard::from_properties(tile_query.get(tile).ok()?)?;
+                Some((hazard, pos))
+            })
+            .max_by_key(|(hazard, _)| hazard.damage);
+        let Some((hazard, pos)) = worst else {
+            continue;
+        };
+
+        let mut event = Damage::new(entity, hazard.damage);
+        if hazard.knockback > 0.0 {
+            let away = (position - lookup.tile_to_world(pos))
+                .try_normalize()
+                .unwrap_or(Vec2::Y);
+            event = event.with_knockback(away * hazard.knockback);
+        }
+        damage.send(event);
+        match cooldown {
+            Some(mut cooldown) => cooldown.0 = hazard.interval,
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(HazardCooldown(hazard.interval));
+            }
+        }
+        if player {
+            sfx.send(PlaySFX::at(hazard.sound(), position));
+            shake.send(HAZARD_SHAKE);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+//! Health and damage: a Damage event takes health off an entity with Health, flashing it red
+//! (FlashTint), playing HURT_SOUND and showing the amount rising from it. At zero health it's
+//! Dying: its Velocity, AabbCollider and AnimationController go, Died is sent for loot and
+//! score, and it plays its DeathAnimation, then is despawned. Without one it's despawned at the
+//! end of the frame, once Died has been handled. The player isn't despawned, staying Dying for
+//! what comes after. Damage to Dying entities is ignored, so dying twice in one step doesn't drop
+//! loot twice.
+//!
+//! Damage with a knockback pushes what it hurts (Knockback), and something with Invulnerability
+//! can't be hurt again for its `duration` after a hit, blinking meanwhile. The player has
+//! PLAYER_INVULNERABILITY; enemies go without unless their archetype gives them some, so rapid
+//! fire hits them every time.
+//!
+//! The player gets Health from GameProgress on spawning, PLAYER_MAX_HEALTH when the game hasn't
+//! set it, and keeps GameProgress::health and max_health (the HUD's hearts) up to date. The
+//! player dying is game over, unless it has reached a checkpoint to come back to.
+
+use crate::gfx::{
+    update_flash_tints, Animation, AnimationController, AnimationResource, FlashTint,
+    SpawnFloatingText,
+};
+use crate::physics::{AabbCollider, Knockback, Velocity};
+use crate::player::Player;
+use crate::save::GameProgress;
+use crate::sound::PlaySFX;
+use crate::state::{AppState, ChangeState, GameplaySet};
+use crate::timestep::FixedSet;
+use bevy::prelude::*;
+
+/// Sound played when something takes damage
+pub const HURT_SOUND: &str = "hurt";
+/// The AnimationResource animation the player dies with
+pub const PLAYER_DEATH_ANIMATION: &str = "player_death";
+/// The player's health when the game hasn't set it
+pub const PLAYER_MAX_HEALTH: i32 = 5;
+/// Colour and seconds of the flash on taking damage
+pub const HIT_FLASH: Color = Color::rgb(1.0, 0.3, 0.3);
+pub const HIT_FLASH_TIME: f32 = 0.15;
+/// Seconds the player can't be hurt for after a hit
+pub const PLAYER_INVULNERABILITY: f32 = 1.0;
+/// How many times a second something Invulnerable blinks on or off
+pub const BLINK_RATE: f32 = 10.0;
+/// Colour of the damage numbers
+const DAMAGE_NUMBER_COLOR: Color = Color::rgb(1.0, 0.4, 0.4);
+
+/// How much damage an entity can take, `current` out of `max`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Health {
+    pub current: i32,
+    pub max: i32,
+}
+
+impl Health {
+    pub fn new(max: i32) -> Self {
+        Health { current: max, max }
+    }
+
+    /// Adds `amount`, up to max
+    pub fn heal(&mut self, amount: i32) {
+        self.current = (self.current + amount).clamp(0, self.max);
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0
+    }
+}
+
+/// Makes an entity Invulnerable for `duration` seconds after each hit
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Invulnerability {
+    pub duration: f32,
+}
+
+impl Invulnerability {
+    pub fn new(duration: f32) -> Self {
+        Invulnerability { duration }
+    }
+}
+
+/// On an entity that was hit lately, ignoring Damage for `remaining` seconds and blinking
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Invulnerable {
+    pub remaining: f32,
+    elapsed: f32,
+    // the sprite's own alpha, put back once it's over
+    alpha: Option<f32>,
+}
+
+impl Invulnerable {
+    /// Whether its sprite is blinked off just now
+    pub fn hidden(&self) -> bool {
+        (self.elapsed * BLINK_RATE) as u32 % 2 == 1
+    }
+}
+
+/// Takes `amount` health off `target`
+/// * source: what dealt it, if anything
+/// * knockback: the push it gives, in world units a second
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct Damage {
+    pub target: Entity,
+    pub amount: i32,
+    pub source: Option<Entity>,
+    pub knockback: Option<Vec2>,
+}
+
+impl Damage {
+    pub fn new(target: Entity, amount: i32) -> Self {
+        Damage {
+            target,
+            amount,
+            source: None,
+            knockback: None,
+        }
+    }
+
+    pub fn from_source(mut self, source: Entity) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn with_knockback(mut self, knockback: Vec2) -> Self {
+        self.knockback = Some(knockback);
+        self
+    }
+}
+
+/// Sent when `entity` runs out of health, at `position`, e.g. to drop loot there
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct Died {
+    pub entity: Entity,
+    pub position: Vec2,
+}
+
+/// Marks an entity that ran out of health, playing its death
+#[derive(Component, Debug, Default)]
+pub struct Dying;
+
+/// The AnimationResource animation an entity plays when it dies
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct DeathAnimation(pub String);
+
+/// The side an entity fights on, e.g. so its projectiles don't hurt its own side. Neutral
+/// projectiles hurt both.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Player,
+    Enemy,
+    #[default]
+    Neutral,
+}
+
+impl Faction {
+    /// Whether what's of this faction may hurt what's of `other`
+    pub fn opposes(self, other: Faction) -> bool {
+        self != other
+    }
+}
+
+/// Health, Damage and Died, and the player's health
+pub struct HealthPlugin;
+
+impl Plugin for HealthPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameProgress>()
+            .add_event::<Damage>()
+            .add_event::<Died>()
+            .add_event::<PlaySFX>()
+            .add_event::<SpawnFloatingText>()
+            .add_event::<ChangeState>()
+            .add_systems(
+                Update,
+                (give_player_health, sync_player_health, end_game_on_death).chain(),
+            )
+            .add_systems(Last, despawn_dead)
+            .add_systems(
+                Update,
+                blink_invulnerable
+                    .after(update_flash_tints)
+                    .in_set(GameplaySet),
+            )
+            .add_systems(
+                FixedUpdate,
+                (wear_off_invulnerability.before(apply_damage), apply_damage)
+                    .in_set(FixedSet::PostPhysics)
+                    .in_set(GameplaySet),
+            );
+    }
+}
+
+///
+/// apply_damage: Bevy system
+///
+/// Handles Damage, killing what runs out of health and pushing and making Invulnerable what
+/// doesn't
+#[allow(clippy::type_complexity)]
+pub fn apply_damage(
+    mut commands: Commands,
+    mut damage_events: EventReader<Damage>,
+    animations: Option<Res<AnimationResource>>,
+    mut target_query: Query<
+        (
+            &mut Health,
+            Option<&GlobalTransform>,
+            Option<&DeathAnimation>,
+            Option<&mut FlashTint>,
+            Option<&Invulnerability>,
+            Option<&Sprite>,
+        ),
+        (Without<Dying>, Without<Invulnerable>),
+    >,
+    mut died: EventWriter<Died>,
+    mut sfx: EventWriter<PlaySFX>,
+    mut texts: EventWriter<SpawnFloatingText>,
+) {
+    // made Invulnerable this step, before the command inserting it has been applied
+    let mut invulnerable = Vec::new();
+    for event in damage_events.read() {
+        let Ok((mut health, transform, death, flash, invulnerability, sprite)) =
+            target_query.get_mut(event.target)
+        else {
+            continue;
+        };
+        // already killed this step
+        if health.is_dead() || event.amount <= 0 || invulnerable.contains(&event.target) {
+            continue;
+        }
+        health.current = (health.current - event.amount).max(0);
+        let position = transform.map_or(Vec2::ZERO, |transform| transform.translation().truncate());
+        match flash {
+            Some(mut flash) => flash.restart(),
+            None => {
+                commands
+                    .entity(event.target)
+                    .insert(FlashTint::new(HIT_FLASH, HIT_FLASH_TIME));
+            }
+        }
+        sfx.send(PlaySFX::at(HURT_SOUND, position));
+        texts.send(
+            SpawnFloatingText::new(event.amount.to_string(), position)
+                .with_color(DAMAGE_NUMBER_COLOR),
+        );
+        if !health.is_dead() {
+            let mut entity = commands.entity(event.target);
+            if let Some(knockback) = event.knockback {
+                entity.insert(Knockback(knockback));
+            }
+            if let Some(invulnerability) = invulnerability {
+                entity.insert(Invulnerable {
+                    remaining: invulnerability.duration,
+                    elapsed: 0.0,
+                    alpha: sprite.map(|sprite| sprite.color.a()),
+                });
+                invulnerable.push(event.target);
+            }
+            continue;
+        }
+
+        died.send(Died {
+            entity: event.target,
+            position,
+        });
+        let mut entity = commands.entity(event.target);
+        entity.insert(Dying).remove::<(
+            Velocity,
+            Knockback,
+            AabbCollider,
+            AnimationController,
+            Animation,
+        )>();
+        let animation = death.and_then(|death| {
+            animations
+                .as_ref()
+                .and_then(|animations| animations.get(&death.0))
+        });
+        if let Some(animation) = animation {
+            entity.insert((
+                TextureAtlas {
+                    layout: animation.atlas().clone(),
+                    index: animation.frame(),
+                },
+                animation.texture().clone(),
+                animation,
+            ));
+        }
+    }
+}
+
+///
+/// wear_off_invulnerability: Bevy system
+///
+/// Counts down Invulnerable, putting the sprite's alpha back once it's over
+pub fn wear_off_invulnerability(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Invulnerable, Option<&mut Sprite>)>,
+) {
+    for (entity, mut invulnerable, sprite) in query.iter_mut() {
+        invulnerable.elapsed += time.delta_seconds();
+        invulnerable.remaining -= time.delta_seconds();
+        if invulnerable.remaining > 0.0 {
+            continue;
+        }
+        if let (Some(mut sprite), Some(alpha)) = (sprite, invulnerable.alpha) {
+            sprite.color.set_a(alpha);
+        }
+        commands.entity(entity).remove::<Invulnerable>();
+    }
+}
+
+///
+/// blink_invulnerable: Bevy system
+///
+/// Blinks Invulnerable sprites, turning their alpha off and on BLINK_RATE times a second
+pub fn blink_invulnerable(mut query: Query<(&Invulnerable, &mut Sprite)>) {
+    for (invulnerable, mut sprite) in query.iter_mut() {
+        let Some(alpha) = invulnerable.alpha else {
+            continue;
+        };
+        let shown = if invulnerable.hidden() { 0.0 } else { alpha };
+        if sprite.color.a() != shown {
+            sprite.color.set_a(shown);
+        }
+    }
+}
+
+type NewPlayer = (With<Player>, Without<Health>, Without<Dying>);
+
+///
+/// give_player_health: Bevy system
+///
+/// Gives a new player the Health GameProgress says it has, its DeathAnimation and
+/// Faction::Player
+pub fn give_player_health(
+    mut commands: Commands,
+    progress: Res<GameProgress>,
+    player_query: Query<Entity, NewPlayer>,
+) {
+    for player in player_query.iter() {
+        let max = progress
+            .max_health
+            .or(progress.health)
+            .unwrap_or(PLAYER_MAX_HEALTH);
+        let current = progress.health.unwrap_or(max).clamp(0, max);
+        commands.entity(player).insert((
+            Health { current, max },
+            DeathAnimation(PLAYER_DEATH_ANIMATION.to_string()),
+            Faction::Player,
+        ));
+    }
+}
+
+///
+/// sync_player_health: Bevy system
+///
+/// Keeps GameProgress::health and max_health at the player's Health
+pub fn sync_player_health(
+    mut progress: ResMut<GameProgress>,
+    player_query: Query<&Health, (With<Player>, Changed<Health>)>,
+) {
+    for health in player_query.iter() {
+        if progress.health != Some(health.current) {
+            progress.health = Some(health.current);
+        }
+        if progress.max_health != Some(health.max) {
+            progress.max_health = Some(health.max);
+        }
+    }
+}
+
+///
+/// end_game_on_death: Bevy system
+///
+/// Goes to AppState::GameOver when the player dies without an active checkpoint
+pub fn end_game_on_death(
+    mut deaths: EventReader<Died>,
+    progress: Res<GameProgress>,
+    player_query: Query<(), With<Player>>,
+    mut change_state: EventWriter<ChangeState>,
+) {
+    let died = deaths
+        .read()
+        .any(|death| player_query.contains(death.entity));
+    if died && progress.checkpoint.is_none() {
+        change_state.send(ChangeState(AppState::GameOver));
+    }
+}
+
+// the player stays for the game over screen
+type DoneDying = (With<Dying>, Without<Animation>, Without<Player>);
+
+///
+/// despawn_dead: Bevy system
+///
+/// Despawns Dying entities done with their death, but the player
+pub fn despawn_dead(mut commands: Commands, dead_query: Query<Entity, DoneDying>) {
+    for entity in dead_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,1443 @@
+// Loads Tiled (.tmx) maps into bevy_ecs_tilemap layers.
+//
+// Based on the bevy_ecs_tilemap tiled example helper, without the `atlas` feature paths.
+//
+// Functional limitations:
+//   * Only tile layers (finite or infinite), image layers and object layers are loaded.
+//   * Staggered (non-hexagonal) isometric maps are placed with the orthogonal conversions.
+//   * Text objects on isometric maps aren't drawn, nor the text of templated text objects.
+//   * zstd compressed layer data needs the `zstd` feature, which doesn't build for wasm.
+
+use std::collections::BTreeMap;
+use std::io::{Cursor, ErrorKind};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AssetPath, AsyncReadExt, LoadContext, ReadAssetBytesError},
+    log,
+    prelude::*,
+    transform::TransformSystem,
+    utils::{BoxedFuture, HashMap},
+};
+use bevy_ecs_tilemap::prelude::*;
+
+use crate::state::GameplaySet;
+use thiserror::Error;
+
+mod animation;
+mod bounds;
+mod budget;
+mod collision;
+mod depth;
+mod doors;
+mod edit;
+mod image;
+mod layer_markers;
+mod layers;
+mod lifecycle;
+mod lookup;
+mod markers;
+mod minimap;
+mod nav;
+mod objects;
+mod parallax;
+mod properties;
+mod reload;
+mod spawn;
+mod stream;
+mod triggers;
+mod validate;
+mod world;
+
+pub use animation::{
+    animate_tiles, register_tile_animations, AnimatedTiledTile, TileAnimation, TileFrame,
+    TilemapAnimations, TilesetTile,
+};
+pub use bounds::MapBounds;
+pub use budget::{spawn_map_batches, MapSpawnBudget, MapSpawning, PendingTiles};
+
+#[cfg(feature = "dev")]
+pub use collision::CollisionDebug;
+pub use collision::{
+    ColliderShape, CollisionMap, ShapeCollider, TileCollider, COLLIDERS_LAYER, COLLIDES_PROPERTY,
+    COLLISION_LAYER, ONE_WAY_PROPERTY,
+};
+pub use depth::{LayerDepth, LayerDepths, Z_BAND_PROPERTY, Z_PROPERTY};
+pub use doors::{
+    advance_door_transition, change_map, restart_map, use_doors, ChangeMap, Door, DoorTransition,
+    RestartMap, DOOR_TYPE, INTERACT_KEY,
+};
+pub use edit::{apply_tile_edits, SetTile, TilemapSource};
+pub use image::{ImageLayerTexture, TiledImageLayer};
+pub use layer_markers::{apply_layer_markers, LayerMarkerRegistry, LayerName, RegisterLayerMarker};
+pub use layers::{
+    apply_layer_colors, set_layer_tint, set_layer_visibility, HiddenLayers, SetLayerTint,
+    SetLayerVisibility, TiledLayer,
+};
+pub use lifecycle::{handle_map_requests, CurrentMap, LoadMap, MapUnloaded, UnloadMap};
+pub use lookup::{sync_tile_lookup, TileLookup};
+pub use markers::{MapMarker, MapMarkers};
+pub use minimap::{update_minimap, MinimapSettings, MinimapTexture, MINIMAP_COLOR_PROPERTY};
+pub use nav::{
+    follow_paths, update_nav_grid, Connectivity, NavGrid, PathBlocked, PathCompleted, PathFollow,
+    PathOptions, COST_PROPERTY,
+};
+pub use objects::{
+    tmx_to_world, ObjectShape, ObjectSpawner, RegisterTiledObject, TiledObject,
+    TiledObjectRegistry, TiledText,
+};
+pub use parallax::{apply_parallax, LayerParallax};
+pub use properties::{
+    update_tile_properties, MapProperties, RegisterTileProperty, TileProperties,
+    TilePropertyRegistry, TilemapTileProperties,
+};
+pub use reload::{record_tile_edits, reload_maps, replay_tile_edits, ReloadMap, TileEditLog};
+pub use spawn::{
+    place_at_spawn_point, PlacedAtSpawn, SpawnPointName, SpawnPointReady, DEFAULT_SPAWN,
+};
+pub use stream::{stream_tile_chunks, StreamedTiles, TileStreaming};
+pub use triggers::{
+    track_triggers, TriggerEntered, TriggerExited, TriggerOccupancy, TriggerRegion, TriggerSensor,
+    TRIGGERS_LAYER,
+};
+pub use validate::{validate_map, validate_map_files, MapProblem, MapReport};
+pub use world::{
+    spawn_world_members, TiledWorld, TiledWorldBundle, TiledWorldLoader, TiledWorldLoaderError,
+    WorldMap, WorldMember, WorldMembers,
+};
+
+#[derive(Default)]
+pub struct TiledMapPlugin;
+
+impl Plugin for TiledMapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TiledMap>()
+            .register_asset_loader(TiledLoader)
+            .init_asset::<TiledWorld>()
+            .register_asset_loader(TiledWorldLoader)
+            .init_resource::<TiledObjectRegistry>()
+            .init_resource::<CollisionMap>()
+            .init_resource::<MapBounds>()
+            .init_resource::<CurrentMap>()
+            .init_resource::<MapMarkers>()
+            .init_resource::<MapProperties>()
+            .init_resource::<TileLookup>()
+            .init_resource::<NavGrid>()
+            .init_resource::<TriggerOccupancy>()
+            .init_resource::<DoorTransition>()
+            .init_resource::<HiddenLayers>()
+            .init_resource::<MapSpawnBudget>()
+            .init_resource::<TileEditLog>()
+            .init_resource::<MinimapSettings>()
+            .init_resource::<MinimapTexture>()
+            .add_event::<MapLoaded>()
+            .add_event::<ReloadMap>()
+            .add_event::<RestartMap>()
+            .add_event::<ChangeMap>()
+            .add_event::<MapUnloaded>()
+            .add_event::<LoadMap>()
+            .add_event::<UnloadMap>()
+            .add_event::<SetLayerVisibility>()
+            .add_event::<SetLayerTint>()
+            .add_event::<SpawnPointReady>()
+            .add_event::<SetTile>()
+            .add_event::<TriggerEntered>()
+            .add_event::<TriggerExited>()
+            .add_event::<PathCompleted>()
+            .add_event::<PathBlocked>()
+            .add_systems(
+                Update,
+                (
+                    handle_map_requests.before(process_loaded_maps),
+                    spawn_world_members
+                        .after(handle_map_requests)
+                        .before(process_loaded_maps),
+                    process_loaded_maps,
+                    clear_map_resources.after(process_loaded_maps),
+                    sync_tile_lookup.after(clear_map_resources),
+                    spawn_map_batches
+                        .after(process_loaded_maps)
+                        .before(stream_tile_chunks),
+                    replay_tile_edits
+                        .after(spawn_map_batches)
+                        .before(stream_tile_chunks),
+                    (reload_maps, record_tile_edits),
+                    stream_tile_chunks
+                        .after(process_loaded_maps)
+                        .before(apply_tile_edits),
+                    apply_tile_edits
+                        .after(process_loaded_maps)
+                        .before(register_tile_animations)
+                        .before(update_tile_properties)
+                        .before(sync_tile_lookup),
+                    (register_tile_animations, animate_tiles)
+                        .chain()
+                        .after(process_loaded_maps),
+                    update_tile_properties.after(process_loaded_maps),
+                    apply_layer_markers.after(process_loaded_maps),
+                    update_minimap.after(apply_tile_edits),
+                    (update_nav_grid, follow_paths.in_set(GameplaySet))
+                        .chain()
+                        .after(apply_tile_edits)
+                        .after(update_tile_properties),
+                    set_layer_visibility.after(process_loaded_maps),
+                    place_at_spawn_point.after(process_loaded_maps),
+                    (restart_map, change_map, use_doors, advance_door_transition)
+                        .chain()
+                        .after(place_at_spawn_point),
+                    (set_layer_tint, apply_layer_colors)
+                        .chain()
+                        .after(process_loaded_maps),
+                ),
+            )
+            // after the camera has moved for the frame
+            .add_systems(
+                PostUpdate,
+                (
+                    apply_parallax.before(TransformSystem::TransformPropagate),
+                    track_triggers
+                        .after(TransformSystem::TransformPropagate)
+                        .in_set(GameplaySet),
+                ),
+            );
+
+        // headless apps have no GizmoPlugin to draw with
+        #[cfg(feature = "dev")]
+        app.init_resource::<CollisionDebug>().add_systems(
+            Update,
+            collision::draw_collision_gizmos.run_if(resource_exists::<GizmoConfigStore>),
+        );
+        #[cfg(feature = "dev")]
+        {
+            lifecycle::register_console_commands(app);
+            spawn::register_console_commands(app);
+        }
+    }
+}
+
+#[derive(TypePath, Asset)]
+pub struct TiledMap {
+    pub map: tiled::Map,
+
+    pub tilemap_textures: HashMap<usize, TilemapTexture>,
+
+    // The offset into the tileset_images for each tile id within each tileset.
+    pub tile_image_offsets: HashMap<(usize, tiled::TileId), u32>,
+
+    // The image of each image layer that has one, by layer id.
+    pub image_layers: HashMap<u32, ImageLayerTexture>,
+
+    // What tiled doesn't parse, read from the TMX itself.
+    pub tmx: TmxExtras,
+}
+
+/// The parts of a TMX file tiled 0.11 skips over
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TmxExtras {
+    /// the length of a hexagon's flat side along the stagger axis, 0 off hexagonal maps
+    pub hex_side_length: u32,
+    /// the string and rectangle of each text object, by object id. Text objects made from a
+    /// template aren't read.
+    pub texts: HashMap<u32, TmxText>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TmxText {
+    pub text: String,
+    pub size: Vec2,
+}
+
+impl TmxExtras {
+    /// Reads them out of a TMX file's text
+    pub fn read(tmx: &str) -> Self {
+        let map = start_tags(tmx, "map").next().unwrap_or_default();
+        Self {
+            hex_side_length: attribute(map, "hexsidelength")
+                .and_then(|length| length.parse().ok())
+                .unwrap_or_default(),
+            texts: text_objects(tmx),
+        }
+    }
+}
+
+/// The text objects of a TMX file, by object id
+fn text_objects(tmx: &str) -> HashMap<u32, TmxText> {
+    // an object can't hold another, so what follows its start tag up to the next object holds
+    // its text, if it has any
+    tmx.split("<object")
+        .skip(1)
+        .filter(|object| object.starts_with(char::is_whitespace))
+        .filter_map(|object| {
+            let tag = &object[..object.find('>')?];
+            let id = attribute(tag, "id")?.parse().ok()?;
+            let text = &object[object.find("<text")?..];
+            let tag_end = text.find('>')?;
+            let content = if text[..tag_end].ends_with('/') {
+                ""
+            } else {
+                let text = &text[tag_end + 1..];
+                &text[..text.find("</text>")?]
+            };
+            let length = |name| {
+                attribute(tag, name)
+                    .and_then(|length| length.parse().ok())
+                    .unwrap_or_default()
+            };
+            Some((
+                id,
+                TmxText {
+                    text: unescape(content),
+                    size: Vec2::new(length("width"), length("height")),
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Text with XML's predefined entities replaced by their characters
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+impl TiledMap {
+    /// Index into the tilemap texture of a tileset for one of its tiles, None for tiles without
+    /// an image. GIDs with flag bits tiled doesn't strip (e.g. the hexagonal 120° rotation) come
+    /// out as ids past the end of the tileset, which would index outside the texture.
+    pub fn texture_index(&self, tileset_index: usize, id: tiled::TileId) -> Option<u32> {
+        match self.tilemap_textures.get(&tileset_index)? {
+            TilemapTexture::Single(_) => {
+                let tileset = self.map.tilesets().get(tileset_index)?;
+                (id < tileset.tilecount).then_some(id)
+            }
+            TilemapTexture::Vector(_) => self.tile_image_offsets.get(&(tileset_index, id)).copied(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// Stores the entities spawned for each tiled layer, by index in drawing order with groups
+// flattened. A tile layer gets one
+// tilemap per tileset it uses.
+#[derive(Component, Default)]
+pub struct TiledLayersStorage {
+    pub storage: HashMap<u32, Vec<Entity>>,
+}
+
+///
+/// MapLoaded
+///
+/// Sent whenever a map entity's map loads or reloads. It's sent from the same command queue
+/// that spawns the map, so by the time any system can read it:
+///   * every tile, tilemap and object entity of the map exists, with the object spawners run
+///   * MapBounds, the CollisionMap, MapMarkers, the TileLookup and the MapProperties resource
+///     describe the map
+///   * the map entity has its MapProperties
+///
+/// Under a MapSpawnBudget the map spawns over several frames, hidden, and MapLoaded is sent with
+/// the last batch, as the map's layers are shown. A door's fade holds until then.
+///
+/// For a world it's sent for the world entity once its members have spawned (or one of them
+/// reloaded), with empty properties: those of each member are on its map entity, and the
+/// MapProperties resource stays empty.
+#[derive(Event, Debug, Clone)]
+pub struct MapLoaded {
+    pub map: Entity,
+    /// asset path of the map, empty for maps added to the assets directly
+    pub path: String,
+    pub size_in_tiles: UVec2,
+    pub tile_size: Vec2,
+    /// the MapBounds resource as of the load, world_rect being the map's world extents
+    pub bounds: MapBounds,
+    pub properties: MapProperties,
+    /// whether the map was already spawned and its asset changed, e.g. hot reloaded. Things
+    /// placed on the map, like the player, stay where they are.
+    pub reloaded: bool,
+}
+
+#[derive(Default, Bundle)]
+pub struct TiledMapBundle {
+    pub tiled_map: Handle<TiledMap>,
+    pub storage: TiledLayersStorage,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+    pub render_settings: TilemapRenderSettings,
+    /// whether tile layers spawn all at once or in chunks around the main camera
+    pub streaming: TileStreaming,
+    /// the z of each layer
+    pub depths: LayerDepths,
+}
+
+/// Serves the TMX bytes and the external tilesets and object templates read ahead of time
+/// through the load context, since tiled reads files synchronously and there's no filesystem on
+/// wasm
+#[derive(Clone)]
+struct BytesResourceReader {
+    map_path: PathBuf,
+    bytes: Arc<[u8]>,
+    files: HashMap<PathBuf, Arc<[u8]>>,
+}
+
+impl BytesResourceReader {
+    fn new(map_path: &Path, bytes: &[u8]) -> Self {
+        Self {
+            map_path: normalize_path(map_path),
+            bytes: Arc::from(bytes),
+            files: HashMap::default(),
+        }
+    }
+
+    fn add_file(&mut self, path: &Path, bytes: Vec<u8>) {
+        self.files
+            .insert(normalize_path(path), Arc::from(bytes.into_boxed_slice()));
+    }
+
+    /// The same tilesets and templates, serving other TMX bytes for the map
+    fn with_map(&self, bytes: &[u8]) -> Self {
+        Self {
+            map_path: self.map_path.clone(),
+            bytes: Arc::from(bytes),
+            files: self.files.clone(),
+        }
+    }
+}
+
+impl tiled::ResourceReader for BytesResourceReader {
+    type Resource = Cursor<Arc<[u8]>>;
+    type Error = std::io::Error;
+
+    fn read_from(&mut self, path: &Path) -> std::result::Result<Self::Resource, Self::Error> {
+        let path = normalize_path(path);
+        if path == self.map_path {
+            return Ok(Cursor::new(self.bytes.clone()));
+        }
+        match self.files.get(&path) {
+            Some(bytes) => Ok(Cursor::new(bytes.clone())),
+            None => Err(std::io::Error::new(
+                ErrorKind::NotFound,
+                format!("{} was not read by the asset loader", path.display()),
+            )),
+        }
+    }
+}
+
+/// `path` in the asset source the map is loaded from, so a map from somewhere other than the
+/// assets directory, like a dropped one, finds its tilesets and images next to it
+fn in_source(load_context: &LoadContext, path: &Path) -> AssetPath<'static> {
+    AssetPath::from(normalize_path(path))
+        .with_source(load_context.asset_path().source().clone_owned())
+}
+
+/// Resolves `.` and `..` so tiled's joined paths match the asset paths they were read from
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// The attributes of every `<element ...>` start tag in an XML document
+fn start_tags<'a>(xml: &'a str, element: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    xml.match_indices('<').filter_map(move |(start, _)| {
+        let tag = xml[start + 1..].strip_prefix(element)?;
+        if !tag.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            return None;
+        }
+        Some(&tag[..tag.find('>').unwrap_or(tag.len())])
+    })
+}
+
+/// The value of an attribute within a start tag's attributes
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pattern = format!("{}=", name);
+    for (start, _) in tag.match_indices(&pattern) {
+        if !tag[..start].ends_with(char::is_whitespace) {
+            continue;
+        }
+        let value = &tag[start + pattern.len()..];
+        let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let end = value[1..].find(quote)?;
+        return Some(&value[1..end + 1]);
+    }
+    None
+}
+
+/// The `source` of every external `<tileset>` reference in a TMX file
+fn external_tileset_sources(tmx: &str) -> Vec<String> {
+    start_tags(tmx, "tileset")
+        .filter_map(|tag| attribute(tag, "source"))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The id and `template` of every templated `<object>` in a TMX file
+fn object_templates(tmx: &str) -> Vec<(u32, String)> {
+    start_tags(tmx, "object")
+        .filter_map(|tag| {
+            let template = attribute(tag, "template")?;
+            let id = attribute(tag, "id").and_then(|id| id.parse().ok());
+            Some((id.unwrap_or_default(), template.to_string()))
+        })
+        .collect()
+}
+
+/// The repeatx and repeaty flags of each image layer in a TMX file, by layer id
+fn image_layer_repeats(tmx: &str) -> HashMap<u32, BVec2> {
+    start_tags(tmx, "imagelayer")
+        .filter_map(|tag| {
+            let id = attribute(tag, "id")?.parse().ok()?;
+            let flag = |name| attribute(tag, name) == Some("1");
+            Some((id, BVec2::new(flag("repeatx"), flag("repeaty"))))
+        })
+        .collect()
+}
+
+/// Each `<layer>` element of a TMX file: its name and where it is in the text
+fn layer_elements(tmx: &str) -> Vec<(&str, Range<usize>)> {
+    let mut layers = Vec::new();
+    let mut from = 0;
+    while let Some(found) = tmx[from..].find("<layer") {
+        let start = from + found;
+        let attributes = start + "<layer".len();
+        if !tmx[attributes..].starts_with(char::is_whitespace) {
+            from = attributes;
+            continue;
+        }
+        let tag_end = tmx[start..]
+            .find('>')
+            .map_or(tmx.len(), |end| start + end + 1);
+        let end = if tmx[..tag_end].ends_with("/>") {
+            tag_end
+        } else {
+            tmx[tag_end..]
+                .find("</layer>")
+                .map_or(tmx.len(), |end| tag_end + end + "</layer>".len())
+        };
+        let name = attribute(&tmx[attributes..tag_end], "name").unwrap_or_default();
+        layers.push((name, start..end));
+        from = end;
+    }
+    layers
+}
+
+/// A TMX file with only the layer elements at `keep` left in
+fn without_other_layers(tmx: &str, layers: &[(&str, Range<usize>)], keep: Option<usize>) -> String {
+    let mut stripped = String::with_capacity(tmx.len());
+    let mut from = 0;
+    for (index, (_, span)) in layers.iter().enumerate() {
+        if Some(index) != keep {
+            stripped.push_str(&tmx[from..span.start]);
+            from = span.end;
+        }
+    }
+    stripped.push_str(&tmx[from..]);
+    stripped
+}
+
+/// Loads each tile layer of a map that tiled rejected on its own, returning the name of the first
+/// that fails and why. None when the map fails without any of its layers too.
+fn broken_layer(tmx: &str, reader: &BytesResourceReader) -> Option<(String, String)> {
+    let load = |tmx: &str| {
+        tiled::Loader::with_cache_and_reader(
+            tiled::DefaultResourceCache::new(),
+            reader.with_map(tmx.as_bytes()),
+        )
+        .load_tmx_map(&reader.map_path)
+    };
+    let layers = layer_elements(tmx);
+    if load(&without_other_layers(tmx, &layers, None)).is_err() {
+        return None;
+    }
+    layers.iter().enumerate().find_map(|(index, (name, _))| {
+        load(&without_other_layers(tmx, &layers, Some(index)))
+            .err()
+            .map(|e| (name.to_string(), e.to_string()))
+    })
+}
+
+pub struct TiledLoader;
+
+#[derive(Debug, Error)]
+pub enum TiledAssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load Tiled file: {0}")]
+    Io(#[from] std::io::Error),
+    /// An external tileset referenced by the map could not be read
+    #[error("Could not read tileset {}: {source}", path.display())]
+    MissingTileset {
+        path: PathBuf,
+        source: ReadAssetBytesError,
+    },
+    /// An object template referenced by the map could not be read
+    #[error("Could not read template {} of object {object}: {source}", path.display())]
+    MissingTemplate {
+        object: u32,
+        path: PathBuf,
+        source: ReadAssetBytesError,
+    },
+    /// An external tileset referenced by the map could not be parsed
+    #[error("Could not parse tileset {}: {message}", path.display())]
+    InvalidTileset { path: PathBuf, message: String },
+    /// A tile layer's data is corrupt, truncated or compressed in a way that isn't supported
+    #[error("Could not decode the data of layer {layer}: {message}")]
+    InvalidLayerData { layer: String, message: String },
+    /// The map parsed, but validation found problems with it, listed one per line
+    #[error("Invalid map:\n{0}")]
+    Invalid(MapReport),
+}
+
+impl AssetLoader for TiledLoader {
+    type Asset = TiledMap;
+    type Settings = ();
+    type Error = TiledAssetLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+
+            // tiled resolves external tilesets relative to the map, so read them from the same place
+            let map_dir = load_context
+                .path()
+                .parent()
+                .expect("The asset load context was empty.")
+                .to_path_buf();
+            let tmx = String::from_utf8_lossy(&bytes);
+            let mut report = MapReport::new(load_context.path());
+            validate::check_tmx(&mut report, &tmx);
+            if !report.is_ok() {
+                return Err(TiledAssetLoaderError::Invalid(report));
+            }
+
+            let mut resource_reader = BytesResourceReader::new(load_context.path(), &bytes);
+            // tilesets are relative to the file naming them: the map, or a template
+            let mut tileset_sources: Vec<PathBuf> = external_tileset_sources(&tmx)
+                .into_iter()
+                .map(|source| normalize_path(&map_dir.join(source)))
+                .collect();
+            let mut template_paths = Vec::new();
+            for (object, source) in object_templates(&tmx) {
+                let path = normalize_path(&map_dir.join(source));
+                if template_paths.contains(&path) {
+                    continue;
+                }
+                let template_bytes =
+                    load_context
+                        .read_asset_bytes(in_source(load_context, &path))
+                        .await
+                        .map_err(|source| TiledAssetLoaderError::MissingTemplate {
+                            object,
+                            path: path.clone(),
+                            source,
+                        })?;
+                let template_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+                let template = String::from_utf8_lossy(&template_bytes);
+                tileset_sources.extend(
+                    external_tileset_sources(&template)
+                        .into_iter()
+                        .map(|source| normalize_path(&template_dir.join(source))),
+                );
+                resource_reader.add_file(&path, template_bytes);
+                template_paths.push(path);
+            }
+
+            let mut tileset_paths = Vec::new();
+            for path in tileset_sources {
+                if tileset_paths.contains(&path) {
+                    continue;
+                }
+                let tileset_bytes =
+                    load_context
+                        .read_asset_bytes(in_source(load_context, &path))
+                        .await
+                        .map_err(|source| TiledAssetLoaderError::MissingTileset {
+                            path: path.clone(),
+                            source,
+                        })?;
+                resource_reader.add_file(&path, tileset_bytes);
+                tileset_paths.push(path);
+            }
+
+            let mut loader = tiled::Loader::with_cache_and_reader(
+                tiled::DefaultResourceCache::new(),
+                resource_reader.clone(),
+            );
+            // parse the tilesets on their own first so a broken one is reported by name
+            for path in tileset_paths {
+                if let Err(e) = loader.load_tsx_tileset(&path) {
+                    return Err(TiledAssetLoaderError::InvalidTileset {
+                        path,
+                        message: e.to_string(),
+                    });
+                }
+            }
+            let map = match loader.load_tmx_map(load_context.path()) {
+                Ok(map) => map,
+                Err(e) => {
+                    // tiled's decoding errors don't say which layer they came from
+                    if let Some((layer, message)) = broken_layer(&tmx, &resource_reader) {
+                        return Err(TiledAssetLoaderError::InvalidLayerData { layer, message });
+                    }
+                    return Err(
+                        std::io::Error::other(format!("Could not load TMX map: {}", e)).into(),
+                    );
+                }
+            };
+            validate::check_map(&mut report, &map);
+            report.log_warnings();
+            if !report.is_ok() {
+                return Err(TiledAssetLoaderError::Invalid(report));
+            }
+
+            let mut tilemap_textures = HashMap::default();
+            let mut tile_image_offsets = HashMap::default();
+
+            for (tileset_index, tileset) in map.tilesets().iter().enumerate() {
+                let tilemap_texture = match &tileset.image {
+                    None => {
+                        let mut tile_images: Vec<Handle<Image>> = Vec::new();
+                        for (tile_id, tile) in tileset.tiles() {
+                            if let Some(img) = &tile.image {
+                                let asset_path = in_source(load_context, &img.source);
+                                log::info!(
+                                    "Loading tile image from {:?} as image ({}, {})",
+                                    asset_path,
+                                    tileset_index,
+                                    tile_id
+                                );
+                                let texture: Handle<Image> = load_context.load(asset_path.clone());
+                                tile_image_offsets
+                                    .insert((tileset_index, tile_id), tile_images.len() as u32);
+                                tile_images.push(texture.clone());
+                            }
+                        }
+
+                        TilemapTexture::Vector(tile_images)
+                    }
+                    Some(img) => {
+                        // tiled already joins image sources onto the directory of the file that
+                        // declares them: the map for embedded tilesets, the .tsx for external ones
+                        let asset_path = in_source(load_context, &img.source);
+                        let texture: Handle<Image> = load_context.load(asset_path.clone());
+
+                        TilemapTexture::Single(texture.clone())
+                    }
+                };
+
+                tilemap_textures.insert(tileset_index, tilemap_texture);
+            }
+
+            let repeats = image_layer_repeats(&tmx);
+            let mut image_layers = HashMap::default();
+            for layer in layers::resolve_layers(&map) {
+                let tiled::LayerType::Image(image_layer) = layer.layer.layer_type() else {
+                    continue;
+                };
+                let Some(img) = &image_layer.image else {
+                    continue;
+                };
+                image_layers.insert(
+                    layer.layer.id(),
+                    ImageLayerTexture {
+                        texture: load_context.load(in_source(load_context, &img.source)),
+                        size: Vec2::new(img.width as f32, img.height as f32),
+                        repeat: repeats.get(&layer.layer.id()).copied().unwrap_or_default(),
+                    },
+                );
+            }
+
+            let asset_map = TiledMap {
+                map,
+                tilemap_textures,
+                tile_image_offsets,
+                image_layers,
+                tmx: TmxExtras::read(&tmx),
+            };
+
+            log::info!("Loaded map: {}", load_context.path().display());
+            Ok(asset_map)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["tmx"];
+        EXTENSIONS
+    }
+}
+
+type MapEntity<'a> = (
+    Entity,
+    &'a Handle<TiledMap>,
+    &'a mut TiledLayersStorage,
+    &'a TilemapRenderSettings,
+    Option<&'a WorldMember>,
+    Option<&'a TileStreaming>,
+    Option<&'a LayerDepths>,
+    Option<&'a MapProperties>,
+);
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_loaded_maps(
+    mut commands: Commands,
+    mut map_events: EventReader<AssetEvent<TiledMap>>,
+    maps: Res<Assets<TiledMap>>,
+    object_registry: Option<Res<TiledObjectRegistry>>,
+    tile_properties: Option<Res<TilePropertyRegistry>>,
+    marker_registry: Option<Res<LayerMarkerRegistry>>,
+    hidden_layers: Res<HiddenLayers>,
+    budget: Res<MapSpawnBudget>,
+    mut collision: ResMut<CollisionMap>,
+    mut bounds: ResMut<MapBounds>,
+    mut markers: ResMut<MapMarkers>,
+    mut map_properties: ResMut<MapProperties>,
+    tile_storage_query: Query<(Entity, &TileStorage)>,
+    mut map_query: Query<MapEntity>,
+    new_maps: Query<&Handle<TiledMap>, Added<Handle<TiledMap>>>,
+    world_query: Query<&Handle<TiledWorld>>,
+) {
+    let _span = crate::profiling::span("process_loaded_maps");
+    let mut changed_maps = Vec::<AssetId<TiledMap>>::default();
+    let mut reloaded_maps = Vec::<AssetId<TiledMap>>::default();
+    let mut added_maps = Vec::<AssetId<TiledMap>>::default();
+    for event in map_events.read() {
+        match event {
+            AssetEvent::Added { id } => {
+                log::info!("Map added!");
+                changed_maps.push(*id);
+                added_maps.push(*id);
+            }
+            AssetEvent::Modified { id } => {
+                log::info!("Map changed!");
+                changed_maps.push(*id);
+                reloaded_maps.push(*id);
+            }
+            AssetEvent::Removed { id } => {
+                log::info!("Map removed!");
+                // if mesh was modified and removed in the same update, ignore the modification
+                // events are ordered so future modification events are ok
+                changed_maps.retain(|changed_handle| changed_handle == id);
+            }
+            _ => continue,
+        }
+    }
+
+    // If we have new map entities add them to the changed_maps list.
+    // A map that finished loading in the same frame as its entity only needs spawning once.
+    for new_map_handle in new_maps.iter() {
+        if !changed_maps.contains(&new_map_handle.id()) {
+            changed_maps.push(new_map_handle.id());
+        }
+    }
+
+    let mut loaded_worlds = Vec::new();
+    for changed_map in changed_maps.iter() {
+        for (
+            map_entity,
+            map_handle,
+            mut layer_storage,
+            render_settings,
+            member,
+            streaming,
+            depths,
+            spawned,
+        ) in map_query.iter_mut()
+        {
+            // only deal with currently changed map
+            if map_handle.id() != *changed_map {
+                continue;
+            }
+            // an asset added before its entity was spawned with the entity, a frame before the event
+            if spawned.is_some()
+                && added_maps.contains(changed_map)
+                && !reloaded_maps.contains(changed_map)
+            {
+                continue;
+            }
+            if let Some(tiled_map) = maps.get(map_handle) {
+                despawn_layers(&mut commands, &mut layer_storage, &tile_storage_query);
+                match member {
+                    // the map resources cover the whole world, so only the member's part goes
+                    Some(member) => {
+                        collision.clear_rect(member.rect());
+                        markers.remove_member(&member.name);
+                    }
+                    None => {
+                        *bounds = MapBounds::of(tiled_map);
+                        collision::reset_for_map(&mut collision, &bounds);
+                        markers.clear();
+                    }
+                }
+                let bounds = member.map_or(*bounds, |member| member.bounds);
+                let member_name = member.map(|member| member.name.as_str());
+                // under a budget, the resources are built now and the entities over the next frames
+                let deferred = *budget != MapSpawnBudget::Unlimited;
+                let reloaded = reloaded_maps.contains(changed_map);
+                let mut spawning = MapSpawning::new(bounds, reloaded);
+                let default_depths = LayerDepths::default();
+                let depths = depths.unwrap_or(&default_depths);
+
+                for (layer_index, layer) in
+                    layers::resolve_layers(&tiled_map.map).iter().enumerate()
+                {
+                    // markers are data, so they're kept from layers that aren't spawned too
+                    if let tiled::LayerType::Objects(object_layer) = layer.layer.layer_type() {
+                        markers::add_markers(
+                            &mut markers,
+                            &bounds,
+                            member_name,
+                            layer,
+                            &object_layer,
+                        );
+                    }
+                    if !layer.visible && *hidden_layers == HiddenLayers::Skip {
+                        continue;
+                    }
+                    let z = depths.z(layer, layer_index);
+                    let layer_name = LayerName::new(&layer.path);
+                    let tags = layer_markers::LayerTags {
+                        name: &layer_name,
+                        registry: marker_registry.as_deref(),
+                    };
+                    let layer_entities = match layer.layer.layer_type() {
+                        tiled::LayerType::Tiles(tile_layer) => {
+                            let tiles = match tile_layer {
+                                tiled::TileLayer::Finite(layer_data) if member.is_some() => {
+                                    member_tiles_by_tileset(&layer_data, &bounds)
+                                }
+                                tiled::TileLayer::Finite(layer_data) => {
+                                    tiles_by_tileset(&layer_data)
+                                }
+                                tiled::TileLayer::Infinite(layer_data) => {
+                                    chunked_tiles_by_tileset(&layer_data, &bounds)
+                                }
+                            };
+                            spawn_tile_layer(
+                                &mut commands,
+                                &mut collision,
+                                tile_properties.as_deref(),
+                                map_handle,
+                                tiled_map,
+                                &bounds,
+                                layer,
+                                tiles,
+                                z,
+                                render_settings,
+                                streaming.copied().unwrap_or_default(),
+                                deferred,
+                                tags,
+                            )
+                        }
+                        tiled::LayerType::Image(_) => {
+                            let Some(image) = tiled_map.image_layers.get(&layer.layer.id()) else {
+                                continue;
+                            };
+                            vec![image::spawn_image_layer(
+                                &mut commands,
+                                &bounds,
+                                layer,
+                                image,
+                                z,
+                            )]
+                        }
+                        tiled::LayerType::Objects(_) if deferred => {
+                            spawning.defer_object_layer(layer_index, z);
+                            continue;
+                        }
+                        tiled::LayerType::Objects(object_layer) => {
+                            vec![objects::spawn_object_layer(
+                                &mut commands,
+                                object_registry.as_deref(),
+                                &mut collision,
+                                &bounds,
+                                layer,
+                                &object_layer,
+                                &tiled_map.tmx,
+                                z,
+                            )]
+                        }
+                        _ => {
+                            log::info!(
+                                "Skipping layer {} because only tile, image and object layers are supported.",
+                                layer.path
+                            );
+                            continue;
+                        }
+                    };
+                    insert_parallax(&mut commands, &bounds, layer, &layer_entities);
+                    tags.insert_all(&mut commands, &layer_entities);
+                    if deferred {
+                        spawning.hide(&mut commands, &layer_entities, layer.visible);
+                    }
+                    layer_storage
+                        .storage
+                        .insert(layer_index as u32, layer_entities);
+                }
+
+                let properties = MapProperties(tiled_map.map.properties.clone());
+                commands.entity(map_entity).insert(properties.clone());
+                let loaded = match member {
+                    // worlds send theirs once all their members are there
+                    Some(_) => None,
+                    None => {
+                        *map_properties = properties.clone();
+                        Some(MapLoaded {
+                            map: map_entity,
+                            path: map_handle
+                                .path()
+                                .map(|path| path.to_string())
+                                .unwrap_or_default(),
+                            size_in_tiles: bounds.size,
+                            tile_size: bounds.tile_size,
+                            bounds,
+                            properties,
+                            reloaded,
+                        })
+                    }
+                };
+                if deferred {
+                    // spawn_map_batches takes it from here
+                    spawning.set_loaded(loaded);
+                    commands.entity(map_entity).insert(spawning);
+                    continue;
+                }
+
+                commands.add(lookup::rebuild_tile_lookup);
+                commands.add(nav::rebuild_nav_grid);
+                commands.add(minimap::rebuild_minimap);
+                match loaded {
+                    // queued behind the spawns above, see MapLoaded
+                    Some(loaded) => {
+                        commands.add(move |world: &mut World| {
+                            world.send_event(loaded);
+                        });
+                    }
+                    None => {
+                        if let Some(member) = member {
+                            match loaded_worlds
+                                .iter_mut()
+                                .find(|(world, _)| *world == member.world)
+                            {
+                                Some((_, world_reloaded)) => *world_reloaded |= reloaded,
+                                None => loaded_worlds.push((member.world, reloaded)),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // after every member that changed, so the whole world is there
+    for (world_entity, reloaded) in loaded_worlds {
+        let loaded = world_loaded(
+            world_entity,
+            world_query.get(world_entity).ok(),
+            &bounds,
+            reloaded,
+        );
+        commands.add(move |world: &mut World| {
+            world.send_event(loaded);
+        });
+    }
+}
+
+/// The MapLoaded of a world, sent once its members are spawned
+fn world_loaded(
+    world_entity: Entity,
+    world_handle: Option<&Handle<TiledWorld>>,
+    bounds: &MapBounds,
+    reloaded: bool,
+) -> MapLoaded {
+    MapLoaded {
+        map: world_entity,
+        path: world_handle
+            .and_then(|world_handle| world_handle.path())
+            .map(|path| path.to_string())
+            .unwrap_or_default(),
+        size_in_tiles: bounds.size,
+        tile_size: bounds.tile_size,
+        bounds: *bounds,
+        properties: MapProperties::default(),
+        reloaded,
+    }
+}
+
+/// Adds LayerParallax to the entities of a layer; layers scrolling 1:1 don't need the parallax
+/// system
+fn insert_parallax(
+    commands: &mut Commands,
+    bounds: &MapBounds,
+    layer: &layers::ResolvedLayer,
+    layer_entities: &[Entity],
+) {
+    if layer.parallax == Vec2::ONE {
+        return;
+    }
+    let reference = bounds.tmx_to_world(0.0, 0.0);
+    for entity in layer_entities.iter() {
+        commands
+            .entity(*entity)
+            .insert(LayerParallax::new(layer.parallax, reference));
+    }
+}
+
+/// Despawns the tiles and layer entities spawned for a map, with the objects on its object layers
+fn despawn_layers(
+    commands: &mut Commands,
+    layer_storage: &mut TiledLayersStorage,
+    tile_storage_query: &Query<(Entity, &TileStorage)>,
+) {
+    for layer_entity in layer_storage.storage.values().flatten() {
+        if let Ok((_, layer_tile_storage)) = tile_storage_query.get(*layer_entity) {
+            for tile in layer_tile_storage.iter().flatten() {
+                commands.entity(*tile).despawn_recursive()
+            }
+        }
+        // object layers and their objects
+        commands.entity(*layer_entity).despawn_recursive();
+    }
+    layer_storage.storage.clear();
+}
+
+///
+/// clear_map_resources: Bevy system
+///
+/// Empties the CollisionMap, MapBounds, MapMarkers, MapProperties and TileEditLog once no map
+/// entity is left
+pub fn clear_map_resources(
+    mut removed: RemovedComponents<Handle<TiledMap>>,
+    map_query: Query<(), With<Handle<TiledMap>>>,
+    mut collision: ResMut<CollisionMap>,
+    mut bounds: ResMut<MapBounds>,
+    mut markers: ResMut<MapMarkers>,
+    mut map_properties: ResMut<MapProperties>,
+    mut edit_log: ResMut<TileEditLog>,
+) {
+    if removed.read().count() > 0 && map_query.is_empty() {
+        collision.clear();
+        *bounds = MapBounds::default();
+        markers.clear();
+        map_properties.clear();
+        edit_log.clear();
+    }
+}
+
+///
+/// PlacedTile
+///
+/// A tile of a layer, in bevy_ecs_tilemap coordinates (y up)
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedTile {
+    pub pos: TilePos,
+    pub tileset_index: usize,
+    /// index of the tile within its tileset, already rebased from the map-wide GID
+    pub id: tiled::TileId,
+    pub flip: TileFlip,
+}
+
+/// The tiles of a layer grouped by the tileset they come from, in tileset order
+pub fn tiles_by_tileset(layer: &tiled::FiniteTileLayer) -> BTreeMap<usize, Vec<PlacedTile>> {
+    let mut tilesets: BTreeMap<usize, Vec<PlacedTile>> = BTreeMap::new();
+    let height = layer.height();
+    for x in 0..layer.width() {
+        for y in 0..height {
+            // Transform TMX coords into bevy coords.
+            let mapped_y = height - 1 - y;
+            if let Some(data) = layer.get_tile_data(x as i32, mapped_y as i32) {
+                push_placed_tile(&mut tilesets, TilePos { x, y }, data);
+            }
+        }
+    }
+    tilesets
+}
+
+/// The tiles of a finite layer of a world member grouped by tileset, positioned within the
+/// member's bounds
+fn member_tiles_by_tileset(
+    layer: &tiled::FiniteTileLayer,
+    bounds: &MapBounds,
+) -> BTreeMap<usize, Vec<PlacedTile>> {
+    let mut tilesets: BTreeMap<usize, Vec<PlacedTile>> = BTreeMap::new();
+    for y in 0..layer.height() as i32 {
+        for x in 0..layer.width() as i32 {
+            let Some(data) = layer.get_tile_data(x, y) else {
+                continue;
+            };
+            if let Some(pos) = bounds.tile_pos(x, y) {
+                push_placed_tile(&mut tilesets, pos, data);
+            }
+        }
+    }
+    tilesets
+}
+
+/// The tiles of an infinite layer grouped by tileset, positioned within the map's bounds
+pub fn chunked_tiles_by_tileset(
+    layer: &tiled::InfiniteTileLayer,
+    bounds: &MapBounds,
+) -> BTreeMap<usize, Vec<PlacedTile>> {
+    let mut tilesets: BTreeMap<usize, Vec<PlacedTile>> = BTreeMap::new();
+    let (width, height) = (
+        tiled::ChunkData::WIDTH as i32,
+        tiled::ChunkData::HEIGHT as i32,
+    );
+    for ((chunk_x, chunk_y), chunk) in layer.chunks() {
+        for y in 0..height {
+            for x in 0..width {
+                let Some(data) = chunk.get_tile_data(x, y) else {
+                    continue;
+                };
+                if let Some(pos) = bounds.tile_pos(chunk_x * width + x, chunk_y * height + y) {
+                    push_placed_tile(&mut tilesets, pos, data);
+                }
+            }
+        }
+    }
+    tilesets
+}
+
+// tiled strips the flip bits off the GID; Tiled applies the diagonal flip first, which is how
+// bevy_ecs_tilemap applies TileFlip too
+fn push_placed_tile(
+    tilesets: &mut BTreeMap<usize, Vec<PlacedTile>>,
+    pos: TilePos,
+    data: &tiled::LayerTileData,
+) {
+    tilesets
+        .entry(data.tileset_index())
+        .or_default()
+        .push(PlacedTile {
+            pos,
+            tileset_index: data.tileset_index(),
+            id: data.id(),
+            flip: TileFlip {
+                x: data.flip_h,
+                y: data.flip_v,
+                d: data.flip_d,
+            },
+        });
+}
+
+// The TilemapBundle requires that all tile images come exclusively from a single tiled texture
+// or from a Vec of independent per-tile images. Furthermore, all of the per-tile images must be
+// the same size. Since Tiled allows tiles of mixed tilesets on each layer and allows
+// differently-sized tile images in each tileset, this means we need to spawn each combination
+// of tileset and layer separately. The tilemaps of one layer share its z.
+#[allow(clippy::too_many_arguments)]
+fn spawn_tile_layer(
+    commands: &mut Commands,
+    collision: &mut CollisionMap,
+    tile_properties: Option<&TilePropertyRegistry>,
+    map_handle: &Handle<TiledMap>,
+    tiled_map: &TiledMap,
+    bounds: &MapBounds,
+    layer: &layers::ResolvedLayer,
+    tiles: BTreeMap<usize, Vec<PlacedTile>>,
+    z: f32,
+    render_settings: &TilemapRenderSettings,
+    streaming: TileStreaming,
+    deferred: bool,
+    tags: layer_markers::LayerTags,
+) -> Vec<Entity> {
+    let map = &tiled_map.map;
+    let map_size = bounds.tilemap_size();
+    let grid_size = bounds.tilemap_grid_size();
+    let map_type = bounds.map_type;
+
+    let mut layer_entities = Vec::new();
+    for (tileset_index, tiles) in tiles {
+        let tileset = &map.tilesets()[tileset_index];
+        let source = TilemapSource {
+            map: map_handle.clone(),
+            tileset_index,
+            collision_layer: collision::is_collision_layer(&layer.layer),
+        };
+        let Some(tilemap_texture) = tiled_map.tilemap_textures.get(&tileset_index) else {
+            log::warn!("Skipped creating layer with missing tilemap textures.");
+            continue;
+        };
+
+        let tile_size = TilemapTileSize {
+            x: tileset.tile_width as f32,
+            y: tileset.tile_height as f32,
+        };
+        let tile_spacing = TilemapSpacing {
+            x: tileset.spacing as f32,
+            y: tileset.spacing as f32,
+        };
+
+        let texture_index_of = |id: tiled::TileId| tiled_map.texture_index(tileset_index, id);
+
+        let mut tile_storage = TileStorage::empty(map_size);
+        let layer_entity = commands.spawn_empty().id();
+        let tiled_layer = TiledLayer::new(layer);
+        let color = TileColor(tiled_layer.color());
+        let mut streamed = StreamedTiles::new(streaming);
+        // left for spawn_map_batches
+        let mut pending = Vec::new();
+
+        for placed in tiles {
+            if texture_index_of(placed.id).is_none() {
+                log::warn!(
+                    "Skipping tile {} of tileset {} without an image.",
+                    placed.id,
+                    tileset.name
+                );
+                continue;
+            }
+
+            // the collision data covers the whole map, whether its tiles are streamed or not
+            let (x, y) = (placed.pos.x as i32, placed.pos.y as i32);
+            let tile = tileset.get_tile(placed.id);
+            if collision::is_solid_tile(&layer.layer, tile.as_ref()) {
+                collision.set_solid(x, y, true);
+            } else if collision::tile_is_one_way(tile.as_ref()) {
+                collision.set_one_way(x, y, true);
+            }
+            let shapes = placed_shapes(collision, tile.as_ref(), &placed);
+            for shape in shapes.iter() {
+                collision.add_shape(shape.clone());
+            }
+
+            match streamed.as_mut() {
+                Some(streamed) => streamed.insert(placed),
+                None if deferred => pending.push(placed),
+                None => {
+                    let tile_entity = spawn_tile(
+                        commands,
+                        tiled_map,
+                        tile_properties,
+                        layer_entity,
+                        &source,
+                        &placed,
+                        color,
+                        shapes,
+                        tags,
+                    );
+                    tile_storage.set(&placed.pos, tile_entity);
+                }
+            }
+        }
+
+        commands.entity(layer_entity).insert((
+            TilemapBundle {
+                grid_size,
+                size: map_size,
+                storage: tile_storage,
+                texture: tilemap_texture.clone(),
+                tile_size,
+                spacing: tile_spacing,
+                transform: get_tilemap_center_transform(&map_size, &grid_size, &map_type, z)
+                    * Transform::from_xyz(layer.offset.x, -layer.offset.y, 0.0),
+                map_type,
+                render_settings: *render_settings,
+                visibility: layers::visibility(layer.visible),
+                ..Default::default()
+            },
+            tiled_layer,
+            source,
+        ));
+        if let Some(streamed) = streamed {
+            commands.entity(layer_entity).insert(streamed);
+        }
+        if !pending.is_empty() {
+            commands
+                .entity(layer_entity)
+                .insert(PendingTiles::new(pending));
+        }
+        let animations = animation::tileset_animations(tileset, texture_index_of);
+        if !animations.is_empty() {
+            commands.entity(layer_entity).insert(animations);
+        }
+        // for tiles swapped at runtime, see update_tile_properties
+        let tileset_properties = properties::tileset_properties(tileset);
+        if !tileset_properties.is_empty() {
+            commands.entity(layer_entity).insert(tileset_properties);
+        }
+        layer_entities.push(layer_entity);
+    }
+    layer_entities
+}
+
+/// The collision shapes of a placed tile, in world space
+fn placed_shapes(
+    collision: &CollisionMap,
+    tile: Option<&tiled::Tile>,
+    placed: &PlacedTile,
+) -> Vec<ColliderShape> {
+    let Some(tile) = tile else {
+        return Vec::new();
+    };
+    collision::tile_shapes(
+        tile,
+        collision.tile_rect(placed.pos.x as i32, placed.pos.y as i32),
+        collision::ShapeFlip {
+            horizontal: placed.flip.x,
+            vertical: placed.flip.y,
+            diagonal: placed.flip.d,
+        },
+    )
+}
+
+/// Spawns the entity of a tile with its property components and colliders, given its
+/// collision shapes. The CollisionMap is left alone: when streamed tiles spawn again, it
+/// already has them.
+#[allow(clippy::too_many_arguments)]
+fn spawn_tile(
+    commands: &mut Commands,
+    tiled_map: &TiledMap,
+    tile_properties: Option<&TilePropertyRegistry>,
+    tilemap: Entity,
+    source: &TilemapSource,
+    placed: &PlacedTile,
+    color: TileColor,
+    shapes: Vec<ColliderShape>,
+    tags: layer_markers::LayerTags,
+) -> Entity {
+    let texture_index = tiled_map
+        .texture_index(placed.tileset_index, placed.id)
+        .unwrap_or_default();
+    let mut tile_entity = commands.spawn((
+        TileBundle {
+            position: placed.pos,
+            tilemap_id: TilemapId(tilemap),
+            texture_index: TileTextureIndex(texture_index),
+            flip: placed.flip,
+            color,
+            ..Default::default()
+        },
+        TilesetTile(placed.id),
+    ));
+    tags.insert(&mut tile_entity);
+
+    let tile = tiled_map.map.tilesets()[placed.tileset_index].get_tile(placed.id);
+    if let Some(tile) = &tile {
+        if !tile.properties.is_empty() {
+            properties::insert_tile_properties(&mut tile_entity, tile_properties, &tile.properties);
+        }
+    }
+    if source.collision_layer || collision::tile_collides(tile.as_ref()) {
+        tile_entity.insert(TileCollider);
+    }
+    if !shapes.is_empty() {
+        tile_entity.insert(ShapeCollider { shapes });
+    }
+    tile_entity.id()
+}
@@ -0,0 +1,167 @@
+// Tiles animated in Tiled's tile animation editor. Every tile that shows an animated tileset
+// tile follows the same clock, so all the water on a map moves in phase.
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_tilemap::prelude::*;
+use std::sync::Arc;
+
+/// One frame of a tile animation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TileFrame {
+    pub texture_index: u32,
+    /// seconds
+    pub duration: f32,
+}
+
+///
+/// TileAnimation
+///
+/// The frames of an animated tileset tile, with their own durations
+#[derive(Debug, Clone, PartialEq)]
+pub struct TileAnimation {
+    frames: Vec<TileFrame>,
+    duration: f32,
+}
+
+impl TileAnimation {
+    pub fn new(frames: Vec<TileFrame>) -> Self {
+        let duration = frames.iter().map(|frame| frame.duration.max(0.0)).sum();
+        TileAnimation { frames, duration }
+    }
+
+    pub fn frames(&self) -> &[TileFrame] {
+        &self.frames
+    }
+
+    /// Seconds for one loop of the animation
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    /// Texture index shown `elapsed` seconds after the animation clock started
+    pub fn frame_at(&self, elapsed: f64) -> u32 {
+        let Some(first) = self.frames.first() else {
+            return 0;
+        };
+        if self.duration <= 0.0 {
+            return first.texture_index;
+        }
+
+        let mut time = elapsed.rem_euclid(self.duration as f64) as f32;
+        for frame in self.frames.iter() {
+            if time < frame.duration {
+                return frame.texture_index;
+            }
+            time -= frame.duration.max(0.0);
+        }
+        // rounding at the very end of the loop
+        first.texture_index
+    }
+}
+
+///
+/// TilemapAnimations
+///
+/// The animations of a tilemap's tileset, by tile id, on the tilemap entity
+#[derive(Component, Debug, Default, Clone)]
+pub struct TilemapAnimations(HashMap<tiled::TileId, Arc<TileAnimation>>);
+
+impl TilemapAnimations {
+    pub fn insert(&mut self, id: tiled::TileId, animation: TileAnimation) {
+        self.0.insert(id, Arc::new(animation));
+    }
+
+    pub fn get(&self, id: tiled::TileId) -> Option<&TileAnimation> {
+        self.0.get(&id).map(Arc::as_ref)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+///
+/// TilesetTile
+///
+/// The tileset tile a tile entity shows. To swap a tile at runtime, change this along with its
+/// TileTextureIndex; the tile picks up (or drops) its animation on the next update. The id is
+/// within the tileset of the tile's tilemap.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TilesetTile(pub tiled::TileId);
+
+/// The animation currently driving a tile's TileTextureIndex
+#[derive(Component, Debug, Clone)]
+pub struct AnimatedTiledTile(Arc<TileAnimation>);
+
+/// The animations of a tileset, given the texture index of each of its tiles
+pub(super) fn tileset_animations(
+    tileset: &tiled::Tileset,
+    texture_index: impl Fn(tiled::TileId) -> Option<u32>,
+) -> TilemapAnimations {
+    let mut animations = TilemapAnimations::default();
+    for (id, tile) in tileset.tiles() {
+        let Some(frames) = &tile.animation else {
+            continue;
+        };
+        let frames = frames
+            .iter()
+            .filter_map(|frame| {
+                Some(TileFrame {
+                    texture_index: texture_index(frame.tile_id)?,
+                    duration: frame.duration as f32 / 1000.0,
+                })
+            })
+            .collect::<Vec<_>>();
+        if !frames.is_empty() {
+            animations.insert(id, TileAnimation::new(frames));
+        }
+    }
+    animations
+}
+
+///
+/// register_tile_animations: Bevy system
+///
+/// Attaches the tilemap's animation to tiles showing an animated tileset tile, including tiles
+/// spawned or swapped at runtime, and detaches it from tiles that no longer are
+pub fn register_tile_animations(
+    mut commands: Commands,
+    tile_query: Query<(Entity, &TilesetTile, &TilemapId), Changed<TilesetTile>>,
+    tilemap_query: Query<&TilemapAnimations>,
+) {
+    for (entity, tile, tilemap_id) in tile_query.iter() {
+        let animation = tilemap_query
+            .get(tilemap_id.0)
+            .ok()
+            .and_then(|animations| animations.0.get(&tile.0));
+        match animation {
+            Some(animation) => {
+                commands
+                    .entity(entity)
+                    .insert(AnimatedTiledTile(animation.clone()));
+            }
+            None => {
+                commands.entity(entity).remove::<AnimatedTiledTile>();
+            }
+        }
+    }
+}
+
+///
+/// animate_tiles: Bevy system
+///
+/// Shows the current frame of every animated tile. Runs on game time, so animations pause with it.
+pub fn animate_tiles(
+    time: Res<Time>,
+    mut tile_query: Query<(&AnimatedTiledTile, &mut TileTextureIndex)>,
+) {
+    let _span = crate::profiling::span("animate_tiles");
+    let elapsed = time.elapsed_seconds_f64();
+    for (animated, mut texture_index) in tile_query.iter_mut() {
+        let index = animated.0.frame_at(elapsed);
+        // only touch tiles whose frame changed, so the renderer re-extracts as little as possible
+        if texture_index.0 != index {
+            texture_index.0 = index;
+        }
+    }
+}
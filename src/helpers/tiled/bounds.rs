@@ -0,0 +1,299 @@
+// The area a map covers, for finite maps and for infinite maps made of chunks.
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::{
+    helpers::{hex_grid::neighbors::HexNeighbors, square_grid::neighbors::Neighbors},
+    prelude::*,
+};
+
+use super::TiledMap;
+
+///
+/// MapBounds
+///
+/// The tiles a map covers, in TMX tile coordinates (y down). Finite maps cover their declared
+/// width and height. Infinite maps cover the union of their non-empty chunks, which can start
+/// at negative coordinates. The map is spawned centred on the origin, whatever the transform of
+/// its map entity, so world_rect is where it is in world space too.
+///
+/// Isometric (diamond) maps project through Tiled's isometric grid. Hexagonal maps, pointy or
+/// flat topped, are measured from the top left of the box around their tiles, like Tiled does,
+/// and may sit up to a quarter tile off centre. Other orientations use the orthogonal
+/// conversions.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq)]
+pub struct MapBounds {
+    /// TMX coordinates of the top left tile
+    pub min: IVec2,
+    /// width and height in tiles
+    pub size: UVec2,
+    pub tile_size: Vec2,
+    /// bevy_ecs_tilemap's grid size, which differs from tile_size on hexagonal maps
+    pub grid_size: Vec2,
+    pub map_type: TilemapType,
+}
+
+/// The bevy_ecs_tilemap grid and grid size for a map of `size` tiles
+fn layout(map: &tiled::Map, hex_side_length: u32, size: UVec2) -> (TilemapType, Vec2) {
+    let tile_size = Vec2::new(map.tile_width as f32, map.tile_height as f32);
+    match map.orientation {
+        tiled::Orientation::Hexagonal => {
+            // Tiled steps (tile + side length) / 2 along the stagger axis, bevy_ecs_tilemap
+            // three quarters of the grid size
+            let step = |tile: f32| (tile + hex_side_length as f32) * 2.0 / 3.0;
+            let odd = matches!(map.stagger_index, tiled::StaggerIndex::Odd);
+            match map.stagger_axis {
+                tiled::StaggerAxis::Y => {
+                    // rows count up from the bottom here, which swaps which rows are shifted
+                    // when there's an even number of them
+                    let system = if odd == (size.y % 2 == 1) {
+                        HexCoordSystem::RowOdd
+                    } else {
+                        HexCoordSystem::RowEven
+                    };
+                    let grid_size = Vec2::new(tile_size.x, step(tile_size.y));
+                    (TilemapType::Hexagon(system), grid_size)
+                }
+                tiled::StaggerAxis::X => {
+                    // columns Tiled shifts down are the ones left low when the others shift up
+                    let system = if odd {
+                        HexCoordSystem::ColumnEven
+                    } else {
+                        HexCoordSystem::ColumnOdd
+                    };
+                    let grid_size = Vec2::new(step(tile_size.x), tile_size.y);
+                    (TilemapType::Hexagon(system), grid_size)
+                }
+            }
+        }
+        tiled::Orientation::Isometric => {
+            (TilemapType::Isometric(IsoCoordSystem::Diamond), tile_size)
+        }
+        tiled::Orientation::Staggered => {
+            (TilemapType::Isometric(IsoCoordSystem::Staggered), tile_size)
+        }
+        tiled::Orientation::Orthogonal => (TilemapType::Square, tile_size),
+    }
+}
+
+impl MapBounds {
+    pub fn of(tiled_map: &TiledMap) -> Self {
+        let map = &tiled_map.map;
+        let hex_side_length = tiled_map.tmx.hex_side_length;
+        let size = UVec2::new(map.width, map.height);
+        let (map_type, grid_size) = layout(map, hex_side_length, size);
+        let declared = MapBounds {
+            min: IVec2::ZERO,
+            size,
+            tile_size: Vec2::new(map.tile_width as f32, map.tile_height as f32),
+            grid_size,
+            map_type,
+        };
+        if !map.infinite() {
+            return declared;
+        }
+
+        let chunk_size = IVec2::new(
+            tiled::ChunkData::WIDTH as i32,
+            tiled::ChunkData::HEIGHT as i32,
+        );
+        let mut covered: Option<(IVec2, IVec2)> = None;
+        for layer in super::layers::resolve_layers(map) {
+            let Some(tiled::TileLayer::Infinite(layer)) = layer.layer.as_tile_layer() else {
+                continue;
+            };
+            for ((x, y), chunk) in layer.chunks() {
+                if !chunk_has_tiles(&chunk) {
+                    continue;
+                }
+                let start = IVec2::new(x, y) * chunk_size;
+                let end = start + chunk_size;
+                covered = Some(match covered {
+                    Some((min, max)) => (min.min(start), max.max(end)),
+                    None => (start, end),
+                });
+            }
+        }
+
+        match covered {
+            Some((min, max)) => {
+                let size = (max - min).as_uvec2();
+                let (map_type, grid_size) = layout(map, hex_side_length, size);
+                MapBounds {
+                    min,
+                    size,
+                    grid_size,
+                    map_type,
+                    ..declared
+                }
+            }
+            // nothing painted yet
+            None => declared,
+        }
+    }
+
+    pub fn tilemap_size(&self) -> TilemapSize {
+        TilemapSize {
+            x: self.size.x,
+            y: self.size.y,
+        }
+    }
+
+    pub fn tilemap_grid_size(&self) -> TilemapGridSize {
+        TilemapGridSize {
+            x: self.grid_size.x,
+            y: self.grid_size.y,
+        }
+    }
+
+    pub fn is_isometric(&self) -> bool {
+        self.map_type == TilemapType::Isometric(IsoCoordSystem::Diamond)
+    }
+
+    pub fn is_hexagonal(&self) -> bool {
+        matches!(self.map_type, TilemapType::Hexagon(_))
+    }
+
+    /// The area the map covers in world space. For isometric maps, the box around the diamond,
+    /// for hexagonal maps the box around the hexagons.
+    pub fn world_rect(&self) -> Rect {
+        if self.is_isometric() {
+            let across = (self.size.x + self.size.y) as f32 / 2.0;
+            return Rect::from_center_size(Vec2::ZERO, across * self.tile_size);
+        }
+        if self.is_hexagonal() && self.size.cmpgt(UVec2::ZERO).all() {
+            // the outermost tiles are along the edges, in either of the two shifts
+            let edge = |length: u32| [0, 1, length.saturating_sub(2), length - 1];
+            let mut rect: Option<Rect> = None;
+            for y in edge(self.size.y) {
+                for x in edge(self.size.x) {
+                    let x = x.min(self.size.x - 1);
+                    let y = y.min(self.size.y - 1);
+                    let tile =
+                        Rect::from_center_size(self.tile_center(TilePos { x, y }), self.tile_size);
+                    rect = Some(rect.map_or(tile, |rect| rect.union(tile)));
+                }
+            }
+            return rect.unwrap_or_default();
+        }
+        Rect::from_center_size(Vec2::ZERO, self.size.as_vec2() * self.tile_size)
+    }
+
+    /// Whether a world position is within world_rect, edges included. Nothing is on a map
+    /// without tiles.
+    pub fn contains(&self, position: Vec2) -> bool {
+        !self.is_empty() && self.world_rect().contains(position)
+    }
+
+    /// The point of world_rect closest to a world position, e.g. to keep a camera on the map.
+    /// Positions are left alone when there's no map.
+    pub fn clamp(&self, position: Vec2) -> Vec2 {
+        if self.is_empty() {
+            return position;
+        }
+        let rect = self.world_rect();
+        position.clamp(rect.min, rect.max)
+    }
+
+    /// Whether the map covers no tiles, like the bounds left when no map is loaded
+    pub fn is_empty(&self) -> bool {
+        self.size.cmpeq(UVec2::ZERO).any()
+    }
+
+    /// World position of the centre of a tile, by bevy_ecs_tilemap position
+    pub fn tile_center(&self, pos: TilePos) -> Vec2 {
+        let size = self.tilemap_size();
+        let grid_size = self.tilemap_grid_size();
+        let map = get_tilemap_center_transform(&size, &grid_size, &self.map_type, 0.0);
+        map.translation.truncate() + pos.center_in_world(&grid_size, &self.map_type)
+    }
+
+    /// The bevy_ecs_tilemap position of the tile at a world position, if it's on the map
+    pub fn tile_at(&self, position: Vec2) -> Option<TilePos> {
+        let size = self.tilemap_size();
+        let grid_size = self.tilemap_grid_size();
+        let map = get_tilemap_center_transform(&size, &grid_size, &self.map_type, 0.0);
+        let local = position - map.translation.truncate();
+        TilePos::from_world_pos(&local, &size, &grid_size, &self.map_type)
+    }
+
+    /// The tiles on the map sharing an edge with `pos`: up to six on hexagonal maps, four on
+    /// the others
+    pub fn neighbors(&self, pos: TilePos) -> Vec<TilePos> {
+        let size = self.tilemap_size();
+        match self.map_type {
+            TilemapType::Hexagon(system) => {
+                HexNeighbors::get_neighboring_positions(&pos, &size, &system)
+                    .iter()
+                    .copied()
+                    .collect()
+            }
+            _ => Neighbors::get_square_neighboring_positions(&pos, &size, false)
+                .iter()
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// The bevy_ecs_tilemap position (y up) of a tile at TMX coordinates, if it's in bounds
+    pub fn tile_pos(&self, x: i32, y: i32) -> Option<TilePos> {
+        let local = IVec2::new(x, y) - self.min;
+        if local.x < 0
+            || local.y < 0
+            || local.x >= self.size.x as i32
+            || local.y >= self.size.y as i32
+        {
+            return None;
+        }
+        Some(TilePos {
+            x: local.x as u32,
+            y: self.size.y - 1 - local.y as u32,
+        })
+    }
+
+    /// Converts a position in Tiled's pixel space (origin at the top left of tile (0, 0), y down)
+    /// to world space. Isometric maps measure both axes in tile heights from the top corner of
+    /// tile (0, 0), like Tiled does.
+    pub fn tmx_to_world(&self, x: f32, y: f32) -> Vec2 {
+        if self.is_isometric() {
+            let (width, height) = (self.size.x as f32, self.size.y as f32);
+            let u = x / self.tile_size.y - self.min.x as f32;
+            let v = y / self.tile_size.y - self.min.y as f32;
+            return Vec2::new(
+                (u - v + (height - width) / 2.0) * self.tile_size.x / 2.0,
+                ((height + width) / 2.0 - u - v) * self.tile_size.y / 2.0,
+            );
+        }
+        if self.is_hexagonal() {
+            let rect = self.world_rect();
+            let local = Vec2::new(x, y) - self.min.as_vec2() * self.hex_step();
+            return Vec2::new(rect.min.x + local.x, rect.max.y - local.y);
+        }
+        let min = self.min.as_vec2() * self.tile_size;
+        let half = self.size.as_vec2() * self.tile_size / 2.0;
+        Vec2::new(x - min.x - half.x, min.y + half.y - y)
+    }
+
+    // distance between the rows and columns of a hexagonal map in Tiled's pixel space
+    fn hex_step(&self) -> Vec2 {
+        match self.map_type {
+            TilemapType::Hexagon(HexCoordSystem::RowEven | HexCoordSystem::RowOdd) => {
+                Vec2::new(self.grid_size.x, self.grid_size.y * 0.75)
+            }
+            _ => Vec2::new(self.grid_size.x * 0.75, self.grid_size.y),
+        }
+    }
+
+    /// Converts a distance in Tiled's pixel space (y down) to world space
+    pub fn tmx_delta_to_world(&self, delta: Vec2) -> Vec2 {
+        if self.is_isometric() {
+            let (u, v) = (delta.x / self.tile_size.y, delta.y / self.tile_size.y);
+            return Vec2::new((u - v) * self.tile_size.x, -(u + v) * self.tile_size.y) / 2.0;
+        }
+        Vec2::new(delta.x, -delta.y)
+    }
+}
+
+fn chunk_has_tiles(chunk: &tiled::Chunk) -> bool {
+    (0..tiled::ChunkData::HEIGHT as i32)
+        .any(|y| (0..tiled::ChunkData::WIDTH as i32).any(|x| chunk.get_tile_data(x, y).is_some()))
+}
@@ -0,0 +1,296 @@
+// Spawning maps a few entities at a time, so a big map doesn't hitch the frame it loads in. The
+// map resources are all built the first frame; only the tile and object entities are spread out.
+
+use super::bounds::MapBounds;
+use super::edit::TilemapSource;
+use super::layer_markers::{LayerMarkerRegistry, LayerName, LayerTags};
+use super::layers::{self, TiledLayer};
+use super::objects::{self, TiledObjectRegistry};
+use super::properties::TilePropertyRegistry;
+use super::world::{TiledWorld, WorldMember, WorldMembers};
+use super::{
+    insert_parallax, lookup, minimap, nav, placed_shapes, spawn_tile, world_loaded, CollisionMap,
+    MapLoaded, PlacedTile, TiledLayersStorage, TiledMap,
+};
+use bevy::{prelude::*, utils::Instant};
+use bevy_ecs_tilemap::prelude::*;
+
+///
+/// MapSpawnBudget
+///
+/// How much of a map spawns per frame
+/// * Unlimited: all of it, the frame it loads. The default.
+/// * Entities: about this many tile and object entities a frame. Object layers spawn whole.
+/// * Millis: as many as fit in this many milliseconds a frame, at least one
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub enum MapSpawnBudget {
+    #[default]
+    Unlimited,
+    Entities(usize),
+    Millis(f32),
+}
+
+// what's left of the budget this frame
+struct FrameBudget {
+    budget: MapSpawnBudget,
+    start: Instant,
+    spawned: usize,
+}
+
+impl FrameBudget {
+    fn new(budget: MapSpawnBudget) -> Self {
+        FrameBudget {
+            budget,
+            start: Instant::now(),
+            spawned: 0,
+        }
+    }
+
+    fn is_spent(&self) -> bool {
+        match self.budget {
+            MapSpawnBudget::Unlimited => false,
+            MapSpawnBudget::Entities(entities) => self.spawned >= entities.max(1),
+            MapSpawnBudget::Millis(millis) => {
+                self.spawned > 0 && self.start.elapsed().as_secs_f32() * 1000.0 >= millis
+            }
+        }
+    }
+}
+
+///
+/// PendingTiles
+///
+/// On tilemaps whose tiles are still spawning under a MapSpawnBudget
+#[derive(Component, Debug, Default)]
+pub struct PendingTiles(Vec<PlacedTile>);
+
+impl PendingTiles {
+    pub(super) fn new(mut tiles: Vec<PlacedTile>) -> Self {
+        // popped from the back, so they spawn in order
+        tiles.reverse();
+        PendingTiles(tiles)
+    }
+
+    /// How many tiles are left to spawn
+    pub fn remaining(&self) -> usize {
+        self.0.len()
+    }
+}
+
+///
+/// MapSpawning
+///
+/// On map entities still spawning under a MapSpawnBudget, e.g. to show a loading indicator.
+/// Their layers stay hidden until it's gone.
+#[derive(Component, Debug)]
+pub struct MapSpawning {
+    bounds: MapBounds,
+    // object layers left to spawn, by index in drawing order, with their z
+    object_layers: Vec<(usize, f32)>,
+    // the layer entities spawned so far, with the visibility they get once the map is done
+    hidden: Vec<(Entity, Visibility)>,
+    loaded: Option<MapLoaded>,
+    reloaded: bool,
+}
+
+impl MapSpawning {
+    pub(super) fn new(bounds: MapBounds, reloaded: bool) -> Self {
+        MapSpawning {
+            bounds,
+            object_layers: Vec::new(),
+            hidden: Vec::new(),
+            loaded: None,
+            reloaded,
+        }
+    }
+
+    pub(super) fn defer_object_layer(&mut self, layer_index: usize, z: f32) {
+        self.object_layers.push((layer_index, z));
+    }
+
+    /// Hides the entities of a layer until the map is done
+    pub(super) fn hide(&mut self, commands: &mut Commands, entities: &[Entity], visible: bool) {
+        for entity in entities.iter() {
+            commands.entity(*entity).insert(Visibility::Hidden);
+            self.hidden.push((*entity, layers::visibility(visible)));
+        }
+    }
+
+    /// The MapLoaded to send once the map is done, None for the members of a world
+    pub(super) fn set_loaded(&mut self, loaded: Option<MapLoaded>) {
+        self.loaded = loaded;
+    }
+}
+
+type SpawningMap<'a> = (
+    Entity,
+    &'a Handle<TiledMap>,
+    &'a mut MapSpawning,
+    &'a mut TiledLayersStorage,
+    Option<&'a WorldMember>,
+);
+
+type PendingTilemap<'a> = (
+    Entity,
+    &'a TilemapSource,
+    &'a TiledLayer,
+    &'a LayerName,
+    &'a mut TileStorage,
+    &'a mut PendingTiles,
+);
+
+///
+/// spawn_map_batches: Bevy system
+///
+/// Spawns what the budget allows of maps still spawning: their tiles first, then their object
+/// layers. Once a map is done its layers are shown, the TileLookup and NavGrid rebuilt and
+/// MapLoaded sent, then for a world once its last member is done. A LoadMap in the meantime
+/// despawns the partial map like any other.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_map_batches(
+    mut commands: Commands,
+    budget: Res<MapSpawnBudget>,
+    maps: Res<Assets<TiledMap>>,
+    object_registry: Option<Res<TiledObjectRegistry>>,
+    tile_properties: Option<Res<TilePropertyRegistry>>,
+    marker_registry: Option<Res<LayerMarkerRegistry>>,
+    mut collision: ResMut<CollisionMap>,
+    bounds: Res<MapBounds>,
+    mut map_query: Query<SpawningMap>,
+    mut tilemap_query: Query<PendingTilemap>,
+    world_query: Query<(&WorldMembers, &Handle<TiledWorld>)>,
+    spawning_query: Query<(), With<MapSpawning>>,
+) {
+    let _span = crate::profiling::span("spawn_map_batches");
+    let mut budget = FrameBudget::new(*budget);
+    let mut finished = Vec::new();
+    let mut finished_worlds = Vec::new();
+
+    for (map_entity, map_handle, mut spawning, mut layer_storage, member) in map_query.iter_mut() {
+        if budget.is_spent() {
+            break;
+        }
+        let Some(tiled_map) = maps.get(map_handle) else {
+            continue;
+        };
+
+        let mut layer_indices = layer_storage.storage.keys().copied().collect::<Vec<_>>();
+        layer_indices.sort_unstable();
+        let mut tiles_left = false;
+        for layer_index in layer_indices {
+            for layer_entity in layer_storage.storage[&layer_index].iter() {
+                let Ok((tilemap, source, layer, layer_name, mut storage, mut pending)) =
+                    tilemap_query.get_mut(*layer_entity)
+                else {
+                    continue;
+                };
+                let color = TileColor(layer.color());
+                let tags = LayerTags {
+                    name: layer_name,
+                    registry: marker_registry.as_deref(),
+                };
+                while !budget.is_spent() {
+                    let Some(placed) = pending.0.pop() else {
+                        break;
+                    };
+                    let tile = tiled_map.map.tilesets()[placed.tileset_index].get_tile(placed.id);
+                    let shapes = placed_shapes(&collision, tile.as_ref(), &placed);
+                    let entity = spawn_tile(
+                        &mut commands,
+                        tiled_map,
+                        tile_properties.as_deref(),
+                        tilemap,
+                        source,
+                        &placed,
+                        color,
+                        shapes,
+                        tags,
+                    );
+                    storage.set(&placed.pos, entity);
+                    budget.spawned += 1;
+                }
+                if pending.0.is_empty() {
+                    commands.entity(tilemap).remove::<PendingTiles>();
+                } else {
+                    tiles_left = true;
+                }
+            }
+        }
+
+        if !tiles_left && !spawning.object_layers.is_empty() {
+            let resolved = layers::resolve_layers(&tiled_map.map);
+            while !budget.is_spent() && !spawning.object_layers.is_empty() {
+                let (layer_index, z) = spawning.object_layers.remove(0);
+                let layer = &resolved[layer_index];
+                let tiled::LayerType::Objects(object_layer) = layer.layer.layer_type() else {
+                    continue;
+                };
+                let bounds = spawning.bounds;
+                let entity = objects::spawn_object_layer(
+                    &mut commands,
+                    object_registry.as_deref(),
+                    &mut collision,
+                    &bounds,
+                    layer,
+                    &object_layer,
+                    &tiled_map.tmx,
+                    z,
+                );
+                budget.spawned += object_layer.objects().count().max(1);
+                insert_parallax(&mut commands, &bounds, layer, &[entity]);
+                let layer_name = LayerName::new(&layer.path);
+                LayerTags {
+                    name: &layer_name,
+                    registry: marker_registry.as_deref(),
+                }
+                .insert_all(&mut commands, &[entity]);
+                spawning.hide(&mut commands, &[entity], layer.visible);
+                layer_storage
+                    .storage
+                    .insert(layer_index as u32, vec![entity]);
+            }
+        }
+        if tiles_left || !spawning.object_layers.is_empty() {
+            continue;
+        }
+
+        for (entity, visibility) in spawning.hidden.drain(..) {
+            commands.entity(entity).insert(visibility);
+        }
+        commands.entity(map_entity).remove::<MapSpawning>();
+        commands.add(lookup::rebuild_tile_lookup);
+        commands.add(nav::rebuild_nav_grid);
+        commands.add(minimap::rebuild_minimap);
+        if let Some(loaded) = spawning.loaded.take() {
+            commands.add(move |world: &mut World| {
+                world.send_event(loaded);
+            });
+        }
+        finished.push(map_entity);
+        if let Some(member) = member {
+            match finished_worlds
+                .iter_mut()
+                .find(|(world, _)| *world == member.world)
+            {
+                Some((_, reloaded)) => *reloaded |= spawning.reloaded,
+                None => finished_worlds.push((member.world, spawning.reloaded)),
+            }
+        }
+    }
+
+    for (world_entity, reloaded) in finished_worlds {
+        let Ok((members, world_handle)) = world_query.get(world_entity) else {
+            continue;
+        };
+        let done = members
+            .entities()
+            .iter()
+            .all(|member| finished.contains(member) || !spawning_query.contains(*member));
+        if done {
+            let loaded = world_loaded(world_entity, Some(world_handle), &bounds, reloaded);
+            commands.add(move |world: &mut World| {
+                world.send_event(loaded);
+            });
+        }
+    }
+}
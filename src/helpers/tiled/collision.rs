@@ -0,0 +1,664 @@
+// Collision read from the map:
+//   * solid tiles: tiles with `collides = true`, and every tile on a layer named "collision"
+//     (in any group)
+//   * one-way platforms: tiles with `one_way = true`, solid only to things landing on them from
+//     above, on square maps
+//   * shapes: objects on an object layer named "colliders", and the shapes drawn on tiles in
+//     Tiled's tile collision editor
+
+use super::{MapBounds, ObjectShape};
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::{HexCoordSystem, TilePos, TilemapType};
+use std::f32::consts::TAU;
+
+/// Tile property that makes a tile solid
+pub const COLLIDES_PROPERTY: &str = "collides";
+/// Tile property that makes a tile a one-way platform
+pub const ONE_WAY_PROPERTY: &str = "one_way";
+/// Layer whose tiles are all solid
+pub const COLLISION_LAYER: &str = "collision";
+/// Object layer whose objects are collision shapes
+pub const COLLIDERS_LAYER: &str = "colliders";
+
+// how far below a one-way platform's top an edge can be and still be on it
+const LANDING_TOLERANCE: f32 = 1e-3;
+// vertices used to approximate ellipses
+pub(super) const ELLIPSE_SEGMENTS: usize = 16;
+
+/// Marks a solid tile entity
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct TileCollider;
+
+/// A collision shape in world space
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColliderShape {
+    Aabb(Rect),
+    Polygon(Vec<Vec2>),
+}
+
+impl ColliderShape {
+    // axis aligned shapes are kept as boxes, anything else as its outline
+    fn from_points(points: Vec<Vec2>, axis_aligned: bool) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        if axis_aligned {
+            let rect = points
+                .iter()
+                .fold(Rect::from_corners(points[0], points[0]), |rect, point| {
+                    rect.union_point(*point)
+                });
+            Some(ColliderShape::Aabb(rect))
+        } else {
+            Some(ColliderShape::Polygon(points))
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        match self {
+            ColliderShape::Aabb(rect) => *rect,
+            ColliderShape::Polygon(points) => points
+                .iter()
+                .fold(Rect::from_corners(points[0], points[0]), |rect, point| {
+                    rect.union_point(*point)
+                }),
+        }
+    }
+
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self {
+            ColliderShape::Aabb(rect) => rect.contains(point),
+            ColliderShape::Polygon(points) => polygon_contains(points, point),
+        }
+    }
+
+    pub fn intersects(&self, rect: Rect) -> bool {
+        if self.bounds().intersect(rect).is_empty() {
+            return false;
+        }
+        let ColliderShape::Polygon(points) = self else {
+            return true;
+        };
+        let corners = [
+            rect.min,
+            Vec2::new(rect.max.x, rect.min.y),
+            rect.max,
+            Vec2::new(rect.min.x, rect.max.y),
+        ];
+        points.iter().any(|point| rect.contains(*point))
+            || corners
+                .iter()
+                .any(|corner| polygon_contains(points, *corner))
+            || edges(points)
+                .any(|(a, b)| edges(&corners).any(|(c, d)| segments_intersect(a, b, c, d)))
+    }
+}
+
+fn edges(points: &[Vec2]) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+    points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| (*a, *b))
+}
+
+// even-odd rule, so concave outlines work too
+fn polygon_contains(points: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+    for (a, b) in edges(points) {
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn segments_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let side = |p: Vec2, q: Vec2, r: Vec2| (q - p).perp_dot(r - p);
+    let (d1, d2) = (side(c, d, a), side(c, d, b));
+    let (d3, d4) = (side(a, b, c), side(a, b, d));
+    d1 * d2 < 0.0 && d3 * d4 < 0.0
+}
+
+/// The collision shapes of a tile instance or a collider object, in world space
+#[derive(Component, Debug, Clone)]
+pub struct ShapeCollider {
+    pub shapes: Vec<ColliderShape>,
+}
+
+// how tile coordinates lie in the world
+#[derive(Debug, Default, Clone, Copy)]
+enum Grid {
+    #[default]
+    Square,
+    Isometric,
+    // laid out by bevy_ecs_tilemap, from the map's bounds
+    Hexagon(MapBounds),
+}
+
+///
+/// CollisionMap
+///
+/// Which tiles of the current map are solid, by tile coordinates (x right, y up, matching
+/// TilePos). Rebuilt whenever a map spawns and cleared when it goes away. On isometric maps
+/// the tile axes run along the diamond's edges, like bevy_ecs_tilemap's diamond grid, and on
+/// hexagonal maps they follow its hex grid.
+#[derive(Debug, Default, Resource)]
+pub struct CollisionMap {
+    size: UVec2,
+    tile_size: Vec2,
+    // world position of the bottom left corner of tile (0, 0), or its centre on other grids
+    origin: Vec2,
+    grid: Grid,
+    solid: Vec<u64>,
+    one_way: Vec<u64>,
+    shapes: Vec<ColliderShape>,
+}
+
+impl CollisionMap {
+    /// Empties the map and resizes it for a map of `size` tiles
+    pub fn reset(&mut self, size: UVec2, tile_size: Vec2, origin: Vec2) {
+        self.size = size;
+        self.tile_size = tile_size;
+        self.origin = origin;
+        self.grid = Grid::Square;
+        self.solid.clear();
+        self.solid
+            .resize(((size.x * size.y) as usize).div_ceil(64), 0);
+        self.one_way.clear();
+        self.one_way.resize(self.solid.len(), 0);
+        self.shapes.clear();
+    }
+
+    /// Like reset, for an isometric map whose tile (0, 0) is centred on `origin`
+    pub fn reset_isometric(&mut self, size: UVec2, tile_size: Vec2, origin: Vec2) {
+        self.reset(size, tile_size, origin);
+        self.grid = Grid::Isometric;
+    }
+
+    /// Like reset, for the hexagonal map covering `bounds`
+    pub fn reset_hexagonal(&mut self, bounds: &MapBounds) {
+        let origin = bounds.tile_center(TilePos { x: 0, y: 0 });
+        self.reset(bounds.size, bounds.tile_size, origin);
+        self.grid = Grid::Hexagon(*bounds);
+    }
+
+    pub fn is_isometric(&self) -> bool {
+        matches!(self.grid, Grid::Isometric)
+    }
+
+    pub fn is_hexagonal(&self) -> bool {
+        matches!(self.grid, Grid::Hexagon(_))
+    }
+
+    pub fn clear(&mut self) {
+        self.reset(UVec2::ZERO, Vec2::ZERO, Vec2::ZERO);
+    }
+
+    /// Size of the map in tiles
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    pub fn tile_size(&self) -> Vec2 {
+        self.tile_size
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.size.x || y as u32 >= self.size.y {
+            return None;
+        }
+        Some(y as usize * self.size.x as usize + x as usize)
+    }
+
+    pub fn set_solid(&mut self, x: i32, y: i32, solid: bool) {
+        let Some(index) = self.index(x, y) else {
+            return;
+        };
+        if solid {
+            self.solid[index / 64] |= 1 << (index % 64);
+        } else {
+            self.solid[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    /// Whether a tile is solid. Tiles outside the map aren't.
+    pub fn is_solid(&self, x: i32, y: i32) -> bool {
+        self.index(x, y)
+            .is_some_and(|index| self.solid[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    pub fn set_one_way(&mut self, x: i32, y: i32, one_way: bool) {
+        let Some(index) = self.index(x, y) else {
+            return;
+        };
+        if one_way {
+            self.one_way[index / 64] |= 1 << (index % 64);
+        } else {
+            self.one_way[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    /// Whether a tile is a one-way platform. They aren't solid, overlaps passes them over.
+    pub fn is_one_way(&self, x: i32, y: i32) -> bool {
+        self.index(x, y)
+            .is_some_and(|index| self.one_way[index / 64] & (1 << (index % 64)) != 0)
+    }
+
+    /// The top of the highest one-way platform the bottom edge of a world space rectangle moves
+    /// onto going down by `drop`, from above it. One the edge starts on counts, one it starts
+    /// below doesn't, so things jump up through them. Always None on other than square maps.
+    pub fn one_way_landing(&self, rect: Rect, drop: f32) -> Option<f32> {
+        if !matches!(self.grid, Grid::Square) || self.tile_size.y <= 0.0 || drop < 0.0 {
+            return None;
+        }
+        let local = |y: f32| (y - self.origin.y) / self.tile_size.y;
+        // rows whose top edge is within (min.y - drop, min.y], give or take rounding, so what
+        // landed on one stays on it
+        let highest = local(rect.min.y + LANDING_TOLERANCE).floor() as i32 - 1;
+        let lowest = local(rect.min.y - drop).floor() as i32;
+        // columns the rectangle reaches into, not just touches
+        let first = ((rect.min.x - self.origin.x) / self.tile_size.x).floor() as i32;
+        let last = ((rect.max.x - self.origin.x) / self.tile_size.x).ceil() as i32 - 1;
+        (lowest..=highest)
+            .rev()
+            .find(|y| (first..=last).any(|x| self.is_one_way(x, *y)))
+            .map(|y| self.origin.y + (y + 1) as f32 * self.tile_size.y)
+    }
+
+    /// The tile coordinates containing a world position, which may lie outside the map. On
+    /// hexagonal maps, positions off the map are all at (-1, -1).
+    pub fn tile_at(&self, position: Vec2) -> IVec2 {
+        if self.tile_size.x <= 0.0 || self.tile_size.y <= 0.0 {
+            return IVec2::ZERO;
+        }
+        let local = (position - self.origin) / self.tile_size;
+        match &self.grid {
+            Grid::Square => local.floor().as_ivec2(),
+            Grid::Isometric => {
+                // inverse of the diamond projection, tile centres at whole coordinates
+                let grid = Vec2::new(local.x - local.y, local.x + local.y);
+                (grid + 0.5).floor().as_ivec2()
+            }
+            Grid::Hexagon(bounds) => bounds.tile_at(position).map_or(IVec2::splat(-1), |pos| {
+                IVec2::new(pos.x as i32, pos.y as i32)
+            }),
+        }
+    }
+
+    pub fn add_shape(&mut self, shape: ColliderShape) {
+        self.shapes.push(shape);
+    }
+
+    /// Removes a shape added with add_shape, e.g. that of a tile removed at runtime
+    pub fn remove_shape(&mut self, shape: &ColliderShape) {
+        if let Some(index) = self.shapes.iter().position(|other| other == shape) {
+            self.shapes.remove(index);
+        }
+    }
+
+    /// Clears the solid tiles and removes the shapes centred within a world space rectangle,
+    /// e.g. the part of a world one of its maps covers
+    pub fn clear_rect(&mut self, rect: Rect) {
+        for y in 0..self.size.y as i32 {
+            for x in 0..self.size.x as i32 {
+                if rect.contains(self.tile_rect(x, y).center()) {
+                    self.set_solid(x, y, false);
+                    self.set_one_way(x, y, false);
+                }
+            }
+        }
+        self.shapes
+            .retain(|shape| !rect.contains(shape.bounds().center()));
+    }
+
+    /// Every collision shape, from collider objects and tile collision shapes
+    pub fn shapes(&self) -> &[ColliderShape] {
+        &self.shapes
+    }
+
+    /// Whether a world position is inside a solid tile or a collision shape
+    pub fn is_solid_at(&self, position: Vec2) -> bool {
+        let tile = self.tile_at(position);
+        self.is_solid(tile.x, tile.y) || self.shapes.iter().any(|shape| shape.contains(position))
+    }
+
+    /// Whether a world space rectangle overlaps a solid tile or a collision shape. Touching an
+    /// edge doesn't count.
+    pub fn overlaps(&self, rect: Rect) -> bool {
+        match self.grid {
+            Grid::Square => {}
+            Grid::Isometric => return self.overlaps_isometric(rect),
+            Grid::Hexagon(_) => return self.overlaps_hexagonal(rect),
+        }
+        let min = self.tile_at(rect.min);
+        let mut max = self.tile_at(rect.max);
+        // a rectangle ending exactly on a tile edge doesn't reach into the next tile
+        let end = self.tile_rect(max.x, max.y).min;
+        if rect.max.x <= end.x && max.x > min.x {
+            max.x -= 1;
+        }
+        if rect.max.y <= end.y && max.y > min.y {
+            max.y -= 1;
+        }
+        let tiles = (min.y..=max.y).any(|y| (min.x..=max.x).any(|x| self.is_solid(x, y)));
+        tiles || self.shapes.iter().any(|shape| shape.intersects(rect))
+    }
+
+    // the rectangle's corners give the range of diamonds it can touch
+    fn overlaps_isometric(&self, rect: Rect) -> bool {
+        let corners = [
+            self.tile_at(rect.min),
+            self.tile_at(Vec2::new(rect.max.x, rect.min.y)),
+            self.tile_at(rect.max),
+            self.tile_at(Vec2::new(rect.min.x, rect.max.y)),
+        ];
+        let min = corners.iter().fold(corners[0], |a, b| a.min(*b));
+        let max = corners.iter().fold(corners[0], |a, b| a.max(*b));
+        let tiles = (min.y..=max.y).any(|y| {
+            (min.x..=max.x).any(|x| self.is_solid(x, y) && self.tile_intersects(x, y, rect))
+        });
+        tiles || self.shapes.iter().any(|shape| shape.intersects(rect))
+    }
+
+    // hexagonal rows don't line up with world axes, so check every solid tile
+    fn overlaps_hexagonal(&self, rect: Rect) -> bool {
+        let tiles = self
+            .solid_tiles()
+            .any(|tile| self.tile_intersects(tile.x, tile.y, rect));
+        tiles || self.shapes.iter().any(|shape| shape.intersects(rect))
+    }
+
+    fn tile_intersects(&self, x: i32, y: i32, rect: Rect) -> bool {
+        !self.tile_rect(x, y).intersect(rect).is_empty()
+            && ColliderShape::Polygon(self.tile_outline(x, y)).intersects(rect)
+    }
+
+    /// The world space rectangle a tile covers. For isometric and hexagonal maps, the box
+    /// around its diamond or hexagon.
+    pub fn tile_rect(&self, x: i32, y: i32) -> Rect {
+        if let Grid::Square = self.grid {
+            let min = self.origin + Vec2::new(x as f32, y as f32) * self.tile_size;
+            return Rect::from_corners(min, min + self.tile_size);
+        }
+        Rect::from_center_size(self.tile_center(x, y), self.tile_size)
+    }
+
+    /// The corners of a tile, counter-clockwise: a diamond on isometric maps and a hexagon on
+    /// hexagonal ones
+    pub fn tile_outline(&self, x: i32, y: i32) -> Vec<Vec2> {
+        let rect = self.tile_rect(x, y);
+        let (center, half) = (rect.center(), rect.half_size());
+        match &self.grid {
+            Grid::Square => vec![
+                rect.min,
+                Vec2::new(rect.max.x, rect.min.y),
+                rect.max,
+                Vec2::new(rect.min.x, rect.max.y),
+            ],
+            Grid::Isometric => vec![
+                center - Vec2::Y * half.y,
+                center + Vec2::X * half.x,
+                center + Vec2::Y * half.y,
+                center - Vec2::X * half.x,
+            ],
+            Grid::Hexagon(bounds) => {
+                // half of Tiled's hex side length, from the grid bevy_ecs_tilemap steps by
+                let pointy = matches!(
+                    bounds.map_type,
+                    TilemapType::Hexagon(HexCoordSystem::RowEven | HexCoordSystem::RowOdd)
+                );
+                let corners = if pointy {
+                    let side = (bounds.grid_size.y * 1.5 - bounds.tile_size.y) / 2.0;
+                    [
+                        Vec2::new(0.0, -half.y),
+                        Vec2::new(half.x, -side),
+                        Vec2::new(half.x, side),
+                        Vec2::new(0.0, half.y),
+                        Vec2::new(-half.x, side),
+                        Vec2::new(-half.x, -side),
+                    ]
+                } else {
+                    let side = (bounds.grid_size.x * 1.5 - bounds.tile_size.x) / 2.0;
+                    [
+                        Vec2::new(-side, -half.y),
+                        Vec2::new(side, -half.y),
+                        Vec2::new(half.x, 0.0),
+                        Vec2::new(side, half.y),
+                        Vec2::new(-side, half.y),
+                        Vec2::new(-half.x, 0.0),
+                    ]
+                };
+                corners.iter().map(|corner| center + *corner).collect()
+            }
+        }
+    }
+
+    fn tile_center(&self, x: i32, y: i32) -> Vec2 {
+        match &self.grid {
+            Grid::Square => self.tile_rect(x, y).center(),
+            Grid::Isometric => {
+                let (x, y) = (x as f32, y as f32);
+                self.origin + Vec2::new(x + y, y - x) * self.tile_size / 2.0
+            }
+            Grid::Hexagon(bounds) => bounds.tile_center(TilePos {
+                x: x.max(0) as u32,
+                y: y.max(0) as u32,
+            }),
+        }
+    }
+
+    /// Coordinates of every solid tile
+    pub fn solid_tiles(&self) -> impl Iterator<Item = IVec2> + '_ {
+        let width = self.size.x.max(1) as i32;
+        (0..(self.size.x * self.size.y) as i32)
+            .map(move |index| IVec2::new(index % width, index / width))
+            .filter(move |tile| self.is_solid(tile.x, tile.y))
+    }
+}
+
+/// Whether a spawned tile is solid, from its layer and properties
+pub(super) fn is_solid_tile(layer: &tiled::Layer, tile: Option<&tiled::Tile>) -> bool {
+    is_collision_layer(layer) || tile_collides(tile)
+}
+
+/// Whether every tile of a layer is solid
+pub(super) fn is_collision_layer(layer: &tiled::Layer) -> bool {
+    layer.name.eq_ignore_ascii_case(COLLISION_LAYER)
+}
+
+/// Whether a tileset tile is solid wherever it's placed
+pub(super) fn tile_collides(tile: Option<&tiled::Tile>) -> bool {
+    tile.is_some_and(|tile| {
+        matches!(
+            tile.properties.get(COLLIDES_PROPERTY),
+            Some(tiled::PropertyValue::BoolValue(true))
+        )
+    })
+}
+
+/// Whether a tileset tile is a one-way platform wherever it's placed
+pub(super) fn tile_is_one_way(tile: Option<&tiled::Tile>) -> bool {
+    tile.is_some_and(|tile| {
+        matches!(
+            tile.properties.get(ONE_WAY_PROPERTY),
+            Some(tiled::PropertyValue::BoolValue(true))
+        )
+    })
+}
+
+/// Outline of an object's shape around its origin, in Tiled's y down pixel space. Returns the
+/// points and whether they form an axis aligned box.
+fn object_outline(object: &tiled::ObjectData) -> (Vec<Vec2>, bool) {
+    let (points, boxed) = match &object.shape {
+        tiled::ObjectShape::Rect { width, height } => (
+            vec![
+                Vec2::ZERO,
+                Vec2::new(*width, 0.0),
+                Vec2::new(*width, *height),
+                Vec2::new(0.0, *height),
+            ],
+            true,
+        ),
+        tiled::ObjectShape::Ellipse { width, height } => {
+            let radius = Vec2::new(*width, *height) / 2.0;
+            let points = (0..ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * TAU;
+                    radius + Vec2::from_angle(angle) * radius
+                })
+                .collect();
+            (points, false)
+        }
+        tiled::ObjectShape::Polygon { points } => (
+            points.iter().map(|(x, y)| Vec2::new(*x, *y)).collect(),
+            false,
+        ),
+        _ => (Vec::new(), false),
+    };
+
+    // clockwise on screen, which is counter-clockwise maths in y down space
+    let rotation = Vec2::from_angle(object.rotation.to_radians());
+    let origin = Vec2::new(object.x, object.y);
+    let points = points
+        .into_iter()
+        .map(|point| origin + rotation.rotate(point))
+        .collect();
+    (points, boxed && object.rotation == 0.0)
+}
+
+///
+/// ShapeFlip
+///
+/// The flips of a tile instance, which Tiled applies diagonal first, then horizontal, then
+/// vertical
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct ShapeFlip {
+    pub horizontal: bool,
+    pub vertical: bool,
+    pub diagonal: bool,
+}
+
+/// The collision editor shapes of a tile placed over `rect`, mirrored to match the flips of
+/// that instance
+pub(super) fn tile_shapes(tile: &tiled::Tile, rect: Rect, flip: ShapeFlip) -> Vec<ColliderShape> {
+    let Some(collision) = &tile.collision else {
+        return Vec::new();
+    };
+    let size = rect.size();
+    let to_world = |mut point: Vec2| {
+        if flip.diagonal {
+            point = Vec2::new(point.y, point.x);
+        }
+        if flip.horizontal {
+            point.x = size.x - point.x;
+        }
+        if flip.vertical {
+            point.y = size.y - point.y;
+        }
+        Vec2::new(rect.min.x + point.x, rect.max.y - point.y)
+    };
+
+    collision
+        .object_data()
+        .iter()
+        .filter_map(|object| {
+            let (points, boxed) = object_outline(object);
+            ColliderShape::from_points(points.into_iter().map(to_world).collect(), boxed)
+        })
+        .collect()
+}
+
+/// The collision shape of an object on the colliders layer, from its world transform
+pub(super) fn object_collider(
+    transform: &GlobalTransform,
+    shape: &ObjectShape,
+) -> Option<ColliderShape> {
+    let (points, boxed) = match shape {
+        ObjectShape::Rect { size } => {
+            let half = *size / 2.0;
+            (
+                vec![
+                    -half,
+                    Vec2::new(half.x, -half.y),
+                    half,
+                    Vec2::new(-half.x, half.y),
+                ],
+                true,
+            )
+        }
+        ObjectShape::Ellipse { size } => {
+            let radius = *size / 2.0;
+            let points = (0..ELLIPSE_SEGMENTS)
+                .map(|i| Vec2::from_angle(i as f32 / ELLIPSE_SEGMENTS as f32 * TAU) * radius)
+                .collect();
+            (points, false)
+        }
+        ObjectShape::Polygon { points } => (points.clone(), false),
+        ObjectShape::Point | ObjectShape::Polyline { .. } => return None,
+    };
+
+    let (_, rotation, _) = transform.to_scale_rotation_translation();
+    let boxed = boxed && rotation.angle_between(Quat::IDENTITY) < 1e-4;
+    let points = points
+        .into_iter()
+        .map(|point| transform.transform_point(point.extend(0.0)).truncate())
+        .collect();
+    ColliderShape::from_points(points, boxed)
+}
+
+/// Whether an object layer holds collision shapes
+pub(super) fn is_colliders_layer(layer: &tiled::Layer) -> bool {
+    layer.name.eq_ignore_ascii_case(COLLIDERS_LAYER)
+}
+
+/// Resets the CollisionMap for a map about to spawn
+pub(super) fn reset_for_map(collision: &mut CollisionMap, bounds: &MapBounds) {
+    if bounds.is_isometric() {
+        let origin = bounds.tile_center(TilePos { x: 0, y: 0 });
+        collision.reset_isometric(bounds.size, bounds.tile_size, origin);
+    } else if bounds.is_hexagonal() {
+        collision.reset_hexagonal(bounds);
+    } else {
+        collision.reset(bounds.size, bounds.tile_size, bounds.world_rect().min);
+    }
+}
+
+/// Draws solid tiles while enabled
+#[cfg(feature = "dev")]
+#[derive(Debug, Default, Resource)]
+pub struct CollisionDebug(pub bool);
+
+///
+/// draw_collision_gizmos: Bevy system
+///
+/// Outlines every solid tile while CollisionDebug is on. F4 toggles it.
+#[cfg(feature = "dev")]
+pub fn draw_collision_gizmos(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut debug: ResMut<CollisionDebug>,
+    collision: Res<CollisionMap>,
+    mut gizmos: Gizmos,
+) {
+    if keys.is_some_and(|keys| keys.just_pressed(KeyCode::F4)) {
+        debug.0 = !debug.0;
+    }
+    if !debug.0 {
+        return;
+    }
+    for tile in collision.solid_tiles() {
+        let outline = collision.tile_outline(tile.x, tile.y);
+        gizmos.linestrip_2d(outline.iter().chain(outline.first()).copied(), Color::RED);
+    }
+    for shape in collision.shapes() {
+        match shape {
+            ColliderShape::Aabb(rect) => {
+                gizmos.rect_2d(rect.center(), 0.0, rect.size(), Color::ORANGE);
+            }
+            ColliderShape::Polygon(points) => {
+                gizmos.linestrip_2d(points.iter().chain(points.first()).copied(), Color::ORANGE);
+            }
+        }
+    }
+}
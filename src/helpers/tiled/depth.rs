@@ -0,0 +1,94 @@
+// Where map layers sit in z: tile layers, image layers and object layers alike, objects' sprites
+// being children of their layer.
+
+use super::layers::ResolvedLayer;
+use super::properties::{float_property, string_property};
+use crate::gfx::SpriteLayer;
+use bevy::{log, prelude::*};
+
+/// Layer property putting the layer in a SpriteLayer band, e.g. `z_band = "above_actors"`
+pub const Z_BAND_PROPERTY: &str = "z_band";
+/// Layer property giving the layer its own z, e.g. `z = 250`
+pub const Z_PROPERTY: &str = "z";
+
+///
+/// LayerDepth
+///
+/// * Band: in a SpriteLayer band, at the band's z plus the layer's index in drawing order
+/// * Z: at exactly this z
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayerDepth {
+    Band(SpriteLayer),
+    Z(f32),
+}
+
+///
+/// LayerDepths
+///
+/// On the map entity, chosen with TiledMapBundle::depths: the depth of layers by name or path.
+/// Layers it doesn't name use their `z` or `z_band` property, then their index in drawing order,
+/// which puts them in SpriteLayer::Background as before.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct LayerDepths {
+    layers: Vec<(String, LayerDepth)>,
+}
+
+impl LayerDepths {
+    /// Puts the layer called `layer` (its name or its path) in a band
+    pub fn with_band(self, layer: impl Into<String>, band: SpriteLayer) -> Self {
+        self.with_depth(layer, LayerDepth::Band(band))
+    }
+
+    /// Puts the layer called `layer` (its name or its path) at exactly `z`
+    pub fn with_z(self, layer: impl Into<String>, z: f32) -> Self {
+        self.with_depth(layer, LayerDepth::Z(z))
+    }
+
+    pub fn with_depth(mut self, layer: impl Into<String>, depth: LayerDepth) -> Self {
+        let layer = layer.into();
+        self.layers.retain(|(name, _)| *name != layer);
+        self.layers.push((layer, depth));
+        self
+    }
+
+    /// The depth of a layer, None when it's left to its properties
+    pub fn get(&self, name: &str, path: &str) -> Option<LayerDepth> {
+        // a path names the layer more precisely than its name
+        self.layers
+            .iter()
+            .find(|(layer, _)| layer == path)
+            .or_else(|| self.layers.iter().find(|(layer, _)| layer == name))
+            .map(|(_, depth)| *depth)
+    }
+
+    /// The z of the layer at `layer_index` in drawing order
+    pub(super) fn z(&self, layer: &ResolvedLayer, layer_index: usize) -> f32 {
+        let depth = self
+            .get(&layer.layer.name, &layer.path)
+            .or_else(|| layer_depth(layer));
+        match depth {
+            Some(LayerDepth::Z(z)) => z,
+            Some(LayerDepth::Band(band)) => band.z() + layer_index as f32,
+            None => layer_index as f32,
+        }
+    }
+}
+
+/// The depth a layer's properties give it
+fn layer_depth(layer: &ResolvedLayer) -> Option<LayerDepth> {
+    let properties = &layer.layer.properties;
+    if let Some(z) = float_property(properties, Z_PROPERTY) {
+        return Some(LayerDepth::Z(z));
+    }
+    let name = string_property(properties, Z_BAND_PROPERTY)?;
+    let band = SpriteLayer::from_name(name);
+    if band.is_none() {
+        log::warn!(
+            "Layer {} has an unknown {}: {}",
+            layer.path,
+            Z_BAND_PROPERTY,
+            name
+        );
+    }
+    band.map(LayerDepth::Band)
+}
@@ -0,0 +1,262 @@
+// Doors between maps: objects of type "door" are trigger regions that fade out, load their
+// target map, start the player at the target spawn point and fade back in. The player and camera
+// aren't part of the map, so they live through the switch. RestartMap goes the same way, back
+// into the current map, and ChangeMap to any other.
+
+use super::properties::{bool_property, string_property};
+use super::{
+    CurrentMap, LoadMap, MapLoaded, PlacedAtSpawn, TiledMap, TiledObject, TriggerEntered,
+    TriggerOccupancy,
+};
+use crate::gfx::ScreenFade;
+use bevy::{asset::LoadState, log, prelude::*};
+
+/// Object type of doors
+pub const DOOR_TYPE: &str = "door";
+/// Key that opens doors with `requires_interact`
+pub const INTERACT_KEY: KeyCode = KeyCode::KeyE;
+/// Seconds to fade out and back in through a door
+const DOOR_FADE: f32 = 0.3;
+
+///
+/// Door
+///
+/// On door objects, from their properties:
+/// * target_map: path of the map it leads to, relative to the assets directory
+/// * target_spawn: the spawn point to start at there, DEFAULT_SPAWN when unset
+/// * requires_interact: whether the player has to press INTERACT_KEY inside, instead of just
+///   walking in
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct Door {
+    pub target_map: String,
+    pub target_spawn: Option<String>,
+    pub requires_interact: bool,
+}
+
+/// The door a door object describes, if it names its target map
+pub(super) fn door(object: &TiledObject) -> Option<Door> {
+    let Some(target_map) = string_property(&object.properties, "target_map") else {
+        log::warn!("Door {} ({}) has no target_map", object.name, object.id);
+        return None;
+    };
+    Some(Door {
+        target_map: target_map.to_string(),
+        target_spawn: string_property(&object.properties, "target_spawn").map(str::to_string),
+        requires_interact: bool_property(&object.properties, "requires_interact").unwrap_or(false),
+    })
+}
+
+///
+/// DoorTransition
+///
+/// Where the trip through a door is at. Doors can't be used until it's Idle again, so a player
+/// arriving inside a door on the other side has to step out and back in to use it.
+/// * FadingOut: fading out while the target map loads
+/// * Loading: LoadMap sent, waiting for MapLoaded
+/// * FadingIn: on the new map, or back on the old one if the target failed to load
+#[derive(Resource, Debug, Default)]
+pub enum DoorTransition {
+    #[default]
+    Idle,
+    FadingOut {
+        door: Door,
+        map: Handle<TiledMap>,
+    },
+    Loading {
+        map: Handle<TiledMap>,
+    },
+    FadingIn,
+}
+
+impl DoorTransition {
+    pub fn is_idle(&self) -> bool {
+        matches!(self, DoorTransition::Idle)
+    }
+}
+
+/// Starts the current map over from its default spawn point, fading out and back in as through
+/// a door. Ignored while a DoorTransition is going.
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct RestartMap;
+
+/// Goes to `target_map`, at the `target_spawn` there or DEFAULT_SPAWN, fading out and back in as
+/// through a door. Ignored while a DoorTransition is going.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct ChangeMap {
+    pub target_map: String,
+    pub target_spawn: Option<String>,
+}
+
+impl ChangeMap {
+    pub fn new(target_map: impl Into<String>) -> Self {
+        ChangeMap {
+            target_map: target_map.into(),
+            target_spawn: None,
+        }
+    }
+
+    pub fn with_spawn(mut self, spawn: impl Into<String>) -> Self {
+        self.target_spawn = Some(spawn.into());
+        self
+    }
+}
+
+///
+/// restart_map: Bevy system
+///
+/// Handles RestartMap with a DoorTransition into the current map
+pub fn restart_map(
+    mut events: EventReader<RestartMap>,
+    current: Res<CurrentMap>,
+    asset_server: Res<AssetServer>,
+    mut transition: ResMut<DoorTransition>,
+    fade: Option<ResMut<ScreenFade>>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Some(path) = current.path() else {
+        log::warn!("RestartMap: no map is loaded");
+        return;
+    };
+    if !transition.is_idle() {
+        log::warn!("RestartMap: a map transition is going already");
+        return;
+    }
+
+    let door = Door {
+        target_map: path.to_string(),
+        target_spawn: None,
+        requires_interact: false,
+    };
+    start_transition(door, &asset_server, &mut transition, fade);
+}
+
+///
+/// change_map: Bevy system
+///
+/// Handles ChangeMap with a DoorTransition into its map, the last one sent winning
+pub fn change_map(
+    mut events: EventReader<ChangeMap>,
+    asset_server: Res<AssetServer>,
+    mut transition: ResMut<DoorTransition>,
+    fade: Option<ResMut<ScreenFade>>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    if !transition.is_idle() {
+        log::warn!("ChangeMap: a map transition is going already");
+        return;
+    }
+
+    let door = Door {
+        target_map: event.target_map.clone(),
+        target_spawn: event.target_spawn.clone(),
+        requires_interact: false,
+    };
+    start_transition(door, &asset_server, &mut transition, fade);
+}
+
+/// Fades out while `door`'s target map loads
+fn start_transition(
+    door: Door,
+    asset_server: &AssetServer,
+    transition: &mut DoorTransition,
+    fade: Option<ResMut<ScreenFade>>,
+) {
+    if let Some(mut fade) = fade {
+        fade.fade_out(DOOR_FADE);
+    }
+    // loaded ahead, so a missing map is found before the current one is torn down
+    let map = asset_server.load(door.target_map.clone());
+    *transition = DoorTransition::FadingOut { door, map };
+}
+
+///
+/// use_doors: Bevy system
+///
+/// Starts a DoorTransition when the player (the PlacedAtSpawn TriggerSensor) walks into a door,
+/// or presses INTERACT_KEY inside one that requires it
+#[allow(clippy::too_many_arguments)]
+pub fn use_doors(
+    mut entered: EventReader<TriggerEntered>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    occupancy: Res<TriggerOccupancy>,
+    door_query: Query<(Entity, &Door)>,
+    player_query: Query<(), With<PlacedAtSpawn>>,
+    asset_server: Res<AssetServer>,
+    mut transition: ResMut<DoorTransition>,
+    fade: Option<ResMut<ScreenFade>>,
+) {
+    let walked_into: Vec<Entity> = entered
+        .read()
+        .filter(|event| player_query.contains(event.entity))
+        .map(|event| event.region)
+        .collect();
+    if !transition.is_idle() {
+        return;
+    }
+
+    let interact = keys.is_some_and(|keys| keys.just_pressed(INTERACT_KEY));
+    let used = door_query.iter().find(|(region, door)| {
+        if door.requires_interact {
+            interact
+                && occupancy
+                    .occupants(*region)
+                    .any(|entity| player_query.contains(entity))
+        } else {
+            walked_into.contains(region)
+        }
+    });
+    let Some((_, door)) = used else {
+        return;
+    };
+    start_transition(door.clone(), &asset_server, &mut transition, fade);
+}
+
+///
+/// advance_door_transition: Bevy system
+///
+/// Moves the DoorTransition along as the fade finishes and the target map loads. A target map
+/// that fails to load logs an error and fades back in on the current map, player untouched.
+pub fn advance_door_transition(
+    mut loaded: EventReader<MapLoaded>,
+    mut load_map: EventWriter<LoadMap>,
+    asset_server: Res<AssetServer>,
+    mut transition: ResMut<DoorTransition>,
+    mut fade: Option<ResMut<ScreenFade>>,
+) {
+    let map_loaded = loaded.read().count() > 0;
+    let faded = fade.as_ref().is_none_or(|fade| fade.is_done());
+
+    let next = match &*transition {
+        DoorTransition::FadingOut { door, map } => match asset_server.load_state(map.id()) {
+            LoadState::Failed => {
+                log::error!("Door target map {} failed to load", door.target_map);
+                Some(DoorTransition::FadingIn)
+            }
+            LoadState::Loaded if faded => {
+                let mut load = LoadMap::new(door.target_map.clone());
+                if let Some(spawn) = &door.target_spawn {
+                    load = load.with_spawn(spawn.clone());
+                }
+                load_map.send(load);
+                Some(DoorTransition::Loading { map: map.clone() })
+            }
+            _ => None,
+        },
+        DoorTransition::Loading { .. } if map_loaded => Some(DoorTransition::FadingIn),
+        DoorTransition::FadingIn if faded => Some(DoorTransition::Idle),
+        _ => None,
+    };
+    let Some(next) = next else {
+        return;
+    };
+    if matches!(next, DoorTransition::FadingIn) {
+        if let Some(fade) = fade.as_mut() {
+            fade.fade_in(DOOR_FADE);
+        }
+    }
+    *transition = next;
+}
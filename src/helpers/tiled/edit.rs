@@ -0,0 +1,261 @@
+// Changing tiles after a map has spawned: SetTile swaps, places or removes a tile and patches
+// the collision data, the tile's property components and (through its TileStorage) the
+// TileLookup to match.
+
+use super::collision::{self, CollisionMap, ShapeCollider, TileCollider};
+use super::layer_markers::{LayerMarkerRegistry, LayerName, LayerTags};
+use super::layers::TiledLayer;
+use super::properties::{self, TilePropertyRegistry};
+use super::{TiledMap, TilesetTile};
+use bevy::{log, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+
+///
+/// TilemapSource
+///
+/// On the tilemaps of tile layers: the map and tileset their tiles come from, for SetTile
+#[derive(Component, Debug, Clone)]
+pub struct TilemapSource {
+    pub map: Handle<TiledMap>,
+    pub tileset_index: usize,
+    /// whether the layer is the collision layer, making all its tiles solid
+    pub collision_layer: bool,
+}
+
+///
+/// SetTile
+///
+/// Changes the tile at `pos` on the tile layer with that name or path. `tile_id` is a tile of
+/// the layer's tileset, or of the tileset named with with_tileset when the layer uses several;
+/// None removes the tile. Of several edits to the same tile in a frame, the last one wins.
+#[derive(Event, Debug, Clone)]
+pub struct SetTile {
+    pub layer: String,
+    pub pos: TilePos,
+    pub tile_id: Option<u32>,
+    pub flip: TileFlip,
+    pub tileset: Option<String>,
+}
+
+impl SetTile {
+    pub fn new(layer: impl Into<String>, pos: TilePos, tile_id: u32) -> Self {
+        SetTile {
+            layer: layer.into(),
+            pos,
+            tile_id: Some(tile_id),
+            flip: TileFlip::default(),
+            tileset: None,
+        }
+    }
+
+    pub fn remove(layer: impl Into<String>, pos: TilePos) -> Self {
+        SetTile {
+            layer: layer.into(),
+            pos,
+            tile_id: None,
+            flip: TileFlip::default(),
+            tileset: None,
+        }
+    }
+
+    pub fn with_flip(mut self, flip: TileFlip) -> Self {
+        self.flip = flip;
+        self
+    }
+
+    pub fn with_tileset(mut self, tileset: impl Into<String>) -> Self {
+        self.tileset = Some(tileset.into());
+        self
+    }
+}
+
+///
+/// apply_tile_edits: Bevy system
+///
+/// Handles SetTile. Tiles that stay put keep their entity and only have their components
+/// swapped, so the tilemap's TileStorage only changes when tiles come or go.
+#[allow(clippy::too_many_arguments)]
+pub fn apply_tile_edits(
+    mut commands: Commands,
+    mut events: EventReader<SetTile>,
+    maps: Res<Assets<TiledMap>>,
+    registry: Option<Res<TilePropertyRegistry>>,
+    marker_registry: Option<Res<LayerMarkerRegistry>>,
+    mut collision: ResMut<CollisionMap>,
+    mut tilemap_query: Query<(Entity, &TiledLayer, &TilemapSource, &mut TileStorage)>,
+    shape_query: Query<&ShapeCollider>,
+    collider_query: Query<(), With<TileCollider>>,
+    name_query: Query<&LayerName>,
+) {
+    let edits: Vec<&SetTile> = events.read().collect();
+    for (index, edit) in edits.iter().enumerate() {
+        let superseded = edits[index + 1..]
+            .iter()
+            .any(|later| later.layer == edit.layer && later.pos == edit.pos);
+        if superseded {
+            continue;
+        }
+
+        let mut targets: Vec<(Entity, usize, Handle<TiledMap>)> = tilemap_query
+            .iter()
+            .filter(|(_, layer, _, _)| layer.matches(&edit.layer))
+            .map(|(entity, _, source, _)| (entity, source.tileset_index, source.map.clone()))
+            .collect();
+        if targets.is_empty() {
+            log::warn!("SetTile: there's no tile layer {}", edit.layer);
+            continue;
+        }
+        targets.sort_by_key(|(_, tileset_index, _)| *tileset_index);
+        let Some(tiled_map) = maps.get(&targets[0].2) else {
+            continue;
+        };
+        let size = tilemap_query.get(targets[0].0).unwrap().3.size;
+        if edit.pos.x >= size.x || edit.pos.y >= size.y {
+            log::warn!(
+                "SetTile: ({}, {}) is off layer {}",
+                edit.pos.x,
+                edit.pos.y,
+                edit.layer
+            );
+            continue;
+        }
+
+        // the tilemap the new tile goes on, and its index in its texture
+        let placed = match edit.tile_id {
+            None => None,
+            Some(id) => {
+                let target = targets.iter().find(|(_, tileset_index, _)| {
+                    edit.tileset
+                        .as_ref()
+                        .is_none_or(|name| tiled_map.map.tilesets()[*tileset_index].name == *name)
+                });
+                let Some((tilemap, tileset_index, _)) = target else {
+                    log::warn!(
+                        "SetTile: layer {} has no tiles from tileset {}",
+                        edit.layer,
+                        edit.tileset.as_deref().unwrap_or_default()
+                    );
+                    continue;
+                };
+                let Some(texture_index) = tiled_map.texture_index(*tileset_index, id) else {
+                    log::warn!(
+                        "SetTile: tile {} isn't in the tileset of layer {}",
+                        id,
+                        edit.layer
+                    );
+                    continue;
+                };
+                Some((*tilemap, *tileset_index, id, texture_index))
+            }
+        };
+
+        // whatever other tilemaps of the layer have there goes
+        for (tilemap, _, _) in targets.iter() {
+            if placed.is_some_and(|(placed, ..)| placed == *tilemap) {
+                continue;
+            }
+            let (_, _, _, mut storage) = tilemap_query.get_mut(*tilemap).unwrap();
+            // checked first, so tilemaps with nothing there aren't marked changed
+            let Some(tile) = storage.get(&edit.pos) else {
+                continue;
+            };
+            if let Ok(shapes) = shape_query.get(tile) {
+                for shape in shapes.shapes.iter() {
+                    collision.remove_shape(shape);
+                }
+            }
+            storage.remove(&edit.pos);
+            commands.entity(tile).despawn_recursive();
+        }
+
+        let (x, y) = (edit.pos.x as i32, edit.pos.y as i32);
+        let mut solid = false;
+        let mut one_way = false;
+        if let Some((tilemap, tileset_index, id, texture_index)) = placed {
+            let (_, layer, source, mut storage) = tilemap_query.get_mut(tilemap).unwrap();
+            let tileset = &tiled_map.map.tilesets()[tileset_index];
+            let tile = tileset.get_tile(id);
+            solid = source.collision_layer || collision::tile_collides(tile.as_ref());
+            one_way = !solid && collision::tile_is_one_way(tile.as_ref());
+
+            // the existing tile entity, without its old shapes and properties
+            let existing = storage.get(&edit.pos);
+            let mut entity = match existing {
+                Some(existing) => {
+                    if let Ok(shapes) = shape_query.get(existing) {
+                        for shape in shapes.shapes.iter() {
+                            collision.remove_shape(shape);
+                        }
+                    }
+                    let mut entity = commands.entity(existing);
+                    entity
+                        .insert((TileTextureIndex(texture_index), edit.flip, TilesetTile(id)))
+                        .remove::<(ShapeCollider, TileCollider)>();
+                    entity
+                }
+                None => {
+                    let mut entity = commands.spawn((
+                        TileBundle {
+                            position: edit.pos,
+                            tilemap_id: TilemapId(tilemap),
+                            texture_index: TileTextureIndex(texture_index),
+                            flip: edit.flip,
+                            color: TileColor(layer.color()),
+                            ..Default::default()
+                        },
+                        TilesetTile(id),
+                    ));
+                    if let Ok(layer_name) = name_query.get(tilemap) {
+                        LayerTags {
+                            name: layer_name,
+                            registry: marker_registry.as_deref(),
+                        }
+                        .insert(&mut entity);
+                    }
+                    // update_tile_properties only handles tiles that were already there
+                    if let Some(tile) = &tile {
+                        if !tile.properties.is_empty() {
+                            properties::insert_tile_properties(
+                                &mut entity,
+                                registry.as_deref(),
+                                &tile.properties,
+                            );
+                        }
+                    }
+                    storage.set(&edit.pos, entity.id());
+                    entity
+                }
+            };
+
+            if solid {
+                entity.insert(TileCollider);
+            }
+            if let Some(tile) = &tile {
+                let shapes = collision::tile_shapes(
+                    tile,
+                    collision.tile_rect(x, y),
+                    collision::ShapeFlip {
+                        horizontal: edit.flip.x,
+                        vertical: edit.flip.y,
+                        diagonal: edit.flip.d,
+                    },
+                );
+                if !shapes.is_empty() {
+                    for shape in shapes.iter() {
+                        collision.add_shape(shape.clone());
+                    }
+                    entity.insert(ShapeCollider { shapes });
+                }
+            }
+        }
+
+        // solid tiles on other layers keep the spot solid
+        let solid_elsewhere = tilemap_query
+            .iter()
+            .filter(|(entity, ..)| !targets.iter().any(|(target, ..)| target == entity))
+            .filter_map(|(_, _, _, storage)| storage.get(&edit.pos))
+            .any(|tile| collider_query.contains(tile));
+        collision.set_solid(x, y, solid || solid_elsewhere);
+        collision.set_one_way(x, y, one_way && !solid_elsewhere);
+    }
+}
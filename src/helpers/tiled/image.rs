@@ -0,0 +1,91 @@
+// Image layers: one sprite per layer, tiled across the map bounds on the axes the layer repeats.
+
+use super::bounds::MapBounds;
+use super::layers::{self, ResolvedLayer, TiledLayer};
+use bevy::{
+    prelude::*,
+    sprite::{Anchor, ImageScaleMode},
+};
+
+/// The image of an image layer, loaded with the map
+#[derive(Debug, Clone)]
+pub struct ImageLayerTexture {
+    pub texture: Handle<Image>,
+    /// in pixels
+    pub size: Vec2,
+    /// Tiled's repeatx and repeaty
+    pub repeat: BVec2,
+}
+
+/// Marks the sprite spawned for an image layer
+#[derive(Component, Debug, Default)]
+pub struct TiledImageLayer;
+
+/// Start and length along one axis of an image repeated to cover `from..to`, aligned on `offset`
+fn repeat_span(offset: f32, length: f32, from: f32, to: f32) -> (f32, f32) {
+    if length <= 0.0 {
+        return (offset, length);
+    }
+    let start = offset - ((offset - from) / length).ceil() * length;
+    let span = ((to - start) / length).ceil() * length;
+    (start, span)
+}
+
+/// Spawns the sprite of an image layer, returning its entity
+pub(super) fn spawn_image_layer(
+    commands: &mut Commands,
+    bounds: &MapBounds,
+    layer: &ResolvedLayer,
+    image: &ImageLayerTexture,
+    z: f32,
+) -> Entity {
+    // in Tiled's pixel space, like the layer offset. Isometric and hexagonal maps measure image
+    // layers from the top left of the box around their tiles.
+    let (map_min, map_max) = if bounds.is_isometric() || bounds.is_hexagonal() {
+        (Vec2::ZERO, bounds.world_rect().size())
+    } else {
+        let min = bounds.min.as_vec2() * bounds.tile_size;
+        (min, min + bounds.size.as_vec2() * bounds.tile_size)
+    };
+    let (mut start, mut size) = (layer.offset, image.size);
+    if image.repeat.x {
+        (start.x, size.x) = repeat_span(layer.offset.x, image.size.x, map_min.x, map_max.x);
+    }
+    if image.repeat.y {
+        (start.y, size.y) = repeat_span(layer.offset.y, image.size.y, map_min.y, map_max.y);
+    }
+
+    let top_left = if bounds.is_isometric() || bounds.is_hexagonal() {
+        let rect = bounds.world_rect();
+        Vec2::new(rect.min.x + start.x, rect.max.y - start.y)
+    } else {
+        bounds.tmx_to_world(start.x, start.y)
+    };
+
+    let tiled_layer = TiledLayer::new(layer);
+    let mut entity = commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                color: tiled_layer.color(),
+                custom_size: Some(size),
+                anchor: Anchor::TopLeft,
+                ..default()
+            },
+            texture: image.texture.clone(),
+            transform: Transform::from_translation(top_left.extend(z)),
+            visibility: layers::visibility(layer.visible),
+            ..default()
+        },
+        Name::new(layer.path.clone()),
+        tiled_layer,
+        TiledImageLayer,
+    ));
+    if image.repeat.any() {
+        entity.insert(ImageScaleMode::Tiled {
+            tile_x: image.repeat.x,
+            tile_y: image.repeat.y,
+            stretch_value: 1.0,
+        });
+    }
+    entity.id()
+}
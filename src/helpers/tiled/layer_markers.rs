@@ -0,0 +1,138 @@
+// Marker components for the entities of a layer, so games can query the tiles of a layer by type
+// instead of comparing layer names.
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use std::sync::Arc;
+
+///
+/// LayerName
+///
+/// On every tile, and every entity spawned for a layer: the layer's path, its own name for layers
+/// outside groups, e.g. "gameplay/water"
+#[derive(Component, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct LayerName(pub Arc<str>);
+
+impl LayerName {
+    pub(super) fn new(path: &str) -> Self {
+        LayerName(path.into())
+    }
+
+    pub fn path(&self) -> &str {
+        &self.0
+    }
+
+    /// The layer's own name, without its groups
+    pub fn name(&self) -> &str {
+        self.0.rsplit('/').next().unwrap_or_default()
+    }
+
+    /// Whether `name` is the layer's own name or its full path
+    pub fn matches(&self, name: &str) -> bool {
+        self.path() == name || self.name() == name
+    }
+}
+
+type MarkerInserter = Box<dyn Fn(&mut EntityCommands) + Send + Sync>;
+
+///
+/// LayerMarkerRegistry
+///
+/// Marker components for layers, see App::register_layer_marker
+#[derive(Default, Resource)]
+pub struct LayerMarkerRegistry {
+    markers: Vec<(String, MarkerInserter)>,
+}
+
+impl LayerMarkerRegistry {
+    /// Inserts `C::default()` on the tiles and entities of the layer called `layer`
+    pub fn register<C: Component + Default>(&mut self, layer: impl Into<String>) {
+        self.markers.push((
+            layer.into(),
+            Box::new(|commands| {
+                commands.insert(C::default());
+            }),
+        ));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+}
+
+pub trait RegisterLayerMarker {
+    /// Inserts a marker component on every tile of the layer called `layer` (its name or its
+    /// path), and on the entities spawned for the layer: its tilemaps, or the parent of its
+    /// objects. E.g. `app.register_layer_marker::<WaterLayer>("water")` to query
+    /// `Query<&TilePos, With<WaterLayer>>`. Tiles placed by SetTile or streamed back in get it
+    /// too.
+    fn register_layer_marker<C: Component + Default>(
+        &mut self,
+        layer: impl Into<String>,
+    ) -> &mut Self;
+}
+
+impl RegisterLayerMarker for App {
+    fn register_layer_marker<C: Component + Default>(
+        &mut self,
+        layer: impl Into<String>,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(LayerMarkerRegistry::default)
+            .register::<C>(layer);
+        self
+    }
+}
+
+///
+/// LayerTags
+///
+/// The LayerName and registered markers of one layer, for the code spawning its entities
+#[derive(Clone, Copy)]
+pub(super) struct LayerTags<'a> {
+    pub name: &'a LayerName,
+    pub registry: Option<&'a LayerMarkerRegistry>,
+}
+
+impl<'a> LayerTags<'a> {
+    pub fn insert(&self, entity: &mut EntityCommands) {
+        entity.insert(self.name.clone());
+        for (layer, insert) in self
+            .registry
+            .iter()
+            .flat_map(|registry| registry.markers.iter())
+        {
+            if self.name.matches(layer) {
+                insert(entity);
+            }
+        }
+    }
+
+    /// Tags the entities spawned for the layer
+    pub fn insert_all(&self, commands: &mut Commands, entities: &[Entity]) {
+        for entity in entities.iter() {
+            self.insert(&mut commands.entity(*entity));
+        }
+    }
+}
+
+///
+/// apply_layer_markers: Bevy system
+///
+/// Inserts the markers registered after a map spawned on the entities of its layers. Entities
+/// spawned from then on get them as they spawn.
+pub fn apply_layer_markers(
+    mut commands: Commands,
+    registry: Option<Res<LayerMarkerRegistry>>,
+    layer_query: Query<(Entity, &LayerName)>,
+) {
+    let Some(registry) = registry.filter(|registry| registry.is_changed()) else {
+        return;
+    };
+    for (entity, name) in layer_query.iter() {
+        LayerTags {
+            name,
+            registry: Some(&*registry),
+        }
+        .insert(&mut commands.entity(entity));
+    }
+}
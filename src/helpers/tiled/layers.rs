@@ -0,0 +1,217 @@
+// Per-layer appearance read from the map, and the events that change it at runtime.
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+///
+/// TiledLayer
+///
+/// On every entity spawned for a layer: the tilemaps of tile layers and the parents of objects.
+/// Opacity and tint include those of the groups the layer is in.
+#[derive(Component, Debug, Clone)]
+pub struct TiledLayer {
+    pub name: String,
+    /// the names of the groups the layer is in and its own, e.g. "gameplay/collision"
+    pub path: String,
+    pub opacity: f32,
+    /// Tiled's tint color, white when the layer has none
+    pub tint: Color,
+}
+
+impl TiledLayer {
+    pub(super) fn new(layer: &ResolvedLayer) -> Self {
+        TiledLayer {
+            name: layer.layer.name.clone(),
+            path: layer.path.clone(),
+            opacity: layer.opacity,
+            tint: layer.tint,
+        }
+    }
+
+    /// Whether `name` is the layer's own name or its full path
+    pub fn matches(&self, name: &str) -> bool {
+        self.name == name || self.path == name
+    }
+
+    /// The color tiles of the layer are drawn with: the tint, faded by the opacity
+    pub fn color(&self) -> Color {
+        let color = self.tint;
+        color.with_a(color.a() * self.opacity)
+    }
+}
+
+///
+/// HiddenLayers
+///
+/// What to do with layers hidden in Tiled
+/// * Spawn: spawn them with Visibility::Hidden, so data-only layers (collision, spawn points)
+///   still work. The default.
+/// * Skip: don't spawn them at all
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum HiddenLayers {
+    #[default]
+    Spawn,
+    Skip,
+}
+
+///
+/// ResolvedLayer
+///
+/// A layer with the groups it's nested in folded in: offsets add up, opacities, tints and
+/// parallax factors multiply, and a layer is only visible if all of its groups are
+#[derive(Clone)]
+pub(super) struct ResolvedLayer<'map> {
+    pub layer: tiled::Layer<'map>,
+    pub path: String,
+    /// in Tiled's pixel space (y down)
+    pub offset: Vec2,
+    pub opacity: f32,
+    pub visible: bool,
+    pub tint: Color,
+    pub parallax: Vec2,
+}
+
+/// Every layer of the map that isn't a group, depth first in drawing order
+pub(super) fn resolve_layers(map: &tiled::Map) -> Vec<ResolvedLayer<'_>> {
+    let mut resolved = Vec::new();
+    for layer in map.layers() {
+        resolve_layer(layer, None, &mut resolved);
+    }
+    resolved
+}
+
+fn resolve_layer<'map>(
+    layer: tiled::Layer<'map>,
+    parent: Option<&ResolvedLayer<'map>>,
+    resolved: &mut Vec<ResolvedLayer<'map>>,
+) {
+    let tint = layer.tint_color.map_or(Color::WHITE, |tint| {
+        Color::rgba_u8(tint.red, tint.green, tint.blue, tint.alpha)
+    });
+    let mut current = ResolvedLayer {
+        path: layer.name.clone(),
+        offset: Vec2::new(layer.offset_x, layer.offset_y),
+        opacity: layer.opacity,
+        visible: layer.visible,
+        tint,
+        parallax: Vec2::new(layer.parallax_x, layer.parallax_y),
+        layer,
+    };
+    if let Some(parent) = parent {
+        current.path = format!("{}/{}", parent.path, current.path);
+        current.offset += parent.offset;
+        current.opacity *= parent.opacity;
+        current.visible &= parent.visible;
+        current.tint = multiply(current.tint, parent.tint);
+        current.parallax *= parent.parallax;
+    }
+
+    match current.layer.layer_type() {
+        tiled::LayerType::Group(group) => {
+            for child in group.layers() {
+                resolve_layer(child, Some(&current), resolved);
+            }
+        }
+        _ => resolved.push(current),
+    }
+}
+
+fn multiply(a: Color, b: Color) -> Color {
+    Color::rgba(a.r() * b.r(), a.g() * b.g(), a.b() * b.b(), a.a() * b.a())
+}
+
+/// Shows or hides every layer named `layer_name` (or with that path), e.g. to cut away a roof
+#[derive(Event, Debug, Clone)]
+pub struct SetLayerVisibility {
+    pub layer_name: String,
+    pub visible: bool,
+}
+
+impl SetLayerVisibility {
+    pub fn new(layer_name: impl Into<String>, visible: bool) -> Self {
+        SetLayerVisibility {
+            layer_name: layer_name.into(),
+            visible,
+        }
+    }
+}
+
+/// Tints every layer named `layer_name` (or with that path), replacing the tint it had. Opacity still applies.
+#[derive(Event, Debug, Clone)]
+pub struct SetLayerTint {
+    pub layer_name: String,
+    pub color: Color,
+}
+
+impl SetLayerTint {
+    pub fn new(layer_name: impl Into<String>, color: Color) -> Self {
+        SetLayerTint {
+            layer_name: layer_name.into(),
+            color,
+        }
+    }
+}
+
+pub(super) fn visibility(visible: bool) -> Visibility {
+    if visible {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    }
+}
+
+///
+/// set_layer_visibility: Bevy system
+///
+/// Handles SetLayerVisibility
+pub fn set_layer_visibility(
+    mut events: EventReader<SetLayerVisibility>,
+    mut layer_query: Query<(&TiledLayer, &mut Visibility)>,
+) {
+    for event in events.read() {
+        for (layer, mut layer_visibility) in layer_query.iter_mut() {
+            if layer.matches(&event.layer_name) {
+                *layer_visibility = visibility(event.visible);
+            }
+        }
+    }
+}
+
+///
+/// set_layer_tint: Bevy system
+///
+/// Handles SetLayerTint
+pub fn set_layer_tint(
+    mut events: EventReader<SetLayerTint>,
+    mut layer_query: Query<&mut TiledLayer>,
+) {
+    for event in events.read() {
+        for mut layer in layer_query.iter_mut() {
+            if layer.matches(&event.layer_name) {
+                layer.tint = event.color;
+            }
+        }
+    }
+}
+
+///
+/// apply_layer_colors: Bevy system
+///
+/// Recolors the tiles of tile layers and the sprites of image layers whose TiledLayer changed
+pub fn apply_layer_colors(
+    layer_query: Query<(&TiledLayer, &TileStorage), Changed<TiledLayer>>,
+    mut tile_query: Query<&mut TileColor>,
+    mut image_query: Query<(&TiledLayer, &mut Sprite), Changed<TiledLayer>>,
+) {
+    for (layer, mut sprite) in image_query.iter_mut() {
+        sprite.color = layer.color();
+    }
+    for (layer, storage) in layer_query.iter() {
+        let color = layer.color();
+        for tile in storage.iter().flatten() {
+            if let Ok(mut tile_color) = tile_query.get_mut(*tile) {
+                tile_color.0 = color;
+            }
+        }
+    }
+}
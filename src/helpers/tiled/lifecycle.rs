@@ -0,0 +1,219 @@
+// Switching maps: LoadMap replaces the current map, UnloadMap tears it down.
+
+use super::{
+    despawn_layers, nav, CollisionMap, MapBounds, MapMarkers, MapProperties, SpawnPointName,
+    TileEditLog, TiledLayersStorage, TiledMap, TiledMapBundle, TiledWorldBundle, WorldMember,
+    WorldMembers,
+};
+use bevy::{log, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+
+/// Replaces the current map with the map at `path` (relative to the assets directory). Paths
+/// ending in .world load a Tiled world, whose markers are named after their member map, e.g.
+/// `LoadMap::new("overworld.world").with_spawn("town/spawn")`.
+#[derive(Event, Debug, Clone)]
+pub struct LoadMap {
+    pub path: String,
+    /// the marker to start at, DEFAULT_SPAWN when None
+    pub spawn: Option<String>,
+}
+
+impl LoadMap {
+    pub fn new(path: impl Into<String>) -> Self {
+        LoadMap {
+            path: path.into(),
+            spawn: None,
+        }
+    }
+
+    /// Starts at the marker called `spawn` instead
+    pub fn with_spawn(mut self, spawn: impl Into<String>) -> Self {
+        self.spawn = Some(spawn.into());
+        self
+    }
+}
+
+/// Tears down the current map, or only the member map called `member` when the current map is
+/// a world
+#[derive(Event, Debug, Default, Clone)]
+pub struct UnloadMap {
+    pub member: Option<String>,
+}
+
+impl UnloadMap {
+    /// Unloads the member of the current world called `name`, e.g. "town" for town.tmx
+    pub fn member(name: impl Into<String>) -> Self {
+        UnloadMap {
+            member: Some(name.into()),
+        }
+    }
+}
+
+/// Sent once the entities of a map torn down by LoadMap or UnloadMap are gone and the map
+/// resources are cleared, before the next map (if any) starts loading. Also sent for a member
+/// unloaded from a world, with the member's path.
+#[derive(Event, Debug, Clone)]
+pub struct MapUnloaded {
+    pub path: String,
+}
+
+///
+/// CurrentMap
+///
+/// The map entity spawned by the last LoadMap, and the path it was loaded from
+#[derive(Resource, Debug, Default)]
+pub struct CurrentMap {
+    entity: Option<Entity>,
+    path: Option<String>,
+}
+
+impl CurrentMap {
+    pub fn entity(&self) -> Option<Entity> {
+        self.entity
+    }
+
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+}
+
+///
+/// handle_map_requests: Bevy system
+///
+/// Despawns everything belonging to the current map before spawning the next one: its tiles,
+/// tilemaps, objects and colliders, and anything the game parented to the map entity.
+/// Only the last LoadMap of a frame is loaded, and a LoadMap wins over an UnloadMap sent in the
+/// same frame. Unloading a member of a world leaves MapBounds covering the whole world.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_map_requests(
+    mut commands: Commands,
+    mut load_events: EventReader<LoadMap>,
+    mut unload_events: EventReader<UnloadMap>,
+    asset_server: Res<AssetServer>,
+    mut current: ResMut<CurrentMap>,
+    mut collision: ResMut<CollisionMap>,
+    mut bounds: ResMut<MapBounds>,
+    mut markers: ResMut<MapMarkers>,
+    mut map_properties: ResMut<MapProperties>,
+    mut edit_log: ResMut<TileEditLog>,
+    mut map_query: Query<&mut TiledLayersStorage, With<Handle<TiledMap>>>,
+    mut world_query: Query<&mut WorldMembers>,
+    member_query: Query<(&WorldMember, &Handle<TiledMap>)>,
+    tile_storage_query: Query<(Entity, &TileStorage)>,
+) {
+    let load = load_events.read().last().cloned();
+    let mut unload = false;
+    let mut unload_members = Vec::new();
+    for event in unload_events.read() {
+        match &event.member {
+            Some(member) => unload_members.push(member.clone()),
+            None => unload = true,
+        }
+    }
+    if load.is_none() && !unload && unload_members.is_empty() {
+        return;
+    }
+    if load.is_none() && !unload {
+        if let Some(mut members) = current
+            .entity
+            .and_then(|entity| world_query.get_mut(entity).ok())
+        {
+            for name in unload_members {
+                let Some((entity, member, map_handle)) =
+                    members.entities().iter().find_map(|entity| {
+                        let (member, map_handle) = member_query.get(*entity).ok()?;
+                        (member.name == name).then_some((*entity, member, map_handle))
+                    })
+                else {
+                    log::warn!("The current world has no map called {}", name);
+                    continue;
+                };
+                if let Ok(mut layer_storage) = map_query.get_mut(entity) {
+                    despawn_layers(&mut commands, &mut layer_storage, &tile_storage_query);
+                }
+                commands.entity(entity).despawn_recursive();
+                members.remove(entity);
+                collision.clear_rect(member.rect());
+                markers.remove_member(&member.name);
+                let path = map_handle
+                    .path()
+                    .map(|path| path.to_string())
+                    .unwrap_or_default();
+                log::info!("Unloaded world map: {}", path);
+                // the TileLookup follows the despawned tilemaps on its own
+                commands.add(nav::rebuild_nav_grid);
+                commands.add(move |world: &mut World| {
+                    world.send_event(MapUnloaded { path });
+                });
+            }
+        } else {
+            log::warn!("Only members of a world can be unloaded on their own");
+        }
+        return;
+    }
+
+    if let Some(entity) = current.entity.take() {
+        if let Ok(mut layer_storage) = map_query.get_mut(entity) {
+            despawn_layers(&mut commands, &mut layer_storage, &tile_storage_query);
+        }
+        if let Ok(members) = world_query.get(entity) {
+            for member in members.entities() {
+                if let Ok(mut layer_storage) = map_query.get_mut(*member) {
+                    despawn_layers(&mut commands, &mut layer_storage, &tile_storage_query);
+                }
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+        let path = current.path.take().unwrap_or_default();
+        log::info!("Unloaded map: {}", path);
+        // queued behind the despawns, like MapLoaded
+        commands.add(move |world: &mut World| {
+            world.send_event(MapUnloaded { path });
+        });
+    }
+    collision.clear();
+    *bounds = MapBounds::default();
+    markers.clear();
+    map_properties.clear();
+    edit_log.clear();
+
+    if let Some(LoadMap { path, spawn }) = load {
+        let mut entity = if path.ends_with(".world") {
+            commands.spawn(TiledWorldBundle {
+                world: asset_server.load(path.clone()),
+                ..Default::default()
+            })
+        } else {
+            commands.spawn(TiledMapBundle {
+                tiled_map: asset_server.load(path.clone()),
+                ..Default::default()
+            })
+        };
+        if let Some(spawn) = spawn {
+            entity.insert(SpawnPointName(spawn));
+        }
+        let entity = entity.id();
+        current.entity = Some(entity);
+        current.path = Some(path);
+    }
+}
+
+/// load_map for the console
+#[cfg(feature = "dev")]
+pub(super) fn register_console_commands(app: &mut App) {
+    use crate::console::RegisterConsoleCommand;
+
+    app.register_console_command(
+        "load_map <path> [spawn]",
+        "Switches to a map, at the marker called spawn if given",
+        |args, world| {
+            let path: String = args.required("path")?;
+            let mut load_map = LoadMap::new(path.clone());
+            if let Some(spawn) = args.optional::<String>("spawn")? {
+                load_map = load_map.with_spawn(spawn);
+            }
+            world.send_event(load_map);
+            Ok(format!("Loading {}", path))
+        },
+    );
+}
@@ -0,0 +1,147 @@
+// Conversions between world positions and map tiles, and the tile entities of every tile layer
+// by position, kept in step with the map.
+
+use super::bounds::MapBounds;
+use super::layers::TiledLayer;
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+
+#[derive(Debug, Clone)]
+struct LookupLayer {
+    name: String,
+    path: String,
+    width: u32,
+    // by y * width + x, merged across the tilemaps of the layer's tilesets
+    tiles: Vec<Option<Entity>>,
+}
+
+///
+/// TileLookup
+///
+/// Tile positions are bevy_ecs_tilemap's (y up, from the bottom left tile) and world positions
+/// are for the map spawned centred on the origin, through the map's orientation. Rebuilt when a
+/// map loads, before MapLoaded, and whenever a tilemap's TileStorage changes, so tiles swapped
+/// at runtime show up too.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct TileLookup {
+    bounds: MapBounds,
+    layers: Vec<LookupLayer>,
+}
+
+impl TileLookup {
+    pub fn new<'a>(
+        bounds: MapBounds,
+        tilemaps: impl Iterator<Item = (&'a TiledLayer, &'a TileStorage)>,
+    ) -> Self {
+        let mut layers: Vec<LookupLayer> = Vec::new();
+        for (tiled_layer, storage) in tilemaps {
+            let index = match layers
+                .iter()
+                .position(|layer| layer.path == tiled_layer.path)
+            {
+                Some(index) => index,
+                None => {
+                    layers.push(LookupLayer {
+                        name: tiled_layer.name.clone(),
+                        path: tiled_layer.path.clone(),
+                        width: storage.size.x,
+                        tiles: vec![None; storage.size.count()],
+                    });
+                    layers.len() - 1
+                }
+            };
+            let layer = &mut layers[index];
+            for (slot, tile) in layer.tiles.iter_mut().zip(storage.iter()) {
+                if tile.is_some() {
+                    *slot = *tile;
+                }
+            }
+        }
+        TileLookup { bounds, layers }
+    }
+
+    pub fn bounds(&self) -> &MapBounds {
+        &self.bounds
+    }
+
+    /// The tile at a world position, None off the map
+    pub fn world_to_tile(&self, position: Vec2) -> Option<TilePos> {
+        self.bounds.tile_at(position)
+    }
+
+    /// World position of the centre of a tile
+    pub fn tile_to_world(&self, pos: TilePos) -> Vec2 {
+        self.bounds.tile_center(pos)
+    }
+
+    /// The tile entity at `pos` on the layer with that name or path
+    pub fn tile_entity(&self, layer: &str, pos: TilePos) -> Option<Entity> {
+        self.layers
+            .iter()
+            .filter(|lookup| lookup.name == layer || lookup.path == layer)
+            .find_map(|lookup| {
+                if pos.x >= lookup.width {
+                    return None;
+                }
+                let index = (pos.y * lookup.width + pos.x) as usize;
+                lookup.tiles.get(index).copied().flatten()
+            })
+    }
+
+    /// The tiles on the map overlapping a world rect, touching edges not counted
+    pub fn tiles_in_rect(&self, rect: Rect) -> impl Iterator<Item = TilePos> {
+        let size = self.bounds.size;
+        let mut tiles = Vec::new();
+        if self.bounds.map_type == TilemapType::Square {
+            let map = self.bounds.world_rect();
+            let tile_size = self.bounds.tile_size;
+            let from = ((rect.min - map.min) / tile_size).floor().max(Vec2::ZERO);
+            let to = ((rect.max - map.min) / tile_size)
+                .ceil()
+                .min(size.as_vec2());
+            for y in from.y as u32..to.y.max(0.0) as u32 {
+                for x in from.x as u32..to.x.max(0.0) as u32 {
+                    tiles.push(TilePos { x, y });
+                }
+            }
+        } else {
+            for y in 0..size.y {
+                for x in 0..size.x {
+                    let pos = TilePos { x, y };
+                    let tile =
+                        Rect::from_center_size(self.bounds.tile_center(pos), self.bounds.tile_size);
+                    if !rect.intersect(tile).is_empty() {
+                        tiles.push(pos);
+                    }
+                }
+            }
+        }
+        tiles.into_iter()
+    }
+}
+
+/// Rebuilds the TileLookup from the tilemaps in the world, queued ahead of MapLoaded
+pub(super) fn rebuild_tile_lookup(world: &mut World) {
+    let bounds = *world.resource::<MapBounds>();
+    let mut tilemaps = world.query::<(&TiledLayer, &TileStorage)>();
+    let lookup = TileLookup::new(bounds, tilemaps.iter(world));
+    world.insert_resource(lookup);
+}
+
+///
+/// sync_tile_lookup: Bevy system
+///
+/// Rebuilds the TileLookup when tiles are swapped or removed, or the map goes away
+pub fn sync_tile_lookup(
+    changed_query: Query<(), Changed<TileStorage>>,
+    mut removed: RemovedComponents<TileStorage>,
+    tilemap_query: Query<(&TiledLayer, &TileStorage)>,
+    bounds: Res<MapBounds>,
+    mut lookup: ResMut<TileLookup>,
+) {
+    let removed = removed.read().count() > 0;
+    if changed_query.is_empty() && !removed && !bounds.is_changed() {
+        return;
+    }
+    *lookup = TileLookup::new(*bounds, tilemap_query.iter());
+}
@@ -0,0 +1,90 @@
+// Point objects gathered by name, for gameplay code that only needs to know where things are.
+
+use super::bounds::MapBounds;
+use super::layers::ResolvedLayer;
+use bevy::{log, prelude::*, utils::HashMap};
+
+/// A named point object of the current map
+#[derive(Debug, Clone)]
+pub struct MapMarker {
+    pub id: u32,
+    /// in world space
+    pub position: Vec2,
+    /// its custom properties, e.g. an ordering index on patrol points
+    pub properties: tiled::Properties,
+}
+
+///
+/// MapMarkers
+///
+/// The named point objects of the current map, by name, in map order. Names may repeat.
+/// Rebuilt whenever a map spawns and cleared when it goes away. In a world, names start with
+/// the name of the member map, e.g. "town/spawn" for the marker "spawn" on town.tmx.
+#[derive(Resource, Debug, Default)]
+pub struct MapMarkers(HashMap<String, Vec<MapMarker>>);
+
+impl MapMarkers {
+    /// Every marker called `name`
+    pub fn get(&self, name: &str) -> &[MapMarker] {
+        self.0.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    /// Position of the marker called `name`, for names meant to be unique. Warns and returns
+    /// the first when several markers share it.
+    pub fn get_one(&self, name: &str) -> Option<Vec2> {
+        let markers = self.get(name);
+        if markers.len() > 1 {
+            log::warn!(
+                "{} markers are called \"{}\", using the first",
+                markers.len(),
+                name
+            );
+        }
+        markers.first().map(|marker| marker.position)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &MapMarker)> {
+        self.0
+            .iter()
+            .flat_map(|(name, markers)| markers.iter().map(move |marker| (name.as_str(), marker)))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Removes the markers of a member of a world
+    pub(super) fn remove_member(&mut self, member: &str) {
+        let prefix = format!("{}/", member);
+        self.0.retain(|name, _| !name.starts_with(&prefix));
+    }
+}
+
+/// Adds the named point objects of an object layer, of the world member called `member` if any
+pub(super) fn add_markers(
+    markers: &mut MapMarkers,
+    bounds: &MapBounds,
+    member: Option<&str>,
+    layer: &ResolvedLayer,
+    object_layer: &tiled::ObjectLayer,
+) {
+    let offset = Vec2::new(layer.offset.x, -layer.offset.y);
+    for object in object_layer.objects() {
+        if !matches!(object.shape, tiled::ObjectShape::Point(..)) || object.name.is_empty() {
+            continue;
+        }
+        let name = match member {
+            Some(member) => format!("{}/{}", member, object.name),
+            None => object.name.clone(),
+        };
+        markers.0.entry(name).or_default().push(MapMarker {
+            id: object.id(),
+            position: bounds.tmx_to_world(object.x, object.y) + offset,
+            properties: object.properties.clone(),
+        });
+    }
+}
@@ -0,0 +1,264 @@
+// A minimap drawn from the tile data: a pixel per tile, colored by the topmost visible tile
+// there. Markers on top of it (the player, objectives) are up to the UI.
+
+use super::bounds::MapBounds;
+use super::edit::{SetTile, TilemapSource};
+use super::properties::TilemapTileProperties;
+use super::stream::StreamedTiles;
+use super::{TiledMap, TilesetTile};
+use bevy::{
+    ecs::system::RunSystemOnce,
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+    utils::HashMap,
+};
+use bevy_ecs_tilemap::prelude::*;
+
+/// Tile property choosing the tile's minimap color, e.g. `minimap_color = #3070c0`
+pub const MINIMAP_COLOR_PROPERTY: &str = "minimap_color";
+
+///
+/// MinimapSettings
+///
+/// * max_size: the most pixels the minimap has on a side. Bigger maps are sampled every few
+///   tiles, so a pixel stands for a square of tiles.
+/// * palette: the color of tiles without a minimap_color, by tileset name
+/// * fallback: the color of tiles with neither
+#[derive(Resource, Debug, Clone)]
+pub struct MinimapSettings {
+    pub max_size: u32,
+    pub palette: HashMap<String, Color>,
+    pub fallback: Color,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        MinimapSettings {
+            max_size: 256,
+            palette: HashMap::new(),
+            fallback: Color::GRAY,
+        }
+    }
+}
+
+impl MinimapSettings {
+    /// Colors the tiles of the tileset called `tileset` that have no minimap_color
+    pub fn with_tileset_color(mut self, tileset: impl Into<String>, color: Color) -> Self {
+        self.palette.insert(tileset.into(), color);
+        self
+    }
+}
+
+///
+/// MinimapTexture
+///
+/// The minimap of the current map, redrawn before MapLoaded and patched as SetTile changes tiles.
+/// Its top left pixel is the map's top left tile, as in Tiled; places without a visible tile are
+/// transparent. The handle stays the same from map to map.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct MinimapTexture(pub Handle<Image>);
+
+// tile positions to pixels
+#[derive(Debug, Clone, Copy)]
+struct MinimapLayout {
+    map_size: UVec2,
+    // tiles per pixel, on both sides
+    step: u32,
+    size: UVec2,
+}
+
+impl MinimapLayout {
+    fn new(map_size: UVec2, max_size: u32) -> Self {
+        let longest = map_size.max_element().max(1);
+        let step = longest.div_ceil(max_size.max(1));
+        MinimapLayout {
+            map_size,
+            step,
+            size: ((map_size + UVec2::splat(step - 1)) / step).max(UVec2::ONE),
+        }
+    }
+
+    /// The tile a pixel samples
+    fn sampled_tile(&self, pixel: UVec2) -> Option<TilePos> {
+        let tmx = pixel * self.step;
+        if tmx.x >= self.map_size.x || tmx.y >= self.map_size.y {
+            return None;
+        }
+        Some(TilePos {
+            x: tmx.x,
+            y: self.map_size.y - 1 - tmx.y,
+        })
+    }
+
+    /// The pixel sampling a tile, None for tiles between samples
+    fn pixel_of(&self, pos: TilePos) -> Option<UVec2> {
+        if pos.x >= self.map_size.x || pos.y >= self.map_size.y {
+            return None;
+        }
+        let tmx = UVec2::new(pos.x, self.map_size.y - 1 - pos.y);
+        (tmx % self.step == UVec2::ZERO).then_some(tmx / self.step)
+    }
+}
+
+type MinimapTilemap<'a> = (
+    &'a TilemapSource,
+    &'a TileStorage,
+    Option<&'a TilemapTileProperties>,
+    &'a Transform,
+    &'a Visibility,
+    Option<&'a StreamedTiles>,
+);
+
+/// The tilemaps of the map, topmost first
+fn sorted_tilemaps<'a>(tilemap_query: &'a Query<MinimapTilemap>) -> Vec<MinimapTilemap<'a>> {
+    let mut tilemaps = tilemap_query
+        .iter()
+        .filter(|(_, _, _, _, visibility, _)| **visibility != Visibility::Hidden)
+        .collect::<Vec<_>>();
+    tilemaps.sort_by(|a, b| b.3.translation.z.total_cmp(&a.3.translation.z));
+    tilemaps
+}
+
+/// The color of the topmost tile at `pos`
+fn color_at(
+    settings: &MinimapSettings,
+    maps: &Assets<TiledMap>,
+    tilemaps: &[MinimapTilemap],
+    tile_query: &Query<&TilesetTile>,
+    pos: TilePos,
+) -> Option<Color> {
+    tilemaps
+        .iter()
+        .find_map(|(source, storage, properties, _, _, streamed)| {
+            let id = match storage.checked_get(&pos) {
+                Some(tile) => tile_query.get(tile).ok()?.0,
+                None => streamed.and_then(|streamed| streamed.stored_at(pos))?.id,
+            };
+            let color = properties
+                .and_then(|properties| properties.get_color(id, MINIMAP_COLOR_PROPERTY))
+                .or_else(|| {
+                    let tiled_map = maps.get(&source.map)?;
+                    let tileset = tiled_map.map.tilesets().get(source.tileset_index)?;
+                    settings.palette.get(&tileset.name).copied()
+                })
+                .unwrap_or(settings.fallback);
+            Some(color)
+        })
+}
+
+fn set_pixel(image: &mut Image, layout: &MinimapLayout, pixel: UVec2, color: Option<Color>) {
+    let index = ((pixel.y * layout.size.x + pixel.x) * 4) as usize;
+    let rgba = color.map_or([0; 4], |color| color.as_rgba_u8());
+    if let Some(bytes) = image.data.get_mut(index..index + 4) {
+        bytes.copy_from_slice(&rgba);
+    }
+}
+
+fn draw_minimap(
+    settings: Res<MinimapSettings>,
+    bounds: Res<MapBounds>,
+    maps: Res<Assets<TiledMap>>,
+    images: Option<ResMut<Assets<Image>>>,
+    mut texture: ResMut<MinimapTexture>,
+    tilemap_query: Query<MinimapTilemap>,
+    tile_query: Query<&TilesetTile>,
+) {
+    // without a renderer there's nothing to draw to
+    let Some(mut images) = images else {
+        return;
+    };
+    let layout = MinimapLayout::new(bounds.size, settings.max_size);
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: layout.size.x,
+            height: layout.size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.sampler = ImageSampler::nearest();
+
+    let tilemaps = sorted_tilemaps(&tilemap_query);
+    for y in 0..layout.size.y {
+        for x in 0..layout.size.x {
+            let pixel = UVec2::new(x, y);
+            let Some(pos) = layout.sampled_tile(pixel) else {
+                continue;
+            };
+            let color = color_at(&settings, &maps, &tilemaps, &tile_query, pos);
+            set_pixel(&mut image, &layout, pixel, color);
+        }
+    }
+
+    // the default handle is bevy's white texture, not ours
+    let existing = if texture.0 == Handle::default() {
+        None
+    } else {
+        images.get_mut(&texture.0)
+    };
+    match existing {
+        Some(existing) => *existing = image,
+        None => texture.0 = images.add(image),
+    }
+}
+
+/// Redraws the MinimapTexture from the tilemaps in the world, queued ahead of MapLoaded
+pub(super) fn rebuild_minimap(world: &mut World) {
+    world.run_system_once(draw_minimap);
+}
+
+///
+/// update_minimap: Bevy system
+///
+/// Patches the pixels of tiles changed with SetTile
+#[allow(clippy::too_many_arguments)]
+pub fn update_minimap(
+    mut edits: EventReader<SetTile>,
+    settings: Res<MinimapSettings>,
+    bounds: Res<MapBounds>,
+    maps: Res<Assets<TiledMap>>,
+    images: Option<ResMut<Assets<Image>>>,
+    texture: Res<MinimapTexture>,
+    tilemap_query: Query<MinimapTilemap>,
+    tile_query: Query<&TilesetTile>,
+) {
+    if edits.is_empty() {
+        return;
+    }
+    if texture.0 == Handle::default() {
+        // nothing drawn yet
+        edits.clear();
+        return;
+    }
+    let Some(image) = images
+        .map(|images| images.into_inner())
+        .and_then(|images| images.get_mut(&texture.0))
+    else {
+        edits.clear();
+        return;
+    };
+    let layout = MinimapLayout::new(bounds.size, settings.max_size);
+    if image.texture_descriptor.size.width != layout.size.x
+        || image.texture_descriptor.size.height != layout.size.y
+    {
+        // drawn for another map
+        edits.clear();
+        return;
+    }
+
+    let tilemaps = sorted_tilemaps(&tilemap_query);
+    for edit in edits.read() {
+        let Some(pixel) = layout.pixel_of(edit.pos) else {
+            continue;
+        };
+        let color = color_at(&settings, &maps, &tilemaps, &tile_query, edit.pos);
+        set_pixel(image, &layout, pixel, color);
+    }
+}
@@ -0,0 +1,480 @@
+// Walkable cells for AI and cursor movement, derived from the CollisionMap, with movement costs
+// from the `cost` tile property. A* paths over them, and entities walking those paths.
+
+use super::bounds::MapBounds;
+use super::collision::CollisionMap;
+use super::edit::SetTile;
+use super::properties::{TileProperties, TilemapTileProperties};
+use super::stream::StreamedTiles;
+use bevy::{prelude::*, utils::HashMap};
+use bevy_ecs_tilemap::prelude::*;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Tile property weighting movement through a tile, 1 when unset. The highest of the tiles
+/// stacked on a cell counts.
+pub const COST_PROPERTY: &str = "cost";
+
+/// Which cells count as next to each other
+/// * Four: the cells sharing an edge
+/// * Eight: diagonals too. Without cut_corners, a diagonal step needs both cells beside it
+///   walkable, so paths don't squeeze past the corner of a wall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Four,
+    Eight { cut_corners: bool },
+}
+
+///
+/// NavGrid
+///
+/// A cell per tile, in tile coordinates like the CollisionMap. A cell is blocked when its centre
+/// is solid: a solid tile, or inside a collision shape. Built when a map loads, before MapLoaded,
+/// and patched cell by cell as SetTile changes tiles.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct NavGrid {
+    size: UVec2,
+    blocked: Vec<u64>,
+    costs: Vec<f32>,
+}
+
+impl NavGrid {
+    /// A grid of `size` walkable cells costing 1, e.g. to draw one by hand
+    pub fn new(size: UVec2) -> Self {
+        let mut nav = NavGrid::default();
+        nav.reset(size);
+        nav
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x as u32 >= self.size.x || y as u32 >= self.size.y {
+            return None;
+        }
+        Some(y as usize * self.size.x as usize + x as usize)
+    }
+
+    fn walkable_at(&self, x: i32, y: i32) -> bool {
+        self.index(x, y)
+            .is_some_and(|index| self.blocked[index / 64] & (1 << (index % 64)) == 0)
+    }
+
+    /// Whether a cell can be walked through. Cells off the map can't.
+    pub fn is_walkable(&self, pos: TilePos) -> bool {
+        self.walkable_at(pos.x as i32, pos.y as i32)
+    }
+
+    /// What moving through a walkable cell costs, None for blocked cells
+    pub fn cost(&self, pos: TilePos) -> Option<f32> {
+        let index = self.index(pos.x as i32, pos.y as i32)?;
+        self.is_walkable(pos).then(|| self.costs[index])
+    }
+
+    /// The walkable cells next to `pos`
+    pub fn neighbors(&self, pos: TilePos, connectivity: Connectivity) -> Vec<TilePos> {
+        let (x, y) = (pos.x as i32, pos.y as i32);
+        let mut neighbors = Vec::with_capacity(8);
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            if self.walkable_at(x + dx, y + dy) {
+                neighbors.push(TilePos::new((x + dx) as u32, (y + dy) as u32));
+            }
+        }
+        if let Connectivity::Eight { cut_corners } = connectivity {
+            for (dx, dy) in [(1, 1), (-1, 1), (1, -1), (-1, -1)] {
+                if !self.walkable_at(x + dx, y + dy) {
+                    continue;
+                }
+                let corner_open = self.walkable_at(x + dx, y) && self.walkable_at(x, y + dy);
+                if !cut_corners && !corner_open {
+                    continue;
+                }
+                neighbors.push(TilePos::new((x + dx) as u32, (y + dy) as u32));
+            }
+        }
+        neighbors
+    }
+
+    /// Overrides a cell until the tile on it changes. Cells off the map are left alone.
+    pub fn set_walkable(&mut self, pos: TilePos, walkable: bool) {
+        let Some(index) = self.index(pos.x as i32, pos.y as i32) else {
+            return;
+        };
+        let cost = self.costs[index];
+        self.set_cell(pos.x as i32, pos.y as i32, walkable, cost);
+    }
+
+    /// Overrides the cost of a cell until the tile on it changes
+    pub fn set_cost(&mut self, pos: TilePos, cost: f32) {
+        if let Some(index) = self.index(pos.x as i32, pos.y as i32) {
+            self.costs[index] = cost;
+        }
+    }
+
+    /// The cheapest path from `from` to `to` with A*, both ends included. Stepping into a cell
+    /// costs its cost, times the square root of 2 for diagonal steps. `from` only needs to be on
+    /// the map, so entities overlapping a wall can still leave it.
+    ///
+    /// None when `to` is blocked, walled off or further than `options.max_expanded` allows,
+    /// unless `options.nearest` is set: then the path leads to the reachable cell closest to
+    /// `to` instead.
+    pub fn find_path(
+        &self,
+        from: TilePos,
+        to: TilePos,
+        options: PathOptions,
+    ) -> Option<Vec<TilePos>> {
+        let start = self.index(from.x as i32, from.y as i32)?;
+        let width = self.size.x as usize;
+        let pos_of = |index: usize| TilePos::new((index % width) as u32, (index / width) as u32);
+        let goal_walkable = self.is_walkable(to);
+        if !goal_walkable && !options.nearest {
+            return None;
+        }
+
+        // costs can be below 1, so the heuristic is scaled down to the cheapest cell to stay
+        // admissible
+        let min_cost = self
+            .costs
+            .iter()
+            .copied()
+            .filter(|cost| *cost > 0.0)
+            .fold(1.0_f32, f32::min);
+        let heuristic = |pos: TilePos| {
+            let dx = (pos.x as f32 - to.x as f32).abs();
+            let dy = (pos.y as f32 - to.y as f32).abs();
+            let distance = match options.connectivity {
+                Connectivity::Four => dx + dy,
+                Connectivity::Eight { .. } => {
+                    dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+                }
+            };
+            distance * min_cost
+        };
+
+        let cells = self.costs.len();
+        let mut cost_so_far = vec![f32::INFINITY; cells];
+        let mut came_from = vec![usize::MAX; cells];
+        let mut closed = vec![false; cells];
+        let mut open = BinaryHeap::new();
+        cost_so_far[start] = 0.0;
+        open.push(OpenCell {
+            estimate: heuristic(from),
+            index: start,
+        });
+
+        let mut nearest = (heuristic(from), start);
+        let mut expanded = 0;
+        let mut reached = None;
+        while let Some(OpenCell { index, .. }) = open.pop() {
+            if closed[index] {
+                continue;
+            }
+            let pos = pos_of(index);
+            if pos == to {
+                reached = Some(index);
+                break;
+            }
+            closed[index] = true;
+            let remaining = heuristic(pos);
+            if remaining < nearest.0 {
+                nearest = (remaining, index);
+            }
+            expanded += 1;
+            if expanded > options.max_expanded {
+                break;
+            }
+
+            for neighbor in self.neighbors(pos, options.connectivity) {
+                let next = neighbor.y as usize * width + neighbor.x as usize;
+                if closed[next] {
+                    continue;
+                }
+                let diagonal = neighbor.x != pos.x && neighbor.y != pos.y;
+                let step = if diagonal {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let cost = cost_so_far[index] + step * self.costs[next].max(0.0);
+                if cost < cost_so_far[next] {
+                    cost_so_far[next] = cost;
+                    came_from[next] = index;
+                    open.push(OpenCell {
+                        estimate: cost + heuristic(neighbor),
+                        index: next,
+                    });
+                }
+            }
+        }
+
+        let end = match reached {
+            Some(index) => index,
+            None if options.nearest => nearest.1,
+            None => return None,
+        };
+        let mut path = vec![pos_of(end)];
+        let mut index = end;
+        while index != start {
+            index = came_from[index];
+            path.push(pos_of(index));
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn reset(&mut self, size: UVec2) {
+        self.size = size;
+        let cells = (size.x * size.y) as usize;
+        self.blocked.clear();
+        self.blocked.resize(cells.div_ceil(64), 0);
+        self.costs.clear();
+        self.costs.resize(cells, 1.0);
+    }
+
+    fn set_cell(&mut self, x: i32, y: i32, walkable: bool, cost: f32) {
+        let Some(index) = self.index(x, y) else {
+            return;
+        };
+        if walkable {
+            self.blocked[index / 64] &= !(1 << (index % 64));
+        } else {
+            self.blocked[index / 64] |= 1 << (index % 64);
+        }
+        self.costs[index] = cost;
+    }
+}
+
+///
+/// PathOptions
+///
+/// How NavGrid::find_path searches
+/// * connectivity: Eight without cutting corners by default
+/// * max_expanded: how many cells the search may expand before giving up, so a goal that can't
+///   be reached doesn't stall the frame by searching the whole map
+/// * nearest: settle for the reachable cell closest to the goal when the goal can't be reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathOptions {
+    pub connectivity: Connectivity,
+    pub max_expanded: usize,
+    pub nearest: bool,
+}
+
+impl Default for PathOptions {
+    fn default() -> Self {
+        PathOptions {
+            connectivity: Connectivity::Eight { cut_corners: false },
+            max_expanded: 4096,
+            nearest: false,
+        }
+    }
+}
+
+// a cell in the open set of find_path, popped cheapest estimate first
+struct OpenCell {
+    estimate: f32,
+    index: usize,
+}
+
+impl PartialEq for OpenCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenCell {}
+
+impl PartialOrd for OpenCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenCell {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, BinaryHeap is a max-heap
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// Works out one cell from the collision data and the properties of the tiles on it
+fn update_cell(
+    nav: &mut NavGrid,
+    collision: &CollisionMap,
+    x: i32,
+    y: i32,
+    costs: impl Iterator<Item = f32>,
+) {
+    let walkable = !collision.is_solid_at(collision.tile_rect(x, y).center());
+    let cost = costs
+        .fold(None, |highest: Option<f32>, cost| {
+            Some(highest.map_or(cost, |highest| highest.max(cost)))
+        })
+        .unwrap_or(1.0);
+    nav.set_cell(x, y, walkable, cost);
+}
+
+/// Rebuilds the NavGrid for the whole map, queued ahead of MapLoaded. Streamed tiles count
+/// whether their chunk is spawned or not.
+pub(super) fn rebuild_nav_grid(world: &mut World) {
+    let mut tilemaps = world.query::<&TileStorage>();
+    let mut tiles = world.query::<&TileProperties>();
+    let mut streamed = world.query::<(&StreamedTiles, &TilemapTileProperties)>();
+    let mut stored_costs: HashMap<TilePos, Vec<f32>> = HashMap::new();
+    for (streamed, properties) in streamed.iter(world) {
+        for placed in streamed.stored() {
+            if let Some(cost) = properties.get_float(placed.id, COST_PROPERTY) {
+                stored_costs.entry(placed.pos).or_default().push(cost);
+            }
+        }
+    }
+    let collision = world.resource::<CollisionMap>();
+    let mut nav = NavGrid::default();
+    nav.reset(collision.size());
+
+    for y in 0..nav.size.y {
+        for x in 0..nav.size.x {
+            let pos = TilePos { x, y };
+            let costs = tilemaps
+                .iter(world)
+                .filter_map(|storage| storage.checked_get(&pos))
+                .filter_map(|tile| tiles.get(world, tile).ok())
+                .filter_map(|properties| properties.get_float(COST_PROPERTY))
+                .chain(stored_costs.get(&pos).into_iter().flatten().copied());
+            update_cell(&mut nav, collision, x as i32, y as i32, costs);
+        }
+    }
+    world.insert_resource(nav);
+}
+
+///
+/// update_nav_grid: Bevy system
+///
+/// Patches the cells of tiles changed with SetTile, and empties the grid when the map goes away
+pub fn update_nav_grid(
+    mut edits: EventReader<SetTile>,
+    collision: Res<CollisionMap>,
+    tilemap_query: Query<&TileStorage>,
+    tile_query: Query<&TileProperties>,
+    mut nav: ResMut<NavGrid>,
+) {
+    let _span = crate::profiling::span("update_nav_grid");
+    if nav.size != collision.size() {
+        nav.reset(collision.size());
+    }
+    for edit in edits.read() {
+        // the chunks of edited tiles have been streamed in
+        let costs = tilemap_query
+            .iter()
+            .filter_map(|storage| storage.checked_get(&edit.pos))
+            .filter_map(|tile| tile_query.get(tile).ok())
+            .filter_map(|properties| properties.get_float(COST_PROPERTY));
+        update_cell(
+            &mut nav,
+            &collision,
+            edit.pos.x as i32,
+            edit.pos.y as i32,
+            costs,
+        );
+    }
+}
+
+///
+/// PathFollow
+///
+/// Walks an entity from tile centre to tile centre along a path from NavGrid::find_path, at
+/// `speed` pixels per second. Removed once the entity arrives (PathCompleted) or a tile still
+/// ahead becomes blocked (PathBlocked).
+#[derive(Component, Debug, Clone)]
+pub struct PathFollow {
+    path: Vec<TilePos>,
+    next: usize,
+    pub speed: f32,
+}
+
+impl PathFollow {
+    /// The first tile of the path is taken as where the entity starts
+    pub fn new(path: Vec<TilePos>, speed: f32) -> Self {
+        PathFollow {
+            path,
+            next: 1,
+            speed,
+        }
+    }
+
+    pub fn path(&self) -> &[TilePos] {
+        &self.path
+    }
+
+    /// The tiles still ahead, the one being walked to first
+    pub fn remaining(&self) -> &[TilePos] {
+        &self.path[self.next.min(self.path.len())..]
+    }
+}
+
+/// Sent when an entity reaches the end of its PathFollow
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct PathCompleted {
+    pub entity: Entity,
+}
+
+/// Sent when a tile on the rest of an entity's path becomes blocked, leaving it where it stands
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct PathBlocked {
+    pub entity: Entity,
+    /// the first blocked tile ahead
+    pub at: TilePos,
+}
+
+///
+/// follow_paths: Bevy system
+///
+/// Moves PathFollow entities along their paths, on game time. Their z is kept.
+pub fn follow_paths(
+    mut commands: Commands,
+    time: Res<Time>,
+    nav: Res<NavGrid>,
+    bounds: Res<MapBounds>,
+    mut follower_query: Query<(Entity, &mut PathFollow, &mut Transform)>,
+    mut completed: EventWriter<PathCompleted>,
+    mut blocked: EventWriter<PathBlocked>,
+) {
+    let _span = crate::profiling::span("follow_paths");
+    for (entity, mut follow, mut transform) in follower_query.iter_mut() {
+        if let Some(at) = follow
+            .remaining()
+            .iter()
+            .find(|pos| !nav.is_walkable(**pos))
+        {
+            blocked.send(PathBlocked { entity, at: *at });
+            commands.entity(entity).remove::<PathFollow>();
+            continue;
+        }
+
+        let mut budget = follow.speed * time.delta_seconds();
+        let mut position = transform.translation.truncate();
+        while budget > 0.0 && follow.next < follow.path.len() {
+            let target = bounds.tile_center(follow.path[follow.next]);
+            let distance = position.distance(target);
+            if distance <= budget {
+                position = target;
+                budget -= distance;
+                follow.next += 1;
+            } else {
+                position += (target - position) / distance * budget;
+                budget = 0.0;
+            }
+        }
+        transform.translation = position.extend(transform.translation.z);
+
+        if follow.next >= follow.path.len() {
+            completed.send(PathCompleted { entity });
+            commands.entity(entity).remove::<PathFollow>();
+        }
+    }
+}
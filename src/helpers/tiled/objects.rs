@@ -0,0 +1,461 @@
+// Entities spawned from Tiled object layers, and the registry that turns object types into
+// game entities.
+
+use super::bounds::MapBounds;
+use super::collision::{self, CollisionMap, ShapeCollider};
+use super::doors::{self, DOOR_TYPE};
+use super::layers::{self, ResolvedLayer, TiledLayer};
+use super::triggers;
+use super::{TiledMap, TmxExtras, TmxText};
+use bevy::{
+    ecs::system::EntityCommands,
+    log,
+    prelude::*,
+    sprite::Anchor,
+    text::Text2dBounds,
+    utils::{HashMap, HashSet},
+};
+use std::f32::consts::TAU;
+
+///
+/// ObjectShape
+///
+/// The shape of a Tiled object in world units, relative to its entity's transform
+/// (y up, like the rest of the world; rotation is on the transform)
+///
+/// * Rect, Ellipse: centred on the entity
+/// * Point: the entity's position
+/// * Polygon, Polyline: vertices relative to the object's origin
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    Rect { size: Vec2 },
+    Ellipse { size: Vec2 },
+    Point,
+    Polygon { points: Vec<Vec2> },
+    Polyline { points: Vec<Vec2> },
+}
+
+impl ObjectShape {
+    /// Width and height of the shape's bounds
+    pub fn size(&self) -> Vec2 {
+        match self {
+            ObjectShape::Rect { size } | ObjectShape::Ellipse { size } => *size,
+            ObjectShape::Point => Vec2::ZERO,
+            ObjectShape::Polygon { points } | ObjectShape::Polyline { points } => {
+                if points.is_empty() {
+                    return Vec2::ZERO;
+                }
+                let min = points.iter().fold(Vec2::splat(f32::MAX), |a, b| a.min(*b));
+                let max = points.iter().fold(Vec2::splat(f32::MIN), |a, b| a.max(*b));
+                max - min
+            }
+        }
+    }
+}
+
+///
+/// TiledObject
+///
+/// An object from a Tiled object layer. Every object gets one, whether or not a spawner is
+/// registered for its type.
+///
+/// * name: the object's name in Tiled
+/// * object_type: its class (type), empty if unset
+/// * properties: its custom properties
+/// * shape: its shape in world units
+#[derive(Component, Debug, Clone)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: String,
+    pub object_type: String,
+    pub properties: tiled::Properties,
+    pub shape: ObjectShape,
+}
+
+impl TiledObject {
+    /// A string property, e.g. `item = "key"`
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        super::properties::string_property(&self.properties, name)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        super::properties::bool_property(&self.properties, name)
+    }
+
+    /// An int property, e.g. `amount = 5`
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        super::properties::int_property(&self.properties, name)
+    }
+
+    /// An object property: the id of the object it points to, e.g. `patrol` pointing to a path
+    pub fn get_object(&self, name: &str) -> Option<u32> {
+        match self.properties.get(name)? {
+            // Tiled writes 0 for an object property pointing nowhere
+            tiled::PropertyValue::ObjectValue(id) if *id != 0 => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// A float property, ints included, e.g. `radius = 24`
+    pub fn get_float(&self, name: &str) -> Option<f32> {
+        super::properties::float_property(&self.properties, name)
+    }
+}
+
+/// Marks the child drawing the text of a Tiled text object
+#[derive(Component, Debug, Default)]
+pub struct TiledText;
+
+/// Adds components to a freshly spawned TiledObject entity
+pub type ObjectSpawner = Box<dyn Fn(&mut EntityCommands, &TiledObject) + Send + Sync>;
+
+///
+/// TiledObjectRegistry
+///
+/// Spawners for Tiled object types, see App::register_tiled_object, and the types that are
+/// trigger regions on any layer
+#[derive(Default, Resource)]
+pub struct TiledObjectRegistry {
+    spawners: HashMap<String, ObjectSpawner>,
+    triggers: HashSet<String>,
+}
+
+impl TiledObjectRegistry {
+    pub fn register(
+        &mut self,
+        object_type: impl Into<String>,
+        spawner: impl Fn(&mut EntityCommands, &TiledObject) + Send + Sync + 'static,
+    ) {
+        self.spawners.insert(object_type.into(), Box::new(spawner));
+    }
+
+    pub fn get(&self, object_type: &str) -> Option<&ObjectSpawner> {
+        self.spawners.get(object_type)
+    }
+
+    /// Makes objects of `object_type` trigger regions wherever they're placed, like doors
+    pub fn register_trigger(&mut self, object_type: impl Into<String>) {
+        self.triggers.insert(object_type.into());
+    }
+
+    pub fn is_trigger(&self, object_type: &str) -> bool {
+        self.triggers.contains(object_type)
+    }
+}
+
+pub trait RegisterTiledObject {
+    /// Runs `spawner` on every object of `object_type` when a map spawns, e.g.
+    /// `app.register_tiled_object("chest", |cmds, obj| { cmds.insert(Chest); })`
+    fn register_tiled_object(
+        &mut self,
+        object_type: impl Into<String>,
+        spawner: impl Fn(&mut EntityCommands, &TiledObject) + Send + Sync + 'static,
+    ) -> &mut Self;
+
+    /// register_tiled_object for objects that are trigger regions on any layer, sending
+    /// TriggerEntered and TriggerExited like those of the triggers layer
+    fn register_trigger_object(
+        &mut self,
+        object_type: impl Into<String>,
+        spawner: impl Fn(&mut EntityCommands, &TiledObject) + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterTiledObject for App {
+    fn register_tiled_object(
+        &mut self,
+        object_type: impl Into<String>,
+        spawner: impl Fn(&mut EntityCommands, &TiledObject) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(TiledObjectRegistry::default)
+            .register(object_type, spawner);
+        self
+    }
+
+    fn register_trigger_object(
+        &mut self,
+        object_type: impl Into<String>,
+        spawner: impl Fn(&mut EntityCommands, &TiledObject) + Send + Sync + 'static,
+    ) -> &mut Self {
+        let object_type = object_type.into();
+        let mut registry = self
+            .world
+            .get_resource_or_insert_with(TiledObjectRegistry::default);
+        registry.register_trigger(object_type.clone());
+        registry.register(object_type, spawner);
+        self
+    }
+}
+
+/// Converts a position in Tiled's pixel space (origin top left, y down) to world space, for a
+/// map spawned centred on the origin. Infinite maps scan their chunks for the bounds each call,
+/// so prefer MapBounds::tmx_to_world when converting many positions.
+pub fn tmx_to_world(map: &TiledMap, x: f32, y: f32) -> Vec2 {
+    MapBounds::of(map).tmx_to_world(x, y)
+}
+
+/// The transform (relative to the object layer) and shape of an object
+fn object_placement(
+    bounds: &MapBounds,
+    object: &tiled::ObjectData,
+    text: Option<&TmxText>,
+) -> (Transform, ObjectShape) {
+    if bounds.is_isometric() {
+        return isometric_object_placement(bounds, object, text);
+    }
+    let flip = |(x, y): &(f32, f32)| Vec2::new(*x, -*y);
+    let (shape, center) = match &object.shape {
+        tiled::ObjectShape::Rect { width, height } => {
+            let size = Vec2::new(*width, *height);
+            (ObjectShape::Rect { size }, Some(size))
+        }
+        // tiled doesn't keep a text object's width and height
+        tiled::ObjectShape::Text { .. } => {
+            let size = text.map_or(Vec2::ZERO, |text| text.size);
+            (ObjectShape::Rect { size }, Some(size))
+        }
+        tiled::ObjectShape::Ellipse { width, height } => {
+            let size = Vec2::new(*width, *height);
+            (ObjectShape::Ellipse { size }, Some(size))
+        }
+        tiled::ObjectShape::Point(_, _) => (ObjectShape::Point, None),
+        tiled::ObjectShape::Polygon { points } => (
+            ObjectShape::Polygon {
+                points: points.iter().map(flip).collect(),
+            },
+            None,
+        ),
+        tiled::ObjectShape::Polyline { points } => (
+            ObjectShape::Polyline {
+                points: points.iter().map(flip).collect(),
+            },
+            None,
+        ),
+    };
+
+    // Tiled rotates clockwise around the object's origin: the top left corner of rectangles
+    // and ellipses, the bottom left corner of tile objects
+    let angle = object.rotation.to_radians();
+    let mut origin = Vec2::new(object.x, object.y);
+    if let Some(size) = center {
+        let half = if object.tile_data().is_some() {
+            Vec2::new(size.x, -size.y) / 2.0
+        } else {
+            size / 2.0
+        };
+        let (sin, cos) = angle.sin_cos();
+        origin += Vec2::new(half.x * cos - half.y * sin, half.x * sin + half.y * cos);
+    }
+
+    let transform =
+        Transform::from_translation(bounds.tmx_to_world(origin.x, origin.y).extend(0.0))
+            .with_rotation(Quat::from_rotation_z(-angle));
+    (transform, shape)
+}
+
+// On isometric maps Tiled lays shapes along the tile grid, so rectangles and ellipses come out
+// as projected polygons around the object's origin. Tile objects stand upright, centred above
+// their origin. Rotation isn't projected.
+fn isometric_object_placement(
+    bounds: &MapBounds,
+    object: &tiled::ObjectData,
+    text: Option<&TmxText>,
+) -> (Transform, ObjectShape) {
+    let project = |x: f32, y: f32| bounds.tmx_delta_to_world(Vec2::new(x, y));
+    let rectangle = |width: f32, height: f32| ObjectShape::Polygon {
+        points: vec![
+            project(0.0, 0.0),
+            project(width, 0.0),
+            project(width, height),
+            project(0.0, height),
+        ],
+    };
+    let origin = bounds.tmx_to_world(object.x, object.y);
+    let shape = match &object.shape {
+        tiled::ObjectShape::Rect { width, height } if object.tile_data().is_some() => {
+            let size = Vec2::new(*width, *height);
+            let transform =
+                Transform::from_translation((origin + Vec2::Y * size.y / 2.0).extend(0.0));
+            return (transform, ObjectShape::Rect { size });
+        }
+        tiled::ObjectShape::Rect { width, height } => rectangle(*width, *height),
+        tiled::ObjectShape::Text { .. } => {
+            let size = text.map_or(Vec2::ZERO, |text| text.size);
+            rectangle(size.x, size.y)
+        }
+        tiled::ObjectShape::Ellipse { width, height } => {
+            let radius = Vec2::new(*width, *height) / 2.0;
+            let points = (0..collision::ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / collision::ELLIPSE_SEGMENTS as f32 * TAU;
+                    let point = radius + Vec2::from_angle(angle) * radius;
+                    project(point.x, point.y)
+                })
+                .collect();
+            ObjectShape::Polygon { points }
+        }
+        tiled::ObjectShape::Point(_, _) => ObjectShape::Point,
+        tiled::ObjectShape::Polygon { points } => ObjectShape::Polygon {
+            points: points.iter().map(|(x, y)| project(*x, *y)).collect(),
+        },
+        tiled::ObjectShape::Polyline { points } => ObjectShape::Polyline {
+            points: points.iter().map(|(x, y)| project(*x, *y)).collect(),
+        },
+    };
+    (Transform::from_translation(origin.extend(0.0)), shape)
+}
+
+// Text is drawn by a child anchored inside the object's rectangle, which the object's entity is
+// centred on. Tiled's font family and styles aren't carried over.
+fn spawn_text(entity: &mut EntityCommands, object: &tiled::ObjectData, text: Option<&TmxText>) {
+    let tiled::ObjectShape::Text {
+        pixel_size,
+        wrap,
+        color,
+        halign,
+        valign,
+        ..
+    } = &object.shape
+    else {
+        return;
+    };
+    let TmxText { text, size } = text.cloned().unwrap_or_default();
+    let (x, justify) = match halign {
+        tiled::HorizontalAlignment::Center => (0.0, JustifyText::Center),
+        tiled::HorizontalAlignment::Right => (0.5, JustifyText::Right),
+        // bevy doesn't justify text
+        tiled::HorizontalAlignment::Left | tiled::HorizontalAlignment::Justify => {
+            (-0.5, JustifyText::Left)
+        }
+    };
+    let y = match valign {
+        tiled::VerticalAlignment::Top => 0.5,
+        tiled::VerticalAlignment::Center => 0.0,
+        tiled::VerticalAlignment::Bottom => -0.5,
+    };
+    let anchor = Vec2::new(x, y);
+    let bounds = if *wrap {
+        Vec2::new(size.x, f32::INFINITY)
+    } else {
+        Vec2::splat(f32::INFINITY)
+    };
+    let style = TextStyle {
+        font_size: *pixel_size as f32,
+        color: Color::rgba_u8(color.red, color.green, color.blue, color.alpha),
+        ..default()
+    };
+
+    entity.with_children(|parent| {
+        parent.spawn((
+            Text2dBundle {
+                text: Text::from_section(text, style).with_justify(justify),
+                text_anchor: Anchor::Custom(anchor),
+                text_2d_bounds: Text2dBounds { size: bounds },
+                transform: Transform::from_translation((anchor * size).extend(0.0)),
+                ..default()
+            },
+            TiledText,
+        ));
+    });
+}
+
+/// Spawns an object layer's entity with one child per object, returning the layer entity
+/// Objects on the colliders layer also add their shape to the CollisionMap.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn spawn_object_layer(
+    commands: &mut Commands,
+    registry: Option<&TiledObjectRegistry>,
+    collision: &mut CollisionMap,
+    bounds: &MapBounds,
+    layer: &ResolvedLayer,
+    object_layer: &tiled::ObjectLayer,
+    tmx: &TmxExtras,
+    z: f32,
+) -> Entity {
+    let layer_transform = Transform::from_xyz(layer.offset.x, -layer.offset.y, z);
+    let colliders = collision::is_colliders_layer(&layer.layer);
+    let triggers = triggers::is_triggers_layer(&layer.layer);
+    let layer_entity = commands
+        .spawn((
+            SpatialBundle {
+                transform: layer_transform,
+                visibility: layers::visibility(layer.visible),
+                ..default()
+            },
+            Name::new(layer.path.clone()),
+            TiledLayer::new(layer),
+        ))
+        .id();
+
+    for object in object_layer.objects() {
+        let text = tmx.texts.get(&object.id());
+        let (transform, shape) = object_placement(bounds, &object, text);
+        // tiled merges a template's type and properties into the objects using it
+        let tiled_object = TiledObject {
+            id: object.id(),
+            name: object.name.clone(),
+            object_type: object.user_type.clone(),
+            properties: object.properties.clone(),
+            shape,
+        };
+
+        let mut entity = commands.spawn((
+            SpatialBundle {
+                transform,
+                visibility: if object.visible {
+                    Visibility::Inherited
+                } else {
+                    Visibility::Hidden
+                },
+                ..default()
+            },
+            Name::new(object.name.clone()),
+        ));
+        entity.set_parent(layer_entity);
+        if !bounds.is_isometric() {
+            spawn_text(&mut entity, &object, text);
+        }
+        let world = GlobalTransform::from(layer_transform) * GlobalTransform::from(transform);
+        if colliders {
+            if let Some(shape) = collision::object_collider(&world, &tiled_object.shape) {
+                collision.add_shape(shape.clone());
+                entity.insert(ShapeCollider {
+                    shapes: vec![shape],
+                });
+            }
+        }
+        let is_door = tiled_object.object_type == DOOR_TYPE;
+        let is_trigger =
+            registry.is_some_and(|registry| registry.is_trigger(&tiled_object.object_type));
+        if triggers || is_door || is_trigger {
+            if let Some(region) = triggers::trigger_region(
+                &world,
+                &tiled_object.name,
+                &tiled_object.properties,
+                &tiled_object.shape,
+            ) {
+                entity.insert(region);
+            }
+        }
+        if is_door {
+            if let Some(door) = doors::door(&tiled_object) {
+                entity.insert(door);
+            }
+        }
+        entity.insert(tiled_object.clone());
+        match registry.and_then(|registry| registry.get(&tiled_object.object_type)) {
+            Some(spawner) => spawner(&mut entity, &tiled_object),
+            None if !tiled_object.object_type.is_empty() && !is_door && cfg!(debug_assertions) => {
+                log::warn!(
+                    "No spawner registered for Tiled object type \"{}\" ({})",
+                    tiled_object.object_type,
+                    tiled_object.name
+                );
+            }
+            None => {}
+        }
+    }
+
+    layer_entity
+}
@@ -0,0 +1,63 @@
+// Tiled's per-layer parallax factors, applied against the main camera's steady position.
+
+use crate::gfx::{CameraShakeOffset, MainCamera};
+use bevy::prelude::*;
+
+///
+/// LayerParallax
+///
+/// On layer entities whose parallax factor isn't 1 on both axes. A factor of 0 keeps the layer
+/// fixed to the screen, 0.5 scrolls it at half speed and 2 scrolls it twice as fast.
+#[derive(Component, Debug, Clone)]
+pub struct LayerParallax {
+    pub factor: Vec2,
+    /// world position where the layer sits as placed in Tiled: the map's top left corner
+    pub reference: Vec2,
+    // the layer's translation as spawned, captured on the first update
+    base: Option<Vec3>,
+}
+
+impl LayerParallax {
+    pub fn new(factor: Vec2, reference: Vec2) -> Self {
+        LayerParallax {
+            factor,
+            reference,
+            base: None,
+        }
+    }
+
+    /// Offset of the layer from where it was spawned, for a camera at `camera`
+    pub fn offset(&self, camera: Vec2) -> Vec2 {
+        (camera - self.reference) * (Vec2::ONE - self.factor)
+    }
+}
+
+type ParallaxCamera = (With<MainCamera>, Without<LayerParallax>);
+
+///
+/// apply_parallax: Bevy system
+///
+/// Moves parallax layers with the camera. Screen shake is left out so it moves every layer alike.
+pub fn apply_parallax(
+    camera_query: Query<(&Transform, Option<&CameraShakeOffset>), ParallaxCamera>,
+    mut layer_query: Query<(&mut LayerParallax, &mut Transform)>,
+) {
+    let Ok((camera, shake)) = camera_query.get_single() else {
+        return;
+    };
+    let steady = camera.translation.truncate() - shake.map_or(Vec2::ZERO, |shake| shake.0);
+
+    for (mut parallax, mut transform) in layer_query.iter_mut() {
+        let base = match parallax.base {
+            Some(base) => base,
+            None => {
+                parallax.base = Some(transform.translation);
+                transform.translation
+            }
+        };
+        let translation = base + parallax.offset(steady).extend(0.0);
+        if transform.translation != translation {
+            transform.translation = translation;
+        }
+    }
+}
@@ -0,0 +1,334 @@
+// Custom properties of maps and tiles, with typed lookups, and the registry turning tile
+// properties into components. Asking for the wrong type gives None and a warning, once per
+// property name and type, since these get looked up every frame.
+
+use super::TilesetTile;
+use bevy::{ecs::system::EntityCommands, log, prelude::*, utils::HashMap};
+use bevy_ecs_tilemap::prelude::*;
+use std::sync::Mutex;
+
+/// The custom properties Tiled defines on a tile, on tiles that have any, registered or not
+#[derive(Component, Debug, Clone)]
+pub struct TileProperties(pub tiled::Properties);
+
+impl TileProperties {
+    /// A string property, e.g. `surface = "grass"`
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        string_property(&self.0, name)
+    }
+
+    /// An int property, e.g. `hp = 3`
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        int_property(&self.0, name)
+    }
+
+    /// A float property, ints included, e.g. `cost = 2.5`
+    pub fn get_float(&self, name: &str) -> Option<f32> {
+        float_property(&self.0, name)
+    }
+}
+
+///
+/// MapProperties
+///
+/// The custom properties Tiled defines on the map itself, e.g. `music`, `ambient`, `gravity` or
+/// `darkness`. On the map entity, and as a resource for the current map, set by the time
+/// MapLoaded is sent and emptied when the map goes away.
+#[derive(Component, Resource, Debug, Default, Clone)]
+pub struct MapProperties(pub tiled::Properties);
+
+impl MapProperties {
+    /// A string property, e.g. `music = "overworld"`
+    pub fn get_string(&self, name: &str) -> Option<&str> {
+        string_property(&self.0, name)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        bool_property(&self.0, name)
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        int_property(&self.0, name)
+    }
+
+    /// A float property. Int properties count too, Tiled makes it easy to pick either.
+    pub fn get_float(&self, name: &str) -> Option<f32> {
+        float_property(&self.0, name)
+    }
+
+    pub fn get_color(&self, name: &str) -> Option<Color> {
+        color_property(&self.0, name)
+    }
+
+    /// The property as Tiled stored it
+    pub fn get(&self, name: &str) -> Option<&tiled::PropertyValue> {
+        self.0.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+/// The custom properties of a tilemap's tileset tiles, by tile id, on the tilemap entity
+#[derive(Component, Debug, Default, Clone)]
+pub struct TilemapTileProperties(HashMap<tiled::TileId, tiled::Properties>);
+
+impl TilemapTileProperties {
+    pub fn get(&self, id: tiled::TileId) -> Option<&tiled::Properties> {
+        self.0.get(&id)
+    }
+
+    /// A float property of tile `id`, ints included
+    pub fn get_float(&self, id: tiled::TileId, name: &str) -> Option<f32> {
+        float_property(self.0.get(&id)?, name)
+    }
+
+    /// A color property of tile `id`
+    pub fn get_color(&self, id: tiled::TileId, name: &str) -> Option<Color> {
+        color_property(self.0.get(&id)?, name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+pub(super) fn tileset_properties(tileset: &tiled::Tileset) -> TilemapTileProperties {
+    TilemapTileProperties(
+        tileset
+            .tiles()
+            .filter(|(_, tile)| !tile.properties.is_empty())
+            .map(|(id, tile)| (id, tile.properties.clone()))
+            .collect(),
+    )
+}
+
+type PropertyInserter = Box<dyn Fn(&mut EntityCommands, &tiled::PropertyValue) + Send + Sync>;
+type PropertyRemover = Box<dyn Fn(&mut EntityCommands) + Send + Sync>;
+
+struct TilePropertyHandler {
+    name: String,
+    value: Option<tiled::PropertyValue>,
+    insert: PropertyInserter,
+    remove: PropertyRemover,
+}
+
+impl TilePropertyHandler {
+    fn matches<'a>(&self, properties: &'a tiled::Properties) -> Option<&'a tiled::PropertyValue> {
+        let value = properties.get(&self.name)?;
+        match &self.value {
+            Some(wanted) if wanted != value => None,
+            _ => Some(value),
+        }
+    }
+}
+
+///
+/// TilePropertyRegistry
+///
+/// Components for tile properties, see App::register_tile_property
+#[derive(Default, Resource)]
+pub struct TilePropertyRegistry {
+    handlers: Vec<TilePropertyHandler>,
+}
+
+impl TilePropertyRegistry {
+    /// Inserts the component `insert` makes from the property `name` on tiles that have it, or
+    /// only on those where it equals `value`
+    pub fn register<C: Component>(
+        &mut self,
+        name: impl Into<String>,
+        value: Option<tiled::PropertyValue>,
+        insert: impl Fn(&tiled::PropertyValue) -> C + Send + Sync + 'static,
+    ) {
+        self.handlers.push(TilePropertyHandler {
+            name: name.into(),
+            value,
+            insert: Box::new(move |commands, value| {
+                commands.insert(insert(value));
+            }),
+            remove: Box::new(|commands| {
+                commands.remove::<C>();
+            }),
+        });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+    }
+}
+
+pub trait RegisterTileProperty {
+    /// Inserts a component on every tile whose tileset tile has the property `name`, e.g.
+    /// `app.register_tile_property("damage", |value| Damage::from(value))`
+    fn register_tile_property<C: Component>(
+        &mut self,
+        name: impl Into<String>,
+        insert: impl Fn(&tiled::PropertyValue) -> C + Send + Sync + 'static,
+    ) -> &mut Self;
+
+    /// Like register_tile_property, only for tiles where the property equals `value`, e.g.
+    /// `app.register_tile_property_value("water", PropertyValue::BoolValue(true), |_| Water)`
+    fn register_tile_property_value<C: Component>(
+        &mut self,
+        name: impl Into<String>,
+        value: tiled::PropertyValue,
+        insert: impl Fn(&tiled::PropertyValue) -> C + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterTileProperty for App {
+    fn register_tile_property<C: Component>(
+        &mut self,
+        name: impl Into<String>,
+        insert: impl Fn(&tiled::PropertyValue) -> C + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(TilePropertyRegistry::default)
+            .register(name, None, insert);
+        self
+    }
+
+    fn register_tile_property_value<C: Component>(
+        &mut self,
+        name: impl Into<String>,
+        value: tiled::PropertyValue,
+        insert: impl Fn(&tiled::PropertyValue) -> C + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(TilePropertyRegistry::default)
+            .register(name, Some(value), insert);
+        self
+    }
+}
+
+/// Adds TileProperties and the registered components for a tile's properties
+pub(super) fn insert_tile_properties(
+    entity: &mut EntityCommands,
+    registry: Option<&TilePropertyRegistry>,
+    properties: &tiled::Properties,
+) {
+    entity.insert(TileProperties(properties.clone()));
+    for handler in registry
+        .iter()
+        .flat_map(|registry| registry.handlers.iter())
+    {
+        if let Some(value) = handler.matches(properties) {
+            (handler.insert)(entity, value);
+        }
+    }
+}
+
+///
+/// update_tile_properties: Bevy system
+///
+/// Swaps TileProperties and the registered components of tiles whose TilesetTile changed at
+/// runtime. Tiles spawned with the map get theirs as they spawn.
+pub fn update_tile_properties(
+    mut commands: Commands,
+    registry: Option<Res<TilePropertyRegistry>>,
+    tile_query: Query<(Entity, Ref<TilesetTile>, &TilemapId), Changed<TilesetTile>>,
+    tilemap_query: Query<&TilemapTileProperties>,
+) {
+    for (entity, tile, tilemap_id) in tile_query.iter() {
+        if tile.is_added() {
+            continue;
+        }
+        let mut entity = commands.entity(entity);
+        entity.remove::<TileProperties>();
+        // removals first, so a component registered for several values isn't dropped again
+        for handler in registry
+            .iter()
+            .flat_map(|registry| registry.handlers.iter())
+        {
+            (handler.remove)(&mut entity);
+        }
+        let properties = tilemap_query
+            .get(tilemap_id.0)
+            .ok()
+            .and_then(|properties| properties.get(tile.0));
+        if let Some(properties) = properties {
+            insert_tile_properties(&mut entity, registry.as_deref(), properties);
+        }
+    }
+}
+
+// (property name, type asked for) pairs already warned about
+static MISMATCHES: Mutex<Vec<(String, &'static str)>> = Mutex::new(Vec::new());
+
+fn type_name(value: &tiled::PropertyValue) -> &'static str {
+    match value {
+        tiled::PropertyValue::BoolValue(_) => "bool",
+        tiled::PropertyValue::FloatValue(_) => "float",
+        tiled::PropertyValue::IntValue(_) => "int",
+        tiled::PropertyValue::ColorValue(_) => "color",
+        tiled::PropertyValue::StringValue(_) => "string",
+        tiled::PropertyValue::FileValue(_) => "file",
+        tiled::PropertyValue::ObjectValue(_) => "object",
+    }
+}
+
+fn mismatch<T>(name: &str, wanted: &'static str, value: &tiled::PropertyValue) -> Option<T> {
+    let mut warned = MISMATCHES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !warned.iter().any(|(n, w)| n == name && *w == wanted) {
+        warned.push((name.to_string(), wanted));
+        log::warn!(
+            "Property {} is a {}, not a {}",
+            name,
+            type_name(value),
+            wanted
+        );
+    }
+    None
+}
+
+pub(super) fn string_property<'a>(
+    properties: &'a tiled::Properties,
+    name: &str,
+) -> Option<&'a str> {
+    match properties.get(name)? {
+        tiled::PropertyValue::StringValue(value) => Some(value.as_str()),
+        value => mismatch(name, "string", value),
+    }
+}
+
+pub(super) fn bool_property(properties: &tiled::Properties, name: &str) -> Option<bool> {
+    match properties.get(name)? {
+        tiled::PropertyValue::BoolValue(value) => Some(*value),
+        value => mismatch(name, "bool", value),
+    }
+}
+
+pub(super) fn int_property(properties: &tiled::Properties, name: &str) -> Option<i32> {
+    match properties.get(name)? {
+        tiled::PropertyValue::IntValue(value) => Some(*value),
+        value => mismatch(name, "int", value),
+    }
+}
+
+pub(super) fn float_property(properties: &tiled::Properties, name: &str) -> Option<f32> {
+    match properties.get(name)? {
+        tiled::PropertyValue::FloatValue(value) => Some(*value),
+        tiled::PropertyValue::IntValue(value) => Some(*value as f32),
+        value => mismatch(name, "float", value),
+    }
+}
+
+fn color_property(properties: &tiled::Properties, name: &str) -> Option<Color> {
+    match properties.get(name)? {
+        tiled::PropertyValue::ColorValue(color) => Some(Color::rgba_u8(
+            color.red,
+            color.green,
+            color.blue,
+            color.alpha,
+        )),
+        value => mismatch(name, "color", value),
+    }
+}
@@ -0,0 +1,139 @@
+// Reloading maps while working on them. With the dev feature, native builds watch the assets
+// directory, so saving a map or one of its tilesets in Tiled reloads it; on the web there's no
+// watcher and ReloadMap does it by hand. A reload that fails to parse only logs its error, and
+// the map stays as it was.
+
+use super::edit::{SetTile, TilemapSource};
+use super::layers::TiledLayer;
+use super::{CurrentMap, MapLoaded, TiledMap, WorldMember, WorldMembers};
+use bevy::{log, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+
+/// Reloads the current map from disk, or the member maps of the current world
+#[derive(Event, Debug, Default, Clone)]
+pub struct ReloadMap;
+
+///
+/// TileEditLog
+///
+/// The SetTile edits made to the current map, the last one per layer and position, replayed
+/// when the map reloads. Emptied when the map goes away.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct TileEditLog {
+    edits: Vec<SetTile>,
+}
+
+impl TileEditLog {
+    pub fn edits(&self) -> &[SetTile] {
+        &self.edits
+    }
+
+    fn record(&mut self, edit: &SetTile) {
+        self.edits
+            .retain(|logged| logged.layer != edit.layer || logged.pos != edit.pos);
+        self.edits.push(edit.clone());
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.edits.clear();
+    }
+}
+
+///
+/// reload_maps: Bevy system
+///
+/// Handles ReloadMap
+pub fn reload_maps(
+    mut events: EventReader<ReloadMap>,
+    asset_server: Res<AssetServer>,
+    current: Res<CurrentMap>,
+    world_query: Query<&WorldMembers>,
+    member_query: Query<&Handle<TiledMap>, With<WorldMember>>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+    let Some(path) = current.path() else {
+        log::warn!("ReloadMap: no map is loaded");
+        return;
+    };
+    match current
+        .entity()
+        .and_then(|entity| world_query.get(entity).ok())
+    {
+        Some(members) => {
+            for map_handle in members
+                .entities()
+                .iter()
+                .filter_map(|member| member_query.get(*member).ok())
+            {
+                if let Some(path) = map_handle.path() {
+                    log::info!("Reloading world map: {}", path);
+                    asset_server.reload(path.clone());
+                }
+            }
+        }
+        None => {
+            log::info!("Reloading map: {}", path);
+            asset_server.reload(path.to_string());
+        }
+    }
+}
+
+///
+/// record_tile_edits: Bevy system
+///
+/// Keeps the TileEditLog
+pub fn record_tile_edits(mut edits: EventReader<SetTile>, mut edit_log: ResMut<TileEditLog>) {
+    for edit in edits.read() {
+        edit_log.record(edit);
+    }
+}
+
+///
+/// replay_tile_edits: Bevy system
+///
+/// Sends the TileEditLog again once a map has reloaded. Edits whose layer or position is gone from
+/// the new map, or whose tile is no longer in the layer's tilesets, are dropped.
+pub fn replay_tile_edits(
+    mut loaded: EventReader<MapLoaded>,
+    mut edit_log: ResMut<TileEditLog>,
+    mut edits: EventWriter<SetTile>,
+    maps: Res<Assets<TiledMap>>,
+    tilemap_query: Query<(&TiledLayer, &TilemapSource, &TileStorage)>,
+) {
+    if loaded.read().filter(|event| event.reloaded).count() == 0 {
+        return;
+    }
+    let before = edit_log.edits.len();
+    edit_log
+        .edits
+        .retain(|edit| still_applies(edit, &maps, &tilemap_query));
+    let dropped = before - edit_log.edits.len();
+    if dropped > 0 {
+        log::info!(
+            "Dropped {} tile edits the reloaded map has no place for",
+            dropped
+        );
+    }
+    edits.send_batch(edit_log.edits.iter().cloned());
+}
+
+fn still_applies(
+    edit: &SetTile,
+    maps: &Assets<TiledMap>,
+    tilemap_query: &Query<(&TiledLayer, &TilemapSource, &TileStorage)>,
+) -> bool {
+    tilemap_query
+        .iter()
+        .filter(|(layer, _, _)| layer.matches(&edit.layer))
+        .any(|(_, source, storage)| {
+            let on_layer = edit.pos.x < storage.size.x && edit.pos.y < storage.size.y;
+            on_layer
+                && edit.tile_id.is_none_or(|id| {
+                    maps.get(&source.map).is_some_and(|tiled_map| {
+                        tiled_map.texture_index(source.tileset_index, id).is_some()
+                    })
+                })
+        })
+}
@@ -0,0 +1,134 @@
+// Where the player starts on a map: the marker named "spawn", or the one picked by the LoadMap
+// that loaded it, e.g. "spawn_from_cave" for a door out of the cave.
+
+use super::{MapLoaded, MapMarkers};
+use crate::gfx::MainCamera;
+use bevy::{log, prelude::*};
+
+/// Name of the marker maps start at unless the LoadMap says otherwise
+pub const DEFAULT_SPAWN: &str = "spawn";
+
+/// On a map entity: the marker to start at when its map loads, instead of DEFAULT_SPAWN
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct SpawnPointName(pub String);
+
+impl SpawnPointName {
+    pub fn new(name: impl Into<String>) -> Self {
+        SpawnPointName(name.into())
+    }
+}
+
+/// Marks the entity moved onto the spawn point when a map loads, e.g. the player
+#[derive(Component, Debug, Default)]
+pub struct PlacedAtSpawn;
+
+/// Sent after MapLoaded once the map's spawn point is known and anything PlacedAtSpawn is on it
+#[derive(Event, Debug, Clone)]
+pub struct SpawnPointReady {
+    pub map: Entity,
+    pub name: String,
+    /// in world space
+    pub position: Vec2,
+    /// the marker's custom properties, e.g. `facing`
+    pub properties: tiled::Properties,
+}
+
+///
+/// place_at_spawn_point: Bevy system
+///
+/// Moves PlacedAtSpawn entities onto the spawn point of a map that just loaded and snaps the
+/// MainCamera onto them, so the camera doesn't sweep over from the last map. Falls back to
+/// DEFAULT_SPAWN when the map has no marker with the requested name. Reloads leave them be.
+pub fn place_at_spawn_point(
+    mut loaded: EventReader<MapLoaded>,
+    mut ready: EventWriter<SpawnPointReady>,
+    markers: Res<MapMarkers>,
+    spawn_names: Query<&SpawnPointName>,
+    mut placed: Query<&mut Transform, (With<PlacedAtSpawn>, Without<MainCamera>)>,
+    mut cameras: Query<&mut Transform, With<MainCamera>>,
+) {
+    for event in loaded.read() {
+        if event.reloaded {
+            continue;
+        }
+        let requested = spawn_names
+            .get(event.map)
+            .map_or(DEFAULT_SPAWN, |name| name.0.as_str());
+        let mut name = requested;
+        if markers.get(name).is_empty() && name != DEFAULT_SPAWN {
+            log::warn!(
+                "Map {} has no spawn point \"{}\", using \"{}\"",
+                event.path,
+                name,
+                DEFAULT_SPAWN
+            );
+            name = DEFAULT_SPAWN;
+        }
+        let Some(marker) = markers.get(name).first() else {
+            continue;
+        };
+
+        let position = marker.position;
+        for mut transform in placed.iter_mut() {
+            transform.translation = position.extend(transform.translation.z);
+        }
+        if !placed.is_empty() {
+            for mut camera in cameras.iter_mut() {
+                camera.translation = position.extend(camera.translation.z);
+            }
+        }
+        ready.send(SpawnPointReady {
+            map: event.map,
+            name: name.to_string(),
+            position,
+            properties: marker.properties.clone(),
+        });
+    }
+}
+
+/// teleport for the console: moves what was placed at the spawn point, and the camera, to a tile
+#[cfg(feature = "dev")]
+pub(super) fn register_console_commands(app: &mut App) {
+    use super::MapBounds;
+    use crate::console::{ConsoleError, RegisterConsoleCommand};
+
+    app.register_console_command(
+        "teleport <x> <y>",
+        "Moves the player to a tile, in Tiled's coordinates",
+        |args, world| {
+            let x: i32 = args.required("x")?;
+            let y: i32 = args.required("y")?;
+            let bounds = world
+                .get_resource::<MapBounds>()
+                .copied()
+                .unwrap_or_default();
+            if bounds.is_empty() {
+                return Err(ConsoleError::Failed("no map is loaded".to_string()));
+            }
+            let Some(pos) = bounds.tile_pos(x, y) else {
+                return Err(ConsoleError::Failed(format!(
+                    "({}, {}) is off the map",
+                    x, y
+                )));
+            };
+            let target = bounds.tile_center(pos);
+            let mut placed_query = world
+                .query_filtered::<&mut Transform, (With<PlacedAtSpawn>, Without<MainCamera>)>();
+            let mut moved = 0;
+            for mut transform in placed_query.iter_mut(world) {
+                transform.translation = target.extend(transform.translation.z);
+                moved += 1;
+            }
+            if moved == 0 {
+                return Err(ConsoleError::Failed(
+                    "nothing was placed at the spawn point".to_string(),
+                ));
+            }
+            let mut camera_query = world.query_filtered::<&mut Transform, With<MainCamera>>();
+            for mut camera in camera_query.iter_mut(world) {
+                camera.translation = target.extend(camera.translation.z);
+            }
+            Ok(format!("Teleported to ({}, {})", x, y))
+        },
+    );
+}
@@ -0,0 +1,261 @@
+// Streaming the tiles of big maps in chunks around the main camera. Only tile entities stream:
+// the CollisionMap, NavGrid and TileLookup data stay resident for the whole map.
+
+use super::bounds::MapBounds;
+use super::edit::{SetTile, TilemapSource};
+use super::layer_markers::{LayerMarkerRegistry, LayerName, LayerTags};
+use super::layers::TiledLayer;
+use super::properties::TilePropertyRegistry;
+use super::{placed_shapes, spawn_tile, CollisionMap, PlacedTile, TiledMap, TilesetTile};
+use crate::gfx::MainCamera;
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_ecs_tilemap::prelude::*;
+
+///
+/// TileStreaming
+///
+/// On the map entity, chosen with TiledMapBundle::streaming
+/// * SpawnAll: every tile is spawned with the map. The default.
+/// * Chunks: tile layers are cut into chunks of chunk_size tiles. A chunk spawns once it comes
+///   within spawn_margin chunks of the main camera's view, and despawns once it's more than
+///   despawn_margin chunks away; keep despawn_margin above spawn_margin so chunks on the edge
+///   don't flicker in and out.
+///
+/// Streamed tiles have no entity until their chunk spawns, so MapLoaded readers and
+/// TileLookup::tile_entity don't see them yet. Components the game adds to a tile entity (TileHealth and the like) are lost when its
+/// chunk despawns; the tile itself, as swapped by SetTile, is kept.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub enum TileStreaming {
+    #[default]
+    SpawnAll,
+    Chunks {
+        chunk_size: UVec2,
+        spawn_margin: f32,
+        despawn_margin: f32,
+    },
+}
+
+impl TileStreaming {
+    /// 32×32 chunks, spawned within half a chunk of the view and despawned past a chunk and a half
+    pub fn chunks() -> Self {
+        TileStreaming::Chunks {
+            chunk_size: UVec2::splat(32),
+            spawn_margin: 0.5,
+            despawn_margin: 1.5,
+        }
+    }
+}
+
+///
+/// StreamedTiles
+///
+/// On the tilemaps of streamed maps: which chunks are spawned, and the tiles of those that
+/// aren't
+#[derive(Component, Debug, Clone)]
+pub struct StreamedTiles {
+    chunk_size: UVec2,
+    spawn_margin: f32,
+    despawn_margin: f32,
+    stored: HashMap<UVec2, Vec<PlacedTile>>,
+    spawned: HashSet<UVec2>,
+}
+
+impl StreamedTiles {
+    pub(super) fn new(streaming: TileStreaming) -> Option<Self> {
+        match streaming {
+            TileStreaming::SpawnAll => None,
+            TileStreaming::Chunks {
+                chunk_size,
+                spawn_margin,
+                despawn_margin,
+            } => Some(StreamedTiles {
+                chunk_size: chunk_size.max(UVec2::ONE),
+                spawn_margin,
+                despawn_margin,
+                stored: HashMap::new(),
+                spawned: HashSet::new(),
+            }),
+        }
+    }
+
+    /// Stores a tile of a chunk that isn't spawned
+    pub(super) fn insert(&mut self, placed: PlacedTile) {
+        let chunk = self.chunk_of(placed.pos);
+        self.stored.entry(chunk).or_default().push(placed);
+    }
+
+    pub fn chunk_size(&self) -> UVec2 {
+        self.chunk_size
+    }
+
+    /// The chunk a tile is in
+    pub fn chunk_of(&self, pos: TilePos) -> UVec2 {
+        UVec2::new(pos.x, pos.y) / self.chunk_size
+    }
+
+    pub fn is_spawned(&self, chunk: UVec2) -> bool {
+        self.spawned.contains(&chunk)
+    }
+
+    pub fn spawned_chunks(&self) -> impl Iterator<Item = UVec2> + '_ {
+        self.spawned.iter().copied()
+    }
+
+    /// The tiles of the chunks that aren't spawned
+    pub fn stored(&self) -> impl Iterator<Item = &PlacedTile> {
+        self.stored.values().flatten()
+    }
+
+    /// The tile at `pos`, if its chunk isn't spawned
+    pub fn stored_at(&self, pos: TilePos) -> Option<&PlacedTile> {
+        self.stored
+            .get(&self.chunk_of(pos))?
+            .iter()
+            .find(|placed| placed.pos == pos)
+    }
+}
+
+/// World rect covered by the tiles of a chunk
+fn chunk_rect(bounds: &MapBounds, size: &TilemapSize, chunk: UVec2, chunk_size: UVec2) -> Rect {
+    let min = chunk * chunk_size;
+    let max = (min + chunk_size).min(UVec2::new(size.x, size.y)) - UVec2::ONE;
+    let half_size = bounds.tile_size / 2.0;
+    [
+        (min.x, min.y),
+        (max.x, min.y),
+        (min.x, max.y),
+        (max.x, max.y),
+    ]
+    .iter()
+    .map(|&(x, y)| Rect::from_center_half_size(bounds.tile_center(TilePos { x, y }), half_size))
+    .reduce(|a, b| a.union(b))
+    .unwrap_or_default()
+}
+
+fn overlaps(view: Option<Rect>, rect: Rect) -> bool {
+    view.is_some_and(|view| !view.intersect(rect).is_empty())
+}
+
+///
+/// stream_tile_chunks: Bevy system
+///
+/// Spawns and despawns the chunks of streamed tilemaps as the main camera moves. Chunks with a
+/// tile changed by SetTile this frame are spawned first, so the edit has an entity to change.
+/// Without a main camera, chunks stay as they are.
+#[allow(clippy::too_many_arguments)]
+pub fn stream_tile_chunks(
+    mut commands: Commands,
+    mut edits: EventReader<SetTile>,
+    maps: Res<Assets<TiledMap>>,
+    tile_properties: Option<Res<TilePropertyRegistry>>,
+    marker_registry: Option<Res<LayerMarkerRegistry>>,
+    collision: Res<CollisionMap>,
+    bounds: Res<MapBounds>,
+    camera_query: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+    mut tilemap_query: Query<(
+        Entity,
+        &TiledLayer,
+        &LayerName,
+        &TilemapSource,
+        &mut TileStorage,
+        &mut StreamedTiles,
+    )>,
+    tile_query: Query<(&TilesetTile, &TileFlip)>,
+) {
+    let edited = edits.read().map(|edit| edit.pos).collect::<Vec<_>>();
+    let view = camera_query
+        .get_single()
+        .ok()
+        .map(|(transform, projection)| {
+            let center = transform.translation.truncate();
+            Rect {
+                min: projection.area.min + center,
+                max: projection.area.max + center,
+            }
+        });
+
+    for (tilemap, layer, layer_name, source, mut storage, mut streamed) in tilemap_query.iter_mut()
+    {
+        let Some(tiled_map) = maps.get(&source.map) else {
+            continue;
+        };
+        let chunk_size = streamed.chunk_size;
+        let chunk_span = (chunk_size.as_vec2() * bounds.tile_size).max_element();
+        let grow = |view: Rect, margin: f32| {
+            Rect::from_center_half_size(view.center(), view.half_size() + margin * chunk_span)
+        };
+        let spawn_view = view.map(|view| grow(view, streamed.spawn_margin));
+        let keep_view = view.map(|view| grow(view, streamed.despawn_margin));
+        let forced = edited
+            .iter()
+            .map(|pos| streamed.chunk_of(*pos))
+            .collect::<Vec<_>>();
+        let chunks =
+            (UVec2::new(storage.size.x, storage.size.y) + chunk_size - UVec2::ONE) / chunk_size;
+
+        for chunk_y in 0..chunks.y {
+            for chunk_x in 0..chunks.x {
+                let chunk = UVec2::new(chunk_x, chunk_y);
+                let rect = chunk_rect(&bounds, &storage.size, chunk, chunk_size);
+                let spawned = streamed.is_spawned(chunk);
+                let force = forced.contains(&chunk);
+
+                if !spawned && (force || overlaps(spawn_view, rect)) {
+                    streamed.spawned.insert(chunk);
+                    let color = TileColor(layer.color());
+                    let tags = LayerTags {
+                        name: layer_name,
+                        registry: marker_registry.as_deref(),
+                    };
+                    for placed in streamed.stored.remove(&chunk).unwrap_or_default() {
+                        let tile =
+                            tiled_map.map.tilesets()[placed.tileset_index].get_tile(placed.id);
+                        let shapes = placed_shapes(&collision, tile.as_ref(), &placed);
+                        let entity = spawn_tile(
+                            &mut commands,
+                            tiled_map,
+                            tile_properties.as_deref(),
+                            tilemap,
+                            source,
+                            &placed,
+                            color,
+                            shapes,
+                            tags,
+                        );
+                        storage.set(&placed.pos, entity);
+                    }
+                } else if spawned && !force && view.is_some() && !overlaps(keep_view, rect) {
+                    streamed.spawned.remove(&chunk);
+                    let min = chunk * chunk_size;
+                    let max = (min + chunk_size).min(UVec2::new(storage.size.x, storage.size.y));
+                    let mut stored = Vec::new();
+                    for y in min.y..max.y {
+                        for x in min.x..max.x {
+                            let pos = TilePos { x, y };
+                            let Some(entity) = storage.get(&pos) else {
+                                continue;
+                            };
+                            // the tile as it is now, SetTile swaps included
+                            if let Ok((tile, flip)) = tile_query.get(entity) {
+                                stored.push(PlacedTile {
+                                    pos,
+                                    tileset_index: source.tileset_index,
+                                    id: tile.0,
+                                    flip: *flip,
+                                });
+                            }
+                            storage.remove(&pos);
+                            commands.entity(entity).despawn_recursive();
+                        }
+                    }
+                    if !stored.is_empty() {
+                        streamed.stored.insert(chunk, stored);
+                    }
+                }
+            }
+        }
+    }
+}
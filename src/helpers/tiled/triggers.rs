@@ -0,0 +1,117 @@
+// Trigger regions: objects on the "triggers" layer that send events as sensors walk in and out,
+// for cutscenes, area names and ambushes.
+
+use super::collision;
+use super::objects::ObjectShape;
+use bevy::{prelude::*, utils::HashSet};
+
+/// Object layer whose objects are trigger regions
+pub const TRIGGERS_LAYER: &str = "triggers";
+
+///
+/// TriggerRegion
+///
+/// On the objects of the triggers layer, with their name and custom properties. The rect is in
+/// world space: the box around the object's shape.
+#[derive(Component, Debug, Clone)]
+pub struct TriggerRegion {
+    pub name: String,
+    pub properties: tiled::Properties,
+    pub rect: Rect,
+}
+
+/// Marks entities that set off trigger regions, e.g. the player
+#[derive(Component, Debug, Default)]
+pub struct TriggerSensor;
+
+/// Sent when a sensor moves into a trigger region, or is in one when the map spawns
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerEntered {
+    pub region: Entity,
+    pub entity: Entity,
+}
+
+/// Sent when a sensor leaves a trigger region. Nothing is sent when either side despawns.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TriggerExited {
+    pub region: Entity,
+    pub entity: Entity,
+}
+
+///
+/// TriggerOccupancy
+///
+/// The sensors inside each trigger region as of the last update
+#[derive(Resource, Debug, Default)]
+pub struct TriggerOccupancy(HashSet<(Entity, Entity)>);
+
+impl TriggerOccupancy {
+    pub fn contains(&self, region: Entity, entity: Entity) -> bool {
+        self.0.contains(&(region, entity))
+    }
+
+    /// The sensors inside `region`
+    pub fn occupants(&self, region: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.0
+            .iter()
+            .filter(move |(r, _)| *r == region)
+            .map(|(_, entity)| *entity)
+    }
+}
+
+/// Whether an object layer holds trigger regions
+pub(super) fn is_triggers_layer(layer: &tiled::Layer) -> bool {
+    layer.name.eq_ignore_ascii_case(TRIGGERS_LAYER)
+}
+
+/// The region for an object placed at `transform` in world space, if its shape has an area
+pub(super) fn trigger_region(
+    transform: &GlobalTransform,
+    name: &str,
+    properties: &tiled::Properties,
+    shape: &ObjectShape,
+) -> Option<TriggerRegion> {
+    let shape = collision::object_collider(transform, shape)?;
+    Some(TriggerRegion {
+        name: name.to_string(),
+        properties: properties.clone(),
+        rect: shape.bounds(),
+    })
+}
+
+///
+/// track_triggers: Bevy system
+///
+/// Sends TriggerEntered and TriggerExited as the sensors' global positions move in and out of
+/// trigger regions. Runs after transform propagation, so sensors moved this frame count.
+pub fn track_triggers(
+    regions: Query<(Entity, &TriggerRegion)>,
+    sensors: Query<(Entity, &GlobalTransform), With<TriggerSensor>>,
+    mut occupancy: ResMut<TriggerOccupancy>,
+    mut entered: EventWriter<TriggerEntered>,
+    mut exited: EventWriter<TriggerExited>,
+) {
+    let _span = crate::profiling::span("track_triggers");
+    // despawned regions and sensors just drop out
+    occupancy
+        .0
+        .retain(|(region, entity)| regions.contains(*region) && sensors.contains(*entity));
+
+    for (region_entity, region) in regions.iter() {
+        for (entity, transform) in sensors.iter() {
+            let pair = (region_entity, entity);
+            let inside = region.rect.contains(transform.translation().truncate());
+            if inside && occupancy.0.insert(pair) {
+                entered.send(TriggerEntered {
+                    region: region_entity,
+                    entity,
+                });
+            } else if !inside && occupancy.0.remove(&pair) {
+                exited.send(TriggerExited {
+                    region: region_entity,
+                    entity,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,471 @@
+// Checks maps as they load, so a broken map fails with a list of what's wrong with it instead of
+// rendering garbage. validate_map runs the same checks on a map file without an App, e.g. to lint
+// the maps in assets/ from a test.
+
+use super::collision::{COLLIDES_PROPERTY, ONE_WAY_PROPERTY};
+use super::depth::Z_BAND_PROPERTY;
+use super::layers;
+use super::nav::COST_PROPERTY;
+use super::{
+    attribute, external_tileset_sources, layer_elements, normalize_path, object_templates,
+    start_tags, BytesResourceReader,
+};
+use crate::gfx::SpriteLayer;
+use bevy::{log, math::UVec2, utils::HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+///
+/// MapProblem
+///
+/// Something wrong with a map, and where
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapProblem {
+    pub file: PathBuf,
+    /// the layer's name, with the groups it's in
+    pub layer: Option<String>,
+    pub object: Option<String>,
+    /// in Tiled's tile coordinates (y down)
+    pub tile: Option<UVec2>,
+    pub message: String,
+}
+
+impl fmt::Display for MapProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.display())?;
+        if let Some(layer) = &self.layer {
+            write!(f, ", layer {}", layer)?;
+        }
+        if let Some(object) = &self.object {
+            write!(f, ", object {}", object)?;
+        }
+        if let Some(tile) = self.tile {
+            write!(f, ", tile ({}, {})", tile.x, tile.y)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+///
+/// MapReport
+///
+/// Everything validation found in a map. Errors fail the load, warnings are only logged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapReport {
+    pub file: PathBuf,
+    pub errors: Vec<MapProblem>,
+    pub warnings: Vec<MapProblem>,
+}
+
+impl MapReport {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        MapReport {
+            file: file.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the map has no errors, warnings aside
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn problem(&self, message: impl Into<String>) -> MapProblem {
+        MapProblem {
+            file: self.file.clone(),
+            layer: None,
+            object: None,
+            tile: None,
+            message: message.into(),
+        }
+    }
+
+    fn in_layer(&self, layer: &str, message: impl Into<String>) -> MapProblem {
+        MapProblem {
+            layer: Some(layer.to_string()),
+            ..self.problem(message)
+        }
+    }
+
+    pub(super) fn log_warnings(&self) {
+        for warning in self.warnings.iter() {
+            log::warn!("{}", warning);
+        }
+    }
+}
+
+/// One error per line
+impl fmt::Display for MapReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.errors.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks on the TMX text that tiled would fail on without saying which layer: the encoding and
+/// compression of layer data
+pub(super) fn check_tmx(report: &mut MapReport, tmx: &str) {
+    for (name, span) in layer_elements(tmx) {
+        let Some(data) = start_tags(&tmx[span], "data").next() else {
+            continue;
+        };
+        match attribute(data, "encoding") {
+            None | Some("csv") | Some("base64") => {}
+            Some(encoding) => {
+                let error = report.in_layer(name, format!("unsupported encoding {}", encoding));
+                report.errors.push(error);
+            }
+        }
+        let message = match attribute(data, "compression") {
+            None | Some("zlib") | Some("gzip") => continue,
+            Some("zstd") if cfg!(feature = "zstd") => continue,
+            Some("zstd") => "zstd compression needs the zstd feature".to_string(),
+            Some(compression) => format!("unsupported compression {}", compression),
+        };
+        let error = report.in_layer(name, message);
+        report.errors.push(error);
+    }
+}
+
+/// Checks on the parsed map: tilesets without images, layers that don't fit the map, and tiles
+/// and objects whose GID matches no tileset
+pub(super) fn check_map(report: &mut MapReport, map: &tiled::Map) {
+    for tileset in map.tilesets().iter() {
+        if tileset.image.is_none() && tileset.tiles().all(|(_, tile)| tile.image.is_none()) {
+            let error = report.problem(format!("tileset {} has no image", tileset.name));
+            report.errors.push(error);
+        }
+        for (tile_id, tile) in tileset.tiles() {
+            check_tile_properties(report, &tileset.name, tile_id, &tile.properties);
+        }
+    }
+
+    let mut layer_paths: Vec<String> = Vec::new();
+    for layer in layers::resolve_layers(map) {
+        if layer_paths.contains(&layer.path) {
+            let warning = report.in_layer(
+                &layer.path,
+                "another layer has the same name, so events naming it change both",
+            );
+            report.warnings.push(warning);
+        }
+        layer_paths.push(layer.path.clone());
+        if let Some(tiled::PropertyValue::StringValue(band)) =
+            layer.layer.properties.get(Z_BAND_PROPERTY)
+        {
+            if SpriteLayer::from_name(band).is_none() {
+                let warning = report.in_layer(
+                    &layer.path,
+                    format!(
+                        "unknown {} {}, so it stays at its index",
+                        Z_BAND_PROPERTY, band
+                    ),
+                );
+                report.warnings.push(warning);
+            }
+        }
+
+        let empty = match layer.layer.layer_type() {
+            tiled::LayerType::Tiles(tiled::TileLayer::Finite(data)) => {
+                if data.width() != map.width || data.height() != map.height {
+                    let error = report.in_layer(
+                        &layer.path,
+                        format!(
+                            "the layer is {}×{} tiles but the map is {}×{}",
+                            data.width(),
+                            data.height(),
+                            map.width,
+                            map.height
+                        ),
+                    );
+                    report.errors.push(error);
+                }
+                let mut empty = true;
+                for y in 0..data.height() {
+                    for x in 0..data.width() {
+                        let Some(tile) = data.get_tile_data(x as i32, y as i32) else {
+                            continue;
+                        };
+                        empty = false;
+                        check_tile(report, map, &layer.path, UVec2::new(x, y), tile);
+                    }
+                }
+                empty
+            }
+            tiled::LayerType::Tiles(tiled::TileLayer::Infinite(data)) => {
+                let (width, height) = (
+                    tiled::ChunkData::WIDTH as i32,
+                    tiled::ChunkData::HEIGHT as i32,
+                );
+                let mut empty = true;
+                for ((chunk_x, chunk_y), chunk) in data.chunks() {
+                    for y in 0..height {
+                        for x in 0..width {
+                            let Some(tile) = chunk.get_tile_data(x, y) else {
+                                continue;
+                            };
+                            empty = false;
+                            // chunks can sit left of or above the origin
+                            let (x, y) = (chunk_x * width + x, chunk_y * height + y);
+                            let tile_pos = UVec2::new(x.max(0) as u32, y.max(0) as u32);
+                            check_tile(report, map, &layer.path, tile_pos, tile);
+                        }
+                    }
+                }
+                empty
+            }
+            tiled::LayerType::Objects(object_layer) => {
+                for object in object_layer.objects() {
+                    let Some(tile) = object.tile_data() else {
+                        continue;
+                    };
+                    let tiled::TilesetLocation::Map(tileset_index) = tile.tileset_location() else {
+                        continue;
+                    };
+                    if !in_tileset(&map.tilesets()[*tileset_index], tile.id()) {
+                        let error = MapProblem {
+                            object: Some(format!("{} ({})", object.name, object.id())),
+                            ..report
+                                .in_layer(&layer.path, "its GID isn't in any tileset".to_string())
+                        };
+                        report.errors.push(error);
+                    }
+                }
+                object_layer.objects().next().is_none()
+            }
+            tiled::LayerType::Image(image_layer) => image_layer.image.is_none(),
+            tiled::LayerType::Group(_) => false,
+        };
+        if empty {
+            let warning = report.in_layer(&layer.path, "the layer is empty");
+            report.warnings.push(warning);
+        }
+    }
+}
+
+/// Whether a tileset has a tile with this id. tiled takes a GID past the last tileset for a tile
+/// of the last tileset.
+fn in_tileset(tileset: &tiled::Tileset, id: tiled::TileId) -> bool {
+    match tileset.image {
+        Some(_) => id < tileset.tilecount,
+        // the ids of image collections can have gaps
+        None => tileset.get_tile(id).is_some(),
+    }
+}
+
+fn check_tile(
+    report: &mut MapReport,
+    map: &tiled::Map,
+    layer: &str,
+    tile_pos: UVec2,
+    tile: &tiled::LayerTileData,
+) {
+    let tileset = &map.tilesets()[tile.tileset_index()];
+    let problem = |message: String| MapProblem {
+        tile: Some(tile_pos),
+        ..report.in_layer(layer, message)
+    };
+    if !in_tileset(tileset, tile.id()) {
+        let error = problem("its GID isn't in any tileset".to_string());
+        report.errors.push(error);
+    } else if tileset.image.is_none()
+        && tileset
+            .get_tile(tile.id())
+            .is_some_and(|tile| tile.image.is_none())
+    {
+        let warning = problem(format!(
+            "tile {} of tileset {} has no image, so it's left out",
+            tile.id(),
+            tileset.name
+        ));
+        report.warnings.push(warning);
+    }
+}
+
+/// Tile properties the map helpers read that have the wrong type or the wrong case, and so are
+/// ignored. Properties the game registers are its own business.
+fn check_tile_properties(
+    report: &mut MapReport,
+    tileset: &str,
+    tile_id: tiled::TileId,
+    properties: &tiled::Properties,
+) {
+    for (name, value) in properties.iter() {
+        let message = if name == COLLIDES_PROPERTY || name == ONE_WAY_PROPERTY {
+            match value {
+                tiled::PropertyValue::BoolValue(_) => continue,
+                _ => format!("{} should be a bool", name),
+            }
+        } else if name == COST_PROPERTY {
+            match value {
+                tiled::PropertyValue::FloatValue(_) | tiled::PropertyValue::IntValue(_) => continue,
+                _ => format!("{} should be a number", name),
+            }
+        } else if let Some(known) = [COLLIDES_PROPERTY, ONE_WAY_PROPERTY, COST_PROPERTY]
+            .iter()
+            .find(|known| name.eq_ignore_ascii_case(known))
+        {
+            format!("unknown property {}, did you mean {}?", name, known)
+        } else {
+            continue;
+        };
+        let warning = report.problem(format!(
+            "tile {} of tileset {}: {}",
+            tile_id, tileset, message
+        ));
+        report.warnings.push(warning);
+    }
+}
+
+/// Images the map points at that `exists` says aren't there
+fn check_images(report: &mut MapReport, map: &tiled::Map, exists: impl Fn(&Path) -> bool) {
+    let mut missing = Vec::new();
+    for tileset in map.tilesets().iter() {
+        // tiles are handed out by value, so their images can't be borrowed past them
+        let sources = tileset
+            .image
+            .iter()
+            .map(|image| image.source.clone())
+            .chain(
+                tileset
+                    .tiles()
+                    .filter_map(|(_, tile)| tile.image.as_ref().map(|image| image.source.clone())),
+            );
+        for source in sources {
+            if !exists(&source) {
+                missing.push(report.problem(format!(
+                    "tileset {} uses {}, which doesn't exist",
+                    tileset.name,
+                    source.display()
+                )));
+            }
+        }
+    }
+    for layer in layers::resolve_layers(map) {
+        let tiled::LayerType::Image(image_layer) = layer.layer.layer_type() else {
+            continue;
+        };
+        if let Some(image) = image_layer
+            .image
+            .as_ref()
+            .filter(|image| !exists(&image.source))
+        {
+            missing.push(report.in_layer(
+                &layer.path,
+                format!("{} doesn't exist", image.source.display()),
+            ));
+        }
+    }
+    report.errors.extend(missing);
+}
+
+/// Validates the map file at `path` like the asset loader does, checking that the images it uses
+/// are on disk too. Tilesets and images are found relative to the map, like in Tiled.
+pub fn validate_map(path: impl AsRef<Path>) -> MapReport {
+    let path = path.as_ref();
+    let mut report = MapReport::new(path);
+    let tmx = match std::fs::read_to_string(path) {
+        Ok(tmx) => tmx,
+        Err(e) => {
+            let error = report.problem(format!("could not read the map: {}", e));
+            report.errors.push(error);
+            return report;
+        }
+    };
+    check_tmx(&mut report, &tmx);
+    if !report.is_ok() {
+        return report;
+    }
+    match tiled::Loader::new().load_tmx_map(path) {
+        Ok(map) => {
+            check_map(&mut report, &map);
+            check_images(&mut report, &map, Path::exists);
+        }
+        Err(e) => {
+            let error = report.problem(format!("could not parse the map: {}", e));
+            report.errors.push(error);
+        }
+    }
+    report
+}
+
+/// Validates a map held in memory, like one dropped on the game: `files` holds the map at `path`
+/// and the tilesets, templates and images it uses, by their paths relative to the same root.
+/// What's missing from `files` is reported, as validate_map reports what's missing from disk.
+pub fn validate_map_files(path: impl AsRef<Path>, files: &HashMap<PathBuf, Vec<u8>>) -> MapReport {
+    let path = normalize_path(path.as_ref());
+    let mut report = MapReport::new(&path);
+    let Some(bytes) = files.get(&path) else {
+        let error = report.problem("the map isn't among the files");
+        report.errors.push(error);
+        return report;
+    };
+    let tmx = String::from_utf8_lossy(bytes);
+    check_tmx(&mut report, &tmx);
+    if !report.is_ok() {
+        return report;
+    }
+
+    let map_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let mut reader = BytesResourceReader::new(&path, bytes);
+    let mut tileset_sources: Vec<PathBuf> = external_tileset_sources(&tmx)
+        .into_iter()
+        .map(|source| normalize_path(&map_dir.join(source)))
+        .collect();
+    let mut missing = Vec::new();
+    for (object, source) in object_templates(&tmx) {
+        let template_path = normalize_path(&map_dir.join(source));
+        let Some(template) = files.get(&template_path) else {
+            missing.push(report.problem(format!(
+                "object {} uses template {}, which is missing",
+                object,
+                template_path.display()
+            )));
+            continue;
+        };
+        let template_dir = template_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        tileset_sources.extend(
+            external_tileset_sources(&String::from_utf8_lossy(template))
+                .into_iter()
+                .map(|source| normalize_path(&template_dir.join(source))),
+        );
+        reader.add_file(&template_path, template.clone());
+    }
+    tileset_sources.sort();
+    tileset_sources.dedup();
+    for tileset_path in tileset_sources {
+        match files.get(&tileset_path) {
+            Some(tileset) => reader.add_file(&tileset_path, tileset.clone()),
+            None => missing
+                .push(report.problem(format!("tileset {} is missing", tileset_path.display()))),
+        }
+    }
+    if !missing.is_empty() {
+        report.errors.extend(missing);
+        return report;
+    }
+
+    let mut loader =
+        tiled::Loader::with_cache_and_reader(tiled::DefaultResourceCache::new(), reader);
+    match loader.load_tmx_map(&path) {
+        Ok(map) => {
+            check_map(&mut report, &map);
+            check_images(&mut report, &map, |image| {
+                files.contains_key(&normalize_path(image))
+            });
+        }
+        Err(e) => {
+            let error = report.problem(format!("could not parse the map: {}", e));
+            report.errors.push(error);
+        }
+    }
+    report
+}
@@ -0,0 +1,298 @@
+// Tiled .world files: several maps placed side by side, loaded together as one. The members
+// share the map resources (MapBounds, the CollisionMap, TileLookup and NavGrid), which cover
+// all of them, so queries work across the seams between maps.
+
+use super::bounds::MapBounds;
+use super::collision::{self, CollisionMap};
+use super::depth::LayerDepths;
+use super::{normalize_path, MapMarkers, MapProperties, TiledMap, TiledMapBundle};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AssetPath, AsyncReadExt, LoadState},
+    log,
+    prelude::*,
+    utils::BoxedFuture,
+};
+use bevy_ecs_tilemap::prelude::*;
+use serde::Deserialize;
+use std::path::Path;
+use thiserror::Error;
+
+/// A map of a world and where it sits
+#[derive(Debug, Clone)]
+pub struct WorldMap {
+    /// the file name without its extension, e.g. "town" for town.tmx. Namespaces the member's
+    /// markers.
+    pub name: String,
+    /// of the map's top left corner from the world's, in pixels (y down) like in the .world file
+    pub offset: IVec2,
+    pub map: Handle<TiledMap>,
+}
+
+#[derive(TypePath, Asset, Debug)]
+pub struct TiledWorld {
+    pub maps: Vec<WorldMap>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorldFile {
+    #[serde(default)]
+    maps: Vec<WorldFileMap>,
+    #[serde(default)]
+    patterns: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WorldFileMap {
+    file_name: String,
+    x: i32,
+    y: i32,
+}
+
+#[derive(Debug, Error)]
+pub enum TiledWorldLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load Tiled world: {0}")]
+    Io(#[from] std::io::Error),
+    /// The file isn't a valid .world file
+    #[error("Could not parse Tiled world: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+pub struct TiledWorldLoader;
+
+impl AssetLoader for TiledWorldLoader {
+    type Asset = TiledWorld;
+    type Settings = ();
+    type Error = TiledWorldLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let file: WorldFile = serde_json::from_slice(&bytes)?;
+            if !file.patterns.is_empty() {
+                log::warn!(
+                    "Skipping the map patterns of world {}, only listed maps are supported.",
+                    load_context.path().display()
+                );
+            }
+
+            // member maps are relative to the world file
+            let world_dir = load_context
+                .path()
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default();
+            let maps = file
+                .maps
+                .into_iter()
+                .map(|map| {
+                    let path = normalize_path(&world_dir.join(&map.file_name));
+                    WorldMap {
+                        name: path
+                            .file_stem()
+                            .map(|stem| stem.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                        offset: IVec2::new(map.x, map.y),
+                        map: load_context.load(AssetPath::from(path)),
+                    }
+                })
+                .collect();
+
+            log::info!("Loaded world: {}", load_context.path().display());
+            Ok(TiledWorld { maps })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["world"];
+        EXTENSIONS
+    }
+}
+
+///
+/// WorldMembers
+///
+/// On a world's entity: the map entities of its members, its children, once every member map
+/// has loaded
+#[derive(Component, Debug, Default)]
+pub struct WorldMembers {
+    entities: Vec<Entity>,
+    spawned: bool,
+}
+
+impl WorldMembers {
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub(super) fn remove(&mut self, entity: Entity) {
+        self.entities.retain(|member| *member != entity);
+    }
+}
+
+///
+/// WorldMember
+///
+/// On the map entity of a member of a world
+#[derive(Component, Debug, Clone)]
+pub struct WorldMember {
+    pub world: Entity,
+    pub name: String,
+    // the world's bounds, moved so the member's own TMX coordinates land where it sits
+    pub(super) bounds: MapBounds,
+    rect: Rect,
+}
+
+impl WorldMember {
+    /// The area the member covers in world space
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+}
+
+#[derive(Default, Bundle)]
+pub struct TiledWorldBundle {
+    pub world: Handle<TiledWorld>,
+    pub members: WorldMembers,
+    /// the z of each layer, for every member
+    pub depths: LayerDepths,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+}
+
+///
+/// spawn_world_members: Bevy system
+///
+/// Once a world and all of its maps have loaded, sets MapBounds to the area they cover together,
+/// resets the map resources for it and spawns a map entity per member. Members must be
+/// orthogonal with the tile size of the first, and are snapped onto its tile grid. Members
+/// that fail to load are left out.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_world_members(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    worlds: Res<Assets<TiledWorld>>,
+    maps: Res<Assets<TiledMap>>,
+    mut collision: ResMut<CollisionMap>,
+    mut bounds: ResMut<MapBounds>,
+    mut markers: ResMut<MapMarkers>,
+    mut map_properties: ResMut<MapProperties>,
+    mut world_query: Query<(
+        Entity,
+        &Handle<TiledWorld>,
+        &mut WorldMembers,
+        Option<&LayerDepths>,
+    )>,
+) {
+    for (world_entity, world_handle, mut members, depths) in world_query.iter_mut() {
+        if members.spawned {
+            continue;
+        }
+        let Some(tiled_world) = worlds.get(world_handle) else {
+            continue;
+        };
+        let pending = tiled_world.maps.iter().any(|member| {
+            maps.get(&member.map).is_none()
+                && !matches!(asset_server.load_state(member.map.id()), LoadState::Failed)
+        });
+        if pending {
+            continue;
+        }
+        members.spawned = true;
+
+        let mut tile_size = None;
+        let mut layout = Vec::new();
+        for member in tiled_world.maps.iter() {
+            let Some(tiled_map) = maps.get(&member.map) else {
+                log::error!("Skipping world map {}, it failed to load.", member.name);
+                continue;
+            };
+            let member_bounds = MapBounds::of(tiled_map);
+            if member_bounds.map_type != TilemapType::Square {
+                log::warn!(
+                    "Skipping world map {}, only orthogonal maps can be in a world.",
+                    member.name
+                );
+                continue;
+            }
+            let size = *tile_size.get_or_insert(member_bounds.tile_size);
+            if member_bounds.tile_size != size {
+                log::warn!(
+                    "Skipping world map {}, its tiles aren't the size of the first map's.",
+                    member.name
+                );
+                continue;
+            }
+            let offset = (member.offset.as_vec2() / size).round().as_ivec2();
+            if offset.as_vec2() * size != member.offset.as_vec2() {
+                log::warn!(
+                    "World map {} isn't on the tile grid, snapping it to the nearest tile.",
+                    member.name
+                );
+            }
+            layout.push((member, member_bounds, offset));
+        }
+
+        let covered = layout
+            .iter()
+            .map(|(_, member_bounds, offset)| {
+                let min = member_bounds.min + *offset;
+                (min, min + member_bounds.size.as_ivec2())
+            })
+            .reduce(|(min, max), (start, end)| (min.min(start), max.max(end)));
+        let (Some((min, max)), Some(tile_size)) = (covered, tile_size) else {
+            log::warn!("World has no maps to spawn.");
+            continue;
+        };
+
+        let world_bounds = MapBounds {
+            min,
+            size: (max - min).as_uvec2(),
+            tile_size,
+            grid_size: tile_size,
+            map_type: TilemapType::Square,
+        };
+        *bounds = world_bounds;
+        collision::reset_for_map(&mut collision, &world_bounds);
+        markers.clear();
+        map_properties.clear();
+
+        for (member, member_bounds, offset) in layout {
+            let start = (member_bounds.min + offset).as_vec2() * tile_size;
+            let end = start + member_bounds.size.as_vec2() * tile_size;
+            let rect = Rect::from_corners(
+                world_bounds.tmx_to_world(start.x, start.y),
+                world_bounds.tmx_to_world(end.x, end.y),
+            );
+            let entity = commands
+                .spawn((
+                    TiledMapBundle {
+                        tiled_map: member.map.clone(),
+                        depths: depths.cloned().unwrap_or_default(),
+                        ..Default::default()
+                    },
+                    WorldMember {
+                        world: world_entity,
+                        name: member.name.clone(),
+                        bounds: MapBounds {
+                            min: world_bounds.min - offset,
+                            ..world_bounds
+                        },
+                        rect,
+                    },
+                    Name::new(member.name.clone()),
+                ))
+                .set_parent(world_entity)
+                .id();
+            members.entities.push(entity);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! The input display, for tutorial GIFs and streams: the glyphs of the actions held, the move
+//! axes on a little dial and the last INPUT_HISTORY presses, in the bottom right corner over the
+//! HUD. It goes by ActionState, so it shows the actions the bindings made of the inputs, which
+//! makes it a way to check the bindings too.
+//!
+//! The input_overlay start option, `?inputoverlay=1`, or the input_overlay console command
+//! shows it. Hidden it's not spawned, and its systems don't run.
+
+use super::{Action, ActionAxis, ActionState, ActiveDevice, InputMap};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Number of presses listed
+pub const INPUT_HISTORY: usize = 5;
+/// Size of the dial and its dot in pixels
+const DIAL_SIZE: f32 = 32.0;
+const DOT_SIZE: f32 = 6.0;
+
+///
+/// InputDisplay
+///
+/// Whether the input display is showing
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InputDisplay(pub bool);
+
+///
+/// InputHistory
+///
+/// The actions last pressed while the display shows, newest first, at most INPUT_HISTORY
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub struct InputHistory {
+    presses: VecDeque<Action>,
+}
+
+impl InputHistory {
+    pub fn push(&mut self, action: Action) {
+        self.presses.push_front(action);
+        self.presses.truncate(INPUT_HISTORY);
+    }
+
+    pub fn presses(&self) -> impl Iterator<Item = Action> + '_ {
+        self.presses.iter().copied()
+    }
+}
+
+/// Marks the display's root node
+#[derive(Component)]
+pub struct InputDisplayRoot;
+
+/// Marks the text of the actions held
+#[derive(Component)]
+pub struct InputDisplayHeld;
+
+/// Marks the text of the last presses
+#[derive(Component)]
+pub struct InputDisplayHistory;
+
+/// Marks the dial's dot, placed by the move axes
+#[derive(Component)]
+pub struct InputDisplayDot;
+
+/// The input display, hidden until InputDisplay is on
+pub struct InputDisplayPlugin;
+
+impl Plugin for InputDisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputDisplay>()
+            .init_resource::<InputHistory>()
+            .add_systems(
+                Update,
+                (
+                    show_input_display.run_if(resource_changed::<InputDisplay>),
+                    (record_presses, update_input_display).run_if(display_shown),
+                )
+                    .chain(),
+            );
+
+        #[cfg(feature = "dev")]
+        {
+            use crate::console::{ConsoleError, RegisterConsoleCommand};
+            const USAGE: &str = "input_overlay [on|off]";
+            app.register_console_command(
+                USAGE,
+                "Shows or hides the input display, toggling it without an argument",
+                |args, world| {
+                    let shown = match args.optional::<String>("on|off")?.as_deref() {
+                        Some("on") => true,
+                        Some("off") => false,
+                        Some(other) => {
+                            return Err(ConsoleError::Invalid {
+                                name: "on|off",
+                                value: other.to_string(),
+                                usage: USAGE.to_string(),
+                            })
+                        }
+                        None => !world.resource::<InputDisplay>().0,
+                    };
+                    world.resource_mut::<InputDisplay>().0 = shown;
+                    Ok(format!(
+                        "Input display {}",
+                        if shown { "on" } else { "off" }
+                    ))
+                },
+            );
+        }
+    }
+}
+
+fn display_shown(display: Res<InputDisplay>) -> bool {
+    display.0
+}
+
+///
+/// show_input_display: Bevy system
+///
+/// Spawns the display when InputDisplay turns on and despawns it when it turns off, the history
+/// starting over each time
+pub fn show_input_display(
+    mut commands: Commands,
+    display: Res<InputDisplay>,
+    mut history: ResMut<InputHistory>,
+    root_query: Query<Entity, With<InputDisplayRoot>>,
+) {
+    for root in root_query.iter() {
+        commands.entity(root).despawn_recursive();
+    }
+    *history = InputHistory::default();
+    if display.0 {
+        spawn_input_display(&mut commands);
+    }
+}
+
+fn spawn_input_display(commands: &mut Commands) {
+    let text = |size: f32| {
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: size,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+    };
+    commands
+        .spawn((
+            InputDisplayRoot,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(8.0),
+                    bottom: Val::Px(8.0),
+                    align_items: AlignItems::FlexEnd,
+                    column_gap: Val::Px(8.0),
+                    padding: UiRect::all(Val::Px(6.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+                // over the HUD and the prompt, under the dialogue box
+                z_index: ZIndex::Global(35),
+                ..default()
+            },
+        ))
+        .with_children(|display| {
+            display.spawn((InputDisplayHistory, text(12.0)));
+            display.spawn((InputDisplayHeld, text(18.0)));
+            display
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(DIAL_SIZE),
+                        height: Val::Px(DIAL_SIZE),
+                        ..default()
+                    },
+                    background_color: Color::rgba(1.0, 1.0, 1.0, 0.15).into(),
+                    ..default()
+                })
+                .with_children(|dial| {
+                    dial.spawn((
+                        InputDisplayDot,
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                width: Val::Px(DOT_SIZE),
+                                height: Val::Px(DOT_SIZE),
+                                ..default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+///
+/// record_presses: Bevy system
+///
+/// Adds the frame's presses to the InputHistory
+pub fn record_presses(actions: Res<ActionState>, mut history: ResMut<InputHistory>) {
+    for action in Action::ALL {
+        if actions.just_pressed(action) {
+            history.push(action);
+        }
+    }
+}
+
+/// The glyph of `action` on the active device, or its name if nothing there binds it
+fn action_glyph(action: Action, map: Option<&InputMap>, device: ActiveDevice) -> String {
+    map.and_then(|map| map.glyph(action, device.glyphs()))
+        .unwrap_or_else(|| action.label().to_string())
+}
+
+///
+/// update_input_display: Bevy system
+///
+/// Writes the actions held and the last presses, and puts the dial's dot where the move axes are
+#[allow(clippy::type_complexity)]
+pub fn update_input_display(
+    actions: Res<ActionState>,
+    history: Res<InputHistory>,
+    map: Option<Res<InputMap>>,
+    device: Option<Res<ActiveDevice>>,
+    mut held_query: Query<&mut Text, (With<InputDisplayHeld>, Without<InputDisplayHistory>)>,
+    mut history_query: Query<&mut Text, (With<InputDisplayHistory>, Without<InputDisplayHeld>)>,
+    mut dot_query: Query<&mut Style, With<InputDisplayDot>>,
+) {
+    let map = map.as_deref();
+    let device = device.as_deref().copied().unwrap_or_default();
+    let held = actions
+        .held()
+        .iter()
+        .map(|action| action_glyph(*action, map, device))
+        .collect::<Vec<_>>()
+        .join(" ");
+    for mut text in held_query.iter_mut() {
+        if text.sections[0].value != held {
+            text.sections[0].value = held.clone();
+        }
+    }
+    let presses = history
+        .presses()
+        .map(|action| format!("{} {}", action_glyph(action, map, device), action.label()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    for mut text in history_query.iter_mut() {
+        if text.sections[0].value != presses {
+            text.sections[0].value = presses.clone();
+        }
+    }
+
+    // up on the axis is up on screen
+    let travel = DIAL_SIZE - DOT_SIZE;
+    let left = Val::Px((actions.axis(ActionAxis::MoveX) + 1.0) / 2.0 * travel);
+    let top = Val::Px((1.0 - actions.axis(ActionAxis::MoveY)) / 2.0 * travel);
+    for mut style in dot_query.iter_mut() {
+        if (style.left, style.top) != (left, top) {
+            (style.left, style.top) = (left, top);
+        }
+    }
+}
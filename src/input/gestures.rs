@@ -0,0 +1,173 @@
+//! Two-finger gestures on a touch screen: pinching zooms the main camera through SetZoom, and
+//! with GestureSettings::free_look, dragging pans it until a tap or a few idle seconds put it
+//! back.
+//!
+//! Only touches that began away from the touch controls and the UI's buttons count, and nothing
+//! happens with fewer than two, so one finger plays as it would without gestures.
+
+use super::touch::{read_touch_controls, TouchControls};
+use super::ActionSet;
+use crate::gfx::{MainCamera, SetZoom, PIXELS_PER_UNIT};
+use bevy::{input::touch::Touches, input::InputSystem, prelude::*};
+use std::collections::BTreeMap;
+
+/// A touch lifted within this many seconds of starting, without moving TAP_DISTANCE, is a tap
+const TAP_TIME: f64 = 0.25;
+/// Logical pixels a tap can move
+const TAP_DISTANCE: f32 = 12.0;
+
+///
+/// GestureSettings
+///
+/// * free_look: two-finger drags pan the camera
+/// * free_look_timeout: real seconds without a drag after which the camera goes back
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct GestureSettings {
+    pub free_look: bool,
+    pub free_look_timeout: f64,
+}
+
+impl Default for GestureSettings {
+    fn default() -> Self {
+        GestureSettings {
+            free_look: true,
+            free_look_timeout: 3.0,
+        }
+    }
+}
+
+///
+/// FreeLook
+///
+/// How far two-finger drags have panned the camera, in world units, and when the last one did
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct FreeLook {
+    pub offset: Vec2,
+    last_pan: f64,
+}
+
+impl FreeLook {
+    /// Whether the camera is panned away
+    pub fn is_active(&self) -> bool {
+        self.offset != Vec2::ZERO
+    }
+}
+
+/// The touches gestures follow, by id: when each began and where
+#[derive(Resource, Debug, Default)]
+pub struct GestureTouches {
+    started: BTreeMap<u64, (f64, Vec2)>,
+    /// the distance between the two fingers and their midpoint last frame
+    pinch: Option<(f32, Vec2)>,
+}
+
+/// GestureSettings, FreeLook and the systems reading them
+pub struct GesturePlugin;
+
+impl Plugin for GesturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GestureSettings>()
+            .init_resource::<FreeLook>()
+            .init_resource::<GestureTouches>()
+            .add_event::<SetZoom>()
+            .add_systems(
+                PreUpdate,
+                recognize_gestures
+                    .after(InputSystem)
+                    .after(read_touch_controls)
+                    .before(ActionSet),
+            );
+    }
+}
+
+///
+/// recognize_gestures: Bevy system
+///
+/// Follows the touches that began away from the controls: two of them pinch and pan, a tap puts
+/// a panned camera back, as does a while without panning
+#[allow(clippy::too_many_arguments)]
+pub fn recognize_gestures(
+    touches: Option<Res<Touches>>,
+    time: Res<Time<Real>>,
+    settings: Res<GestureSettings>,
+    controls: Option<Res<TouchControls>>,
+    button_query: Query<(&Node, &GlobalTransform, &ViewVisibility), With<Interaction>>,
+    mut camera_query: Query<(&mut Transform, &OrthographicProjection), With<MainCamera>>,
+    mut gestures: ResMut<GestureTouches>,
+    mut free_look: ResMut<FreeLook>,
+    mut zooms: EventWriter<SetZoom>,
+) {
+    let Some(touches) = touches else {
+        return;
+    };
+    let now = time.elapsed_seconds_f64();
+    let on_controls = |touch: &bevy::input::touch::Touch| {
+        controls
+            .as_ref()
+            .is_some_and(|controls| controls.claims(touch.id()))
+            || button_query.iter().any(|(node, transform, visibility)| {
+                visibility.get() && node.logical_rect(transform).contains(touch.position())
+            })
+    };
+    for touch in touches.iter_just_pressed() {
+        if !on_controls(touch) {
+            gestures
+                .started
+                .insert(touch.id(), (now, touch.start_position()));
+        }
+    }
+
+    let mut tapped = false;
+    for touch in touches
+        .iter_just_released()
+        .chain(touches.iter_just_canceled())
+    {
+        if let Some((started, from)) = gestures.started.remove(&touch.id()) {
+            tapped |= gestures.started.is_empty()
+                && gestures.pinch.is_none()
+                && now - started <= TAP_TIME
+                && from.distance(touch.position()) <= TAP_DISTANCE;
+        }
+    }
+    gestures
+        .started
+        .retain(|id, _| touches.get_pressed(*id).is_some());
+
+    let fingers: Vec<Vec2> = gestures
+        .started
+        .keys()
+        .filter_map(|id| touches.get_pressed(*id))
+        .map(|touch| touch.position())
+        .collect();
+    let pinch = match fingers.as_slice() {
+        [first, second] => Some((first.distance(*second), (*first + *second) / 2.0)),
+        _ => None,
+    };
+    if let (Some((distance, middle)), Some((last_distance, last_middle))) = (pinch, gestures.pinch)
+    {
+        if distance > 0.0 && last_distance > 0.0 && distance != last_distance {
+            // fingers apart zoom in
+            zooms.send(SetZoom::By(last_distance / distance));
+        }
+        let moved = middle - last_middle;
+        if settings.free_look && moved != Vec2::ZERO {
+            for (mut transform, projection) in camera_query.iter_mut() {
+                // dragging moves the world with the fingers, y pointing down on screen
+                let pan = Vec2::new(-moved.x, moved.y) * projection.scale / PIXELS_PER_UNIT;
+                transform.translation += pan.extend(0.0);
+                free_look.offset += pan;
+            }
+            free_look.last_pan = now;
+        }
+    }
+    // a finger going down or up starts the pinch over, so the zoom doesn't jump
+    gestures.pinch = pinch.filter(|_| !touches.any_just_pressed() && !tapped);
+
+    let timed_out = now - free_look.last_pan >= settings.free_look_timeout;
+    if free_look.is_active() && (tapped || (timed_out && pinch.is_none())) {
+        for (mut transform, _) in camera_query.iter_mut() {
+            transform.translation -= free_look.offset.extend(0.0);
+        }
+        free_look.offset = Vec2::ZERO;
+    }
+}
@@ -0,0 +1,863 @@
+//! What the keys, mouse buttons and gamepads do, rebindable from the settings menu.
+//!
+//! InputMap binds each Action to any number of inputs. ActionState holds the frame's actions,
+//! read from them in PreUpdate, and frame-based systems like pausing read it. Gameplay in
+//! FixedUpdate reads FixedActionState rather than any of them, so a replay can stand in for the
+//! player: see the replay module.
+//!
+//! A tap that comes and goes between two fixed steps is held for the next step, so it isn't
+//! lost. Presses of some actions are buffered on top, for gameplay to `consume` once within their
+//! window, like a jump pressed just before landing.
+//!
+//! Gamepads work like the keys: sticks are read through a dead zone and a response curve, and the
+//! device used last is kept in ActiveDevice so prompts show its glyphs. The active pad going away
+//! mid-play pauses the game.
+//!
+//! The settings menu rebinds actions with StartRebind, see the rebind module. On phones,
+//! TouchControlsPlugin puts a joystick and buttons on screen, feeding VirtualControls, and
+//! GesturePlugin zooms and pans the camera with two fingers. InputDisplayPlugin shows the actions
+//! on screen, for recordings, see the display module.
+
+use crate::settings::{Settings, SettingsChanged};
+use crate::state::{AppState, ChangeState, GameplaySet};
+use crate::timestep::FixedSet;
+use crate::toast::ShowToast;
+use bevy::input::gamepad::{GamepadAxisChangedEvent, GamepadConnection, GamepadConnectionEvent};
+use bevy::{ecs::system::SystemParam, input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+mod display;
+mod gestures;
+mod rebind;
+mod touch;
+
+pub use display::{
+    record_presses, show_input_display, update_input_display, InputDisplay, InputDisplayDot,
+    InputDisplayHeld, InputDisplayHistory, InputDisplayPlugin, InputDisplayRoot, InputHistory,
+    INPUT_HISTORY,
+};
+pub use gestures::{FreeLook, GesturePlugin, GestureSettings};
+pub use rebind::{
+    rebind, Binding, BindingsChanged, PressedInputs, RebindConflict, RebindRejected, Rebinding,
+    ResolveRebind, StartRebind, RESERVED_KEYS,
+};
+pub use touch::{TouchControls, TouchControlsPlugin};
+
+/// Something the player does with a key, button or stick
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Interact,
+    Attack,
+    Jump,
+    Pause,
+    ToggleMute,
+    ToggleFullscreen,
+}
+
+/// A pair of opposite actions read as one value from -1 to 1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ActionAxis {
+    /// MoveLeft to MoveRight
+    MoveX,
+    /// MoveDown to MoveUp
+    MoveY,
+}
+
+impl ActionAxis {
+    /// Every axis
+    pub const ALL: [ActionAxis; 2] = [ActionAxis::MoveX, ActionAxis::MoveY];
+
+    /// The actions at the negative and the positive end of the axis
+    pub fn actions(self) -> (Action, Action) {
+        match self {
+            ActionAxis::MoveX => (Action::MoveLeft, Action::MoveRight),
+            ActionAxis::MoveY => (Action::MoveDown, Action::MoveUp),
+        }
+    }
+}
+
+///
+/// InputMap
+///
+/// The keys, mouse buttons, gamepad buttons and gamepad axes of each action. An action can have
+/// any number of each, and actions the settings don't bind keep their defaults. A stick pushed
+/// past `dead_zone` holds the action at that end of its axis.
+/// * dead_zone: how far a stick can move off the middle and still read 0, from 0 to 1
+/// * response_curve: the power of a stick's travel past the dead zone: 1 reads it as it is,
+///   more gives finer control near the middle
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct InputMap {
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+    mouse_buttons: BTreeMap<Action, Vec<MouseButton>>,
+    gamepad_buttons: BTreeMap<Action, Vec<GamepadButtonType>>,
+    gamepad_axes: BTreeMap<ActionAxis, Vec<GamepadAxisType>>,
+    pub dead_zone: f32,
+    pub response_curve: f32,
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let keys = vec![
+            (Action::MoveUp, vec![KeyCode::KeyW, KeyCode::ArrowUp]),
+            (Action::MoveDown, vec![KeyCode::KeyS, KeyCode::ArrowDown]),
+            (Action::MoveLeft, vec![KeyCode::KeyA, KeyCode::ArrowLeft]),
+            (Action::MoveRight, vec![KeyCode::KeyD, KeyCode::ArrowRight]),
+            (Action::Interact, vec![KeyCode::KeyE, KeyCode::Space]),
+            (Action::Attack, vec![KeyCode::KeyJ]),
+            (Action::Jump, vec![KeyCode::KeyL]),
+            (Action::Pause, vec![KeyCode::Escape, KeyCode::KeyP]),
+            (Action::ToggleMute, vec![KeyCode::KeyM]),
+            (Action::ToggleFullscreen, vec![KeyCode::F11]),
+        ];
+        let gamepad_buttons = vec![
+            (Action::MoveUp, vec![GamepadButtonType::DPadUp]),
+            (Action::MoveDown, vec![GamepadButtonType::DPadDown]),
+            (Action::MoveLeft, vec![GamepadButtonType::DPadLeft]),
+            (Action::MoveRight, vec![GamepadButtonType::DPadRight]),
+            (Action::Interact, vec![GamepadButtonType::South]),
+            (Action::Attack, vec![GamepadButtonType::West]),
+            (Action::Jump, vec![GamepadButtonType::East]),
+            (Action::Pause, vec![GamepadButtonType::Start]),
+        ];
+        InputMap {
+            keys: keys.into_iter().collect(),
+            mouse_buttons: vec![(Action::Attack, vec![MouseButton::Left])]
+                .into_iter()
+                .collect(),
+            gamepad_buttons: gamepad_buttons.into_iter().collect(),
+            gamepad_axes: vec![
+                (ActionAxis::MoveX, vec![GamepadAxisType::LeftStickX]),
+                (ActionAxis::MoveY, vec![GamepadAxisType::LeftStickY]),
+            ]
+            .into_iter()
+            .collect(),
+            dead_zone: 0.2,
+            response_curve: 2.0,
+        }
+    }
+}
+
+impl InputMap {
+    /// The keys bound to `action`
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    /// Binds `keys` to `action`, replacing its keys. No keys leaves it without keys.
+    pub fn bind(&mut self, action: Action, keys: Vec<KeyCode>) {
+        self.keys.insert(action, keys);
+    }
+
+    /// The mouse buttons bound to `action`
+    pub fn mouse_buttons(&self, action: Action) -> &[MouseButton] {
+        self.mouse_buttons
+            .get(&action)
+            .map_or(&[], |buttons| buttons.as_slice())
+    }
+
+    /// Binds `buttons` to `action`, replacing its mouse buttons
+    pub fn bind_mouse(&mut self, action: Action, buttons: Vec<MouseButton>) {
+        self.mouse_buttons.insert(action, buttons);
+    }
+
+    /// The gamepad buttons bound to `action`, on any gamepad
+    pub fn gamepad_buttons(&self, action: Action) -> &[GamepadButtonType] {
+        self.gamepad_buttons
+            .get(&action)
+            .map_or(&[], |buttons| buttons.as_slice())
+    }
+
+    /// Binds `buttons` to `action`, replacing its gamepad buttons
+    pub fn bind_gamepad(&mut self, action: Action, buttons: Vec<GamepadButtonType>) {
+        self.gamepad_buttons.insert(action, buttons);
+    }
+
+    /// The gamepad axes bound to `axis`, on any gamepad
+    pub fn gamepad_axes(&self, axis: ActionAxis) -> &[GamepadAxisType] {
+        self.gamepad_axes
+            .get(&axis)
+            .map_or(&[], |axes| axes.as_slice())
+    }
+
+    /// Binds `axes` to `axis`, replacing its gamepad axes
+    pub fn bind_gamepad_axes(&mut self, axis: ActionAxis, axes: Vec<GamepadAxisType>) {
+        self.gamepad_axes.insert(axis, axes);
+    }
+
+    /// A stick value read through the dead zone and the response curve, from -1 to 1
+    pub fn stick_response(&self, value: f32) -> f32 {
+        let travel = (value.abs() - self.dead_zone) / (1.0 - self.dead_zone).max(f32::EPSILON);
+        if travel <= 0.0 {
+            return 0.0;
+        }
+        travel.min(1.0).powf(self.response_curve).copysign(value)
+    }
+
+    /// The glyph of the first binding of `action` in `glyphs`, for prompts like "Press E"
+    pub fn glyph(&self, action: Action, glyphs: Glyphs) -> Option<String> {
+        match glyphs {
+            Glyphs::Keyboard => self
+                .keys(action)
+                .first()
+                .map(|key| key_glyph(*key))
+                .or_else(|| {
+                    self.mouse_buttons(action)
+                        .first()
+                        .map(|button| mouse_glyph(*button))
+                }),
+            Glyphs::Gamepad => self
+                .gamepad_buttons(action)
+                .first()
+                .map(|button| gamepad_glyph(*button)),
+        }
+    }
+
+    /// Every action with a binding
+    fn actions(&self) -> BTreeSet<Action> {
+        let axes = self.gamepad_axes.keys().flat_map(|axis| {
+            let (negative, positive) = axis.actions();
+            [negative, positive]
+        });
+        self.keys
+            .keys()
+            .chain(self.mouse_buttons.keys())
+            .chain(self.gamepad_buttons.keys())
+            .copied()
+            .chain(axes)
+            .collect()
+    }
+}
+
+///
+/// VirtualControls
+///
+/// The actions held and the axes moved by on-screen controls, read into ActionState along with
+/// the devices. The axes are from -1 to 1, through InputMap::stick_response already, and like a
+/// stick hold the actions at their ends.
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct VirtualControls {
+    pub held: BTreeSet<Action>,
+    pub axes: BTreeMap<ActionAxis, f32>,
+}
+
+impl VirtualControls {
+    /// Whether the controls hold or move nothing
+    pub fn is_idle(&self) -> bool {
+        self.held.is_empty() && self.axes.values().all(|value| *value == 0.0)
+    }
+}
+
+/// InputDevices: Bevy system parameter
+///
+/// The inputs InputMap binds, and VirtualControls. Any of them can be missing, like in tests, and
+/// then nothing on it is held.
+#[derive(SystemParam)]
+pub struct InputDevices<'w> {
+    virtual_controls: Option<Res<'w, VirtualControls>>,
+    keys: Option<Res<'w, ButtonInput<KeyCode>>>,
+    mouse_buttons: Option<Res<'w, ButtonInput<MouseButton>>>,
+    gamepads: Option<Res<'w, Gamepads>>,
+    gamepad_buttons: Option<Res<'w, ButtonInput<GamepadButton>>>,
+    gamepad_axes: Option<Res<'w, Axis<GamepadAxis>>>,
+}
+
+impl InputDevices<'_> {
+    /// Whether a button of `action` is held, and whether one was pressed or released this frame
+    fn buttons(&self, map: &InputMap, action: Action) -> [bool; 3] {
+        let mut state = [false; 3];
+        let mut add = |pressed: bool, just_pressed: bool, just_released: bool| {
+            state[0] |= pressed;
+            state[1] |= just_pressed;
+            state[2] |= just_released;
+        };
+        if let Some(controls) = &self.virtual_controls {
+            add(controls.held.contains(&action), false, false);
+        }
+        if let Some(input) = &self.keys {
+            let keys = map.keys(action).iter().copied();
+            add(
+                input.any_pressed(keys.clone()),
+                input.any_just_pressed(keys.clone()),
+                input.any_just_released(keys),
+            );
+        }
+        if let Some(input) = &self.mouse_buttons {
+            let buttons = map.mouse_buttons(action).iter().copied();
+            add(
+                input.any_pressed(buttons.clone()),
+                input.any_just_pressed(buttons.clone()),
+                input.any_just_released(buttons),
+            );
+        }
+        if let (Some(gamepads), Some(input)) = (&self.gamepads, &self.gamepad_buttons) {
+            for gamepad in gamepads.iter() {
+                let buttons = map
+                    .gamepad_buttons(action)
+                    .iter()
+                    .map(|button| GamepadButton::new(gamepad, *button));
+                add(
+                    input.any_pressed(buttons.clone()),
+                    input.any_just_pressed(buttons.clone()),
+                    input.any_just_released(buttons),
+                );
+            }
+        }
+        state
+    }
+
+    /// The stick value of `axis` furthest from the middle through InputMap::stick_response,
+    /// VirtualControls counting as a stick, or 0 if none is past the dead zone
+    fn stick(&self, map: &InputMap, axis: ActionAxis) -> f32 {
+        let on_screen = self
+            .virtual_controls
+            .as_ref()
+            .and_then(|controls| controls.axes.get(&axis).copied())
+            .unwrap_or_default();
+        let (Some(gamepads), Some(input)) = (&self.gamepads, &self.gamepad_axes) else {
+            return on_screen;
+        };
+        gamepads
+            .iter()
+            .flat_map(|gamepad| {
+                map.gamepad_axes(axis)
+                    .iter()
+                    .filter_map(move |axis_type| input.get(GamepadAxis::new(gamepad, *axis_type)))
+            })
+            .map(|value| map.stick_response(value))
+            .fold(on_screen, |furthest: f32, value| {
+                if value.abs() > furthest.abs() {
+                    value
+                } else {
+                    furthest
+                }
+            })
+    }
+}
+
+///
+/// ActionState
+///
+/// The actions held this frame, those that started or stopped being held this frame, and the
+/// value of each axis: the stick if one is pushed, else the actions at its ends.
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct ActionState {
+    held: BTreeSet<Action>,
+    just_pressed: BTreeSet<Action>,
+    just_released: BTreeSet<Action>,
+    axes: BTreeMap<ActionAxis, f32>,
+    unstepped: BTreeSet<Action>,
+}
+
+impl ActionState {
+    /// Whether `action` is held
+    pub fn pressed(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// Whether `action` started being held this frame
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    /// Whether `action` stopped being held this frame
+    pub fn just_released(&self, action: Action) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    /// The actions held
+    pub fn held(&self) -> &BTreeSet<Action> {
+        &self.held
+    }
+
+    /// The value of `axis`, from -1 to 1
+    pub fn axis(&self, axis: ActionAxis) -> f32 {
+        self.axes.get(&axis).copied().unwrap_or_default()
+    }
+
+    /// The actions pressed since the last call, for a fixed step to see the taps that came and
+    /// went since the one before
+    pub fn take_presses(&mut self) -> BTreeSet<Action> {
+        std::mem::take(&mut self.unstepped)
+    }
+
+    /// Reads the frame's actions from `devices`
+    pub fn update(&mut self, map: &InputMap, devices: &InputDevices) {
+        let sticks: BTreeMap<ActionAxis, f32> = ActionAxis::ALL
+            .iter()
+            .map(|axis| (*axis, devices.stick(map, *axis)))
+            .collect();
+        let previous = std::mem::take(&mut self.held);
+        self.just_pressed.clear();
+        self.just_released.clear();
+        let mut actions = map.actions();
+        if let Some(controls) = &devices.virtual_controls {
+            actions.extend(controls.held.iter().copied());
+        }
+        for action in actions {
+            let [mut pressed, just_pressed, just_released] = devices.buttons(map, action);
+            pressed |= sticks.iter().any(|(axis, value)| {
+                let (negative, positive) = axis.actions();
+                (*value < 0.0 && action == negative) || (*value > 0.0 && action == positive)
+            });
+            if pressed {
+                self.held.insert(action);
+            }
+            if just_pressed || (pressed && !previous.contains(&action)) {
+                self.just_pressed.insert(action);
+            }
+            if just_released || (!pressed && previous.contains(&action)) {
+                self.just_released.insert(action);
+            }
+        }
+        self.unstepped.extend(self.just_pressed.iter().copied());
+        self.axes = sticks
+            .into_iter()
+            .map(|(axis, stick)| {
+                let value = if stick != 0.0 {
+                    stick
+                } else {
+                    digital_axis(&self.held, axis)
+                };
+                (axis, value)
+            })
+            .collect();
+    }
+}
+
+/// The value of `axis` from the actions at its ends: -1, 0 or 1
+fn digital_axis(held: &BTreeSet<Action>, axis: ActionAxis) -> f32 {
+    let (negative, positive) = axis.actions();
+    let end = |action| if held.contains(&action) { 1.0 } else { 0.0 };
+    end(positive) - end(negative)
+}
+
+/// Seconds a press of Interact, Attack or Jump stays buffered by default
+pub const BUFFER_WINDOW: f32 = 0.12;
+
+///
+/// FixedActionState
+///
+/// The actions held during the current fixed step of gameplay, taken from ActionState at its
+/// start, and those held in the step before. A tap between two steps is held for the next one.
+/// Sticks count as the actions at the ends of their axes, so replays, which keep actions, repeat
+/// them exactly.
+///
+/// Presses of actions with a buffer window can be consumed once within it, steps later. Actions
+/// without one, like movement, aren't buffered. Everything here follows from the actions held
+/// and the fixed clock, so it replays the same.
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct FixedActionState {
+    held: BTreeSet<Action>,
+    previous: BTreeSet<Action>,
+    now: f64,
+    held_since: BTreeMap<Action, f64>,
+    presses: BTreeMap<Action, f64>,
+    // held_since and presses as the step found them, for set_held to work them out again
+    step_start: (BTreeMap<Action, f64>, BTreeMap<Action, f64>),
+    buffer_windows: BTreeMap<Action, f32>,
+}
+
+impl Default for FixedActionState {
+    fn default() -> Self {
+        FixedActionState {
+            held: BTreeSet::new(),
+            previous: BTreeSet::new(),
+            now: 0.0,
+            held_since: BTreeMap::new(),
+            presses: BTreeMap::new(),
+            step_start: Default::default(),
+            buffer_windows: vec![
+                (Action::Interact, BUFFER_WINDOW),
+                (Action::Attack, BUFFER_WINDOW),
+                (Action::Jump, BUFFER_WINDOW),
+            ]
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+impl FixedActionState {
+    /// Whether `action` is held in this step
+    pub fn pressed(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    /// Whether `action` is held in this step and wasn't in the last
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.held.contains(&action) && !self.previous.contains(&action)
+    }
+
+    /// Whether `action` was held in the last step and isn't in this one
+    pub fn just_released(&self, action: Action) -> bool {
+        !self.held.contains(&action) && self.previous.contains(&action)
+    }
+
+    /// The value of `axis` in this step: -1, 0 or 1
+    pub fn axis(&self, axis: ActionAxis) -> f32 {
+        digital_axis(&self.held, axis)
+    }
+
+    /// The actions held in this step
+    pub fn held(&self) -> &BTreeSet<Action> {
+        &self.held
+    }
+
+    /// The actions held in the last step
+    pub fn previous(&self) -> &BTreeSet<Action> {
+        &self.previous
+    }
+
+    /// Takes the buffered press of `action`, if it was pressed within its buffer window and
+    /// hasn't been consumed yet. One press is consumed once, however many steps it waits.
+    pub fn consume(&mut self, action: Action) -> bool {
+        self.presses.remove(&action).is_some()
+    }
+
+    /// Whether a press of `action` is buffered, waiting to be consumed
+    pub fn buffered(&self, action: Action) -> bool {
+        self.presses.contains_key(&action)
+    }
+
+    /// Seconds `action` has been held for, 0 when it isn't. In the step it's released, how long
+    /// the hold that just ended was, for charged attacks and the like.
+    pub fn held_for(&self, action: Action) -> f32 {
+        self.held_since
+            .get(&action)
+            .map_or(0.0, |since| (self.now - since) as f32)
+    }
+
+    /// Seconds a press of `action` stays buffered, 0 when it isn't
+    pub fn buffer_window(&self, action: Action) -> f32 {
+        self.buffer_windows
+            .get(&action)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Buffers presses of `action` for `seconds`, or stops buffering them with 0
+    pub fn set_buffer_window(&mut self, action: Action, seconds: f32) {
+        if seconds > 0.0 {
+            self.buffer_windows.insert(action, seconds);
+        } else {
+            self.buffer_windows.remove(&action);
+            self.presses.remove(&action);
+        }
+    }
+
+    /// Starts a step at `now` seconds on the fixed clock in which `held` are held
+    pub fn step(&mut self, held: BTreeSet<Action>, now: f64) {
+        self.previous = std::mem::replace(&mut self.held, held);
+        self.now = now;
+        self.step_start = (self.held_since.clone(), self.presses.clone());
+        self.track_presses();
+    }
+
+    /// Replaces the actions held in this step, keeping the last step's
+    pub fn set_held(&mut self, held: BTreeSet<Action>) {
+        self.held = held;
+        (self.held_since, self.presses) = self.step_start.clone();
+        self.track_presses();
+    }
+
+    /// Times the actions pressed this step, buffers them and drops the buffered presses that
+    /// are too old
+    fn track_presses(&mut self) {
+        let (held, previous, now) = (&self.held, &self.previous, self.now);
+        for action in held.difference(previous) {
+            self.held_since.insert(*action, now);
+            if self.buffer_windows.contains_key(action) {
+                self.presses.insert(*action, now);
+            }
+        }
+        // released this step, for held_for to say how long the hold was
+        self.held_since
+            .retain(|action, _| held.contains(action) || previous.contains(action));
+        let windows = &self.buffer_windows;
+        self.presses.retain(|action, at| {
+            windows
+                .get(action)
+                .is_some_and(|window| now - *at <= *window as f64)
+        });
+    }
+}
+
+/// Whether gameplay gets the player's actions (on by default). Screens over the game that act
+/// on the actions themselves, like the dialogue box, turn it off while they're up.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameplayInput(pub bool);
+
+impl Default for GameplayInput {
+    fn default() -> Self {
+        GameplayInput(true)
+    }
+}
+
+/// The glyphs a prompt shows for an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Glyphs {
+    Keyboard,
+    Gamepad,
+}
+
+///
+/// ActiveDevice
+///
+/// The device the player used last: a key or mouse button pressed makes it the keyboard, a
+/// gamepad button pressed or a stick pushed past the dead zone makes it that gamepad
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ActiveDevice {
+    #[default]
+    KeyboardMouse,
+    Gamepad(Gamepad),
+}
+
+impl ActiveDevice {
+    /// The glyphs prompts should show
+    pub fn glyphs(self) -> Glyphs {
+        match self {
+            ActiveDevice::KeyboardMouse => Glyphs::Keyboard,
+            ActiveDevice::Gamepad(_) => Glyphs::Gamepad,
+        }
+    }
+}
+
+fn key_glyph(key: KeyCode) -> String {
+    let glyph = match key {
+        KeyCode::ArrowUp => "↑",
+        KeyCode::ArrowDown => "↓",
+        KeyCode::ArrowLeft => "←",
+        KeyCode::ArrowRight => "→",
+        KeyCode::Escape => "Esc",
+        KeyCode::Backquote => "`",
+        _ => {
+            let name = format!("{:?}", key);
+            let short = name
+                .strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Digit"));
+            return short.unwrap_or(&name).to_string();
+        }
+    };
+    glyph.to_string()
+}
+
+fn mouse_glyph(button: MouseButton) -> String {
+    match button {
+        MouseButton::Left => "LMB".to_string(),
+        MouseButton::Right => "RMB".to_string(),
+        MouseButton::Middle => "MMB".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn gamepad_glyph(button: GamepadButtonType) -> String {
+    let glyph = match button {
+        GamepadButtonType::South => "Ⓐ",
+        GamepadButtonType::East => "Ⓑ",
+        GamepadButtonType::West => "Ⓧ",
+        GamepadButtonType::North => "Ⓨ",
+        GamepadButtonType::LeftTrigger => "LB",
+        GamepadButtonType::RightTrigger => "RB",
+        GamepadButtonType::LeftTrigger2 => "LT",
+        GamepadButtonType::RightTrigger2 => "RT",
+        GamepadButtonType::LeftThumb => "L3",
+        GamepadButtonType::RightThumb => "R3",
+        GamepadButtonType::Start => "☰",
+        GamepadButtonType::Select => "⧉",
+        GamepadButtonType::DPadUp => "✚↑",
+        GamepadButtonType::DPadDown => "✚↓",
+        GamepadButtonType::DPadLeft => "✚←",
+        GamepadButtonType::DPadRight => "✚→",
+        other => return format!("{:?}", other),
+    };
+    glyph.to_string()
+}
+
+/// System set of update_action_state, for systems that take input before the actions or read
+/// them in PreUpdate
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ActionSet;
+
+/// InputMap following the bindings in Settings, rebinding, ActionState, FixedActionState,
+/// GameplayInput and ActiveDevice
+pub struct InputPlugin;
+
+impl Plugin for InputPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputMap>()
+            .init_resource::<ActionState>()
+            .init_resource::<FixedActionState>()
+            .init_resource::<GameplayInput>()
+            .init_resource::<ActiveDevice>()
+            .init_resource::<Rebinding>()
+            .add_event::<StartRebind>()
+            .add_event::<ResolveRebind>()
+            .add_event::<RebindConflict>()
+            .add_event::<RebindRejected>()
+            .add_event::<BindingsChanged>()
+            .add_event::<SettingsChanged>()
+            // also added by bevy's InputPlugin and the AppStatePlugin and ToastPlugin
+            .add_event::<GamepadAxisChangedEvent>()
+            .add_event::<GamepadConnectionEvent>()
+            .add_event::<ChangeState>()
+            .add_event::<ShowToast>()
+            .add_systems(
+                PreUpdate,
+                (
+                    apply_input_settings,
+                    rebind,
+                    update_action_state,
+                    track_active_device,
+                    handle_gamepad_connections,
+                )
+                    .chain()
+                    .in_set(ActionSet)
+                    .after(InputSystem),
+            )
+            .add_systems(
+                FixedUpdate,
+                read_actions.in_set(FixedSet::Input).in_set(GameplaySet),
+            )
+            // taps on the menus aren't for gameplay
+            .add_systems(OnEnter(AppState::InGame), forget_taps);
+    }
+}
+
+///
+/// update_action_state: Bevy system
+///
+/// Reads the frame's ActionState from the inputs InputMap binds
+pub fn update_action_state(
+    map: Res<InputMap>,
+    devices: InputDevices,
+    mut actions: ResMut<ActionState>,
+) {
+    actions.update(&map, &devices);
+}
+
+///
+/// track_active_device: Bevy system
+///
+/// Makes the device the player just used the ActiveDevice
+pub fn track_active_device(
+    map: Res<InputMap>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mouse_buttons: Option<Res<ButtonInput<MouseButton>>>,
+    gamepad_buttons: Option<Res<ButtonInput<GamepadButton>>>,
+    mut axis_events: EventReader<GamepadAxisChangedEvent>,
+    mut device: ResMut<ActiveDevice>,
+) {
+    let mut used = None;
+    if keys.is_some_and(|keys| keys.get_just_pressed().next().is_some())
+        || mouse_buttons.is_some_and(|mouse| mouse.get_just_pressed().next().is_some())
+    {
+        used = Some(ActiveDevice::KeyboardMouse);
+    }
+    let stick = ActionAxis::ALL
+        .iter()
+        .flat_map(|axis| map.gamepad_axes(*axis));
+    let stick_pushed = axis_events
+        .read()
+        .filter(|event| {
+            stick.clone().any(|axis| *axis == event.axis_type)
+                && map.stick_response(event.value) != 0.0
+        })
+        .last()
+        .map(|event| event.gamepad);
+    let button_pressed = gamepad_buttons.and_then(|buttons| {
+        buttons
+            .get_just_pressed()
+            .next()
+            .map(|button| button.gamepad)
+    });
+    if let Some(gamepad) = button_pressed.or(stick_pushed) {
+        used = Some(ActiveDevice::Gamepad(gamepad));
+    }
+    if let Some(used) = used {
+        device.set_if_neq(used);
+    }
+}
+
+///
+/// handle_gamepad_connections: Bevy system
+///
+/// Shows a toast when a gamepad connects. When the ActiveDevice disconnects, goes back to the
+/// keyboard and, mid-play, pauses the game with a toast saying so.
+pub fn handle_gamepad_connections(
+    mut connections: EventReader<GamepadConnectionEvent>,
+    state: Option<Res<State<AppState>>>,
+    mut device: ResMut<ActiveDevice>,
+    mut changes: EventWriter<ChangeState>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    for event in connections.read() {
+        match &event.connection {
+            GamepadConnection::Connected(info) => {
+                toasts.send(ShowToast::info(format!("{} connected", info.name)));
+            }
+            GamepadConnection::Disconnected => {
+                if *device != ActiveDevice::Gamepad(event.gamepad) {
+                    continue;
+                }
+                *device = ActiveDevice::KeyboardMouse;
+                toasts.send(ShowToast::error("Controller disconnected"));
+                if state
+                    .as_ref()
+                    .is_some_and(|state| *state.get() == AppState::InGame)
+                {
+                    changes.send(ChangeState(AppState::Paused));
+                }
+            }
+        }
+    }
+}
+
+///
+/// read_actions: Bevy system
+///
+/// Starts the fixed step's FixedActionState with the actions held in ActionState and those
+/// tapped since the last step, or none while GameplayInput is off
+pub fn read_actions(
+    time: Res<Time>,
+    mut frame: ResMut<ActionState>,
+    input: Res<GameplayInput>,
+    mut actions: ResMut<FixedActionState>,
+) {
+    let tapped = frame.take_presses();
+    let held = if input.0 {
+        frame.held().union(&tapped).copied().collect()
+    } else {
+        BTreeSet::new()
+    };
+    actions.step(held, time.elapsed_seconds_f64());
+}
+
+/// Drops the presses the fixed steps haven't seen
+fn forget_taps(mut frame: ResMut<ActionState>) {
+    frame.take_presses();
+}
+
+///
+/// apply_input_settings: Bevy system
+///
+/// Takes the bindings from Settings when they change
+pub fn apply_input_settings(
+    mut events: EventReader<SettingsChanged>,
+    settings: Option<Res<Settings>>,
+    mut map: ResMut<InputMap>,
+    mut changes: EventWriter<BindingsChanged>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    if let Some(settings) = settings.filter(|settings| settings.bindings != *map) {
+        *map = settings.bindings.clone();
+        changes.send(BindingsChanged);
+    }
+}
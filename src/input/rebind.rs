@@ -0,0 +1,359 @@
+//! Rebinding from the settings menu: StartRebind captures the next key or button pressed and
+//! binds it to the action, in Settings so the bindings are saved with it.
+
+use super::{gamepad_glyph, key_glyph, mouse_glyph, Action, InputMap};
+use crate::settings::Settings;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use std::collections::BTreeMap;
+
+/// Keys that can't be bound: pressing one while capturing ends the capture, so Escape backs out
+pub const RESERVED_KEYS: [KeyCode; 2] = [KeyCode::Escape, KeyCode::Backquote];
+
+impl Action {
+    /// Every action, in the order the settings menu lists them
+    pub const ALL: [Action; 10] = [
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Interact,
+        Action::Attack,
+        Action::Jump,
+        Action::Pause,
+        Action::ToggleMute,
+        Action::ToggleFullscreen,
+    ];
+
+    /// The action's name in the settings menu
+    pub fn label(self) -> &'static str {
+        match self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Interact => "Interact",
+            Action::Attack => "Attack",
+            Action::Jump => "Jump",
+            Action::Pause => "Pause",
+            Action::ToggleMute => "Mute",
+            Action::ToggleFullscreen => "Fullscreen",
+        }
+    }
+}
+
+/// A key or button an action can be bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    /// The binding as the settings menu and prompts show it, like "E", "LMB" or "Ⓐ"
+    pub fn label(self) -> String {
+        match self {
+            Binding::Key(key) => key_glyph(key),
+            Binding::Mouse(button) => mouse_glyph(button),
+            Binding::Gamepad(button) => gamepad_glyph(button),
+        }
+    }
+}
+
+impl InputMap {
+    /// The keys, mouse buttons and gamepad buttons of `action`, in that order
+    pub fn bindings(&self, action: Action) -> Vec<Binding> {
+        let keys = self.keys(action).iter().map(|key| Binding::Key(*key));
+        let mouse = self
+            .mouse_buttons(action)
+            .iter()
+            .map(|b| Binding::Mouse(*b));
+        let pad = self
+            .gamepad_buttons(action)
+            .iter()
+            .map(|b| Binding::Gamepad(*b));
+        keys.chain(mouse).chain(pad).collect()
+    }
+
+    /// The label of every binding of every action, for the settings menu
+    pub fn labels(&self) -> Vec<(Action, Vec<String>)> {
+        Action::ALL
+            .iter()
+            .map(|action| {
+                let labels = self.bindings(*action).into_iter().map(Binding::label);
+                (*action, labels.collect())
+            })
+            .collect()
+    }
+
+    /// The actions other than `action` that `binding` is bound to
+    pub fn conflicts(&self, action: Action, binding: Binding) -> Vec<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .filter(|other| *other != action && self.bindings(*other).contains(&binding))
+            .collect()
+    }
+
+    /// Puts `binding` in `slot` of the bindings of its kind of `action`, after the last if the
+    /// slot is past them, and takes it off the other actions. With `swap`, the binding it replaces
+    /// goes to the first of them instead, where `binding` was.
+    pub fn set_binding(&mut self, action: Action, slot: usize, binding: Binding, swap: bool) {
+        match binding {
+            Binding::Key(key) => place(&mut self.keys, action, slot, key, swap),
+            Binding::Mouse(button) => place(&mut self.mouse_buttons, action, slot, button, swap),
+            Binding::Gamepad(button) => {
+                place(&mut self.gamepad_buttons, action, slot, button, swap)
+            }
+        }
+    }
+
+    /// Puts back the default bindings, keeping the stick's dead zone and response curve
+    pub fn reset_bindings(&mut self) {
+        *self = InputMap {
+            dead_zone: self.dead_zone,
+            response_curve: self.response_curve,
+            ..InputMap::default()
+        };
+    }
+}
+
+/// InputMap::set_binding for one kind of binding
+fn place<T: Copy + PartialEq>(
+    lists: &mut BTreeMap<Action, Vec<T>>,
+    action: Action,
+    slot: usize,
+    input: T,
+    swap: bool,
+) {
+    let inputs = lists.entry(action).or_default();
+    if let Some(at) = inputs.iter().position(|bound| *bound == input) {
+        // already bound to the action: move it to the slot
+        if slot < inputs.len() {
+            inputs.swap(at, slot);
+        } else {
+            inputs.remove(at);
+            inputs.push(input);
+        }
+        return;
+    }
+    let replaced = inputs.get(slot).copied();
+    if slot < inputs.len() {
+        inputs[slot] = input;
+    } else {
+        inputs.push(input);
+    }
+
+    let mut swap_with = replaced.filter(|_| swap);
+    for (other, inputs) in lists.iter_mut() {
+        if *other == action {
+            continue;
+        }
+        let Some(at) = inputs.iter().position(|bound| *bound == input) else {
+            continue;
+        };
+        match swap_with.take() {
+            Some(replaced) if !inputs.contains(&replaced) => inputs[at] = replaced,
+            _ => {
+                inputs.remove(at);
+            }
+        }
+    }
+}
+
+/// Starts capturing the next key or button pressed for `slot` of `action` (see
+/// InputMap::set_binding), replacing any capture already going
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartRebind {
+    pub action: Action,
+    pub slot: usize,
+}
+
+/// What to do with a captured binding that another action has, sent after RebindConflict
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveRebind {
+    /// bind it, giving the other action the binding it replaces
+    Swap,
+    /// bind it, taking it off the other action
+    Take,
+    /// leave the bindings as they were
+    Cancel,
+}
+
+/// Sent when the captured binding is bound to `others` already, for the settings menu to ask
+/// what to do. Capturing waits for ResolveRebind.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct RebindConflict {
+    pub action: Action,
+    pub binding: Binding,
+    pub others: Vec<Action>,
+}
+
+/// Sent when a reserved key ended the capture
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RebindRejected {
+    pub action: Action,
+    pub key: KeyCode,
+}
+
+/// Sent when InputMap's bindings change, by a rebind or through Settings
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct BindingsChanged;
+
+///
+/// Rebinding
+///
+/// Where a rebind is at. While capturing, the keys and buttons pressed are taken from the input
+/// resources, so they don't also do their action.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub enum Rebinding {
+    #[default]
+    Idle,
+    Capturing {
+        action: Action,
+        slot: usize,
+    },
+    Conflict {
+        action: Action,
+        slot: usize,
+        binding: Binding,
+    },
+}
+
+impl Rebinding {
+    /// Whether a rebind is waiting for a key, a button or ResolveRebind
+    pub fn is_active(&self) -> bool {
+        *self != Rebinding::Idle
+    }
+}
+
+/// PressedInputs: Bevy system parameter
+///
+/// The key and button inputs, which capturing takes presses from
+#[derive(SystemParam)]
+pub struct PressedInputs<'w> {
+    keys: Option<ResMut<'w, ButtonInput<KeyCode>>>,
+    mouse_buttons: Option<ResMut<'w, ButtonInput<MouseButton>>>,
+    gamepad_buttons: Option<ResMut<'w, ButtonInput<GamepadButton>>>,
+}
+
+impl PressedInputs<'_> {
+    /// A binding pressed this frame, taking it off its input
+    fn take_pressed(&mut self) -> Option<Binding> {
+        if let Some(keys) = self.keys.as_mut() {
+            // bound first, the if let's temporaries would hold the borrow through reset
+            let pressed = keys.get_just_pressed().next().copied();
+            if let Some(key) = pressed {
+                keys.reset(key);
+                return Some(Binding::Key(key));
+            }
+        }
+        if let Some(buttons) = self.mouse_buttons.as_mut() {
+            let pressed = buttons.get_just_pressed().next().copied();
+            if let Some(button) = pressed {
+                buttons.reset(button);
+                return Some(Binding::Mouse(button));
+            }
+        }
+        if let Some(buttons) = self.gamepad_buttons.as_mut() {
+            let pressed = buttons.get_just_pressed().next().copied();
+            if let Some(button) = pressed {
+                buttons.reset(button);
+                return Some(Binding::Gamepad(button.button_type));
+            }
+        }
+        None
+    }
+
+    /// Takes every press, so nothing does its action while capturing
+    fn take_all(&mut self) {
+        while self.take_pressed().is_some() {}
+    }
+}
+
+///
+/// rebind: Bevy system
+///
+/// Starts, captures and resolves rebinds. The bindings go to Settings, or straight to InputMap
+/// without SettingsPlugin.
+#[allow(clippy::too_many_arguments)]
+pub fn rebind(
+    mut starts: EventReader<StartRebind>,
+    mut resolutions: EventReader<ResolveRebind>,
+    mut rebinding: ResMut<Rebinding>,
+    mut inputs: PressedInputs,
+    mut map: ResMut<InputMap>,
+    settings: Option<ResMut<Settings>>,
+    mut conflicts: EventWriter<RebindConflict>,
+    mut rejections: EventWriter<RebindRejected>,
+    mut changes: EventWriter<BindingsChanged>,
+) {
+    let resolution = resolutions.read().last().copied();
+    if let Some(start) = starts.read().last() {
+        *rebinding = Rebinding::Capturing {
+            action: start.action,
+            slot: start.slot,
+        };
+        // the press that asked for it isn't the binding
+        inputs.take_all();
+        return;
+    }
+
+    let (action, slot, binding, swap) = match rebinding.clone() {
+        Rebinding::Idle => return,
+        Rebinding::Capturing { action, slot } => {
+            let Some(binding) = inputs.take_pressed() else {
+                return;
+            };
+            inputs.take_all();
+            if let Binding::Key(key) = binding {
+                if RESERVED_KEYS.contains(&key) {
+                    *rebinding = Rebinding::Idle;
+                    rejections.send(RebindRejected { action, key });
+                    return;
+                }
+            }
+            let others = map.conflicts(action, binding);
+            if !others.is_empty() {
+                warn!("{:?} is bound to {:?} already", binding, others);
+                *rebinding = Rebinding::Conflict {
+                    action,
+                    slot,
+                    binding,
+                };
+                conflicts.send(RebindConflict {
+                    action,
+                    binding,
+                    others,
+                });
+                return;
+            }
+            (action, slot, binding, false)
+        }
+        Rebinding::Conflict {
+            action,
+            slot,
+            binding,
+        } => {
+            // the menu asking what to do needs the keys
+            match resolution {
+                None => return,
+                Some(ResolveRebind::Cancel) => {
+                    *rebinding = Rebinding::Idle;
+                    return;
+                }
+                Some(ResolveRebind::Swap) => (action, slot, binding, true),
+                Some(ResolveRebind::Take) => (action, slot, binding, false),
+            }
+        }
+    };
+
+    *rebinding = Rebinding::Idle;
+    let mut bindings = map.clone();
+    bindings.set_binding(action, slot, binding, swap);
+    if let Some(mut settings) = settings {
+        settings.bindings = bindings.clone();
+    }
+    *map = bindings;
+    changes.send(BindingsChanged);
+}
@@ -0,0 +1,402 @@
+//! On-screen controls for phones: a joystick appearing wherever the left thumb comes down, and
+//! action buttons on the right. They switch on with the first touch, or from the start with the
+//! `touch` flag, and feed VirtualControls, so nothing else knows they aren't a gamepad.
+//!
+//! Every touch is followed on its own: the one that started the joystick moves it until it
+//! lifts, and each button is held while a touch that started on it stays down.
+
+use super::{Action, ActionAxis, ActionSet, InputMap, VirtualControls};
+use bevy::{input::touch::Touches, input::InputSystem, prelude::*, window::PrimaryWindow};
+use std::collections::BTreeMap;
+
+/// Joystick and button color, and how opaque it gets while held
+const CONTROL_COLOR: (f32, f32, f32) = (1.0, 1.0, 1.0);
+const IDLE_ALPHA: f32 = 0.2;
+const HELD_ALPHA: f32 = 0.45;
+
+///
+/// TouchControls
+///
+/// What's on screen and who's touching it. Sizes are fractions of the shorter side of the window,
+/// so the controls are the same under the thumb in portrait and landscape.
+/// * enabled: the controls are shown and read. Off until the screen is first touched.
+/// * forced: Some(true) keeps them on from the start and Some(false) off, from StartOptions
+/// * buttons: the actions of the buttons, the first at the bottom right
+/// * stick_radius: how far the joystick knob goes from where the thumb came down
+/// * button_radius: the size of the buttons
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TouchControls {
+    pub enabled: bool,
+    pub forced: Option<bool>,
+    pub buttons: Vec<Action>,
+    pub stick_radius: f32,
+    pub button_radius: f32,
+    stick: Option<StickTouch>,
+    button_touches: BTreeMap<u64, Action>,
+}
+
+impl Default for TouchControls {
+    fn default() -> Self {
+        TouchControls {
+            enabled: false,
+            forced: None,
+            buttons: vec![Action::Attack, Action::Interact],
+            stick_radius: 0.12,
+            button_radius: 0.08,
+            stick: None,
+            button_touches: BTreeMap::new(),
+        }
+    }
+}
+
+/// The touch holding the joystick, where it came down and where it is, in logical pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StickTouch {
+    id: u64,
+    origin: Vec2,
+    position: Vec2,
+}
+
+impl TouchControls {
+    /// The centre of button `index` in a window of `size`, the buttons going up and left from
+    /// the bottom right corner
+    pub fn button_center(&self, index: usize, size: Vec2) -> Vec2 {
+        let short = size.min_element();
+        let radius = self.button_radius * short;
+        let margin = 0.06 * short + radius;
+        let step = index as f32 * radius;
+        Vec2::new(size.x - margin - step * 2.4, size.y - margin - step * 1.2)
+    }
+
+    /// The button a touch at `position` lands on: within a bit more than its radius, for thumbs
+    fn button_at(&self, position: Vec2, size: Vec2) -> Option<Action> {
+        let reach = self.button_radius * size.min_element() * 1.2;
+        self.buttons
+            .iter()
+            .enumerate()
+            .find(|(index, _)| self.button_center(*index, size).distance(position) <= reach)
+            .map(|(_, action)| *action)
+    }
+
+    /// Where the joystick came down and how far it's pushed, -1 to 1 on each axis with y up,
+    /// before the dead zone
+    pub fn stick(&self, size: Vec2) -> Option<(Vec2, Vec2)> {
+        let stick = self.stick?;
+        let radius = (self.stick_radius * size.min_element()).max(1.0);
+        let offset = (stick.position - stick.origin) / radius;
+        Some((
+            stick.origin,
+            Vec2::new(offset.x, -offset.y).clamp_length_max(1.0),
+        ))
+    }
+
+    /// Whether a touch holds the button of `action`
+    pub fn holds(&self, action: Action) -> bool {
+        self.button_touches.values().any(|held| *held == action)
+    }
+
+    /// Whether the touch `id` holds the joystick or a button
+    pub fn claims(&self, id: u64) -> bool {
+        self.stick.is_some_and(|stick| stick.id == id) || self.button_touches.contains_key(&id)
+    }
+
+    /// Lets go of every touch
+    fn release(&mut self) {
+        self.stick = None;
+        self.button_touches.clear();
+    }
+}
+
+/// The on-screen layer of the controls
+#[derive(Component, Debug)]
+pub struct TouchControlsRoot;
+
+/// The ring the joystick knob moves in
+#[derive(Component, Debug)]
+pub struct StickBase;
+
+/// The joystick knob
+#[derive(Component, Debug)]
+pub struct StickKnob;
+
+/// An on-screen button: its place in TouchControls::buttons and its action
+#[derive(Component, Debug)]
+pub struct TouchButton(pub usize, pub Action);
+
+///
+/// TouchControlsPlugin
+///
+/// * forced: TouchControls::forced, from StartOptions::touch_controls
+#[derive(Default)]
+pub struct TouchControlsPlugin {
+    pub forced: Option<bool>,
+}
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TouchControls {
+            enabled: self.forced.unwrap_or(false),
+            forced: self.forced,
+            ..default()
+        })
+        .init_resource::<VirtualControls>()
+        .add_systems(Startup, spawn_touch_controls)
+        .add_systems(
+            PreUpdate,
+            read_touch_controls.after(InputSystem).before(ActionSet),
+        )
+        .add_systems(
+            Update,
+            (respawn_touch_buttons, layout_touch_controls).chain(),
+        );
+    }
+}
+
+///
+/// read_touch_controls: Bevy system
+///
+/// Switches the controls on at the first touch, follows the touches on them and feeds what they
+/// hold to VirtualControls
+pub fn read_touch_controls(
+    touches: Option<Res<Touches>>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    map: Res<InputMap>,
+    mut controls: ResMut<TouchControls>,
+    mut virtual_controls: ResMut<VirtualControls>,
+) {
+    let Some(touches) = touches else {
+        return;
+    };
+    if controls.forced.is_none() && !controls.enabled && touches.any_just_pressed() {
+        controls.enabled = true;
+    }
+    let size = window_query
+        .get_single()
+        .map(|window| Vec2::new(window.width(), window.height()))
+        .ok();
+    let Some(size) = size.filter(|_| controls.enabled) else {
+        if controls.stick.is_some() || !controls.button_touches.is_empty() {
+            controls.release();
+        }
+        if !virtual_controls.is_idle() {
+            *virtual_controls = VirtualControls::default();
+        }
+        return;
+    };
+
+    for touch in touches.iter_just_pressed() {
+        let position = touch.position();
+        if let Some(action) = controls.button_at(position, size) {
+            controls.button_touches.insert(touch.id(), action);
+        } else if controls.stick.is_none() && position.x < size.x / 2.0 {
+            controls.stick = Some(StickTouch {
+                id: touch.id(),
+                origin: position,
+                position,
+            });
+        }
+    }
+    // a tap that starts and ends in one frame still holds its button for that frame
+    controls
+        .button_touches
+        .retain(|id, _| touches.get_pressed(*id).is_some() || touches.just_pressed(*id));
+    if let Some(stick) = controls.stick {
+        controls.stick = touches.get_pressed(stick.id).map(|touch| StickTouch {
+            position: touch.position(),
+            ..stick
+        });
+    }
+
+    let pushed = controls
+        .stick(size)
+        .map_or(Vec2::ZERO, |(_, offset)| offset);
+    let pushed = pushed.normalize_or_zero() * map.stick_response(pushed.length());
+    let axes = vec![(ActionAxis::MoveX, pushed.x), (ActionAxis::MoveY, pushed.y)];
+    let fed = VirtualControls {
+        held: controls.button_touches.values().copied().collect(),
+        axes: axes.into_iter().collect(),
+    };
+    virtual_controls.set_if_neq(fed);
+}
+
+fn control_color(alpha: f32) -> BackgroundColor {
+    let (r, g, b) = CONTROL_COLOR;
+    BackgroundColor(Color::rgba(r, g, b, alpha))
+}
+
+fn spawn_touch_controls(mut commands: Commands) {
+    commands
+        .spawn((
+            TouchControlsRoot,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    ..default()
+                },
+                visibility: Visibility::Hidden,
+                // over the game, under the toasts
+                z_index: ZIndex::Global(50),
+                ..default()
+            },
+        ))
+        .with_children(|root| {
+            let node = || NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..default()
+                },
+                background_color: control_color(IDLE_ALPHA),
+                visibility: Visibility::Hidden,
+                ..default()
+            };
+            root.spawn((StickBase, node()));
+            root.spawn((StickKnob, node()));
+        });
+}
+
+///
+/// respawn_touch_buttons: Bevy system
+///
+/// Gives each of TouchControls::buttons a node, again when they change
+pub fn respawn_touch_buttons(
+    mut commands: Commands,
+    controls: Res<TouchControls>,
+    root_query: Query<Entity, With<TouchControlsRoot>>,
+    button_query: Query<(Entity, &TouchButton)>,
+) {
+    let Ok(root) = root_query.get_single() else {
+        return;
+    };
+    let mut shown: Vec<&TouchButton> = button_query.iter().map(|(_, button)| button).collect();
+    shown.sort_by_key(|button| button.0);
+    if shown
+        .iter()
+        .map(|button| button.1)
+        .eq(controls.buttons.iter().copied())
+    {
+        return;
+    }
+    for (entity, _) in button_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.entity(root).with_children(|root| {
+        for (index, action) in controls.buttons.iter().enumerate() {
+            root.spawn((
+                TouchButton(index, *action),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: control_color(IDLE_ALPHA),
+                    ..default()
+                },
+            ))
+            .with_children(|button| {
+                button.spawn(TextBundle::from_section(
+                    action.label(),
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ));
+            });
+        }
+    });
+}
+
+/// Places `style` as a square of `radius` around `center`
+fn place(style: &mut Style, center: Vec2, radius: f32) {
+    style.left = Val::Px(center.x - radius);
+    style.top = Val::Px(center.y - radius);
+    style.width = Val::Px(radius * 2.0);
+    style.height = Val::Px(radius * 2.0);
+}
+
+///
+/// layout_touch_controls: Bevy system
+///
+/// Shows the controls while they're enabled, sized to the window, with the joystick under the
+/// thumb on it and held buttons brighter
+#[allow(clippy::type_complexity)]
+pub fn layout_touch_controls(
+    controls: Res<TouchControls>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut root_query: Query<&mut Visibility, With<TouchControlsRoot>>,
+    mut stick_query: Query<
+        (&mut Style, &mut Visibility, Has<StickKnob>),
+        (
+            Or<(With<StickBase>, With<StickKnob>)>,
+            Without<TouchControlsRoot>,
+        ),
+    >,
+    mut button_query: Query<
+        (&TouchButton, &mut Style, &mut BackgroundColor, &Children),
+        (Without<StickBase>, Without<StickKnob>),
+    >,
+    mut text_query: Query<&mut Text>,
+) {
+    let Ok(window) = window_query.get_single() else {
+        return;
+    };
+    let size = Vec2::new(window.width(), window.height());
+    let short = size.min_element();
+    for mut visibility in root_query.iter_mut() {
+        visibility.set_if_neq(if controls.enabled {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+    if !controls.enabled {
+        return;
+    }
+
+    let stick_radius = controls.stick_radius * short;
+    let stick = controls.stick(size);
+    for (mut style, mut visibility, is_knob) in stick_query.iter_mut() {
+        let Some((origin, offset)) = stick else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+        if is_knob {
+            let knob = origin + Vec2::new(offset.x, -offset.y) * stick_radius;
+            place(&mut style, knob, stick_radius * 0.45);
+        } else {
+            place(&mut style, origin, stick_radius);
+        }
+    }
+
+    let button_radius = controls.button_radius * short;
+    for (button, mut style, mut color, children) in button_query.iter_mut() {
+        let Some(action) = controls.buttons.get(button.0) else {
+            continue;
+        };
+        place(
+            &mut style,
+            controls.button_center(button.0, size),
+            button_radius,
+        );
+        let alpha = if controls.holds(*action) {
+            HELD_ALPHA
+        } else {
+            IDLE_ALPHA
+        };
+        if color.0 != control_color(alpha).0 {
+            *color = control_color(alpha);
+        }
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                if let Some(section) = text.sections.first_mut() {
+                    section.style.font_size = button_radius * 0.5;
+                }
+            }
+        }
+    }
+}
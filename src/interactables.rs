@@ -0,0 +1,257 @@
+//! Signs, levers and NPCs placed in Tiled: objects of type "sign", "lever" and "npc" become
+//! Interactables the prompt offers, set up from their properties, with no code for each one.
+//! * sign: `text`, the locale key of what it says, shown in the dialogue box
+//! * lever: `target`, the GameProgress flag it toggles, so where it's at is saved with the game.
+//!   Pulling it sends LeverToggled and plays LEVER_SOUND, and it shows the first frame of the
+//!   LEVER_ANIMATION clip while off and the second while on.
+//! * npc: `dialogue`, the id of one of the map's DialogueTrees to talk through, or else an id
+//!   whose lines are the locale keys `<dialogue>.1`, `<dialogue>.2` and on, spoken by `speaker`,
+//!   a locale key, `<dialogue>.speaker` when unset
+//!
+//! Any of them can set `radius`, how near the player has to be, INTERACT_RADIUS when unset.
+//! Nothing happens while the dialogue box is open.
+
+use crate::gfx::AnimationResource;
+use crate::helpers::tiled::{RegisterTiledObject, TiledObject};
+use crate::locale::Locale;
+use crate::save::GameProgress;
+use crate::sound::PlaySFX;
+use crate::ui::{
+    Dialogue, DialogueLine, DialogueTreeLookup, Interactable, Interacted, StartDialogue,
+    StartDialogueTree,
+};
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+/// Object types made interactable
+pub const SIGN_TYPE: &str = "sign";
+pub const LEVER_TYPE: &str = "lever";
+pub const NPC_TYPE: &str = "npc";
+/// How near the player has to be to an object to interact with it, unless it says otherwise
+pub const INTERACT_RADIUS: f32 = 20.0;
+/// The AnimationResource clip levers are drawn with: off on its first frame, on on its second
+pub const LEVER_ANIMATION: &str = "lever";
+/// Sound played when a lever is pulled
+pub const LEVER_SOUND: &str = "lever";
+/// Locale key of the name over what signs say
+pub const SIGN_SPEAKER: &str = "sign.speaker";
+
+/// What interacting with an object does
+/// * Sign: shows `text`, a locale key
+/// * Lever: toggles the flag `target`
+/// * Npc: says the lines of `dialogue` as `speaker`
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub enum ObjectInteraction {
+    Sign { text: String },
+    Lever { target: String },
+    Npc { dialogue: String, speaker: String },
+}
+
+/// Sent when a lever is pulled: `name` is the flag it toggles and `state` whether it's set now
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct LeverToggled {
+    pub name: String,
+    pub state: bool,
+}
+
+/// Spawners for signs, levers and NPCs, and what interacting with them does
+pub struct InteractablesPlugin;
+
+impl Plugin for InteractablesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameProgress>()
+            .init_resource::<Dialogue>()
+            .add_event::<Interacted>()
+            .add_event::<StartDialogue>()
+            .add_event::<StartDialogueTree>()
+            .add_event::<LeverToggled>()
+            .add_event::<PlaySFX>()
+            .register_tiled_object(SIGN_TYPE, spawn_interactable)
+            .register_tiled_object(LEVER_TYPE, spawn_interactable)
+            .register_tiled_object(NPC_TYPE, spawn_interactable)
+            .add_systems(
+                Update,
+                (use_interactables, (dress_levers, show_levers).chain()),
+            );
+    }
+}
+
+/// What interacting with `object` does and the locale key its prompt shows, if it's a sign, lever
+/// or NPC with the properties it needs
+pub fn object_interaction(object: &TiledObject) -> Option<(ObjectInteraction, &'static str)> {
+    let property = |name: &str| {
+        let value = object.get_string(name);
+        if value.is_none() {
+            warn!(
+                "The {} {} ({}) has no {}",
+                object.object_type, object.name, object.id, name
+            );
+        }
+        value.map(str::to_string)
+    };
+    match object.object_type.as_str() {
+        SIGN_TYPE => Some((
+            ObjectInteraction::Sign {
+                text: property("text")?,
+            },
+            "prompt.read",
+        )),
+        LEVER_TYPE => Some((
+            ObjectInteraction::Lever {
+                target: property("target")?,
+            },
+            "prompt.use",
+        )),
+        NPC_TYPE => {
+            let dialogue = property("dialogue")?;
+            let speaker = object
+                .get_string("speaker")
+                .map_or_else(|| format!("{}.speaker", dialogue), str::to_string);
+            Some((ObjectInteraction::Npc { dialogue, speaker }, "prompt.talk"))
+        }
+        _ => None,
+    }
+}
+
+fn spawn_interactable(entity: &mut EntityCommands, object: &TiledObject) {
+    let Some((interaction, prompt_key)) = object_interaction(object) else {
+        return;
+    };
+    entity.insert((
+        interaction,
+        Interactable {
+            prompt_key: prompt_key.to_string(),
+            radius: object.get_float("radius").unwrap_or(INTERACT_RADIUS),
+        },
+    ));
+}
+
+/// The lines of the dialogue `id`: its locale keys `<id>.1`, `<id>.2` and on, as far as the
+/// active language has them, or just `id` when it has none
+pub fn dialogue_lines(id: &str, speaker: &str, locale: Option<&Locale>) -> Vec<DialogueLine> {
+    let mut lines: Vec<DialogueLine> = (1..)
+        .map(|n| format!("{}.{}", id, n))
+        .take_while(|key| locale.is_some_and(|locale| locale.get(key).is_some()))
+        .map(|key| DialogueLine::new(speaker, key))
+        .collect();
+    if lines.is_empty() {
+        lines.push(DialogueLine::new(speaker, id));
+    }
+    lines
+}
+
+///
+/// use_interactables: Bevy system
+///
+/// Opens the dialogue box for signs and NPCs interacted with, through the NPC's dialogue tree if
+/// the map has it, and pulls levers, unless the dialogue box is open already
+#[allow(clippy::too_many_arguments)]
+pub fn use_interactables(
+    mut interactions: EventReader<Interacted>,
+    dialogue: Res<Dialogue>,
+    locale: Option<Res<Locale>>,
+    trees: DialogueTreeLookup,
+    mut progress: ResMut<GameProgress>,
+    object_query: Query<(&ObjectInteraction, Option<&GlobalTransform>)>,
+    mut dialogues: EventWriter<StartDialogue>,
+    mut tree_dialogues: EventWriter<StartDialogueTree>,
+    mut toggles: EventWriter<LeverToggled>,
+    mut sfx: EventWriter<PlaySFX>,
+) {
+    let mut talking = dialogue.is_open();
+    for event in interactions.read() {
+        let Ok((interaction, transform)) = object_query.get(event.entity) else {
+            continue;
+        };
+        if talking {
+            continue;
+        }
+        match interaction {
+            ObjectInteraction::Sign { text } => {
+                dialogues.send(StartDialogue {
+                    lines: vec![DialogueLine::new(SIGN_SPEAKER, text.clone())],
+                });
+                talking = true;
+            }
+            ObjectInteraction::Npc { dialogue, speaker } => {
+                match trees.get(dialogue) {
+                    Some(tree) => {
+                        tree_dialogues.send(StartDialogueTree {
+                            tree: tree.clone(),
+                            speaker: speaker.clone(),
+                        });
+                    }
+                    None => {
+                        dialogues.send(StartDialogue {
+                            lines: dialogue_lines(dialogue, speaker, locale.as_deref()),
+                        });
+                    }
+                }
+                talking = true;
+            }
+            ObjectInteraction::Lever { target } => {
+                let state = progress.toggle_flag(target);
+                toggles.send(LeverToggled {
+                    name: target.clone(),
+                    state,
+                });
+                let position =
+                    transform.map_or(Vec2::ZERO, |transform| transform.translation().truncate());
+                sfx.send(PlaySFX::at(LEVER_SOUND, position));
+            }
+        }
+    }
+}
+
+///
+/// dress_levers: Bevy system
+///
+/// Gives new levers the sprite of the LEVER_ANIMATION clip, if there is one
+pub fn dress_levers(
+    mut commands: Commands,
+    animations: Option<Res<AnimationResource>>,
+    lever_query: Query<(Entity, &ObjectInteraction), Added<ObjectInteraction>>,
+) {
+    let Some(clip) = animations.and_then(|animations| animations.get(LEVER_ANIMATION)) else {
+        return;
+    };
+    for (entity, interaction) in lever_query.iter() {
+        if !matches!(interaction, ObjectInteraction::Lever { .. }) {
+            continue;
+        }
+        commands.entity(entity).insert((
+            Sprite::default(),
+            TextureAtlas {
+                layout: clip.atlas().clone(),
+                index: clip.frame(),
+            },
+            clip.texture().clone(),
+        ));
+    }
+}
+
+///
+/// show_levers: Bevy system
+///
+/// Shows each lever's frame of the LEVER_ANIMATION clip for whether its flag is set
+pub fn show_levers(
+    progress: Res<GameProgress>,
+    animations: Option<Res<AnimationResource>>,
+    mut lever_query: Query<(&ObjectInteraction, &mut TextureAtlas)>,
+) {
+    let Some(clip) = animations.and_then(|animations| animations.get(LEVER_ANIMATION)) else {
+        return;
+    };
+    let frames = clip.frames();
+    for (interaction, mut atlas) in lever_query.iter_mut() {
+        let ObjectInteraction::Lever { target } = interaction else {
+            continue;
+        };
+        let on = progress.has_flag(target) as usize;
+        let Some(&index) = frames.get(on).or(frames.last()) else {
+            continue;
+        };
+        if atlas.index != index {
+            atlas.index = index;
+        }
+    }
+}
@@ -0,0 +1,233 @@
+//! Posting final scores to the jam's score API, at StartOptions::leaderboard_url.
+//!
+//! Send SubmitScore and wait for ScoreSubmitted or ScoreSubmitFailed; each SubmitScore gets one
+//! of them. On the web the score is POSTed with fetch as
+//! `{ "name": "...", "score": 1200, "build": {...} }`, build being the game's BuildInfo.
+//! The native build has no HTTP client, so a stub answers in its place (see StubLeaderboard) and
+//! the game over flow runs the same on both.
+
+use crate::build_info::BuildInfo;
+use crate::options::StartOptions;
+use bevy::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Seconds a submission may take before it's given up on
+pub const SUBMIT_TIMEOUT: f32 = 10.0;
+
+/// Posts `score` under the player's `name`
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct SubmitScore {
+    pub name: String,
+    pub score: u64,
+}
+
+/// The API answered a SubmitScore. It can refuse a score it got, e.g. an offensive name, by
+/// answering `{ "accepted": false }`; any other successful answer accepts it.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ScoreSubmitted {
+    pub accepted: bool,
+}
+
+/// A SubmitScore didn't get through: no leaderboard_url, a network error, an error status or
+/// SUBMIT_TIMEOUT passing
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ScoreSubmitFailed {
+    pub reason: String,
+}
+
+type Outcome = Result<bool, String>;
+
+/// Where a request in flight leaves its outcome
+type Slot = Arc<Mutex<Option<Outcome>>>;
+
+struct Submission {
+    slot: Slot,
+    started: Duration,
+}
+
+///
+/// ScoreSubmissions
+///
+/// The SubmitScore requests in flight
+#[derive(Resource, Default)]
+pub struct ScoreSubmissions {
+    in_flight: Vec<Submission>,
+}
+
+impl ScoreSubmissions {
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
+
+///
+/// StubLeaderboard
+///
+/// What the native build's stand-in for the score API answers: Some(Ok(accepted)), an error
+/// with Some(Err(reason)), or nothing at all with None, to try the timeout
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct StubLeaderboard(pub Option<Result<bool, String>>);
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for StubLeaderboard {
+    fn default() -> Self {
+        StubLeaderboard(Some(Ok(true)))
+    }
+}
+
+/// Submitting scores
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SubmitScore>()
+            .add_event::<ScoreSubmitted>()
+            .add_event::<ScoreSubmitFailed>()
+            .init_resource::<ScoreSubmissions>()
+            .add_systems(
+                Update,
+                (
+                    submit_scores.run_if(on_event::<SubmitScore>()),
+                    poll_score_submissions,
+                )
+                    .chain(),
+            );
+        #[cfg(not(target_arch = "wasm32"))]
+        app.init_resource::<StubLeaderboard>();
+    }
+}
+
+///
+/// submit_scores: Bevy system
+///
+/// Sends a request for each SubmitScore
+pub fn submit_scores(
+    mut events: EventReader<SubmitScore>,
+    options: Option<Res<StartOptions>>,
+    time: Res<Time<Real>>,
+    mut submissions: ResMut<ScoreSubmissions>,
+    mut failed: EventWriter<ScoreSubmitFailed>,
+    #[cfg(not(target_arch = "wasm32"))] stub: Res<StubLeaderboard>,
+) {
+    let url = options
+        .as_ref()
+        .and_then(|options| options.leaderboard_url.as_deref());
+    for event in events.read() {
+        let Some(url) = url else {
+            failed.send(ScoreSubmitFailed {
+                reason: "no leaderboard_url in the start options".to_string(),
+            });
+            continue;
+        };
+        let body = serde_json::json!({
+            "name": event.name,
+            "score": event.score,
+            "build": BuildInfo::current(),
+        })
+        .to_string();
+        let slot = Slot::default();
+
+        #[cfg(target_arch = "wasm32")]
+        fetch::post(url.to_string(), body, slot.clone());
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            info!("Leaderboard stub: POST {} {}", url, body);
+            if let Ok(mut outcome) = slot.lock() {
+                *outcome = stub.0.clone();
+            }
+        }
+
+        submissions.in_flight.push(Submission {
+            slot,
+            started: time.elapsed(),
+        });
+    }
+}
+
+///
+/// poll_score_submissions: Bevy system
+///
+/// Sends ScoreSubmitted or ScoreSubmitFailed for the requests that have finished or timed out.
+/// A request timed out on the web may still reach the API, but its answer is ignored.
+pub fn poll_score_submissions(
+    time: Res<Time<Real>>,
+    mut submissions: ResMut<ScoreSubmissions>,
+    mut submitted: EventWriter<ScoreSubmitted>,
+    mut failed: EventWriter<ScoreSubmitFailed>,
+) {
+    let now = time.elapsed();
+    submissions.in_flight.retain(|submission| {
+        let outcome = submission
+            .slot
+            .lock()
+            .ok()
+            .and_then(|mut outcome| outcome.take());
+        match outcome {
+            Some(Ok(accepted)) => {
+                submitted.send(ScoreSubmitted { accepted });
+            }
+            Some(Err(reason)) => {
+                failed.send(ScoreSubmitFailed { reason });
+            }
+            None if (now - submission.started).as_secs_f32() >= SUBMIT_TIMEOUT => {
+                failed.send(ScoreSubmitFailed {
+                    reason: format!("no answer after {} seconds", SUBMIT_TIMEOUT),
+                });
+            }
+            None => return true,
+        }
+        false
+    });
+}
+
+#[cfg(target_arch = "wasm32")]
+mod fetch {
+    use super::Slot;
+    use js_sys::Reflect;
+    use wasm_bindgen::{JsCast, JsValue};
+    use wasm_bindgen_futures::JsFuture;
+
+    /// POSTs `body` to `url`, leaving the outcome in `slot` once it's known
+    pub(super) fn post(url: String, body: String, slot: Slot) {
+        wasm_bindgen_futures::spawn_local(async move {
+            let outcome = request(&url, body).await.map_err(|e| {
+                e.as_string()
+                    .unwrap_or_else(|| format!("the request failed: {:?}", e))
+            });
+            if let Ok(mut slot) = slot.lock() {
+                *slot = Some(outcome);
+            }
+        });
+    }
+
+    async fn request(url: &str, body: String) -> Result<bool, JsValue> {
+        let window = web_sys::window().ok_or("there's no window to fetch from")?;
+        // a plain object rather than RequestInit, whose setters changed across web-sys releases
+        let init = js_sys::Object::new();
+        let headers = js_sys::Object::new();
+        Reflect::set(&headers, &"Content-Type".into(), &"application/json".into())?;
+        Reflect::set(&init, &"method".into(), &"POST".into())?;
+        Reflect::set(&init, &"headers".into(), &headers)?;
+        Reflect::set(&init, &"body".into(), &body.into())?;
+
+        let response: web_sys::Response =
+            JsFuture::from(window.fetch_with_str_and_init(url, init.unchecked_ref()))
+                .await?
+                .dyn_into()?;
+        if !response.ok() {
+            return Err(format!("the server answered {}", response.status()).into());
+        }
+        // a body that isn't JSON or doesn't say is an acceptance
+        let accepted = match response.json() {
+            Ok(json) => JsFuture::from(json)
+                .await
+                .ok()
+                .and_then(|json| Reflect::get(&json, &"accepted".into()).ok())
+                .and_then(|accepted| accepted.as_bool()),
+            Err(_) => None,
+        };
+        Ok(accepted.unwrap_or(true))
+    }
+}
@@ -1,42 +1,292 @@
+#[cfg(target_arch = "wasm32")]
 mod utils;
-mod gfx;
-mod sound;
+#[cfg(feature = "dev")]
+pub mod asset_reload;
+pub mod bridge;
+pub mod canvas;
+pub mod build_info;
+pub mod checkpoints;
+#[cfg(feature = "dev")]
+pub mod console;
+pub mod crash;
+pub mod cursor;
+pub mod destructible;
+pub mod diagnostics;
+pub mod dropped_map;
+pub mod enemies;
+pub mod exits;
+pub mod fullscreen;
+pub mod gfx;
+pub mod hazards;
+pub mod health;
+pub mod lifecycle;
+pub mod locale;
+pub mod loading;
+pub mod locks;
+pub mod manifest;
+pub mod memory;
+mod map;
+pub mod picking;
+pub mod pointer;
+pub mod input;
+pub mod interactables;
+pub mod leaderboard;
+pub mod options;
+pub mod physics;
+pub mod pickups;
+pub mod platformer;
+pub mod platforms;
+pub mod player;
+pub mod pool;
+pub mod profiling;
+pub mod projectiles;
+pub mod replay;
+pub mod rng;
+pub mod run_stats;
+pub mod save;
+pub mod settings;
+pub mod sound;
+pub mod state;
+pub mod storage;
+#[cfg(feature = "test-harness")]
+pub mod testing;
+pub mod timestep;
+pub mod toast;
+pub mod ui;
+#[cfg(target_arch = "wasm32")]
+mod web;
 
-use wasm_bindgen::prelude::*;
 use bevy::prelude::*;
 use bevy_ecs_tilemap::prelude::*;
+use lifecycle::LifecyclePlugin;
+use options::StartOptions;
+use state::AppState;
+#[cfg(target_arch = "wasm32")]
+pub use web::{get_version, restart, send_command, set_fullscreen, start, stop};
 
-mod helpers;
+pub mod helpers;
 
-fn startup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(Camera2dBundle::default());
-
-    let map_handle: Handle<helpers::tiled::TiledMap> = asset_server.load("map.tmx");
+/// The first map is downloaded while Loading
+fn preload_map(
+    options: Res<StartOptions>,
+    asset_server: Res<AssetServer>,
+    mut preload: ResMut<loading::PreloadAssets>,
+) {
+    preload.add(asset_server.load_untyped(options.map.clone()));
+}
 
-    commands.spawn(helpers::tiled::TiledMapBundle {
-        tiled_map: map_handle,
-        ..Default::default()
-    });
+/// Leaving the menu for a new game: the first map and its music. Continuing a saved game loads
+/// its map instead.
+fn start_game(
+    options: Res<StartOptions>,
+    pending_load: Option<Res<save::PendingLoad>>,
+    mut load_map: EventWriter<helpers::tiled::LoadMap>,
+    mut play_music: EventWriter<sound::PlayMusic>,
+) {
+    if pending_load.is_none() {
+        load_map.send(helpers::tiled::LoadMap::new(options.map.clone()));
+    }
+    if let Some(music) = &options.music {
+        play_music.send(sound::PlayMusic::new(music.clone()));
+    }
 }
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
-// allocator.
-#[cfg(feature = "wee_alloc")]
+// allocator. It saves ~10K but is unmaintained and leaks under churn like ours,
+// so release builds use the default allocator.
+#[cfg(all(
+    feature = "wee_alloc",
+    target_arch = "wasm32",
+    not(feature = "track-alloc")
+))]
 #[global_allocator]
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
-#[wasm_bindgen]
-extern {
-    fn alert(s: &str);
+// `track-alloc` counts what's allocated for MemoryStats, around whichever
+// allocator would be used otherwise.
+#[cfg(all(feature = "track-alloc", feature = "wee_alloc", target_arch = "wasm32"))]
+#[global_allocator]
+static ALLOC: memory::TrackingAllocator<wee_alloc::WeeAlloc> =
+    memory::TrackingAllocator::new(wee_alloc::WeeAlloc::INIT);
+
+#[cfg(all(
+    feature = "track-alloc",
+    not(all(feature = "wee_alloc", target_arch = "wasm32"))
+))]
+#[global_allocator]
+static ALLOC: memory::TrackingAllocator<std::alloc::System> =
+    memory::TrackingAllocator::new(std::alloc::System);
+
+/// The game, ready to run: the same App on the web and natively
+pub fn app(mut options: StartOptions, lifecycle: LifecyclePlugin) -> App {
+    let mut app = App::new();
+    // registers its asset source, which has to come before the AssetPlugin
+    app.add_plugins(dropped_map::DroppedMapPlugin);
+    // on the web, replaces the default asset source
+    #[cfg(feature = "dev")]
+    app.add_plugins(asset_reload::AssetReloadPlugin {
+        asset_path: options.asset_path.clone(),
+    });
+    app.add_plugins((
+        DefaultPlugins
+            .set(WindowPlugin {
+                primary_window: Some(options.window()),
+                ..default()
+            })
+            .set(AssetPlugin {
+                file_path: options.asset_path.clone(),
+                // maps reload as they're saved in Tiled
+                watch_for_changes_override: Some(cfg!(feature = "dev")),
+                ..default()
+            })
+            .set(bevy::log::LogPlugin {
+                // crash reports hold the last lines logged
+                update_subscriber: options
+                    .crash_report_url
+                    .is_some()
+                    .then_some(crash::with_log_ring as fn(_) -> _),
+                ..default()
+            }),
+        TilemapPlugin,
+        helpers::tiled::TiledMapPlugin,
+        gfx::GFXPlugin,
+        destructible::DestructiblePlugin,
+        sound::SoundPlugin {
+            // game.assets.ron lists them
+            manifests: Vec::new(),
+            // saved with the other settings
+            persist_settings: false,
+            ..default()
+        },
+        settings::SettingsPlugin::default(),
+        (
+            input::InputPlugin,
+            input::TouchControlsPlugin {
+                forced: options.touch_controls,
+            },
+            input::GesturePlugin,
+            input::InputDisplayPlugin,
+        ),
+        state::AppStatePlugin,
+        (
+            physics::PhysicsPlugin,
+            player::PlayerPlugin,
+            platformer::PlatformerPlugin,
+            platforms::PlatformsPlugin,
+            interactables::InteractablesPlugin,
+            pickups::PickupsPlugin,
+            locks::LocksPlugin,
+            enemies::EnemiesPlugin,
+            health::HealthPlugin,
+            hazards::HazardsPlugin,
+            projectiles::ProjectilesPlugin,
+            checkpoints::CheckpointsPlugin,
+            exits::ExitsPlugin,
+            run_stats::RunStatsPlugin::default(),
+        ),
+        manifest::AssetManifestPlugin,
+        save::SavePlugin,
+        (
+            bridge::BridgePlugin::default(),
+            leaderboard::LeaderboardPlugin,
+            diagnostics::DiagnosticsOverlayPlugin,
+            memory::MemoryPlugin,
+            timestep::FixedStepPlugin,
+            pool::PoolPlugin,
+            build_info::BuildInfoPlugin,
+            locale::LocalePlugin,
+            canvas::CanvasFitPlugin {
+                observe_parent: options.fit_to_parent,
+            },
+            fullscreen::FullscreenPlugin,
+            (
+                pointer::PointerPlugin,
+                picking::PickingPlugin,
+                cursor::CursorPlugin,
+            ),
+            toast::ToastPlugin,
+            replay::ReplayPlugin,
+            ui::UiPlugin,
+        ),
+        lifecycle,
+    ))
+    .add_systems(Startup, preload_map)
+    .add_systems(
+        OnTransition {
+            from: AppState::MainMenu,
+            to: AppState::InGame,
+        },
+        start_game,
+    )
+    // skipped ahead with StartOptions::skip_to
+    .add_systems(
+        OnTransition {
+            from: AppState::Loading,
+            to: AppState::InGame,
+        },
+        start_game,
+    );
+
+    #[cfg(feature = "profiling")]
+    app.add_plugins(profiling::ProfilingPlugin);
+
+    #[cfg(feature = "dev")]
+    app.add_plugins(console::ConsolePlugin);
+
+    // a replay brings the options it was recorded with
+    app.insert_resource(replay::Replay::from_options(&mut options));
+
+    match options.skip_to {
+        Some(state) if state.can_skip_to() => {
+            app.world.resource_mut::<loading::LoadingSettings>().next = state;
+        }
+        Some(state) => warn!("Can't skip to {:?} from Loading", state),
+        None => {}
+    }
+    if options.input_overlay {
+        app.insert_resource(input::InputDisplay(true));
+    }
+    if options.debug {
+        app.insert_resource(diagnostics::DiagnosticsOverlay(true));
+        #[cfg(feature = "dev")]
+        app.insert_resource(helpers::tiled::CollisionDebug(true));
+    }
+
+    if let Some(hz) = options.fixed_hz {
+        match timestep::FixedStepConfig::new(hz) {
+            Some(config) => {
+                app.insert_resource(config);
+            }
+            None => warn!("Ignoring fixed_hz {}, it isn't a rate", hz),
+        }
+    }
+
+    let rng = options.rng();
+    info!("Random seed: {}", rng.seed());
+    crash::configure(options.crash_report_url.clone(), rng.seed());
+    app.insert_resource(rng);
+
+    let language = options
+        .language
+        .clone()
+        .or_else(|| app.world.resource::<settings::Settings>().language.clone())
+        .or_else(locale::browser_language);
+    app.insert_resource(locale::Locale::new(language.as_deref()));
+
+    // set before the first frame, so the settings don't save it
+    if options.muted {
+        app.world.resource_mut::<settings::Settings>().audio.muted = true;
+        app.world.resource_mut::<sound::AudioChannels>().muted = true;
+    }
+    app.insert_resource(options);
+    app
 }
 
-#[wasm_bindgen]
-pub fn start() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins,
-            gfx::GFXPlugin,
-            sound::SoundPlugin,
-        ))
-        .run()
+/// Runs the game in a window of its own until it's closed
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run(mut options: StartOptions) -> Result<(), options::StartError> {
+    let lifecycle = lifecycle::begin(&mut options)?;
+    app(options, lifecycle).run();
+    lifecycle::end();
+    Ok(())
 }
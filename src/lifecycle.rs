@@ -0,0 +1,289 @@
+//! The running game, shared by start(), stop() and restart(): they can't reach into the App once
+//! winit owns it, so they raise signals the App checks every frame.
+//!
+//! On wasm the App lives on after start() returns, so the page can only have one at a time.
+
+use crate::gfx::{CameraShake, CameraShakeOffset, MainCamera, ScreenFade};
+use crate::helpers::tiled::LoadMap;
+use crate::options::{StartError, StartOptions};
+use crate::save::GameProgress;
+use crate::sound::{StopAmbient, StopDucking, StopMusic};
+use crate::state::AppState;
+use bevy::{
+    app::AppExit,
+    audio::{AudioSink, AudioSinkPlayback, AudioSource, SpatialAudioSink},
+    prelude::*,
+};
+use std::cell::RefCell;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Id of the canvas start() makes when the page doesn't name one
+#[cfg(target_arch = "wasm32")]
+const CANVAS_ID: &str = "gamedevjam2024-canvas";
+
+///
+/// StopSignal
+///
+/// Raised by stop(), telling the App to stop its sounds and exit
+#[derive(Resource, Debug, Clone, Default)]
+pub struct StopSignal(Arc<AtomicBool>);
+
+impl StopSignal {
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+///
+/// RestartSignal
+///
+/// Raised by restart(), telling the App to send ResetGame
+#[derive(Resource, Debug, Clone, Default)]
+pub struct RestartSignal(Arc<AtomicBool>);
+
+impl RestartSignal {
+    pub fn restart(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether restart() was called since the last take, lowering the signal
+    pub fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Sent to start the game over: the map goes back to the initial one, entities marked Gameplay
+/// are despawned, the camera and ScreenFade are reset and every sound stops. Loaded assets,
+/// SoundResource and settings are kept. From Paused or GameOver, play resumes InGame; this is
+/// what a "play again" button sends.
+#[derive(Event, Debug, Default, Clone)]
+pub struct ResetGame;
+
+/// Marks an entity that belongs to a run, despawned with its children by ResetGame. What's
+/// spawned for the map goes with the map already.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Gameplay;
+
+///
+/// LifecyclePlugin
+///
+/// Stopping and restarting the game, signalled through the plugin's StopSignal and RestartSignal
+#[derive(Default)]
+pub struct LifecyclePlugin {
+    pub stop: StopSignal,
+    pub restart: RestartSignal,
+}
+
+impl Plugin for LifecyclePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ResetGame>()
+            .init_resource::<StartOptions>()
+            .insert_resource(self.stop.clone())
+            .insert_resource(self.restart.clone())
+            .add_systems(First, (exit_on_stop, reset_on_restart))
+            .add_systems(Update, reset_game);
+    }
+}
+
+struct Running {
+    signal: StopSignal,
+    restart: RestartSignal,
+    // the canvas start() made, removed from the page on stop()
+    #[cfg(target_arch = "wasm32")]
+    canvas: Option<web_sys::Element>,
+    // the selector of the canvas drawn to, for set_fullscreen()
+    #[cfg(target_arch = "wasm32")]
+    selector: Option<String>,
+}
+
+thread_local! {
+    static RUNNING: RefCell<Option<Running>> = const { RefCell::new(None) };
+}
+
+/// Whether a game started and hasn't been stopped
+pub fn is_running() -> bool {
+    RUNNING.with(|running| running.borrow().is_some())
+}
+
+/// Registers a new game, making it a canvas of its own if the options don't name one. Fails if
+/// one is already running.
+pub(crate) fn begin(options: &mut StartOptions) -> Result<LifecyclePlugin, StartError> {
+    if is_running() {
+        return Err(StartError::AlreadyRunning);
+    }
+    let plugin = LifecyclePlugin::default();
+    let running = Running {
+        signal: plugin.stop.clone(),
+        restart: plugin.restart.clone(),
+        #[cfg(target_arch = "wasm32")]
+        canvas: match options.canvas {
+            Some(_) => None,
+            None => {
+                let canvas = make_canvas().ok_or(StartError::NoWebGl2)?;
+                options.canvas = Some(format!("#{}", CANVAS_ID));
+                Some(canvas)
+            }
+        },
+        #[cfg(target_arch = "wasm32")]
+        selector: options.canvas.clone(),
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = options;
+    RUNNING.with(|cell| *cell.borrow_mut() = Some(running));
+    Ok(plugin)
+}
+
+/// Stops the running game, if there is one. It exits at the start of its next frame, after which
+/// start() can run a new one.
+pub fn stop() {
+    let Some(running) = RUNNING.with(|running| running.borrow_mut().take()) else {
+        return;
+    };
+    running.signal.stop();
+    #[cfg(target_arch = "wasm32")]
+    if let Some(canvas) = running.canvas {
+        canvas.remove();
+    }
+}
+
+/// Starts the running game over, if there is one, on its next frame
+pub fn restart() {
+    RUNNING.with(|running| {
+        if let Some(running) = running.borrow().as_ref() {
+            running.restart.restart();
+        }
+    });
+}
+
+/// CSS selector of the running game's canvas, None if no game is running
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn canvas_selector() -> Option<String> {
+    RUNNING.with(|running| {
+        running
+            .borrow()
+            .as_ref()
+            .and_then(|running| running.selector.clone())
+    })
+}
+
+/// Forgets the running game once its App returned, natively
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn end() {
+    RUNNING.with(|running| *running.borrow_mut() = None);
+}
+
+/// A canvas at the end of the page's body, like the one bevy would make
+#[cfg(target_arch = "wasm32")]
+fn make_canvas() -> Option<web_sys::Element> {
+    let document = web_sys::window()?.document()?;
+    let canvas = document.create_element("canvas").ok()?;
+    canvas.set_id(CANVAS_ID);
+    document.body()?.append_child(&canvas).ok()?;
+    Some(canvas)
+}
+
+type PlayingSound<'a> = (Entity, Option<&'a AudioSink>, Option<&'a SpatialAudioSink>);
+
+///
+/// exit_on_stop: Bevy system
+///
+/// Once StopSignal is raised, silences and despawns every sound and exits the App, which frees
+/// it and lets go of the canvas
+pub fn exit_on_stop(
+    mut commands: Commands,
+    signal: Res<StopSignal>,
+    audio_query: Query<PlayingSound, With<Handle<AudioSource>>>,
+    mut exit: EventWriter<AppExit>,
+    mut exiting: Local<bool>,
+) {
+    if *exiting || !signal.is_stopped() {
+        return;
+    }
+    *exiting = true;
+    for (entity, sink, spatial_sink) in audio_query.iter() {
+        // despawning drops the sink, but not before the frame ends
+        if let Some(sink) = sink {
+            sink.stop();
+        }
+        if let Some(sink) = spatial_sink {
+            sink.stop();
+        }
+        commands.entity(entity).despawn();
+    }
+    exit.send(AppExit);
+}
+
+///
+/// reset_on_restart: Bevy system
+///
+/// Sends ResetGame when RestartSignal is raised
+pub fn reset_on_restart(signal: Res<RestartSignal>, mut reset: EventWriter<ResetGame>) {
+    if signal.take() {
+        reset.send(ResetGame);
+    }
+}
+
+///
+/// reset_game: Bevy system
+///
+/// Starts over on ResetGame. Music and ambient loops stop at once rather than fading, so a fade
+/// still running from the last run doesn't carry over.
+#[allow(clippy::too_many_arguments)]
+pub fn reset_game(
+    mut commands: Commands,
+    mut events: EventReader<ResetGame>,
+    options: Res<StartOptions>,
+    fade: Option<ResMut<ScreenFade>>,
+    camera_shake: Option<ResMut<CameraShake>>,
+    mut camera_query: Query<(&mut Transform, Option<&mut CameraShakeOffset>), With<MainCamera>>,
+    gameplay_query: Query<Entity, With<Gameplay>>,
+    mut load_map: EventWriter<LoadMap>,
+    mut stop_music: EventWriter<StopMusic>,
+    mut stop_ambient: EventWriter<StopAmbient>,
+    mut stop_ducking: EventWriter<StopDucking>,
+    state: Option<Res<State<AppState>>>,
+    next_state: Option<ResMut<NextState<AppState>>>,
+    progress: Option<ResMut<GameProgress>>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    if let Some(mut progress) = progress {
+        *progress = GameProgress::default();
+    }
+
+    for entity in gameplay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    load_map.send(LoadMap::new(options.map.clone()));
+
+    for (mut transform, shake) in camera_query.iter_mut() {
+        *transform = Transform::default();
+        if let Some(mut shake) = shake {
+            shake.0 = Vec2::ZERO;
+        }
+    }
+    if let Some(mut fade) = fade {
+        *fade = ScreenFade::default();
+    }
+    if let Some(mut camera_shake) = camera_shake {
+        *camera_shake = CameraShake::default();
+    }
+
+    stop_music.send(StopMusic::with_fade_out(0.0));
+    stop_ambient.send(StopAmbient::all());
+    stop_ducking.send(StopDucking);
+
+    let ended =
+        state.is_some_and(|state| matches!(state.get(), AppState::Paused | AppState::GameOver));
+    if let Some(mut next_state) = next_state.filter(|_| ended) {
+        next_state.set(AppState::InGame);
+    }
+}
@@ -0,0 +1,247 @@
+//! The Loading state: waiting for what the game starts with to download before leaving it, with a
+//! progress bar drawn from plain UI nodes so it shows before any of it has arrived.
+
+use crate::gfx::AnimationResource;
+use crate::manifest::AssetManifests;
+use crate::sound::{SoundManifests, SoundResource};
+use crate::state::{apply_state_changes, AppState, ChangeState};
+use bevy::{asset::LoadState, prelude::*, utils::HashSet};
+
+///
+/// LoadProgress
+///
+/// The assets Loading waits on: how many there are, and how many are in or failed. Sounds from
+/// manifests join the total as their manifests are read.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+    pub failed: usize,
+}
+
+impl LoadProgress {
+    /// Whether every asset is in or failed
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed >= self.total
+    }
+
+    /// From 0.0 to 1.0, failures counting as done
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        (self.loaded + self.failed) as f32 / self.total as f32
+    }
+}
+
+///
+/// PreloadAssets
+///
+/// What Loading waits on besides the game's sounds and animations: what the asset manifest lists,
+/// and the handles of anything else loaded at startup.
+#[derive(Resource, Debug, Default)]
+pub struct PreloadAssets {
+    handles: Vec<UntypedHandle>,
+}
+
+impl PreloadAssets {
+    pub fn add<A: Asset>(&mut self, handle: Handle<A>) {
+        self.handles.push(handle.untyped());
+    }
+}
+
+///
+/// LoadingSettings
+///
+/// * timeout: seconds Loading waits before moving on without what's still loading
+/// * next: the state after Loading
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct LoadingSettings {
+    pub timeout: f32,
+    pub next: AppState,
+}
+
+impl Default for LoadingSettings {
+    fn default() -> Self {
+        LoadingSettings {
+            timeout: 15.0,
+            next: AppState::MainMenu,
+        }
+    }
+}
+
+/// Marks the root node of the loading screen
+#[derive(Component, Debug)]
+pub struct LoadingScreen;
+
+/// Marks the part of the progress bar that fills up
+#[derive(Component, Debug)]
+pub struct LoadingBar;
+
+/// The Loading state's progress tracking and loading screen, added by AppStatePlugin
+pub struct LoadingPlugin;
+
+impl Plugin for LoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LoadProgress>()
+            .init_resource::<PreloadAssets>()
+            .init_resource::<LoadingSettings>()
+            .add_systems(OnEnter(AppState::Loading), spawn_loading_screen)
+            .add_systems(OnExit(AppState::Loading), despawn_loading_screen)
+            .add_systems(
+                Update,
+                (track_loading, update_loading_bar)
+                    .chain()
+                    .run_if(in_state(AppState::Loading))
+                    .before(apply_state_changes),
+            );
+    }
+}
+
+/// Where a handle is in loading, None while it still is
+fn loaded(asset_server: &AssetServer, handle: &UntypedHandle) -> Option<bool> {
+    // handles added straight to Assets aren't tracked by the server and are in already
+    match asset_server.get_load_state(handle.id()) {
+        Some(LoadState::NotLoaded) | Some(LoadState::Loading) => None,
+        Some(LoadState::Failed) => Some(false),
+        Some(LoadState::Loaded) | None => Some(true),
+    }
+}
+
+///
+/// track_loading: Bevy system
+///
+/// Updates LoadProgress, and moves on to LoadingSettings::next once everything is in or the
+/// timeout is up, logging what didn't make it
+#[allow(clippy::too_many_arguments)]
+pub fn track_loading(
+    time: Res<Time<Real>>,
+    asset_server: Res<AssetServer>,
+    settings: Res<LoadingSettings>,
+    preload: Res<PreloadAssets>,
+    sounds: Option<Res<SoundResource>>,
+    manifests: Option<Res<SoundManifests>>,
+    animations: Option<Res<AnimationResource>>,
+    asset_manifests: Option<Res<AssetManifests>>,
+    mut progress: ResMut<LoadProgress>,
+    mut changes: EventWriter<ChangeState>,
+    mut started: Local<Option<f64>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let started = *started.get_or_insert(now);
+
+    let mut handles: Vec<UntypedHandle> = preload.handles.clone();
+    if let Some(sounds) = &sounds {
+        handles.extend(sounds.iter().map(|(_, handle)| handle.clone().untyped()));
+    }
+    if let Some(animations) = &animations {
+        handles.extend(
+            animations
+                .iter()
+                .map(|(_, animation)| animation.texture().clone().untyped()),
+        );
+    }
+    let mut seen = HashSet::new();
+    handles.retain(|handle| seen.insert(handle.id()));
+
+    let mut waiting = Vec::new();
+    let mut failed = Vec::new();
+    *progress = LoadProgress {
+        total: handles.len(),
+        ..default()
+    };
+    for handle in handles.iter() {
+        match loaded(&asset_server, handle) {
+            Some(true) => progress.loaded += 1,
+            Some(false) => {
+                progress.failed += 1;
+                failed.push(handle);
+            }
+            None => waiting.push(handle),
+        }
+    }
+    // what unread manifests list isn't in the total yet
+    let manifests_read = manifests.is_none_or(|manifests| manifests.is_applied())
+        && asset_manifests.is_none_or(|manifests| manifests.is_applied());
+
+    let timed_out = now - started >= settings.timeout as f64;
+    let done = progress.is_done() && manifests_read;
+    if !done && !timed_out {
+        return;
+    }
+    for handle in failed.iter() {
+        warn!("Failed to load {:?}", handle.path());
+    }
+    if timed_out {
+        for handle in waiting.iter() {
+            warn!("Gave up waiting on {:?}", handle.path());
+        }
+        if !manifests_read {
+            warn!("Gave up waiting on the asset and sound manifests");
+        }
+    }
+    changes.send(ChangeState(settings.next));
+}
+
+fn spawn_loading_screen(mut commands: Commands) {
+    commands
+        .spawn((
+            LoadingScreen,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::BLACK.into(),
+                ..default()
+            },
+        ))
+        .with_children(|screen| {
+            screen
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Percent(40.0),
+                        height: Val::Px(12.0),
+                        ..default()
+                    },
+                    background_color: Color::DARK_GRAY.into(),
+                    ..default()
+                })
+                .with_children(|bar| {
+                    bar.spawn((
+                        LoadingBar,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..default()
+                            },
+                            background_color: Color::WHITE.into(),
+                            ..default()
+                        },
+                    ));
+                });
+        });
+}
+
+fn update_loading_bar(
+    progress: Res<LoadProgress>,
+    mut bar_query: Query<&mut Style, With<LoadingBar>>,
+) {
+    for mut style in bar_query.iter_mut() {
+        style.width = Val::Percent(progress.fraction() * 100.0);
+    }
+}
+
+fn despawn_loading_screen(
+    mut commands: Commands,
+    screen_query: Query<Entity, With<LoadingScreen>>,
+) {
+    for entity in screen_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,284 @@
+//! Translations of the text the player sees. Each language is a RON map of keys to text in
+//! src/lang, built into the game; English is the fallback for keys another language lacks.
+//!
+//! Look text up in the Locale resource with `t!`: `t!(locale, "menu.build", build = info)` fills
+//! in the language's `{build}` with `info.to_string()`. UI text that should follow language
+//! changes gets a LocalizedText component instead, which keeps its Text up to date. Captions use
+//! the "caption.<sound name>" key when there is one, and floating text can be localized with
+//! SpawnFloatingText::localized.
+//!
+//! The language is the `language` start option, else the one saved in Settings, else the
+//! browser's, else English. SetLanguage changes it while the game runs, and saves it.
+
+use crate::crash;
+use crate::settings::Settings;
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// The language used for keys the active one lacks
+pub const FALLBACK_LANGUAGE: &str = "en";
+
+/// The built-in languages, by code
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("en", include_str!("lang/en.ron")),
+    ("de", include_str!("lang/de.ron")),
+];
+
+/// Looks up the text of `key` in a Locale, filling in placeholders: `t!(locale, "hud.points")`,
+/// or `t!(locale, "hud.coins_collected", count = 3)` for "Collected {count} coins".
+#[macro_export]
+macro_rules! t {
+    ($locale:expr, $key:expr) => {
+        $locale.text($key)
+    };
+    ($locale:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $locale.format($key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+
+///
+/// Locale
+///
+/// The active language and the text of every language. Missing keys are logged once each.
+#[derive(Resource, Debug)]
+pub struct Locale {
+    language: &'static str,
+    tables: HashMap<&'static str, HashMap<String, String>>,
+    missing: Mutex<HashSet<(&'static str, String)>>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        let tables = LANGUAGES
+            .iter()
+            .map(|(language, text)| {
+                let table = ron::from_str(text).unwrap_or_else(|e| {
+                    error!("The {} translation is unreadable: {}", language, e);
+                    HashMap::new()
+                });
+                (*language, table)
+            })
+            .collect();
+        Locale {
+            language: FALLBACK_LANGUAGE,
+            tables,
+            missing: Mutex::new(HashSet::new()),
+        }
+    }
+}
+
+impl Locale {
+    /// The Locale for `language`, English when there's no translation to it
+    pub fn new(language: Option<&str>) -> Self {
+        let mut locale = Locale::default();
+        match language.map(|language| (language, Locale::supported(language))) {
+            Some((_, Some(supported))) => locale.language = supported,
+            Some((language, None)) => {
+                info!("No {} translation, using {}", language, FALLBACK_LANGUAGE)
+            }
+            None => {}
+        }
+        locale
+    }
+
+    /// The built-in language matching a language tag, e.g. "de" for "de-AT"
+    pub fn supported(language: &str) -> Option<&'static str> {
+        let primary = language.split(&['-', '_'][..]).next()?.to_lowercase();
+        LANGUAGES
+            .iter()
+            .map(|(code, _)| *code)
+            .find(|code| *code == primary)
+    }
+
+    /// Code of the active language, e.g. "en"
+    pub fn language(&self) -> &'static str {
+        self.language
+    }
+
+    /// The text of `key` in the active language, the fallback's if it has none, or `key`
+    /// itself if neither does
+    pub fn text(&self, key: &str) -> String {
+        if let Some(text) = self.get(key) {
+            return text.to_string();
+        }
+        self.report_missing(self.language, key);
+        match self
+            .table(FALLBACK_LANGUAGE)
+            .and_then(|table| table.get(key))
+        {
+            Some(text) => text.clone(),
+            None => {
+                self.report_missing(FALLBACK_LANGUAGE, key);
+                key.to_string()
+            }
+        }
+    }
+
+    /// The text of `key` with each `{name}` replaced by the value of that name in `args`
+    pub fn format<K: AsRef<str>, V: AsRef<str>>(&self, key: &str, args: &[(K, V)]) -> String {
+        interpolate(&self.text(key), args)
+    }
+
+    /// The text of `key` in the active language only, without logging when it's missing
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.table(self.language)
+            .and_then(|table| table.get(key))
+            .map(String::as_str)
+    }
+
+    fn table(&self, language: &str) -> Option<&HashMap<String, String>> {
+        self.tables.get(language)
+    }
+
+    fn report_missing(&self, language: &'static str, key: &str) {
+        let Ok(mut missing) = self.missing.lock() else {
+            return;
+        };
+        if missing.insert((language, key.to_string())) {
+            warn!("No {} text for {}", language, key);
+        }
+    }
+}
+
+/// `text` with each `{name}` replaced by the value of that name in `args`. Placeholders without
+/// a value are left as they are.
+pub fn interpolate<K: AsRef<str>, V: AsRef<str>>(text: &str, args: &[(K, V)]) -> String {
+    let mut result = text.to_string();
+    for (name, value) in args {
+        result = result.replace(&format!("{{{}}}", name.as_ref()), value.as_ref());
+    }
+    result
+}
+
+/// The language the browser is set to, e.g. "de-DE"
+#[cfg(target_arch = "wasm32")]
+pub fn browser_language() -> Option<String> {
+    web_sys::window().and_then(|window| window.navigator().language())
+}
+
+/// The language the browser is set to, None natively
+#[cfg(not(target_arch = "wasm32"))]
+pub fn browser_language() -> Option<String> {
+    None
+}
+
+/// Switches to the language with this code, e.g. "de", and saves it in Settings
+#[derive(Event, Debug, Clone)]
+pub struct SetLanguage(pub String);
+
+/// Sent when the active language changes, for text that isn't a LocalizedText to update
+#[derive(Event, Debug, Clone)]
+pub struct LocaleChanged {
+    pub language: &'static str,
+}
+
+///
+/// LocalizedText
+///
+/// Keeps the first section of the entity's Text the text of `key` in the active language
+#[derive(Component, Debug, Clone, Default)]
+pub struct LocalizedText {
+    pub key: String,
+    pub args: Vec<(String, String)>,
+}
+
+impl LocalizedText {
+    pub fn new(key: impl Into<String>) -> Self {
+        LocalizedText {
+            key: key.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Fills in `{name}` with `value`
+    pub fn with_arg(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.args.push((name.into(), value.to_string()));
+        self
+    }
+
+    pub fn resolve(&self, locale: &Locale) -> String {
+        locale.format(&self.key, &self.args)
+    }
+}
+
+/// Locale, SetLanguage and LocalizedText
+pub struct LocalePlugin;
+
+impl Plugin for LocalePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Locale>()
+            .add_event::<SetLanguage>()
+            .add_event::<LocaleChanged>()
+            .add_systems(
+                Update,
+                (
+                    set_language.run_if(on_event::<SetLanguage>()),
+                    refresh_localized_text,
+                    localize_crash_overlay.run_if(resource_changed::<Locale>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+///
+/// set_language: Bevy system
+///
+/// Handles SetLanguage
+pub fn set_language(
+    mut events: EventReader<SetLanguage>,
+    mut locale: ResMut<Locale>,
+    settings: Option<ResMut<Settings>>,
+    mut changed: EventWriter<LocaleChanged>,
+) {
+    let Some(SetLanguage(requested)) = events.read().last() else {
+        return;
+    };
+    let Some(language) = Locale::supported(requested) else {
+        warn!(
+            "No {} translation, keeping {}",
+            requested,
+            locale.language()
+        );
+        return;
+    };
+    if locale.language() != language {
+        locale.language = language;
+        changed.send(LocaleChanged { language });
+    }
+    if let Some(mut settings) = settings {
+        if settings.language.as_deref() != Some(language) {
+            settings.language = Some(language.to_string());
+        }
+    }
+}
+
+///
+/// refresh_localized_text: Bevy system
+///
+/// Sets the text of new and changed LocalizedText, and of all of them when the language changes
+pub fn refresh_localized_text(
+    mut changed: EventReader<LocaleChanged>,
+    locale: Res<Locale>,
+    mut text_query: Query<(Ref<LocalizedText>, &mut Text)>,
+) {
+    let all = changed.read().last().is_some();
+    for (localized, mut text) in text_query.iter_mut() {
+        if !all && !localized.is_changed() {
+            continue;
+        }
+        if let Some(section) = text.sections.first_mut() {
+            section.value = localized.resolve(&locale);
+        }
+    }
+}
+
+///
+/// localize_crash_overlay: Bevy system
+///
+/// Gives the crash overlay the active language's text, since there's no Locale to ask once the
+/// game has panicked
+pub fn localize_crash_overlay(locale: Res<Locale>) {
+    crash::set_overlay_text(t!(locale, "crash.message"), t!(locale, "crash.reload"));
+}
@@ -0,0 +1,246 @@
+//! Locked doors: Tiled objects of type "locked_door", drawn over the tiles that block the way,
+//! open for the player carrying a key pickup of their `lock`. Walking into one or interacting
+//! with it with the key uses the key up, plays UNLOCK_SOUND and DOOR_OPEN_ANIMATION where the
+//! door is, and removes the tiles under it through SetTile, so the CollisionMap opens up there.
+//! Interacting without the key shows "It's locked".
+//!
+//! GameProgress remembers the doors opened, so they stay open for the rest of the game and in
+//! saves: a door opened before has its tiles removed again as its map spawns, without a sound.
+//!
+//! Object properties:
+//! * lock: the key that opens it, e.g. "red"
+//! * layer: the tile layer whose tiles it removes, all of them when unset
+//! * keep_key: opening it leaves the player the key
+//! * radius: how near the player has to be to interact with it
+
+use crate::gfx::SpawnBurst;
+use crate::helpers::tiled::{
+    apply_tile_edits, CurrentMap, RegisterTiledObject, SetTile, TileLookup, TiledLayer,
+    TiledObject, TilemapSource,
+};
+use crate::interactables::INTERACT_RADIUS;
+use crate::locale::Locale;
+use crate::physics::AabbCollider;
+use crate::player::Player;
+use crate::save::GameProgress;
+use crate::sound::PlaySFX;
+use crate::state::GameplaySet;
+use crate::toast::ShowToast;
+use crate::ui::{Interactable, Interacted};
+use bevy::{ecs::system::EntityCommands, prelude::*, transform::TransformSystem};
+
+/// Object type of locked doors
+pub const LOCKED_DOOR_TYPE: &str = "locked_door";
+/// Sound played where a door is unlocked
+pub const UNLOCK_SOUND: &str = "unlock";
+/// The AnimationResource animation played where a door opens
+pub const DOOR_OPEN_ANIMATION: &str = "door_open";
+/// Locale key of what interacting with a door without its key says
+pub const LOCKED_TEXT: &str = "locks.locked";
+
+/// How far from a door the player still walks into it, as its tiles keep them from overlapping
+const TOUCH_MARGIN: f32 = 2.0;
+
+/// On locked door objects, from their properties
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct LockedDoor {
+    pub lock: String,
+    pub layer: Option<String>,
+    pub keep_key: bool,
+}
+
+/// Sent when the player opens the locked door `entity` with a key to `lock`
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct DoorUnlocked {
+    pub entity: Entity,
+    pub lock: String,
+}
+
+/// Locked doors from Tiled, and opening them
+pub struct LocksPlugin;
+
+impl Plugin for LocksPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameProgress>()
+            .init_resource::<CurrentMap>()
+            .add_event::<DoorUnlocked>()
+            .add_event::<Interacted>()
+            .add_event::<SetTile>()
+            .add_event::<PlaySFX>()
+            .add_event::<SpawnBurst>()
+            .add_event::<ShowToast>()
+            .register_tiled_object(LOCKED_DOOR_TYPE, spawn_locked_door)
+            .add_systems(
+                Update,
+                unlock_doors.before(apply_tile_edits).in_set(GameplaySet),
+            )
+            .add_systems(
+                PostUpdate,
+                // where the doors are in the world
+                reopen_doors.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn spawn_locked_door(entity: &mut EntityCommands, object: &TiledObject) {
+    let Some(lock) = object.get_string("lock") else {
+        warn!(
+            "The locked door {} ({}) has no lock",
+            object.name, object.id
+        );
+        return;
+    };
+    let reach = INTERACT_RADIUS + object.shape.size().max_element() / 2.0;
+    entity.insert((
+        LockedDoor {
+            lock: lock.to_string(),
+            layer: object.get_string("layer").map(str::to_string),
+            keep_key: object.get_bool("keep_key").unwrap_or(false),
+        },
+        Interactable {
+            prompt_key: "prompt.unlock".to_string(),
+            radius: object.get_float("radius").unwrap_or(reach),
+        },
+    ));
+}
+
+/// The world rect a door covers
+fn door_rect(object: &TiledObject, transform: &GlobalTransform) -> Rect {
+    Rect::from_center_size(transform.translation().truncate(), object.shape.size())
+}
+
+/// Removes the tiles under a door and despawns it
+fn open_door(
+    commands: &mut Commands,
+    entity: Entity,
+    door: &LockedDoor,
+    rect: Rect,
+    lookup: &TileLookup,
+    layer_query: &Query<&TiledLayer, With<TilemapSource>>,
+    set_tile: &mut EventWriter<SetTile>,
+) {
+    let mut layers: Vec<&str> = Vec::new();
+    for layer in layer_query.iter() {
+        let wanted = door.layer.as_ref().is_none_or(|name| layer.matches(name));
+        if wanted && !layers.contains(&layer.path.as_str()) {
+            layers.push(&layer.path);
+        }
+    }
+    for pos in lookup.tiles_in_rect(rect) {
+        for layer in layers.iter() {
+            if lookup.tile_entity(layer, pos).is_some() {
+                set_tile.send(SetTile::remove(*layer, pos));
+            }
+        }
+    }
+    commands.entity(entity).despawn_recursive();
+}
+
+///
+/// unlock_doors: Bevy system
+///
+/// Opens the doors the player walks into or interacts with carrying their key, recording them in
+/// GameProgress. Interacting without the key shows LOCKED_TEXT.
+#[allow(clippy::too_many_arguments)]
+pub fn unlock_doors(
+    mut commands: Commands,
+    mut interactions: EventReader<Interacted>,
+    mut progress: ResMut<GameProgress>,
+    current: Res<CurrentMap>,
+    lookup: Res<TileLookup>,
+    locale: Option<Res<Locale>>,
+    player_query: Query<(&AabbCollider, &GlobalTransform), With<Player>>,
+    door_query: Query<(Entity, &LockedDoor, &TiledObject, &GlobalTransform)>,
+    layer_query: Query<&TiledLayer, With<TilemapSource>>,
+    mut set_tile: EventWriter<SetTile>,
+    mut unlocked: EventWriter<DoorUnlocked>,
+    mut sfx: EventWriter<PlaySFX>,
+    mut bursts: EventWriter<SpawnBurst>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let interacted: Vec<Entity> = interactions.read().map(|event| event.entity).collect();
+    let players: Vec<Rect> = player_query
+        .iter()
+        .map(|(collider, transform)| collider.rect(transform.translation().truncate()))
+        .collect();
+    for (entity, door, object, transform) in door_query.iter() {
+        let rect = door_rect(object, transform);
+        let reach = Rect::from_center_half_size(rect.center(), rect.half_size() + TOUCH_MARGIN);
+        let touching = players
+            .iter()
+            .any(|player| !player.intersect(reach).is_empty());
+        let used = interacted.contains(&entity);
+        if !touching && !used {
+            continue;
+        }
+        if !progress.has_key(&door.lock) {
+            if used {
+                let text = match locale.as_deref() {
+                    Some(locale) => locale.text(LOCKED_TEXT),
+                    None => Locale::default().text(LOCKED_TEXT),
+                };
+                toasts.send(ShowToast::info(text));
+            }
+            continue;
+        }
+
+        if !door.keep_key {
+            progress.use_key(&door.lock);
+        }
+        if let Some(map) = current.path() {
+            progress.unlock(map, object.id);
+        }
+        let position = rect.center();
+        sfx.send(PlaySFX::at(UNLOCK_SOUND, position));
+        bursts.send(SpawnBurst {
+            animation: DOOR_OPEN_ANIMATION.to_string(),
+            position,
+        });
+        open_door(
+            &mut commands,
+            entity,
+            door,
+            rect,
+            &lookup,
+            &layer_query,
+            &mut set_tile,
+        );
+        unlocked.send(DoorUnlocked {
+            entity,
+            lock: door.lock.clone(),
+        });
+    }
+}
+
+///
+/// reopen_doors: Bevy system
+///
+/// Opens the doors the player opened before as they spawn
+pub fn reopen_doors(
+    mut commands: Commands,
+    progress: Res<GameProgress>,
+    current: Res<CurrentMap>,
+    lookup: Res<TileLookup>,
+    door_query: Query<(Entity, &LockedDoor, &TiledObject, &GlobalTransform), Added<LockedDoor>>,
+    layer_query: Query<&TiledLayer, With<TilemapSource>>,
+    mut set_tile: EventWriter<SetTile>,
+) {
+    let Some(map) = current.path() else {
+        return;
+    };
+    for (entity, door, object, transform) in door_query.iter() {
+        if !progress.has_unlocked(map, object.id) {
+            continue;
+        }
+        let rect = door_rect(object, transform);
+        open_door(
+            &mut commands,
+            entity,
+            door,
+            rect,
+            &lookup,
+            &layer_query,
+            &mut set_tile,
+        );
+    }
+}
@@ -0,0 +1,37 @@
+//! The game as a desktop app, for profiling and quicker iteration than in the browser. Assets are
+//! read from `assets/` next to the executable, or in the crate root under `cargo run`; pass a map
+//! path to start somewhere other than map.tmx, e.g. `cargo run -- dungeon.tmx`, and a seed after
+//! it to replay a run: `cargo run -- map.tmx 1234`. GAMEDEVJAM_ environment variables set the same
+//! flags as the web build's URL, e.g. `GAMEDEVJAM_DEBUG=1 GAMEDEVJAM_STATE=in_game cargo run`;
+//! the arguments win over them.
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use gamedevjam2024::options::{env_flags, StartOptions};
+
+    let mut options = StartOptions::default();
+    for problem in options.apply_flags(env_flags()) {
+        eprintln!("GAMEDEVJAM_ flags: {}", problem);
+    }
+    let mut args = std::env::args().skip(1);
+    if let Some(map) = args.next() {
+        options.map = map;
+    }
+    if let Some(seed) = args.next() {
+        match seed.parse() {
+            Ok(seed) => options.seed = Some(seed),
+            Err(_) => {
+                eprintln!("Not a seed: {}", seed);
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Err(e) = gamedevjam2024::run(options) {
+        eprintln!("Could not start the game: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// the web version starts from JavaScript, see src/web.rs
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,501 @@
+//! `.assets.ron` manifests: everything the game preloads, in one place. Each entry is read on its
+//! own, so a bad one is reported and skipped instead of failing the whole manifest:
+//!
+//! (
+//!     images: [
+//!         (
+//!             path: "graphics/hero.png",
+//!             atlas: Some((tile_size: (16.0, 16.0), columns: 3, rows: 1)),
+//!             animations: [(name: "walking", first: 0, last: 2, frame_time: 0.1, kind: Repeat)],
+//!         ),
+//!     ],
+//!     // .sounds.ron manifests, see sound::SoundEntry
+//!     sounds: ["sounds/game.sounds.ron"],
+//!     maps: ["map.tmx"],
+//!     fonts: ["fonts/ui.ttf"],
+//! )
+
+use crate::gfx::{Animation, AnimationResource, AnimationType};
+use crate::loading::PreloadAssets;
+use crate::options::StartOptions;
+use crate::sound::SoundManifests;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AssetPath, AsyncReadExt, LoadContext, LoadState},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+///
+/// AtlasEntry
+///
+/// How an image is cut into frames, as TextureAtlasLayout::from_grid does it
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AtlasEntry {
+    pub tile_size: (f32, f32),
+    pub columns: usize,
+    pub rows: usize,
+    #[serde(default)]
+    pub padding: Option<(f32, f32)>,
+    #[serde(default)]
+    pub offset: Option<(f32, f32)>,
+}
+
+///
+/// AnimationEntry
+///
+/// An AnimationResource animation over the frames `first` to `last` of its image's atlas
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AnimationEntry {
+    pub name: String,
+    pub first: usize,
+    pub last: usize,
+    /// seconds each frame shows for
+    pub frame_time: f32,
+    pub kind: AnimationType,
+}
+
+///
+/// ImageEntry
+///
+/// An image to load, with the atlas its animations are cut from
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ImageEntry {
+    pub path: String,
+    #[serde(default)]
+    pub atlas: Option<AtlasEntry>,
+    #[serde(default)]
+    pub animations: Vec<AnimationEntry>,
+}
+
+const KINDS: [&str; 4] = ["images", "sounds", "maps", "fonts"];
+
+/// The entries of a `.assets.ron` file that parsed
+#[derive(Asset, TypePath, Debug, Default, Clone, PartialEq)]
+pub struct AssetManifest {
+    pub images: Vec<ImageEntry>,
+    pub sounds: Vec<String>,
+    pub maps: Vec<String>,
+    pub fonts: Vec<String>,
+}
+
+impl AssetManifest {
+    /// Parses a manifest, skipping the entries that don't parse with an error for each. Only a
+    /// file that isn't RON fails as a whole.
+    pub fn parse(bytes: &[u8]) -> Result<(Self, Vec<String>), ron::error::SpannedError> {
+        let mut errors = Vec::new();
+        let text = String::from_utf8_lossy(bytes);
+        // ron::Value forgets the names of enum variants, so the Value only checks the file is RON
+        // and each entry is read from its own text
+        let kinds: Vec<(&str, Vec<&str>)> = match ron::de::from_bytes(bytes)? {
+            ron::Value::Map(_) => items(&text)
+                .into_iter()
+                .filter_map(|item| item.split_once(':'))
+                .map(|(kind, entries)| {
+                    let kind = skip_comments(kind).trim().trim_matches('"');
+                    let entries = if skip_comments(entries).starts_with('[') {
+                        items(entries)
+                    } else {
+                        vec![entries]
+                    };
+                    (kind, entries)
+                })
+                .collect(),
+            _ => {
+                errors.push("the manifest should be a map of asset kinds".to_string());
+                Vec::new()
+            }
+        };
+
+        let mut manifest = AssetManifest::default();
+        for (kind, entries) in kinds {
+            if !KINDS.contains(&kind) {
+                errors.push(format!("unknown asset kind {}", kind));
+                continue;
+            }
+            for (index, value) in entries.into_iter().enumerate() {
+                let entry = format!("{} #{}", kind, index);
+                let parsed = match kind {
+                    "images" => ron::from_str(value).map(|image| manifest.images.push(image)),
+                    "sounds" => ron::from_str(value).map(|path| manifest.sounds.push(path)),
+                    "maps" => ron::from_str(value).map(|path| manifest.maps.push(path)),
+                    "fonts" => ron::from_str(value).map(|path| manifest.fonts.push(path)),
+                    _ => continue,
+                };
+                if let Err(e) = parsed {
+                    errors.push(format!("skipping {}: {}", entry, e));
+                }
+            }
+        }
+        Ok((manifest, errors))
+    }
+}
+
+/// `text` from its first token on, past whitespace and comments
+fn skip_comments(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        if let Some(rest) = text.strip_prefix("//") {
+            text = rest.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(rest) = text.strip_prefix("/*") {
+            text = rest.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            return text;
+        }
+    }
+}
+
+/// The text of each item of the RON list, tuple or struct that `text` starts with, split at its
+/// own commas but not those of what's nested or quoted inside it
+pub(crate) fn items(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let mut end = None;
+        match c {
+            '(' | '[' | '{' => {
+                depth += 1;
+                if depth == 1 {
+                    start = at + 1;
+                }
+            }
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(at);
+                }
+            }
+            ',' if depth == 1 => end = Some(at),
+            '"' | '\'' => {
+                while let Some((_, inside)) = chars.next() {
+                    match inside {
+                        '\\' => {
+                            chars.next();
+                        }
+                        _ if inside == c => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek().map(|(_, next)| *next) == Some('/') => {
+                chars.find(|(_, inside)| *inside == '\n');
+            }
+            '/' if chars.peek().map(|(_, next)| *next) == Some('*') => {
+                chars.next();
+                while let Some((_, inside)) = chars.next() {
+                    if inside == '*' && chars.peek().map(|(_, next)| *next) == Some('/') {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+        if let Some(end) = end {
+            let item = &text[start..end];
+            // the trailing comma, or an item commented out
+            if !skip_comments(item).is_empty() {
+                items.push(item);
+            }
+            start = at + 1;
+            if depth == 0 {
+                break;
+            }
+        }
+    }
+    items
+}
+
+#[derive(Default)]
+pub struct AssetManifestLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum AssetManifestLoaderError {
+    #[error("Could not read the manifest: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the manifest: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for AssetManifestLoader {
+    type Asset = AssetManifest;
+    type Settings = ();
+    type Error = AssetManifestLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let (manifest, errors) = AssetManifest::parse(&bytes)?;
+            for e in errors {
+                error!("{}: {}", load_context.path().display(), e);
+            }
+            Ok(manifest)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["assets.ron"]
+    }
+}
+
+///
+/// AssetManifests
+///
+/// The manifests requested at startup, and what they asked for that's still loading
+#[derive(Debug, Default, Resource)]
+pub struct AssetManifests {
+    pending: Vec<Handle<AssetManifest>>,
+    applied: Vec<Handle<AssetManifest>>,
+    loading: Vec<(String, UntypedHandle)>,
+}
+
+impl AssetManifests {
+    pub fn load(&mut self, asset_server: &AssetServer, path: String) {
+        self.pending.push(asset_server.load(path));
+    }
+
+    /// The manifests whose loads have started
+    pub fn applied(&self) -> &[Handle<AssetManifest>] {
+        &self.applied
+    }
+
+    /// Applies a manifest that's already in Assets<AssetManifest>
+    pub fn add(&mut self, handle: Handle<AssetManifest>) {
+        self.pending.push(handle);
+    }
+
+    /// True once every manifest has loaded and its loads have started
+    pub fn is_applied(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+///
+/// AssetManifestPlugin
+///
+/// Loads StartOptions::manifest at startup, preloading what it lists while Loading
+pub struct AssetManifestPlugin;
+
+impl Plugin for AssetManifestPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<AnimationResource>() {
+            app.insert_resource(AnimationResource::new());
+        }
+        app.init_asset::<AssetManifest>()
+            .init_asset_loader::<AssetManifestLoader>()
+            .init_resource::<AssetManifests>()
+            .init_resource::<PreloadAssets>()
+            .add_systems(Startup, load_asset_manifest)
+            .add_systems(
+                Update,
+                (apply_asset_manifests, report_failed_assets).chain(),
+            );
+    }
+}
+
+fn load_asset_manifest(
+    asset_server: Res<AssetServer>,
+    options: Option<Res<StartOptions>>,
+    mut manifests: ResMut<AssetManifests>,
+) {
+    let path = options.map_or_else(|| StartOptions::default().manifest, |o| o.manifest.clone());
+    manifests.load(&asset_server, path);
+}
+
+///
+/// apply_asset_manifests: Bevy system
+///
+/// Once a manifest has loaded, starts loading what it lists for Loading to wait on, and registers
+/// its animations in AnimationResource and its sound manifests with the sound module
+#[allow(clippy::too_many_arguments)]
+pub fn apply_asset_manifests(
+    asset_server: Res<AssetServer>,
+    manifest_assets: Res<Assets<AssetManifest>>,
+    mut manifests: ResMut<AssetManifests>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut animations: ResMut<AnimationResource>,
+    mut preload: ResMut<PreloadAssets>,
+    mut sound_manifests: Option<ResMut<SoundManifests>>,
+) {
+    let mut still_pending = Vec::new();
+    let pending = std::mem::take(&mut manifests.pending);
+    for handle in pending {
+        let Some(manifest) = manifest_assets.get(&handle) else {
+            if let Some(LoadState::Failed) = asset_server.get_load_state(&handle) {
+                // the loader already logged why
+                error!("Asset manifest failed to load: {:?}", handle.path());
+            } else {
+                still_pending.push(handle);
+            }
+            continue;
+        };
+
+        for image in manifest.images.iter() {
+            let texture: Handle<Image> = asset_server.load(image.path.clone());
+            preload.add(texture.clone());
+            manifests
+                .loading
+                .push((image.path.clone(), texture.clone().untyped()));
+            register_animations(
+                handle.path(),
+                image,
+                &texture,
+                &mut layouts,
+                &mut animations,
+            );
+        }
+
+        match sound_manifests.as_mut() {
+            Some(sound_manifests) => {
+                for path in manifest.sounds.iter() {
+                    sound_manifests.load(&asset_server, path.clone());
+                }
+            }
+            None if !manifest.sounds.is_empty() => {
+                warn!(
+                    "{:?} lists sounds, but there's no SoundPlugin",
+                    handle.path()
+                );
+            }
+            None => {}
+        }
+
+        for path in manifest.maps.iter().chain(manifest.fonts.iter()) {
+            // untyped, so an App without text or maps can still preload them
+            let loaded = asset_server.load_untyped(path.clone());
+            preload.add(loaded.clone());
+            manifests.loading.push((path.clone(), loaded.untyped()));
+        }
+        manifests.applied.push(handle);
+    }
+    manifests.pending = still_pending;
+}
+
+/// Registers the animations of `image`, a manifest entry, in AnimationResource
+fn register_animations(
+    manifest: Option<&AssetPath<'static>>,
+    image: &ImageEntry,
+    texture: &Handle<Image>,
+    layouts: &mut Assets<TextureAtlasLayout>,
+    animations: &mut AnimationResource,
+) {
+    let layout = image.atlas.as_ref().map(|atlas| {
+        layouts.add(TextureAtlasLayout::from_grid(
+            Vec2::from(atlas.tile_size),
+            atlas.columns,
+            atlas.rows,
+            atlas.padding.map(Vec2::from),
+            atlas.offset.map(Vec2::from),
+        ))
+    });
+    for entry in image.animations.iter() {
+        let Some(layout) = layout.clone() else {
+            error!(
+                "{:?}: animation \"{}\" of {} needs the image to have an atlas",
+                manifest, entry.name, image.path
+            );
+            continue;
+        };
+        if entry.last < entry.first {
+            error!(
+                "{:?}: animation \"{}\" ends before it starts",
+                manifest, entry.name
+            );
+            continue;
+        }
+        let animation = Animation::new(
+            layout,
+            (entry.first..=entry.last).collect(),
+            entry.frame_time,
+            entry.kind.clone(),
+        )
+        .with_texture(texture.clone());
+        animations.insert(entry.name.clone(), animation);
+    }
+}
+
+/// Brings AnimationResource up to date with a manifest that changed from `old` to `new`: the
+/// animations of its changed images are registered again, and those it no longer lists are
+/// removed. Returns what changed, e.g. "animation walking changed".
+pub fn patch_animations(
+    asset_server: &AssetServer,
+    layouts: &mut Assets<TextureAtlasLayout>,
+    animations: &mut AnimationResource,
+    manifest: Option<&AssetPath<'static>>,
+    old: &AssetManifest,
+    new: &AssetManifest,
+) -> Vec<String> {
+    let old_entries = animation_entries(old);
+    let new_entries = animation_entries(new);
+    let mut changes = Vec::new();
+    for name in old_entries.keys() {
+        if !new_entries.contains_key(name) {
+            animations.remove(name);
+            changes.push(format!("animation {} removed", name));
+        }
+    }
+    for (name, entry) in new_entries.iter() {
+        match old_entries.get(name) {
+            None => changes.push(format!("animation {} added", name)),
+            Some(old_entry) if old_entry != entry => {
+                changes.push(format!("animation {} changed", name))
+            }
+            Some(_) => {}
+        }
+    }
+    for image in new
+        .images
+        .iter()
+        .filter(|image| !old.images.contains(image))
+    {
+        let texture: Handle<Image> = asset_server.load(image.path.clone());
+        register_animations(manifest, image, &texture, layouts, animations);
+    }
+    changes
+}
+
+/// The animations of a manifest by name, with the image and atlas they're cut from
+fn animation_entries(
+    manifest: &AssetManifest,
+) -> BTreeMap<&str, (&str, Option<&AtlasEntry>, &AnimationEntry)> {
+    manifest
+        .images
+        .iter()
+        .flat_map(|image| {
+            image.animations.iter().map(move |entry| {
+                (
+                    entry.name.as_str(),
+                    (image.path.as_str(), image.atlas.as_ref(), entry),
+                )
+            })
+        })
+        .collect()
+}
+
+///
+/// report_failed_assets: Bevy system
+///
+/// Logs the manifest entries whose file failed to load
+pub fn report_failed_assets(asset_server: Res<AssetServer>, mut manifests: ResMut<AssetManifests>) {
+    manifests.loading.retain(
+        |(path, handle)| match asset_server.get_load_state(handle.id()) {
+            Some(LoadState::Loaded) | None => false,
+            Some(LoadState::Failed) => {
+                error!("{} failed to load", path);
+                false
+            }
+            _ => true,
+        },
+    );
+}
@@ -0,0 +1,57 @@
+//! Lookups over the tile layers spawned from the Tiled map
+
+use crate::helpers::tiled::TileProperties;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+
+///
+/// TileProbe: Bevy system parameter
+///
+/// Finds the tiles under a world position across every tile layer
+#[derive(SystemParam)]
+pub struct TileProbe<'w, 's> {
+    layers: Query<
+        'w,
+        's,
+        (
+            &'static TileStorage,
+            &'static TilemapSize,
+            &'static TilemapGridSize,
+            &'static TilemapType,
+            &'static GlobalTransform,
+        ),
+    >,
+    properties: Query<'w, 's, &'static TileProperties>,
+}
+
+impl<'w, 's> TileProbe<'w, 's> {
+    /// Tile entities at a world position, from the topmost layer down
+    pub fn tiles_at(&self, position: Vec2) -> Vec<Entity> {
+        let mut hits: Vec<(f32, Entity)> = Vec::new();
+        for (storage, size, grid_size, map_type, transform) in self.layers.iter() {
+            let local = transform
+                .compute_matrix()
+                .inverse()
+                .transform_point3(position.extend(0.0))
+                .truncate();
+            let Some(tile_pos) = TilePos::from_world_pos(&local, size, grid_size, map_type) else {
+                continue;
+            };
+            if let Some(tile) = storage.get(&tile_pos) {
+                hits.push((transform.translation().z, tile));
+            }
+        }
+        hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+        hits.into_iter().map(|(_, tile)| tile).collect()
+    }
+
+    /// A string property of the topmost tile at a world position that defines it
+    pub fn string_property_at(&self, position: Vec2, name: &str) -> Option<&str> {
+        self.tiles_at(position).into_iter().find_map(|tile| {
+            self.properties
+                .get(tile)
+                .ok()
+                .and_then(|properties| properties.get_string(name))
+        })
+    }
+}
@@ -0,0 +1,183 @@
+//! How much memory the game holds, to tell entity leaks from allocator growth over a long
+//! session. MemoryStats is sampled every MEMORY_SAMPLE_INTERVAL seconds, shown on the
+//! diagnostics overlay and logged each time it passes another MEMORY_LOG_STEP.
+//!
+//! On the web, linear_memory is the size of the wasm memory, which only ever grows. Builds with
+//! the `track-alloc` feature also count what's allocated through TrackingAllocator, so a heap
+//! that keeps growing while `allocated` stays put points at fragmentation rather than a leak.
+
+use bevy::prelude::*;
+use std::alloc::{GlobalAlloc, Layout};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Seconds of real time between samples
+pub const MEMORY_SAMPLE_INTERVAL: f32 = 5.0;
+/// Memory use is logged each time it passes a multiple of this many bytes
+pub const MEMORY_LOG_STEP: usize = 64 * 1024 * 1024;
+
+#[cfg(target_arch = "wasm32")]
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+///
+/// TrackingAllocator
+///
+/// An allocator counting the bytes and allocations live through it, installed as the global
+/// allocator by the `track-alloc` feature (around wee_alloc's when that's on too). The counts
+/// are shared by every TrackingAllocator.
+pub struct TrackingAllocator<A> {
+    inner: A,
+}
+
+impl<A> TrackingAllocator<A> {
+    pub const fn new(inner: A) -> Self {
+        TrackingAllocator { inner }
+    }
+}
+
+fn added(size: usize) {
+    let allocated = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK_ALLOCATED.fetch_max(allocated, Ordering::Relaxed);
+}
+
+fn removed(size: usize) {
+    ALLOCATED.fetch_sub(size, Ordering::Relaxed);
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            added(layout.size());
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            added(layout.size());
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        removed(layout.size());
+        ALLOCATIONS.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            removed(layout.size());
+            added(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Bytes allocated through TrackingAllocator and not yet freed
+pub fn allocated() -> usize {
+    ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// The most bytes allocated through TrackingAllocator at once
+pub fn peak_allocated() -> usize {
+    PEAK_ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// Allocations made through TrackingAllocator and not yet freed
+pub fn live_allocations() -> usize {
+    ALLOCATIONS.load(Ordering::Relaxed)
+}
+
+///
+/// MemoryStats
+///
+/// The last memory sample. What a build can't measure is None.
+/// * linear_memory: bytes of wasm memory, on the web
+/// * allocated, peak_allocated, allocations: TrackingAllocator's counts, with `track-alloc`
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct MemoryStats {
+    pub linear_memory: Option<usize>,
+    pub allocated: Option<usize>,
+    pub peak_allocated: Option<usize>,
+    pub allocations: Option<usize>,
+}
+
+impl MemoryStats {
+    /// Measures what this build can
+    pub fn sample() -> MemoryStats {
+        #[cfg(target_arch = "wasm32")]
+        let linear_memory = Some(core::arch::wasm32::memory_size::<0>() * WASM_PAGE_SIZE);
+        #[cfg(not(target_arch = "wasm32"))]
+        let linear_memory = None;
+        let tracking = cfg!(feature = "track-alloc");
+        MemoryStats {
+            linear_memory,
+            allocated: tracking.then(allocated),
+            peak_allocated: tracking.then(peak_allocated),
+            allocations: tracking.then(live_allocations),
+        }
+    }
+
+    /// The bytes in use, the wasm memory when it's known
+    pub fn in_use(&self) -> Option<usize> {
+        self.linear_memory.or(self.allocated)
+    }
+
+    /// The multiple of MEMORY_LOG_STEP the memory in use has passed, if it's higher than
+    /// `logged_step` (counted in steps), which becomes it
+    pub fn passed_step(&self, logged_step: &mut usize) -> Option<usize> {
+        let step = self.in_use()? / MEMORY_LOG_STEP;
+        if step <= *logged_step {
+            return None;
+        }
+        *logged_step = step;
+        Some(step * MEMORY_LOG_STEP)
+    }
+}
+
+/// Sampling MemoryStats
+pub struct MemoryPlugin;
+
+impl Plugin for MemoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MemoryStats>()
+            .add_systems(Update, sample_memory);
+    }
+}
+
+/// Bytes as MiB
+pub fn mib(bytes: usize) -> f32 {
+    bytes as f32 / (1024.0 * 1024.0)
+}
+
+///
+/// sample_memory: Bevy system
+///
+/// Updates MemoryStats every MEMORY_SAMPLE_INTERVAL seconds, logging each new MEMORY_LOG_STEP
+/// passed
+pub fn sample_memory(
+    time: Res<Time<Real>>,
+    mut stats: ResMut<MemoryStats>,
+    mut since_sampled: Local<Option<f32>>,
+    mut logged_step: Local<usize>,
+) {
+    let since = since_sampled.get_or_insert(MEMORY_SAMPLE_INTERVAL);
+    *since += time.delta_seconds();
+    if *since < MEMORY_SAMPLE_INTERVAL {
+        return;
+    }
+    *since = 0.0;
+    *stats = MemoryStats::sample();
+    if let Some(passed) = stats.passed_step(&mut logged_step) {
+        info!("Memory use passed {:.0} MiB: {:?}", mib(passed), *stats);
+    }
+}
@@ -0,0 +1,320 @@
+use crate::rng::GameRng;
+use crate::state::AppState;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::JsValue;
+
+///
+/// StartError
+///
+/// What start() throws at the page instead of freezing it
+#[derive(Error, Debug)]
+pub enum StartError {
+    #[cfg(target_arch = "wasm32")]
+    #[error("invalid start options: {0}")]
+    Options(#[from] serde_wasm_bindgen::Error),
+    #[error("no canvas matches {0}")]
+    NoCanvas(String),
+    #[error("{0} is not a canvas")]
+    NotACanvas(String),
+    #[error("this browser can't draw with WebGL2")]
+    NoWebGl2,
+    #[error("the game is already running, stop() it first")]
+    AlreadyRunning,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl From<StartError> for JsValue {
+    fn from(e: StartError) -> Self {
+        js_sys::Error::new(&e.to_string()).into()
+    }
+}
+
+///
+/// StartOptions
+///
+/// What the page hosting the game chooses, passed to start() as a JS object with these fields in
+/// camelCase. Missing fields keep their defaults, so `start({})` and `start()` start the game as
+/// it always has.
+///
+/// * canvas: CSS selector of the canvas to draw to, e.g. "#game". None makes a new canvas.
+/// * width, height: logical size of the window
+/// * fit_to_parent: resize the canvas to its parent element as that resizes, at the screen's
+///   pixel density, instead of keeping width and height (see the canvas module)
+/// * asset_path: where assets are loaded from, relative to the page (natively, to the
+///   executable)
+/// * map: the map a new game starts on, relative to asset_path
+/// * music: the SoundResource track a new game starts playing, if any
+/// * manifest: the .assets.ron manifest of what to preload, relative to asset_path
+/// * muted: start with all sound muted, whatever the saved audio settings say
+/// * seed: seeds GameRng, to replay a run. Without one the seed comes from entropy; either way
+///   it's logged at startup.
+/// * debug: show the diagnostics overlay from the start, and the other debug overlays with the
+///   dev feature
+/// * skip_to: the state to go to once Loading is done, instead of MainMenu: "in_game" or
+///   "game_over", for testing
+/// * leaderboard_url: the score API SubmitScore posts to, if any
+/// * fixed_hz: how many times a second FixedUpdate runs, DEFAULT_FIXED_HZ when None
+/// * language: the language to show the game in, e.g. "de", over the saved and the browser's
+/// * crash_report_url: where the web build POSTs a CrashReport when the game panics. None, the
+///   default, sends nothing and keeps no log for one.
+/// * record: record the session's input for replaying it, see the replay module
+/// * replay: replay the last recording instead of reading input
+/// * check_every: while recording, hash the game's state every this many fixed steps, for the
+///   replay to check it runs the same
+/// * touch_controls: Some(true) shows the on-screen controls from the start, Some(false) never
+///   does, and None, the default, shows them once the screen is touched
+/// * input_overlay: show the actions pressed on screen from the start, see InputDisplay
+///
+/// Flags can override them for playtesting without code changes, see apply_flags.
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct StartOptions {
+    pub canvas: Option<String>,
+    pub width: f32,
+    pub height: f32,
+    pub fit_to_parent: bool,
+    pub asset_path: String,
+    pub map: String,
+    pub music: Option<String>,
+    pub manifest: String,
+    pub muted: bool,
+    pub seed: Option<u64>,
+    pub debug: bool,
+    pub skip_to: Option<AppState>,
+    pub leaderboard_url: Option<String>,
+    pub fixed_hz: Option<f64>,
+    pub language: Option<String>,
+    pub crash_report_url: Option<String>,
+    pub record: bool,
+    pub replay: bool,
+    pub check_every: Option<u32>,
+    pub touch_controls: Option<bool>,
+    pub input_overlay: bool,
+}
+
+impl Default for StartOptions {
+    fn default() -> Self {
+        StartOptions {
+            canvas: None,
+            width: 1280.0,
+            height: 720.0,
+            fit_to_parent: false,
+            asset_path: "assets".to_string(),
+            map: "map.tmx".to_string(),
+            music: None,
+            manifest: "game.assets.ron".to_string(),
+            muted: false,
+            seed: None,
+            debug: false,
+            skip_to: None,
+            leaderboard_url: None,
+            fixed_hz: None,
+            language: None,
+            crash_report_url: None,
+            record: false,
+            replay: false,
+            check_every: None,
+            touch_controls: None,
+            input_overlay: false,
+        }
+    }
+}
+
+impl StartOptions {
+    /// Reads the options passed to start(), undefined and null being the defaults
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_js(options: JsValue) -> Result<Self, StartError> {
+        if options.is_undefined() || options.is_null() {
+            return Ok(StartOptions::default());
+        }
+        Ok(serde_wasm_bindgen::from_value(options)?)
+    }
+
+    /// Overrides options with flags: the page's URL query on the web, e.g.
+    /// `?map=level3.tmx&mute=1&seed=42&debug=1&state=in_game&hz=30&record=1&check=64`, and GAMEDEVJAM_ environment
+    /// variables natively, e.g. `GAMEDEVJAM_SEED=42`. The keys are those of FLAGS. Returns what
+    /// was wrong with them, for the caller to log: unknown keys and invalid values, which leave
+    /// their option as it was.
+    pub fn apply_flags<K, V>(&mut self, flags: impl IntoIterator<Item = (K, V)>) -> Vec<String>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut problems = Vec::new();
+        let mut unknown = Vec::new();
+        for (key, value) in flags {
+            let (key, value) = (key.as_ref(), value.as_ref());
+            match key {
+                "map" if !value.is_empty() => self.map = value.to_string(),
+                "mute" => match parse_flag(value) {
+                    Some(muted) => self.muted = muted,
+                    None => problems.push(format!("mute={} isn't 0 or 1", value)),
+                },
+                "seed" => match value.parse() {
+                    Ok(seed) => self.seed = Some(seed),
+                    Err(_) => {
+                        problems.push(format!("seed={} isn't a number, seeding at random", value))
+                    }
+                },
+                "debug" => match parse_flag(value) {
+                    Some(debug) => self.debug = debug,
+                    None => problems.push(format!("debug={} isn't 0 or 1", value)),
+                },
+                "state" => match AppState::from_name(value).filter(|state| state.can_skip_to()) {
+                    Some(state) => self.skip_to = Some(state),
+                    None => problems.push(format!(
+                        "state={} isn't main_menu, in_game or game_over",
+                        value
+                    )),
+                },
+                "hz" => match value.parse::<f64>() {
+                    Ok(hz) if hz.is_finite() && hz > 0.0 => self.fixed_hz = Some(hz),
+                    _ => problems.push(format!("hz={} isn't a rate", value)),
+                },
+                "record" => match parse_flag(value) {
+                    Some(record) => self.record = record,
+                    None => problems.push(format!("record={} isn't 0 or 1", value)),
+                },
+                "replay" => match parse_flag(value) {
+                    Some(replay) => self.replay = replay,
+                    None => problems.push(format!("replay={} isn't 0 or 1", value)),
+                },
+                "check" => match value.parse::<u32>() {
+                    Ok(every) if every > 0 => self.check_every = Some(every),
+                    _ => problems.push(format!("check={} isn't a number of steps", value)),
+                },
+                "touch" => match parse_flag(value) {
+                    Some(touch) => self.touch_controls = Some(touch),
+                    None => problems.push(format!("touch={} isn't 0 or 1", value)),
+                },
+                "inputoverlay" => match parse_flag(value) {
+                    Some(shown) => self.input_overlay = shown,
+                    None => problems.push(format!("inputoverlay={} isn't 0 or 1", value)),
+                },
+                "map" => problems.push("map= is empty".to_string()),
+                _ => unknown.push(key.to_string()),
+            }
+        }
+        if !unknown.is_empty() {
+            problems.push(format!("ignoring unknown flags: {}", unknown.join(", ")));
+        }
+        problems
+    }
+
+    /// Checks the page can run the game with these options, since bevy panics on a missing
+    /// canvas or WebGL2 context deep in the first frame
+    #[cfg(target_arch = "wasm32")]
+    pub fn check_page(&self) -> Result<(), StartError> {
+        use wasm_bindgen::JsCast;
+
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or(StartError::NoWebGl2)?;
+        if let Some(selector) = &self.canvas {
+            // an invalid selector throws
+            let element = document
+                .query_selector(selector)
+                .ok()
+                .flatten()
+                .ok_or_else(|| StartError::NoCanvas(selector.clone()))?;
+            if !element.is_instance_of::<web_sys::HtmlCanvasElement>() {
+                return Err(StartError::NotACanvas(selector.clone()));
+            }
+        }
+
+        // on a canvas of our own, as a canvas keeps the first kind of context it's asked for
+        let canvas = document
+            .create_element("canvas")
+            .ok()
+            .and_then(|canvas| canvas.dyn_into::<web_sys::HtmlCanvasElement>().ok())
+            .ok_or(StartError::NoWebGl2)?;
+        match canvas.get_context("webgl2") {
+            Ok(Some(_)) => Ok(()),
+            _ => Err(StartError::NoWebGl2),
+        }
+    }
+
+    /// Checks the page can run the game with these options, always fine natively
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn check_page(&self) -> Result<(), StartError> {
+        Ok(())
+    }
+
+    /// The GameRng of a run with these options
+    pub fn rng(&self) -> GameRng {
+        self.seed.map_or_else(GameRng::from_entropy, GameRng::new)
+    }
+
+    pub fn window(&self) -> Window {
+        Window {
+            canvas: self.canvas.clone(),
+            resolution: (self.width, self.height).into(),
+            ..default()
+        }
+    }
+}
+
+/// The keys StartOptions::apply_flags knows
+pub const FLAGS: &[&str] = &[
+    "map",
+    "mute",
+    "seed",
+    "debug",
+    "state",
+    "hz",
+    "record",
+    "replay",
+    "check",
+    "touch",
+    "inputoverlay",
+];
+
+/// Prefix of the environment variables holding flags natively, e.g. GAMEDEVJAM_MAP
+#[cfg(not(target_arch = "wasm32"))]
+pub const FLAG_ENV_PREFIX: &str = "GAMEDEVJAM_";
+
+fn parse_flag(value: &str) -> Option<bool> {
+    match value {
+        "1" | "true" | "" => Some(true),
+        "0" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// The flags in the query of the page's URL, e.g. `index.html?map=level3.tmx&mute=1`. A key
+/// without a value, like `?debug`, is a flag turned on.
+#[cfg(target_arch = "wasm32")]
+pub fn url_flags() -> Vec<(String, String)> {
+    let Some(search) = web_sys::window().and_then(|window| window.location().search().ok()) else {
+        return Vec::new();
+    };
+    let decode = |part: &str| {
+        // + is a space in form encoding, which decodeURIComponent leaves alone
+        let part = part.replace('+', " ");
+        js_sys::decode_uri_component(&part).map_or(part, String::from)
+    };
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (decode(key), decode(value)),
+            None => (decode(pair), String::new()),
+        })
+        .collect()
+}
+
+/// The flags in GAMEDEVJAM_ environment variables, keyed in lowercase: GAMEDEVJAM_SEED=42 is
+/// the seed flag
+#[cfg(not(target_arch = "wasm32"))]
+pub fn env_flags() -> Vec<(String, String)> {
+    std::env::vars()
+        .filter_map(|(key, value)| {
+            let key = key.strip_prefix(FLAG_ENV_PREFIX)?.to_lowercase();
+            Some((key, value))
+        })
+        .collect()
+}
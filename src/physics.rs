@@ -0,0 +1,310 @@
+//! Moving things: an entity with a Velocity moves by it in FixedSet::Physics. That's on game
+//! time, so pausing stops it and slow motion slows it down.
+//!
+//! With an AabbCollider it stops at the walls of the CollisionMap, moving along x and then y so
+//! it slides along a wall it meets at an angle. Each axis's move is cut into steps no longer than
+//! a tile or the collider, so nothing fast goes through a wall one tile thick, and the velocity
+//! into a wall it meets is dropped, with a HitWall. A collider that starts out in a wall, e.g.
+//! spawned there, moves freely until it's out. One-way platforms stop colliders coming down onto
+//! them; moving up or sideways, they go through.
+//!
+//! Knockback pushes an entity on top of its Velocity, moving with it, walls and all, so nothing
+//! is knocked through one, and dies down over a few steps, going once it's below KNOCKBACK_REST.
+//!
+//! A KinematicCollider is moved by its own system rather than by a Velocity, e.g. a moving
+//! platform, and is solid from above like a one-way platform: colliders coming down land on its
+//! top, and on_platform finds those standing on it.
+
+use crate::helpers::tiled::CollisionMap;
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::prelude::*;
+
+/// Most steps a collider's move along an axis is cut into, however fast it goes
+pub const MAX_SUBSTEPS: u32 = 16;
+/// Halvings of the step that meets a wall, finding where it does: to 1/256 of the step
+const CONTACT_ITERATIONS: u32 = 8;
+/// How far below a collider on_ground looks for the ground
+pub const GROUND_PROBE: f32 = 0.5;
+/// How far a collider can sink into a KinematicCollider's top and still be on it, for rounding
+const LANDING_TOLERANCE: f32 = 1e-3;
+/// How quickly Knockback dies down: the share of it left after a second is e to the minus this
+pub const KNOCKBACK_DAMPING: f32 = 12.0;
+/// The speed below which Knockback is done, in world units a second
+pub const KNOCKBACK_REST: f32 = 1.0;
+
+/// How fast an entity moves, in world units a second
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Velocity(pub Vec2);
+
+/// A push an entity moves by on top of its Velocity, in world units a second, e.g. from Damage.
+/// Removed once it's died down.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Knockback(pub Vec2);
+
+/// The box an entity collides with walls as, centred `offset` from its translation, e.g. a
+/// character's feet
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct AabbCollider {
+    pub half_extents: Vec2,
+    pub offset: Vec2,
+}
+
+/// The box of an entity moved by something else than its Velocity, centred on its translation,
+/// that colliders land on from above
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct KinematicCollider {
+    pub half_extents: Vec2,
+}
+
+impl KinematicCollider {
+    pub fn new(half_extents: Vec2) -> Self {
+        KinematicCollider { half_extents }
+    }
+
+    /// The box with its entity at `position`
+    pub fn rect(&self, position: Vec2) -> Rect {
+        Rect::from_center_half_size(position, self.half_extents)
+    }
+}
+
+impl AabbCollider {
+    pub fn new(half_extents: Vec2) -> Self {
+        AabbCollider {
+            half_extents,
+            offset: Vec2::ZERO,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vec2) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// The box with its entity at `position`
+    pub fn rect(&self, position: Vec2) -> Rect {
+        Rect::from_center_half_size(position + self.offset, self.half_extents)
+    }
+}
+
+/// Sent when an AabbCollider runs into a wall, `normal` pointing out of the wall
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct HitWall {
+    pub entity: Entity,
+    pub normal: Vec2,
+}
+
+/// Velocity, Knockback, AabbCollider and HitWall
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<HitWall>().add_systems(
+            FixedUpdate,
+            (
+                integrate_velocity,
+                move_colliders,
+                damp_knockback
+                    .after(integrate_velocity)
+                    .after(move_colliders),
+            )
+                .in_set(FixedSet::Physics)
+                .in_set(GameplaySet),
+        );
+    }
+}
+
+/// How far an entity moves over `delta` seconds by its Velocity and Knockback
+fn motion(velocity: &Velocity, knockback: Option<&Knockback>, delta: f32) -> Vec2 {
+    (velocity.0 + knockback.map_or(Vec2::ZERO, |knockback| knockback.0)) * delta
+}
+
+///
+/// integrate_velocity: Bevy system
+///
+/// Moves entities without an AabbCollider by their Velocity and Knockback over the step
+pub fn integrate_velocity(
+    time: Res<Time>,
+    mut query: Query<(&Velocity, Option<&Knockback>, &mut Transform), Without<AabbCollider>>,
+) {
+    let delta = time.delta_seconds();
+    for (velocity, knockback, mut transform) in query.iter_mut() {
+        let motion = motion(velocity, knockback, delta);
+        if motion != Vec2::ZERO {
+            transform.translation += motion.extend(0.0);
+        }
+    }
+}
+
+///
+/// move_colliders: Bevy system
+///
+/// Moves entities with an AabbCollider by their Velocity and Knockback over the step, up to
+/// the walls they meet
+#[allow(clippy::type_complexity)]
+pub fn move_colliders(
+    time: Res<Time>,
+    map: Option<Res<CollisionMap>>,
+    mut query: Query<(
+        Entity,
+        &AabbCollider,
+        &mut Velocity,
+        Option<&mut Knockback>,
+        &mut Transform,
+    )>,
+    platform_query: Query<(&KinematicCollider, &Transform), Without<AabbCollider>>,
+    mut hits: EventWriter<HitWall>,
+) {
+    let _span = crate::profiling::span("move_colliders");
+    let delta = time.delta_seconds();
+    let platforms = platform_rects(&platform_query);
+    for (entity, collider, mut velocity, mut knockback, mut transform) in query.iter_mut() {
+        let motion = motion(&velocity, knockback.as_deref(), delta);
+        if motion == Vec2::ZERO {
+            continue;
+        }
+        let mut position = transform.translation.truncate();
+        let normals = match map.as_deref() {
+            Some(map) if !map.overlaps(collider.rect(position)) => {
+                sweep(map, &platforms, collider, &mut position, motion)
+            }
+            _ => {
+                position += motion;
+                [None, None]
+            }
+        };
+        transform.translation = position.extend(transform.translation.z);
+        for normal in normals.iter().flatten().copied() {
+            let into = velocity.0.dot(normal);
+            velocity.0 -= normal * into;
+            if let Some(knockback) = knockback.as_mut() {
+                let into = knockback.0.dot(normal);
+                knockback.0 -= normal * into;
+            }
+            hits.send(HitWall { entity, normal });
+        }
+    }
+}
+
+///
+/// damp_knockback: Bevy system
+///
+/// Lets Knockback die down, removing it once it's below KNOCKBACK_REST
+pub fn damp_knockback(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Knockback)>,
+) {
+    let damping = (-KNOCKBACK_DAMPING * time.delta_seconds()).exp();
+    for (entity, mut knockback) in query.iter_mut() {
+        knockback.0 *= damping;
+        if knockback.0.length() < KNOCKBACK_REST {
+            commands.entity(entity).remove::<Knockback>();
+        }
+    }
+}
+
+/// Whether the collider of an entity at `position` stands on a wall or a one-way platform, one
+/// within GROUND_PROBE under it
+pub fn on_ground(map: &CollisionMap, collider: &AabbCollider, position: Vec2) -> bool {
+    let rect = collider.rect(position);
+    let below = Rect::new(
+        rect.min.x,
+        rect.min.y - GROUND_PROBE,
+        rect.max.x,
+        rect.min.y,
+    );
+    map.overlaps(below) || map.one_way_landing(rect, GROUND_PROBE).is_some()
+}
+
+/// The boxes of the KinematicColliders of `query`
+pub fn platform_rects(
+    query: &Query<(&KinematicCollider, &Transform), Without<AabbCollider>>,
+) -> Vec<Rect> {
+    query
+        .iter()
+        .map(|(platform, transform)| platform.rect(transform.translation.truncate()))
+        .collect()
+}
+
+/// Whether the collider of an entity at `position` stands on one of the `platforms`, the top
+/// of one within GROUND_PROBE under it
+pub fn on_platform(platforms: &[Rect], collider: &AabbCollider, position: Vec2) -> bool {
+    platform_landing(platforms, collider.rect(position), GROUND_PROBE).is_some()
+}
+
+/// The highest top of the `platforms` that `rect` lands on coming down by `drop`, if any
+pub fn platform_landing(platforms: &[Rect], rect: Rect, drop: f32) -> Option<f32> {
+    platforms
+        .iter()
+        .filter(|platform| platform.min.x < rect.max.x && rect.min.x < platform.max.x)
+        .map(|platform| platform.max.y)
+        .filter(|top| *top <= rect.min.y + LANDING_TOLERANCE && *top >= rect.min.y - drop)
+        .reduce(f32::max)
+}
+
+/// Moves `position` by `motion`, along x and then y, stopping at walls and landing on the tops
+/// of one-way platforms and `platforms`. Returns the normals of the walls met on each axis.
+pub fn sweep(
+    map: &CollisionMap,
+    platforms: &[Rect],
+    collider: &AabbCollider,
+    position: &mut Vec2,
+    motion: Vec2,
+) -> [Option<Vec2>; 2] {
+    let mut normals = [None, None];
+    for (normal, axis) in normals.iter_mut().zip([Vec2::X, Vec2::Y]) {
+        let distance = motion.dot(axis);
+        if distance == 0.0 {
+            continue;
+        }
+        let longest = [
+            map.tile_size().dot(axis),
+            collider.half_extents.dot(axis) * 2.0,
+        ]
+        .iter()
+        .copied()
+        .filter(|size| *size > 0.0)
+        .fold(f32::INFINITY, f32::min);
+        let steps = (distance.abs() / longest)
+            .ceil()
+            .clamp(1.0, MAX_SUBSTEPS as f32) as u32;
+        let step = axis * distance / steps as f32;
+        for _ in 0..steps {
+            // the part of the step it gets through, if it's stopped
+            let mut reach = None;
+            if map.overlaps(collider.rect(*position + step)) {
+                let (mut free, mut blocked) = (0.0, 1.0);
+                for _ in 0..CONTACT_ITERATIONS {
+                    let middle = (free + blocked) / 2.0;
+                    if map.overlaps(collider.rect(*position + step * middle)) {
+                        blocked = middle;
+                    } else {
+                        free = middle;
+                    }
+                }
+                reach = Some(free);
+            }
+            if axis == Vec2::Y && distance < 0.0 {
+                let rect = collider.rect(*position);
+                let landing = [
+                    map.one_way_landing(rect, -step.y),
+                    platform_landing(platforms, rect, -step.y),
+                ];
+                if let Some(top) = landing.iter().flatten().copied().reduce(f32::max) {
+                    let landing = ((rect.min.y - top) / -step.y).max(0.0);
+                    reach = Some(reach.map_or(landing, |free: f32| free.min(landing)));
+                }
+            }
+            match reach {
+                None => *position += step,
+                Some(free) => {
+                    *position += step * free;
+                    *normal = Some(-axis * distance.signum());
+                    break;
+                }
+            }
+        }
+    }
+    normals
+}
@@ -0,0 +1,155 @@
+//! Pointing at and clicking things in the world, for clicking an enemy to inspect it or a chest to
+//! open it. Entities with Pickable are hit by the cursor within a box around them, and the
+//! topmost one under CursorWorldPosition, by z, is the one pointed at: PointerEntered and
+//! PointerExited are sent as that changes, and Clicked for each mouse button pressed on it.
+//!
+//! UI nodes with an Interaction, like buttons, swallow the pointer while it's over them, so
+//! clicking a button doesn't also click what's behind it. Give a panel an Interaction for it to
+//! do the same. A Highlight tints its sprite while it's pointed at.
+
+use crate::pointer::CursorWorldPosition;
+use bevy::prelude::*;
+
+/// Can be pointed at and clicked within a box `size` across, centered on the entity
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Pickable {
+    pub size: Vec2,
+}
+
+/// Tints the entity's sprite `color` while it's pointed at, then puts back the color it had
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Highlight {
+    pub color: Color,
+    restore: Option<Color>,
+}
+
+impl Highlight {
+    pub fn new(color: Color) -> Self {
+        Highlight {
+            color,
+            restore: None,
+        }
+    }
+}
+
+///
+/// PointedAt
+///
+/// The Pickable under the cursor, if any
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PointedAt(pub Option<Entity>);
+
+/// Sent when the cursor comes onto `entity`
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerEntered {
+    pub entity: Entity,
+}
+
+/// Sent when the cursor leaves `entity`, or something over it comes between them
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointerExited {
+    pub entity: Entity,
+}
+
+/// Sent when `button` is pressed with the cursor on `entity`
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clicked {
+    pub entity: Entity,
+    pub button: MouseButton,
+}
+
+/// Pickable, Highlight, PointedAt and the pointer events
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PointedAt>()
+            .init_resource::<CursorWorldPosition>()
+            .add_event::<PointerEntered>()
+            .add_event::<PointerExited>()
+            .add_event::<Clicked>()
+            .add_systems(Update, (pick, highlight_picked).chain());
+    }
+}
+
+///
+/// pick: Bevy system
+///
+/// Finds the topmost Pickable under the cursor, unless the cursor is over the UI, sending
+/// PointerEntered and PointerExited as it changes and Clicked for the mouse buttons pressed on it
+#[allow(clippy::too_many_arguments)]
+pub fn pick(
+    cursor: Res<CursorWorldPosition>,
+    mouse: Option<Res<ButtonInput<MouseButton>>>,
+    ui_query: Query<&Interaction, With<Node>>,
+    pickable_query: Query<(Entity, &Pickable, &GlobalTransform, Option<&Visibility>)>,
+    mut pointed: ResMut<PointedAt>,
+    mut entered: EventWriter<PointerEntered>,
+    mut exited: EventWriter<PointerExited>,
+    mut clicked: EventWriter<Clicked>,
+) {
+    let over_ui = ui_query
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+    let hit = cursor
+        .0
+        .filter(|_| !over_ui)
+        .and_then(|position| {
+            pickable_query
+                .iter()
+                .filter(|(.., visibility)| visibility.is_none_or(|v| *v != Visibility::Hidden))
+                .filter(|(_, pickable, transform, _)| {
+                    Rect::from_center_size(transform.translation().truncate(), pickable.size)
+                        .contains(position)
+                })
+                .max_by(|(_, _, a, _), (_, _, b, _)| {
+                    a.translation().z.total_cmp(&b.translation().z)
+                })
+        })
+        .map(|(entity, ..)| entity);
+
+    if pointed.0 != hit {
+        if let Some(entity) = pointed.0 {
+            exited.send(PointerExited { entity });
+        }
+        if let Some(entity) = hit {
+            entered.send(PointerEntered { entity });
+        }
+        pointed.0 = hit;
+    }
+    let (Some(entity), Some(mouse)) = (hit, mouse) else {
+        return;
+    };
+    for button in mouse.get_just_pressed() {
+        clicked.send(Clicked {
+            entity,
+            button: *button,
+        });
+    }
+}
+
+///
+/// highlight_picked: Bevy system
+///
+/// Tints the sprites of Highlights as the pointer comes onto them and puts them back as it leaves
+pub fn highlight_picked(
+    mut entered: EventReader<PointerEntered>,
+    mut exited: EventReader<PointerExited>,
+    mut highlight_query: Query<(&mut Highlight, &mut Sprite)>,
+) {
+    for event in exited.read() {
+        if let Ok((mut highlight, mut sprite)) = highlight_query.get_mut(event.entity) {
+            if let Some(color) = highlight.restore.take() {
+                sprite.color = color;
+            }
+        }
+    }
+    for event in entered.read() {
+        if let Ok((mut highlight, mut sprite)) = highlight_query.get_mut(event.entity) {
+            if highlight.restore.is_none() {
+                highlight.restore = Some(sprite.color);
+            }
+            sprite.color = highlight.color;
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Coins, hearts and keys: Tiled objects of type "coin", "heart" and "key", or spawned with
+//! spawn_pickup, e.g. dropped by enemies. They bob gently where they are, and the player picks one
+//! up by walking its AabbCollider into it: coins add their amount to GameProgress::score, hearts
+//! heal its Health, or GameProgress::health up to max_health before it has any, and keys go to
+//! GameProgress::keys for the locked doors of their lock. Picking one up plays its sound and its
+//! sparkle where it was, shows "+1" rising from there and sends PickupCollected.
+//!
+//! Pickups on maps are picked up once: GameProgress remembers them, so they're gone when the
+//! player comes back to the map, and from saves. Spawned ones are new every time.
+//!
+//! Object properties:
+//! * amount: how much it's worth, 1 if unset
+//! * key: on keys, the lock they open, e.g. "red"
+
+use crate::gfx::{AnimationResource, SpawnBurst, SpawnFloatingText};
+use crate::health::Health;
+use crate::helpers::tiled::{CurrentMap, RegisterTiledObject, TiledObject};
+use crate::physics::AabbCollider;
+use crate::player::Player;
+use crate::save::GameProgress;
+use crate::sound::PlaySFX;
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+/// Object types spawning pickups
+pub const COIN_TYPE: &str = "coin";
+pub const HEART_TYPE: &str = "heart";
+pub const KEY_TYPE: &str = "key";
+/// The AnimationResource animation played where a pickup is picked up
+pub const SPARKLE_ANIMATION: &str = "sparkle";
+/// Half the size of the box the player picks a pickup up by touching
+pub const PICKUP_HALF_EXTENTS: Vec2 = Vec2::splat(5.0);
+
+/// What a pickup gives the player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PickupKind {
+    Coin,
+    Heart,
+    Key,
+}
+
+impl PickupKind {
+    /// The kind spawned by objects of `object_type`, if any
+    pub fn from_object_type(object_type: &str) -> Option<Self> {
+        match object_type {
+            COIN_TYPE => Some(PickupKind::Coin),
+            HEART_TYPE => Some(PickupKind::Heart),
+            KEY_TYPE => Some(PickupKind::Key),
+            _ => None,
+        }
+    }
+
+    /// The AnimationResource animation it's drawn with, and the sound it's picked up with
+    pub fn name(self) -> &'static str {
+        match self {
+            PickupKind::Coin => COIN_TYPE,
+            PickupKind::Heart => HEART_TYPE,
+            PickupKind::Key => KEY_TYPE,
+        }
+    }
+}
+
+/// Picked up by the player for `amount` of `kind`
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub amount: i32,
+}
+
+/// On key pickups: the lock the key opens
+#[derive(Component, Debug, Clone, PartialEq, Eq)]
+pub struct PickupKey(pub String);
+
+/// Sent when the player picks a pickup up
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PickupCollected {
+    pub kind: PickupKind,
+    pub amount: i32,
+}
+
+/// Moves an entity up and down by `height` world units and back every `period` seconds
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Bob {
+    pub height: f32,
+    pub period: f32,
+    offset: f32,
+    elapsed: f32,
+}
+
+impl Bob {
+    pub fn new(height: f32, period: f32) -> Self {
+        Bob {
+            height,
+            period,
+            offset: 0.0,
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl Default for Bob {
+    fn default() -> Self {
+        Bob::new(1.5, 1.2)
+    }
+}
+
+/// Pickups from Tiled and spawn_pickup, and picking them up
+pub struct PickupsPlugin;
+
+impl Plugin for PickupsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameProgress>()
+            .init_resource::<CurrentMap>()
+            .add_event::<PickupCollected>()
+            .add_event::<PlaySFX>()
+            .add_event::<SpawnBurst>()
+            .add_event::<SpawnFloatingText>()
+            .register_tiled_object(COIN_TYPE, spawn_object_pickup)
+            .register_tiled_object(HEART_TYPE, spawn_object_pickup)
+            .register_tiled_object(KEY_TYPE, spawn_object_pickup)
+            .add_systems(Update, (prepare_pickups, bob.in_set(GameplaySet)).chain())
+            .add_systems(
+                FixedUpdate,
+                collect_pickups
+                    .in_set(FixedSet::PostPhysics)
+                    .in_set(GameplaySet),
+            );
+    }
+}
+
+fn spawn_object_pickup(entity: &mut EntityCommands, object: &TiledObject) {
+    let Some(kind) = PickupKind::from_object_type(&object.object_type) else {
+        return;
+    };
+    if kind == PickupKind::Key {
+        let Some(lock) = object.get_string("key") else {
+            warn!(
+                "The key {} ({}) has no key property",
+                object.name, object.id
+            );
+            return;
+        };
+        entity.insert(PickupKey(lock.to_string()));
+    }
+    entity.insert(pickup_bundle(kind, object.get_int("amount").unwrap_or(1)));
+}
+
+fn pickup_bundle(kind: PickupKind, amount: i32) -> (Pickup, Bob, AabbCollider) {
+    (
+        Pickup { kind, amount },
+        Bob::default(),
+        AabbCollider::new(PICKUP_HALF_EXTENTS),
+    )
+}
+
+/// Spawns a pickup at `position` that isn't remembered once picked up, e.g. an enemy's drop
+pub fn spawn_pickup(
+    commands: &mut Commands,
+    kind: PickupKind,
+    amount: i32,
+    position: Vec3,
+) -> Entity {
+    commands
+        .spawn((
+            pickup_bundle(kind, amount),
+            SpatialBundle::from_transform(Transform::from_translation(position)),
+        ))
+        .id()
+}
+
+///
+/// prepare_pickups: Bevy system
+///
+/// Despawns new pickups on maps that have been picked up already, and gives the rest the sprite
+/// of their kind's animation, if there is one
+pub fn prepare_pickups(
+    mut commands: Commands,
+    progress: Res<GameProgress>,
+    current: Res<CurrentMap>,
+    animations: Option<Res<AnimationResource>>,
+    pickup_query: Query<(Entity, &Pickup, Option<&TiledObject>), Added<Pickup>>,
+) {
+    for (entity, pickup, object) in pickup_query.iter() {
+        let collected = object
+            .zip(current.path())
+            .is_some_and(|(object, map)| progress.has_collected(map, object.id));
+        if collected {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let Some(animation) = animations
+            .as_ref()
+            .and_then(|animations| animations.get(pickup.kind.name()))
+        else {
+            continue;
+        };
+        commands.entity(entity).insert((
+            Sprite::default(),
+            TextureAtlas {
+                layout: animation.atlas().clone(),
+                index: animation.frame(),
+            },
+            animation.texture().clone(),
+            animation,
+        ));
+    }
+}
+
+///
+/// bob: Bevy system
+///
+/// Moves Bob entities up and down
+pub fn bob(time: Res<Time>, mut query: Query<(&mut Bob, &mut Transform)>) {
+    for (mut bob, mut transform) in query.iter_mut() {
+        bob.elapsed = (bob.elapsed + time.delta_seconds()) % bob.period.max(f32::EPSILON);
+        let offset = (bob.elapsed / bob.period * std::f32::consts::TAU).sin() * bob.height;
+        transform.translation.y += offset - bob.offset;
+        bob.offset = offset;
+    }
+}
+
+///
+/// collect_pickups: Bevy system
+///
+/// Picks up the pickups the player touches, remembering those on maps in GameProgress
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn collect_pickups(
+    mut commands: Commands,
+    mut progress: ResMut<GameProgress>,
+    current: Res<CurrentMap>,
+    mut player_query: Query<(&AabbCollider, &GlobalTransform, Option<&mut Health>), With<Player>>,
+    pickup_query: Query<(
+        Entity,
+        &Pickup,
+        &AabbCollider,
+        Ref<GlobalTransform>,
+        Option<&TiledObject>,
+        Option<&PickupKey>,
+    )>,
+    mut collected: EventWriter<PickupCollected>,
+    mut sfx: EventWriter<PlaySFX>,
+    mut bursts: EventWriter<SpawnBurst>,
+    mut texts: EventWriter<SpawnFloatingText>,
+) {
+    for (player_collider, player_transform, mut player_health) in player_query.iter_mut() {
+        let player = player_collider.rect(player_transform.translation().truncate());
+        for (entity, pickup, collider, transform, object, key) in pickup_query.iter() {
+            // a new pickup's GlobalTransform is still at the origin until PostUpdate
+            if transform.is_added() {
+                continue;
+            }
+            let position = transform.translation().truncate();
+            if player.intersect(collider.rect(position)).is_empty() {
+                continue;
+            }
+            match pickup.kind {
+                PickupKind::Coin => {
+                    progress.score = progress.score.saturating_add_signed(pickup.amount as i64)
+                }
+                PickupKind::Heart if player_health.is_some() => {
+                    if let Some(health) = player_health.as_deref_mut() {
+                        health.heal(pickup.amount);
+                    }
+                }
+                PickupKind::Heart => {
+                    let health = progress.health.unwrap_or(0) + pickup.amount;
+                    progress.health = Some(match progress.max_health {
+                        Some(max) => health.min(max),
+                        None => health,
+                    });
+                }
+                PickupKind::Key => {
+                    if let Some(key) = key {
+                        for _ in 0..pickup.amount.max(1) {
+                            progress.add_key(key.0.clone());
+                        }
+                    }
+                }
+            }
+            if let (Some(object), Some(map)) = (object, current.path()) {
+                progress.collect(map, object.id);
+            }
+            commands.entity(entity).despawn_recursive();
+            collected.send(PickupCollected {
+                kind: pickup.kind,
+                amount: pickup.amount,
+            });
+            sfx.send(PlaySFX::at(pickup.kind.name(), position));
+            bursts.send(SpawnBurst {
+                animation: SPARKLE_ANIMATION.to_string(),
+                position,
+            });
+            texts.send(
+                SpawnFloatingText::localized("hud.points", position)
+                    .with_arg("points", pickup.amount),
+            );
+        }
+    }
+}
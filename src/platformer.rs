@@ -0,0 +1,294 @@
+//! Side-view movement: a PlatformerController on an entity with a Velocity and an AabbCollider
+//! makes it fall with the Gravity and stand on walls, one-way platforms and KinematicColliders,
+//! e.g. moving platforms. On the player it takes over from the top-down movement: MoveLeft and
+//! MoveRight walk, and Jump jumps from the ground, or up to coyote_time after walking off a
+//! ledge. Jump presses are buffered, so one pressed just before landing still jumps, and letting
+//! go of Jump on the way up cuts the jump short, for small hops.
+//!
+//! Maps with a `gravity` property are side-view: while one is loaded the player has a
+//! PlatformerController, and Gravity is the property, in world units a second squared. The
+//! controller drives the AnimationController through the JUMP, FALL and LAND states, playing
+//! LAND_SOUND on landing.
+
+use crate::gfx::{apply_animation_states, Animation, AnimationController};
+use crate::helpers::tiled::{CollisionMap, MapProperties};
+use crate::input::{Action, ActionAxis, FixedActionState};
+use crate::physics::{
+    on_ground, on_platform, platform_rects, sweep, AabbCollider, KinematicCollider, Velocity,
+    GROUND_PROBE,
+};
+use crate::player::{Facing, MovementStats, Player, IDLE, PLAYER_SPEED, WALK};
+use crate::sound::PlaySFX;
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::prelude::*;
+
+/// Map property that makes a map side-view, with that gravity
+pub const GRAVITY_PROPERTY: &str = "gravity";
+/// Gravity in world units a second squared, when the map doesn't say
+pub const DEFAULT_GRAVITY: f32 = 720.0;
+/// AnimationController states of platformers in the air, going up and coming down, and landing
+pub const JUMP: &str = "jump";
+pub const FALL: &str = "fall";
+pub const LAND: &str = "land";
+/// Sound played when a platformer lands
+pub const LAND_SOUND: &str = "land";
+/// Seconds in the air before coming down counts as landing, so stepping down a little doesn't
+pub const LANDING_AIR_TIME: f32 = 0.1;
+
+///
+/// Gravity
+///
+/// How fast platformers in the air speed up coming down, in world units a second squared, from
+/// the map's gravity property
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Gravity(pub f32);
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity(DEFAULT_GRAVITY)
+    }
+}
+
+/// Side-view movement, see the module docs
+/// * jump_speed: upwards speed a jump starts with, in world units a second
+/// * jump_cut: what the upwards speed is multiplied by when Jump is let go of on the way up
+/// * coyote_time: seconds after walking off a ledge that a jump still works
+/// * max_fall_speed: in world units a second
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct PlatformerController {
+    pub jump_speed: f32,
+    pub jump_cut: f32,
+    pub coyote_time: f32,
+    pub max_fall_speed: f32,
+    grounded: bool,
+    landed: bool,
+    // going up from a jump, until Jump is let go of or it starts coming down
+    rising: bool,
+    airborne: f32,
+}
+
+impl Default for PlatformerController {
+    fn default() -> Self {
+        PlatformerController {
+            jump_speed: 240.0,
+            jump_cut: 0.4,
+            coyote_time: 0.1,
+            max_fall_speed: 400.0,
+            // so spawning doesn't count as landing
+            grounded: true,
+            landed: false,
+            rising: false,
+            airborne: 0.0,
+        }
+    }
+}
+
+impl PlatformerController {
+    /// Whether it was on the ground at the start of the step
+    pub fn grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Whether it landed at the start of the step, after LANDING_AIR_TIME or more in the air
+    pub fn landed(&self) -> bool {
+        self.landed
+    }
+
+    /// Seconds since it was last on the ground, infinite once it jumped
+    pub fn airborne(&self) -> f32 {
+        self.airborne
+    }
+}
+
+/// Gravity, PlatformerController and side-view maps
+pub struct PlatformerPlugin;
+
+impl Plugin for PlatformerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Gravity>()
+            .add_systems(Update, apply_side_view)
+            .add_systems(
+                FixedUpdate,
+                (
+                    move_platformers.in_set(FixedSet::Gameplay),
+                    animate_platformers
+                        .in_set(FixedSet::PostPhysics)
+                        .before(apply_animation_states),
+                )
+                    .in_set(GameplaySet),
+            );
+    }
+}
+
+///
+/// apply_side_view: Bevy system
+///
+/// Sets the Gravity from the map's gravity property, and gives the player a
+/// PlatformerController on maps that have one and takes it away on maps that don't
+pub fn apply_side_view(
+    mut commands: Commands,
+    properties: Option<Res<MapProperties>>,
+    mut gravity: ResMut<Gravity>,
+    player_query: Query<(Entity, Has<PlatformerController>), With<Player>>,
+    added_query: Query<(), Added<Player>>,
+) {
+    let changed = properties
+        .as_ref()
+        .is_some_and(|properties| properties.is_changed());
+    if !changed && added_query.is_empty() {
+        return;
+    }
+    let side_view = properties
+        .as_deref()
+        .and_then(|properties| properties.get_float(GRAVITY_PROPERTY));
+    let wanted = Gravity(side_view.unwrap_or(DEFAULT_GRAVITY));
+    if *gravity != wanted {
+        *gravity = wanted;
+    }
+    for (player, platformer) in player_query.iter() {
+        match (side_view.is_some(), platformer) {
+            (true, false) => {
+                commands
+                    .entity(player)
+                    .insert(PlatformerController::default());
+            }
+            (false, true) => {
+                commands.entity(player).remove::<PlatformerController>();
+            }
+            _ => {}
+        }
+    }
+}
+
+///
+/// move_platformers: Bevy system
+///
+/// Finds which platformers are on the ground, walks and jumps the player's and pulls those in
+/// the air down with the Gravity
+#[allow(clippy::type_complexity)]
+pub fn move_platformers(
+    time: Res<Time>,
+    gravity: Res<Gravity>,
+    map: Option<Res<CollisionMap>>,
+    mut actions: ResMut<FixedActionState>,
+    mut sfx: EventWriter<PlaySFX>,
+    mut query: Query<(
+        &mut PlatformerController,
+        &mut Velocity,
+        &AabbCollider,
+        &mut Transform,
+        Option<&MovementStats>,
+        Has<Player>,
+    )>,
+    platform_query: Query<(&KinematicCollider, &Transform), Without<AabbCollider>>,
+) {
+    let delta = time.delta_seconds();
+    let platforms = platform_rects(&platform_query);
+    for (mut controller, mut velocity, collider, mut transform, stats, player) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let grounded = velocity.0.y <= 0.0
+            && (map
+                .as_deref()
+                .is_some_and(|map| on_ground(map, collider, position))
+                || on_platform(&platforms, collider, position));
+        // onto what it stands on, rather than up to GROUND_PROBE over it
+        if let Some(map) = map.as_deref().filter(|_| grounded) {
+            let mut settled = position;
+            sweep(
+                map,
+                &platforms,
+                collider,
+                &mut settled,
+                Vec2::new(0.0, -GROUND_PROBE),
+            );
+            transform.translation.y = settled.y;
+        }
+        controller.landed =
+            grounded && !controller.grounded && controller.airborne > LANDING_AIR_TIME;
+        if controller.landed {
+            sfx.send(PlaySFX::at(LAND_SOUND, position));
+        }
+        if grounded {
+            controller.airborne = 0.0;
+        } else {
+            controller.airborne += delta;
+        }
+        controller.grounded = grounded;
+
+        if player {
+            let speed = stats.map_or(PLAYER_SPEED, |stats| stats.speed);
+            velocity.0.x = actions.axis(ActionAxis::MoveX) * speed;
+            if actions.buffered(Action::Jump) && controller.airborne <= controller.coyote_time {
+                actions.consume(Action::Jump);
+                velocity.0.y = controller.jump_speed;
+                controller.grounded = false;
+                controller.rising = true;
+                // one jump per time on the ground, coyote time or not
+                controller.airborne = f32::INFINITY;
+            } else if controller.rising && !actions.pressed(Action::Jump) {
+                velocity.0.y *= controller.jump_cut;
+                controller.rising = false;
+            }
+        }
+        if velocity.0.y <= 0.0 {
+            controller.rising = false;
+        }
+
+        if !controller.grounded {
+            velocity.0.y = (velocity.0.y - gravity.0 * delta).max(-controller.max_fall_speed);
+        } else if velocity.0.y < 0.0 {
+            velocity.0.y = 0.0;
+        }
+    }
+}
+
+///
+/// animate_platformers: Bevy system
+///
+/// Plays JUMP going up and FALL coming down, LAND once on landing, and then the walk and idle
+/// clips as walkers do, facing left or right
+#[allow(clippy::type_complexity)]
+pub fn animate_platformers(
+    mut query: Query<(
+        &PlatformerController,
+        &Velocity,
+        &mut Facing,
+        &mut AnimationController,
+        &mut Sprite,
+        Option<&Animation>,
+    )>,
+) {
+    for (platformer, velocity, mut facing, mut controller, mut sprite, animation) in
+        query.iter_mut()
+    {
+        let landing = controller.state() == LAND
+            && velocity.0.x == 0.0
+            && !animation.is_some_and(Animation::finished);
+        let state = if !platformer.grounded() {
+            if velocity.0.y > 0.0 {
+                JUMP
+            } else {
+                FALL
+            }
+        } else if platformer.landed() || landing {
+            LAND
+        } else if velocity.0.x != 0.0 {
+            WALK
+        } else {
+            IDLE
+        };
+        if controller.state() != state {
+            controller.set_state(state);
+        }
+        if let Some(moved) = Facing::from_direction(Vec2::new(velocity.0.x, 0.0)) {
+            if *facing != moved {
+                *facing = moved;
+            }
+        }
+        let flipped = *facing == Facing::Left;
+        if sprite.flip_x != flipped {
+            sprite.flip_x = flipped;
+        }
+    }
+}
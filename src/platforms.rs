@@ -0,0 +1,353 @@
+//! Moving platforms: Tiled objects of type "platform" spawn a platform the size of the object,
+//! solid from above, that moves along the polyline or polygon their `path` property points at:
+//! back and forth along a polyline, round a polygon. Drawn with the PLATFORM_ANIMATION when there
+//! is one, as a plain box otherwise.
+//!
+//! Platforms are KinematicColliders. Colliders standing on one go with it, sideways included,
+//! but only as far as the walls let them: a platform doesn't push anything through a wall. One
+//! carrying something up into a ceiling passes through it instead, unless it `crushes`, when it
+//! damages it too. Where a platform is comes from the PlatformClock, the fixed-step time since its
+//! map loaded, so it's the same in replays and platforms on the same path stay together.
+//!
+//! Object properties:
+//! * path: the polyline or polygon it follows, its centre on the path; it stays put without one
+//! * speed: how fast it moves, in world units a second, DEFAULT_PLATFORM_SPEED if unset
+//! * pause: seconds it waits at either end of a polyline, or at the start of a polygon
+//! * crushes: whether it damages what it carries into a ceiling
+//! * damage: how much crushing does, CRUSH_DAMAGE if unset
+
+use crate::enemies::patrol_from_path;
+use crate::gfx::{AnimationResource, SpriteLayer};
+use crate::health::Damage;
+use crate::helpers::tiled::{
+    CollisionMap, CurrentMap, MapLoaded, RegisterTiledObject, TiledObject,
+};
+use crate::physics::{
+    move_colliders, platform_landing, sweep, AabbCollider, KinematicCollider, Velocity,
+    GROUND_PROBE,
+};
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::{ecs::system::EntityCommands, prelude::*, transform::TransformSystem};
+
+/// Object type of moving platforms
+pub const PLATFORM_TYPE: &str = "platform";
+/// The AnimationResource animation platforms are drawn with
+pub const PLATFORM_ANIMATION: &str = "platform";
+/// How fast platforms move when their object doesn't say, in world units a second
+pub const DEFAULT_PLATFORM_SPEED: f32 = 40.0;
+/// How much crushing platforms damage what they crush, when their object doesn't say
+pub const CRUSH_DAMAGE: i32 = 1;
+/// What platforms without a PLATFORM_ANIMATION are drawn in
+pub const PLATFORM_COLOR: Color = Color::rgb(0.55, 0.4, 0.3);
+
+/// On platform objects, until their platform spawns: the object id of their path
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlatformSpawner {
+    pub path: Option<u32>,
+}
+
+/// A platform moving along `waypoints`, where its centre is, at `speed` world units a second
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct MovingPlatform {
+    pub waypoints: Vec<Vec2>,
+    /// round the waypoints and back to the first, rather than back and forth
+    pub looped: bool,
+    pub speed: f32,
+    /// seconds waited at the ends
+    pub pause: f32,
+    /// damage done to what it carries into a ceiling, None to pass through it
+    pub crushes: Option<i32>,
+}
+
+impl MovingPlatform {
+    pub fn new(waypoints: Vec<Vec2>, looped: bool, speed: f32) -> Self {
+        MovingPlatform {
+            waypoints,
+            looped,
+            speed,
+            pause: 0.0,
+            crushes: None,
+        }
+    }
+
+    pub fn with_pause(mut self, pause: f32) -> Self {
+        self.pause = pause;
+        self
+    }
+
+    pub fn with_crushing(mut self, damage: i32) -> Self {
+        self.crushes = Some(damage);
+        self
+    }
+
+    /// The stretches between the waypoints, the one back to the first included when looped
+    fn segments(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let closing = match (self.looped, self.waypoints.first(), self.waypoints.last()) {
+            (true, Some(first), Some(last)) => Some((*last, *first)),
+            _ => None,
+        };
+        self.waypoints
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain(closing)
+    }
+
+    /// How long the path is, end to end or round
+    pub fn length(&self) -> f32 {
+        self.segments().map(|(from, to)| from.distance(to)).sum()
+    }
+
+    /// Where the platform is `seconds` after it started out at the first waypoint
+    pub fn position_at(&self, seconds: f64) -> Vec2 {
+        let Some(first) = self.waypoints.first() else {
+            return Vec2::ZERO;
+        };
+        let length = self.length();
+        if length <= 0.0 || self.speed <= 0.0 {
+            return *first;
+        }
+        let speed = self.speed as f64;
+        let travel = length as f64 / speed;
+        let pause = self.pause.max(0.0) as f64;
+        let distance = if self.looped {
+            seconds.rem_euclid(travel + pause).min(travel) * speed
+        } else {
+            let phase = seconds.rem_euclid(2.0 * (travel + pause));
+            if phase < travel {
+                phase * speed
+            } else if phase < travel + pause {
+                length as f64
+            } else {
+                ((2.0 * travel + pause - phase) * speed).max(0.0)
+            }
+        };
+        self.point_at(distance as f32)
+    }
+
+    /// The point `distance` along the path
+    fn point_at(&self, mut distance: f32) -> Vec2 {
+        for (from, to) in self.segments() {
+            let length = from.distance(to);
+            if distance <= length {
+                return from.lerp(to, if length > 0.0 { distance / length } else { 0.0 });
+            }
+            distance -= length;
+        }
+        let end = if self.looped {
+            self.waypoints.first()
+        } else {
+            self.waypoints.last()
+        };
+        end.copied().unwrap_or_default()
+    }
+}
+
+///
+/// PlatformClock
+///
+/// Fixed-step seconds of gameplay since the current map loaded, which platforms move by
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PlatformClock {
+    seconds: f64,
+}
+
+impl PlatformClock {
+    pub fn seconds(&self) -> f64 {
+        self.seconds
+    }
+}
+
+/// Moving platforms from Tiled, and carrying what stands on them
+pub struct PlatformsPlugin;
+
+impl Plugin for PlatformsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlatformClock>()
+            .init_resource::<CurrentMap>()
+            .add_event::<MapLoaded>()
+            .add_event::<Damage>()
+            .register_tiled_object(PLATFORM_TYPE, spawn_platform_object)
+            .add_systems(Update, reset_platform_clock)
+            .add_systems(
+                FixedUpdate,
+                (tick_platform_clock, move_platforms)
+                    .chain()
+                    .in_set(FixedSet::Physics)
+                    .before(move_colliders)
+                    .in_set(GameplaySet),
+            )
+            .add_systems(
+                PostUpdate,
+                // where the objects and their paths are in the world
+                spawn_platforms.after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+fn spawn_platform_object(entity: &mut EntityCommands, object: &TiledObject) {
+    entity.insert(PlatformSpawner {
+        path: object.get_object("path"),
+    });
+}
+
+///
+/// reset_platform_clock: Bevy system
+///
+/// Starts the PlatformClock over as a map loads
+pub fn reset_platform_clock(mut loaded: EventReader<MapLoaded>, mut clock: ResMut<PlatformClock>) {
+    if loaded.read().count() > 0 {
+        *clock = PlatformClock::default();
+    }
+}
+
+///
+/// tick_platform_clock: Bevy system
+///
+/// Moves the PlatformClock on by the step
+pub fn tick_platform_clock(time: Res<Time>, mut clock: ResMut<PlatformClock>) {
+    clock.seconds += time.delta_seconds_f64();
+}
+
+///
+/// spawn_platforms: Bevy system
+///
+/// Spawns the platforms of new "platform" objects, under the current map
+pub fn spawn_platforms(
+    mut commands: Commands,
+    clock: Res<PlatformClock>,
+    current: Res<CurrentMap>,
+    animations: Option<Res<AnimationResource>>,
+    spawner_query: Query<
+        (&PlatformSpawner, &TiledObject, &GlobalTransform),
+        Added<PlatformSpawner>,
+    >,
+    object_query: Query<(&TiledObject, &GlobalTransform)>,
+    map_query: Query<&GlobalTransform>,
+) {
+    for (spawner, object, transform) in spawner_query.iter() {
+        let map = current.entity().filter(|map| map_query.contains(*map));
+        // from the world to under the map
+        let to_map = map
+            .and_then(|map| map_query.get(map).ok())
+            .map_or(GlobalTransform::IDENTITY, |map| {
+                GlobalTransform::from(map.affine().inverse())
+            });
+        let start = (to_map * *transform).translation().truncate();
+
+        let path = spawner.path.and_then(|id| {
+            let path = object_query.iter().find(|(path, _)| path.id == id);
+            let patrol =
+                path.and_then(|(path, transform)| patrol_from_path(path, &(to_map * *transform)));
+            if patrol.is_none() {
+                warn!(
+                    "The path of the platform {} ({}) isn't a polyline or polygon",
+                    object.name, object.id
+                );
+            }
+            patrol
+        });
+        let (waypoints, looped) =
+            path.map_or((vec![start], false), |path| (path.waypoints, path.looped));
+        let mut platform = MovingPlatform::new(
+            waypoints,
+            looped,
+            object.get_float("speed").unwrap_or(DEFAULT_PLATFORM_SPEED),
+        )
+        .with_pause(object.get_float("pause").unwrap_or(0.0));
+        if object.get_bool("crushes").unwrap_or(false) {
+            platform = platform.with_crushing(object.get_int("damage").unwrap_or(CRUSH_DAMAGE));
+        }
+
+        let size = object.shape.size();
+        let position = platform
+            .position_at(clock.seconds())
+            .extend(SpriteLayer::Actors.z());
+        let animation = animations
+            .as_ref()
+            .and_then(|animations| animations.get(PLATFORM_ANIMATION));
+        let mut entity = commands.spawn((
+            KinematicCollider::new(size / 2.0),
+            SpriteBundle {
+                sprite: Sprite {
+                    color: match animation {
+                        Some(_) => Color::WHITE,
+                        None => PLATFORM_COLOR,
+                    },
+                    custom_size: Some(size),
+                    ..default()
+                },
+                transform: Transform::from_translation(position),
+                ..default()
+            },
+            Name::new(format!("platform {}", object.id)),
+            platform,
+        ));
+        if let Some(animation) = animation {
+            entity.insert((
+                TextureAtlas {
+                    layout: animation.atlas().clone(),
+                    index: animation.frame(),
+                },
+                animation.texture().clone(),
+                animation,
+            ));
+        }
+        if let Some(map) = map {
+            entity.set_parent(map);
+        }
+    }
+}
+
+///
+/// move_platforms: Bevy system
+///
+/// Moves platforms to where the PlatformClock says they are, carrying what stands on them as far
+/// as the walls let it, and crushing what crushing ones carry into a ceiling
+#[allow(clippy::type_complexity)]
+pub fn move_platforms(
+    clock: Res<PlatformClock>,
+    map: Option<Res<CollisionMap>>,
+    mut platform_query: Query<
+        (Entity, &MovingPlatform, &KinematicCollider, &mut Transform),
+        Without<AabbCollider>,
+    >,
+    mut rider_query: Query<(Entity, &AabbCollider, &Velocity, &mut Transform)>,
+    mut damage: EventWriter<Damage>,
+) {
+    for (entity, platform, collider, mut transform) in platform_query.iter_mut() {
+        let from = transform.translation.truncate();
+        let to = platform.position_at(clock.seconds());
+        if from == to {
+            continue;
+        }
+        transform.translation = to.extend(transform.translation.z);
+        let (before, after) = (collider.rect(from), collider.rect(to));
+
+        for (rider, rider_collider, velocity, mut rider_transform) in rider_query.iter_mut() {
+            let mut position = rider_transform.translation.truncate();
+            let rect = rider_collider.rect(position);
+            let riding =
+                velocity.0.y <= 0.0 && platform_landing(&[before], rect, GROUND_PROBE).is_some();
+            if !riding {
+                continue;
+            }
+            // onto its new top, whatever it sank or rose by
+            let motion = Vec2::new(to.x - from.x, after.max.y - rect.min.y);
+            let normals = match map.as_deref() {
+                Some(map) if !map.overlaps(rect) => {
+                    sweep(map, &[], rider_collider, &mut position, motion)
+                }
+                _ => {
+                    position += motion;
+                    [None, None]
+                }
+            };
+            rider_transform.translation = position.extend(rider_transform.translation.z);
+            let into_ceiling = normals[1].is_some_and(|normal| normal.y < 0.0);
+            if let (true, Some(amount)) = (into_ceiling, platform.crushes) {
+                damage.send(Damage::new(rider, amount).from_source(entity));
+            }
+        }
+    }
+}
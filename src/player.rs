@@ -0,0 +1,258 @@
+//! The player: a sprite walking where the movement actions point, at MovementStats::speed
+//! whichever way, diagonals included. Movement reads FixedActionState, so replays drive it too,
+//! and goes through its Velocity, which stops with the game when it's paused and slows with the
+//! TimeScale. Its feet are an AabbCollider, stopping it at walls.
+//!
+//! The sprite plays the PLAYER_IDLE_ANIMATION and PLAYER_WALK_ANIMATION clips of the
+//! AnimationResource as it stands and walks, facing the way it last moved: flipped when that's
+//! left. The player is PlacedAtSpawn, so maps put it on their spawn point, and the camera
+//! follows it from when it spawns.
+//!
+//! A player is spawned on entering InGame if there isn't one, and despawned on going back to
+//! the main menu. On side-view maps it moves as a platformer instead, see the platformer module.
+
+use crate::gfx::{
+    apply_animation_states, AnimationController, AnimationResource, CameraTarget, SpriteLayer,
+};
+use crate::health::{Invulnerability, PLAYER_INVULNERABILITY};
+use crate::helpers::tiled::PlacedAtSpawn;
+use crate::input::{ActionAxis, FixedActionState};
+use crate::physics::{AabbCollider, Velocity};
+use crate::platformer::{PlatformerController, FALL, JUMP, LAND};
+use crate::state::{AppState, GameplaySet};
+use crate::timestep::FixedSet;
+use bevy::prelude::*;
+
+/// The AnimationResource animation played while the player stands still
+pub const PLAYER_IDLE_ANIMATION: &str = "player_idle";
+/// The AnimationResource animation played while the player walks
+pub const PLAYER_WALK_ANIMATION: &str = "player_walk";
+/// The AnimationResource animations played going up, coming down and landing on side-view maps.
+/// The player does without them.
+pub const PLAYER_JUMP_ANIMATION: &str = "player_jump";
+pub const PLAYER_FALL_ANIMATION: &str = "player_fall";
+pub const PLAYER_LAND_ANIMATION: &str = "player_land";
+/// The player's AnimationController states
+pub const IDLE: &str = "idle";
+pub const WALK: &str = "walk";
+/// The player's speed unless its MovementStats say otherwise, in world units a second
+pub const PLAYER_SPEED: f32 = 80.0;
+
+/// The box around the player's feet that collides with walls
+pub const PLAYER_COLLIDER: AabbCollider = AabbCollider {
+    half_extents: Vec2::new(5.0, 3.0),
+    offset: Vec2::new(0.0, -5.0),
+};
+
+/// Marks the player
+#[derive(Component, Debug, Default)]
+pub struct Player;
+
+/// How a walker moves
+/// * speed: in world units a second
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct MovementStats {
+    pub speed: f32,
+}
+
+impl Default for MovementStats {
+    fn default() -> Self {
+        MovementStats {
+            speed: PLAYER_SPEED,
+        }
+    }
+}
+
+/// The way a walker last moved
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Facing {
+    Left,
+    Right,
+    Up,
+    #[default]
+    Down,
+}
+
+impl Facing {
+    /// The way of `direction`, left or right when it's as much sideways as up or down. None when
+    /// it's zero.
+    pub fn from_direction(direction: Vec2) -> Option<Self> {
+        if direction == Vec2::ZERO {
+            None
+        } else if direction.x.abs() >= direction.y.abs() {
+            Some(if direction.x < 0.0 {
+                Facing::Left
+            } else {
+                Facing::Right
+            })
+        } else if direction.y < 0.0 {
+            Some(Facing::Down)
+        } else {
+            Some(Facing::Up)
+        }
+    }
+}
+
+/// Spawns the player at `position`, with the idle and walk clips of `animations` it has. Without
+/// them it's a sprite of the default image.
+pub fn spawn_player(
+    commands: &mut Commands,
+    animations: &AnimationResource,
+    position: Vec2,
+) -> Entity {
+    let mut controller = AnimationController::new();
+    for (state, name) in [(IDLE, PLAYER_IDLE_ANIMATION), (WALK, PLAYER_WALK_ANIMATION)] {
+        match animations.get(name) {
+            Some(clip) => controller = controller.with_clip(state, clip),
+            None => warn!("The player has no {} animation", name),
+        }
+    }
+    for (state, name) in [
+        (JUMP, PLAYER_JUMP_ANIMATION),
+        (FALL, PLAYER_FALL_ANIMATION),
+        (LAND, PLAYER_LAND_ANIMATION),
+    ] {
+        if let Some(clip) = animations.get(name) {
+            controller = controller.with_clip(state, clip);
+        }
+    }
+    let idle = animations.get(PLAYER_IDLE_ANIMATION);
+    commands
+        .spawn((
+            Player,
+            PlacedAtSpawn,
+            MovementStats::default(),
+            Velocity::default(),
+            PLAYER_COLLIDER,
+            Invulnerability::new(PLAYER_INVULNERABILITY),
+            Facing::default(),
+            controller,
+            SpriteSheetBundle {
+                texture: idle
+                    .as_ref()
+                    .map(|idle| idle.texture().clone())
+                    .unwrap_or_default(),
+                atlas: TextureAtlas {
+                    layout: idle
+                        .as_ref()
+                        .map(|idle| idle.atlas().clone())
+                        .unwrap_or_default(),
+                    index: idle.as_ref().map_or(0, |idle| idle.frame()),
+                },
+                transform: Transform::from_translation(position.extend(SpriteLayer::Actors.z())),
+                ..default()
+            },
+        ))
+        .id()
+}
+
+/// Spawning the player, moving it and switching its animations
+pub struct PlayerPlugin;
+
+impl Plugin for PlayerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraTarget>()
+            .add_systems(OnEnter(AppState::InGame), spawn_missing_player)
+            .add_systems(OnEnter(AppState::MainMenu), despawn_player)
+            .add_systems(Update, follow_spawned_player)
+            .add_systems(
+                FixedUpdate,
+                (
+                    move_player.in_set(FixedSet::Gameplay),
+                    animate_walkers
+                        .in_set(FixedSet::PostPhysics)
+                        .before(apply_animation_states),
+                )
+                    .in_set(GameplaySet),
+            );
+    }
+}
+
+/// Spawns the player at the origin unless there's one, for the map to put on its spawn point
+fn spawn_missing_player(
+    mut commands: Commands,
+    animations: Option<Res<AnimationResource>>,
+    player_query: Query<(), With<Player>>,
+) {
+    if !player_query.is_empty() {
+        return;
+    }
+    let empty = AnimationResource::new();
+    let animations = animations.as_deref().unwrap_or(&empty);
+    spawn_player(&mut commands, animations, Vec2::ZERO);
+}
+
+fn despawn_player(mut commands: Commands, player_query: Query<Entity, With<Player>>) {
+    for player in player_query.iter() {
+        commands.entity(player).despawn_recursive();
+    }
+}
+
+///
+/// follow_spawned_player: Bevy system
+///
+/// Points the camera at a player that just spawned
+pub fn follow_spawned_player(
+    mut target: ResMut<CameraTarget>,
+    player_query: Query<Entity, Added<Player>>,
+) {
+    if let Some(player) = player_query.iter().last() {
+        target.0 = Some(player);
+    }
+}
+
+type TopDownPlayer = (With<Player>, Without<PlatformerController>);
+
+///
+/// move_player: Bevy system
+///
+/// Sets the player's Velocity from the movement axes, as fast diagonally as straight, unless
+/// it's a platformer
+pub fn move_player(
+    actions: Res<FixedActionState>,
+    mut player_query: Query<(&MovementStats, &mut Velocity), TopDownPlayer>,
+) {
+    let direction = Vec2::new(
+        actions.axis(ActionAxis::MoveX),
+        actions.axis(ActionAxis::MoveY),
+    )
+    .normalize_or_zero();
+    for (stats, mut velocity) in player_query.iter_mut() {
+        let wanted = direction * stats.speed;
+        if velocity.0 != wanted {
+            velocity.0 = wanted;
+        }
+    }
+}
+
+///
+/// animate_walkers: Bevy system
+///
+/// Plays the walk clip while moving and the idle one while not, facing the way of the Velocity
+pub fn animate_walkers(
+    mut walker_query: Query<
+        (
+            &Velocity,
+            &mut Facing,
+            &mut AnimationController,
+            &mut Sprite,
+        ),
+        Without<PlatformerController>,
+    >,
+) {
+    for (velocity, mut facing, mut controller, mut sprite) in walker_query.iter_mut() {
+        let state = if velocity.0 == Vec2::ZERO { IDLE } else { WALK };
+        if controller.state() != state {
+            controller.set_state(state);
+        }
+        if let Some(moved) = Facing::from_direction(velocity.0) {
+            if *facing != moved {
+                *facing = moved;
+            }
+        }
+        let flipped = *facing == Facing::Left;
+        if sprite.flip_x != flipped {
+            sprite.flip_x = flipped;
+        }
+    }
+}
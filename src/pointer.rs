@@ -0,0 +1,258 @@
+//! Pointer lock, for aiming with relative mouse movement. Send RequestPointerLock from a system
+//! handling a click or key press, as browsers only lock the pointer in answer to one, and
+//! ReleasePointerLock when done. On the web the pointer is locked to the canvas; natively winit
+//! grabs the cursor, locking it where the platform can and confining it to the window where it
+//! can't, so aiming code is the same on both.
+//!
+//! While the pointer is locked the cursor has no position: CursorWorldPosition is None, and
+//! PointerDelta says how far the mouse moved each frame instead. PointerLock follows what the
+//! browser or the window actually did, checked every frame, as the player can leave pointer lock
+//! with Escape at any time.
+
+use crate::gfx::MainCamera;
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::window::CursorGrabMode;
+use bevy::{
+    input::{mouse::MouseMotion, InputSystem},
+    prelude::*,
+    window::PrimaryWindow,
+};
+
+/// How the cursor is grabbed natively. Windows can't lock it in place, only keep it in the window.
+#[cfg(not(target_arch = "wasm32"))]
+pub const NATIVE_GRAB_MODE: CursorGrabMode = if cfg!(target_os = "windows") {
+    CursorGrabMode::Confined
+} else {
+    CursorGrabMode::Locked
+};
+
+///
+/// PointerLock
+///
+/// Whether the pointer is locked
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PointerLock {
+    locked: bool,
+}
+
+impl PointerLock {
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+/// Locks the pointer. Send it while handling a click or key press, or the browser refuses.
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct RequestPointerLock;
+
+/// Unlocks the pointer. It wins over a RequestPointerLock sent in the same frame.
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct ReleasePointerLock;
+
+///
+/// PointerDelta
+///
+/// How far the mouse moved this frame while the pointer is locked, in the units winit reports
+/// (about physical pixels, y down). Zero while it isn't.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct PointerDelta(pub Vec2);
+
+///
+/// CursorWorldPosition
+///
+/// Where the cursor is in the world, as the MainCamera sees it. None while the pointer is locked,
+/// when the cursor is outside the window, or without a camera.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct CursorWorldPosition(pub Option<Vec2>);
+
+/// PointerLock, its events, PointerDelta and CursorWorldPosition
+pub struct PointerPlugin;
+
+impl Plugin for PointerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PointerLock>()
+            .init_resource::<PointerDelta>()
+            .init_resource::<CursorWorldPosition>()
+            .add_event::<RequestPointerLock>()
+            .add_event::<ReleasePointerLock>()
+            // also added by bevy's InputPlugin
+            .add_event::<MouseMotion>()
+            .add_systems(
+                PreUpdate,
+                (
+                    sync_pointer_lock,
+                    (track_pointer_delta, update_cursor_world_position),
+                )
+                    .chain()
+                    .after(InputSystem),
+            )
+            .add_systems(Update, apply_pointer_lock);
+    }
+}
+
+///
+/// apply_pointer_lock: Bevy system
+///
+/// Handles RequestPointerLock and ReleasePointerLock, asking the browser on the web
+#[cfg(target_arch = "wasm32")]
+pub fn apply_pointer_lock(
+    mut requests: EventReader<RequestPointerLock>,
+    mut releases: EventReader<ReleasePointerLock>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let requested = requests.read().count() > 0;
+    if releases.read().count() > 0 {
+        page::exit_lock();
+    } else if requested {
+        let selector = window_query
+            .get_single()
+            .ok()
+            .and_then(|window| window.canvas.as_deref());
+        page::request_lock(selector);
+    }
+}
+
+///
+/// apply_pointer_lock: Bevy system
+///
+/// Handles RequestPointerLock and ReleasePointerLock, grabbing and hiding the cursor natively
+#[cfg(not(target_arch = "wasm32"))]
+pub fn apply_pointer_lock(
+    mut requests: EventReader<RequestPointerLock>,
+    mut releases: EventReader<ReleasePointerLock>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let requested = requests.read().count() > 0;
+    let grab_mode = if releases.read().count() > 0 {
+        CursorGrabMode::None
+    } else if requested {
+        NATIVE_GRAB_MODE
+    } else {
+        return;
+    };
+    for mut window in window_query.iter_mut() {
+        window.cursor.grab_mode = grab_mode;
+        window.cursor.visible = grab_mode == CursorGrabMode::None;
+    }
+}
+
+///
+/// sync_pointer_lock: Bevy system
+///
+/// Keeps PointerLock what the browser says
+#[cfg(target_arch = "wasm32")]
+pub fn sync_pointer_lock(
+    mut lock: ResMut<PointerLock>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let selector = window_query
+        .get_single()
+        .ok()
+        .and_then(|window| window.canvas.as_deref());
+    set_locked(&mut lock, page::is_locked(selector));
+}
+
+///
+/// sync_pointer_lock: Bevy system
+///
+/// Keeps PointerLock what the window's cursor grab is natively
+#[cfg(not(target_arch = "wasm32"))]
+pub fn sync_pointer_lock(
+    mut lock: ResMut<PointerLock>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+) {
+    let locked = window_query
+        .get_single()
+        .is_ok_and(|window| window.cursor.grab_mode != CursorGrabMode::None);
+    set_locked(&mut lock, locked);
+}
+
+fn set_locked(lock: &mut ResMut<PointerLock>, locked: bool) {
+    if lock.locked != locked {
+        lock.locked = locked;
+        debug!("Pointer {}", if locked { "locked" } else { "unlocked" });
+    }
+}
+
+///
+/// track_pointer_delta: Bevy system
+///
+/// Sums this frame's mouse motion into PointerDelta while the pointer is locked
+pub fn track_pointer_delta(
+    mut motion: EventReader<MouseMotion>,
+    lock: Res<PointerLock>,
+    mut delta: ResMut<PointerDelta>,
+) {
+    let moved: Vec2 = motion.read().map(|motion| motion.delta).sum();
+    let moved = if lock.locked { moved } else { Vec2::ZERO };
+    if delta.0 != moved {
+        delta.0 = moved;
+    }
+}
+
+///
+/// update_cursor_world_position: Bevy system
+///
+/// Keeps CursorWorldPosition under the cursor, None while the pointer is locked
+pub fn update_cursor_world_position(
+    lock: Res<PointerLock>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut cursor: ResMut<CursorWorldPosition>,
+) {
+    let position = if lock.locked {
+        None
+    } else {
+        window_query
+            .get_single()
+            .ok()
+            .and_then(|window| window.cursor_position())
+            .zip(camera_query.get_single().ok())
+            .and_then(|(position, (camera, transform))| {
+                camera.viewport_to_world_2d(transform, position)
+            })
+    };
+    if cursor.0 != position {
+        cursor.0 = position;
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod page {
+    use crate::utils::call_method;
+    use bevy::prelude::*;
+
+    fn document() -> Option<web_sys::Document> {
+        web_sys::window()?.document()
+    }
+
+    /// Whether the pointer is locked to the canvas matching `selector`, or the first canvas
+    pub(super) fn is_locked(selector: Option<&str>) -> bool {
+        document()
+            .and_then(|document| document.pointer_lock_element())
+            .map_or(false, |element| {
+                element
+                    .matches(selector.unwrap_or("canvas"))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Asks the browser to lock the pointer to the canvas matching `selector`, or the first canvas
+    pub(super) fn request_lock(selector: Option<&str>) {
+        match document()
+            .and_then(|document| document.query_selector(selector.unwrap_or("canvas")).ok())
+            .flatten()
+        {
+            Some(canvas) => call_method(&canvas, "requestPointerLock"),
+            None => warn!("There's no canvas to lock the pointer to"),
+        }
+    }
+
+    pub(super) fn exit_lock() {
+        if let Some(document) = document() {
+            if document.pointer_lock_element().is_some() {
+                call_method(&document, "exitPointerLock");
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Entity pools for the short-lived entities spawned in bursts: hit particles, floating text and
+//! one-shot sounds. Spawning and despawning dozens of them a second churns entity ids and archetype
+//! moves and, on the web, memory; a Pool hides finished entities instead and hands them out
+//! again.
+//!
+//! Each kind of pooled entity is a PoolKind component with a Pool of its own. Spawn with
+//! `Pool::spawn_pooled` and give the entity back with `Pool::release` once it's done, where
+//! `despawn` would have been. Pools are emptied when a map is unloaded, so a busy level's entities
+//! don't stay around for the next one.
+
+use crate::gfx::{BurstParticle, FloatingText};
+use crate::helpers::tiled::MapUnloaded;
+use crate::sound::OneShotSfx;
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use std::marker::PhantomData;
+
+/// How many free entities a pool keeps by default, releasing more despawns them
+pub const DEFAULT_POOL_SIZE: usize = 64;
+
+/// A kind of pooled entity: the component marking it, and how to make a released entity ready
+/// to be spawned again
+pub trait PoolKind: Component + Default {
+    /// Name shown in the diagnostics overlay
+    const NAME: &'static str;
+
+    /// Removes what the entity was given when it was spawned, so the next spawn_pooled starts
+    /// from a clean entity. The kind's component is reset to its default.
+    fn reset(entity: &mut EntityCommands);
+}
+
+/// Marks a pooled entity that's free, hidden until it's spawned again
+#[derive(Component, Debug, Default)]
+pub struct InPool;
+
+///
+/// PoolStats
+///
+/// What a pool has done since the game started
+/// * spawned: new entities spawned, because the pool was empty
+/// * reused: free entities handed out again
+/// * released: entities given back to the pool
+/// * despawned: entities despawned instead, because the pool was full or drained
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    pub spawned: u64,
+    pub reused: u64,
+    pub released: u64,
+    pub despawned: u64,
+}
+
+impl PoolStats {
+    /// The share of spawns that reused an entity, None before the first
+    pub fn reuse_rate(&self) -> Option<f32> {
+        let total = self.spawned + self.reused;
+        (total > 0).then(|| self.reused as f32 / total as f32)
+    }
+}
+
+///
+/// Pool
+///
+/// The free entities of kind K, up to max_size of them
+#[derive(Resource, Debug)]
+pub struct Pool<K: PoolKind> {
+    pub max_size: usize,
+    free: Vec<Entity>,
+    stats: PoolStats,
+    kind: PhantomData<K>,
+}
+
+impl<K: PoolKind> Default for Pool<K> {
+    fn default() -> Self {
+        Pool::with_max_size(DEFAULT_POOL_SIZE)
+    }
+}
+
+impl<K: PoolKind> Pool<K> {
+    /// A pool keeping at most `max_size` free entities. Insert it before PoolPlugin is added to
+    /// size a kind's pool differently.
+    pub fn with_max_size(max_size: usize) -> Self {
+        Pool {
+            max_size,
+            free: Vec::new(),
+            stats: PoolStats::default(),
+            kind: PhantomData,
+        }
+    }
+
+    /// Spawns `bundle` on a free entity, or on a new one with K when there's none, and shows it.
+    /// The bundle may hold K, to start it from other than its default.
+    pub fn spawn_pooled(&mut self, commands: &mut Commands, bundle: impl Bundle) -> Entity {
+        while let Some(entity) = self.free.pop() {
+            // despawned by something else while it was free
+            let Some(mut entity) = commands.get_entity(entity) else {
+                continue;
+            };
+            entity
+                .remove::<InPool>()
+                .insert(Visibility::Inherited)
+                .insert(bundle);
+            self.stats.reused += 1;
+            return entity.id();
+        }
+        self.stats.spawned += 1;
+        commands.spawn(K::default()).insert(bundle).id()
+    }
+
+    /// Gives `entity` back to the pool, hiding and resetting it, or despawns it if the pool is
+    /// full. It should be one spawn_pooled gave.
+    pub fn release(&mut self, commands: &mut Commands, entity: Entity) {
+        let Some(mut entity_commands) = commands.get_entity(entity) else {
+            return;
+        };
+        if self.free.len() >= self.max_size {
+            entity_commands.despawn();
+            self.stats.despawned += 1;
+            return;
+        }
+        K::reset(&mut entity_commands);
+        entity_commands.insert((K::default(), Visibility::Hidden, InPool));
+        self.free.push(entity);
+        self.stats.released += 1;
+    }
+
+    /// Despawns every free entity
+    pub fn drain(&mut self, commands: &mut Commands) {
+        for entity in self.free.drain(..) {
+            if let Some(entity) = commands.get_entity(entity) {
+                entity.despawn_recursive();
+                self.stats.despawned += 1;
+            }
+        }
+    }
+
+    /// Number of free entities
+    pub fn free(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        self.stats
+    }
+}
+
+/// Pools for BurstParticle, FloatingText and OneShotSfx. Without it they're spawned and
+/// despawned as they always were.
+pub struct PoolPlugin;
+
+impl Plugin for PoolPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pool<BurstParticle>>()
+            .init_resource::<Pool<FloatingText>>()
+            .init_resource::<Pool<OneShotSfx>>()
+            // also added by TiledMapPlugin, but pools work without it
+            .add_event::<MapUnloaded>()
+            .add_systems(
+                Last,
+                (
+                    drain_pool_on_unload::<BurstParticle>,
+                    drain_pool_on_unload::<FloatingText>,
+                    drain_pool_on_unload::<OneShotSfx>,
+                )
+                    .run_if(on_event::<MapUnloaded>()),
+            );
+    }
+}
+
+///
+/// drain_pool_on_unload: Bevy system
+///
+/// Empties the pool of K when a map is unloaded
+pub fn drain_pool_on_unload<K: PoolKind>(mut commands: Commands, mut pool: ResMut<Pool<K>>) {
+    pool.drain(&mut commands);
+}
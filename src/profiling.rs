@@ -0,0 +1,180 @@
+//! How long the heavy systems take, to find what's slow on low-end laptops. With the `profiling`
+//! feature, systems timed with `let _span = profiling::span("name");` are averaged per frame
+//! over the last TIMING_WINDOW frames into SystemTimings, logged as a table every
+//! TIMING_LOG_INTERVAL seconds (to the console on the web) and listed by the diagnostics
+//! overlay. The spans are tracing spans too, for native profilers.
+//!
+//! Without the feature span() does nothing and compiles away.
+
+use bevy::prelude::*;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+#[cfg(feature = "profiling")]
+use std::sync::Mutex;
+
+/// Number of frames the averages are over
+pub const TIMING_WINDOW: usize = 120;
+/// Seconds of real time between logged tables
+pub const TIMING_LOG_INTERVAL: f32 = 10.0;
+
+/// Spans closed since the last frame's collect_system_timings, in milliseconds
+#[cfg(feature = "profiling")]
+static CLOSED_SPANS: Mutex<Vec<(&'static str, f32)>> = Mutex::new(Vec::new());
+
+/// Times the scope it lives in, until it's dropped
+#[must_use = "the span ends as soon as it's dropped"]
+pub struct Span {
+    #[cfg(feature = "profiling")]
+    name: &'static str,
+    #[cfg(feature = "profiling")]
+    start: bevy::utils::Instant,
+    #[cfg(feature = "profiling")]
+    _span: bevy::utils::tracing::span::EnteredSpan,
+}
+
+/// A Span called `name`, usually the system's name
+#[inline]
+pub fn span(name: &'static str) -> Span {
+    #[cfg(feature = "profiling")]
+    {
+        Span {
+            name,
+            start: bevy::utils::Instant::now(),
+            _span: bevy::log::info_span!("timed", name).entered(),
+        }
+    }
+    #[cfg(not(feature = "profiling"))]
+    {
+        let _ = name;
+        Span {}
+    }
+}
+
+#[cfg(feature = "profiling")]
+impl Drop for Span {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed().as_secs_f32() * 1000.0;
+        if let Ok(mut closed) = CLOSED_SPANS.lock() {
+            closed.push((self.name, elapsed));
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Timing {
+    // milliseconds in each of the last frames the system ran in
+    frames: VecDeque<f32>,
+}
+
+///
+/// SystemTimings
+///
+/// The time the timed systems took per frame they ran in, over the last TIMING_WINDOW frames
+#[derive(Resource, Debug, Default, Clone)]
+pub struct SystemTimings {
+    systems: BTreeMap<&'static str, Timing>,
+}
+
+impl SystemTimings {
+    /// Adds a frame's spans, those of a system that ran more than once adding up
+    pub fn add_frame(&mut self, spans: impl IntoIterator<Item = (&'static str, f32)>) {
+        let mut frame: BTreeMap<&'static str, f32> = BTreeMap::new();
+        for (name, elapsed) in spans {
+            *frame.entry(name).or_default() += elapsed;
+        }
+        for (name, elapsed) in frame {
+            let timing = self.systems.entry(name).or_default();
+            if timing.frames.len() == TIMING_WINDOW {
+                timing.frames.pop_front();
+            }
+            timing.frames.push_back(elapsed);
+        }
+    }
+
+    /// Average milliseconds per frame of the system called `name`
+    pub fn average(&self, name: &str) -> Option<f32> {
+        let timing = self.systems.get(name)?;
+        let total: f32 = timing.frames.iter().sum();
+        Some(total / timing.frames.len().max(1) as f32)
+    }
+
+    /// Slowest frame of the system called `name`, in milliseconds
+    pub fn max(&self, name: &str) -> Option<f32> {
+        let timing = self.systems.get(name)?;
+        timing.frames.iter().copied().reduce(f32::max)
+    }
+
+    /// The `count` systems taking the longest on average, slowest first, with their averages
+    pub fn top(&self, count: usize) -> Vec<(&'static str, f32)> {
+        let mut averages: Vec<(&'static str, f32)> = self
+            .systems
+            .keys()
+            .filter_map(|name| Some((*name, self.average(name)?)))
+            .collect();
+        averages.sort_by(|a, b| b.1.total_cmp(&a.1));
+        averages.truncate(count);
+        averages
+    }
+
+    /// A table of every timed system, slowest first
+    pub fn table(&self) -> String {
+        let mut table = format!("{:<28} {:>8} {:>8}", "system", "avg ms", "max ms");
+        for (name, average) in self.top(self.systems.len()) {
+            let _ = write!(
+                table,
+                "\n{:<28} {:>8.3} {:>8.3}",
+                name,
+                average,
+                self.max(name).unwrap_or(0.0)
+            );
+        }
+        table
+    }
+}
+
+/// Collects the timing spans into SystemTimings and logs them
+#[cfg(feature = "profiling")]
+pub struct ProfilingPlugin;
+
+#[cfg(feature = "profiling")]
+impl Plugin for ProfilingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SystemTimings>()
+            .add_systems(Last, (collect_system_timings, log_system_timings).chain());
+    }
+}
+
+///
+/// collect_system_timings: Bevy system
+///
+/// Adds the spans closed this frame to SystemTimings
+#[cfg(feature = "profiling")]
+pub fn collect_system_timings(mut timings: ResMut<SystemTimings>) {
+    let closed = match CLOSED_SPANS.lock() {
+        Ok(mut closed) => std::mem::take(&mut *closed),
+        Err(_) => return,
+    };
+    timings.add_frame(closed);
+}
+
+///
+/// log_system_timings: Bevy system
+///
+/// Logs the SystemTimings table every TIMING_LOG_INTERVAL seconds
+#[cfg(feature = "profiling")]
+pub fn log_system_timings(
+    time: Res<Time<Real>>,
+    timings: Res<SystemTimings>,
+    mut since_logged: Local<f32>,
+) {
+    *since_logged += time.delta_seconds();
+    if *since_logged < TIMING_LOG_INTERVAL {
+        return;
+    }
+    *since_logged = 0.0;
+    info!(
+        "System timings over the last {} frames:\n{}",
+        TIMING_WINDOW,
+        timings.table()
+    );
+}
@@ -0,0 +1,346 @@
+//! Projectiles for ranged attacks, spawned with spawn_projectile from a ProjectileConfig: the
+//! weapon's speed, damage, sprite and so on. A projectile flies straight with a Velocity and an
+//! AabbCollider, so physics moves it and stops it at walls.
+//!
+//! Hitting a wall, it plays its impact animation and sound where it is and is gone, unless it
+//! bounces: it comes off the wall, by the HitWall normal, up to max_bounces times. Hitting an
+//! entity with Health of a Faction its own opposes sends it a Damage, from the shooter, and it's
+//! gone the same way, unless it pierces: it goes on through, hurting each entity once. It never
+//! hits its owner. Projectiles also go when their lifetime runs out, when they leave the map and
+//! when the map is unloaded.
+//!
+//! Projectiles are pooled when there's a Pool<Projectile>, so a shower of bullets doesn't churn
+//! entities.
+
+use crate::gfx::{Animation, SpawnBurst};
+use crate::health::{Damage, Dying, Faction, Health};
+use crate::helpers::tiled::{MapBounds, MapUnloaded};
+use crate::physics::{AabbCollider, HitWall, Velocity};
+use crate::pool::{InPool, Pool, PoolKind};
+use crate::sound::PlaySFX;
+use crate::state::GameplaySet;
+use crate::timestep::FixedSet;
+use bevy::{ecs::system::EntityCommands, prelude::*};
+
+/// Drawn above characters, below bursts
+const PROJECTILE_Z: f32 = 40.0;
+
+/// What a projectile looks like
+#[derive(Clone, Default)]
+pub enum ProjectileSprite {
+    /// Nothing, e.g. an invisible hitbox
+    #[default]
+    None,
+    Image(Handle<Image>),
+    /// An AnimationResource animation, which should be AnimationType::Repeat
+    Animation(Animation),
+}
+
+///
+/// ProjectileConfig
+///
+/// A kind of projectile, e.g. a weapon's
+/// * speed: in world units a second
+/// * lifetime: seconds before it's gone, having hit nothing
+/// * damage: the Damage amount it deals
+/// * knockback: how hard the Damage pushes away from the projectile, in world units a second
+/// * faction: it only hurts entities of the factions this opposes
+/// * half_extents: half the size of its AabbCollider
+/// * piercing: it goes on through who it hits
+/// * max_bounces: how many times it comes off walls before a wall stops it
+/// * impact_animation, impact_sound: the AnimationResource animation, which should be
+///   AnimationType::Despawn, and the sound played where it stops
+#[derive(Clone)]
+pub struct ProjectileConfig {
+    pub speed: f32,
+    pub lifetime: f32,
+    pub damage: i32,
+    pub knockback: f32,
+    pub faction: Faction,
+    pub half_extents: Vec2,
+    pub sprite: ProjectileSprite,
+    pub piercing: bool,
+    pub max_bounces: u32,
+    pub impact_animation: Option<String>,
+    pub impact_sound: Option<String>,
+}
+
+impl Default for ProjectileConfig {
+    fn default() -> Self {
+        ProjectileConfig {
+            speed: 160.0,
+            lifetime: 2.0,
+            damage: 1,
+            knockback: 0.0,
+            faction: Faction::Neutral,
+            half_extents: Vec2::splat(2.0),
+            sprite: ProjectileSprite::None,
+            piercing: false,
+            max_bounces: 0,
+            impact_animation: None,
+            impact_sound: None,
+        }
+    }
+}
+
+/// A projectile in flight
+/// * owner: who shot it, which it doesn't hit
+/// * velocity: where it's going, which Velocity loses on hitting a wall and it's bounced from
+/// * lifetime: seconds left before it's gone
+/// * bounces: how many more times it comes off a wall
+/// * hit: who a piercing projectile has hurt already
+#[derive(Component, Default)]
+pub struct Projectile {
+    pub owner: Option<Entity>,
+    pub faction: Faction,
+    pub damage: i32,
+    pub knockback: f32,
+    pub velocity: Vec2,
+    pub lifetime: f32,
+    pub piercing: bool,
+    pub bounces: u32,
+    pub impact_animation: Option<String>,
+    pub impact_sound: Option<String>,
+    pub hit: Vec<Entity>,
+}
+
+impl PoolKind for Projectile {
+    const NAME: &'static str = "projectiles";
+
+    fn reset(entity: &mut EntityCommands) {
+        entity.remove::<(
+            Velocity,
+            AabbCollider,
+            Sprite,
+            TextureAtlas,
+            Handle<Image>,
+            Animation,
+        )>();
+    }
+}
+
+/// Projectiles flying, hitting and going, and their pool
+pub struct ProjectilesPlugin;
+
+impl Plugin for ProjectilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pool<Projectile>>()
+            .init_resource::<MapBounds>()
+            .add_event::<Damage>()
+            .add_event::<HitWall>()
+            .add_event::<MapUnloaded>()
+            .add_event::<PlaySFX>()
+            .add_event::<SpawnBurst>()
+            .add_systems(
+                FixedUpdate,
+                update_projectiles
+                    .in_set(FixedSet::PostPhysics)
+                    .in_set(GameplaySet),
+            )
+            .add_systems(
+                Last,
+                despawn_projectiles_on_unload.run_if(on_event::<MapUnloaded>()),
+            );
+    }
+}
+
+/// Spawns a projectile like `config` at `origin`, flying towards `direction`, on a pooled entity
+/// when `pool` is given. `owner`, the shooter, isn't hit by it.
+pub fn spawn_projectile(
+    commands: &mut Commands,
+    pool: Option<&mut Pool<Projectile>>,
+    owner: Option<Entity>,
+    origin: Vec2,
+    direction: Vec2,
+    config: &ProjectileConfig,
+) -> Entity {
+    let velocity = direction.normalize_or_zero() * config.speed;
+    let projectile = Projectile {
+        owner,
+        faction: config.faction,
+        damage: config.damage,
+        knockback: config.knockback,
+        velocity,
+        lifetime: config.lifetime,
+        piercing: config.piercing,
+        bounces: config.max_bounces,
+        impact_animation: config.impact_animation.clone(),
+        impact_sound: config.impact_sound.clone(),
+        hit: Vec::new(),
+    };
+    let bundle = (
+        projectile,
+        Velocity(velocity),
+        AabbCollider::new(config.half_extents),
+        SpatialBundle::from_transform(Transform::from_translation(origin.extend(PROJECTILE_Z))),
+    );
+    let entity = match pool {
+        Some(pool) => pool.spawn_pooled(commands, bundle),
+        None => commands.spawn(bundle).id(),
+    };
+    let mut entity_commands = commands.entity(entity);
+    match &config.sprite {
+        ProjectileSprite::None => {}
+        ProjectileSprite::Image(image) => {
+            entity_commands.insert((Sprite::default(), image.clone()));
+        }
+        ProjectileSprite::Animation(animation) => {
+            entity_commands.insert((
+                Sprite::default(),
+                TextureAtlas {
+                    layout: animation.atlas().clone(),
+                    index: animation.frame(),
+                },
+                animation.texture().clone(),
+                animation.clone(),
+            ));
+        }
+    }
+    entity
+}
+
+/// Why a projectile stops
+enum Stop {
+    /// It hit something, showing its impact
+    Impact,
+    /// It ran out of time or left the map
+    Expired,
+}
+
+///
+/// update_projectiles: Bevy system
+///
+/// Bounces projectiles off the walls they hit, or stops them, hurts who they fly into and
+/// removes those that are done
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn update_projectiles(
+    mut commands: Commands,
+    time: Res<Time>,
+    bounds: Res<MapBounds>,
+    mut pool: Option<ResMut<Pool<Projectile>>>,
+    mut wall_hits: EventReader<HitWall>,
+    mut projectile_query: Query<
+        (
+            Entity,
+            &mut Projectile,
+            &mut Velocity,
+            &AabbCollider,
+            &Transform,
+        ),
+        Without<InPool>,
+    >,
+    target_query: Query<
+        (Entity, &Faction, &AabbCollider, &GlobalTransform),
+        (With<Health>, Without<Dying>, Without<Projectile>),
+    >,
+    mut damage: EventWriter<Damage>,
+    mut sfx: EventWriter<PlaySFX>,
+    mut bursts: EventWriter<SpawnBurst>,
+) {
+    let mut stopped: Vec<(Entity, Stop)> = Vec::new();
+
+    for hit in wall_hits.read() {
+        let Ok((entity, mut projectile, mut velocity, _, _)) = projectile_query.get_mut(hit.entity)
+        else {
+            continue;
+        };
+        if stopped.iter().any(|(stopped, _)| *stopped == entity) {
+            continue;
+        }
+        if projectile.bounces == 0 {
+            stopped.push((entity, Stop::Impact));
+            continue;
+        }
+        projectile.bounces -= 1;
+        let reflected =
+            projectile.velocity - 2.0 * projectile.velocity.dot(hit.normal) * hit.normal;
+        projectile.velocity = reflected;
+        velocity.0 = reflected;
+    }
+
+    let delta = time.delta_seconds();
+    for (entity, mut projectile, _, collider, transform) in projectile_query.iter_mut() {
+        if stopped.iter().any(|(stopped, _)| *stopped == entity) {
+            continue;
+        }
+        let position = transform.translation.truncate();
+        let rect = collider.rect(position);
+        for (target, faction, target_collider, target_transform) in target_query.iter() {
+            if Some(target) == projectile.owner
+                || !projectile.faction.opposes(*faction)
+                || projectile.hit.contains(&target)
+            {
+                continue;
+            }
+            let target_position = target_transform.translation().truncate();
+            if rect
+                .intersect(target_collider.rect(target_position))
+                .is_empty()
+            {
+                continue;
+            }
+            let mut event = Damage::new(target, projectile.damage)
+                .from_source(projectile.owner.unwrap_or(entity));
+            if projectile.knockback > 0.0 {
+                let away = (target_position - position)
+                    .try_normalize()
+                    .unwrap_or_else(|| projectile.velocity.normalize_or_zero());
+                event = event.with_knockback(away * projectile.knockback);
+            }
+            damage.send(event);
+            if !projectile.piercing {
+                stopped.push((entity, Stop::Impact));
+                break;
+            }
+            projectile.hit.push(target);
+        }
+        if stopped.iter().any(|(stopped, _)| *stopped == entity) {
+            continue;
+        }
+
+        projectile.lifetime -= delta;
+        let outside = !bounds.is_empty() && !bounds.contains(position);
+        if projectile.lifetime <= 0.0 || outside {
+            stopped.push((entity, Stop::Expired));
+        }
+    }
+
+    for (entity, stop) in stopped {
+        let Ok((_, projectile, _, _, transform)) = projectile_query.get(entity) else {
+            continue;
+        };
+        if let Stop::Impact = stop {
+            let position = transform.translation.truncate();
+            if let Some(animation) = &projectile.impact_animation {
+                bursts.send(SpawnBurst {
+                    animation: animation.clone(),
+                    position,
+                });
+            }
+            if let Some(sound) = &projectile.impact_sound {
+                sfx.send(PlaySFX::at(sound.clone(), position));
+            }
+        }
+        match pool.as_deref_mut() {
+            Some(pool) => pool.release(&mut commands, entity),
+            None => commands.entity(entity).despawn_recursive(),
+        }
+    }
+}
+
+///
+/// despawn_projectiles_on_unload: Bevy system
+///
+/// Despawns the projectiles in flight when a map is unloaded, and empties their pool
+pub fn despawn_projectiles_on_unload(
+    mut commands: Commands,
+    pool: Option<ResMut<Pool<Projectile>>>,
+    projectile_query: Query<Entity, (With<Projectile>, Without<InPool>)>,
+) {
+    for entity in projectile_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(mut pool) = pool {
+        pool.drain(&mut commands);
+    }
+}
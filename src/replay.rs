@@ -0,0 +1,464 @@
+//! Recording a session's input to replay it, for chasing the bugs that only happen now and then.
+//! With the `record` flag, the seed, the StartOptions and every change to FixedActionState are
+//! kept, with the tick it happened on; with `replay`, the game starts from the last recording
+//! instead and FixedActionState is fed from it at the recorded ticks, whatever keys are held.
+//!
+//! A tick is a fixed step of GameplaySet, counted by ReplayTick, so loading and pauses don't
+//! shift them. A replay only plays out like the recording if gameplay runs in those steps,
+//! reads FixedActionState rather than the keys and draws from GameRng. Maps load in their own
+//! time, so gameplay should wait for MapLoaded rather than count on a map being there at some tick.
+//!
+//! With `check=N` as well, the hash of some of the game's state (see state_hash) is recorded
+//! every N ticks, and the replay compares its own: the first mismatch ends it with
+//! ReplayDiverged, close to where the runs parted ways.
+//!
+//! The recording is written to storage under the "replay" key (localStorage on the web,
+//! settings/replay.ron natively) on SaveReplay, entering GameOver and when the app exits.
+
+use crate::helpers::tiled::PlacedAtSpawn;
+use crate::input::{read_actions, Action, FixedActionState};
+use crate::options::StartOptions;
+use crate::save::GameProgress;
+use crate::state::{AppState, GameplaySet};
+use crate::storage::{self, StorageError};
+use crate::timestep::{FixedSet, FixedStepConfig};
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+use thiserror::Error;
+
+const REPLAY_KEY: &str = "replay";
+
+/// Version of the ReplayData layout, bumped whenever a change would misread older recordings
+pub const REPLAY_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("there is no recording")]
+    NoReplay,
+    #[error("the recording is unreadable: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error(
+        "the recording is from version {found} of the replay format, this build reads {expected}"
+    )]
+    Version { found: u32, expected: u32 },
+    #[error("could not serialize the recording: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+/// An action starting or stopping being held, on the tick it did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionChange {
+    pub tick: u64,
+    pub action: Action,
+    pub pressed: bool,
+}
+
+/// The state_hash at the end of a tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateCheck {
+    pub tick: u64,
+    pub hash: u64,
+}
+
+///
+/// ReplayData
+///
+/// A recorded session
+/// * version: REPLAY_VERSION when it was recorded
+/// * seed: GameRng's seed
+/// * options: the StartOptions the session started with
+/// * fixed_hz: the rate FixedUpdate ran at
+/// * ticks: how many ticks were recorded
+/// * actions: the changes to FixedActionState, in tick order
+/// * check_every, checks: how many ticks apart the state was hashed, and the hashes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayData {
+    pub version: u32,
+    pub seed: u64,
+    pub options: StartOptions,
+    pub fixed_hz: f64,
+    pub ticks: u64,
+    pub actions: Vec<ActionChange>,
+    pub check_every: Option<u32>,
+    pub checks: Vec<StateCheck>,
+}
+
+impl ReplayData {
+    /// An empty recording of a session started with `options`, which have to hold the seed
+    pub fn new(options: &StartOptions) -> Self {
+        ReplayData {
+            version: REPLAY_VERSION,
+            seed: options.seed.unwrap_or_default(),
+            options: options.clone(),
+            fixed_hz: options
+                .fixed_hz
+                .and_then(FixedStepConfig::new)
+                .unwrap_or_default()
+                .hz,
+            ticks: 0,
+            actions: Vec::new(),
+            check_every: options.check_every,
+            checks: Vec::new(),
+        }
+    }
+
+    pub fn to_ron(&self) -> Result<String, ReplayError> {
+        Ok(ron::to_string(self)?)
+    }
+
+    /// Reads a recording of this build's REPLAY_VERSION
+    pub fn from_ron(text: &str) -> Result<Self, ReplayError> {
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(default)]
+            version: u32,
+        }
+        match ron::from_str::<Header>(text)?.version {
+            REPLAY_VERSION => Ok(ron::from_str(text)?),
+            found => Err(ReplayError::Version {
+                found,
+                expected: REPLAY_VERSION,
+            }),
+        }
+    }
+
+    /// Sets what in `options` decides how the game plays to what it was when recording: the
+    /// seed, the first map, the fixed rate and where loading goes. The page's canvas, assets and
+    /// the like stay as they are.
+    pub fn apply_to(&self, options: &mut StartOptions) {
+        options.seed = Some(self.seed);
+        options.map = self.options.map.clone();
+        options.fixed_hz = Some(self.fixed_hz);
+        options.skip_to = self.options.skip_to;
+        options.record = false;
+    }
+}
+
+/// The last recording written to storage
+pub fn read_replay() -> Result<ReplayData, ReplayError> {
+    let text = storage::load(REPLAY_KEY).ok_or(ReplayError::NoReplay)?;
+    ReplayData::from_ron(&text)
+}
+
+/// Writes `data` to storage, replacing the last recording
+pub fn write_replay(data: &ReplayData) -> Result<(), ReplayError> {
+    Ok(storage::save(REPLAY_KEY, &data.to_ron()?)?)
+}
+
+///
+/// ReplayTick
+///
+/// How many ticks gameplay has run: fixed steps of GameplaySet
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayTick(pub u64);
+
+///
+/// Replay
+///
+/// What's being recorded or replayed
+/// * Recording: the session so far
+/// * Playing: feeding `data` back, `held` being the actions held so far and next_action,
+///   next_check the next of its actions and checks to come
+/// * Finished: the replay played out, and the keys work again
+/// * Diverged: the state hashed differently from the recording at `tick`, which ended the replay
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub enum Replay {
+    #[default]
+    Off,
+    Recording(ReplayData),
+    Playing {
+        data: ReplayData,
+        held: BTreeSet<Action>,
+        next_action: usize,
+        next_check: usize,
+    },
+    Finished,
+    Diverged {
+        tick: u64,
+    },
+}
+
+impl Replay {
+    /// A replay of `data`, from its first tick
+    pub fn play(data: ReplayData) -> Self {
+        Replay::Playing {
+            data,
+            held: BTreeSet::new(),
+            next_action: 0,
+            next_check: 0,
+        }
+    }
+
+    /// What the StartOptions ask for: replaying the last recording, taking its options, or
+    /// recording, seeding at random now if no seed was given so the recording has one
+    pub fn from_options(options: &mut StartOptions) -> Self {
+        if options.replay {
+            return match read_replay() {
+                Ok(data) => {
+                    info!(
+                        "Replaying {} ticks recorded with seed {}",
+                        data.ticks, data.seed
+                    );
+                    data.apply_to(options);
+                    Replay::play(data)
+                }
+                Err(e) => {
+                    warn!("Could not replay: {}", e);
+                    Replay::Off
+                }
+            };
+        }
+        if options.record {
+            options.seed.get_or_insert_with(rand::random);
+            info!("Recording input");
+            return Replay::Recording(ReplayData::new(options));
+        }
+        Replay::Off
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self, Replay::Playing { .. })
+    }
+
+    /// The recording so far, if recording
+    pub fn recording(&self) -> Option<&ReplayData> {
+        match self {
+            Replay::Recording(data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the recording to storage, if recording
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct SaveReplay;
+
+/// Sent when a replay has played out
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayFinished {
+    pub ticks: u64,
+}
+
+/// Sent when the state hashed differently from the recording, which ends the replay
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayDiverged {
+    pub tick: u64,
+    pub expected: u64,
+    pub found: u64,
+}
+
+/// FNV-1a, the same on every platform and Rust release, unlike std's hasher
+struct StateHasher(u64);
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    // the lengths of collections, so wasm32 and 64 bit builds agree
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// The hash replays check: GameProgress but for play_time, which follows the frame rate, and
+/// where the entities placed at the spawn point are, in whatever order
+pub fn state_hash(progress: Option<&GameProgress>, positions: impl Iterator<Item = Vec3>) -> u64 {
+    let mut hasher = StateHasher(0xcbf29ce484222325);
+    if let Some(progress) = progress {
+        progress.health.hash(&mut hasher);
+        progress.max_health.hash(&mut hasher);
+        progress.inventory.hash(&mut hasher);
+        progress.flags.hash(&mut hasher);
+        progress.fired_triggers.hash(&mut hasher);
+        progress.score.hash(&mut hasher);
+    }
+    let mut positions: Vec<[u32; 3]> = positions
+        .map(|position| position.to_array().map(f32::to_bits))
+        .collect();
+    positions.sort_unstable();
+    positions.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Recording and replaying input, as Replay says
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Replay>()
+            .init_resource::<ReplayTick>()
+            // also added by InputPlugin
+            .init_resource::<FixedActionState>()
+            .add_event::<SaveReplay>()
+            .add_event::<ReplayFinished>()
+            .add_event::<ReplayDiverged>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    record_or_feed_actions
+                        .in_set(FixedSet::Input)
+                        .after(read_actions),
+                    check_replay_state.in_set(FixedSet::PostPhysics),
+                )
+                    .in_set(GameplaySet),
+            )
+            .add_systems(OnEnter(AppState::GameOver), save_when_game_over)
+            .add_systems(Last, save_replay);
+    }
+}
+
+///
+/// record_or_feed_actions: Bevy system
+///
+/// Records the tick's changes to FixedActionState, or replaces them with the recorded ones
+pub fn record_or_feed_actions(
+    mut replay: ResMut<Replay>,
+    tick: Res<ReplayTick>,
+    mut actions: ResMut<FixedActionState>,
+    mut finished: EventWriter<ReplayFinished>,
+) {
+    let tick = tick.0;
+    match &mut *replay {
+        Replay::Recording(data) => {
+            let (held, previous) = (actions.held(), actions.previous());
+            data.actions.extend(
+                previous
+                    .symmetric_difference(held)
+                    .map(|action| ActionChange {
+                        tick,
+                        action: *action,
+                        pressed: held.contains(action),
+                    }),
+            );
+            data.ticks = tick + 1;
+        }
+        Replay::Playing {
+            data,
+            held,
+            next_action,
+            ..
+        } => {
+            if tick >= data.ticks {
+                info!("The replay played out after {} ticks", data.ticks);
+                finished.send(ReplayFinished { ticks: data.ticks });
+                *replay = Replay::Finished;
+                return;
+            }
+            while let Some(change) = data
+                .actions
+                .get(*next_action)
+                .filter(|change| change.tick <= tick)
+            {
+                if change.pressed {
+                    held.insert(change.action);
+                } else {
+                    held.remove(&change.action);
+                }
+                *next_action += 1;
+            }
+            actions.set_held(held.clone());
+        }
+        _ => {}
+    }
+}
+
+///
+/// check_replay_state: Bevy system
+///
+/// Ends the tick: every check_every ticks, records the state_hash or checks it against the
+/// recording's
+pub fn check_replay_state(
+    mut replay: ResMut<Replay>,
+    mut tick: ResMut<ReplayTick>,
+    progress: Option<Res<GameProgress>>,
+    placed_query: Query<&Transform, With<PlacedAtSpawn>>,
+    mut diverged: EventWriter<ReplayDiverged>,
+) {
+    let current = tick.0;
+    tick.0 += 1;
+    let check_every = match &*replay {
+        Replay::Recording(data) | Replay::Playing { data, .. } => data.check_every,
+        _ => None,
+    };
+    // a check_every of 0 never checks
+    if check_every.is_none_or(|every| current.checked_rem(every as u64) != Some(0)) {
+        return;
+    }
+    let hash = state_hash(
+        progress.as_deref(),
+        placed_query.iter().map(|transform| transform.translation),
+    );
+
+    let mut divergence = None;
+    match &mut *replay {
+        Replay::Recording(data) => data.checks.push(StateCheck {
+            tick: current,
+            hash,
+        }),
+        Replay::Playing {
+            data, next_check, ..
+        } => {
+            while let Some(check) = data
+                .checks
+                .get(*next_check)
+                .filter(|check| check.tick <= current)
+            {
+                *next_check += 1;
+                if check.tick == current && check.hash != hash {
+                    divergence = Some(ReplayDiverged {
+                        tick: current,
+                        expected: check.hash,
+                        found: hash,
+                    });
+                }
+            }
+        }
+        _ => {}
+    }
+    if let Some(event) = divergence {
+        error!(
+            "The replay diverged from the recording at tick {}: the state hashed to {:x}, not {:x}",
+            event.tick, event.found, event.expected
+        );
+        *replay = Replay::Diverged { tick: current };
+        diverged.send(event);
+    }
+}
+
+fn save_when_game_over(mut events: EventWriter<SaveReplay>) {
+    events.send(SaveReplay);
+}
+
+///
+/// save_replay: Bevy system
+///
+/// Writes the recording to storage on SaveReplay and when the app exits
+pub fn save_replay(
+    mut saves: EventReader<SaveReplay>,
+    mut exits: EventReader<AppExit>,
+    replay: Res<Replay>,
+) {
+    let saving = saves.read().count() > 0;
+    let exiting = exits.read().count() > 0;
+    if !saving && !exiting {
+        return;
+    }
+    let Some(data) = replay.recording() else {
+        return;
+    };
+    match write_replay(data) {
+        Ok(()) => info!("Recorded {} ticks", data.ticks),
+        Err(e) => warn!("Could not save the recording: {}", e),
+    }
+}
@@ -0,0 +1,78 @@
+//! The game's randomness, from one seed so a run can be replayed: the same seed and the same
+//! input give the same SFX variations, ambient timings and drops.
+//!
+//! Gameplay code should draw from GameRng instead of `rand::thread_rng`, through a stream of its
+//! own: `game_rng.fork("loot")`, kept in a Local or a resource. Each stream only depends on the
+//! seed and its name, so a new random call in one system doesn't shift the numbers any other
+//! system gets.
+
+use bevy::prelude::*;
+use rand::{RngCore, SeedableRng};
+use rand_xoshiro::Xoshiro256PlusPlus;
+
+///
+/// GameRng
+///
+/// A seeded random number generator: use it with the `rand::Rng` methods. The resource is the
+/// root stream; fork it rather than drawing from it directly.
+#[derive(Resource, Debug, Clone)]
+pub struct GameRng {
+    seed: u64,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        GameRng {
+            seed,
+            rng: Xoshiro256PlusPlus::seed_from_u64(seed),
+        }
+    }
+
+    /// Seeded from the system's entropy, for when no seed was asked for
+    pub fn from_entropy() -> Self {
+        GameRng::new(rand::random())
+    }
+
+    /// The seed the stream was made from. Starting with the root stream's seed replays the run.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The stream called `name`, e.g. "sfx". It's the same stream whatever has been drawn from
+    /// this one, and streams can be forked in turn.
+    pub fn fork(&self, name: &str) -> GameRng {
+        GameRng::new(self.seed ^ fnv1a(name))
+    }
+}
+
+/// A stable hash of stream names: std's hasher may change between Rust releases, which would
+/// change every stream
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// The stream called `name` of the game's GameRng, or one from entropy in apps without one
+pub fn fork_or_entropy(game_rng: Option<&GameRng>, name: &str) -> GameRng {
+    game_rng.map_or_else(GameRng::from_entropy, |game_rng| game_rng.fork(name))
+}
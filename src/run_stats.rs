@@ -0,0 +1,355 @@
+//! The current run through a level: RunStats counts the score of the coins picked up and enemies
+//! defeated, the pickups collected and the time taken, on virtual time so pauses don't count.
+//! LevelComplete freezes it, for the results screen, and restarting the level or moving on to
+//! another map starts it over.
+//!
+//! Finishing a level also keeps the fastest time and the highest score on each map in
+//! BestResults, saved to storage under the "bests" key, apart from saved games. Beating one
+//! sends NewRecord, which plays FANFARE_STINGER and puts up a toast.
+//!
+//! Maps rank a run by its score with the `rank_s`, `rank_a` and `rank_b` properties, the
+//! least score for each rank; see rank.
+
+use crate::bridge::LevelComplete;
+use crate::enemies::Enemy;
+use crate::health::Died;
+use crate::helpers::tiled::{CurrentMap, MapLoaded, MapProperties, RestartMap};
+use crate::locale::Locale;
+use crate::pickups::{PickupCollected, PickupKind};
+use crate::save::GameProgress;
+use crate::sound::PlayStinger;
+use crate::state::GameplaySet;
+use crate::storage;
+use crate::toast::ShowToast;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Storage key of BestResults
+const BESTS_KEY: &str = "bests";
+/// Score for defeating an enemy
+pub const KILL_SCORE: u64 = 10;
+/// Stinger played on a NewRecord
+pub const FANFARE_STINGER: &str = "fanfare";
+
+///
+/// RunStats
+///
+/// The current run through the level on `map`
+/// * score: for the coins picked up and enemies defeated on this run
+/// * time: seconds of play so far
+/// * collected: pickups picked up
+/// * finished: the level is complete, so it no longer changes
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct RunStats {
+    pub map: String,
+    pub score: u64,
+    pub time: f32,
+    pub collected: u32,
+    pub finished: bool,
+}
+
+impl RunStats {
+    /// Starts a new run on `map`
+    pub fn reset(&mut self, map: impl Into<String>) {
+        *self = RunStats {
+            map: map.into(),
+            ..RunStats::default()
+        };
+    }
+}
+
+/// The best results on a map, None before it's first finished
+/// * time: the fastest, in seconds
+/// * score: the highest
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MapBest {
+    pub time: Option<f32>,
+    pub score: Option<u64>,
+}
+
+/// A result better than the best before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Record {
+    Time(f32),
+    Score(u64),
+}
+
+///
+/// BestResults
+///
+/// The best results on each map, by map asset path
+#[derive(Resource, Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BestResults(pub BTreeMap<String, MapBest>);
+
+impl BestResults {
+    pub fn get(&self, map: &str) -> MapBest {
+        self.0.get(map).copied().unwrap_or_default()
+    }
+
+    /// Keeps the time and score of a finished run where they're the best on its map, returning
+    /// those that beat an earlier best. The first run on a map sets its bests without records.
+    pub fn record(&mut self, stats: &RunStats) -> Vec<Record> {
+        let best = self.0.entry(stats.map.clone()).or_default();
+        let mut records = Vec::new();
+        match best.time {
+            Some(time) if stats.time >= time => {}
+            Some(_) => {
+                best.time = Some(stats.time);
+                records.push(Record::Time(stats.time));
+            }
+            None => best.time = Some(stats.time),
+        }
+        match best.score {
+            Some(score) if stats.score <= score => {}
+            Some(_) => {
+                best.score = Some(stats.score);
+                records.push(Record::Score(stats.score));
+            }
+            None => best.score = Some(stats.score),
+        }
+        records
+    }
+}
+
+/// Sent when a finished run beats a best result on `map`
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct NewRecord {
+    pub map: String,
+    pub record: Record,
+}
+
+/// How well a run went, by its score
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rank {
+    S,
+    A,
+    B,
+    C,
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// The rank of `score` on a map with `properties`: the best whose `rank_s`, `rank_a` or `rank_b`
+/// property it reaches, C below them all. None when the map sets none of them.
+pub fn rank(properties: &MapProperties, score: u64) -> Option<Rank> {
+    let thresholds = [
+        (Rank::S, "rank_s"),
+        (Rank::A, "rank_a"),
+        (Rank::B, "rank_b"),
+    ]
+    .map(|(rank, property)| (rank, properties.get_int(property)));
+    if thresholds.iter().all(|(_, least)| least.is_none()) {
+        return None;
+    }
+    let reached = thresholds
+        .iter()
+        .find(|(_, least)| least.is_some_and(|least| score as i64 >= least as i64));
+    Some(reached.map_or(Rank::C, |(rank, _)| *rank))
+}
+
+/// Seconds as minutes, seconds and tenths, e.g. "1:05.3"
+pub fn format_time(seconds: f32) -> String {
+    let tenths = (seconds.max(0.0) * 10.0).floor() as u64;
+    format!("{}:{:02}.{}", tenths / 600, tenths / 10 % 60, tenths % 10)
+}
+
+///
+/// RunStatsPlugin
+///
+/// RunStats, BestResults and NewRecord
+/// * persist: load BestResults from storage, and save them as they change
+pub struct RunStatsPlugin {
+    pub persist: bool,
+}
+
+impl Default for RunStatsPlugin {
+    fn default() -> Self {
+        RunStatsPlugin { persist: true }
+    }
+}
+
+impl Plugin for RunStatsPlugin {
+    fn build(&self, app: &mut App) {
+        let bests = if self.persist {
+            load_bests()
+        } else {
+            BestResults::default()
+        };
+        app.insert_resource(bests)
+            .insert_resource(PersistBests(self.persist))
+            .init_resource::<RunStats>()
+            .init_resource::<GameProgress>()
+            .init_resource::<CurrentMap>()
+            .add_event::<NewRecord>()
+            .add_event::<LevelComplete>()
+            .add_event::<MapLoaded>()
+            .add_event::<RestartMap>()
+            .add_event::<PickupCollected>()
+            .add_event::<Died>()
+            .add_event::<PlayStinger>()
+            .add_event::<ShowToast>()
+            .add_systems(
+                Update,
+                (
+                    start_runs,
+                    (tick_run_time, count_run_score).in_set(GameplaySet),
+                    finish_runs,
+                    celebrate_records,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Whether BestResults are saved to storage
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PersistBests(bool);
+
+/// Reads the saved BestResults, empty when there are none or they can't be read
+pub fn load_bests() -> BestResults {
+    let Some(saved) = storage::load(BESTS_KEY) else {
+        return BestResults::default();
+    };
+    ron::from_str(&saved).unwrap_or_else(|e| {
+        warn!("Ignoring unreadable best results: {}", e);
+        BestResults::default()
+    })
+}
+
+fn save_bests(bests: &BestResults) {
+    let result = ron::to_string(bests)
+        .map_err(|e| e.to_string())
+        .and_then(|text| storage::save(BESTS_KEY, &text).map_err(|e| e.to_string()));
+    if let Err(e) = result {
+        warn!("Could not save best results: {}", e);
+    }
+}
+
+///
+/// start_runs: Bevy system
+///
+/// Starts the RunStats over when the level is restarted or another map is loaded
+pub fn start_runs(
+    mut restarts: EventReader<RestartMap>,
+    mut loaded: EventReader<MapLoaded>,
+    current: Res<CurrentMap>,
+    mut stats: ResMut<RunStats>,
+) {
+    let restarted = restarts.read().count() > 0;
+    let arrived = loaded
+        .read()
+        .filter(|event| !event.reloaded)
+        .map(|event| event.path.clone())
+        .last();
+    if restarted {
+        let map = current.path().unwrap_or(&stats.map).to_string();
+        stats.reset(map);
+    }
+    if let Some(map) = arrived.filter(|map| *map != stats.map) {
+        stats.reset(map);
+    }
+}
+
+///
+/// tick_run_time: Bevy system
+///
+/// Adds the virtual time passed to the run's time, until it's finished
+pub fn tick_run_time(time: Res<Time<Virtual>>, mut stats: ResMut<RunStats>) {
+    if !stats.finished {
+        stats.time += time.delta_seconds();
+    }
+}
+
+///
+/// count_run_score: Bevy system
+///
+/// Adds pickups and defeated enemies to the run, and defeated enemies' KILL_SCORE to
+/// GameProgress::score as coins already are
+pub fn count_run_score(
+    mut collected: EventReader<PickupCollected>,
+    mut deaths: EventReader<Died>,
+    enemy_query: Query<(), With<Enemy>>,
+    mut stats: ResMut<RunStats>,
+    mut progress: ResMut<GameProgress>,
+) {
+    let kills = deaths
+        .read()
+        .filter(|death| enemy_query.contains(death.entity))
+        .count() as u64;
+    if stats.finished {
+        collected.clear();
+        return;
+    }
+    for pickup in collected.read() {
+        stats.collected += 1;
+        if pickup.kind == PickupKind::Coin {
+            stats.score = stats.score.saturating_add_signed(pickup.amount as i64);
+        }
+    }
+    if kills > 0 {
+        stats.score += kills * KILL_SCORE;
+        progress.score += kills * KILL_SCORE;
+    }
+}
+
+///
+/// finish_runs: Bevy system
+///
+/// Freezes the run on LevelComplete and keeps its bests, sending NewRecord for those it beat
+pub fn finish_runs(
+    mut completed: EventReader<LevelComplete>,
+    persist: Res<PersistBests>,
+    mut stats: ResMut<RunStats>,
+    mut bests: ResMut<BestResults>,
+    mut records: EventWriter<NewRecord>,
+) {
+    if completed.read().count() == 0 || stats.finished {
+        return;
+    }
+    stats.finished = true;
+    for record in bests.record(&stats) {
+        records.send(NewRecord {
+            map: stats.map.clone(),
+            record,
+        });
+    }
+    if persist.0 {
+        save_bests(&bests);
+    }
+}
+
+///
+/// celebrate_records: Bevy system
+///
+/// Plays FANFARE_STINGER and puts up a toast for each NewRecord
+pub fn celebrate_records(
+    mut records: EventReader<NewRecord>,
+    locale: Option<Res<Locale>>,
+    mut stingers: EventWriter<PlayStinger>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let mut played = false;
+    for event in records.read() {
+        if !played {
+            stingers.send(PlayStinger::new(FANFARE_STINGER));
+            played = true;
+        }
+        let (key, value) = match event.record {
+            Record::Time(time) => ("results.record_time", format_time(time)),
+            Record::Score(score) => ("results.record_score", score.to_string()),
+        };
+        let args = [("value", value.as_str())];
+        let text = match locale.as_deref() {
+            Some(locale) => locale.format(key, &args),
+            None => Locale::default().format(key, &args),
+        };
+        toasts.send(ShowToast::info(text));
+    }
+}
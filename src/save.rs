@@ -0,0 +1,825 @@
+//! Saved games: SaveGame writes where the player is and what they've done to storage, under the
+//! "save" key (localStorage on the web, settings/save.ron natively), and LoadGame puts it back.
+//!
+//! The game also autosaves, to an "autosave" slot of its own so the player's save is never
+//! overwritten: when a map is entered, at checkpoints (RequestAutosave, or trigger regions with
+//! `checkpoint = true`) and every few minutes of play. Triggers close together make one write.
+//! LoadGame loads whichever slot was written last, passing over slots that can't be read.
+//!
+//! A save holds the current map, the player's position, the GameProgress resource and the tile
+//! edits made to the map. Only the current map's edits are kept: TileEditLog forgets a map's
+//! edits when it's left, and so do saves.
+
+use crate::gfx::MainCamera;
+use crate::helpers::tiled::{
+    place_at_spawn_point, CurrentMap, LoadMap, MapLoaded, PlacedAtSpawn, SetTile, TileEditLog,
+    TriggerEntered, TriggerRegion,
+};
+use crate::state::{AppState, ChangeState, GameplaySet};
+use crate::storage::{self, StorageError};
+use crate::toast::ShowToast;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_ecs_tilemap::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use thiserror::Error;
+
+const SAVE_KEY: &str = "save";
+const AUTOSAVE_KEY: &str = "autosave";
+/// Bool property of trigger regions autosaving when walked into
+pub const CHECKPOINT_PROPERTY: &str = "checkpoint";
+
+/// Version of the SaveData layout, bumped whenever a change would misread older saves. Saves
+/// of other versions are rejected unless `migrate` knows how to bring them up to date.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("there is no saved game")]
+    NoSave,
+    #[error("the saved game is unreadable: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+    #[error(
+        "the saved game is from version {found} of the save format, this build reads {expected}"
+    )]
+    Version { found: u32, expected: u32 },
+    #[error("no map is loaded")]
+    NoMap,
+    #[error("could not serialize the game: {0}")]
+    Serialize(#[from] ron::Error),
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+}
+
+///
+/// GameProgress
+///
+/// What the player has done so far, saved with the game. Gameplay code keeps it up to date;
+/// fired_triggers and play_time are kept by the SavePlugin.
+/// * health: the player's health, None before the game has set it
+/// * max_health: the most health the player can have, the HUD's number of hearts
+/// * inventory: what the player carries, by item name
+/// * keys: the keys the player carries, by the lock they open, with how many of each
+/// * flags: story flags set with set_flag, by levers and dialogue trees among others
+/// * fired_triggers: the trigger regions the player has walked into, as "map#region"
+/// * collected_pickups: the pickups placed on maps the player has picked up, as "map#object id"
+/// * defeated_enemies: the enemies marked `once` the player has defeated, as "map#object id"
+/// * lit_checkpoints: the checkpoints the player has reached, as "map#object id"
+/// * unlocked_doors: the locked doors the player has opened, as "map#object id"
+/// * checkpoint: the checkpoint the player comes back to on dying, None before the first
+/// * score
+/// * play_time: seconds spent in game, not counting pauses
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameProgress {
+    pub health: Option<i32>,
+    pub max_health: Option<i32>,
+    pub inventory: Vec<String>,
+    pub keys: BTreeMap<String, u32>,
+    pub flags: StoryFlags,
+    pub fired_triggers: BTreeSet<String>,
+    pub collected_pickups: BTreeSet<String>,
+    pub defeated_enemies: BTreeSet<String>,
+    pub lit_checkpoints: BTreeSet<String>,
+    pub unlocked_doors: BTreeSet<String>,
+    pub checkpoint: Option<SavedCheckpoint>,
+    pub score: u64,
+    pub play_time: f64,
+}
+
+impl GameProgress {
+    pub fn set_flag(&mut self, flag: impl Into<String>) {
+        self.flags.set(flag);
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.has(flag)
+    }
+
+    /// Sets `flag` if it isn't set and clears it if it is, returning whether it's set now
+    pub fn toggle_flag(&mut self, flag: &str) -> bool {
+        self.flags.toggle(flag)
+    }
+
+    /// Whether the player has been in the trigger region called `region` on the map at `map`,
+    /// e.g. to play a cutscene only once
+    pub fn has_fired(&self, map: &str, region: &str) -> bool {
+        self.fired_triggers.contains(&trigger_key(map, region))
+    }
+
+    /// Whether the player has picked up the pickup that's the object `id` on the map at `map`
+    pub fn has_collected(&self, map: &str, id: u32) -> bool {
+        self.collected_pickups
+            .contains(&trigger_key(map, &id.to_string()))
+    }
+
+    /// Records that the player picked up the pickup that's the object `id` on the map at `map`,
+    /// so it isn't there again
+    pub fn collect(&mut self, map: &str, id: u32) {
+        self.collected_pickups
+            .insert(trigger_key(map, &id.to_string()));
+    }
+
+    /// Whether the player has defeated the `once` enemy that's the object `id` on the map at `map`
+    pub fn has_defeated(&self, map: &str, id: u32) -> bool {
+        self.defeated_enemies
+            .contains(&trigger_key(map, &id.to_string()))
+    }
+
+    /// Records that the player defeated the `once` enemy that's the object `id` on the map at
+    /// `map`, so it doesn't come back
+    pub fn defeat(&mut self, map: &str, id: u32) {
+        self.defeated_enemies
+            .insert(trigger_key(map, &id.to_string()));
+    }
+
+    /// Whether the player has reached the checkpoint that's the object `id` on the map at `map`
+    pub fn has_lit(&self, map: &str, id: u32) -> bool {
+        self.lit_checkpoints
+            .contains(&trigger_key(map, &id.to_string()))
+    }
+
+    /// Records that the player reached the checkpoint that's the object `id` on the map at `map`
+    pub fn light(&mut self, map: &str, id: u32) {
+        self.lit_checkpoints
+            .insert(trigger_key(map, &id.to_string()));
+    }
+
+    /// Whether the player carries a key to `lock`
+    pub fn has_key(&self, lock: &str) -> bool {
+        self.keys.get(lock).is_some_and(|count| *count > 0)
+    }
+
+    pub fn add_key(&mut self, lock: impl Into<String>) {
+        *self.keys.entry(lock.into()).or_default() += 1;
+    }
+
+    /// Takes one of the player's keys to `lock`, returning false if they have none
+    pub fn use_key(&mut self, lock: &str) -> bool {
+        let Some(count) = self.keys.get_mut(lock) else {
+            return false;
+        };
+        *count -= 1;
+        if *count == 0 {
+            self.keys.remove(lock);
+        }
+        true
+    }
+
+    /// Whether the player has opened the locked door that's the object `id` on the map at `map`
+    pub fn has_unlocked(&self, map: &str, id: u32) -> bool {
+        self.unlocked_doors
+            .contains(&trigger_key(map, &id.to_string()))
+    }
+
+    /// Records that the player opened the locked door that's the object `id` on the map at
+    /// `map`, so it stays open
+    pub fn unlock(&mut self, map: &str, id: u32) {
+        self.unlocked_doors
+            .insert(trigger_key(map, &id.to_string()));
+    }
+}
+
+///
+/// StoryFlags
+///
+/// The named flags telling what's happened in the story, e.g. "met_the_mayor", kept in
+/// GameProgress so they're saved with the game. Saved as the set of flags that are set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StoryFlags(BTreeSet<String>);
+
+impl StoryFlags {
+    pub fn set(&mut self, flag: impl Into<String>) {
+        self.0.insert(flag.into());
+    }
+
+    pub fn clear(&mut self, flag: &str) {
+        self.0.remove(flag);
+    }
+
+    pub fn has(&self, flag: &str) -> bool {
+        self.0.contains(flag)
+    }
+
+    /// Sets `flag` if it isn't set and clears it if it is, returning whether it's set now
+    pub fn toggle(&mut self, flag: &str) -> bool {
+        if !self.0.remove(flag) {
+            self.0.insert(flag.to_string());
+        }
+        self.has(flag)
+    }
+
+    /// Whether every flag of `required` is set and none of `excluded` is
+    pub fn allow(&self, required: &[String], excluded: &[String]) -> bool {
+        required.iter().all(|flag| self.has(flag)) && !excluded.iter().any(|flag| self.has(flag))
+    }
+
+    /// The flags that are set
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+}
+
+/// The checkpoint the player comes back to
+/// * map: asset path of the map it's on
+/// * id: its object id
+/// * position: where the player comes back, in world space
+/// * order: its `order` property, telling which of a map's checkpoints come earlier
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedCheckpoint {
+    pub map: String,
+    pub id: u32,
+    pub position: Vec2,
+    pub order: Option<i32>,
+}
+
+fn trigger_key(map: &str, region: &str) -> String {
+    format!("{}#{}", map, region)
+}
+
+/// A SetTile as saved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedTileEdit {
+    pub layer: String,
+    pub x: u32,
+    pub y: u32,
+    pub tile_id: Option<u32>,
+    #[serde(default)]
+    pub flip: (bool, bool, bool),
+    #[serde(default)]
+    pub tileset: Option<String>,
+}
+
+impl From<&SetTile> for SavedTileEdit {
+    fn from(edit: &SetTile) -> Self {
+        SavedTileEdit {
+            layer: edit.layer.clone(),
+            x: edit.pos.x,
+            y: edit.pos.y,
+            tile_id: edit.tile_id,
+            flip: (edit.flip.x, edit.flip.y, edit.flip.d),
+            tileset: edit.tileset.clone(),
+        }
+    }
+}
+
+impl From<&SavedTileEdit> for SetTile {
+    fn from(edit: &SavedTileEdit) -> Self {
+        SetTile {
+            layer: edit.layer.clone(),
+            pos: TilePos {
+                x: edit.x,
+                y: edit.y,
+            },
+            tile_id: edit.tile_id,
+            flip: TileFlip {
+                x: edit.flip.0,
+                y: edit.flip.1,
+                d: edit.flip.2,
+            },
+            tileset: edit.tileset.clone(),
+        }
+    }
+}
+
+///
+/// SaveData
+///
+/// A saved game
+/// * version: SAVE_VERSION when it was written
+/// * map: asset path of the map the player was on
+/// * player: where the PlacedAtSpawn entity was, in world space; None starts at the spawn point
+/// * saved_at: when it was written, in seconds since 1970; 0 for saves from before it was kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveData {
+    pub version: u32,
+    pub map: String,
+    pub player: Option<Vec2>,
+    pub progress: GameProgress,
+    pub tile_edits: Vec<SavedTileEdit>,
+    #[serde(default)]
+    pub saved_at: f64,
+}
+
+impl SaveData {
+    pub fn to_ron(&self) -> Result<String, SaveError> {
+        Ok(ron::to_string(self)?)
+    }
+
+    /// Reads a save of any version this build can migrate
+    pub fn from_ron(text: &str) -> Result<Self, SaveError> {
+        #[derive(Deserialize)]
+        struct Header {
+            #[serde(default)]
+            version: u32,
+        }
+        let header = ron::from_str::<Header>(text)?;
+        migrate(header.version, text)
+    }
+}
+
+/// Reads a save written with version `version` of the layout. Migrations from older versions
+/// go here as the layout changes; until then only the current version is read.
+fn migrate(version: u32, text: &str) -> Result<SaveData, SaveError> {
+    match version {
+        SAVE_VERSION => Ok(ron::from_str(text)?),
+        found => Err(SaveError::Version {
+            found,
+            expected: SAVE_VERSION,
+        }),
+    }
+}
+
+/// Where a game is saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SaveSlot {
+    /// written by SaveGame
+    Manual,
+    /// written by the autosave, never over the manual save
+    Autosave,
+}
+
+impl SaveSlot {
+    pub const ALL: [SaveSlot; 2] = [SaveSlot::Manual, SaveSlot::Autosave];
+
+    fn key(self) -> &'static str {
+        match self {
+            SaveSlot::Manual => SAVE_KEY,
+            SaveSlot::Autosave => AUTOSAVE_KEY,
+        }
+    }
+}
+
+impl fmt::Display for SaveSlot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveSlot::Manual => write!(f, "saved game"),
+            SaveSlot::Autosave => write!(f, "autosave"),
+        }
+    }
+}
+
+/// Writes `save` to `slot` with storage::save_atomic
+pub fn write_slot(slot: SaveSlot, save: &SaveData) -> Result<(), SaveError> {
+    Ok(storage::save_atomic(slot.key(), &save.to_ron()?)?)
+}
+
+/// The game saved in `slot`. A write cut short leaves the whole save under the temp key, so
+/// that's read when the slot itself is missing or unreadable.
+pub fn read_slot(slot: SaveSlot) -> Result<SaveData, SaveError> {
+    let read = |key: &str| {
+        let text = storage::load(key).ok_or(SaveError::NoSave)?;
+        SaveData::from_ron(&text)
+    };
+    read(slot.key()).or_else(|e| match read(&storage::temp_key(slot.key())) {
+        Ok(save) => Ok(save),
+        Err(temp_error) if matches!(e, SaveError::NoSave) => Err(temp_error),
+        Err(_) => Err(e),
+    })
+}
+
+///
+/// SavesRead
+///
+/// What reading the save slots found
+/// * newest: the save written last of those that could be read, and its slot
+/// * unreadable: the slots holding a save that couldn't be read, and why
+#[derive(Debug, Default)]
+pub struct SavesRead {
+    pub newest: Option<(SaveSlot, SaveData)>,
+    pub unreadable: Vec<(SaveSlot, SaveError)>,
+}
+
+impl SavesRead {
+    /// Sorts what was read from each slot; empty slots are left out, and on a tie the first
+    /// slot wins
+    pub fn from_slots(
+        slots: impl IntoIterator<Item = (SaveSlot, Result<SaveData, SaveError>)>,
+    ) -> Self {
+        let mut read = SavesRead::default();
+        for (slot, result) in slots {
+            match result {
+                Ok(save) => {
+                    let newer = read
+                        .newest
+                        .as_ref()
+                        .is_none_or(|(_, newest)| save.saved_at > newest.saved_at);
+                    if newer {
+                        read.newest = Some((slot, save));
+                    }
+                }
+                Err(SaveError::NoSave) => {}
+                Err(e) => read.unreadable.push((slot, e)),
+            }
+        }
+        read
+    }
+}
+
+/// Reads every slot
+pub fn read_saves() -> SavesRead {
+    SavesRead::from_slots(SaveSlot::ALL.map(|slot| (slot, read_slot(slot))))
+}
+
+/// Whether there's a saved game this build can load, for the main menu's "Continue"
+pub fn has_save() -> bool {
+    read_save().is_ok()
+}
+
+/// The newest saved game of any slot
+pub fn read_save() -> Result<SaveData, SaveError> {
+    let read = read_saves();
+    match read.newest {
+        Some((_, save)) => Ok(save),
+        None => Err(read
+            .unreadable
+            .into_iter()
+            .next()
+            .map_or(SaveError::NoSave, |(_, e)| e)),
+    }
+}
+
+/// Seconds since 1970, telling which save is newest
+#[cfg(target_arch = "wasm32")]
+fn wall_clock() -> f64 {
+    js_sys::Date::now() / 1000.0
+}
+
+/// Seconds since 1970, telling which save is newest
+#[cfg(not(target_arch = "wasm32"))]
+fn wall_clock() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |since| since.as_secs_f64())
+}
+
+/// Saves the game where it is
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct SaveGame;
+
+/// Loads the saved game, moving from the main menu into the game if need be
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct LoadGame;
+
+/// Sent once SaveGame has written the save
+#[derive(Event, Debug, Clone)]
+pub struct GameSaved {
+    pub map: String,
+}
+
+/// Autosaves, e.g. at a checkpoint. Requests close together make one write.
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct RequestAutosave;
+
+/// Sent as the autosave is written, for the UI to flash a "saving…" icon
+#[derive(Event, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AutosaveIndicator;
+
+///
+/// AutosaveConfig
+///
+/// * enabled
+/// * interval: seconds of play between autosaves, None to only autosave at maps and checkpoints
+/// * throttle: seconds an autosave waits after it's triggered, taking in the triggers coming
+///   after it
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct AutosaveConfig {
+    pub enabled: bool,
+    pub interval: Option<f64>,
+    pub throttle: f64,
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> Self {
+        AutosaveConfig {
+            enabled: true,
+            interval: Some(300.0),
+            throttle: 3.0,
+        }
+    }
+}
+
+/// Sent once a loaded game's map has spawned and the player and tiles are back in place
+#[derive(Event, Debug, Clone)]
+pub struct GameLoaded {
+    pub map: String,
+}
+
+///
+/// PendingLoad
+///
+/// The save being loaded, until its map has spawned
+#[derive(Resource, Debug, Clone)]
+pub struct PendingLoad(pub SaveData);
+
+/// Saving and loading games
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameProgress>()
+            .init_resource::<AutosaveConfig>()
+            .add_event::<SaveGame>()
+            .add_event::<LoadGame>()
+            .add_event::<GameSaved>()
+            .add_event::<GameLoaded>()
+            .add_event::<RequestAutosave>()
+            .add_event::<AutosaveIndicator>()
+            .add_event::<ChangeState>()
+            .add_event::<ShowToast>()
+            .add_systems(
+                Update,
+                (
+                    save_game.run_if(on_event::<SaveGame>()),
+                    load_game.run_if(on_event::<LoadGame>()),
+                    restore_game.after(place_at_spawn_point),
+                    track_play_time.in_set(GameplaySet),
+                    autosave.after(restore_game).after(track_play_time),
+                ),
+            )
+            .add_systems(PostUpdate, (record_fired_triggers, autosave_at_checkpoints));
+
+        #[cfg(feature = "dev")]
+        register_console_commands(app);
+    }
+}
+
+/// give for the console
+#[cfg(feature = "dev")]
+fn register_console_commands(app: &mut App) {
+    use crate::console::{ConsoleError, RegisterConsoleCommand};
+
+    app.register_console_command(
+        "give <item> [count]",
+        "Adds an item to the inventory, count times",
+        |args, world| {
+            let item: String = args.required("item")?;
+            let count = args.optional::<u32>("count")?.unwrap_or(1);
+            let Some(mut progress) = world.get_resource_mut::<GameProgress>() else {
+                return Err(ConsoleError::Failed("there's no GameProgress".to_string()));
+            };
+            progress
+                .inventory
+                .extend(std::iter::repeat_n(item.clone(), count as usize));
+            Ok(format!("Gave {} {}", count, item))
+        },
+    );
+}
+
+///
+/// GameSnapshot: Bevy system parameter
+///
+/// What goes into a save, as it is now
+#[derive(SystemParam)]
+pub struct GameSnapshot<'w, 's> {
+    current: Res<'w, CurrentMap>,
+    progress: Res<'w, GameProgress>,
+    edit_log: Res<'w, TileEditLog>,
+    player_query: Query<'w, 's, &'static Transform, (With<PlacedAtSpawn>, Without<MainCamera>)>,
+}
+
+impl GameSnapshot<'_, '_> {
+    /// The game as a save written now
+    pub fn save_data(&self) -> Result<SaveData, SaveError> {
+        let map = self.current.path().ok_or(SaveError::NoMap)?;
+        Ok(SaveData {
+            version: SAVE_VERSION,
+            map: map.to_string(),
+            player: self
+                .player_query
+                .iter()
+                .next()
+                .map(|t| t.translation.truncate()),
+            progress: self.progress.clone(),
+            tile_edits: self
+                .edit_log
+                .edits()
+                .iter()
+                .map(SavedTileEdit::from)
+                .collect(),
+            saved_at: wall_clock(),
+        })
+    }
+}
+
+///
+/// save_game: Bevy system
+///
+/// Handles SaveGame
+pub fn save_game(
+    mut events: EventReader<SaveGame>,
+    snapshot: GameSnapshot,
+    mut saved: EventWriter<GameSaved>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    let result = snapshot.save_data().and_then(|save| {
+        write_slot(SaveSlot::Manual, &save)?;
+        Ok(save.map)
+    });
+    match result {
+        Ok(map) => {
+            info!("Game saved");
+            saved.send(GameSaved { map });
+        }
+        Err(e) => warn!("Could not save the game: {}", e),
+    }
+}
+
+///
+/// load_game: Bevy system
+///
+/// Handles LoadGame: restores GameProgress from the newest save and loads its map, leaving the
+/// rest to restore_game once it has spawned. Saves that can't be read get an error toast.
+pub fn load_game(
+    mut commands: Commands,
+    mut events: EventReader<LoadGame>,
+    mut progress: ResMut<GameProgress>,
+    mut load_map: EventWriter<LoadMap>,
+    mut change_state: EventWriter<ChangeState>,
+    mut toasts: EventWriter<ShowToast>,
+    state: Option<Res<State<AppState>>>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    let read = read_saves();
+    for (slot, e) in &read.unreadable {
+        warn!("Could not read the {}: {}", slot, e);
+        toasts.send(ShowToast::error(format!(
+            "The {} couldn't be read: {}",
+            slot, e
+        )));
+    }
+    let Some((slot, save)) = read.newest else {
+        if read.unreadable.is_empty() {
+            warn!("Could not load the game: {}", SaveError::NoSave);
+        }
+        return;
+    };
+    info!("Loading the {}", slot);
+
+    *progress = save.progress.clone();
+    load_map.send(LoadMap::new(save.map.clone()));
+    if state.is_some_and(|state| *state.get() != AppState::InGame) {
+        change_state.send(ChangeState(AppState::InGame));
+    }
+    commands.insert_resource(PendingLoad(save));
+}
+
+///
+/// restore_game: Bevy system
+///
+/// Once the map of a PendingLoad has spawned, replays its tile edits and puts the player and
+/// camera back where they were
+pub fn restore_game(
+    mut commands: Commands,
+    mut loaded: EventReader<MapLoaded>,
+    pending: Option<Res<PendingLoad>>,
+    mut edits: EventWriter<SetTile>,
+    mut done: EventWriter<GameLoaded>,
+    mut player_query: Query<&mut Transform, (With<PlacedAtSpawn>, Without<MainCamera>)>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    let Some(pending) = pending else {
+        loaded.clear();
+        return;
+    };
+    let save = &pending.0;
+    let arrived = loaded
+        .read()
+        .any(|event| !event.reloaded && event.path == save.map);
+    if !arrived {
+        return;
+    }
+
+    edits.send_batch(save.tile_edits.iter().map(SetTile::from));
+    if let Some(position) = save.player {
+        for mut transform in player_query.iter_mut() {
+            transform.translation = position.extend(transform.translation.z);
+        }
+        if !player_query.is_empty() {
+            for mut camera in camera_query.iter_mut() {
+                camera.translation = position.extend(camera.translation.z);
+            }
+        }
+    }
+    done.send(GameLoaded {
+        map: save.map.clone(),
+    });
+    commands.remove_resource::<PendingLoad>();
+}
+
+///
+/// autosave: Bevy system
+///
+/// Writes the autosave slot AutosaveConfig::throttle seconds after it's triggered by a
+/// RequestAutosave, a map being entered or AutosaveConfig::interval seconds of play; the triggers
+/// in between are covered by the same write. Waits for a PendingLoad to be restored.
+#[allow(clippy::too_many_arguments)]
+pub fn autosave(
+    config: Res<AutosaveConfig>,
+    time: Res<Time<Real>>,
+    mut requests: EventReader<RequestAutosave>,
+    mut loaded: EventReader<MapLoaded>,
+    pending_load: Option<Res<PendingLoad>>,
+    snapshot: GameSnapshot,
+    mut indicator: EventWriter<AutosaveIndicator>,
+    mut last_play_time: Local<f64>,
+    mut write_at: Local<Option<f64>>,
+) {
+    let requested = requests.read().count() > 0;
+    let entered = loaded.read().any(|event| !event.reloaded);
+    if !config.enabled {
+        *write_at = None;
+        return;
+    }
+
+    let play_time = snapshot.progress.play_time;
+    // a new game, or a save from earlier on
+    if play_time < *last_play_time {
+        *last_play_time = play_time;
+    }
+    let played = config
+        .interval
+        .is_some_and(|interval| play_time - *last_play_time >= interval);
+    let now = time.elapsed_seconds_f64();
+    if (requested || entered || played) && write_at.is_none() {
+        *write_at = Some(now + config.throttle);
+    }
+
+    if !write_at.is_some_and(|at| now >= at) || pending_load.is_some() {
+        return;
+    }
+    *write_at = None;
+    *last_play_time = play_time;
+    let result = snapshot
+        .save_data()
+        .and_then(|save| write_slot(SaveSlot::Autosave, &save));
+    match result {
+        Ok(()) => {
+            info!("Autosaved");
+            indicator.send(AutosaveIndicator);
+        }
+        Err(e) => warn!("Could not autosave: {}", e),
+    }
+}
+
+///
+/// autosave_at_checkpoints: Bevy system
+///
+/// Sends RequestAutosave when a sensor walks into a trigger region with `checkpoint = true`
+pub fn autosave_at_checkpoints(
+    mut entered: EventReader<TriggerEntered>,
+    region_query: Query<&TriggerRegion>,
+    mut requests: EventWriter<RequestAutosave>,
+) {
+    let checkpoint = entered.read().any(|event| {
+        region_query.get(event.region).is_ok_and(|region| {
+            matches!(
+                region.properties.get(CHECKPOINT_PROPERTY),
+                Some(tiled::PropertyValue::BoolValue(true))
+            )
+        })
+    });
+    if checkpoint {
+        requests.send(RequestAutosave);
+    }
+}
+
+///
+/// track_play_time: Bevy system
+///
+/// Adds the time spent in game to GameProgress::play_time
+pub fn track_play_time(time: Res<Time>, mut progress: ResMut<GameProgress>) {
+    progress.play_time += time.delta_seconds_f64();
+}
+
+///
+/// record_fired_triggers: Bevy system
+///
+/// Adds the trigger regions sensors walk into to GameProgress::fired_triggers
+pub fn record_fired_triggers(
+    mut entered: EventReader<TriggerEntered>,
+    current: Res<CurrentMap>,
+    region_query: Query<&TriggerRegion>,
+    mut progress: ResMut<GameProgress>,
+) {
+    let Some(map) = current.path() else {
+        entered.clear();
+        return;
+    };
+    for event in entered.read() {
+        let Ok(region) = region_query.get(event.region) else {
+            continue;
+        };
+        let key = trigger_key(map, &region.name);
+        // checked first, so GameProgress isn't marked changed every time
+        if !progress.fired_triggers.contains(&key) {
+            progress.fired_triggers.insert(key);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! The player's settings: one blob with the audio levels, graphics options and key bindings,
+//! stored under the "settings" key (localStorage on the web, settings/settings.ron natively).
+//!
+//! Menus change the Settings resource and every module follows: SettingsChanged tells them to
+//! pick up their part of it. Changes are saved once they've settled.
+
+use crate::gfx::GraphicsSettings;
+use crate::input::InputMap;
+use crate::sound::AudioChannels;
+use crate::storage;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const SETTINGS_KEY: &str = "settings";
+
+/// Seconds Settings has to stay unchanged before it is written out
+const SAVE_DELAY: f64 = 0.5;
+
+///
+/// Settings
+///
+/// Everything the settings menu sets. Fields missing from saved settings keep their defaults, so
+/// settings saved by older builds still load.
+#[derive(Resource, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioChannels,
+    pub graphics: GraphicsSettings,
+    pub bindings: InputMap,
+    /// the language the player picked, see the locale module
+    pub language: Option<String>,
+    /// whether sounds show their captions, see CaptionsEnabled
+    pub captions: bool,
+}
+
+impl Settings {
+    /// For the settings menu's reset button
+    pub fn reset_to_defaults(&mut self) {
+        *self = Settings::default();
+    }
+}
+
+/// Sent when Settings changes, and once at startup, for the modules to apply it
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct SettingsChanged;
+
+///
+/// SettingsPlugin
+///
+/// * persist: load Settings from storage, and save it as it changes
+pub struct SettingsPlugin {
+    pub persist: bool,
+}
+
+impl Default for SettingsPlugin {
+    fn default() -> Self {
+        SettingsPlugin { persist: true }
+    }
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        // loaded here rather than in a startup system so the first frame already uses it
+        let settings = if self.persist {
+            load_settings()
+        } else {
+            Settings::default()
+        };
+        app.insert_resource(settings)
+            .add_event::<SettingsChanged>()
+            .add_systems(
+                First,
+                (sync_audio_settings, notify_settings_changed).chain(),
+            );
+        if self.persist {
+            app.add_systems(Update, save_settings);
+        }
+    }
+}
+
+/// Reads saved settings, falling back to the defaults with a warning when they don't parse
+pub fn parse_settings(saved: &str) -> Settings {
+    match ron::from_str::<Settings>(saved) {
+        Ok(mut settings) => {
+            // keep hand-edited files within range
+            let audio = &mut settings.audio;
+            audio.music = audio.music.clamp(0.0, 1.0);
+            audio.sfx = audio.sfx.clamp(0.0, 1.0);
+            audio.ambient = audio.ambient.clamp(0.0, 1.0);
+            settings
+        }
+        Err(e) => {
+            warn!("Ignoring unreadable settings: {}", e);
+            Settings::default()
+        }
+    }
+}
+
+/// The saved settings, or the defaults if there are none or they can't be read
+pub fn load_settings() -> Settings {
+    storage::load(SETTINGS_KEY).map_or_else(Settings::default, |saved| parse_settings(&saved))
+}
+
+///
+/// sync_audio_settings: Bevy system
+///
+/// Keeps Settings::audio up to date with volume and mute changes made through the sound events.
+/// A change to Settings wins over the channels, apply_audio_settings handing it to them.
+pub fn sync_audio_settings(channels: Option<Res<AudioChannels>>, mut settings: ResMut<Settings>) {
+    if settings.is_changed() {
+        return;
+    }
+    // the channels start out at their defaults, not the saved levels
+    let Some(channels) = channels.filter(|channels| channels.is_changed() && !channels.is_added())
+    else {
+        return;
+    };
+    if settings.audio != *channels {
+        settings.audio = channels.clone();
+    }
+}
+
+///
+/// notify_settings_changed: Bevy system
+///
+/// Sends SettingsChanged when Settings changes, and on the first frame
+pub fn notify_settings_changed(settings: Res<Settings>, mut events: EventWriter<SettingsChanged>) {
+    if settings.is_changed() {
+        events.send(SettingsChanged);
+    }
+}
+
+///
+/// save_settings: Bevy system
+///
+/// Writes Settings to storage once it has settled after a change, so dragging a volume slider
+/// doesn't write every frame
+pub fn save_settings(
+    time: Res<Time<Real>>,
+    settings: Res<Settings>,
+    mut save_at: Local<Option<f64>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    // the first run sees the value that was just loaded
+    if settings.is_changed() && !settings.is_added() {
+        *save_at = Some(now + SAVE_DELAY);
+    }
+
+    if !save_at.is_some_and(|at| now >= at) {
+        return;
+    }
+    *save_at = None;
+
+    let serialized = match ron::to_string(&*settings) {
+        Ok(serialized) => serialized,
+        Err(e) => {
+            warn!("Could not serialize settings: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = storage::save(SETTINGS_KEY, &serialized) {
+        warn!("Could not save settings: {}", e);
+    }
+}
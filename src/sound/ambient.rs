@@ -0,0 +1,181 @@
+use super::{
+    log::{SoundAction, SoundLogger, SoundOutcome},
+    AudioChannel, AudioChannels, MusicFade, SoundResource, VolumeTweens,
+};
+use bevy::{
+    audio::{
+        AudioSink, AudioSinkPlayback, AudioSourceBundle, PlaybackMode, PlaybackSettings, Volume,
+    },
+    prelude::*,
+};
+
+///
+/// PlayAmbient
+///
+/// * name: the name the loop was registered under in SoundResource
+/// * fade_in: seconds to ramp the loop up from silence (0.0 starts at full volume)
+#[derive(Event, Debug, Clone)]
+pub struct PlayAmbient {
+    pub name: String,
+    pub fade_in: f32,
+}
+
+impl PlayAmbient {
+    pub fn new(name: impl Into<String>) -> Self {
+        PlayAmbient {
+            name: name.into(),
+            fade_in: 0.0,
+        }
+    }
+
+    pub fn with_fade_in(mut self, fade_in: f32) -> Self {
+        self.fade_in = fade_in.max(0.0);
+        self
+    }
+}
+
+///
+/// StopAmbient
+///
+/// * name: the loop to stop, or None to stop every ambient loop
+/// * fade_out: seconds to fade to silence before despawning (0.0 stops instantly)
+#[derive(Event, Debug, Clone)]
+pub struct StopAmbient {
+    pub name: Option<String>,
+    pub fade_out: f32,
+}
+
+impl StopAmbient {
+    pub fn new(name: impl Into<String>) -> Self {
+        StopAmbient {
+            name: Some(name.into()),
+            fade_out: 0.0,
+        }
+    }
+
+    pub fn all() -> Self {
+        StopAmbient {
+            name: None,
+            fade_out: 0.0,
+        }
+    }
+
+    pub fn with_fade_out(mut self, fade_out: f32) -> Self {
+        self.fade_out = fade_out.max(0.0);
+        self
+    }
+
+    fn matches(&self, playing: &AmbientPlaying) -> bool {
+        self.name.as_ref().is_none_or(|name| *name == playing.0)
+    }
+}
+
+/// Marks an ambient loop entity with the name it was started under.
+/// Ambient loops are left alone by the music systems, so they carry on across music changes.
+#[derive(Debug, Clone, Component)]
+pub struct AmbientPlaying(pub String);
+
+pub fn play_ambient(
+    mut commands: Commands,
+    mut events: EventReader<PlayAmbient>,
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    mut log: SoundLogger,
+    ambient_query: Query<(&AmbientPlaying, Option<&MusicFade>)>,
+) {
+    for event in events.read() {
+        let running = ambient_query.iter().any(|(playing, fade)| {
+            playing.0 == event.name && !fade.is_some_and(MusicFade::is_fading_out)
+        });
+        if running {
+            log.record(
+                SoundAction::PlayAmbient,
+                &event.name,
+                SoundOutcome::Skipped("already playing"),
+                None,
+            );
+            continue;
+        }
+
+        let Some(handle) = sound_resource.get(&event.name) else {
+            warn!("Ambient sound not found: {}", event.name);
+            log.record(
+                SoundAction::PlayAmbient,
+                &event.name,
+                SoundOutcome::Missing,
+                None,
+            );
+            continue;
+        };
+        let volume = sound_resource.defaults(&event.name).volume
+            * channels.effective(AudioChannel::Ambient, &tweens);
+
+        let mut entity = commands.spawn((
+            AudioSourceBundle {
+                source: handle,
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Loop,
+                    volume: Volume::new(if event.fade_in > 0.0 { 0.0 } else { volume }),
+                    ..default()
+                },
+            },
+            AmbientPlaying(event.name.clone()),
+        ));
+        if event.fade_in > 0.0 {
+            entity.insert(MusicFade::fade_in(event.fade_in));
+        }
+        log.record(
+            SoundAction::PlayAmbient,
+            &event.name,
+            SoundOutcome::Played,
+            Some(volume),
+        );
+    }
+}
+
+pub fn stop_ambient(
+    mut commands: Commands,
+    mut events: EventReader<StopAmbient>,
+    mut log: SoundLogger,
+    ambient_query: Query<(Entity, &AmbientPlaying, Option<&MusicFade>)>,
+) {
+    for event in events.read() {
+        for (entity, playing, fade) in ambient_query.iter() {
+            if !event.matches(playing) {
+                continue;
+            }
+            log.record(
+                SoundAction::StopAmbient,
+                &playing.0,
+                SoundOutcome::Stopped,
+                None,
+            );
+            if event.fade_out > 0.0 {
+                let from = fade.map_or(1.0, MusicFade::level);
+                commands
+                    .entity(entity)
+                    .insert(MusicFade::fade_out(from, event.fade_out));
+            } else {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+///
+/// apply_ambient_volume: Bevy system
+///
+/// Sets each ambient sink to its default volume × ambient channel volume × fade level
+pub fn apply_ambient_volume(
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    ambient_query: Query<(&AmbientPlaying, &AudioSink, Option<&MusicFade>)>,
+) {
+    let level = channels.effective(AudioChannel::Ambient, &tweens);
+    for (playing, sink, fade) in ambient_query.iter() {
+        let volume = sound_resource.defaults(&playing.0).volume;
+        sink.set_volume(volume * fade.map_or(1.0, MusicFade::level) * level);
+    }
+}
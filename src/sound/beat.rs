@@ -0,0 +1,136 @@
+use super::{CurrentMusic, SoundResource};
+use bevy::prelude::*;
+
+///
+/// MusicTempo
+///
+/// * bpm: beats per minute
+/// * offset: seconds from the start of the track to its first beat
+/// * beats_per_bar: beats in one bar, 4 for most tracks
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MusicTempo {
+    pub bpm: f32,
+    pub offset: f32,
+    pub beats_per_bar: u32,
+}
+
+impl MusicTempo {
+    pub fn new(bpm: f32) -> Self {
+        MusicTempo {
+            bpm,
+            offset: 0.0,
+            beats_per_bar: 4,
+        }
+    }
+
+    pub fn with_offset(mut self, offset: f32) -> Self {
+        self.offset = offset.max(0.0);
+        self
+    }
+
+    pub fn with_beats_per_bar(mut self, beats_per_bar: u32) -> Self {
+        self.beats_per_bar = beats_per_bar.max(1);
+        self
+    }
+
+    /// Position in beats after `elapsed` seconds of playback, negative before the first beat
+    fn beats_at(&self, elapsed: f32) -> f32 {
+        (elapsed - self.offset) * self.bpm / 60.0
+    }
+}
+
+/// Sent on every beat of the current track, if it has a tempo
+#[derive(Event, Debug, Clone, Copy)]
+pub struct OnBeat {
+    pub beat_index: u64,
+    pub bar: u64,
+}
+
+///
+/// BeatClock
+///
+/// Where the current track is in its beat grid. Empty while the track has no tempo.
+#[derive(Debug, Default, Resource)]
+pub struct BeatClock {
+    tempo: Option<MusicTempo>,
+    track: Option<Entity>,
+    beat: Option<u64>,
+    progress: f32,
+}
+
+impl BeatClock {
+    pub fn tempo(&self) -> Option<MusicTempo> {
+        self.tempo
+    }
+
+    /// Index of the last beat, or None before the first one
+    pub fn beat(&self) -> Option<u64> {
+        self.beat
+    }
+
+    /// Fraction of the way from the last beat to the next one, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        self.progress
+    }
+}
+
+/// Most OnBeat events sent in one frame; after a hitch only the latest beats are sent
+const MAX_BEATS_PER_FRAME: u64 = 4;
+
+///
+/// update_beat_clock: Bevy system
+///
+/// Advances the BeatClock from the current track's playback time and sends OnBeat for every
+/// beat crossed. Pausing the music stops the clock since CurrentMusic stops counting.
+pub fn update_beat_clock(
+    time: Res<Time<Real>>,
+    current: Res<CurrentMusic>,
+    sound_resource: Res<SoundResource>,
+    mut clock: ResMut<BeatClock>,
+    mut beat_events: EventWriter<OnBeat>,
+) {
+    let Some(track) = current.0.as_ref() else {
+        *clock = BeatClock::default();
+        return;
+    };
+    let Some(tempo) = sound_resource.tempo(&track.name) else {
+        *clock = BeatClock::default();
+        return;
+    };
+
+    // the incoming track of a crossfade takes over the clock right away
+    if clock.track != Some(track.entity()) || clock.tempo != Some(tempo) {
+        *clock = BeatClock {
+            tempo: Some(tempo),
+            track: Some(track.entity()),
+            beat: None,
+            progress: 0.0,
+        };
+    }
+
+    // a track still loading hasn't reached its first beat
+    if !track.is_audible() {
+        clock.progress = 0.0;
+        return;
+    }
+    let position = tempo.beats_at(track.elapsed(time.elapsed()).as_secs_f32());
+    if position < 0.0 {
+        clock.progress = 0.0;
+        return;
+    }
+    clock.progress = position.fract();
+
+    let beat = position.floor() as u64;
+    let first = match clock.beat {
+        Some(last) if beat <= last => return,
+        Some(last) => (last + 1).max(beat.saturating_sub(MAX_BEATS_PER_FRAME - 1)),
+        None => beat,
+    };
+    for beat_index in first..=beat {
+        beat_events.send(OnBeat {
+            beat_index,
+            bar: beat_index / tempo.beats_per_bar as u64,
+        });
+    }
+    clock.beat = Some(beat);
+}
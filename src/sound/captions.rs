@@ -0,0 +1,95 @@
+use super::SoundResource;
+use crate::locale::Locale;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Seconds during which the same caption text isn't sent again
+const COALESCE_WINDOW: f64 = 1.0;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+pub enum CaptionImportance {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+///
+/// Caption
+///
+/// * text: what the UI shows when the sound plays, e.g. "[door creaks]"
+/// * importance: lets the UI filter or highlight captions
+#[derive(Debug, Clone)]
+pub struct Caption {
+    pub text: String,
+    pub importance: CaptionImportance,
+}
+
+/// Sent when a sound with a caption plays, while CaptionsEnabled is on
+#[derive(Event, Debug, Clone)]
+pub struct SoundCaption {
+    pub text: String,
+    pub importance: CaptionImportance,
+    /// where a positional sound came from, for a directional indicator
+    pub position: Option<Vec2>,
+}
+
+/// Whether SoundCaption events are sent. Off by default.
+#[derive(Debug, Default, Clone, Copy, Resource)]
+pub struct CaptionsEnabled(pub bool);
+
+/// When each caption text was last sent
+#[derive(Debug, Default, Resource)]
+pub struct RecentCaptions {
+    sent: HashMap<String, f64>,
+}
+
+///
+/// Captions: Bevy system parameter
+///
+/// Sends the caption of a sound that just started playing
+#[derive(SystemParam)]
+pub struct Captions<'w> {
+    enabled: Res<'w, CaptionsEnabled>,
+    recent: ResMut<'w, RecentCaptions>,
+    events: EventWriter<'w, SoundCaption>,
+    locale: Option<Res<'w, Locale>>,
+}
+
+impl<'w> Captions<'w> {
+    pub fn emit(
+        &mut self,
+        sound_resource: &SoundResource,
+        name: &str,
+        position: Option<Vec2>,
+        now: f64,
+    ) {
+        if !self.enabled.0 {
+            return;
+        }
+        let Some(caption) = sound_resource.caption(name) else {
+            return;
+        };
+
+        // "footstep footstep footstep" becomes one caption
+        if let Some(sent) = self.recent.sent.get(&caption.text) {
+            if now - sent < COALESCE_WINDOW {
+                return;
+            }
+        }
+        self.recent.sent.insert(caption.text.clone(), now);
+
+        // translated under "caption.<sound name>", the manifest's text being the fallback
+        let text = self
+            .locale
+            .as_ref()
+            .and_then(|locale| locale.get(&format!("caption.{}", name)))
+            .map_or_else(|| caption.text.clone(), String::from);
+        self.events.send(SoundCaption {
+            text,
+            importance: caption.importance,
+            position,
+        });
+    }
+}
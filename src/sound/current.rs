@@ -0,0 +1,152 @@
+use super::{MusicFade, NowPlaying};
+use bevy::{
+    audio::{AudioSink, AudioSinkPlayback},
+    prelude::*,
+};
+use std::time::Duration;
+
+/// Pause the music that is playing
+#[derive(Event, Default)]
+pub struct PauseMusic;
+
+/// Resume paused music
+#[derive(Event, Default)]
+pub struct ResumeMusic;
+
+///
+/// MusicTrack
+///
+/// * name: the name the track was requested with
+/// * started: real time at which the track became audible (when it was requested until then)
+/// * paused: whether PauseMusic is holding the track
+#[derive(Debug, Clone)]
+pub struct MusicTrack {
+    pub name: String,
+    pub started: Duration,
+    pub paused: bool,
+    entity: Entity,
+    audible: bool,
+    paused_total: Duration,
+    paused_since: Duration,
+}
+
+impl MusicTrack {
+    /// Time the track has spent playing, not counting pauses or the time it took to load.
+    /// bevy's AudioSink doesn't report a position, so this is tracked from real time.
+    pub fn elapsed(&self, now: Duration) -> Duration {
+        if !self.audible {
+            return Duration::ZERO;
+        }
+        let paused = if self.paused {
+            self.paused_total + now.saturating_sub(self.paused_since)
+        } else {
+            self.paused_total
+        };
+        now.saturating_sub(self.started).saturating_sub(paused)
+    }
+
+    /// The entity playing the track
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Whether the track's sink has started, so it can be heard
+    pub fn is_audible(&self) -> bool {
+        self.audible
+    }
+}
+
+///
+/// CurrentMusic
+///
+/// The track that is playing, or None while there is no music. During a crossfade this is
+/// already the incoming track.
+#[derive(Debug, Default, Resource)]
+pub struct CurrentMusic(pub Option<MusicTrack>);
+
+impl CurrentMusic {
+    pub fn name(&self) -> Option<&str> {
+        self.0.as_ref().map(|track| track.name.as_str())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.as_ref().is_some_and(|track| track.paused)
+    }
+
+    /// Playback time of the current track, see MusicTrack::elapsed
+    pub fn elapsed(&self, time: &Time<Real>) -> Option<Duration> {
+        self.0.as_ref().map(|track| track.elapsed(time.elapsed()))
+    }
+}
+
+///
+/// update_current_music: Bevy system
+///
+/// Follows the newest music entity, noting when its sink starts and clearing the track once
+/// it stops or is faded out
+pub fn update_current_music(
+    time: Res<Time<Real>>,
+    mut current: ResMut<CurrentMusic>,
+    added_query: Query<(Entity, &NowPlaying), Added<NowPlaying>>,
+    playing_query: Query<(Option<&AudioSink>, Option<&MusicFade>), With<NowPlaying>>,
+) {
+    let now = time.elapsed();
+    if let Some((entity, playing)) = added_query.iter().last() {
+        current.0 = Some(MusicTrack {
+            name: playing.name.clone(),
+            started: now,
+            paused: false,
+            entity,
+            audible: false,
+            paused_total: Duration::ZERO,
+            paused_since: Duration::ZERO,
+        });
+    }
+
+    let Some(track) = current.0.as_ref() else {
+        return;
+    };
+    match playing_query.get(track.entity) {
+        Ok((_, Some(fade))) if fade.is_fading_out() => current.0 = None,
+        Ok((Some(_), _)) if !track.audible => {
+            if let Some(track) = current.0.as_mut() {
+                track.audible = true;
+                track.started = now;
+            }
+        }
+        Ok(_) => {}
+        Err(_) => current.0 = None,
+    }
+}
+
+pub fn pause_music(
+    time: Res<Time<Real>>,
+    mut pause_events: EventReader<PauseMusic>,
+    mut resume_events: EventReader<ResumeMusic>,
+    mut current: ResMut<CurrentMusic>,
+    sink_query: Query<&AudioSink, With<NowPlaying>>,
+) {
+    let pause = pause_events.read().count() > 0;
+    let resume = resume_events.read().count() > 0;
+    if pause == resume {
+        return;
+    }
+
+    for sink in sink_query.iter() {
+        if pause {
+            sink.pause();
+        } else {
+            sink.play();
+        }
+    }
+
+    let now = time.elapsed();
+    if let Some(track) = current.0.as_mut() {
+        if pause && !track.paused {
+            track.paused_since = now;
+        } else if resume && track.paused {
+            track.paused_total += now.saturating_sub(track.paused_since);
+        }
+        track.paused = pause;
+    }
+}
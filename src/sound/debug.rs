@@ -0,0 +1,142 @@
+use super::{AudioChannel, AudioChannels, PlaySFX, SetVolume, SfxVoice, SoundLog, SoundOutcome};
+use crate::console::RegisterConsoleCommand;
+use crate::toast::ShowToast;
+use bevy::{core::FrameCount, prelude::*};
+use std::fmt::Write;
+
+/// Number of log entries the overlay lists, newest first
+const OVERLAY_ENTRIES: usize = 12;
+
+/// Marks the text entity of the sound debug overlay
+#[derive(Component)]
+pub struct SoundDebugOverlay;
+
+pub fn spawn_sound_overlay(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 14.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(4.0),
+            right: Val::Px(4.0),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.6)),
+        SoundDebugOverlay,
+    ));
+}
+
+///
+/// toggle_sound_overlay: Bevy system
+///
+/// F2 shows or hides the overlay, F3 being the diagnostics overlay
+pub fn toggle_sound_overlay(
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut overlay_query: Query<&mut Visibility, With<SoundDebugOverlay>>,
+) {
+    if !keys.is_some_and(|keys| keys.just_pressed(KeyCode::F2)) {
+        return;
+    }
+    for mut visibility in overlay_query.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+///
+/// update_sound_overlay: Bevy system
+///
+/// Lists the channel volumes, the live SFX count and the most recent SoundLog entries
+pub fn update_sound_overlay(
+    log: Res<SoundLog>,
+    channels: Res<AudioChannels>,
+    voice_query: Query<(), With<SfxVoice>>,
+    mut overlay_query: Query<&mut Text, With<SoundDebugOverlay>>,
+) {
+    let Ok(mut text) = overlay_query.get_single_mut() else {
+        return;
+    };
+
+    let mut overlay = format!(
+        "music {:.2}  sfx {:.2}  ambient {:.2}{}\nlive sfx: {}\n",
+        channels.get(AudioChannel::Music),
+        channels.get(AudioChannel::Sfx),
+        channels.get(AudioChannel::Ambient),
+        if channels.muted { "  (muted)" } else { "" },
+        voice_query.iter().count(),
+    );
+    let entries: Vec<_> = log.entries().collect();
+    for entry in entries.iter().rev().take(OVERLAY_ENTRIES) {
+        let outcome = match entry.outcome {
+            SoundOutcome::Skipped(reason) => format!("skipped: {}", reason),
+            outcome => format!("{:?}", outcome).to_lowercase(),
+        };
+        let _ = write!(
+            overlay,
+            "\n#{} {:?} {} - {}",
+            entry.frame, entry.action, entry.name, outcome
+        );
+        if let Some(volume) = entry.volume {
+            let _ = write!(overlay, " @ {:.2}", volume);
+        }
+    }
+
+    text.sections[0].value = overlay;
+}
+
+/// An AudioChannel as the console reads it
+struct ChannelArg(AudioChannel);
+
+impl std::str::FromStr for ChannelArg {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "music" => Ok(ChannelArg(AudioChannel::Music)),
+            "sfx" => Ok(ChannelArg(AudioChannel::Sfx)),
+            "ambient" => Ok(ChannelArg(AudioChannel::Ambient)),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Shows a warning toast for each sound the frame asked for that isn't registered
+pub fn toast_missing_sounds(
+    log: Res<SoundLog>,
+    frame: Option<Res<FrameCount>>,
+    mut toasts: EventWriter<ShowToast>,
+) {
+    let frame = frame.map_or(0, |frame| frame.0);
+    for entry in log.in_frame(frame) {
+        if entry.outcome == SoundOutcome::Missing {
+            toasts.send(ShowToast::warning(format!("Missing sound: {}", entry.name)));
+        }
+    }
+}
+
+/// play_sfx and set_volume for the console
+pub fn register_console_commands(app: &mut App) {
+    app.register_console_command("play_sfx <name>", "Plays a sound effect", |args, world| {
+        let name: String = args.required("name")?;
+        world.send_event(PlaySFX::new(name.clone()));
+        Ok(format!("Playing {}", name))
+    })
+    .register_console_command(
+        "set_volume <music|sfx|ambient> <volume>",
+        "Sets a channel's volume, from 0 to 1",
+        |args, world| {
+            let ChannelArg(channel) = args.required("channel")?;
+            let volume: f32 = args.required("volume")?;
+            world.send_event(SetVolume { channel, volume });
+            Ok(format!("{:?} volume {}", channel, volume))
+        },
+    );
+}
@@ -0,0 +1,178 @@
+use bevy::{ecs::entity::Entities, prelude::*};
+
+///
+/// DuckMusic
+///
+/// * to_level: fraction of the music channel volume to drop to
+/// * attack: seconds to ramp from full volume down to the ducked level
+/// * release: seconds to ramp from the ducked level back to full volume
+/// * hold: seconds to stay ducked once the ramp down completes, or None to stay ducked until StopDucking
+#[derive(Event, Debug, Clone)]
+pub struct DuckMusic {
+    pub to_level: f32,
+    pub attack: f32,
+    pub release: f32,
+    pub hold: Option<f32>,
+}
+
+impl DuckMusic {
+    pub fn new(to_level: f32) -> Self {
+        DuckMusic {
+            to_level: to_level.clamp(0.0, 1.0),
+            attack: DEFAULT_ATTACK,
+            release: DEFAULT_RELEASE,
+            hold: None,
+        }
+    }
+
+    pub fn with_attack(mut self, attack: f32) -> Self {
+        self.attack = attack.max(0.0);
+        self
+    }
+
+    pub fn with_release(mut self, release: f32) -> Self {
+        self.release = release.max(0.0);
+        self
+    }
+
+    pub fn with_hold(mut self, hold: f32) -> Self {
+        self.hold = Some(hold.max(0.0));
+        self
+    }
+}
+
+/// Releases every DuckMusic that is held indefinitely
+#[derive(Event, Default)]
+pub struct StopDucking;
+
+const DEFAULT_ATTACK: f32 = 0.15;
+const DEFAULT_RELEASE: f32 = 0.5;
+
+#[derive(Debug, Clone)]
+struct Duck {
+    to_level: f32,
+    attack: f32,
+    release: f32,
+    // seconds left before the duck releases, None while held indefinitely
+    remaining: Option<f32>,
+    // ducks started by a sound last as long as its entity
+    source: Option<Entity>,
+}
+
+///
+/// MusicDucking
+///
+/// The active duck requests and the level the music is currently ducked to.
+/// Overlapping ducks don't multiply: the deepest one wins until it releases.
+#[derive(Debug, Resource)]
+pub struct MusicDucking {
+    level: f32,
+    release: f32,
+    ducks: Vec<Duck>,
+}
+
+impl Default for MusicDucking {
+    fn default() -> Self {
+        MusicDucking {
+            level: 1.0,
+            release: DEFAULT_RELEASE,
+            ducks: Vec::new(),
+        }
+    }
+}
+
+impl MusicDucking {
+    /// Current multiplier applied on top of the music channel volume
+    pub fn level(&self) -> f32 {
+        self.level
+    }
+
+    pub fn is_ducking(&self) -> bool {
+        !self.ducks.is_empty() || self.level < 1.0
+    }
+
+    /// Duck the music for as long as `source` exists
+    pub fn duck_while(&mut self, source: Entity, to_level: f32) {
+        self.ducks.push(Duck {
+            to_level: to_level.clamp(0.0, 1.0),
+            attack: DEFAULT_ATTACK,
+            release: DEFAULT_RELEASE,
+            remaining: None,
+            source: Some(source),
+        });
+    }
+
+    fn push(&mut self, event: &DuckMusic) {
+        self.ducks.push(Duck {
+            to_level: event.to_level.clamp(0.0, 1.0),
+            attack: event.attack,
+            release: event.release,
+            remaining: event.hold.map(|hold| event.attack + hold),
+            source: None,
+        });
+    }
+}
+
+///
+/// update_ducking: Bevy system
+///
+/// Expires finished ducks and ramps the ducking level toward the deepest active one, in real time
+pub fn update_ducking(
+    time: Res<Time<Real>>,
+    entities: &Entities,
+    mut duck_events: EventReader<DuckMusic>,
+    mut stop_events: EventReader<StopDucking>,
+    mut ducking: ResMut<MusicDucking>,
+) {
+    for event in duck_events.read() {
+        ducking.push(event);
+    }
+    let stop = stop_events.read().count() > 0;
+
+    let delta = time.delta_seconds();
+    let mut release = None;
+    ducking.ducks.retain_mut(|duck| {
+        let alive = match (duck.source, duck.remaining.as_mut()) {
+            (Some(source), _) => entities.contains(source),
+            (None, Some(remaining)) => {
+                *remaining -= delta;
+                *remaining > 0.0
+            }
+            (None, None) => !stop,
+        };
+        if !alive {
+            release = Some(duck.release);
+        }
+        alive
+    });
+    if let Some(release) = release {
+        ducking.release = release;
+    }
+
+    let deepest = ducking
+        .ducks
+        .iter()
+        .min_by(|a, b| a.to_level.total_cmp(&b.to_level))
+        .cloned();
+    ducking.level = match deepest {
+        Some(duck) if ducking.level > duck.to_level => {
+            ramp(ducking.level, duck.to_level, delta, duck.attack)
+        }
+        Some(duck) => ramp(ducking.level, duck.to_level, delta, ducking.release),
+        // clamps to exactly 1.0 once released
+        None => ramp(ducking.level, 1.0, delta, ducking.release),
+    };
+}
+
+/// Moves `level` toward `target` at a rate of the full 0..1 range per `duration` seconds
+fn ramp(level: f32, target: f32, delta: f32, duration: f32) -> f32 {
+    if duration <= 0.0 {
+        return target;
+    }
+    let step = delta / duration;
+    if level > target {
+        (level - step).max(target)
+    } else {
+        (level + step).min(target)
+    }
+}
@@ -0,0 +1,187 @@
+use super::{
+    sfx::view_half_width, AudioChannel, AudioChannels, PositionalAudio, SoundResource, VolumeTweens,
+};
+use crate::gfx::MainCamera;
+use bevy::{
+    audio::{
+        AudioSink, AudioSinkPlayback, AudioSourceBundle, PlaybackMode, PlaybackSettings,
+        SpatialAudioSink, SpatialScale, Volume,
+    },
+    prelude::*,
+};
+
+///
+/// AudioEmitter
+///
+/// Loops a sound for as long as the entity carrying it exists. Removing the component or
+/// despawning the entity stops the sound.
+///
+/// * sound: the name the loop was registered under in SoundResource
+/// * spatial: pan and attenuate with the entity's position relative to the camera
+/// * volume: multiplier on top of the sound's default volume and the ambient channel
+#[derive(Component, Debug, Clone)]
+pub struct AudioEmitter {
+    pub sound: String,
+    pub spatial: bool,
+    pub volume: f32,
+}
+
+impl AudioEmitter {
+    pub fn new(sound: impl Into<String>) -> Self {
+        AudioEmitter {
+            sound: sound.into(),
+            spatial: false,
+            volume: 1.0,
+        }
+    }
+
+    pub fn spatial(mut self) -> Self {
+        self.spatial = true;
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume.max(0.0);
+        self
+    }
+}
+
+/// The looping audio entity playing for an AudioEmitter
+#[derive(Component, Debug)]
+pub struct EmitterSound {
+    pub emitter: Entity,
+}
+
+// where the sound should come from and how loud, given the emitter and listener
+fn emitter_mix(
+    emitter: &AudioEmitter,
+    position: Option<Vec2>,
+    listener: Option<(Vec2, f32)>,
+    positional: &PositionalAudio,
+    level: f32,
+) -> (f32, Option<Vec2>) {
+    let volume = emitter.volume * level;
+    match (emitter.spatial, position, listener) {
+        (true, Some(position), Some((listener, half_width))) => {
+            let offset = position - listener;
+            let falloff = (1.0 - offset.length() / positional.max_distance).max(0.0);
+            // same clamping as positional SFX, so rodio's falloff doesn't stack on ours
+            (
+                volume * falloff,
+                Some(listener + offset.clamp_length_max(half_width)),
+            )
+        }
+        _ => (volume, None),
+    }
+}
+
+pub fn spawn_emitter_sounds(
+    mut commands: Commands,
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    positional: Res<PositionalAudio>,
+    emitter_query: Query<(Entity, &AudioEmitter, Option<&GlobalTransform>), Added<AudioEmitter>>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let listener = camera_query
+        .get_single()
+        .ok()
+        .map(|(transform, projection)| {
+            (
+                transform.translation().truncate(),
+                view_half_width(projection),
+            )
+        });
+
+    for (entity, emitter, transform) in emitter_query.iter() {
+        let Some(handle) = sound_resource.get(&emitter.sound) else {
+            warn!("Emitter sound not found: {}", emitter.sound);
+            continue;
+        };
+        let level = sound_resource.defaults(&emitter.sound).volume
+            * channels.effective(AudioChannel::Ambient, &tweens);
+        let position = transform.map(|transform| transform.translation().truncate());
+        let (volume, emitted_at) = emitter_mix(emitter, position, listener, &positional, level);
+
+        let mut sound = commands.spawn((
+            AudioSourceBundle {
+                source: handle,
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Loop,
+                    volume: Volume::new(volume),
+                    spatial: emitted_at.is_some(),
+                    spatial_scale: listener
+                        .filter(|_| emitted_at.is_some())
+                        .map(|(_, half_width)| SpatialScale::new_2d(1.0 / half_width)),
+                    ..default()
+                },
+            },
+            EmitterSound { emitter: entity },
+        ));
+        if let Some(position) = emitted_at {
+            sound.insert(TransformBundle::from_transform(
+                Transform::from_translation(position.extend(0.0)),
+            ));
+        }
+    }
+}
+
+type EmitterLoop<'a> = (
+    Entity,
+    &'a EmitterSound,
+    Option<&'a mut Transform>,
+    Option<&'a AudioSink>,
+    Option<&'a SpatialAudioSink>,
+);
+
+///
+/// update_emitter_sounds: Bevy system
+///
+/// Keeps each emitter's loop following its entity and the ambient channel (mute included),
+/// and stops loops whose emitter is gone
+#[allow(clippy::too_many_arguments)]
+pub fn update_emitter_sounds(
+    mut commands: Commands,
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    positional: Res<PositionalAudio>,
+    emitter_query: Query<(&AudioEmitter, Option<&GlobalTransform>)>,
+    mut sound_query: Query<EmitterLoop>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let listener = camera_query
+        .get_single()
+        .ok()
+        .map(|(transform, projection)| {
+            (
+                transform.translation().truncate(),
+                view_half_width(projection),
+            )
+        });
+    let ambient = channels.effective(AudioChannel::Ambient, &tweens);
+
+    for (entity, sound, transform, sink, spatial_sink) in sound_query.iter_mut() {
+        let Ok((emitter, emitter_transform)) = emitter_query.get(sound.emitter) else {
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        let level = sound_resource.defaults(&emitter.sound).volume * ambient;
+        let position = emitter_transform.map(|transform| transform.translation().truncate());
+        let (volume, emitted_at) = emitter_mix(emitter, position, listener, &positional, level);
+
+        if let (Some(mut transform), Some(position)) = (transform, emitted_at) {
+            if transform.translation.truncate() != position {
+                transform.translation = position.extend(0.0);
+            }
+        }
+        if let Some(sink) = sink {
+            sink.set_volume(volume);
+        }
+        if let Some(sink) = spatial_sink {
+            sink.set_volume(volume);
+        }
+    }
+}
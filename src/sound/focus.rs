@@ -0,0 +1,92 @@
+use super::{AmbientPlaying, CurrentMusic, EmitterSound, NowPlaying, Stinger};
+use bevy::{
+    audio::{AudioSink, AudioSinkPlayback, SpatialAudioSink},
+    prelude::*,
+    window::{WindowFocused, WindowOccluded},
+};
+
+/// Whether music and ambient sound pause while the window is in the background (on by default)
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct PauseAudioOnFocusLoss(pub bool);
+
+impl Default for PauseAudioOnFocusLoss {
+    fn default() -> Self {
+        PauseAudioOnFocusLoss(true)
+    }
+}
+
+///
+/// AudioFocus
+///
+/// Whether the window is in the background (unfocused, or hidden like a browser tab that
+/// isn't shown), and whether that currently has audio paused
+#[derive(Debug, Resource)]
+pub struct AudioFocus {
+    focused: bool,
+    occluded: bool,
+    paused: bool,
+}
+
+impl Default for AudioFocus {
+    fn default() -> Self {
+        AudioFocus {
+            focused: true,
+            occluded: false,
+            paused: false,
+        }
+    }
+}
+
+impl AudioFocus {
+    pub fn is_backgrounded(&self) -> bool {
+        !self.focused || self.occluded
+    }
+
+    /// True while audio is paused for being in the background. New SFX are dropped meanwhile.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+type AmbientLoops = Or<(With<AmbientPlaying>, With<EmitterSound>, With<Stinger>)>;
+
+///
+/// pause_audio_on_focus_loss: Bevy system
+///
+/// Pauses music and ambient sound while the window is in the background and resumes it on
+/// return. Music that was paused with PauseMusic stays paused.
+#[allow(clippy::too_many_arguments)]
+pub fn pause_audio_on_focus_loss(
+    mut focus_events: EventReader<WindowFocused>,
+    mut occluded_events: EventReader<WindowOccluded>,
+    setting: Res<PauseAudioOnFocusLoss>,
+    mut focus: ResMut<AudioFocus>,
+    current: Res<CurrentMusic>,
+    music_query: Query<&AudioSink, With<NowPlaying>>,
+    ambient_query: Query<&AudioSink, AmbientLoops>,
+    spatial_query: Query<&SpatialAudioSink, With<EmitterSound>>,
+) {
+    if let Some(event) = focus_events.read().last() {
+        focus.focused = event.focused;
+    }
+    if let Some(event) = occluded_events.read().last() {
+        focus.occluded = event.occluded;
+    }
+
+    let paused = setting.0 && focus.is_backgrounded();
+    if paused {
+        // every frame, so tracks started or resumed in the background stay quiet too
+        music_query.iter().for_each(AudioSinkPlayback::pause);
+        ambient_query.iter().for_each(AudioSinkPlayback::pause);
+        spatial_query.iter().for_each(AudioSinkPlayback::pause);
+    } else if focus.paused {
+        if !current.is_paused() {
+            music_query.iter().for_each(AudioSinkPlayback::play);
+        }
+        ambient_query.iter().for_each(AudioSinkPlayback::play);
+        spatial_query.iter().for_each(AudioSinkPlayback::play);
+    }
+    if focus.paused != paused {
+        focus.paused = paused;
+    }
+}
@@ -0,0 +1,142 @@
+use super::PlaySFX;
+use crate::{gfx::Animation, map::TileProbe};
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+///
+/// StepTrigger
+///
+/// * Distance: a step every this many world units travelled
+/// * Frames: a step whenever the actor's Animation reaches one of these frames
+#[derive(Debug, Clone)]
+pub enum StepTrigger {
+    Distance(f32),
+    Frames(Vec<usize>),
+}
+
+///
+/// Footsteps
+///
+/// * trigger: what makes a step land
+/// * foot_offset: offset from the actor's position to its feet, where the tile is looked up
+#[derive(Debug, Clone, Component)]
+pub struct Footsteps {
+    pub trigger: StepTrigger,
+    pub foot_offset: Vec2,
+    travelled: f32,
+    last_position: Option<Vec2>,
+    last_frame: Option<usize>,
+}
+
+impl Footsteps {
+    pub fn every(distance: f32) -> Self {
+        Footsteps::new(StepTrigger::Distance(distance.max(f32::EPSILON)))
+    }
+
+    pub fn on_frames(frames: Vec<usize>) -> Self {
+        Footsteps::new(StepTrigger::Frames(frames))
+    }
+
+    pub fn with_foot_offset(mut self, foot_offset: Vec2) -> Self {
+        self.foot_offset = foot_offset;
+        self
+    }
+
+    fn new(trigger: StepTrigger) -> Self {
+        Footsteps {
+            trigger,
+            foot_offset: Vec2::ZERO,
+            travelled: 0.0,
+            last_position: None,
+            last_frame: None,
+        }
+    }
+
+    /// Advances the trigger and returns true if a step lands this frame
+    fn step(&mut self, position: Vec2, frame: Option<usize>) -> bool {
+        let moved = self
+            .last_position
+            .map_or(0.0, |last| position.distance(last));
+        self.last_position = Some(position);
+
+        match &self.trigger {
+            StepTrigger::Distance(stride) => {
+                // a teleport or respawn isn't a walk
+                if moved > stride * 4.0 {
+                    self.travelled = 0.0;
+                    return false;
+                }
+                self.travelled += moved;
+                if self.travelled < *stride {
+                    return false;
+                }
+                self.travelled %= stride;
+                true
+            }
+            StepTrigger::Frames(frames) => {
+                let entered = frame.is_some() && frame != self.last_frame;
+                self.last_frame = frame;
+                entered && frame.is_some_and(|frame| frames.contains(&frame))
+            }
+        }
+    }
+}
+
+///
+/// FootstepSurfaces
+///
+/// * property: the custom tile property naming the surface (`surface = "grass"`)
+/// * groups: SFX group to play for each surface
+/// * default_group: SFX group for tiles without a known surface
+#[derive(Debug, Clone, Resource)]
+pub struct FootstepSurfaces {
+    pub property: String,
+    pub groups: HashMap<String, String>,
+    pub default_group: String,
+}
+
+impl Default for FootstepSurfaces {
+    fn default() -> Self {
+        FootstepSurfaces {
+            property: "surface".to_string(),
+            groups: HashMap::new(),
+            default_group: "footstep".to_string(),
+        }
+    }
+}
+
+impl FootstepSurfaces {
+    pub fn insert(&mut self, surface: String, group: String) {
+        self.groups.insert(surface, group);
+    }
+
+    /// The SFX group for a surface, or the default group
+    pub fn group_for(&self, surface: Option<&str>) -> &str {
+        surface
+            .and_then(|surface| self.groups.get(surface))
+            .unwrap_or(&self.default_group)
+    }
+}
+
+///
+/// play_footsteps: Bevy system
+///
+/// Sends PlaySFX for the surface under an actor's feet whenever one of its steps lands
+pub fn play_footsteps(
+    tiles: TileProbe,
+    surfaces: Res<FootstepSurfaces>,
+    mut actor_query: Query<(&mut Footsteps, &GlobalTransform, Option<&Animation>)>,
+    mut events: EventWriter<PlaySFX>,
+) {
+    for (mut footsteps, transform, animation) in actor_query.iter_mut() {
+        let position = transform.translation().truncate();
+        if !footsteps.step(position, animation.map(Animation::frame)) {
+            continue;
+        }
+
+        // the tile where the foot is now, not where the step started
+        let feet = position + footsteps.foot_offset;
+        let surface = tiles.string_property_at(feet, &surfaces.property);
+        events.send(PlaySFX::at(surfaces.group_for(surface), feet));
+    }
+}
@@ -0,0 +1,62 @@
+use super::{MusicFade, NowPlaying, PlayMusic, Playlist};
+use bevy::{
+    audio::{AudioSink, AudioSinkPlayback},
+    prelude::*,
+};
+
+/// Sent once when a track played with `looping: false` reaches its end
+#[derive(Event, Debug, Clone)]
+pub struct MusicFinished {
+    pub name: String,
+}
+
+/// What to go back to once a one-shot track is over
+#[derive(Debug, Clone)]
+pub enum ResumeAfter {
+    Track(String),
+    Playlist,
+}
+
+/// Marks a music entity that plays once, and what it hands back to
+#[derive(Debug, Component)]
+pub struct MusicOnce {
+    pub resume: Option<ResumeAfter>,
+}
+
+///
+/// finish_music: Bevy system
+///
+/// Despawns one-shot tracks that reached their end, sends MusicFinished for each, and picks
+/// the remembered music back up
+pub fn finish_music(
+    mut commands: Commands,
+    mut finished_events: EventWriter<MusicFinished>,
+    mut music_events: EventWriter<PlayMusic>,
+    mut playlist: ResMut<Playlist>,
+    once_query: Query<(
+        Entity,
+        &NowPlaying,
+        &MusicOnce,
+        &AudioSink,
+        Option<&MusicFade>,
+    )>,
+) {
+    for (entity, playing, once, sink, fade) in once_query.iter() {
+        // faded out tracks were stopped or replaced, they didn't finish
+        if !sink.empty() || fade.is_some_and(MusicFade::is_fading_out) {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        finished_events.send(MusicFinished {
+            name: playing.name.clone(),
+        });
+        match &once.resume {
+            Some(ResumeAfter::Track(name)) => {
+                music_events.send(PlayMusic::new(name.clone()));
+            }
+            Some(ResumeAfter::Playlist) => playlist.resume(),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use super::{
+    AudioFocus, AudioUnlocked, PendingAudio, PlayMusic, PlaySFX, SoundConfig, SoundManifests,
+    SoundResource,
+};
+use bevy::{asset::LoadState, ecs::system::SystemParam, prelude::*};
+
+/// Where an AudioSource handle is in loading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AudioLoad {
+    Ready,
+    Loading,
+    Failed,
+}
+
+impl AudioLoad {
+    pub(crate) fn of(asset_server: &AssetServer, handle: &Handle<AudioSource>) -> Self {
+        // handles added straight to Assets aren't tracked by the server and are ready as is
+        match asset_server.get_load_state(handle) {
+            Some(LoadState::NotLoaded) | Some(LoadState::Loading) => AudioLoad::Loading,
+            Some(LoadState::Failed) => AudioLoad::Failed,
+            Some(LoadState::Loaded) | None => AudioLoad::Ready,
+        }
+    }
+
+    /// The state of a set of handles: loading while any of them is
+    pub(crate) fn of_all(asset_server: &AssetServer, handles: &[Handle<AudioSource>]) -> Self {
+        let states: Vec<AudioLoad> = handles
+            .iter()
+            .map(|handle| AudioLoad::of(asset_server, handle))
+            .collect();
+        if states.contains(&AudioLoad::Loading) {
+            AudioLoad::Loading
+        } else if states.contains(&AudioLoad::Failed) {
+            AudioLoad::Failed
+        } else {
+            AudioLoad::Ready
+        }
+    }
+}
+
+///
+/// LoadingAudio
+///
+/// Requests whose sound hadn't finished loading when they were made. They are sent
+/// again once it has: music no matter how long that takes, SFX only within
+/// SoundPlugin::sfx_load_wait seconds of the request.
+#[derive(Debug, Default, Resource)]
+pub struct LoadingAudio {
+    music: Option<PlayMusic>,
+    sfx: Vec<(f64, PlaySFX)>,
+}
+
+impl LoadingAudio {
+    /// Replaces any music already waiting, since only the latest request should play
+    pub(crate) fn queue_music(&mut self, event: PlayMusic) {
+        self.music = Some(event);
+    }
+
+    pub(crate) fn cancel_music(&mut self) {
+        self.music = None;
+    }
+
+    pub(crate) fn queue_sfx(&mut self, now: f64, event: PlaySFX) {
+        self.sfx.push((now, event));
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.music.is_some() || !self.sfx.is_empty()
+    }
+}
+
+///
+/// retry_loading_audio: Bevy system
+///
+/// Resends queued requests whose sounds finished loading, and drops SFX that waited too long
+pub fn retry_loading_audio(
+    time: Res<Time<Real>>,
+    asset_server: Res<AssetServer>,
+    sound_resource: Res<SoundResource>,
+    config: Res<SoundConfig>,
+    mut loading: ResMut<LoadingAudio>,
+    mut music_events: EventWriter<PlayMusic>,
+    mut sfx_events: EventWriter<PlaySFX>,
+) {
+    if let Some(event) = loading.music.take() {
+        let handles = match event.resolve(&sound_resource) {
+            Some((handle, Some(intro))) => vec![handle, intro.looped],
+            Some((handle, None)) => vec![handle],
+            None => Vec::new(),
+        };
+        if AudioLoad::of_all(&asset_server, &handles) == AudioLoad::Loading {
+            loading.music = Some(event);
+        } else {
+            // play_music reports a failed load
+            music_events.send(event);
+        }
+    }
+
+    let now = time.elapsed_seconds_f64();
+    let wait = config.sfx_load_wait.unwrap_or(0.0) as f64;
+    let mut still_loading = Vec::new();
+    for (requested, event) in loading.sfx.drain(..) {
+        let handles = event.handles(&sound_resource);
+        if AudioLoad::of_all(&asset_server, &handles) != AudioLoad::Loading {
+            sfx_events.send(event);
+        } else if now - requested < wait {
+            still_loading.push((requested, event));
+        } else {
+            warn!("Dropping sound that is still loading: {}", event.name);
+        }
+    }
+    loading.sfx = still_loading;
+}
+
+///
+/// PlaybackGate: Bevy system parameter
+///
+/// What decides whether a request can start a sound now: the autoplay gate, the window
+/// being in the background, and the load state of its source
+#[derive(SystemParam)]
+pub struct PlaybackGate<'w> {
+    pub unlocked: Res<'w, AudioUnlocked>,
+    pub focus: Res<'w, AudioFocus>,
+    pub pending: ResMut<'w, PendingAudio>,
+    pub asset_server: Res<'w, AssetServer>,
+    pub waiting: ResMut<'w, LoadingAudio>,
+}
+
+///
+/// SoundLoadProgress
+///
+/// How far along loading every sound registered in SoundResource is, for loading screens
+///
+/// * loaded: sounds ready to play
+/// * total: sounds registered so far, manifest entries included
+/// * failed: names of the sounds that failed to load, sorted
+#[derive(Debug, Default, Clone, Resource)]
+pub struct SoundLoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+    pub failed: Vec<String>,
+}
+
+impl SoundLoadProgress {
+    /// Fraction of the sounds that are done loading, failed ones included
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed.len()) as f32 / self.total as f32
+        }
+    }
+}
+
+/// Sent once, the first time every manifest is applied and no sound is still loading
+#[derive(Event, Debug, Default)]
+pub struct AllSoundsLoaded;
+
+pub fn update_load_progress(
+    asset_server: Res<AssetServer>,
+    sound_resource: Res<SoundResource>,
+    manifests: Res<SoundManifests>,
+    mut progress: ResMut<SoundLoadProgress>,
+    mut loaded_events: EventWriter<AllSoundsLoaded>,
+    mut sent: Local<bool>,
+) {
+    let mut loaded = 0;
+    let mut total = 0;
+    let mut failed = Vec::new();
+    for (name, handle) in sound_resource.iter() {
+        total += 1;
+        match AudioLoad::of(&asset_server, handle) {
+            AudioLoad::Ready => loaded += 1,
+            AudioLoad::Failed => failed.push(name.to_string()),
+            AudioLoad::Loading => {}
+        }
+    }
+    failed.sort();
+
+    if progress.loaded != loaded || progress.total != total || progress.failed != failed {
+        *progress = SoundLoadProgress {
+            loaded,
+            total,
+            failed,
+        };
+    }
+
+    if !*sent && manifests.is_applied() && progress.loaded + progress.failed.len() == total {
+        *sent = true;
+        loaded_events.send(AllSoundsLoaded);
+    }
+}
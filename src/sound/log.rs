@@ -0,0 +1,105 @@
+use bevy::{core::FrameCount, ecs::system::SystemParam, prelude::*};
+use std::collections::VecDeque;
+
+/// Number of actions the SoundLog keeps
+pub const SOUND_LOG_CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundAction {
+    PlaySfx,
+    StopSfx,
+    PlayMusic,
+    StopMusic,
+    PlayAmbient,
+    StopAmbient,
+    PlayStinger,
+}
+
+///
+/// SoundOutcome
+///
+/// * Played: the request started a sound
+/// * Missing: the name isn't registered in SoundResource
+/// * Queued: held back until audio unlocks or the file loads
+/// * Skipped: dropped on purpose, with the reason
+/// * Stopped: a stop request was carried out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundOutcome {
+    Played,
+    Missing,
+    Queued,
+    Skipped(&'static str),
+    Stopped,
+}
+
+#[derive(Debug, Clone)]
+pub struct SoundLogEntry {
+    pub action: SoundAction,
+    pub name: String,
+    pub outcome: SoundOutcome,
+    /// volume the sound started at, for played sounds
+    pub volume: Option<f32>,
+    pub frame: u32,
+}
+
+///
+/// SoundLog
+///
+/// The last SOUND_LOG_CAPACITY requests the sound systems acted on, oldest first
+#[derive(Debug, Default, Resource)]
+pub struct SoundLog {
+    entries: VecDeque<SoundLogEntry>,
+}
+
+impl SoundLog {
+    pub fn entries(&self) -> impl Iterator<Item = &SoundLogEntry> {
+        self.entries.iter()
+    }
+
+    /// The entries recorded during one frame, see FrameCount
+    pub fn in_frame(&self, frame: u32) -> impl Iterator<Item = &SoundLogEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.frame == frame)
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn push(&mut self, entry: SoundLogEntry) {
+        if self.entries.len() == SOUND_LOG_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+}
+
+///
+/// SoundLogger: Bevy system parameter
+///
+/// Records entries in the SoundLog, stamped with the current frame
+#[derive(SystemParam)]
+pub struct SoundLogger<'w> {
+    log: ResMut<'w, SoundLog>,
+    frame: Option<Res<'w, FrameCount>>,
+}
+
+impl<'w> SoundLogger<'w> {
+    pub fn record(
+        &mut self,
+        action: SoundAction,
+        name: &str,
+        outcome: SoundOutcome,
+        volume: Option<f32>,
+    ) {
+        let frame = self.frame.as_ref().map_or(0, |frame| frame.0);
+        self.log.push(SoundLogEntry {
+            action,
+            name: name.to_string(),
+            outcome,
+            volume,
+            frame,
+        });
+    }
+}
@@ -0,0 +1,309 @@
+use super::{Caption, CaptionImportance, MusicTempo, SoundDefaults, SoundResource};
+use crate::manifest::items;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext, LoadState},
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use thiserror::Error;
+
+///
+/// SoundEntry
+///
+/// One sound of a `.sounds.ron` manifest. Everything but name and path is optional:
+///
+/// (name: "hit", path: "sfx/hit.ogg", volume: Some(0.8), group: Some("impacts"))
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SoundEntry {
+    pub name: String,
+    pub path: String,
+    #[serde(default)]
+    pub volume: Option<f32>,
+    #[serde(default)]
+    pub speed: Option<f32>,
+    #[serde(default)]
+    pub speed_jitter: Option<f32>,
+    #[serde(default)]
+    pub spatial: Option<bool>,
+    #[serde(default)]
+    pub min_interval: Option<f32>,
+    #[serde(default)]
+    pub duck_music: Option<f32>,
+    /// variation group the sound also plays under
+    #[serde(default)]
+    pub group: Option<String>,
+    /// tempo of a music track, for OnBeat
+    #[serde(default)]
+    pub bpm: Option<f32>,
+    /// seconds to the first beat
+    #[serde(default)]
+    pub beat_offset: Option<f32>,
+    #[serde(default)]
+    pub beats_per_bar: Option<u32>,
+    /// shown to players with captions enabled, e.g. "[door creaks]"
+    #[serde(default)]
+    pub caption: Option<String>,
+    #[serde(default)]
+    pub importance: Option<CaptionImportance>,
+}
+
+impl SoundEntry {
+    fn defaults(&self) -> SoundDefaults {
+        let fallback = SoundDefaults::default();
+        SoundDefaults {
+            volume: self.volume.unwrap_or(fallback.volume),
+            speed: self.speed.unwrap_or(fallback.speed),
+            speed_jitter: self.speed_jitter.map_or(fallback.speed_jitter, f32::abs),
+            spatial: self.spatial.unwrap_or(fallback.spatial),
+            min_interval: self.min_interval,
+            duck_music: self.duck_music,
+        }
+    }
+
+    fn tempo(&self) -> Option<MusicTempo> {
+        let mut tempo = MusicTempo::new(self.bpm?).with_offset(self.beat_offset.unwrap_or(0.0));
+        if let Some(beats_per_bar) = self.beats_per_bar {
+            tempo = tempo.with_beats_per_bar(beats_per_bar);
+        }
+        Some(tempo)
+    }
+}
+
+/// The entries of a `.sounds.ron` file that parsed
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct SoundManifest {
+    pub entries: Vec<SoundEntry>,
+}
+
+#[derive(Default)]
+pub struct SoundManifestLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum SoundManifestLoaderError {
+    #[error("Could not read the manifest: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the manifest: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for SoundManifestLoader {
+    type Asset = SoundManifest;
+    type Settings = ();
+    type Error = SoundManifestLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+
+            // parse entry by entry so one bad line doesn't take every sound down with it. The
+            // entries are read from their own text, ron::Value forgetting enum variant names.
+            let values: Vec<ron::Value> = ron::de::from_bytes(&bytes)?;
+            let text = String::from_utf8_lossy(&bytes);
+            let mut entries = Vec::with_capacity(values.len());
+            for (index, (value, item)) in values.iter().zip(items(&text)).enumerate() {
+                let name = entry_name(value).unwrap_or_else(|| format!("#{}", index));
+                match ron::from_str::<SoundEntry>(item) {
+                    Ok(entry) => entries.push(entry),
+                    Err(e) => error!(
+                        "{}: skipping sound entry {}: {}",
+                        load_context.path().display(),
+                        name,
+                        e
+                    ),
+                }
+            }
+            Ok(SoundManifest { entries })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["sounds.ron"]
+    }
+}
+
+/// The name field of an entry that may not otherwise deserialize
+fn entry_name(value: &ron::Value) -> Option<String> {
+    let ron::Value::Map(map) = value else {
+        return None;
+    };
+    let (_, name) = map
+        .iter()
+        .find(|(key, _)| matches!(key, ron::Value::String(key) if key == "name"))?;
+    match name {
+        ron::Value::String(name) => Some(format!("\"{}\"", name)),
+        _ => None,
+    }
+}
+
+///
+/// SoundManifests
+///
+/// Manifests requested at startup, and the sounds they registered that are still loading
+#[derive(Debug, Default, Resource)]
+pub struct SoundManifests {
+    pending: Vec<Handle<SoundManifest>>,
+    applied: Vec<Handle<SoundManifest>>,
+    loading: Vec<(String, Handle<AudioSource>)>,
+}
+
+impl SoundManifests {
+    pub fn load(&mut self, asset_server: &AssetServer, path: String) {
+        self.pending.push(asset_server.load(path));
+    }
+
+    /// The manifests whose entries are registered
+    pub fn applied(&self) -> &[Handle<SoundManifest>] {
+        &self.applied
+    }
+
+    /// True once every manifest has loaded and registered its entries
+    pub fn is_applied(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// True once every manifest has been applied and all of its sounds finished loading
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty() && self.loading.is_empty()
+    }
+}
+
+///
+/// apply_sound_manifests: Bevy system
+///
+/// Registers the entries of each manifest in SoundResource once it has loaded
+pub fn apply_sound_manifests(
+    asset_server: Res<AssetServer>,
+    manifest_assets: Res<Assets<SoundManifest>>,
+    mut manifests: ResMut<SoundManifests>,
+    mut sound_resource: ResMut<SoundResource>,
+) {
+    let mut applied = Vec::new();
+    let mut still_pending = Vec::new();
+    for handle in std::mem::take(&mut manifests.pending) {
+        if let Some(manifest) = manifest_assets.get(&handle) {
+            register_entries(&asset_server, &mut sound_resource, manifest, &mut applied);
+            manifests.applied.push(handle);
+        } else if let Some(LoadState::Failed) = asset_server.get_load_state(&handle) {
+            // the loader already logged why
+            error!("Sound manifest failed to load: {:?}", handle.path());
+        } else {
+            still_pending.push(handle);
+        }
+    }
+    manifests.pending = still_pending;
+    manifests.loading.extend(applied);
+}
+
+/// Brings SoundResource up to date with a manifest that changed from `old` to `new`: its
+/// entries are registered again and those it no longer lists are forgotten. Returns what changed,
+/// e.g. "sound hit changed".
+pub fn patch_sounds(
+    asset_server: &AssetServer,
+    sound_resource: &mut SoundResource,
+    manifests: &mut SoundManifests,
+    old: &SoundManifest,
+    new: &SoundManifest,
+) -> Vec<String> {
+    let by_name = |manifest: &SoundManifest| -> BTreeMap<String, SoundEntry> {
+        manifest
+            .entries
+            .iter()
+            .map(|entry| (entry.name.clone(), entry.clone()))
+            .collect()
+    };
+    let old_entries = by_name(old);
+    let new_entries = by_name(new);
+    let mut changes = Vec::new();
+    for name in old_entries.keys() {
+        if !new_entries.contains_key(name) {
+            sound_resource.remove(name);
+            changes.push(format!("sound {} removed", name));
+        }
+    }
+    for (name, entry) in new_entries.iter() {
+        match old_entries.get(name) {
+            None => changes.push(format!("sound {} added", name)),
+            Some(old_entry) if old_entry != entry => {
+                changes.push(format!("sound {} changed", name))
+            }
+            Some(_) => {}
+        }
+    }
+    let new_groups: Vec<&String> = new
+        .entries
+        .iter()
+        .filter_map(|e| e.group.as_ref())
+        .collect();
+    for group in old.entries.iter().filter_map(|e| e.group.as_ref()) {
+        if !new_groups.contains(&group) && !new_entries.contains_key(group) {
+            sound_resource.remove(group);
+        }
+    }
+    register_entries(asset_server, sound_resource, new, &mut manifests.loading);
+    changes
+}
+
+fn register_entries(
+    asset_server: &AssetServer,
+    sound_resource: &mut SoundResource,
+    manifest: &SoundManifest,
+    loading: &mut Vec<(String, Handle<AudioSource>)>,
+) {
+    let mut groups: HashMap<String, Vec<Handle<AudioSource>>> = HashMap::new();
+    for entry in manifest.entries.iter() {
+        let handle: Handle<AudioSource> = asset_server.load(entry.path.clone());
+        sound_resource.insert_with_defaults(entry.name.clone(), handle.clone(), entry.defaults());
+        if let Some(tempo) = entry.tempo() {
+            sound_resource.insert_tempo(entry.name.clone(), tempo);
+        }
+        if let Some(text) = &entry.caption {
+            sound_resource.set_caption(
+                entry.name.clone(),
+                Caption {
+                    text: text.clone(),
+                    importance: entry.importance.unwrap_or_default(),
+                },
+            );
+        }
+        if let Some(group) = &entry.group {
+            if !groups.contains_key(group) {
+                // a group plays with the settings of its first member
+                sound_resource.set_defaults(group.clone(), entry.defaults());
+            }
+            groups
+                .entry(group.clone())
+                .or_default()
+                .push(handle.clone());
+        }
+        loading.push((entry.name.clone(), handle));
+    }
+    for (name, members) in groups {
+        sound_resource.insert_group(name, members);
+    }
+}
+
+///
+/// report_failed_sounds: Bevy system
+///
+/// Logs manifest sounds whose file failed to load, by entry name
+pub fn report_failed_sounds(asset_server: Res<AssetServer>, mut manifests: ResMut<SoundManifests>) {
+    manifests
+        .loading
+        .retain(|(name, handle)| match asset_server.get_load_state(handle) {
+            Some(LoadState::Loaded) => false,
+            Some(LoadState::Failed) => {
+                error!("Sound \"{}\" failed to load from {:?}", name, handle.path());
+                false
+            }
+            _ => true,
+        });
+}
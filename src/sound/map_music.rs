@@ -0,0 +1,34 @@
+use super::{CurrentMusic, PlayAmbient, PlayMusic};
+use crate::helpers::tiled::MapLoaded;
+use bevy::prelude::*;
+
+/// Map property naming the music track to play on the map
+pub const MUSIC_PROPERTY: &str = "music";
+/// Map property naming the ambient loop to play on the map
+pub const AMBIENT_PROPERTY: &str = "ambient";
+/// Seconds to crossfade into a map's music, and to fade its ambient loop in
+const MAP_FADE: f32 = 1.0;
+
+///
+/// play_map_music: Bevy system
+///
+/// Starts the music and ambient loop a loaded map names in its properties. Maps without
+/// them leave whatever is playing alone.
+pub fn play_map_music(
+    mut map_events: EventReader<MapLoaded>,
+    current: Res<CurrentMusic>,
+    mut music_events: EventWriter<PlayMusic>,
+    mut ambient_events: EventWriter<PlayAmbient>,
+) {
+    for event in map_events.read() {
+        if let Some(music) = event.properties.get_string(MUSIC_PROPERTY) {
+            if current.name() != Some(music) {
+                music_events.send(PlayMusic::new(music).with_crossfade(MAP_FADE));
+            }
+        }
+        if let Some(ambient) = event.properties.get_string(AMBIENT_PROPERTY) {
+            // play_ambient already skips loops that are running
+            ambient_events.send(PlayAmbient::new(ambient).with_fade_in(MAP_FADE));
+        }
+    }
+}
@@ -0,0 +1,1221 @@
+use crate::helpers::tiled::MapLoaded;
+use crate::input::{Action, ActionState};
+use crate::rng::GameRng;
+use crate::settings::SettingsChanged;
+use crate::state::TimeScale;
+use bevy::{
+    app::{App, Plugin},
+    asset::AssetServer,
+    audio::{
+        AudioSink, AudioSinkPlayback, AudioSource, AudioSourceBundle, PlaybackMode,
+        PlaybackSettings, Volume,
+    },
+    prelude::*,
+    window::{WindowFocused, WindowOccluded},
+};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+mod ambient;
+mod beat;
+mod captions;
+mod current;
+#[cfg(feature = "dev")]
+mod debug;
+mod ducking;
+mod emitter;
+mod focus;
+mod footsteps;
+mod jingle;
+mod loading;
+mod log;
+mod manifest;
+mod map_music;
+mod playlist;
+mod scheduler;
+mod settings;
+mod sfx;
+mod stinger;
+mod tween;
+mod unlock;
+
+pub use ambient::{AmbientPlaying, PlayAmbient, StopAmbient};
+pub use beat::{BeatClock, MusicTempo, OnBeat};
+pub use captions::{Caption, CaptionImportance, CaptionsEnabled, SoundCaption};
+pub use current::{CurrentMusic, MusicTrack, PauseMusic, ResumeMusic};
+pub use ducking::{DuckMusic, MusicDucking, StopDucking};
+pub use emitter::{AudioEmitter, EmitterSound};
+pub use focus::{AudioFocus, PauseAudioOnFocusLoss};
+pub use footsteps::{FootstepSurfaces, Footsteps, StepTrigger};
+pub use jingle::{MusicFinished, MusicOnce, ResumeAfter};
+pub use loading::{AllSoundsLoaded, LoadingAudio, SoundLoadProgress};
+use log::SoundLogger;
+pub use log::{SoundAction, SoundLog, SoundLogEntry, SoundOutcome, SOUND_LOG_CAPACITY};
+pub use manifest::{patch_sounds, SoundEntry, SoundManifest, SoundManifestLoader, SoundManifests};
+pub use playlist::{NextTrack, PlayPlaylist, Playlist, PreviousTrack};
+pub use scheduler::{AmbientOneShot, AmbientScheduler};
+pub use sfx::{
+    OneShotSfx, PlaySFX, PositionalAudio, SfxFadeOut, SfxPriority, SfxRateLimiter, SfxTag,
+    SfxVoice, StopSFX,
+};
+pub use stinger::{PlayStinger, Stinger, Stingers};
+pub use tween::{Easing, TweenVolume, VolumeTweens};
+pub use unlock::{AudioUnlocked, PendingAudio};
+
+///
+/// SoundPlugin
+///
+/// * verbose: debug-log requests the sound module drops on purpose (e.g. rate limited SFX)
+/// * min_sfx_interval: seconds that must pass before the same SFX can play again, unless
+///   the sound overrides it in its SoundDefaults
+/// * max_sfx_voices: maximum number of SFX playing at once (music doesn't count)
+/// * persist_settings: load the channel volumes and mute flag from storage, and save them
+///   whenever they change. Off when SettingsPlugin saves them along with the other settings.
+/// * manifests: `.sounds.ron` files to register sounds from at startup
+/// * sfx_load_wait: seconds an SFX requested before its file loaded may wait for it, or None
+///   to drop it right away (music always waits)
+/// * pitch_follows_time_scale: play sounds faster and higher, or slower and lower, with the
+///   TimeScale. Off, sound ignores slow motion.
+#[derive(Debug, Clone)]
+pub struct SoundPlugin {
+    pub verbose: bool,
+    pub min_sfx_interval: f32,
+    pub max_sfx_voices: usize,
+    pub persist_settings: bool,
+    pub manifests: Vec<String>,
+    pub sfx_load_wait: Option<f32>,
+    pub pitch_follows_time_scale: bool,
+}
+
+impl Default for SoundPlugin {
+    fn default() -> Self {
+        SoundPlugin {
+            verbose: false,
+            min_sfx_interval: 0.05,
+            max_sfx_voices: 24,
+            persist_settings: true,
+            manifests: vec!["sounds/game.sounds.ron".to_string()],
+            sfx_load_wait: Some(3.0),
+            pitch_follows_time_scale: false,
+        }
+    }
+}
+
+/// The SoundPlugin settings, available to the sound systems
+#[derive(Debug, Clone, Resource)]
+pub struct SoundConfig {
+    pub verbose: bool,
+    pub min_sfx_interval: f32,
+    pub max_sfx_voices: usize,
+    pub manifests: Vec<String>,
+    pub sfx_load_wait: Option<f32>,
+    pub pitch_follows_time_scale: bool,
+}
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SoundConfig {
+            verbose: self.verbose,
+            min_sfx_interval: self.min_sfx_interval,
+            max_sfx_voices: self.max_sfx_voices,
+            manifests: self.manifests.clone(),
+            sfx_load_wait: self.sfx_load_wait,
+            pitch_follows_time_scale: self.pitch_follows_time_scale,
+        })
+        .insert_resource(SoundResource::new())
+        .init_asset::<SoundManifest>()
+        .init_asset_loader::<SoundManifestLoader>()
+        .init_resource::<SoundManifests>()
+        .init_resource::<Playlist>()
+        .init_resource::<PositionalAudio>()
+        .init_resource::<SfxRateLimiter>()
+        .init_resource::<MusicDucking>()
+        .init_resource::<AudioUnlocked>()
+        .init_resource::<PendingAudio>()
+        .init_resource::<LoadingAudio>()
+        .init_resource::<CurrentMusic>()
+        .init_resource::<BeatClock>()
+        .init_resource::<FootstepSurfaces>()
+        .init_resource::<CaptionsEnabled>()
+        .init_resource::<captions::RecentCaptions>()
+        .init_resource::<SoundLog>()
+        .init_resource::<VolumeTweens>()
+        .init_resource::<Stingers>()
+        .init_resource::<AmbientScheduler>()
+        .init_resource::<PauseAudioOnFocusLoss>()
+        .init_resource::<AudioFocus>()
+        .init_resource::<SoundLoadProgress>()
+        .add_event::<PlaySFX>()
+        .add_event::<StopSFX>()
+        .add_event::<PlayMusic>()
+        .add_event::<StopMusic>()
+        .add_event::<SetVolume>()
+        .add_event::<PlayPlaylist>()
+        .add_event::<NextTrack>()
+        .add_event::<PreviousTrack>()
+        .add_event::<DuckMusic>()
+        .add_event::<StopDucking>()
+        .add_event::<ToggleMute>()
+        .add_event::<SetMuted>()
+        .add_event::<PauseMusic>()
+        .add_event::<ResumeMusic>()
+        .add_event::<MusicFinished>()
+        .add_event::<OnBeat>()
+        .add_event::<PlayAmbient>()
+        .add_event::<StopAmbient>()
+        .add_event::<SoundCaption>()
+        .add_event::<TweenVolume>()
+        .add_event::<PlayStinger>()
+        .add_event::<AllSoundsLoaded>()
+        // also added by TiledMapPlugin and WindowPlugin, but the sound module works without them
+        .add_event::<MapLoaded>()
+        .add_event::<WindowFocused>()
+        .add_event::<WindowOccluded>()
+        .add_event::<SettingsChanged>()
+        .add_systems(Startup, load_sound_manifests)
+        .add_systems(PreUpdate, fork_group_rng.run_if(resource_added::<GameRng>))
+        .add_systems(
+            Update,
+            (
+                manifest::apply_sound_manifests,
+                manifest::report_failed_sounds.after(manifest::apply_sound_manifests),
+                loading::update_load_progress.after(manifest::apply_sound_manifests),
+                loading::retry_loading_audio
+                    .before(sfx::play_sfx)
+                    .before(play_music),
+                // runs before anything that applies channel levels to sinks
+                tween::tween_volume
+                    .before(apply_music_volume)
+                    .before(sfx::apply_sfx_volume)
+                    .before(ambient::apply_ambient_volume)
+                    .before(emitter::update_emitter_sounds),
+                stinger::play_stingers
+                    .before(current::pause_music)
+                    .before(ducking::update_ducking),
+                scheduler::schedule_ambient_one_shots.before(sfx::play_sfx),
+                focus::pause_audio_on_focus_loss
+                    .after(current::pause_music)
+                    .after(stinger::play_stingers)
+                    .before(sfx::play_sfx),
+                map_music::play_map_music
+                    .before(play_music)
+                    .before(ambient::play_ambient),
+                stinger::apply_stinger_volume
+                    .after(set_volume)
+                    .after(set_muted)
+                    .after(tween::tween_volume),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                current::update_current_music
+                    .after(play_music)
+                    .after(stop_music)
+                    .after(playlist::play_playlist)
+                    .after(playlist::update_playlist)
+                    .after(update_music_fades),
+                current::pause_music.after(current::update_current_music),
+                beat::update_beat_clock.after(current::pause_music),
+                ambient::play_ambient.run_if(on_event::<PlayAmbient>()),
+                ambient::stop_ambient
+                    .run_if(on_event::<StopAmbient>())
+                    .after(ambient::play_ambient),
+                footsteps::play_footsteps.before(sfx::play_sfx),
+                ambient::apply_ambient_volume
+                    .after(ambient::stop_ambient)
+                    .after(update_music_fades)
+                    .after(set_muted),
+                emitter::spawn_emitter_sounds,
+                emitter::update_emitter_sounds
+                    .after(emitter::spawn_emitter_sounds)
+                    .after(set_muted)
+                    .after(set_volume),
+                jingle::finish_music
+                    .after(advance_music_intros)
+                    .before(playlist::update_playlist)
+                    .before(play_music),
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                settings::apply_audio_settings.before(set_volume),
+                settings::apply_caption_settings,
+            ),
+        )
+        .add_systems(
+            Update,
+            (
+                set_volume.run_if(on_event::<SetVolume>()),
+                toggle_mute_on_key,
+                set_muted.after(toggle_mute_on_key),
+                sfx::apply_sfx_volume.after(set_volume).after(set_muted),
+                unlock::unlock_audio_on_input.run_if(unlock::audio_locked),
+                unlock::flush_pending_audio
+                    .run_if(resource_changed::<AudioUnlocked>)
+                    .after(unlock::unlock_audio_on_input),
+                sfx::update_spatial_listener,
+                sfx::play_sfx
+                    .run_if(on_event::<PlaySFX>())
+                    .after(sfx::update_spatial_listener)
+                    .after(set_muted)
+                    .after(unlock::flush_pending_audio),
+                sfx::stop_sfx
+                    .run_if(on_event::<StopSFX>())
+                    .after(sfx::play_sfx),
+                sfx::update_sfx_fades,
+                play_music
+                    .run_if(on_event::<PlayMusic>())
+                    .after(unlock::flush_pending_audio),
+                stop_music.run_if(on_event::<StopMusic>()),
+                playlist::play_playlist.run_if(on_event::<PlayPlaylist>()),
+                playlist::update_playlist.after(playlist::play_playlist),
+                advance_music_intros,
+                update_music_fades,
+                ducking::update_ducking.after(sfx::play_sfx),
+                apply_music_volume
+                    .after(set_volume)
+                    .after(set_muted)
+                    .after(update_music_fades)
+                    .after(ducking::update_ducking),
+                scale_pitch_with_time
+                    .run_if(|config: Res<SoundConfig>| config.pitch_follows_time_scale),
+            ),
+        );
+
+        app.add_systems(Update, sfx::release_one_shot_sfx.after(sfx::play_sfx));
+
+        // loaded here rather than in a startup system so the first track already uses it
+        if self.persist_settings {
+            app.insert_resource(settings::load_audio_settings())
+                .add_systems(Update, settings::save_audio_settings.after(set_muted));
+        } else {
+            app.init_resource::<AudioChannels>();
+        }
+
+        #[cfg(feature = "dev")]
+        debug::register_console_commands(app);
+        #[cfg(feature = "dev")]
+        app.add_systems(Startup, debug::spawn_sound_overlay)
+            .add_systems(
+                Update,
+                (
+                    debug::toggle_sound_overlay,
+                    debug::update_sound_overlay.after(apply_music_volume),
+                ),
+            )
+            .add_event::<crate::toast::ShowToast>()
+            // after every sound system of the frame
+            .add_systems(PostUpdate, debug::toast_missing_sounds);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioChannel {
+    Music,
+    Sfx,
+    Ambient,
+}
+
+///
+/// AudioChannels
+///
+/// Volume level of each channel, from 0.0 to 1.0, and whether all sound is muted.
+/// Muting leaves the levels alone so unmuting goes back to them.
+#[derive(Debug, Clone, PartialEq, Resource, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioChannels {
+    pub music: f32,
+    pub sfx: f32,
+    pub ambient: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioChannels {
+    fn default() -> Self {
+        AudioChannels {
+            music: 1.0,
+            sfx: 1.0,
+            ambient: 1.0,
+            muted: false,
+        }
+    }
+}
+
+impl AudioChannels {
+    pub fn get(&self, channel: AudioChannel) -> f32 {
+        match channel {
+            AudioChannel::Music => self.music,
+            AudioChannel::Sfx => self.sfx,
+            AudioChannel::Ambient => self.ambient,
+        }
+    }
+
+    pub fn set(&mut self, channel: AudioChannel, volume: f32) {
+        let volume = volume.clamp(0.0, 1.0);
+        match channel {
+            AudioChannel::Music => self.music = volume,
+            AudioChannel::Sfx => self.sfx = volume,
+            AudioChannel::Ambient => self.ambient = volume,
+        }
+    }
+
+    /// Level the channel actually plays at: the player's volume times the tween level, or
+    /// 0.0 while muted
+    pub fn effective(&self, channel: AudioChannel, tweens: &VolumeTweens) -> f32 {
+        if self.muted {
+            0.0
+        } else {
+            self.get(channel) * tweens.level(channel)
+        }
+    }
+}
+
+///
+/// IntroLoop
+///
+/// A music entry made of two registered sounds: the intro plays once, then the loop repeats
+#[derive(Debug, Clone)]
+pub struct IntroLoop {
+    pub intro: String,
+    pub looped: String,
+}
+
+///
+/// SfxGroup
+///
+/// Variations of one sound; playing the group picks a random member
+#[derive(Debug, Clone)]
+pub struct SfxGroup {
+    members: Vec<Handle<AudioSource>>,
+    last: Option<usize>,
+}
+
+impl SfxGroup {
+    /// Picks a random member, avoiding the one picked last time when there is a choice
+    fn pick(&mut self, rng: &mut impl Rng) -> Option<Handle<AudioSource>> {
+        let index = match (self.members.len(), self.last) {
+            (0, _) => return None,
+            (1, _) => 0,
+            (len, Some(last)) => {
+                let index = rng.gen_range(0..len - 1);
+                if index >= last {
+                    index + 1
+                } else {
+                    index
+                }
+            }
+            (len, None) => rng.gen_range(0..len),
+        };
+        self.last = Some(index);
+        self.members.get(index).cloned()
+    }
+}
+
+///
+/// SoundDefaults
+///
+/// Settings a sound plays with unless the event overrides them
+///
+/// * volume: volume before the channel level is applied
+/// * speed: playback speed multiplier
+/// * speed_jitter: random speed variation (0.08 = ±8%)
+/// * spatial: whether the sound is panned and attenuated when played at a position
+/// * min_interval: seconds before the sound can play again, overriding SoundPlugin::min_sfx_interval
+///   (Some(0.0) turns rate limiting off for rapid-fire sounds)
+/// * duck_music: music level to duck to for as long as the sound plays
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundDefaults {
+    pub volume: f32,
+    pub speed: f32,
+    pub speed_jitter: f32,
+    pub spatial: bool,
+    pub min_interval: Option<f32>,
+    pub duck_music: Option<f32>,
+}
+
+impl Default for SoundDefaults {
+    fn default() -> Self {
+        SoundDefaults {
+            volume: 1.0,
+            speed: 1.0,
+            speed_jitter: 0.0,
+            spatial: true,
+            min_interval: None,
+            duck_music: None,
+        }
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct SoundResource {
+    map: HashMap<String, Handle<AudioSource>>,
+    groups: HashMap<String, SfxGroup>,
+    intro_loops: HashMap<String, IntroLoop>,
+    defaults: HashMap<String, SoundDefaults>,
+    tempos: HashMap<String, MusicTempo>,
+    captions: HashMap<String, Caption>,
+    // the stream `get` picks group members from, forked from GameRng when there is one
+    rng: Mutex<Option<GameRng>>,
+}
+
+impl SoundResource {
+    pub fn new() -> Self {
+        SoundResource {
+            map: HashMap::new(),
+            groups: HashMap::new(),
+            intro_loops: HashMap::new(),
+            defaults: HashMap::new(),
+            tempos: HashMap::new(),
+            captions: HashMap::new(),
+            rng: Mutex::new(None),
+        }
+    }
+
+    /// Insert a new Handle<AudioSource> along with the settings it plays with by default
+    pub fn insert_with_defaults(
+        &mut self,
+        name: String,
+        handle: Handle<AudioSource>,
+        defaults: SoundDefaults,
+    ) {
+        self.defaults.insert(name.clone(), defaults);
+        self.insert(name, handle);
+    }
+
+    /// Replace the default settings of a sound or group
+    pub fn set_defaults(&mut self, name: String, defaults: SoundDefaults) {
+        self.defaults.insert(name, defaults);
+    }
+
+    /// Get the default settings of a sound or group
+    pub fn defaults(&self, name: &str) -> SoundDefaults {
+        self.defaults.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Set the default random speed variation (e.g. 0.08 for ±8%) of a sound or group
+    pub fn set_speed_jitter(&mut self, name: String, jitter: f32) {
+        self.defaults.entry(name).or_default().speed_jitter = jitter.abs();
+    }
+
+    /// Register a group of variations played at random under one name
+    pub fn insert_group(&mut self, name: String, members: Vec<Handle<AudioSource>>) {
+        self.groups.insert(
+            name,
+            SfxGroup {
+                members,
+                last: None,
+            },
+        );
+    }
+
+    /// Resolve a name to a handle, picking a group member without repeating the previous pick
+    pub fn pick(&mut self, name: &str, rng: &mut impl Rng) -> Option<Handle<AudioSource>> {
+        if let Some(handle) = self.map.get(name) {
+            return Some(handle.clone());
+        }
+        self.groups.get_mut(name)?.pick(rng)
+    }
+
+    /// Register a music entry that plays `intro` once and then repeats `looped`
+    pub fn insert_intro_loop(&mut self, name: String, intro: String, looped: String) {
+        self.intro_loops.insert(name, IntroLoop { intro, looped });
+    }
+
+    /// Get the intro/loop pair registered under a music name
+    pub fn get_intro_loop(&self, name: &str) -> Option<&IntroLoop> {
+        self.intro_loops.get(name)
+    }
+
+    /// Register the beat grid of a music entry, for OnBeat and the BeatClock
+    pub fn insert_tempo(&mut self, name: String, tempo: MusicTempo) {
+        self.tempos.insert(name, tempo);
+    }
+
+    pub fn tempo(&self, name: &str) -> Option<MusicTempo> {
+        self.tempos.get(name).copied()
+    }
+
+    /// Give a sound or group a caption, sent as SoundCaption whenever it plays
+    pub fn set_caption(&mut self, name: String, caption: Caption) {
+        self.captions.insert(name, caption);
+    }
+
+    pub fn caption(&self, name: &str) -> Option<&Caption> {
+        self.captions.get(name)
+    }
+
+    /// Insert a new Handle<AudioSource>
+    pub fn insert(&mut self, name: String, handle: Handle<AudioSource>) {
+        self.map.insert(name, handle.clone());
+    }
+
+    /// Forget a sound or group, with its settings, tempo and caption
+    pub fn remove(&mut self, name: &str) {
+        self.map.remove(name);
+        self.groups.remove(name);
+        self.intro_loops.remove(name);
+        self.defaults.remove(name);
+        self.tempos.remove(name);
+        self.captions.remove(name);
+    }
+
+    /// Every handle a name can play: the sound itself, or all members of a group
+    pub fn handles(&self, name: &str) -> Vec<Handle<AudioSource>> {
+        match self.map.get(name) {
+            Some(handle) => vec![handle.clone()],
+            None => self
+                .groups
+                .get(name)
+                .map(|group| group.members.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The handle registered under a name, to resolve once and play with
+    /// PlaySFX::from_handle / PlayMusic::from_handle
+    pub fn handle(&self, name: &str) -> Option<Handle<AudioSource>> {
+        self.map.get(name).cloned()
+    }
+
+    /// Every registered name with its handle. Groups and intro/loop entries refer to these.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Handle<AudioSource>)> {
+        self.map
+            .iter()
+            .map(|(name, handle)| (name.as_str(), handle))
+    }
+
+    /// Get a Handle<AudioSource>, or a random member if the name is a group
+    pub fn get(&self, name: &str) -> Option<Handle<AudioSource>> {
+        if let Some(handle) = self.map.get(name) {
+            return Some(handle.clone());
+        }
+        let group = self.groups.get(name)?;
+        let mut rng = self.rng.lock().unwrap_or_else(|e| e.into_inner());
+        group
+            .members
+            .choose(rng.get_or_insert_with(GameRng::from_entropy))
+            .cloned()
+    }
+
+    /// Set the stream `get` picks group members from
+    pub fn set_rng(&mut self, rng: GameRng) {
+        *self.rng.get_mut().unwrap_or_else(|e| e.into_inner()) = Some(rng);
+    }
+}
+
+///
+/// PlayMusic
+///
+/// * name: the name the track was registered under in SoundResource
+/// * handle: plays this source directly instead of looking the name up (see from_handle)
+/// * crossfade: seconds to fade the current track out while this one fades in (0.0 swaps instantly)
+/// * fade_in: seconds to ramp this track up from silence (0.0 uses the crossfade duration)
+/// * force_restart: restart the track even if it is already playing
+/// * looping: repeat the track; when false it plays once and sends MusicFinished at the end
+/// * resume_previous: once a non-looping track finishes, go back to the music (or playlist)
+///   that was playing before it
+#[derive(Event, Debug, Clone)]
+pub struct PlayMusic {
+    pub name: String,
+    pub handle: Option<Handle<AudioSource>>,
+    pub crossfade: f32,
+    pub fade_in: f32,
+    pub force_restart: bool,
+    pub looping: bool,
+    pub resume_previous: bool,
+}
+
+impl PlayMusic {
+    pub fn new(name: impl Into<String>) -> Self {
+        PlayMusic {
+            name: name.into(),
+            handle: None,
+            crossfade: 0.0,
+            fade_in: 0.0,
+            force_restart: false,
+            looping: true,
+            resume_previous: false,
+        }
+    }
+
+    /// Plays a handle resolved ahead of time, skipping the name lookup (and intro/loop
+    /// entries). The track is named after its asset path so NowPlaying and CurrentMusic
+    /// still tell tracks apart.
+    pub fn from_handle(handle: Handle<AudioSource>) -> Self {
+        let name = match handle.path() {
+            Some(path) => path.to_string(),
+            None => format!("{:?}", handle.id()),
+        };
+        PlayMusic {
+            handle: Some(handle),
+            ..PlayMusic::new(name)
+        }
+    }
+
+    pub fn with_crossfade(mut self, crossfade: f32) -> Self {
+        self.crossfade = crossfade.max(0.0);
+        self
+    }
+
+    pub fn with_fade_in(mut self, fade_in: f32) -> Self {
+        self.fade_in = fade_in.max(0.0);
+        self
+    }
+
+    pub fn with_force_restart(mut self) -> Self {
+        self.force_restart = true;
+        self
+    }
+
+    pub fn with_looping(mut self, looping: bool) -> Self {
+        self.looping = looping;
+        self
+    }
+
+    /// Play once, then hand back to whatever was playing before
+    pub fn with_resume_previous(mut self) -> Self {
+        self.looping = false;
+        self.resume_previous = true;
+        self
+    }
+
+    /// The handle to start with and, for IntroLoop entries, the MusicIntro that hands over
+    /// to the loop
+    pub(crate) fn resolve(
+        &self,
+        sound_resource: &SoundResource,
+    ) -> Option<(Handle<AudioSource>, Option<MusicIntro>)> {
+        match &self.handle {
+            Some(handle) => Some((handle.clone(), None)),
+            None => resolve_music(sound_resource, &self.name),
+        }
+    }
+
+    /// Duration of the incoming track's fade in
+    fn fade_in_duration(&self) -> f32 {
+        if self.fade_in > 0.0 {
+            self.fade_in
+        } else {
+            self.crossfade
+        }
+    }
+}
+
+///
+/// StopMusic
+///
+/// * fade_out: seconds to fade the current track to silence before despawning it (0.0 stops instantly)
+#[derive(Event, Default)]
+pub struct StopMusic {
+    pub fade_out: f32,
+}
+
+impl StopMusic {
+    pub fn with_fade_out(fade_out: f32) -> Self {
+        StopMusic {
+            fade_out: fade_out.max(0.0),
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct SetVolume {
+    pub channel: AudioChannel,
+    pub volume: f32,
+}
+
+/// Flips AudioChannels::muted
+#[derive(Event, Default)]
+pub struct ToggleMute;
+
+/// Mutes (true) or unmutes (false) all sound
+#[derive(Event)]
+pub struct SetMuted(pub bool);
+
+///
+/// NowPlaying
+///
+/// Marks a music entity. A track that is fading out (stopped or crossfaded away)
+/// keeps the marker until it despawns, but no longer counts as the current track.
+#[derive(Component)]
+pub struct NowPlaying {
+    pub name: String,
+}
+
+///
+/// MusicIntro
+///
+/// On a music entity that is still playing the intro of an IntroLoop entry.
+/// Once the intro's sink runs dry the same entity switches over to the loop.
+#[derive(Component)]
+pub struct MusicIntro {
+    looped: Handle<AudioSource>,
+}
+
+///
+/// MusicFade
+///
+/// Ramps the volume of a music or ambient entity between two levels, as a fraction of its
+/// channel volume.
+/// Uses real time so fades keep running while virtual time is paused.
+#[derive(Component, Debug, Clone)]
+pub struct MusicFade {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+    despawn: bool,
+}
+
+impl MusicFade {
+    /// Fade from silence up to the channel volume
+    pub fn fade_in(duration: f32) -> Self {
+        MusicFade {
+            from: 0.0,
+            to: 1.0,
+            duration,
+            elapsed: 0.0,
+            despawn: false,
+        }
+    }
+
+    /// Fade from the given level down to silence, then despawn the entity
+    pub fn fade_out(from: f32, duration: f32) -> Self {
+        MusicFade {
+            from,
+            to: 0.0,
+            duration,
+            elapsed: 0.0,
+            despawn: true,
+        }
+    }
+
+    /// Current volume level of the fade
+    pub fn level(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return self.to;
+        }
+        let t = (self.elapsed / self.duration).clamp(0.0, 1.0);
+        self.from + (self.to - self.from) * t
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// True if the entity will be despawned at the end of the fade
+    pub fn is_fading_out(&self) -> bool {
+        self.despawn
+    }
+
+    fn tick(&mut self, delta: f32) {
+        self.elapsed += delta;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_music(
+    mut commands: Commands,
+    mut events: EventReader<PlayMusic>,
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    ducking: Res<MusicDucking>,
+    mut gate: loading::PlaybackGate,
+    mut playlist: ResMut<Playlist>,
+    current: Res<CurrentMusic>,
+    mut captions: captions::Captions,
+    mut log: SoundLogger,
+    time: Res<Time<Real>>,
+    playing_query: Query<(Entity, &NowPlaying, Option<&MusicFade>)>,
+) {
+    // only the most recent request this frame should end up playing
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    if !gate.unlocked.0 {
+        gate.pending.queue_music(event.clone());
+        log.record(
+            SoundAction::PlayMusic,
+            &event.name,
+            SoundOutcome::Queued,
+            None,
+        );
+        return;
+    }
+
+    let already_playing = playing_query.iter().any(|(_, playing, fade)| {
+        playing.name == event.name && !fade.is_some_and(MusicFade::is_fading_out)
+    });
+    if already_playing && !event.force_restart {
+        log.record(
+            SoundAction::PlayMusic,
+            &event.name,
+            SoundOutcome::Skipped("already playing"),
+            None,
+        );
+        return;
+    }
+
+    let (handle, intro) = match event.resolve(&sound_resource) {
+        Some(resolved) => resolved,
+        None => {
+            warn!("Music not found: {}", event.name);
+            log.record(
+                SoundAction::PlayMusic,
+                &event.name,
+                SoundOutcome::Missing,
+                None,
+            );
+            return;
+        }
+    };
+
+    let mut handles = vec![handle.clone()];
+    handles.extend(intro.as_ref().map(|intro| intro.looped.clone()));
+    match loading::AudioLoad::of_all(&gate.asset_server, &handles) {
+        loading::AudioLoad::Loading => {
+            // the current track keeps playing until the new one can take over
+            gate.waiting.queue_music(event.clone());
+            log.record(
+                SoundAction::PlayMusic,
+                &event.name,
+                SoundOutcome::Queued,
+                None,
+            );
+            return;
+        }
+        loading::AudioLoad::Failed => {
+            warn!("Music failed to load: {}", event.name);
+            log.record(
+                SoundAction::PlayMusic,
+                &event.name,
+                SoundOutcome::Skipped("failed to load"),
+                None,
+            );
+            return;
+        }
+        loading::AudioLoad::Ready => gate.waiting.cancel_music(),
+    }
+
+    let resume = if !event.looping && event.resume_previous {
+        if playlist.is_active() {
+            Some(ResumeAfter::Playlist)
+        } else {
+            current
+                .name()
+                .map(|name| ResumeAfter::Track(name.to_string()))
+        }
+    } else {
+        None
+    };
+
+    // an explicit track replaces whatever playlist was running, unless it comes back after
+    match resume {
+        Some(ResumeAfter::Playlist) => playlist.suspend(),
+        _ => playlist.clear(),
+    }
+
+    for (entity, _, fade) in playing_query.iter() {
+        release_music(&mut commands, entity, fade, event.crossfade);
+    }
+
+    // a one-shot track ignores the loop half of an intro/loop entry
+    let mode = if intro.is_some() || !event.looping {
+        PlaybackMode::Once
+    } else {
+        PlaybackMode::Loop
+    };
+    let level = music_level(&channels, &tweens, &ducking);
+    let entity = spawn_music(
+        &mut commands,
+        handle,
+        &event.name,
+        mode,
+        level,
+        event.fade_in_duration(),
+    );
+    if !event.looping {
+        commands.entity(entity).insert(MusicOnce { resume });
+    } else if let Some(intro) = intro {
+        commands.entity(entity).insert(intro);
+    }
+    captions.emit(
+        &sound_resource,
+        &event.name,
+        None,
+        time.elapsed_seconds_f64(),
+    );
+    log.record(
+        SoundAction::PlayMusic,
+        &event.name,
+        SoundOutcome::Played,
+        Some(level),
+    );
+}
+
+/// Looks up a music name, returning the handle to start with and, for IntroLoop entries,
+/// the MusicIntro that hands over to the loop
+fn resolve_music(
+    sound_resource: &SoundResource,
+    name: &str,
+) -> Option<(Handle<AudioSource>, Option<MusicIntro>)> {
+    match sound_resource.get_intro_loop(name) {
+        Some(entry) => {
+            let intro = sound_resource.get(&entry.intro)?;
+            let looped = sound_resource.get(&entry.looped)?;
+            Some((intro, Some(MusicIntro { looped })))
+        }
+        None => sound_resource.get(name).map(|handle| (handle, None)),
+    }
+}
+
+pub fn stop_music(
+    mut commands: Commands,
+    mut events: EventReader<StopMusic>,
+    mut pending: ResMut<PendingAudio>,
+    mut waiting: ResMut<LoadingAudio>,
+    mut playlist: ResMut<Playlist>,
+    mut log: SoundLogger,
+    playing_query: Query<(Entity, &NowPlaying, Option<&MusicFade>)>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+
+    pending.cancel_music();
+    waiting.cancel_music();
+    playlist.clear();
+
+    for (entity, playing, fade) in playing_query.iter() {
+        log.record(
+            SoundAction::StopMusic,
+            &playing.name,
+            SoundOutcome::Stopped,
+            None,
+        );
+        // a PlayMusic arriving mid-fade treats this entity like any other playing track
+        release_music(&mut commands, entity, fade, event.fade_out);
+    }
+}
+
+/// Volume a music track at full fade level plays at
+pub(crate) fn music_level(
+    channels: &AudioChannels,
+    tweens: &VolumeTweens,
+    ducking: &MusicDucking,
+) -> f32 {
+    channels.effective(AudioChannel::Music, tweens) * ducking.level()
+}
+
+/// Spawns a music entity, starting silent if it fades in
+pub(crate) fn spawn_music(
+    commands: &mut Commands,
+    handle: Handle<AudioSource>,
+    name: &str,
+    mode: PlaybackMode,
+    level: f32,
+    fade_in: f32,
+) -> Entity {
+    let mut entity = commands.spawn((
+        AudioSourceBundle {
+            source: handle,
+            settings: PlaybackSettings {
+                mode,
+                volume: Volume::new(if fade_in > 0.0 { 0.0 } else { level }),
+                ..default()
+            },
+        },
+        NowPlaying {
+            name: name.to_string(),
+        },
+    ));
+    if fade_in > 0.0 {
+        entity.insert(MusicFade::fade_in(fade_in));
+    }
+    entity.id()
+}
+
+/// Fades a music entity out over the given duration, or despawns it right away
+pub(crate) fn release_music(
+    commands: &mut Commands,
+    entity: Entity,
+    fade: Option<&MusicFade>,
+    fade_out: f32,
+) {
+    if fade_out > 0.0 {
+        // keep the old track around until it has faded to silence
+        let from = fade.map_or(1.0, MusicFade::level);
+        commands
+            .entity(entity)
+            .insert(MusicFade::fade_out(from, fade_out));
+    } else {
+        commands.entity(entity).despawn();
+    }
+}
+
+///
+/// advance_music_intros: Bevy system
+///
+/// Swaps a finished intro for its loop on the same entity, so NowPlaying, fades and
+/// stop requests keep treating the pair as one track
+pub fn advance_music_intros(
+    mut commands: Commands,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    ducking: Res<MusicDucking>,
+    intro_query: Query<(Entity, &MusicIntro, &AudioSink, Option<&MusicFade>)>,
+) {
+    for (entity, intro, sink, fade) in intro_query.iter() {
+        if !sink.empty() {
+            continue;
+        }
+
+        let volume =
+            fade.map_or(1.0, MusicFade::level) * music_level(&channels, &tweens, &ducking);
+        // removing the sink makes bevy_audio start playing the new source on this entity
+        commands
+            .entity(entity)
+            .remove::<(AudioSink, MusicIntro)>()
+            .insert((
+                intro.looped.clone(),
+                PlaybackSettings {
+                    mode: PlaybackMode::Loop,
+                    volume: Volume::new(volume),
+                    ..default()
+                },
+            ));
+    }
+}
+
+///
+/// update_music_fades: Bevy system
+///
+/// Advances every MusicFade, despawning tracks that finished fading out
+pub fn update_music_fades(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut fade_query: Query<(Entity, &mut MusicFade, Option<&AudioSink>)>,
+) {
+    for (entity, mut fade, sink) in fade_query.iter_mut() {
+        // no sink while loading or mid intro/loop handover: a fade in waits for it,
+        // a fade out keeps counting down so the track still goes away on time
+        if sink.is_none() && !fade.despawn {
+            continue;
+        }
+
+        fade.tick(time.delta_seconds());
+        if fade.finished() {
+            if fade.despawn {
+                commands.entity(entity).despawn();
+            } else {
+                commands.entity(entity).remove::<MusicFade>();
+            }
+        }
+    }
+}
+
+///
+/// apply_music_volume: Bevy system
+///
+/// Sets each music sink to channel volume × fade level × ducking level
+pub fn apply_music_volume(
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    ducking: Res<MusicDucking>,
+    music_query: Query<(&AudioSink, Option<&MusicFade>), With<NowPlaying>>,
+) {
+    let level = music_level(&channels, &tweens, &ducking);
+    for (sink, fade) in music_query.iter() {
+        sink.set_volume(fade.map_or(1.0, MusicFade::level) * level);
+    }
+}
+
+///
+/// fork_group_rng: Bevy system
+///
+/// Gives SoundResource::get a stream of the game's GameRng, so a replay picks the same group
+/// members
+pub fn fork_group_rng(mut sound_resource: ResMut<SoundResource>, game_rng: Res<GameRng>) {
+    sound_resource.set_rng(game_rng.fork("sound_groups"));
+}
+
+pub fn load_sound_manifests(
+    asset_server: Res<AssetServer>,
+    config: Res<SoundConfig>,
+    mut manifests: ResMut<SoundManifests>,
+) {
+    for path in config.manifests.iter() {
+        manifests.load(&asset_server, path.clone());
+    }
+}
+
+pub fn set_volume(mut events: EventReader<SetVolume>, mut channels: ResMut<AudioChannels>) {
+    for event in events.read() {
+        channels.set(event.channel, event.volume);
+    }
+}
+
+///
+/// set_muted: Bevy system
+///
+/// Applies the frame's ToggleMute requests, then its last SetMuted, which wins over any
+/// toggles sent alongside it
+pub fn set_muted(
+    mut toggle_events: EventReader<ToggleMute>,
+    mut set_events: EventReader<SetMuted>,
+    mut channels: ResMut<AudioChannels>,
+) {
+    let mut muted = channels.muted;
+    for _ in toggle_events.read() {
+        muted = !muted;
+    }
+    // an explicit state wins over toggles sent the same frame
+    if let Some(SetMuted(state)) = set_events.read().last() {
+        muted = *state;
+    }
+    if muted != channels.muted {
+        channels.muted = muted;
+    }
+}
+
+///
+/// scale_pitch_with_time: Bevy system
+///
+/// With pitch_follows_time_scale, keeps music and ambient loops playing at the TimeScale. SFX
+/// are scaled as they start.
+pub fn scale_pitch_with_time(
+    time_scale: Option<Res<TimeScale>>,
+    sink_query: Query<&AudioSink, Without<SfxVoice>>,
+) {
+    let speed = time_scale.map_or(1.0, |time_scale| time_scale.get());
+    for sink in sink_query.iter() {
+        if sink.speed() != speed {
+            sink.set_speed(speed);
+        }
+    }
+}
+
+///
+/// toggle_mute_on_key: Bevy system
+///
+/// Sends ToggleMute when Action::ToggleMute is pressed, M without InputPlugin
+pub fn toggle_mute_on_key(
+    actions: Option<Res<ActionState>>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut events: EventWriter<ToggleMute>,
+) {
+    let pressed = match (actions, keys) {
+        (Some(actions), _) => actions.just_pressed(Action::ToggleMute),
+        (None, Some(keys)) => keys.just_pressed(KeyCode::KeyM),
+        (None, None) => false,
+    };
+    if pressed {
+        events.send(ToggleMute);
+    }
+}
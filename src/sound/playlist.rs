@@ -0,0 +1,241 @@
+use super::{
+    music_level, release_music, spawn_music, AudioChannels, MusicDucking, MusicFade, NowPlaying,
+    SoundResource, VolumeTweens,
+};
+use crate::rng::{self, GameRng};
+use bevy::{
+    audio::{AudioSink, AudioSinkPlayback, PlaybackMode},
+    prelude::*,
+};
+use rand::seq::SliceRandom;
+
+///
+/// PlayPlaylist
+///
+/// * names: tracks to play in order
+/// * shuffle: play the tracks in a random order (reshuffled every time the list repeats)
+/// * repeat: start over once the last track finishes
+#[derive(Event)]
+pub struct PlayPlaylist {
+    pub names: Vec<String>,
+    pub shuffle: bool,
+    pub repeat: bool,
+}
+
+/// Skip to the next track of the playlist
+#[derive(Event, Default)]
+pub struct NextTrack;
+
+/// Go back to the previous track of the playlist
+#[derive(Event, Default)]
+pub struct PreviousTrack;
+
+///
+/// Playlist
+///
+/// The queue of tracks started by PlayPlaylist. Empty while no playlist is running.
+#[derive(Debug, Default, Resource)]
+pub struct Playlist {
+    names: Vec<String>,
+    order: Vec<usize>,
+    position: usize,
+    shuffle: bool,
+    repeat: bool,
+    // a one-shot track is playing over the list
+    suspended: bool,
+    // restart the current entry on the next update
+    restart: bool,
+    // shuffles the order
+    rng: Option<GameRng>,
+}
+
+impl Playlist {
+    pub fn is_active(&self) -> bool {
+        !self.order.is_empty()
+    }
+
+    /// Name of the track the playlist is currently on
+    pub fn current(&self) -> Option<&str> {
+        self.order
+            .get(self.position)
+            .map(|index| self.names[*index].as_str())
+    }
+
+    /// Index of the current track within the play order
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.names.clear();
+        self.order.clear();
+        self.position = 0;
+        self.suspended = false;
+        self.restart = false;
+    }
+
+    /// Holds the playlist while another track plays over it
+    pub(crate) fn suspend(&mut self) {
+        self.suspended = true;
+    }
+
+    /// Picks the playlist back up from the start of the track it was on
+    pub(crate) fn resume(&mut self) {
+        if self.suspended {
+            self.suspended = false;
+            self.restart = true;
+        }
+    }
+
+    fn start(&mut self, names: Vec<String>, shuffle: bool, repeat: bool) {
+        self.order = (0..names.len()).collect();
+        self.names = names;
+        self.position = 0;
+        self.shuffle = shuffle;
+        self.repeat = repeat;
+        self.suspended = false;
+        self.restart = false;
+        self.reorder();
+    }
+
+    fn reorder(&mut self) {
+        if self.shuffle {
+            let rng = self.rng.get_or_insert_with(GameRng::from_entropy);
+            self.order.shuffle(rng);
+        }
+    }
+
+    /// Moves through the play order by offset. Returns false once a non-repeating list runs out.
+    fn step(&mut self, offset: isize) -> bool {
+        let target = self.position as isize + offset;
+        let len = self.order.len() as isize;
+
+        if target < 0 {
+            self.position = if self.repeat { (len - 1) as usize } else { 0 };
+        } else if target >= len {
+            if !self.repeat {
+                self.clear();
+                return false;
+            }
+            self.reorder();
+            self.position = 0;
+        } else {
+            self.position = target as usize;
+        }
+        true
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_playlist(
+    mut commands: Commands,
+    mut events: EventReader<PlayPlaylist>,
+    mut playlist: ResMut<Playlist>,
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    ducking: Res<MusicDucking>,
+    playing_query: Query<(Entity, Option<&MusicFade>), With<NowPlaying>>,
+    game_rng: Option<Res<GameRng>>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    if playlist.rng.is_none() {
+        playlist.rng = Some(rng::fork_or_entropy(game_rng.as_deref(), "playlist"));
+    }
+    if event.names.is_empty() {
+        warn!("Ignoring empty playlist");
+        return;
+    }
+
+    playlist.start(event.names.clone(), event.shuffle, event.repeat);
+    for (entity, fade) in playing_query.iter() {
+        release_music(&mut commands, entity, fade, 0.0);
+    }
+    start_current(
+        &mut commands,
+        &playlist,
+        &sound_resource,
+        music_level(&channels, &tweens, &ducking),
+    );
+}
+
+type PlayingTrack<'a> = (Entity, Option<&'a AudioSink>, Option<&'a MusicFade>);
+
+///
+/// update_playlist: Bevy system
+///
+/// Starts the next entry when the current track finishes, and handles NextTrack/PreviousTrack
+#[allow(clippy::too_many_arguments)]
+pub fn update_playlist(
+    mut commands: Commands,
+    mut next_events: EventReader<NextTrack>,
+    mut previous_events: EventReader<PreviousTrack>,
+    mut playlist: ResMut<Playlist>,
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    ducking: Res<MusicDucking>,
+    playing_query: Query<PlayingTrack, With<NowPlaying>>,
+) {
+    let skip = next_events.read().count() as isize - previous_events.read().count() as isize;
+    if !playlist.is_active() || playlist.suspended {
+        return;
+    }
+
+    // a track without a sink hasn't started yet, so it isn't finished either, and nor is one
+    // that hasn't been spawned yet
+    let finished = !playing_query.is_empty()
+        && playing_query.iter().all(|(_, sink, fade)| {
+            fade.is_some_and(MusicFade::is_fading_out) || sink.is_some_and(|sink| sink.empty())
+        });
+
+    let offset = if playlist.restart {
+        playlist.restart = false;
+        0
+    } else if skip != 0 {
+        skip
+    } else if finished {
+        1
+    } else {
+        return;
+    };
+
+    for (entity, _, fade) in playing_query.iter() {
+        release_music(&mut commands, entity, fade, 0.0);
+    }
+    if playlist.step(offset) {
+        start_current(
+            &mut commands,
+            &playlist,
+            &sound_resource,
+            music_level(&channels, &tweens, &ducking),
+        );
+    }
+}
+
+fn start_current(
+    commands: &mut Commands,
+    playlist: &Playlist,
+    sound_resource: &SoundResource,
+    level: f32,
+) {
+    let Some(name) = playlist.current() else {
+        return;
+    };
+    match sound_resource.get(name) {
+        Some(handle) => {
+            spawn_music(commands, handle, name, PlaybackMode::Once, level, 0.0);
+        }
+        None => warn!("Music not found: {}", name),
+    }
+}
@@ -0,0 +1,134 @@
+use super::PlaySFX;
+use crate::rng::{self, GameRng};
+use crate::{gfx::MainCamera, helpers::tiled::TiledMap};
+use bevy::prelude::*;
+use rand::Rng;
+use std::f32::consts::TAU;
+
+///
+/// AmbientOneShot
+///
+/// * group: sound or group name to play, a random member each time for groups
+/// * min_interval, max_interval: seconds between tries, picked at random in this range
+/// * chance: probability (0.0 - 1.0) that a try actually plays
+/// * positional_radius: play at a random spot within this distance of the camera, or None
+///   to play without a position
+#[derive(Debug, Clone)]
+pub struct AmbientOneShot {
+    pub group: String,
+    pub min_interval: f32,
+    pub max_interval: f32,
+    pub chance: f32,
+    pub positional_radius: Option<f32>,
+}
+
+impl AmbientOneShot {
+    pub fn new(group: impl Into<String>, min_interval: f32, max_interval: f32) -> Self {
+        let min_interval = min_interval.max(0.0);
+        AmbientOneShot {
+            group: group.into(),
+            min_interval,
+            max_interval: max_interval.max(min_interval),
+            chance: 1.0,
+            positional_radius: None,
+        }
+    }
+
+    pub fn with_chance(mut self, chance: f32) -> Self {
+        self.chance = chance.clamp(0.0, 1.0);
+        self
+    }
+
+    pub fn with_positional_radius(mut self, radius: f32) -> Self {
+        self.positional_radius = Some(radius.max(0.0));
+        self
+    }
+
+    fn roll_interval(&self, rng: &mut impl Rng) -> f32 {
+        if self.max_interval > self.min_interval {
+            rng.gen_range(self.min_interval..=self.max_interval)
+        } else {
+            self.min_interval
+        }
+    }
+}
+
+///
+/// AmbientScheduler
+///
+/// Plays the entries' one-shots at random intervals. Runs on game time, so it stops while the
+/// game is paused, and starts every entry's timer over when a map is loaded.
+#[derive(Debug, Default, Resource)]
+pub struct AmbientScheduler {
+    // each entry with the seconds left until its next try
+    entries: Vec<(AmbientOneShot, Option<f32>)>,
+}
+
+impl AmbientScheduler {
+    pub fn add(&mut self, entry: AmbientOneShot) {
+        self.entries.push((entry, None));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &AmbientOneShot> {
+        self.entries.iter().map(|(entry, _)| entry)
+    }
+
+    /// Rolls a fresh interval for every entry
+    pub fn reset(&mut self) {
+        for (_, timer) in self.entries.iter_mut() {
+            *timer = None;
+        }
+    }
+}
+
+pub fn schedule_ambient_one_shots(
+    time: Res<Time>,
+    mut scheduler: ResMut<AmbientScheduler>,
+    mut events: EventWriter<PlaySFX>,
+    map_query: Query<(), Changed<Handle<TiledMap>>>,
+    camera_query: Query<&GlobalTransform, With<MainCamera>>,
+    game_rng: Option<Res<GameRng>>,
+    mut rng: Local<Option<GameRng>>,
+) {
+    if !map_query.is_empty() {
+        scheduler.reset();
+    }
+    if scheduler.entries.is_empty() {
+        return;
+    }
+
+    let listener = camera_query
+        .get_single()
+        .ok()
+        .map(|transform| transform.translation().truncate());
+    let delta = time.delta_seconds();
+    let rng = rng.get_or_insert_with(|| rng::fork_or_entropy(game_rng.as_deref(), "ambient"));
+
+    for (entry, timer) in scheduler.entries.iter_mut() {
+        let remaining = timer.get_or_insert_with(|| entry.roll_interval(rng));
+        *remaining -= delta;
+        if *remaining > 0.0 {
+            continue;
+        }
+        *remaining = entry.roll_interval(rng);
+        if !rng.gen_bool(entry.chance.clamp(0.0, 1.0) as f64) {
+            continue;
+        }
+
+        match (entry.positional_radius, listener) {
+            (Some(radius), Some(listener)) => {
+                // sqrt spreads the spots evenly over the disc instead of bunching at the centre
+                let distance = radius * rng.gen::<f32>().sqrt();
+                let offset = Vec2::from_angle(rng.gen_range(0.0..TAU)) * distance;
+                events.send(PlaySFX::at(entry.group.clone(), listener + offset));
+            }
+            _ => {
+                events.send(PlaySFX::new(entry.group.clone()));
+            }
+        }
+    }
+}
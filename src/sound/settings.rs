@@ -0,0 +1,96 @@
+use super::{AudioChannels, CaptionsEnabled};
+use crate::settings::{Settings, SettingsChanged};
+use crate::storage;
+use bevy::prelude::*;
+
+const SETTINGS_KEY: &str = "audio";
+
+/// Seconds AudioChannels has to stay unchanged before it is written out
+const SAVE_DELAY: f64 = 0.5;
+
+/// The saved channel levels and mute flag, or the defaults if there are none or they can't be read
+pub fn load_audio_settings() -> AudioChannels {
+    let Some(saved) = storage::load(SETTINGS_KEY) else {
+        return AudioChannels::default();
+    };
+    match ron::from_str::<AudioChannels>(&saved) {
+        Ok(mut channels) => {
+            // keep hand-edited files within range
+            channels.music = channels.music.clamp(0.0, 1.0);
+            channels.sfx = channels.sfx.clamp(0.0, 1.0);
+            channels.ambient = channels.ambient.clamp(0.0, 1.0);
+            channels
+        }
+        Err(e) => {
+            warn!("Ignoring unreadable audio settings: {}", e);
+            AudioChannels::default()
+        }
+    }
+}
+
+///
+/// save_audio_settings: Bevy system
+///
+/// Writes AudioChannels to storage once it has settled after a change, so dragging a
+/// volume slider doesn't write every frame
+pub fn save_audio_settings(
+    time: Res<Time<Real>>,
+    channels: Res<AudioChannels>,
+    mut save_at: Local<Option<f64>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    // the first run sees the value that was just loaded
+    if channels.is_changed() && !channels.is_added() {
+        *save_at = Some(now + SAVE_DELAY);
+    }
+
+    if !save_at.is_some_and(|at| now >= at) {
+        return;
+    }
+    *save_at = None;
+
+    let serialized = match ron::to_string(&*channels) {
+        Ok(serialized) => serialized,
+        Err(e) => {
+            warn!("Could not serialize audio settings: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = storage::save(SETTINGS_KEY, &serialized) {
+        warn!("Could not save audio settings: {}", e);
+    }
+}
+
+///
+/// apply_audio_settings: Bevy system
+///
+/// Takes the channel levels and mute flag from Settings when they change
+pub fn apply_audio_settings(
+    mut events: EventReader<SettingsChanged>,
+    settings: Option<Res<Settings>>,
+    mut channels: ResMut<AudioChannels>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    if let Some(settings) = settings.filter(|settings| settings.audio != *channels) {
+        *channels = settings.audio.clone();
+    }
+}
+
+///
+/// apply_caption_settings: Bevy system
+///
+/// Takes CaptionsEnabled from Settings when it changes
+pub fn apply_caption_settings(
+    mut events: EventReader<SettingsChanged>,
+    settings: Option<Res<Settings>>,
+    mut captions: ResMut<CaptionsEnabled>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    if let Some(settings) = settings.filter(|settings| settings.captions != captions.0) {
+        captions.0 = settings.captions;
+    }
+}
@@ -0,0 +1,685 @@
+use super::{
+    captions::Captions,
+    loading::{AudioLoad, PlaybackGate},
+    log::{SoundAction, SoundLogger, SoundOutcome},
+    AudioChannel, AudioChannels, MusicDucking, SoundConfig, SoundResource, VolumeTweens,
+};
+use crate::gfx::MainCamera;
+use crate::pool::{InPool, Pool, PoolKind};
+use crate::rng::{self, GameRng};
+use crate::state::TimeScale;
+use bevy::{
+    audio::{AudioSink, AudioSinkPlayback, PlaybackMode, SpatialAudioSink, SpatialScale, Volume},
+    ecs::system::EntityCommands,
+    prelude::*,
+};
+use rand::Rng;
+use std::collections::HashMap;
+
+///
+/// PlaySFX
+///
+/// * name: the name the sound was registered under in SoundResource
+/// * handle: plays this source directly instead of looking the name up (see from_handle)
+/// * position: world position of the emitter, or None for a non-positional sound
+/// * volume: overrides the sound's default volume when set
+/// * speed: overrides the sound's default speed when set
+/// * speed_jitter: overrides the sound's default random speed variation when set
+/// * priority: decides which sounds give way once the SFX voice cap is reached
+/// * looping: repeat until stopped with StopSFX instead of despawning when finished
+#[derive(Event, Debug, Clone)]
+pub struct PlaySFX {
+    pub name: String,
+    pub handle: Option<Handle<AudioSource>>,
+    pub position: Option<Vec2>,
+    pub volume: Option<f32>,
+    pub speed: Option<f32>,
+    pub speed_jitter: Option<f32>,
+    pub priority: SfxPriority,
+    pub looping: bool,
+}
+
+impl PlaySFX {
+    pub fn new(name: impl Into<String>) -> Self {
+        PlaySFX {
+            name: name.into(),
+            handle: None,
+            position: None,
+            volume: None,
+            speed: None,
+            speed_jitter: None,
+            priority: SfxPriority::Normal,
+            looping: false,
+        }
+    }
+
+    /// Plays a handle resolved ahead of time (e.g. with SoundResource::handle), skipping the
+    /// name lookup. The sound goes through the same volume, limiting and voice rules, but
+    /// without a name it uses the default SoundDefaults and can't be stopped by name.
+    pub fn from_handle(handle: Handle<AudioSource>) -> Self {
+        PlaySFX {
+            handle: Some(handle),
+            ..PlaySFX::new(String::new())
+        }
+    }
+
+    /// A sound panned and attenuated relative to the MainCamera
+    pub fn at(name: impl Into<String>, position: Vec2) -> Self {
+        PlaySFX {
+            position: Some(position),
+            ..PlaySFX::new(name)
+        }
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = Some(volume.max(0.0));
+        self
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Vary the speed randomly by up to ±jitter (0.08 = ±8%)
+    pub fn with_speed_jitter(mut self, jitter: f32) -> Self {
+        self.speed_jitter = Some(jitter.abs());
+        self
+    }
+
+    pub fn with_priority(mut self, priority: SfxPriority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// The source to play this time, picking a group member for group names
+    fn resolve(
+        &self,
+        sound_resource: &mut SoundResource,
+        rng: &mut impl Rng,
+    ) -> Option<Handle<AudioSource>> {
+        match &self.handle {
+            Some(handle) => Some(handle.clone()),
+            None => sound_resource.pick(&self.name, rng),
+        }
+    }
+
+    /// Every source this request could play
+    pub(crate) fn handles(&self, sound_resource: &SoundResource) -> Vec<Handle<AudioSource>> {
+        match &self.handle {
+            Some(handle) => vec![handle.clone()],
+            None => sound_resource.handles(&self.name),
+        }
+    }
+}
+
+///
+/// StopSFX
+///
+/// Stops playing SFX, either every instance of one name or everything on the SFX channel.
+/// A fade_out of 0.0 stops instantly.
+#[derive(Event, Debug, Clone)]
+pub enum StopSFX {
+    ByName { name: String, fade_out: f32 },
+    All { fade_out: f32 },
+}
+
+impl StopSFX {
+    pub fn by_name(name: impl Into<String>) -> Self {
+        StopSFX::ByName {
+            name: name.into(),
+            fade_out: 0.0,
+        }
+    }
+
+    pub fn all() -> Self {
+        StopSFX::All { fade_out: 0.0 }
+    }
+
+    pub fn with_fade_out(mut self, duration: f32) -> Self {
+        match &mut self {
+            StopSFX::ByName { fade_out, .. } | StopSFX::All { fade_out } => {
+                *fade_out = duration.max(0.0)
+            }
+        }
+        self
+    }
+
+    fn fade_out(&self) -> f32 {
+        match self {
+            StopSFX::ByName { fade_out, .. } | StopSFX::All { fade_out } => *fade_out,
+        }
+    }
+
+    fn matches(&self, tag: &SfxTag) -> bool {
+        match self {
+            StopSFX::ByName { name, .. } => tag.0 == *name,
+            StopSFX::All { .. } => true,
+        }
+    }
+}
+
+/// The name a playing SFX entity was requested with
+#[derive(Debug, Clone, Component)]
+pub struct SfxTag(pub String);
+
+/// Fades a stopped SFX entity to silence, then despawns it
+#[derive(Debug, Component)]
+pub struct SfxFadeOut {
+    from: Option<f32>,
+    duration: f32,
+    elapsed: f32,
+}
+
+/// Marks a pooled SFX entity: a one-shot sound that doesn't duck the music, given back to the
+/// Pool<OneShotSfx> once it has finished instead of being despawned
+#[derive(Debug, Default, Component)]
+pub struct OneShotSfx;
+
+impl PoolKind for OneShotSfx {
+    const NAME: &'static str = "sfx";
+
+    fn reset(entity: &mut EntityCommands) {
+        entity.remove::<(SfxVoice, SfxTag, SfxFadeOut, TransformBundle)>();
+    }
+}
+
+///
+/// SfxPriority
+///
+/// When the voice cap is reached, a request replaces the oldest playing sound of the lowest
+/// priority below its own, and is dropped if there is none. Low requests are always dropped.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SfxPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// Marks a playing SFX entity so it counts against the voice cap
+#[derive(Debug, Component)]
+pub struct SfxVoice {
+    pub priority: SfxPriority,
+    pub started: f64,
+    // request volume × attenuation, before the SFX channel is applied
+    volume: f32,
+}
+
+/// Picks the voice to stop to make room for a request, if the request outranks any
+fn voice_to_replace(voices: &[(Entity, SfxPriority, f64)], priority: SfxPriority) -> Option<usize> {
+    voices
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, voice_priority, _))| *voice_priority < priority)
+        .min_by(|(_, a), (_, b)| a.1.cmp(&b.1).then(a.2.total_cmp(&b.2)))
+        .map(|(index, _)| index)
+}
+
+/// Samples a speed multiplier in [1 - jitter, 1 + jitter]; a jitter of 0 gives exactly 1.0
+fn sample_speed(jitter: f32, rng: &mut impl Rng) -> f32 {
+    if jitter > 0.0 {
+        1.0 + rng.gen_range(-jitter..=jitter)
+    } else {
+        1.0
+    }
+}
+
+///
+/// PositionalAudio
+///
+/// * max_distance: positional sounds farther than this from the camera (in world units) are skipped
+#[derive(Debug, Clone, Resource)]
+pub struct PositionalAudio {
+    pub max_distance: f32,
+}
+
+impl Default for PositionalAudio {
+    fn default() -> Self {
+        PositionalAudio { max_distance: 64.0 }
+    }
+}
+
+///
+/// SfxRateLimiter
+///
+/// Tracks when each SFX last played so bursts of identical requests collapse into one sound
+#[derive(Debug, Default, Resource)]
+pub struct SfxRateLimiter {
+    last_played: HashMap<String, f64>,
+    last_played_handles: HashMap<AssetId<AudioSource>, f64>,
+}
+
+impl SfxRateLimiter {
+    /// Records the play and returns true if `name` hasn't played within `interval` seconds
+    pub fn try_play(&mut self, name: &str, now: f64, interval: f32) -> bool {
+        if interval <= 0.0 {
+            return true;
+        }
+        if let Some(last) = self.last_played.get(name) {
+            if now - last < interval as f64 {
+                return false;
+            }
+        }
+        self.last_played.insert(name.to_string(), now);
+        true
+    }
+
+    /// try_play for sounds requested by handle
+    pub fn try_play_handle(&mut self, id: AssetId<AudioSource>, now: f64, interval: f32) -> bool {
+        if interval <= 0.0 {
+            return true;
+        }
+        if let Some(last) = self.last_played_handles.get(&id) {
+            if now - last < interval as f64 {
+                return false;
+            }
+        }
+        self.last_played_handles.insert(id, now);
+        true
+    }
+}
+
+///
+/// update_spatial_listener: Bevy system
+///
+/// Keeps a SpatialListener on the MainCamera with its ears at the edges of the view
+pub fn update_spatial_listener(
+    mut commands: Commands,
+    mut camera_query: Query<
+        (
+            Entity,
+            &OrthographicProjection,
+            Option<&mut SpatialListener>,
+        ),
+        With<MainCamera>,
+    >,
+) {
+    for (entity, projection, listener) in camera_query.iter_mut() {
+        let half_width = view_half_width(projection);
+        match listener {
+            Some(mut listener) => {
+                if listener.right_ear_offset.x != half_width {
+                    listener.left_ear_offset = Vec3::new(-half_width, 0.0, 0.0);
+                    listener.right_ear_offset = Vec3::new(half_width, 0.0, 0.0);
+                }
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(SpatialListener::new(half_width * 2.0));
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_sfx(
+    mut commands: Commands,
+    mut events: EventReader<PlaySFX>,
+    mut sound_resource: ResMut<SoundResource>,
+    (channels, tweens): (Res<AudioChannels>, Res<VolumeTweens>),
+    positional: Res<PositionalAudio>,
+    config: Res<SoundConfig>,
+    time: Res<Time<Real>>,
+    mut limiter: ResMut<SfxRateLimiter>,
+    mut ducking: ResMut<MusicDucking>,
+    mut gate: PlaybackGate,
+    mut captions: Captions,
+    mut log: SoundLogger,
+    voice_query: Query<(Entity, &SfxVoice)>,
+    camera_query: Query<(&GlobalTransform, &OrthographicProjection), With<MainCamera>>,
+    (time_scale, mut pool): (Option<Res<TimeScale>>, Option<ResMut<Pool<OneShotSfx>>>),
+    (game_rng, mut rng): (Option<Res<GameRng>>, Local<Option<GameRng>>),
+) {
+    let listener = camera_query
+        .get_single()
+        .ok()
+        .map(|(transform, projection)| {
+            (
+                transform.translation().truncate(),
+                view_half_width(projection),
+            )
+        });
+
+    let mut voices: Vec<(Entity, SfxPriority, f64)> = voice_query
+        .iter()
+        .map(|(entity, voice)| (entity, voice.priority, voice.started))
+        .collect();
+
+    if channels.muted {
+        for event in events.read() {
+            log.record(
+                SoundAction::PlaySfx,
+                &event.name,
+                SoundOutcome::Skipped("muted"),
+                None,
+            );
+        }
+        return;
+    }
+
+    let sfx_level = channels.effective(AudioChannel::Sfx, &tweens);
+
+    // dropped rather than queued, so they don't all burst out when the window comes back
+    if gate.focus.is_paused() {
+        for event in events.read() {
+            log.record(
+                SoundAction::PlaySfx,
+                &event.name,
+                SoundOutcome::Skipped("backgrounded"),
+                None,
+            );
+        }
+        return;
+    }
+
+    let now = time.elapsed_seconds_f64();
+    if !gate.unlocked.0 {
+        for event in events.read() {
+            gate.pending.queue_sfx(now, event.clone());
+            log.record(
+                SoundAction::PlaySfx,
+                &event.name,
+                SoundOutcome::Queued,
+                None,
+            );
+        }
+        return;
+    }
+
+    let rng = rng.get_or_insert_with(|| rng::fork_or_entropy(game_rng.as_deref(), "sfx"));
+    for event in events.read() {
+        let Some(handle) = event.resolve(&mut sound_resource, rng) else {
+            warn!("Sound not found: {}", event.name);
+            log.record(
+                SoundAction::PlaySfx,
+                &event.name,
+                SoundOutcome::Missing,
+                None,
+            );
+            continue;
+        };
+
+        match AudioLoad::of(&gate.asset_server, &handle) {
+            AudioLoad::Ready => {}
+            AudioLoad::Loading if config.sfx_load_wait.is_some() => {
+                gate.waiting.queue_sfx(now, event.clone());
+                log.record(
+                    SoundAction::PlaySfx,
+                    &event.name,
+                    SoundOutcome::Queued,
+                    None,
+                );
+                continue;
+            }
+            AudioLoad::Loading => {
+                warn!("Dropping sound that is still loading: {}", event.name);
+                log.record(
+                    SoundAction::PlaySfx,
+                    &event.name,
+                    SoundOutcome::Skipped("still loading"),
+                    None,
+                );
+                continue;
+            }
+            AudioLoad::Failed => {
+                warn!("Sound failed to load: {}", event.name);
+                log.record(
+                    SoundAction::PlaySfx,
+                    &event.name,
+                    SoundOutcome::Skipped("failed to load"),
+                    None,
+                );
+                continue;
+            }
+        }
+
+        let defaults = sound_resource.defaults(&event.name);
+        let interval = defaults.min_interval.unwrap_or(config.min_sfx_interval);
+        let allowed = match &event.handle {
+            Some(handle) => limiter.try_play_handle(handle.id(), now, interval),
+            None => limiter.try_play(&event.name, now, interval),
+        };
+        if !allowed {
+            if config.verbose {
+                debug!("Rate limited sound: {}", event.name);
+            }
+            log.record(
+                SoundAction::PlaySfx,
+                &event.name,
+                SoundOutcome::Skipped("rate limited"),
+                None,
+            );
+            continue;
+        }
+
+        let mut volume = event.volume.unwrap_or(defaults.volume);
+        let mut emitter = None;
+        if let (Some(position), Some((listener, half_width)), true) =
+            (event.position, listener, defaults.spatial)
+        {
+            let offset = position - listener;
+            let distance = offset.length();
+            if distance > positional.max_distance {
+                log.record(
+                    SoundAction::PlaySfx,
+                    &event.name,
+                    SoundOutcome::Skipped("out of range"),
+                    None,
+                );
+                continue;
+            }
+            volume *= 1.0 - distance / positional.max_distance;
+            // keep the emitter within half a view of the listener so rodio's own
+            // distance falloff doesn't stack on top of the attenuation above
+            emitter = Some((listener + offset.clamp_length_max(half_width), half_width));
+        }
+
+        if voices.len() >= config.max_sfx_voices {
+            let Some(index) = voice_to_replace(&voices, event.priority) else {
+                if config.verbose {
+                    debug!("Voice cap reached, dropping sound: {}", event.name);
+                }
+                log.record(
+                    SoundAction::PlaySfx,
+                    &event.name,
+                    SoundOutcome::Skipped("voice cap"),
+                    None,
+                );
+                continue;
+            };
+            let (replaced, _, _) = voices.swap_remove(index);
+            commands.entity(replaced).despawn();
+        }
+
+        let jitter = event.speed_jitter.unwrap_or(defaults.speed_jitter);
+        let mut speed = event.speed.unwrap_or(defaults.speed) * sample_speed(jitter, rng);
+        if let Some(time_scale) = time_scale
+            .as_ref()
+            .filter(|_| config.pitch_follows_time_scale)
+        {
+            speed *= time_scale.get();
+        }
+
+        // the ducking lasts as long as the entity, so sounds ducking the music aren't pooled
+        let pool = pool
+            .as_deref_mut()
+            .filter(|_| !event.looping && defaults.duck_music.is_none());
+        let bundle = (
+            AudioSourceBundle {
+                source: handle,
+                settings: PlaybackSettings {
+                    mode: match (event.looping, pool.is_some()) {
+                        (true, _) => PlaybackMode::Loop,
+                        (false, true) => PlaybackMode::Remove,
+                        (false, false) => PlaybackMode::Despawn,
+                    },
+                    volume: Volume::new(volume * sfx_level),
+                    speed,
+                    spatial: emitter.is_some(),
+                    spatial_scale: emitter
+                        .map(|(_, half_width)| SpatialScale::new_2d(1.0 / half_width)),
+                    ..default()
+                },
+            },
+            SfxVoice {
+                priority: event.priority,
+                started: now,
+                volume,
+            },
+            SfxTag(event.name.clone()),
+        );
+        let entity = match pool {
+            Some(pool) => pool.spawn_pooled(&mut commands, bundle),
+            None => commands.spawn(bundle).id(),
+        };
+        let mut entity = commands.entity(entity);
+        voices.push((entity.id(), event.priority, now));
+        if let Some(level) = defaults.duck_music {
+            ducking.duck_while(entity.id(), level);
+        }
+        if let Some((position, _)) = emitter {
+            entity.insert(TransformBundle::from_transform(
+                Transform::from_translation(position.extend(0.0)),
+            ));
+        }
+        captions.emit(&sound_resource, &event.name, event.position, now);
+        log.record(
+            SoundAction::PlaySfx,
+            &event.name,
+            SoundOutcome::Played,
+            Some(volume * sfx_level),
+        );
+    }
+}
+
+// bevy removes the source once the sound is done
+type FinishedOneShot = (
+    With<OneShotSfx>,
+    Without<Handle<AudioSource>>,
+    Without<InPool>,
+);
+
+///
+/// release_one_shot_sfx: Bevy system
+///
+/// Gives pooled SFX back to their pool once bevy has removed their audio source, when they've
+/// finished playing
+pub fn release_one_shot_sfx(
+    mut commands: Commands,
+    mut pool: Option<ResMut<Pool<OneShotSfx>>>,
+    finished_query: Query<Entity, FinishedOneShot>,
+) {
+    for entity in finished_query.iter() {
+        match pool.as_deref_mut() {
+            Some(pool) => pool.release(&mut commands, entity),
+            None => commands.entity(entity).despawn(),
+        }
+    }
+}
+
+pub fn stop_sfx(
+    mut commands: Commands,
+    mut events: EventReader<StopSFX>,
+    mut log: SoundLogger,
+    sfx_query: Query<(Entity, &SfxTag), Without<SfxFadeOut>>,
+) {
+    for event in events.read() {
+        let name = match event {
+            StopSFX::ByName { name, .. } => name.as_str(),
+            StopSFX::All { .. } => "*",
+        };
+        log.record(SoundAction::StopSfx, name, SoundOutcome::Stopped, None);
+        for (entity, _) in sfx_query.iter().filter(|(_, tag)| event.matches(tag)) {
+            if event.fade_out() > 0.0 {
+                commands.entity(entity).insert(SfxFadeOut {
+                    from: None,
+                    duration: event.fade_out(),
+                    elapsed: 0.0,
+                });
+            } else {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+type SfxSinks<'a> = (
+    &'a SfxVoice,
+    Option<&'a AudioSink>,
+    Option<&'a SpatialAudioSink>,
+);
+
+///
+/// apply_sfx_volume: Bevy system
+///
+/// Applies SFX channel changes (volume, tweens and mute) to SFX that are already playing
+pub fn apply_sfx_volume(
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    mut applied: Local<Option<f32>>,
+    voice_query: Query<SfxSinks, Without<SfxFadeOut>>,
+) {
+    let level = channels.effective(AudioChannel::Sfx, &tweens);
+    if *applied == Some(level) {
+        return;
+    }
+    *applied = Some(level);
+
+    for (voice, sink, spatial_sink) in voice_query.iter() {
+        let volume = voice.volume * level;
+        if let Some(sink) = sink {
+            sink.set_volume(volume);
+        }
+        if let Some(sink) = spatial_sink {
+            sink.set_volume(volume);
+        }
+    }
+}
+
+///
+/// update_sfx_fades: Bevy system
+///
+/// Ramps stopped SFX down from the volume they were playing at, in real time
+pub fn update_sfx_fades(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut fade_query: Query<(
+        Entity,
+        &mut SfxFadeOut,
+        Option<&AudioSink>,
+        Option<&SpatialAudioSink>,
+    )>,
+) {
+    for (entity, mut fade, sink, spatial_sink) in fade_query.iter_mut() {
+        let sink: &dyn AudioSinkPlayback = match (sink, spatial_sink) {
+            (Some(sink), _) => sink,
+            (_, Some(sink)) => sink,
+            // never started, so there's nothing to fade
+            (None, None) => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+
+        let from = *fade.from.get_or_insert_with(|| sink.volume());
+        fade.elapsed += time.delta_seconds();
+        let t = (fade.elapsed / fade.duration).clamp(0.0, 1.0);
+        sink.set_volume(from * (1.0 - t));
+
+        if t >= 1.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Half the width of the camera's view in world units
+pub(crate) fn view_half_width(projection: &OrthographicProjection) -> f32 {
+    projection.area.half_size().x.max(1.0)
+}
@@ -0,0 +1,184 @@
+use super::{
+    captions::Captions,
+    log::{SoundAction, SoundLogger, SoundOutcome},
+    AudioChannel, AudioChannels, AudioUnlocked, MusicDucking, PauseMusic, ResumeMusic,
+    SoundResource, VolumeTweens,
+};
+use bevy::{
+    audio::{
+        AudioSink, AudioSinkPlayback, AudioSourceBundle, PlaybackMode, PlaybackSettings, Volume,
+    },
+    ecs::entity::Entities,
+    prelude::*,
+};
+use std::collections::VecDeque;
+
+///
+/// PlayStinger
+///
+/// A short one-shot (level-up fanfare, etc.) on the music channel, played over the current
+/// track without restarting it. Stingers sent while one is playing wait their turn.
+///
+/// * name: the name the stinger was registered under in SoundResource
+/// * duck_music_to: fraction of the music volume to drop the track to while the stinger plays
+/// * replace: pause the track instead, resuming it once the stinger is over
+#[derive(Event, Debug, Clone)]
+pub struct PlayStinger {
+    pub name: String,
+    pub duck_music_to: Option<f32>,
+    pub replace: bool,
+}
+
+impl PlayStinger {
+    pub fn new(name: impl Into<String>) -> Self {
+        PlayStinger {
+            name: name.into(),
+            duck_music_to: None,
+            replace: false,
+        }
+    }
+
+    pub fn with_duck_music_to(mut self, level: f32) -> Self {
+        self.duck_music_to = Some(level.clamp(0.0, 1.0));
+        self
+    }
+
+    pub fn replacing(mut self) -> Self {
+        self.replace = true;
+        self
+    }
+}
+
+/// Marks a stinger entity
+#[derive(Debug, Component)]
+pub struct Stinger {
+    pub name: String,
+}
+
+///
+/// Stingers
+///
+/// The stinger that is playing and the ones waiting behind it
+#[derive(Debug, Default, Resource)]
+pub struct Stingers {
+    queue: VecDeque<PlayStinger>,
+    // the stinger entity and whether it paused the music
+    playing: Option<(Entity, bool)>,
+}
+
+impl Stingers {
+    pub fn is_playing(&self) -> bool {
+        self.playing.is_some()
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+///
+/// play_stingers: Bevy system
+///
+/// Queues PlayStinger requests and, once the previous stinger has despawned, hands the music
+/// back and starts the next one
+#[allow(clippy::too_many_arguments)]
+pub fn play_stingers(
+    mut commands: Commands,
+    entities: &Entities,
+    mut events: EventReader<PlayStinger>,
+    mut stingers: ResMut<Stingers>,
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    unlocked: Res<AudioUnlocked>,
+    mut ducking: ResMut<MusicDucking>,
+    mut pause_events: EventWriter<PauseMusic>,
+    mut resume_events: EventWriter<ResumeMusic>,
+    mut captions: Captions,
+    mut log: SoundLogger,
+    time: Res<Time<Real>>,
+) {
+    stingers.queue.extend(events.read().cloned());
+
+    if let Some((entity, paused)) = stingers.playing {
+        if entities.contains(entity) {
+            return;
+        }
+        stingers.playing = None;
+        if paused {
+            resume_events.send(ResumeMusic);
+        }
+    }
+    if !unlocked.0 {
+        return;
+    }
+
+    while let Some(event) = stingers.queue.pop_front() {
+        let Some(handle) = sound_resource.get(&event.name) else {
+            warn!("Stinger not found: {}", event.name);
+            log.record(
+                SoundAction::PlayStinger,
+                &event.name,
+                SoundOutcome::Missing,
+                None,
+            );
+            continue;
+        };
+
+        let volume = sound_resource.defaults(&event.name).volume
+            * channels.effective(AudioChannel::Music, &tweens);
+        let entity = commands
+            .spawn((
+                AudioSourceBundle {
+                    source: handle,
+                    settings: PlaybackSettings {
+                        mode: PlaybackMode::Despawn,
+                        volume: Volume::new(volume),
+                        ..default()
+                    },
+                },
+                Stinger {
+                    name: event.name.clone(),
+                },
+            ))
+            .id();
+
+        if event.replace {
+            pause_events.send(PauseMusic);
+        } else if let Some(level) = event.duck_music_to {
+            // released by the ducking system once the stinger despawns
+            ducking.duck_while(entity, level);
+        }
+        stingers.playing = Some((entity, event.replace));
+
+        captions.emit(
+            &sound_resource,
+            &event.name,
+            None,
+            time.elapsed_seconds_f64(),
+        );
+        log.record(
+            SoundAction::PlayStinger,
+            &event.name,
+            SoundOutcome::Played,
+            Some(volume),
+        );
+        break;
+    }
+}
+
+///
+/// apply_stinger_volume: Bevy system
+///
+/// Keeps stingers on the music channel volume, mute included. Ducking doesn't apply to them.
+pub fn apply_stinger_volume(
+    sound_resource: Res<SoundResource>,
+    channels: Res<AudioChannels>,
+    tweens: Res<VolumeTweens>,
+    stinger_query: Query<(&Stinger, &AudioSink)>,
+) {
+    let level = channels.effective(AudioChannel::Music, &tweens);
+    for (stinger, sink) in stinger_query.iter() {
+        sink.set_volume(sound_resource.defaults(&stinger.name).volume * level);
+    }
+}
@@ -0,0 +1,134 @@
+use super::AudioChannel;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Shape of a volume tween over its duration
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    #[default]
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps progress `t` in 0..=1 to the fraction of the change applied
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+///
+/// TweenVolume
+///
+/// Moves a channel's tween level to `to` over `duration` seconds of real time. A new tween on
+/// the same channel replaces the running one, starting from wherever it got to.
+///
+/// The level multiplies the player's volume for the channel rather than replacing it, so a
+/// fade to silence and back never touches the saved AudioChannels.
+///
+/// * channel: the channel to move
+/// * to: target level as a fraction of the player's volume, 0.0 - 1.0
+/// * duration: seconds to get there (0.0 jumps straight to it)
+/// * easing: shape of the ramp
+#[derive(Event, Debug, Clone)]
+pub struct TweenVolume {
+    pub channel: AudioChannel,
+    pub to: f32,
+    pub duration: f32,
+    pub easing: Easing,
+}
+
+impl TweenVolume {
+    pub fn new(channel: AudioChannel, to: f32, duration: f32) -> Self {
+        TweenVolume {
+            channel,
+            to: to.clamp(0.0, 1.0),
+            duration: duration.max(0.0),
+            easing: Easing::default(),
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Tween {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+}
+
+///
+/// VolumeTweens
+///
+/// The tween running on each channel, if any, and the level each channel's tweens left it at
+#[derive(Debug, Default, Resource)]
+pub struct VolumeTweens {
+    tweens: HashMap<AudioChannel, Tween>,
+    levels: HashMap<AudioChannel, f32>,
+}
+
+impl VolumeTweens {
+    pub fn is_tweening(&self, channel: AudioChannel) -> bool {
+        self.tweens.contains_key(&channel)
+    }
+
+    /// Multiplier on the channel's volume, 1.0 until a tween moves it
+    pub fn level(&self, channel: AudioChannel) -> f32 {
+        self.levels.get(&channel).copied().unwrap_or(1.0)
+    }
+}
+
+///
+/// tween_volume: Bevy system
+///
+/// Starts TweenVolume requests and advances the running tweens. The level is always
+/// recomputed from the tween's start rather than stepped, and snaps to `to` at the end.
+pub fn tween_volume(
+    time: Res<Time<Real>>,
+    mut events: EventReader<TweenVolume>,
+    mut tweens: ResMut<VolumeTweens>,
+) {
+    for event in events.read() {
+        let from = tweens.level(event.channel);
+        tweens.tweens.insert(
+            event.channel,
+            Tween {
+                from,
+                to: event.to.clamp(0.0, 1.0),
+                duration: event.duration,
+                elapsed: 0.0,
+                easing: event.easing,
+            },
+        );
+    }
+    if tweens.tweens.is_empty() {
+        return;
+    }
+
+    let delta = time.delta_seconds();
+    let VolumeTweens { tweens, levels } = &mut *tweens;
+    tweens.retain(|channel, tween| {
+        tween.elapsed += delta;
+        if tween.duration <= 0.0 || tween.elapsed >= tween.duration {
+            levels.insert(*channel, tween.to);
+            return false;
+        }
+        let t = tween.easing.apply(tween.elapsed / tween.duration);
+        levels.insert(*channel, tween.from + (tween.to - tween.from) * t);
+        true
+    });
+}
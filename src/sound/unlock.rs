@@ -0,0 +1,92 @@
+use super::{PlayMusic, PlaySFX};
+use bevy::{input::touch::Touches, prelude::*};
+
+/// Queued SFX older than this many seconds are dropped instead of played late
+const STALE_SFX: f64 = 1.0;
+
+///
+/// AudioUnlocked
+///
+/// Whether sound can play yet. Browsers block audio until the player interacts with the
+/// page, so on wasm this starts false and flips on the first click, key press or touch.
+#[derive(Debug, Clone, Copy, Resource)]
+pub struct AudioUnlocked(pub bool);
+
+impl Default for AudioUnlocked {
+    fn default() -> Self {
+        AudioUnlocked(!cfg!(target_arch = "wasm32"))
+    }
+}
+
+///
+/// PendingAudio
+///
+/// Requests made while audio was locked, replayed once it unlocks
+#[derive(Debug, Default, Resource)]
+pub struct PendingAudio {
+    music: Option<PlayMusic>,
+    sfx: Vec<(f64, PlaySFX)>,
+}
+
+impl PendingAudio {
+    /// Only the latest music request is kept
+    pub(crate) fn queue_music(&mut self, event: PlayMusic) {
+        self.music = Some(event);
+    }
+
+    pub(crate) fn cancel_music(&mut self) {
+        self.music = None;
+    }
+
+    pub(crate) fn queue_sfx(&mut self, now: f64, event: PlaySFX) {
+        self.sfx.push((now, event));
+    }
+}
+
+pub fn audio_locked(unlocked: Res<AudioUnlocked>) -> bool {
+    !unlocked.0
+}
+
+///
+/// unlock_audio_on_input: Bevy system
+///
+/// Unlocks audio on the first mouse click, key press or touch
+pub fn unlock_audio_on_input(
+    mut unlocked: ResMut<AudioUnlocked>,
+    mouse: Option<Res<ButtonInput<MouseButton>>>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    touches: Option<Res<Touches>>,
+) {
+    let gesture = mouse.is_some_and(|mouse| mouse.get_just_pressed().next().is_some())
+        || keys.is_some_and(|keys| keys.get_just_pressed().next().is_some())
+        || touches.is_some_and(|touches| touches.any_just_pressed());
+    if gesture {
+        unlocked.0 = true;
+    }
+}
+
+///
+/// flush_pending_audio: Bevy system
+///
+/// Resends the requests queued while audio was locked, skipping SFX that are too old to matter
+pub fn flush_pending_audio(
+    time: Res<Time<Real>>,
+    unlocked: Res<AudioUnlocked>,
+    mut pending: ResMut<PendingAudio>,
+    mut music_events: EventWriter<PlayMusic>,
+    mut sfx_events: EventWriter<PlaySFX>,
+) {
+    if !unlocked.0 {
+        return;
+    }
+
+    if let Some(event) = pending.music.take() {
+        music_events.send(event);
+    }
+    let now = time.elapsed_seconds_f64();
+    for (requested, event) in pending.sfx.drain(..) {
+        if now - requested <= STALE_SFX {
+            sfx_events.send(event);
+        }
+    }
+}
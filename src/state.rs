@@ -0,0 +1,467 @@
+//! Where the game is, from loading to game over. Code asks for a change with ChangeState or
+//! TogglePause instead of reaching for NextState, so the rules about which changes are allowed
+//! live here.
+
+use crate::input::{Action, ActionState};
+use crate::loading::LoadingPlugin;
+use crate::sound::{DuckMusic, StopDucking};
+use bevy::{prelude::*, window::WindowOccluded};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Most virtual time a frame may add right after the window comes back, so a browser's first
+/// throttled frame doesn't jump the world ahead
+const RESUME_MAX_DELTA: Duration = Duration::from_millis(33);
+
+///
+/// AppState
+///
+/// * Loading: until the assets the game starts with are in, see LoadProgress
+/// * MainMenu: the main menu is up, see MainMenuPlugin
+/// * InGame: playing
+/// * Paused: the world frozen, menus and music still going
+/// * GameOver: the run has ended
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppState {
+    #[default]
+    Loading,
+    MainMenu,
+    InGame,
+    Paused,
+    GameOver,
+}
+
+impl AppState {
+    /// The state by its name in snake_case, e.g. "in_game"
+    pub fn from_name(name: &str) -> Option<AppState> {
+        match name {
+            "loading" => Some(AppState::Loading),
+            "main_menu" => Some(AppState::MainMenu),
+            "in_game" => Some(AppState::InGame),
+            "paused" => Some(AppState::Paused),
+            "game_over" => Some(AppState::GameOver),
+            _ => None,
+        }
+    }
+
+    /// Whether Loading can go straight on to the state, see StartOptions::skip_to
+    pub fn can_skip_to(self) -> bool {
+        matches!(
+            self,
+            AppState::MainMenu | AppState::InGame | AppState::GameOver
+        )
+    }
+}
+
+/// Asks to move to another state. Paused can only be entered from InGame; other changes are
+/// always allowed.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeState(pub AppState);
+
+/// Pauses the game when InGame, resumes it when Paused, and does nothing otherwise. Sent by the
+/// keys of Action::Pause.
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct TogglePause;
+
+/// Freezes the world for this many seconds of real time, e.g. for a heavy hit to land.
+/// Overlapping hitstops don't add up: the one ending last wins.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct Hitstop(pub f32);
+
+/// Slowest TimeScale, so slow motion never quite stops the world: that's what pausing is for
+pub const MIN_TIME_SCALE: f32 = 0.05;
+/// Fastest TimeScale
+pub const MAX_TIME_SCALE: f32 = 4.0;
+/// Seconds a timed SetTimeScale takes to ease back to 1.0
+pub const TIME_SCALE_RAMP: f32 = 0.25;
+
+///
+/// SetTimeScale
+///
+/// Runs virtual time, and with it FixedUpdate, at `scale` times real time: below 1.0 for slow
+/// motion, above for fast-forward. The scale is clamped to MIN_TIME_SCALE..=MAX_TIME_SCALE, and
+/// 0 or less is ignored. With a duration in real seconds, it eases back to 1.0 afterwards.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct SetTimeScale {
+    pub scale: f32,
+    pub duration: Option<f32>,
+}
+
+impl SetTimeScale {
+    /// Until the next SetTimeScale
+    pub fn new(scale: f32) -> Self {
+        SetTimeScale {
+            scale,
+            duration: None,
+        }
+    }
+
+    /// For `duration` real seconds
+    pub fn for_seconds(scale: f32, duration: f32) -> Self {
+        SetTimeScale {
+            scale,
+            duration: Some(duration),
+        }
+    }
+}
+
+///
+/// TimeScale
+///
+/// The speed virtual time runs at when nothing holds it, set with SetTimeScale
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct TimeScale {
+    scale: f32,
+    // real seconds before a timed scale starts easing back
+    remaining: Option<f32>,
+    // the scale eased back from, and seconds into the ramp
+    ramp: Option<(f32, f32)>,
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        TimeScale {
+            scale: 1.0,
+            remaining: None,
+            ramp: None,
+        }
+    }
+}
+
+impl TimeScale {
+    pub fn get(&self) -> f32 {
+        self.scale
+    }
+}
+
+/// Gameplay systems, which only run InGame and while the window is shown. Plugins put their
+/// systems in it whether or not the app has states: without AppStatePlugin nothing gates it.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GameplaySet;
+
+///
+/// AppStatePlugin
+///
+/// AppState, the events changing it, Loading, and GameplaySet only running InGame. Pausing stops
+/// Time<Virtual>, which FixedUpdate runs on, so what's timed in virtual time stops too; UI and
+/// sound run on real time and carry on. So do hiding the window, see PauseOnHidden, and Hitstop.
+pub struct AppStatePlugin;
+
+impl Plugin for AppStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<AppState>()
+            .add_plugins(LoadingPlugin)
+            .init_resource::<PauseOnHidden>()
+            .init_resource::<PauseOnKey>()
+            .init_resource::<DuckMusicWhenPaused>()
+            .init_resource::<TimeHolds>()
+            .init_resource::<TimeScale>()
+            .add_event::<ChangeState>()
+            .add_event::<TogglePause>()
+            .add_event::<Hitstop>()
+            .add_event::<SetTimeScale>()
+            .add_event::<WindowOccluded>()
+            .add_event::<DuckMusic>()
+            .add_event::<StopDucking>()
+            .configure_sets(Update, GameplaySet.run_if(gameplay_running))
+            .configure_sets(FixedUpdate, GameplaySet.run_if(gameplay_running))
+            .configure_sets(PostUpdate, GameplaySet.run_if(gameplay_running))
+            .add_systems(OnEnter(AppState::Paused), hold_for_menu)
+            .add_systems(OnExit(AppState::Paused), release_menu_hold)
+            .add_systems(
+                Update,
+                (
+                    (pause_on_key, apply_state_changes).chain(),
+                    // after the state transitions of the frame
+                    (
+                        hold_while_hidden,
+                        update_hitstop,
+                        update_time_scale,
+                        apply_time_holds,
+                    )
+                        .chain(),
+                ),
+            );
+
+        #[cfg(feature = "dev")]
+        register_console_commands(app);
+    }
+}
+
+/// timescale for the console
+#[cfg(feature = "dev")]
+fn register_console_commands(app: &mut App) {
+    use crate::console::RegisterConsoleCommand;
+
+    app.register_console_command(
+        "timescale <scale> [seconds]",
+        "Slows down or speeds up the game, for a while if seconds are given",
+        |args, world| {
+            let scale: f32 = args.required("scale")?;
+            let event = match args.optional::<f32>("seconds")? {
+                Some(seconds) => SetTimeScale::for_seconds(scale, seconds),
+                None => SetTimeScale::new(scale),
+            };
+            world.send_event(event);
+            Ok(format!("Time scale {}", scale))
+        },
+    );
+}
+
+/// Whether the game pauses while its window is hidden, e.g. in a browser tab that isn't shown
+/// (on by default). The attract-mode demo turns it off to keep playing in the background.
+/// Sound pauses separately, see PauseAudioOnFocusLoss.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PauseOnHidden(pub bool);
+
+impl Default for PauseOnHidden {
+    fn default() -> Self {
+        PauseOnHidden(true)
+    }
+}
+
+/// Whether Action::Pause toggles the pause (on by default). Screens over the pause menu turn it
+/// off while they use the key to back out.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PauseOnKey(pub bool);
+
+impl Default for PauseOnKey {
+    fn default() -> Self {
+        PauseOnKey(true)
+    }
+}
+
+/// The fraction of its volume music ducks to while Paused, None to play on at full volume
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct DuckMusicWhenPaused(pub Option<f32>);
+
+impl Default for DuckMusicWhenPaused {
+    fn default() -> Self {
+        DuckMusicWhenPaused(Some(0.4))
+    }
+}
+
+///
+/// TimeHolds
+///
+/// What's keeping Time<Virtual> stopped. Time only runs again once nothing holds it, so showing
+/// the window again doesn't end a pause the player asked for, a hitstop running out doesn't end
+/// either, and so on.
+/// * menu: the game is Paused
+/// * hidden: the window is hidden and PauseOnHidden is on
+/// * hitstop: real seconds of Hitstop left
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct TimeHolds {
+    pub menu: bool,
+    pub hidden: bool,
+    pub hitstop: f32,
+}
+
+impl TimeHolds {
+    pub fn any(&self) -> bool {
+        self.menu || self.hidden || self.hitstop > 0.0
+    }
+}
+
+/// Run condition of GameplaySet
+pub fn gameplay_running(state: Option<Res<State<AppState>>>, holds: Res<TimeHolds>) -> bool {
+    state.is_some_and(|state| *state.get() == AppState::InGame) && !holds.hidden
+}
+
+///
+/// apply_state_changes: Bevy system
+///
+/// Applies ChangeState and TogglePause, the last allowed one of a frame winning. The new state is
+/// entered at the start of the next frame.
+pub fn apply_state_changes(
+    mut changes: EventReader<ChangeState>,
+    mut toggles: EventReader<TogglePause>,
+    state: Res<State<AppState>>,
+    mut next_state: ResMut<NextState<AppState>>,
+) {
+    let mut current = *state.get();
+    let mut next = None;
+    for ChangeState(to) in changes.read() {
+        if *to == AppState::Paused && current != AppState::InGame {
+            warn!("Can't pause from {:?}", current);
+            continue;
+        }
+        next = Some(*to);
+        current = *to;
+    }
+    for _ in toggles.read() {
+        let to = match current {
+            AppState::InGame => AppState::Paused,
+            AppState::Paused => AppState::InGame,
+            _ => continue,
+        };
+        next = Some(to);
+        current = to;
+    }
+    if let Some(next) = next.filter(|next| next != state.get()) {
+        next_state.set(next);
+    }
+}
+
+/// Sends TogglePause when Action::Pause is pressed, Escape without InputPlugin, unless PauseOnKey
+/// is off
+pub fn pause_on_key(
+    setting: Res<PauseOnKey>,
+    actions: Option<Res<ActionState>>,
+    keys: Option<Res<ButtonInput<KeyCode>>>,
+    mut toggles: EventWriter<TogglePause>,
+) {
+    if !setting.0 {
+        return;
+    }
+    let pressed = match (actions, keys) {
+        (Some(actions), _) => actions.just_pressed(Action::Pause),
+        (None, Some(keys)) => keys.just_pressed(KeyCode::Escape),
+        (None, None) => false,
+    };
+    if pressed {
+        toggles.send(TogglePause);
+    }
+}
+
+fn hold_for_menu(
+    mut holds: ResMut<TimeHolds>,
+    duck: Res<DuckMusicWhenPaused>,
+    mut duck_music: EventWriter<DuckMusic>,
+) {
+    holds.menu = true;
+    if let Some(level) = duck.0 {
+        duck_music.send(DuckMusic::new(level));
+    }
+}
+
+fn release_menu_hold(
+    mut holds: ResMut<TimeHolds>,
+    duck: Res<DuckMusicWhenPaused>,
+    mut stop_ducking: EventWriter<StopDucking>,
+) {
+    holds.menu = false;
+    if duck.0.is_some() {
+        stop_ducking.send(StopDucking);
+    }
+}
+
+///
+/// hold_while_hidden: Bevy system
+///
+/// Keeps TimeHolds::hidden in step with the window being hidden and PauseOnHidden
+pub fn hold_while_hidden(
+    mut occluded_events: EventReader<WindowOccluded>,
+    setting: Res<PauseOnHidden>,
+    mut occluded: Local<bool>,
+    mut holds: ResMut<TimeHolds>,
+) {
+    if let Some(event) = occluded_events.read().last() {
+        *occluded = event.occluded;
+    }
+    let hidden = setting.0 && *occluded;
+    if holds.hidden != hidden {
+        holds.hidden = hidden;
+    }
+}
+
+///
+/// update_hitstop: Bevy system
+///
+/// Starts Hitstops and counts TimeHolds::hitstop down in real time
+pub fn update_hitstop(
+    mut events: EventReader<Hitstop>,
+    time: Res<Time<Real>>,
+    mut holds: ResMut<TimeHolds>,
+) {
+    if holds.hitstop > 0.0 {
+        holds.hitstop = (holds.hitstop - time.delta_seconds()).max(0.0);
+    }
+    for Hitstop(seconds) in events.read() {
+        if *seconds > holds.hitstop {
+            holds.hitstop = *seconds;
+        }
+    }
+}
+
+///
+/// update_time_scale: Bevy system
+///
+/// Handles SetTimeScale, and runs out timed scales: once their duration is up they ease back to
+/// 1.0 over TIME_SCALE_RAMP seconds. Both count real time, and stand still while TimeHolds holds
+/// time, so a pause doesn't use up a slow motion.
+pub fn update_time_scale(
+    mut events: EventReader<SetTimeScale>,
+    time: Res<Time<Real>>,
+    holds: Res<TimeHolds>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    for event in events.read() {
+        if !event.scale.is_finite() || event.scale <= 0.0 {
+            warn!(
+                "SetTimeScale: {} isn't a time scale; stop time with TogglePause or Hitstop",
+                event.scale
+            );
+            continue;
+        }
+        *time_scale = TimeScale {
+            scale: event.scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE),
+            remaining: event.duration.map(|duration| duration.max(0.0)),
+            ramp: None,
+        };
+    }
+    if holds.any() || (time_scale.remaining.is_none() && time_scale.ramp.is_none()) {
+        return;
+    }
+
+    let delta = time.delta_seconds();
+    let time_scale = &mut *time_scale;
+    if let Some(remaining) = time_scale.remaining.as_mut() {
+        *remaining -= delta;
+        if *remaining <= 0.0 {
+            time_scale.remaining = None;
+            time_scale.ramp = Some((time_scale.scale, 0.0));
+        }
+    } else if let Some((from, elapsed)) = time_scale.ramp.as_mut() {
+        *elapsed += delta;
+        let t = (*elapsed / TIME_SCALE_RAMP).min(1.0);
+        time_scale.scale = *from + (1.0 - *from) * t * t * (3.0 - 2.0 * t);
+        if t >= 1.0 {
+            time_scale.scale = 1.0;
+            time_scale.ramp = None;
+        }
+    }
+}
+
+///
+/// apply_time_holds: Bevy system
+///
+/// Stops Time<Virtual> while TimeHolds holds it by dropping its relative speed to zero, and runs
+/// it at the TimeScale otherwise. Virtual time doesn't move while stopped, so FixedUpdate has no
+/// steps to catch up on afterwards. Coming back from being hidden, the next frame's virtual delta
+/// is also clamped to RESUME_MAX_DELTA.
+pub fn apply_time_holds(
+    holds: Res<TimeHolds>,
+    time_scale: Res<TimeScale>,
+    mut time: ResMut<Time<Virtual>>,
+    mut was_hidden: Local<bool>,
+    mut clamped_from: Local<Option<Duration>>,
+) {
+    // the clamped frame has gone by
+    if let Some(max_delta) = clamped_from.take() {
+        time.set_max_delta(max_delta);
+    }
+
+    let speed = if holds.any() { 0.0 } else { time_scale.get() };
+    if time.relative_speed() != speed {
+        let resuming = time.relative_speed() == 0.0;
+        time.set_relative_speed(speed);
+        if resuming && *was_hidden {
+            let max_delta = time.max_delta();
+            *clamped_from = Some(max_delta);
+            time.set_max_delta(RESUME_MAX_DELTA.min(max_delta));
+        }
+    }
+    *was_hidden = holds.hidden;
+}
@@ -0,0 +1,107 @@
+//! Small key/value persistence for settings and saves.
+//!
+//! On wasm the values live in `window.localStorage`; native builds write one file per key
+//! into a `settings` directory next to the executable's working directory.
+//!
+//! save_atomic is for values that mustn't be lost to a write cut short, like saved games: the
+//! value goes to temp_key(key) first, so there's always a whole copy under one of the two.
+
+use thiserror::Error;
+
+/// Prefix keeping our keys apart from anything else on the same origin
+#[cfg(target_arch = "wasm32")]
+const KEY_PREFIX: &str = "gamedevjam2024.";
+
+#[cfg(not(target_arch = "wasm32"))]
+const SETTINGS_DIR: &str = "settings";
+
+#[derive(Error, Debug)]
+pub enum StorageError {
+    #[error("storage is unavailable")]
+    Unavailable,
+    #[error("could not write to storage: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Reads the value stored under `key`, or None if there is none or storage can't be reached
+#[cfg(target_arch = "wasm32")]
+pub fn load(key: &str) -> Option<String> {
+    local_storage()?
+        .get_item(&format!("{}{}", KEY_PREFIX, key))
+        .ok()?
+}
+
+/// Stores `value` under `key`, replacing what was there
+#[cfg(target_arch = "wasm32")]
+pub fn save(key: &str, value: &str) -> Result<(), StorageError> {
+    local_storage()
+        .ok_or(StorageError::Unavailable)?
+        .set_item(&format!("{}{}", KEY_PREFIX, key), value)
+        // quota exceeded or storage denied
+        .map_err(|_| StorageError::Unavailable)
+}
+
+/// Removes the value stored under `key`, if any
+#[cfg(target_arch = "wasm32")]
+pub fn remove(key: &str) {
+    if let Some(storage) = local_storage() {
+        let _ = storage.remove_item(&format!("{}{}", KEY_PREFIX, key));
+    }
+}
+
+/// Stores `value` under `key` by way of temp_key(key): if the tab closes halfway the old value
+/// or the new one is left whole under one of them
+#[cfg(target_arch = "wasm32")]
+pub fn save_atomic(key: &str, value: &str) -> Result<(), StorageError> {
+    let temp = temp_key(key);
+    save(&temp, value)?;
+    save(key, value)?;
+    remove(&temp);
+    Ok(())
+}
+
+/// localStorage throws in some private browsing modes instead of returning None
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+/// Reads the value stored under `key`, or None if there is none or storage can't be reached
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load(key: &str) -> Option<String> {
+    std::fs::read_to_string(path(key)).ok()
+}
+
+/// Stores `value` under `key`, replacing what was there
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save(key: &str, value: &str) -> Result<(), StorageError> {
+    std::fs::create_dir_all(SETTINGS_DIR)?;
+    std::fs::write(path(key), value)?;
+    Ok(())
+}
+
+/// Removes the value stored under `key`, if any
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove(key: &str) {
+    let _ = std::fs::remove_file(path(key));
+}
+
+/// Stores `value` under `key` by writing temp_key(key) and renaming it over `key`, so a write
+/// cut short leaves the old value
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_atomic(key: &str, value: &str) -> Result<(), StorageError> {
+    let temp = temp_key(key);
+    save(&temp, value)?;
+    std::fs::rename(path(&temp), path(key))?;
+    Ok(())
+}
+
+/// Where save_atomic writes `key`'s value before it's in place
+pub fn temp_key(key: &str) -> String {
+    format!("{}.tmp", key)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn path(key: &str) -> std::path::PathBuf {
+    std::path::Path::new(SETTINGS_DIR).join(format!("{}.ron", key))
+}
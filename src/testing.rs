@@ -0,0 +1,117 @@
+//! Building the game's plugins into an App without a window, renderer or audio device, to drive
+//! their systems frame by frame with `app.update()` from tests. Enabled by the test-harness
+//! feature, which the crate's own tests turn on.
+
+use crate::destructible::DestructiblePlugin;
+use crate::dropped_map::DroppedMapPlugin;
+use crate::gfx::GFXPlugin;
+use crate::helpers::tiled::TiledMapPlugin;
+use crate::input::InputPlugin;
+use crate::locale::LocalePlugin;
+use crate::pool::PoolPlugin;
+use crate::replay::ReplayPlugin;
+use crate::rng::GameRng;
+use crate::settings::SettingsPlugin;
+use crate::sound::{SoundPlugin, SoundResource};
+use crate::timestep::FixedStepPlugin;
+use crate::toast::ToastPlugin;
+use bevy::{
+    audio::AudioSource,
+    prelude::*,
+    time::TimeUpdateStrategy,
+    window::{PrimaryWindow, WindowResolution},
+};
+use std::time::Duration;
+
+/// The seed of game_app's GameRng
+pub const TEST_SEED: u64 = 2024;
+
+/// How far time moves on each update: one FixedUpdate step at DEFAULT_FIXED_HZ, so FixedUpdate
+/// systems run exactly once a frame
+pub const FRAME: Duration = Duration::from_nanos(15_625_000);
+
+/// MinimalPlugins and assets from disk without watching them, on a clock that moves FRAME every
+/// update. The asset types the game's plugins expect are registered, since there's no renderer
+/// or audio output to do it.
+pub fn headless_app() -> App {
+    headless_app_after(App::new())
+}
+
+/// headless_app on an App that already has the plugins that must come before the AssetPlugin,
+/// like DroppedMapPlugin
+pub fn headless_app_after(mut app: App) -> App {
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin {
+            watch_for_changes_override: Some(false),
+            ..default()
+        },
+    ))
+    .init_asset::<Image>()
+    .init_asset::<TextureAtlasLayout>()
+    .init_asset::<AudioSource>()
+    .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+    app
+}
+
+/// headless_app with the game's plugins: everything the game runs apart from bevy's rendering,
+/// windowing and audio output. Settings aren't loaded from or saved to storage, and GameRng is
+/// seeded with TEST_SEED so runs repeat.
+pub fn game_app() -> App {
+    let mut before = App::new();
+    before.add_plugins(DroppedMapPlugin);
+    let mut app = headless_app_after(before);
+    app.add_plugins((
+        TiledMapPlugin,
+        GFXPlugin,
+        DestructiblePlugin,
+        SoundPlugin {
+            persist_settings: false,
+            manifests: Vec::new(),
+            ..default()
+        },
+        SettingsPlugin { persist: false },
+        InputPlugin,
+        FixedStepPlugin,
+        PoolPlugin,
+        LocalePlugin,
+        ToastPlugin,
+        ReplayPlugin,
+    ))
+    .insert_resource(GameRng::new(TEST_SEED));
+    app
+}
+
+/// Runs `frames` updates
+pub fn run_frames(app: &mut App, frames: u32) {
+    for _ in 0..frames {
+        app.update();
+    }
+}
+
+/// A silent sound registered in SoundResource under `name`, for the sound events to play
+pub fn stub_sound(app: &mut App, name: &str) -> Handle<AudioSource> {
+    let handle = app
+        .world
+        .resource_mut::<Assets<AudioSource>>()
+        .add(AudioSource {
+            bytes: Vec::new().into(),
+        });
+    app.world
+        .resource_mut::<SoundResource>()
+        .insert(name.to_string(), handle.clone());
+    handle
+}
+
+/// A primary window at `scale_factor`, standing in for the one winit would make
+pub fn spawn_window(app: &mut App, scale_factor: f32) -> Entity {
+    app.world
+        .spawn((
+            Window {
+                resolution: WindowResolution::default().with_scale_factor_override(scale_factor),
+                ..default()
+            },
+            PrimaryWindow,
+        ))
+        .id()
+}
@@ -0,0 +1,115 @@
+//! The fixed timestep: how often FixedUpdate runs, from FixedStepConfig, and the order of what
+//! runs in it. Gameplay code that needs a steady step goes in one of the FixedSets:
+//!
+//! ```ignore
+//! app.add_systems(FixedUpdate, move_enemies.in_set(FixedSet::Gameplay).in_set(GameplaySet));
+//! ```
+//!
+//! The rate can change while the game runs, with SetFixedRate. Anything interpolating between
+//! steps should use `Time<Fixed>::overstep_fraction`, which follows it, rather than assume
+//! DEFAULT_FIXED_HZ.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The rate FixedUpdate runs at unless told otherwise, bevy's own default
+pub const DEFAULT_FIXED_HZ: f64 = 64.0;
+pub const MIN_FIXED_HZ: f64 = 10.0;
+pub const MAX_FIXED_HZ: f64 = 240.0;
+
+///
+/// FixedStepConfig
+///
+/// How many times a second FixedUpdate runs, from MIN_FIXED_HZ to MAX_FIXED_HZ
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FixedStepConfig {
+    pub hz: f64,
+}
+
+impl Default for FixedStepConfig {
+    fn default() -> Self {
+        FixedStepConfig {
+            hz: DEFAULT_FIXED_HZ,
+        }
+    }
+}
+
+impl FixedStepConfig {
+    /// A config for `hz` steps a second, or None if that isn't a rate. It's clamped to
+    /// MIN_FIXED_HZ..=MAX_FIXED_HZ.
+    pub fn new(hz: f64) -> Option<Self> {
+        (hz.is_finite() && hz > 0.0).then(|| FixedStepConfig {
+            hz: hz.clamp(MIN_FIXED_HZ, MAX_FIXED_HZ),
+        })
+    }
+}
+
+///
+/// FixedSet
+///
+/// The stages of a FixedUpdate step, in order
+/// * Input: turning what the player asked for into intents
+/// * Gameplay: AI, movement intents, rules
+/// * Physics: moving things and resolving collisions
+/// * PostPhysics: what follows from where things ended up, e.g. animations
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FixedSet {
+    Input,
+    Gameplay,
+    Physics,
+    PostPhysics,
+}
+
+/// Changes FixedStepConfig::hz while the game runs, for experimenting
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SetFixedRate(pub f64);
+
+/// The fixed timestep and the order of the FixedSets
+pub struct FixedStepPlugin;
+
+impl Plugin for FixedStepPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FixedStepConfig>()
+            .add_event::<SetFixedRate>()
+            .configure_sets(
+                FixedUpdate,
+                (
+                    FixedSet::Input,
+                    FixedSet::Gameplay,
+                    FixedSet::Physics,
+                    FixedSet::PostPhysics,
+                )
+                    .chain(),
+            )
+            // before the frame's fixed steps
+            .add_systems(PreUpdate, (set_fixed_rate, apply_fixed_step).chain());
+    }
+}
+
+///
+/// set_fixed_rate: Bevy system
+///
+/// Handles SetFixedRate
+pub fn set_fixed_rate(mut events: EventReader<SetFixedRate>, mut config: ResMut<FixedStepConfig>) {
+    for SetFixedRate(hz) in events.read() {
+        match FixedStepConfig::new(*hz) {
+            Some(new_config) => *config = new_config,
+            None => warn!("SetFixedRate: {} isn't a rate", hz),
+        }
+    }
+}
+
+///
+/// apply_fixed_step: Bevy system
+///
+/// Sets Time<Fixed>'s timestep from FixedStepConfig when it changes
+pub fn apply_fixed_step(config: Res<FixedStepConfig>, mut time: ResMut<Time<Fixed>>) {
+    if !config.is_changed() {
+        return;
+    }
+    let hz = config.hz.clamp(MIN_FIXED_HZ, MAX_FIXED_HZ);
+    if (time.timestep().as_secs_f64() * hz - 1.0).abs() > 1e-9 {
+        info!("Fixed timestep: {} Hz", hz);
+        time.set_timestep_hz(hz);
+    }
+}
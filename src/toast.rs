@@ -0,0 +1,312 @@
+//! Short messages in the bottom right corner, such as "Game saved" or why a dropped map can't be
+//! played. Send ShowToast; toasts stack up with the newest at the bottom, slide in and fade out
+//! when their time is up, on real time so they do while the game is paused too. The same toast
+//! sent again while it's up counts up on it, "Game saved ×3", instead of stacking.
+//!
+//! Toasts aren't tied to a state: one raised while loading is still up in game.
+
+use bevy::prelude::*;
+
+/// Seconds a toast stays up by default
+pub const TOAST_TIME: f32 = 6.0;
+/// Most toasts shown at once, the oldest go first
+pub const MAX_TOASTS: usize = 4;
+/// Seconds after a toast last came in that the same one counts up on it
+pub const REPEAT_WINDOW: f32 = 2.0;
+/// Toasts are over everything but the color filter and the screen fade
+pub const TOAST_Z: i32 = i32::MAX - 2;
+
+/// Seconds a toast takes to slide in
+const SLIDE_TIME: f32 = 0.25;
+/// How far right of its place a toast starts sliding in from
+const SLIDE_DISTANCE: f32 = 48.0;
+/// Seconds a toast takes to fade out at the end of its time
+const FADE_TIME: f32 = 0.5;
+
+const BACKGROUND: Color = Color::rgba(0.0, 0.0, 0.0, 0.8);
+
+/// How bad the news is, picking the toast's accent color
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ToastLevel {
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+impl ToastLevel {
+    pub fn accent(self) -> Color {
+        match self {
+            ToastLevel::Info => Color::rgb(0.35, 0.6, 1.0),
+            ToastLevel::Warning => Color::rgb(1.0, 0.75, 0.2),
+            ToastLevel::Error => Color::rgb(0.95, 0.25, 0.25),
+        }
+    }
+}
+
+/// Shows `text` for `duration` seconds
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct ShowToast {
+    pub text: String,
+    pub duration: f32,
+    pub level: ToastLevel,
+}
+
+impl ShowToast {
+    pub fn info(text: impl Into<String>) -> Self {
+        ShowToast {
+            text: text.into(),
+            duration: TOAST_TIME,
+            level: ToastLevel::Info,
+        }
+    }
+
+    pub fn warning(text: impl Into<String>) -> Self {
+        ShowToast {
+            level: ToastLevel::Warning,
+            ..ShowToast::info(text)
+        }
+    }
+
+    pub fn error(text: impl Into<String>) -> Self {
+        ShowToast {
+            level: ToastLevel::Error,
+            ..ShowToast::info(text)
+        }
+    }
+
+    pub fn with_duration(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+}
+
+/// The node toasts are stacked in
+#[derive(Component, Debug)]
+pub struct ToastRoot;
+
+/// A toast on screen
+/// * count: how many times it came in, shown after its text past 1
+/// * remaining: seconds it has left
+/// * age: seconds since it came in first
+/// * since_repeat: seconds since it came in last
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Toast {
+    pub text: String,
+    pub level: ToastLevel,
+    pub count: usize,
+    pub remaining: f32,
+    pub age: f32,
+    pub since_repeat: f32,
+}
+
+impl Toast {
+    /// The text shown, with the count when it came in more than once
+    pub fn label(&self) -> String {
+        if self.count > 1 {
+            format!("{} ×{}", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// Marks a toast's text
+#[derive(Component, Debug)]
+pub struct ToastText;
+
+/// ShowToast
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ShowToast>()
+            .add_systems(Startup, spawn_toast_root)
+            .add_systems(Update, (show_toasts, expire_toasts, animate_toasts).chain());
+    }
+}
+
+fn spawn_toast_root(mut commands: Commands) {
+    commands.spawn((
+        ToastRoot,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(16.0),
+                bottom: Val::Px(16.0),
+                max_width: Val::Percent(60.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::FlexEnd,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            z_index: ZIndex::Global(TOAST_Z),
+            ..default()
+        },
+    ));
+}
+
+///
+/// show_toasts: Bevy system
+///
+/// Adds a toast for each ShowToast, or counts up on the same one if it came in within
+/// REPEAT_WINDOW, making room by removing the oldest
+pub fn show_toasts(
+    mut commands: Commands,
+    mut events: EventReader<ShowToast>,
+    root_query: Query<(Entity, Option<&Children>), With<ToastRoot>>,
+    mut toast_query: Query<(&mut Toast, &Children)>,
+    mut text_query: Query<&mut Text, With<ToastText>>,
+) {
+    let Ok((root, children)) = root_query.get_single() else {
+        events.clear();
+        return;
+    };
+    // the same toast coming in several times this frame counts up once
+    let mut arrived: Vec<(ShowToast, usize)> = Vec::new();
+    for toast in events.read() {
+        info!("Toast: {}", toast.text);
+        let repeat = arrived
+            .iter_mut()
+            .find(|(seen, _)| seen.text == toast.text && seen.level == toast.level);
+        match repeat {
+            Some((seen, count)) => {
+                seen.duration = toast.duration;
+                *count += 1;
+            }
+            None => arrived.push((toast.clone(), 1)),
+        }
+    }
+
+    let mut shown: Vec<Entity> = children
+        .map(|children| children.to_vec())
+        .unwrap_or_default();
+    for (event, count) in arrived {
+        let repeated = shown.iter().copied().find(|entity| {
+            toast_query.get(*entity).is_ok_and(|(toast, _)| {
+                toast.text == event.text
+                    && toast.level == event.level
+                    && toast.since_repeat <= REPEAT_WINDOW
+            })
+        });
+        if let Some((mut toast, children)) =
+            repeated.and_then(|entity| toast_query.get_mut(entity).ok())
+        {
+            toast.count += count;
+            toast.remaining = event.duration;
+            toast.since_repeat = 0.0;
+            let label = toast.label();
+            for child in children.iter() {
+                if let Ok(mut text) = text_query.get_mut(*child) {
+                    text.sections[0].value = label.clone();
+                }
+            }
+            continue;
+        }
+
+        let toast = Toast {
+            text: event.text,
+            level: event.level,
+            count,
+            remaining: event.duration,
+            age: 0.0,
+            since_repeat: 0.0,
+        };
+        let label = toast.label();
+        let accent = toast.level.accent();
+        let entity = commands
+            .spawn((
+                toast,
+                NodeBundle {
+                    style: Style {
+                        padding: UiRect::axes(Val::Px(12.0), Val::Px(8.0)),
+                        border: UiRect::left(Val::Px(4.0)),
+                        left: Val::Px(SLIDE_DISTANCE),
+                        ..default()
+                    },
+                    background_color: BACKGROUND.into(),
+                    border_color: accent.into(),
+                    ..default()
+                },
+            ))
+            .with_children(|toast_node| {
+                toast_node.spawn((
+                    ToastText,
+                    TextBundle::from_section(
+                        label,
+                        TextStyle {
+                            font_size: 16.0,
+                            color: Color::WHITE,
+                            ..default()
+                        },
+                    ),
+                ));
+            })
+            .id();
+        commands.entity(root).add_child(entity);
+        shown.push(entity);
+    }
+    while shown.len() > MAX_TOASTS {
+        commands.entity(shown.remove(0)).despawn_recursive();
+    }
+}
+
+///
+/// expire_toasts: Bevy system
+///
+/// Ages toasts and removes those whose time is up
+pub fn expire_toasts(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut toast_query: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in toast_query.iter_mut() {
+        toast.remaining -= time.delta_seconds();
+        toast.age += time.delta_seconds();
+        toast.since_repeat += time.delta_seconds();
+        if toast.remaining <= 0.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+///
+/// animate_toasts: Bevy system
+///
+/// Slides new toasts in from the right and fades out those about to go
+pub fn animate_toasts(
+    mut toast_query: Query<(
+        &Toast,
+        &Children,
+        &mut Style,
+        &mut BackgroundColor,
+        &mut BorderColor,
+    )>,
+    mut text_query: Query<&mut Text, With<ToastText>>,
+) {
+    for (toast, children, mut style, mut background, mut border) in toast_query.iter_mut() {
+        let left = if toast.age < SLIDE_TIME {
+            // easing out, fast at first
+            SLIDE_DISTANCE * (1.0 - toast.age / SLIDE_TIME).powi(2)
+        } else {
+            0.0
+        };
+        if style.left != Val::Px(left) {
+            style.left = Val::Px(left);
+        }
+
+        let alpha = (toast.remaining / FADE_TIME).clamp(0.0, 1.0);
+        if border.0.a() == alpha {
+            continue;
+        }
+        background.0 = BACKGROUND.with_a(BACKGROUND.a() * alpha);
+        border.0 = toast.level.accent().with_a(alpha);
+        for child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(*child) {
+                for section in text.sections.iter_mut() {
+                    section.style.color.set_a(alpha);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,672 @@
+//! The dialogue box: StartDialogue opens a panel at the bottom of the screen that types out its
+//! lines one at a time, with the speaker's name, portrait and voice blips. Interact shows the
+//! rest of a line still typing, and the next line once it's all there. After the last line the
+//! box closes and DialogueFinished lets whatever started it carry on.
+//!
+//! StartDialogueTree talks through a DialogueTree instead, one node's lines after another. Once
+//! the last line of a node offering choices is typed out, they come up under it as MenuButtons,
+//! moved through and picked like a menu's, and the tree goes on where the choice leads. Nodes
+//! and choices set and clear GameProgress's StoryFlags as they come up.
+//!
+//! The speaker and text of a line are locale keys. Gameplay doesn't get the actions while the
+//! box is up, see GameplayInput.
+
+use super::dialogue_tree::{DialogueNode, DialogueTree};
+use super::{navigate_menus, spawn_menu_button, ButtonActivated, MenuButton, MenuFocus};
+use crate::input::{Action, ActionState, GameplayInput};
+use crate::locale::Locale;
+use crate::save::{GameProgress, StoryFlags};
+use crate::sound::PlaySFX;
+use crate::state::AppState;
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Characters typed out each second
+pub const DIALOGUE_SPEED: f32 = 40.0;
+
+/// A voice blip plays every this many characters typed, spaces aside
+const BLIP_EVERY: usize = 3;
+
+const PORTRAIT_SIZE: f32 = 96.0;
+
+/// A line of dialogue
+/// * speaker: locale key of the speaker's name
+/// * text: locale key of what they say
+/// * portrait: path of the speaker's portrait image, if any
+/// * voice: the SFX blipping as the text types, by its name in SoundResource, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogueLine {
+    pub speaker: String,
+    pub text: String,
+    pub portrait: Option<String>,
+    pub voice: Option<String>,
+}
+
+impl DialogueLine {
+    pub fn new(speaker: impl Into<String>, text: impl Into<String>) -> Self {
+        DialogueLine {
+            speaker: speaker.into(),
+            text: text.into(),
+            portrait: None,
+            voice: None,
+        }
+    }
+
+    pub fn with_portrait(mut self, path: impl Into<String>) -> Self {
+        self.portrait = Some(path.into());
+        self
+    }
+
+    pub fn with_voice(mut self, sfx: impl Into<String>) -> Self {
+        self.voice = Some(sfx.into());
+        self
+    }
+}
+
+/// Opens the dialogue box with `lines`, or queues them after the lines of the one already open
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct StartDialogue {
+    pub lines: Vec<DialogueLine>,
+}
+
+/// Opens the dialogue box talking through `tree`, its lines said by `speaker` unless the tree or
+/// node names its own. Ignored while another tree is being talked through.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct StartDialogueTree {
+    pub tree: DialogueTree,
+    pub speaker: String,
+}
+
+/// Sent when the dialogue box closes after its last line
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct DialogueFinished;
+
+/// Where a conversation through a DialogueTree is at
+/// * node: the id of the node whose lines are being said
+/// * speaker: who says them when neither the tree nor the node say
+#[derive(Debug, Clone, PartialEq)]
+struct TreeProgress {
+    tree: DialogueTree,
+    node: String,
+    speaker: String,
+}
+
+///
+/// Dialogue
+///
+/// The open dialogue, if any
+/// * line: the line shown, with its text in the active language
+/// * queued: the lines after it
+/// * typed: how many characters of the line are typed out so far
+/// * tree: the DialogueTree being talked through, if any
+/// * choices: the choices of its node up on screen, by their index in the node
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct Dialogue {
+    line: Option<(DialogueLine, String)>,
+    queued: VecDeque<DialogueLine>,
+    typed: f32,
+    tree: Option<TreeProgress>,
+    choices: Vec<usize>,
+}
+
+impl Dialogue {
+    /// Whether the dialogue box is up
+    pub fn is_open(&self) -> bool {
+        self.line.is_some()
+    }
+
+    /// The line shown
+    pub fn line(&self) -> Option<&DialogueLine> {
+        self.line.as_ref().map(|(line, _)| line)
+    }
+
+    /// The part of the line's text typed out so far
+    pub fn shown_text(&self) -> String {
+        self.line
+            .as_ref()
+            .map(|(_, text)| text.chars().take(self.typed as usize).collect())
+            .unwrap_or_default()
+    }
+
+    /// Whether the whole line is typed out
+    pub fn is_typed(&self) -> bool {
+        self.line
+            .as_ref()
+            .is_none_or(|(_, text)| self.typed as usize >= text.chars().count())
+    }
+
+    /// The id of the DialogueTree node being said, if a tree is being talked through
+    pub fn node(&self) -> Option<&str> {
+        self.tree.as_ref().map(|tree| tree.node.as_str())
+    }
+
+    /// The locale keys of the choices up on screen
+    pub fn choices(&self) -> Vec<&str> {
+        let Some(node) = self.current_node() else {
+            return Vec::new();
+        };
+        self.choices
+            .iter()
+            .filter_map(|index| node.choices.get(*index))
+            .map(|choice| choice.text.as_str())
+            .collect()
+    }
+
+    fn current_node(&self) -> Option<&DialogueNode> {
+        let tree = self.tree.as_ref()?;
+        tree.tree.nodes.get(&tree.node)
+    }
+
+    /// Whether the node's lines are all said and it offers choices `flags` allow, to be picked
+    /// before going on
+    fn awaits_choice(&self, flags: &StoryFlags) -> bool {
+        self.queued.is_empty()
+            && self
+                .current_node()
+                .is_some_and(|node| !node.offered(flags).is_empty())
+    }
+
+    /// Goes to the tree's node `id`, setting and clearing its flags and queueing its lines, and
+    /// on through the `next` of nodes without lines. Returns false when there's nothing to say,
+    /// the tree having ended.
+    fn go_to(&mut self, id: Option<String>, flags: &mut StoryFlags) -> bool {
+        let mut next = id;
+        let Some(progress) = self.tree.as_mut() else {
+            return false;
+        };
+        // no further than every node once, in case lineless nodes lead round in a circle
+        for _ in 0..=progress.tree.nodes.len() {
+            let Some(id) = next.take() else {
+                break;
+            };
+            let Some(node) = progress.tree.nodes.get(&id) else {
+                warn!("The dialogue leads to node {}, which doesn't exist", id);
+                break;
+            };
+            for flag in node.set.iter() {
+                flags.set(flag.clone());
+            }
+            for flag in node.clear.iter() {
+                flags.clear(flag);
+            }
+            let speaker = node
+                .speaker
+                .as_ref()
+                .or(progress.tree.speaker.as_ref())
+                .unwrap_or(&progress.speaker);
+            self.queued
+                .extend(node.lines.iter().map(|text| DialogueLine {
+                    speaker: speaker.clone(),
+                    text: text.clone(),
+                    portrait: progress.tree.portrait.clone(),
+                    voice: progress.tree.voice.clone(),
+                }));
+            progress.node = id;
+            if !node.lines.is_empty() {
+                return true;
+            }
+            if !node.offered(flags).is_empty() {
+                warn!(
+                    "Node {} offers choices, but has no lines to offer them on",
+                    progress.node
+                );
+                break;
+            }
+            next = node.next.clone();
+        }
+        self.tree = None;
+        false
+    }
+
+    /// Goes on from the node whose lines are all said, without a choice, to its `next`
+    fn follow_tree(&mut self, flags: &mut StoryFlags) -> bool {
+        let next = self.current_node().and_then(|node| node.next.clone());
+        next.is_some() && self.go_to(next, flags)
+    }
+
+    /// Shows the next queued line, returning false when there's none left
+    fn next_line(&mut self, locale: Option<&Locale>) -> bool {
+        self.typed = 0.0;
+        self.line = self.queued.pop_front().map(|line| {
+            let text = locale.map_or_else(|| line.text.clone(), |locale| locale.text(&line.text));
+            (line, text)
+        });
+        self.line.is_some()
+    }
+}
+
+/// Marks the dialogue box's root node
+#[derive(Component, Debug)]
+pub struct DialogueBox;
+
+/// Marks the speaker's portrait in the dialogue box
+#[derive(Component, Debug)]
+pub struct DialoguePortrait;
+
+/// Marks the speaker's name in the dialogue box
+#[derive(Component, Debug)]
+pub struct DialogueSpeaker;
+
+/// Marks the text the line is typed into
+#[derive(Component, Debug)]
+pub struct DialogueText;
+
+/// Where the choices go, under the text
+#[derive(Component, Debug)]
+pub struct DialogueChoices;
+
+/// A choice up in the dialogue box, by its index in the node's choices
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DialogueChoiceButton(pub usize);
+
+/// The dialogue box, closed without DialogueFinished on going back to the main menu
+pub struct DialoguePlugin;
+
+impl Plugin for DialoguePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dialogue>()
+            .init_resource::<GameplayInput>()
+            .init_resource::<GameProgress>()
+            .init_resource::<MenuFocus>()
+            .add_event::<StartDialogue>()
+            .add_event::<StartDialogueTree>()
+            .add_event::<DialogueFinished>()
+            .add_event::<ButtonActivated>()
+            .add_event::<PlaySFX>()
+            .add_systems(OnEnter(AppState::MainMenu), close_dialogue)
+            .add_systems(
+                Update,
+                (
+                    // before new lines come in, so the press that started the dialogue or
+                    // picked a choice doesn't also skip its first line
+                    advance_dialogue.run_if(in_state(AppState::InGame)),
+                    choose_dialogue
+                        .after(navigate_menus)
+                        .run_if(in_state(AppState::InGame)),
+                    start_dialogue.run_if(
+                        on_event::<StartDialogue>().or_else(on_event::<StartDialogueTree>()),
+                    ),
+                    type_dialogue.run_if(in_state(AppState::InGame)),
+                    offer_choices.run_if(in_state(AppState::InGame)),
+                    show_dialogue,
+                    enable_choices,
+                )
+                    .chain(),
+            );
+    }
+}
+
+///
+/// start_dialogue: Bevy system
+///
+/// Queues the lines of StartDialogue, and those of the first node of a StartDialogueTree, opening
+/// the box if it's closed
+pub fn start_dialogue(
+    mut commands: Commands,
+    mut starts: EventReader<StartDialogue>,
+    mut tree_starts: EventReader<StartDialogueTree>,
+    mut dialogue: ResMut<Dialogue>,
+    mut input: ResMut<GameplayInput>,
+    mut progress: ResMut<GameProgress>,
+    locale: Option<Res<Locale>>,
+) {
+    for start in starts.read() {
+        dialogue.queued.extend(start.lines.iter().cloned());
+    }
+    for start in tree_starts.read() {
+        if dialogue.tree.is_some() {
+            continue;
+        }
+        let Some(node) = start.tree.start_node(&progress.flags).map(str::to_string) else {
+            warn!("The story flags allow none of the dialogue's start nodes");
+            continue;
+        };
+        dialogue.tree = Some(TreeProgress {
+            tree: start.tree.clone(),
+            node: node.clone(),
+            speaker: start.speaker.clone(),
+        });
+        dialogue.go_to(Some(node), &mut progress.flags);
+    }
+    if dialogue.is_open() || !dialogue.next_line(locale.as_deref()) {
+        return;
+    }
+    input.0 = false;
+    spawn_dialogue_box(&mut commands);
+}
+
+fn spawn_dialogue_box(commands: &mut Commands) {
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            left: Val::Percent(10.0),
+            right: Val::Percent(10.0),
+            bottom: Val::Px(24.0),
+            min_height: Val::Px(PORTRAIT_SIZE + 24.0),
+            padding: UiRect::all(Val::Px(12.0)),
+            column_gap: Val::Px(16.0),
+            ..default()
+        },
+        background_color: Color::rgba(0.05, 0.05, 0.1, 0.9).into(),
+        // under the pause menu
+        z_index: ZIndex::Global(40),
+        ..default()
+    };
+    let text = |size: f32, color: Color| {
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: size,
+                color,
+                ..default()
+            },
+        )
+    };
+    commands.spawn((DialogueBox, root)).with_children(|panel| {
+        panel.spawn((
+            DialoguePortrait,
+            ImageBundle {
+                style: Style {
+                    width: Val::Px(PORTRAIT_SIZE),
+                    height: Val::Px(PORTRAIT_SIZE),
+                    display: Display::None,
+                    ..default()
+                },
+                ..default()
+            },
+        ));
+        panel
+            .spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    flex_grow: 1.0,
+                    row_gap: Val::Px(6.0),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|lines| {
+                lines.spawn((DialogueSpeaker, text(18.0, Color::rgb(1.0, 0.85, 0.5))));
+                lines.spawn((DialogueText, text(20.0, Color::WHITE)));
+                lines.spawn((
+                    DialogueChoices,
+                    NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: Val::Px(6.0),
+                            margin: UiRect::top(Val::Px(6.0)),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                ));
+            });
+    });
+}
+
+///
+/// advance_dialogue: Bevy system
+///
+/// On Interact, types out the rest of the line, or moves to the next one once it's all there,
+/// going on to the tree's next node after the last of a node's lines. Choices are picked
+/// instead, see choose_dialogue. Past the last line, closes the box and sends DialogueFinished.
+#[allow(clippy::too_many_arguments)]
+pub fn advance_dialogue(
+    mut commands: Commands,
+    actions: Option<Res<ActionState>>,
+    mut dialogue: ResMut<Dialogue>,
+    mut input: ResMut<GameplayInput>,
+    mut progress: ResMut<GameProgress>,
+    locale: Option<Res<Locale>>,
+    box_query: Query<Entity, With<DialogueBox>>,
+    mut finished: EventWriter<DialogueFinished>,
+) {
+    let pressed = actions.is_some_and(|actions| actions.just_pressed(Action::Interact));
+    if !pressed || !dialogue.is_open() {
+        return;
+    }
+    if !dialogue.is_typed() {
+        dialogue.typed = f32::MAX;
+        return;
+    }
+    if dialogue.awaits_choice(&progress.flags) {
+        return;
+    }
+    if dialogue.next_line(locale.as_deref())
+        || (dialogue.follow_tree(&mut progress.flags) && dialogue.next_line(locale.as_deref()))
+    {
+        return;
+    }
+    end_dialogue(&mut commands, &mut dialogue, &mut input, &box_query);
+    finished.send(DialogueFinished);
+}
+
+/// Despawns the dialogue box and gives gameplay the actions back
+fn end_dialogue(
+    commands: &mut Commands,
+    dialogue: &mut Dialogue,
+    input: &mut GameplayInput,
+    box_query: &Query<Entity, With<DialogueBox>>,
+) {
+    for entity in box_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    dialogue.line = None;
+    dialogue.queued.clear();
+    dialogue.tree = None;
+    dialogue.choices.clear();
+    input.0 = true;
+}
+
+///
+/// offer_choices: Bevy system
+///
+/// Puts up the choices of the node once its last line is typed out, those StoryFlags allow
+pub fn offer_choices(
+    mut commands: Commands,
+    mut dialogue: ResMut<Dialogue>,
+    progress: Res<GameProgress>,
+    choices_query: Query<Entity, With<DialogueChoices>>,
+) {
+    if !dialogue.choices.is_empty() || !dialogue.is_typed() || !dialogue.is_open() {
+        return;
+    }
+    if !dialogue.awaits_choice(&progress.flags) {
+        return;
+    }
+    let Some(node) = dialogue.current_node() else {
+        return;
+    };
+    let offered = node.offered(&progress.flags);
+    let labels: Vec<(usize, String)> = offered
+        .iter()
+        .map(|index| (*index, node.choices[*index].text.clone()))
+        .collect();
+    for entity in choices_query.iter() {
+        commands.entity(entity).with_children(|choices| {
+            for (order, (index, label)) in labels.iter().enumerate() {
+                let button = MenuButton {
+                    index: order,
+                    enabled: true,
+                };
+                spawn_menu_button(choices, button, label, DialogueChoiceButton(*index));
+            }
+        });
+    }
+    dialogue.choices = offered;
+}
+
+///
+/// choose_dialogue: Bevy system
+///
+/// Takes the choice activated, setting and clearing its flags, and goes on to the node it leads
+/// to, or closes the box and sends DialogueFinished when it ends the conversation
+#[allow(clippy::too_many_arguments)]
+pub fn choose_dialogue(
+    mut commands: Commands,
+    mut activations: EventReader<ButtonActivated>,
+    mut dialogue: ResMut<Dialogue>,
+    mut input: ResMut<GameplayInput>,
+    mut progress: ResMut<GameProgress>,
+    mut focus: ResMut<MenuFocus>,
+    locale: Option<Res<Locale>>,
+    button_query: Query<&DialogueChoiceButton>,
+    choices_query: Query<Entity, With<DialogueChoices>>,
+    box_query: Query<Entity, With<DialogueBox>>,
+    mut finished: EventWriter<DialogueFinished>,
+) {
+    let chosen = activations
+        .read()
+        .filter_map(|ButtonActivated(entity)| button_query.get(*entity).ok())
+        .map(|button| button.0)
+        .find(|index| dialogue.choices.contains(index));
+    let Some(choice) = chosen.and_then(|index| dialogue.current_node()?.choices.get(index)) else {
+        return;
+    };
+    let choice = choice.clone();
+    for entity in choices_query.iter() {
+        commands.entity(entity).despawn_descendants();
+    }
+    dialogue.choices.clear();
+    focus.0 = None;
+
+    for flag in choice.set.iter() {
+        progress.flags.set(flag.clone());
+    }
+    for flag in choice.clear.iter() {
+        progress.flags.clear(flag);
+    }
+    if choice.next.is_some()
+        && dialogue.go_to(choice.next, &mut progress.flags)
+        && dialogue.next_line(locale.as_deref())
+    {
+        return;
+    }
+    end_dialogue(&mut commands, &mut dialogue, &mut input, &box_query);
+    finished.send(DialogueFinished);
+}
+
+/// Keeps the choices from being moved to or picked while the game isn't running, e.g. under the
+/// pause menu, focusing the first again when it's back
+fn enable_choices(
+    state: Option<Res<State<AppState>>>,
+    mut focus: ResMut<MenuFocus>,
+    mut button_query: Query<(Entity, &mut MenuButton), With<DialogueChoiceButton>>,
+) {
+    let enabled = state.is_none_or(|state| *state.get() == AppState::InGame);
+    for (entity, mut button) in button_query.iter_mut() {
+        if button.enabled == enabled {
+            continue;
+        }
+        button.enabled = enabled;
+        if enabled && button.index == 0 && focus.0.is_none() {
+            focus.0 = Some(entity);
+        }
+    }
+}
+
+/// Closes the dialogue box, dropping its lines
+fn close_dialogue(
+    mut commands: Commands,
+    mut dialogue: ResMut<Dialogue>,
+    mut input: ResMut<GameplayInput>,
+    box_query: Query<Entity, With<DialogueBox>>,
+) {
+    for entity in box_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if dialogue.is_open() {
+        *dialogue = Dialogue::default();
+        input.0 = true;
+    }
+}
+
+///
+/// type_dialogue: Bevy system
+///
+/// Types out the line at DIALOGUE_SPEED, blipping its voice as it goes
+pub fn type_dialogue(
+    time: Res<Time>,
+    mut dialogue: ResMut<Dialogue>,
+    mut sfx: EventWriter<PlaySFX>,
+) {
+    if dialogue.is_typed() {
+        return;
+    }
+    let Some((line, text)) = dialogue.line.clone() else {
+        return;
+    };
+    let before = dialogue.typed as usize;
+    dialogue.typed += time.delta_seconds() * DIALOGUE_SPEED;
+    let after = dialogue.typed as usize;
+
+    let Some(voice) = line.voice else {
+        return;
+    };
+    // counting only the characters that make a sound
+    let voiced = |typed: usize| {
+        text.chars()
+            .take(typed)
+            .filter(|c| !c.is_whitespace())
+            .count()
+    };
+    let (before, after) = (voiced(before), voiced(after));
+    if after / BLIP_EVERY > before / BLIP_EVERY || (before == 0 && after > 0) {
+        sfx.send(PlaySFX::new(voice));
+    }
+}
+
+///
+/// show_dialogue: Bevy system
+///
+/// Shows the line's speaker, portrait and the text typed out so far
+pub fn show_dialogue(
+    dialogue: Res<Dialogue>,
+    asset_server: Option<Res<AssetServer>>,
+    locale: Option<Res<Locale>>,
+    mut portrait_query: Query<(&mut UiImage, &mut Style), With<DialoguePortrait>>,
+    mut speaker_query: Query<&mut Text, (With<DialogueSpeaker>, Without<DialogueText>)>,
+    mut text_query: Query<&mut Text, (With<DialogueText>, Without<DialogueSpeaker>)>,
+) {
+    if !dialogue.is_changed() {
+        return;
+    }
+    let Some(line) = dialogue.line() else {
+        return;
+    };
+    let set = |text: &mut Text, value: String| {
+        if let Some(section) = text.sections.first_mut() {
+            if section.value != value {
+                section.value = value;
+            }
+        }
+    };
+    let speaker = locale
+        .as_ref()
+        .map_or_else(|| line.speaker.clone(), |locale| locale.text(&line.speaker));
+    for mut text in speaker_query.iter_mut() {
+        set(&mut text, speaker.clone());
+    }
+    for mut text in text_query.iter_mut() {
+        set(&mut text, dialogue.shown_text());
+    }
+
+    for (mut image, mut style) in portrait_query.iter_mut() {
+        let portrait = line
+            .portrait
+            .as_ref()
+            .zip(asset_server.as_ref())
+            .map(|(path, server)| server.load(path.clone()));
+        let display = if portrait.is_some() {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+        if let Some(portrait) = portrait.filter(|portrait| *portrait != image.texture) {
+            image.texture = portrait;
+        }
+    }
+}
@@ -0,0 +1,291 @@
+//! Branching dialogue: `.dialogue.ron` assets of DialogueTrees, where each tree's nodes say some
+//! lines and then lead on to another node, end the conversation or offer the player 2 to 4
+//! choices that do either. A map names the file of its NPCs' trees with its `dialogue` property,
+//! relative to the assets directory, and it's loaded with the map; an NPC whose `dialogue` names
+//! one of its trees talks through it instead of saying locale lines.
+//!
+//! Nodes and choices can depend on StoryFlags, only coming up while their `requires` flags are
+//! set and their `unless` flags aren't, and set or clear flags themselves. A tree starts at the
+//! first of its `start` nodes the flags allow, so talking to an NPC again can skip what's been
+//! heard already:
+//!
+//! ```ron
+//! {
+//!     "elder": (
+//!         speaker: Some("elder.speaker"),
+//!         start: ["again", "intro"],
+//!         nodes: {
+//!             "intro": (
+//!                 lines: ["elder.intro.1", "elder.intro.2"],
+//!                 set: ["heard_elder"],
+//!                 choices: [
+//!                     (text: "elder.ask_cave", next: Some("cave")),
+//!                     (text: "elder.bye"),
+//!                 ],
+//!             ),
+//!             "again": (requires: ["heard_elder"], lines: ["elder.again"], next: Some("cave")),
+//!             "cave": (lines: ["elder.cave"]),
+//!         },
+//!     ),
+//! }
+//! ```
+//!
+//! Lines and choice texts are locale keys. See the dialogue box for how trees are played.
+
+use crate::helpers::tiled::{MapLoaded, MapUnloaded};
+use crate::save::StoryFlags;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// The map property naming the map's `.dialogue.ron` file
+pub const DIALOGUE_PROPERTY: &str = "dialogue";
+/// The fewest and most choices a node can end with
+pub const CHOICES: std::ops::RangeInclusive<usize> = 2..=4;
+
+/// A choice the player can make at the end of a node
+/// * text: locale key of what it says
+/// * next: the node it leads to, None to end the conversation
+/// * requires, unless: the StoryFlags that have to be set, and not be set, for it to be offered
+/// * set, clear: the StoryFlags it sets and clears when chosen
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DialogueChoice {
+    pub text: String,
+    pub next: Option<String>,
+    pub requires: Vec<String>,
+    pub unless: Vec<String>,
+    pub set: Vec<String>,
+    pub clear: Vec<String>,
+}
+
+/// A step of a conversation
+/// * speaker: locale key of who says its lines, the tree's speaker when None
+/// * lines: locale keys of what they say, in order
+/// * choices: offered once the last line is typed out, the ones StoryFlags allow
+/// * next: the node after this one when it offers no choices, None to end the conversation
+/// * requires, unless: the StoryFlags that have to be set, and not be set, for it to start the
+///   tree
+/// * set, clear: the StoryFlags it sets and clears as it starts
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DialogueNode {
+    pub speaker: Option<String>,
+    pub lines: Vec<String>,
+    pub choices: Vec<DialogueChoice>,
+    pub next: Option<String>,
+    pub requires: Vec<String>,
+    pub unless: Vec<String>,
+    pub set: Vec<String>,
+    pub clear: Vec<String>,
+}
+
+impl DialogueNode {
+    /// The indices of the choices `flags` allow
+    pub fn offered(&self, flags: &StoryFlags) -> Vec<usize> {
+        (0..self.choices.len())
+            .filter(|index| {
+                let choice = &self.choices[*index];
+                flags.allow(&choice.requires, &choice.unless)
+            })
+            .collect()
+    }
+}
+
+/// The conversation with an NPC
+/// * speaker: locale key of who's talking, the NPC's `speaker` when None
+/// * portrait, voice: as on a DialogueLine
+/// * start: the nodes it can start at, the first StoryFlags allow being the one it does
+/// * nodes: by their ids
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DialogueTree {
+    pub speaker: Option<String>,
+    pub portrait: Option<String>,
+    pub voice: Option<String>,
+    pub start: Vec<String>,
+    pub nodes: BTreeMap<String, DialogueNode>,
+}
+
+impl DialogueTree {
+    /// The id of the node the tree starts at with `flags`, if they allow any
+    pub fn start_node(&self, flags: &StoryFlags) -> Option<&str> {
+        self.start
+            .iter()
+            .find(|id| {
+                self.nodes
+                    .get(id.as_str())
+                    .is_some_and(|node| flags.allow(&node.requires, &node.unless))
+            })
+            .map(String::as_str)
+    }
+
+    /// What's wrong with the tree: nodes that are missing or end with too few or too many
+    /// choices
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.start.is_empty() {
+            problems.push("it has no start nodes".to_string());
+        }
+        let missing = |id: &String| !self.nodes.contains_key(id);
+        for id in self.start.iter().filter(|id| missing(id)) {
+            problems.push(format!("start node {} doesn't exist", id));
+        }
+        for (id, node) in self.nodes.iter() {
+            if !node.choices.is_empty() && !CHOICES.contains(&node.choices.len()) {
+                problems.push(format!(
+                    "node {} has {} choices, nodes end with {} to {}",
+                    id,
+                    node.choices.len(),
+                    CHOICES.start(),
+                    CHOICES.end()
+                ));
+            }
+            let leads_to = node.next.iter().chain(
+                node.choices
+                    .iter()
+                    .filter_map(|choice| choice.next.as_ref()),
+            );
+            for next in leads_to.filter(|next| missing(next)) {
+                problems.push(format!(
+                    "node {} leads to {}, which doesn't exist",
+                    id, next
+                ));
+            }
+        }
+        problems
+    }
+}
+
+/// The trees of a `.dialogue.ron` file, by their ids
+#[derive(Asset, TypePath, Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(transparent)]
+pub struct DialogueTrees(pub BTreeMap<String, DialogueTree>);
+
+impl DialogueTrees {
+    /// Parses a `.dialogue.ron` file
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text)
+    }
+
+    /// What's wrong with each tree, as "tree: problem"
+    pub fn problems(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .flat_map(|(id, tree)| {
+                tree.problems()
+                    .into_iter()
+                    .map(move |problem| format!("{}: {}", id, problem))
+            })
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct DialogueTreesLoader;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum DialogueTreesLoaderError {
+    #[error("Could not read the dialogue: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse the dialogue: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for DialogueTreesLoader {
+    type Asset = DialogueTrees;
+    type Settings = ();
+    type Error = DialogueTreesLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let trees: DialogueTrees = ron::de::from_bytes(&bytes)?;
+            // still played, as far as they go
+            for problem in trees.problems() {
+                error!("{}: {}", load_context.path().display(), problem);
+            }
+            Ok(trees)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dialogue.ron"]
+    }
+}
+
+///
+/// MapDialogue
+///
+/// The dialogue trees of the current map, from its `dialogue` property
+#[derive(Resource, Debug, Default, Clone)]
+pub struct MapDialogue(pub Option<Handle<DialogueTrees>>);
+
+///
+/// DialogueTreeLookup: Bevy system parameter
+///
+/// Finds the current map's dialogue trees
+#[derive(SystemParam)]
+pub struct DialogueTreeLookup<'w> {
+    map: Option<Res<'w, MapDialogue>>,
+    trees: Option<Res<'w, Assets<DialogueTrees>>>,
+}
+
+impl DialogueTreeLookup<'_> {
+    /// The current map's tree `id`, once its file has loaded
+    pub fn get(&self, id: &str) -> Option<&DialogueTree> {
+        let handle = self.map.as_ref()?.0.as_ref()?;
+        self.trees.as_ref()?.get(handle)?.0.get(id)
+    }
+}
+
+/// The `.dialogue.ron` asset and loading the current map's
+pub struct DialogueTreePlugin;
+
+impl Plugin for DialogueTreePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<DialogueTrees>()
+            .init_asset_loader::<DialogueTreesLoader>()
+            .init_resource::<MapDialogue>()
+            .add_event::<MapLoaded>()
+            .add_event::<MapUnloaded>()
+            .add_systems(Update, load_map_dialogue);
+    }
+}
+
+///
+/// load_map_dialogue: Bevy system
+///
+/// Loads the file named by the `dialogue` property of a map as it's loaded, and lets it go with
+/// the map
+pub fn load_map_dialogue(
+    mut loaded: EventReader<MapLoaded>,
+    mut unloaded: EventReader<MapUnloaded>,
+    asset_server: Option<Res<AssetServer>>,
+    mut dialogue: ResMut<MapDialogue>,
+) {
+    if unloaded.read().count() > 0 {
+        dialogue.0 = None;
+    }
+    let Some(event) = loaded.read().last() else {
+        return;
+    };
+    dialogue.0 = event
+        .properties
+        .get_string(DIALOGUE_PROPERTY)
+        .zip(asset_server)
+        .map(|(path, server)| server.load(path.to_string()));
+}
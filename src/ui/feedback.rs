@@ -0,0 +1,146 @@
+//! Hover and press feedback for any Button with ButtonFeedback: it's tinted and grows a little
+//! while hovered, is nudged down while held, and plays a sound as it's hovered and as it's
+//! clicked. Disabled buttons are dimmed and make no sound.
+//!
+//! MenuButtons are hovered by MenuFocus rather than the mouse, which moves the focus itself, so
+//! moving to one with the keyboard or a gamepad looks and sounds the same as pointing at it. The
+//! SFX rate limiter stops the hover sound machine-gunning as the mouse flicks across a column of
+//! buttons.
+
+use super::{
+    ButtonActivated, MenuButton, MenuFocus, UiSounds, BUTTON_COLOR, DISABLED_COLOR, FOCUSED_COLOR,
+};
+use crate::sound::PlaySFX;
+use bevy::prelude::*;
+
+/// How much bigger a hovered button is
+pub const HOVER_SCALE: f32 = 1.05;
+/// Pixels a held button moves down by
+pub const PRESS_OFFSET: f32 = 2.0;
+
+/// How a Button looks and sounds as it's hovered and clicked
+/// * color, hover_color, disabled_color: its background, hovered and disabled
+/// * hover_sfx, click_sfx: the SFX played, UiSounds' when None
+/// * disabled: dims it and keeps it quiet, as does its MenuButton being disabled
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct ButtonFeedback {
+    pub color: Color,
+    pub hover_color: Color,
+    pub disabled_color: Color,
+    pub hover_sfx: Option<String>,
+    pub click_sfx: Option<String>,
+    pub disabled: bool,
+    hovered: bool,
+}
+
+impl Default for ButtonFeedback {
+    fn default() -> Self {
+        ButtonFeedback {
+            color: BUTTON_COLOR,
+            hover_color: FOCUSED_COLOR,
+            disabled_color: DISABLED_COLOR,
+            hover_sfx: None,
+            click_sfx: None,
+            disabled: false,
+            hovered: false,
+        }
+    }
+}
+
+impl ButtonFeedback {
+    /// Plays `hover` and `click` instead of UiSounds'
+    pub fn with_sounds(mut self, hover: impl Into<String>, click: impl Into<String>) -> Self {
+        self.hover_sfx = Some(hover.into());
+        self.click_sfx = Some(click.into());
+        self
+    }
+
+    /// Whether the button is shown hovered
+    pub fn is_hovered(&self) -> bool {
+        self.hovered
+    }
+}
+
+///
+/// button_feedback: Bevy system
+///
+/// Tints, scales and nudges Buttons with ButtonFeedback by their Interaction, or MenuFocus for
+/// MenuButtons, playing the hover SFX as one becomes hovered and the click SFX as it's pressed or
+/// activated. Buttons hovered as they're spawned, such as a menu's first focus, stay quiet.
+#[allow(clippy::type_complexity)]
+pub fn button_feedback(
+    focus: Res<MenuFocus>,
+    sounds: Res<UiSounds>,
+    mut activations: EventReader<ButtonActivated>,
+    mut button_query: Query<
+        (
+            Entity,
+            Ref<Interaction>,
+            Option<&MenuButton>,
+            &mut ButtonFeedback,
+            &mut BackgroundColor,
+            &mut Transform,
+            &mut Style,
+        ),
+        With<Button>,
+    >,
+    mut sfx: EventWriter<PlaySFX>,
+) {
+    let mut activated: Vec<Entity> = activations.read().map(|event| event.0).collect();
+    for (
+        entity,
+        interaction,
+        menu_button,
+        mut feedback,
+        mut background,
+        mut transform,
+        mut style,
+    ) in button_query.iter_mut()
+    {
+        let disabled = feedback.disabled || menu_button.is_some_and(|button| !button.enabled);
+        let pressed = !disabled && *interaction == Interaction::Pressed;
+        let hovered = !disabled
+            && match menu_button {
+                Some(_) => focus.0 == Some(entity),
+                None => *interaction != Interaction::None,
+            };
+
+        if hovered && !feedback.hovered && !feedback.is_added() {
+            let name = feedback.hover_sfx.as_ref().unwrap_or(&sounds.hover);
+            sfx.send(PlaySFX::new(name.clone()));
+        }
+        // a mouse press on a MenuButton also activates it, one sound for both
+        let clicked = activated.contains(&entity) || (pressed && interaction.is_changed());
+        activated.retain(|button| *button != entity);
+        if clicked && !disabled {
+            let name = feedback.click_sfx.as_ref().unwrap_or(&sounds.confirm);
+            sfx.send(PlaySFX::new(name.clone()));
+        }
+        if feedback.hovered != hovered {
+            feedback.hovered = hovered;
+        }
+
+        let color = if disabled {
+            feedback.disabled_color
+        } else if hovered || pressed {
+            feedback.hover_color
+        } else {
+            feedback.color
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+        let scale = Vec3::splat(if hovered { HOVER_SCALE } else { 1.0 });
+        if transform.scale != scale {
+            transform.scale = scale;
+        }
+        let top = if pressed {
+            Val::Px(PRESS_OFFSET)
+        } else {
+            Val::Auto
+        };
+        if style.top != top {
+            style.top = top;
+        }
+    }
+}
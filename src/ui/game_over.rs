@@ -0,0 +1,275 @@
+//! The game over screen, over the frozen world while in AppState::GameOver, after the player has
+//! died with no checkpoint to come back to. Gameplay has stopped already, GameplaySet only running
+//! InGame. The world darkens over DARKEN_TIME seconds of real time, the music gives way to
+//! GAME_OVER_MUSIC played once, ending with MusicFinished, and the menu offers to retry from the
+//! active checkpoint, when there is one, to restart the level or to quit to the main menu.
+//!
+//! Retrying and restarting are done on leaving GameOver, so the player that died is still there
+//! when InGame is entered and no second one is spawned for it. The ScreenFade goes black at once
+//! under the darkened screen, for the respawn or the restart to fade back in from, the player
+//! comes back with full health and the music that was interrupted plays again.
+
+use super::{
+    despawn_menu, navigate_menus, spawn_menu_button, ButtonActivated, MenuButton, MenuFocus,
+};
+use crate::checkpoints::Respawn;
+use crate::gfx::ScreenFade;
+use crate::helpers::tiled::{RestartMap, UnloadMap};
+use crate::locale::LocalizedText;
+use crate::player::Player;
+use crate::save::GameProgress;
+use crate::sound::{CurrentMusic, PlayMusic};
+use crate::state::{AppState, ChangeState};
+use bevy::prelude::*;
+
+/// How dark the world gets behind the menu
+const DARK: Color = Color::rgba(0.0, 0.0, 0.0, 0.8);
+/// Seconds the world takes to darken
+pub const DARKEN_TIME: f32 = 1.0;
+/// Music played once on game over
+pub const GAME_OVER_MUSIC: &str = "game_over";
+/// Seconds for the interrupted music to fade back in on playing on
+const MUSIC_FADE: f32 = 1.0;
+
+/// A button of the game over screen
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameOverButton {
+    RetryFromCheckpoint,
+    RestartLevel,
+    QuitToMenu,
+}
+
+impl GameOverButton {
+    /// The buttons, top to bottom; RetryFromCheckpoint is disabled without an active checkpoint
+    pub const ALL: [GameOverButton; 3] = [
+        GameOverButton::RetryFromCheckpoint,
+        GameOverButton::RestartLevel,
+        GameOverButton::QuitToMenu,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            GameOverButton::RetryFromCheckpoint => "game_over.retry",
+            GameOverButton::RestartLevel => "pause.restart",
+            GameOverButton::QuitToMenu => "pause.quit_to_menu",
+        }
+    }
+}
+
+/// Marks the game over screen's root node, which darkens the world behind it
+#[derive(Component, Debug)]
+pub struct GameOverRoot;
+
+/// Real seconds since the game over screen opened, darkening it
+#[derive(Component, Debug, Default)]
+struct Darkening(f32);
+
+///
+/// GameOverChoice
+///
+/// What the game over screen is left for
+/// * button: the button chosen, done on leaving GameOver; None when GameOver is left otherwise,
+///   e.g. by ResetGame
+/// * music: the track GAME_OVER_MUSIC interrupted
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub struct GameOverChoice {
+    pub button: Option<GameOverButton>,
+    pub music: Option<String>,
+}
+
+/// The game over screen, spawned on entering GameOver and despawned on leaving it
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GameOverChoice>()
+            .add_event::<ChangeState>()
+            .add_event::<RestartMap>()
+            .add_event::<UnloadMap>()
+            .add_event::<PlayMusic>()
+            .add_systems(OnEnter(AppState::GameOver), open_game_over)
+            .add_systems(
+                OnExit(AppState::GameOver),
+                (despawn_menu::<GameOverRoot>, leave_game_over),
+            )
+            .add_systems(
+                Update,
+                (
+                    darken_game_over,
+                    run_game_over
+                        .after(navigate_menus)
+                        .run_if(in_state(AppState::GameOver)),
+                ),
+            );
+    }
+}
+
+///
+/// open_game_over: Bevy system
+///
+/// Spawns the game over screen and plays GAME_OVER_MUSIC over the music
+pub fn open_game_over(
+    mut commands: Commands,
+    mut focus: ResMut<MenuFocus>,
+    mut choice: ResMut<GameOverChoice>,
+    progress: Option<Res<GameProgress>>,
+    respawn: Option<Res<Respawn>>,
+    current: Option<Res<CurrentMusic>>,
+    mut music: EventWriter<PlayMusic>,
+) {
+    *choice = GameOverChoice {
+        button: None,
+        music: current
+            .as_ref()
+            .and_then(|current| current.name())
+            .filter(|name| *name != GAME_OVER_MUSIC)
+            .map(str::to_string),
+    };
+    music.send(PlayMusic::new(GAME_OVER_MUSIC).with_looping(false));
+    let checkpoint =
+        respawn.is_some() && progress.is_some_and(|progress| progress.checkpoint.is_some());
+
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.0),
+            ..default()
+        },
+        background_color: Color::NONE.into(),
+        // over the HUD, under the toasts
+        z_index: ZIndex::Global(50),
+        ..default()
+    };
+    commands
+        .spawn((GameOverRoot, Darkening::default(), root))
+        .with_children(|menu| {
+            menu.spawn((
+                LocalizedText::new("game_over.title"),
+                TextBundle::from_section(
+                    "game_over.title",
+                    TextStyle {
+                        font_size: 36.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(16.0)),
+                    ..default()
+                }),
+            ));
+            focus.0 = None;
+            for (index, button) in GameOverButton::ALL.iter().enumerate() {
+                let enabled = checkpoint || *button != GameOverButton::RetryFromCheckpoint;
+                let menu_button = MenuButton { index, enabled };
+                let entity = spawn_menu_button(menu, menu_button, button.label(), *button);
+                // the first one that can be chosen
+                if enabled && focus.0.is_none() {
+                    focus.0 = Some(entity);
+                }
+            }
+        });
+}
+
+/// Eases the game over screen's background to DARK
+fn darken_game_over(
+    time: Res<Time<Real>>,
+    mut root_query: Query<(&mut Darkening, &mut BackgroundColor), With<GameOverRoot>>,
+) {
+    for (mut darkening, mut background) in root_query.iter_mut() {
+        if darkening.0 >= DARKEN_TIME {
+            continue;
+        }
+        darkening.0 += time.delta_seconds();
+        let t = (darkening.0 / DARKEN_TIME).min(1.0);
+        // eased out, darkening quickly and then settling
+        let eased = 1.0 - (1.0 - t) * (1.0 - t);
+        background.0 = DARK.with_a(DARK.a() * eased);
+    }
+}
+
+///
+/// run_game_over: Bevy system
+///
+/// Leaves GameOver for what the activated button says, keeping the choice for leave_game_over
+pub fn run_game_over(
+    mut activations: EventReader<ButtonActivated>,
+    button_query: Query<&GameOverButton>,
+    mut choice: ResMut<GameOverChoice>,
+    mut change_state: EventWriter<ChangeState>,
+    mut unload: EventWriter<UnloadMap>,
+) {
+    for ButtonActivated(entity) in activations.read() {
+        let Ok(button) = button_query.get(*entity) else {
+            continue;
+        };
+        choice.button = Some(*button);
+        match button {
+            GameOverButton::RetryFromCheckpoint | GameOverButton::RestartLevel => {
+                change_state.send(ChangeState(AppState::InGame));
+            }
+            // start_game loads the first map again on Play
+            GameOverButton::QuitToMenu => {
+                change_state.send(ChangeState(AppState::MainMenu));
+                unload.send(UnloadMap::default());
+            }
+        }
+    }
+}
+
+///
+/// leave_game_over: Bevy system
+///
+/// Brings the player back at the active checkpoint, or at the start of the level again, as the
+/// game over screen chose
+#[allow(clippy::too_many_arguments)]
+pub fn leave_game_over(
+    mut commands: Commands,
+    mut choice: ResMut<GameOverChoice>,
+    progress: Option<ResMut<GameProgress>>,
+    respawn: Option<ResMut<Respawn>>,
+    fade: Option<ResMut<ScreenFade>>,
+    player_query: Query<Entity, With<Player>>,
+    mut restart: EventWriter<RestartMap>,
+    mut music: EventWriter<PlayMusic>,
+) {
+    let GameOverChoice {
+        button,
+        music: track,
+    } = std::mem::take(&mut *choice);
+    let checkpoint = progress
+        .as_ref()
+        .and_then(|progress| progress.checkpoint.clone());
+    match button {
+        Some(GameOverButton::RetryFromCheckpoint) => {
+            let (Some(checkpoint), Some(mut respawn)) = (checkpoint, respawn) else {
+                return;
+            };
+            // advance_respawn takes it from here, the fade done already
+            *respawn = Respawn::FadingOut(checkpoint);
+        }
+        Some(GameOverButton::RestartLevel) => {
+            // spawn_missing_player spawns a new one for the map to place
+            for player in player_query.iter() {
+                commands.entity(player).despawn_recursive();
+            }
+            if let Some(mut progress) = progress {
+                progress.health = progress.max_health;
+            }
+            restart.send(RestartMap);
+        }
+        Some(GameOverButton::QuitToMenu) | None => return,
+    }
+
+    if let Some(mut fade) = fade {
+        fade.fade_out(0.0);
+    }
+    if let Some(track) = track {
+        music.send(PlayMusic::new(track).with_crossfade(MUSIC_FADE));
+    }
+}
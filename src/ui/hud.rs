@@ -0,0 +1,458 @@
+//! The in-game HUD: the player's hearts and score from GameProgress in the top corners, with the
+//! keys they carry under the hearts, the time of the run from RunStats between them, and the name
+//! of the area the player walks into across the top for a moment. It's up from the first frame in game until going back to the main
+//! menu, and hidden while the dialogue box is open or HideHud is on.
+//!
+//! Nodes only change with the values they show. The score counts up to a new value, pulsing as
+//! it goes, and the hearts shake when health goes down.
+//!
+//! Area names are trigger regions with an `area` property, the locale key of the name.
+
+use super::Dialogue;
+use crate::helpers::tiled::{TriggerEntered, TriggerRegion};
+use crate::locale::Locale;
+use crate::run_stats::{format_time, RunStats};
+use crate::save::GameProgress;
+use crate::state::AppState;
+use bevy::prelude::*;
+
+/// Seconds an area's name stays up
+pub const AREA_BANNER_TIME: f32 = 2.5;
+/// The trigger region property naming an area
+pub const AREA_PROPERTY: &str = "area";
+
+/// Seconds the area name takes to fade in and out
+const AREA_FADE: f32 = 0.4;
+/// Each second, the shown score closes this many times the gap to the real one
+const SCORE_CATCH_UP: f32 = 8.0;
+/// Seconds the score's pulse takes to settle
+const PULSE_TIME: f32 = 0.15;
+/// How much bigger the score gets at the height of a pulse
+const PULSE_SCALE: f32 = 0.3;
+/// Seconds the hearts shake for on taking damage
+const SHAKE_TIME: f32 = 0.35;
+/// Pixels the hearts shake by at first
+const SHAKE_DISTANCE: f32 = 4.0;
+
+const HEART_FULL: Color = Color::rgb(0.9, 0.15, 0.2);
+const HEART_EMPTY: Color = Color::rgba(0.2, 0.2, 0.25, 0.8);
+const COIN_COLOR: Color = Color::rgb(1.0, 0.8, 0.2);
+/// Keys of locks without a color of their own
+const KEY_COLOR: Color = Color::rgb(0.8, 0.8, 0.85);
+
+///
+/// HideHud
+///
+/// Hides the HUD while on, e.g. while a cutscene's camera moves. The dialogue box hides it on its
+/// own.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HideHud(pub bool);
+
+/// Marks the HUD's root node
+#[derive(Component, Debug)]
+pub struct HudRoot;
+
+/// The row of hearts, one per point of GameProgress::max_health
+/// * health, max: the values shown
+/// * shake: seconds of shaking left
+#[derive(Component, Debug, Default)]
+pub struct Hearts {
+    pub health: i32,
+    pub max: i32,
+    pub shake: f32,
+}
+
+/// A heart and its place in the row, full while the player has more health than that
+#[derive(Component, Debug)]
+pub struct Heart(pub i32);
+
+/// The score, counting up to GameProgress::score
+/// * shown: the value shown
+/// * pulse: seconds left of its pulse
+#[derive(Component, Debug, Default)]
+pub struct ScoreCounter {
+    pub shown: u64,
+    pub pulse: f32,
+}
+
+/// The keys the player carries, one icon each in the color of its lock, from GameProgress::keys
+#[derive(Component, Debug, Default)]
+pub struct KeyIcons {
+    pub shown: Vec<String>,
+}
+
+/// The time of the run so far, from RunStats
+#[derive(Component, Debug, Default)]
+pub struct RunTimer;
+
+/// The name of the area walked into, and the seconds it has left up
+#[derive(Component, Debug, Default)]
+pub struct AreaBanner {
+    pub remaining: f32,
+}
+
+/// The HUD
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HideHud>()
+            .init_resource::<Dialogue>()
+            .add_event::<TriggerEntered>()
+            .add_systems(OnEnter(AppState::MainMenu), despawn_hud)
+            .add_systems(
+                Update,
+                (
+                    spawn_hud.run_if(
+                        in_state(AppState::InGame).and_then(not(any_with_component::<HudRoot>)),
+                    ),
+                    (
+                        update_hearts,
+                        shake_hearts,
+                        update_keys,
+                        update_score,
+                        update_run_timer,
+                        show_area_names,
+                        fade_area_banner,
+                        hide_hud,
+                    )
+                        .run_if(any_with_component::<HudRoot>),
+                )
+                    .chain(),
+            );
+    }
+}
+
+///
+/// spawn_hud: Bevy system
+///
+/// Spawns the HUD, empty until the update systems fill it in
+pub fn spawn_hud(mut commands: Commands) {
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            padding: UiRect::all(Val::Px(16.0)),
+            justify_content: JustifyContent::SpaceBetween,
+            align_items: AlignItems::FlexStart,
+            ..default()
+        },
+        // under the dialogue box
+        z_index: ZIndex::Global(30),
+        ..default()
+    };
+    let text = |size: f32, color: Color| {
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: size,
+                color,
+                ..default()
+            },
+        )
+    };
+    let row = || NodeBundle {
+        style: Style {
+            column_gap: Val::Px(4.0),
+            ..default()
+        },
+        ..default()
+    };
+    commands.spawn((HudRoot, root)).with_children(|hud| {
+        hud.spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(6.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|corner| {
+            corner.spawn((Hearts::default(), row()));
+            corner.spawn((KeyIcons::default(), row()));
+        });
+        hud.spawn((RunTimer, text(24.0, Color::WHITE)));
+        hud.spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                column_gap: Val::Px(6.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|score| {
+            score.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(14.0),
+                    height: Val::Px(14.0),
+                    ..default()
+                },
+                background_color: COIN_COLOR.into(),
+                ..default()
+            });
+            score.spawn((ScoreCounter::default(), text(24.0, Color::WHITE)));
+        });
+        // across the top whatever the window's shape, below the corners
+        hud.spawn((
+            AreaBanner::default(),
+            text(32.0, Color::NONE)
+                .with_text_justify(JustifyText::Center)
+                .with_style(Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Percent(15.0),
+                    width: Val::Percent(100.0),
+                    ..default()
+                }),
+        ));
+    });
+}
+
+/// Despawns the HUD
+fn despawn_hud(mut commands: Commands, root_query: Query<Entity, With<HudRoot>>) {
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+///
+/// update_hearts: Bevy system
+///
+/// Keeps one heart per point of max_health, falling back to health, and fills as many as the
+/// player has. Health going down shakes them.
+pub fn update_hearts(
+    mut commands: Commands,
+    progress: Option<Res<GameProgress>>,
+    mut hearts_query: Query<(Entity, &mut Hearts)>,
+    mut heart_query: Query<(&Heart, &mut BackgroundColor)>,
+) {
+    let health = progress
+        .as_ref()
+        .and_then(|progress| progress.health)
+        .unwrap_or(0);
+    let max = progress
+        .as_ref()
+        .and_then(|progress| progress.max_health.or(progress.health))
+        .unwrap_or(0)
+        .max(health);
+    for (entity, mut hearts) in hearts_query.iter_mut() {
+        if hearts.max != max {
+            hearts.max = max;
+            commands
+                .entity(entity)
+                .despawn_descendants()
+                .with_children(|row| {
+                    for index in 0..max {
+                        row.spawn((
+                            Heart(index),
+                            NodeBundle {
+                                style: Style {
+                                    width: Val::Px(18.0),
+                                    height: Val::Px(16.0),
+                                    ..default()
+                                },
+                                background_color: heart_color(index, health).into(),
+                                ..default()
+                            },
+                        ));
+                    }
+                });
+        }
+        if hearts.health == health {
+            continue;
+        }
+        if health < hearts.health {
+            hearts.shake = SHAKE_TIME;
+        }
+        hearts.health = health;
+        for (heart, mut color) in heart_query.iter_mut() {
+            color.0 = heart_color(heart.0, health);
+        }
+    }
+}
+
+///
+/// update_keys: Bevy system
+///
+/// Shows an icon for each key in GameProgress::keys
+pub fn update_keys(
+    mut commands: Commands,
+    progress: Option<Res<GameProgress>>,
+    mut icons_query: Query<(Entity, &mut KeyIcons)>,
+) {
+    let keys: Vec<String> = progress
+        .iter()
+        .flat_map(|progress| progress.keys.iter())
+        .flat_map(|(lock, count)| std::iter::repeat_n(lock.clone(), *count as usize))
+        .collect();
+    for (entity, mut icons) in icons_query.iter_mut() {
+        if icons.shown == keys {
+            continue;
+        }
+        commands
+            .entity(entity)
+            .despawn_descendants()
+            .with_children(|row| {
+                for lock in keys.iter() {
+                    row.spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(10.0),
+                            height: Val::Px(16.0),
+                            ..default()
+                        },
+                        background_color: key_color(lock).into(),
+                        ..default()
+                    });
+                }
+            });
+        icons.shown = keys.clone();
+    }
+}
+
+/// The color of the icon of a key to `lock`, for the usual color names
+pub fn key_color(lock: &str) -> Color {
+    match lock {
+        "red" => Color::rgb(0.9, 0.2, 0.2),
+        "blue" => Color::rgb(0.25, 0.45, 1.0),
+        "green" => Color::rgb(0.25, 0.8, 0.3),
+        "yellow" | "gold" => Color::rgb(1.0, 0.8, 0.2),
+        "purple" => Color::rgb(0.65, 0.3, 0.9),
+        _ => KEY_COLOR,
+    }
+}
+
+fn heart_color(index: i32, health: i32) -> Color {
+    if index < health {
+        HEART_FULL
+    } else {
+        HEART_EMPTY
+    }
+}
+
+/// Shakes the hearts side to side, less and less
+fn shake_hearts(time: Res<Time<Real>>, mut hearts_query: Query<(&mut Hearts, &mut Style)>) {
+    for (mut hearts, mut style) in hearts_query.iter_mut() {
+        if hearts.shake <= 0.0 {
+            continue;
+        }
+        hearts.shake = (hearts.shake - time.delta_seconds()).max(0.0);
+        let strength = hearts.shake / SHAKE_TIME;
+        let offset = (hearts.shake * 60.0).sin() * SHAKE_DISTANCE * strength;
+        style.left = Val::Px(offset);
+    }
+}
+
+///
+/// update_score: Bevy system
+///
+/// Counts the shown score up (or down) to GameProgress::score, pulsing as it changes
+pub fn update_score(
+    time: Res<Time<Real>>,
+    progress: Option<Res<GameProgress>>,
+    mut score_query: Query<(&mut ScoreCounter, &mut Text, &mut Transform)>,
+) {
+    let score = progress.map_or(0, |progress| progress.score);
+    let dt = time.delta_seconds();
+    for (mut counter, mut text, mut transform) in score_query.iter_mut() {
+        if counter.shown != score {
+            let gap = score as f64 - counter.shown as f64;
+            // at least a point a frame, so the last few don't crawl
+            let step = (gap * (SCORE_CATCH_UP * dt).min(1.0) as f64).abs().max(1.0);
+            counter.shown = if gap > 0.0 {
+                (counter.shown + step as u64).min(score)
+            } else {
+                counter.shown.saturating_sub(step as u64).max(score)
+            };
+            counter.pulse = PULSE_TIME;
+            text.sections[0].value = counter.shown.to_string();
+        } else if text.sections[0].value.is_empty() {
+            text.sections[0].value = score.to_string();
+        }
+
+        if counter.pulse > 0.0 || transform.scale != Vec3::ONE {
+            counter.pulse = (counter.pulse - dt).max(0.0);
+            let scale = 1.0 + PULSE_SCALE * counter.pulse / PULSE_TIME;
+            transform.scale = Vec3::splat(scale);
+        }
+    }
+}
+
+///
+/// update_run_timer: Bevy system
+///
+/// Shows the time of the run, in tenths of a second
+pub fn update_run_timer(
+    stats: Option<Res<RunStats>>,
+    mut timer_query: Query<&mut Text, With<RunTimer>>,
+) {
+    let shown = stats.map_or_else(|| format_time(0.0), |stats| format_time(stats.time));
+    for mut text in timer_query.iter_mut() {
+        if text.sections[0].value != shown {
+            text.sections[0].value = shown.clone();
+        }
+    }
+}
+
+///
+/// show_area_names: Bevy system
+///
+/// Puts up the name of the area of a trigger region walked into
+pub fn show_area_names(
+    mut entered: EventReader<TriggerEntered>,
+    region_query: Query<&TriggerRegion>,
+    locale: Option<Res<Locale>>,
+    mut banner_query: Query<(&mut AreaBanner, &mut Text)>,
+) {
+    let name = entered
+        .read()
+        .filter_map(|event| region_query.get(event.region).ok())
+        .filter_map(|region| match region.properties.get(AREA_PROPERTY) {
+            Some(tiled::PropertyValue::StringValue(key)) => Some(key.clone()),
+            _ => None,
+        })
+        .last();
+    let Some(key) = name else {
+        return;
+    };
+    let name = locale.map_or_else(|| key.clone(), |locale| locale.text(&key));
+    for (mut banner, mut text) in banner_query.iter_mut() {
+        banner.remaining = AREA_BANNER_TIME;
+        text.sections[0].value = name.clone();
+    }
+}
+
+/// Fades the area name in and out over its time
+fn fade_area_banner(time: Res<Time<Real>>, mut banner_query: Query<(&mut AreaBanner, &mut Text)>) {
+    for (mut banner, mut text) in banner_query.iter_mut() {
+        if banner.remaining <= 0.0 {
+            continue;
+        }
+        banner.remaining = (banner.remaining - time.delta_seconds()).max(0.0);
+        let shown = AREA_BANNER_TIME - banner.remaining;
+        let alpha = (shown / AREA_FADE)
+            .min(banner.remaining / AREA_FADE)
+            .clamp(0.0, 1.0);
+        text.sections[0].style.color = Color::WHITE.with_a(alpha);
+    }
+}
+
+///
+/// hide_hud: Bevy system
+///
+/// Hides the HUD while the dialogue box is open or HideHud is on
+pub fn hide_hud(
+    dialogue: Res<Dialogue>,
+    hide: Res<HideHud>,
+    mut root_query: Query<&mut Visibility, With<HudRoot>>,
+) {
+    let wanted = if dialogue.is_open() || hide.0 {
+        Visibility::Hidden
+    } else {
+        Visibility::Inherited
+    };
+    for mut visibility in root_query.iter_mut() {
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
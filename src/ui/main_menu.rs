@@ -0,0 +1,250 @@
+//! The main menu, up while in AppState::MainMenu: Play starts a new game, Continue loads the
+//! saved one, Settings opens the settings and Quit closes the game (not on the web, where
+//! there's nothing to quit to).
+
+use super::{
+    despawn_menu, navigate_menus, spawn_menu_button, ButtonActivated, MenuButton, MenuFocus,
+    OpenSettings, SettingsClosed,
+};
+use crate::gfx::ScreenFade;
+use crate::locale::LocalizedText;
+use crate::save::{has_save, GameSaved, LoadGame};
+use crate::state::{AppState, ChangeState};
+use bevy::{app::AppExit, prelude::*};
+
+/// Seconds the menu takes to fade in from black
+pub const MENU_FADE: f32 = 0.4;
+
+/// A button of the main menu
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MainMenuButton {
+    Play,
+    Continue,
+    Settings,
+    Quit,
+}
+
+impl MainMenuButton {
+    /// The buttons shown, top to bottom
+    #[cfg(not(target_arch = "wasm32"))]
+    pub const SHOWN: &'static [MainMenuButton] = &[
+        MainMenuButton::Play,
+        MainMenuButton::Continue,
+        MainMenuButton::Settings,
+        MainMenuButton::Quit,
+    ];
+    /// The buttons shown, top to bottom
+    #[cfg(target_arch = "wasm32")]
+    pub const SHOWN: &'static [MainMenuButton] = &[
+        MainMenuButton::Play,
+        MainMenuButton::Continue,
+        MainMenuButton::Settings,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            MainMenuButton::Play => "menu.play",
+            MainMenuButton::Continue => "menu.continue",
+            MainMenuButton::Settings => "menu.settings",
+            MainMenuButton::Quit => "menu.quit",
+        }
+    }
+}
+
+/// Marks the main menu's root node
+#[derive(Component, Debug)]
+pub struct MainMenuRoot;
+
+///
+/// ContinueAvailable
+///
+/// Whether there's a saved game for Continue to load, checked when the menu opens and after
+/// each save
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ContinueAvailable(pub bool);
+
+/// The main menu, spawned on entering MainMenu and despawned on leaving it. Like the pause
+/// menu, it goes away while the settings are open and comes back on SettingsClosed.
+pub struct MainMenuPlugin;
+
+impl Plugin for MainMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ContinueAvailable>()
+            .add_event::<ChangeState>()
+            .add_event::<LoadGame>()
+            .add_event::<GameSaved>()
+            .add_systems(
+                OnEnter(AppState::MainMenu),
+                (check_for_save, open_main_menu).chain(),
+            )
+            .add_systems(OnExit(AppState::MainMenu), despawn_menu::<MainMenuRoot>)
+            .add_systems(
+                Update,
+                (
+                    (
+                        check_for_save.run_if(on_event::<GameSaved>()),
+                        enable_continue,
+                    )
+                        .chain()
+                        .before(navigate_menus),
+                    (
+                        run_main_menu,
+                        reopen_main_menu.run_if(on_event::<SettingsClosed>()),
+                    )
+                        .chain()
+                        .run_if(in_state(AppState::MainMenu))
+                        .after(navigate_menus),
+                ),
+            );
+    }
+}
+
+fn check_for_save(mut available: ResMut<ContinueAvailable>) {
+    let found = has_save();
+    if available.0 != found {
+        available.0 = found;
+    }
+}
+
+/// Spawns the main menu with `focused` focused, Continue only enabled if `available`
+pub fn spawn_main_menu(
+    commands: &mut Commands,
+    available: ContinueAvailable,
+    focus: &mut MenuFocus,
+    focused: MainMenuButton,
+) {
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.0),
+            ..default()
+        },
+        ..default()
+    };
+    commands.spawn((MainMenuRoot, root)).with_children(|menu| {
+        menu.spawn((
+            LocalizedText::new("menu.title"),
+            TextBundle::from_section(
+                "menu.title",
+                TextStyle {
+                    font_size: 48.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                margin: UiRect::bottom(Val::Px(24.0)),
+                ..default()
+            }),
+        ));
+        for (index, button) in MainMenuButton::SHOWN.iter().enumerate() {
+            let enabled = *button != MainMenuButton::Continue || available.0;
+            let entity =
+                spawn_menu_button(menu, MenuButton { index, enabled }, button.label(), *button);
+            if *button == focused {
+                focus.0 = Some(entity);
+            }
+        }
+    });
+}
+
+///
+/// open_main_menu: Bevy system
+///
+/// Spawns the menu, focused on Continue if there's a game to continue and Play otherwise, and
+/// fades it in from black
+pub fn open_main_menu(
+    mut commands: Commands,
+    available: Res<ContinueAvailable>,
+    mut focus: ResMut<MenuFocus>,
+    fade: Option<ResMut<ScreenFade>>,
+) {
+    let first = if available.0 {
+        MainMenuButton::Continue
+    } else {
+        MainMenuButton::Play
+    };
+    spawn_main_menu(&mut commands, *available, &mut focus, first);
+
+    if let Some(mut fade) = fade {
+        fade.fade_out(0.0);
+        fade.fade_in(MENU_FADE);
+    }
+}
+
+/// Keeps Continue enabled only while there's a save to continue
+fn enable_continue(
+    available: Res<ContinueAvailable>,
+    mut button_query: Query<(&MainMenuButton, &mut MenuButton)>,
+) {
+    if !available.is_changed() {
+        return;
+    }
+    for (button, mut menu_button) in button_query.iter_mut() {
+        if *button == MainMenuButton::Continue && menu_button.enabled != available.0 {
+            menu_button.enabled = available.0;
+        }
+    }
+}
+
+///
+/// run_main_menu: Bevy system
+///
+/// Does what the activated button says
+#[allow(clippy::too_many_arguments)]
+pub fn run_main_menu(
+    mut commands: Commands,
+    mut activations: EventReader<ButtonActivated>,
+    button_query: Query<&MainMenuButton>,
+    root_query: Query<Entity, With<MainMenuRoot>>,
+    mut change_state: EventWriter<ChangeState>,
+    mut load_game: EventWriter<LoadGame>,
+    mut open_settings: EventWriter<OpenSettings>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for ButtonActivated(entity) in activations.read() {
+        let Ok(button) = button_query.get(*entity) else {
+            continue;
+        };
+        match button {
+            MainMenuButton::Play => {
+                change_state.send(ChangeState(AppState::InGame));
+            }
+            // load_game moves into the game once the save is read
+            MainMenuButton::Continue => {
+                load_game.send(LoadGame);
+            }
+            MainMenuButton::Settings => {
+                for root in root_query.iter() {
+                    commands.entity(root).despawn_recursive();
+                }
+                open_settings.send(OpenSettings);
+            }
+            MainMenuButton::Quit => {
+                exit.send(AppExit);
+            }
+        }
+    }
+}
+
+/// Brings the main menu back when the settings it opened close, without fading in again
+fn reopen_main_menu(
+    mut commands: Commands,
+    available: Res<ContinueAvailable>,
+    mut focus: ResMut<MenuFocus>,
+    root_query: Query<(), With<MainMenuRoot>>,
+) {
+    if root_query.is_empty() {
+        spawn_main_menu(
+            &mut commands,
+            *available,
+            &mut focus,
+            MainMenuButton::Settings,
+        );
+    }
+}
@@ -0,0 +1,434 @@
+//! The game's menus. Their buttons are MenuButtons: hovering one or moving to it with the
+//! movement actions focuses it, and clicking it or pressing Interact on the focused one sends
+//! ButtonActivated for the menu to act on. Buttons with ButtonFeedback light up and play sounds
+//! as they're hovered and clicked.
+//!
+//! Up and down go through a menu's buttons in order, wrapping round unless MenuNavigation says
+//! not to. Buttons laid out in a grid are Focusable, moving in all four directions to their
+//! neighbors or the nearest button that way. While focus is moved with the keyboard or a
+//! gamepad the focused button has a ring around it, which the mouse takes away again. Pause
+//! backs out of the settings screen and the pause menu.
+//!
+//! The dialogue box with its branching dialogue trees, the HUD, the interaction prompt, the
+//! results screen and the game over screen are here too, see their modules.
+
+mod dialogue;
+mod dialogue_tree;
+mod feedback;
+mod game_over;
+mod hud;
+mod main_menu;
+mod pause_menu;
+mod prompt;
+mod results;
+mod settings_menu;
+
+pub use dialogue::{
+    advance_dialogue, choose_dialogue, offer_choices, show_dialogue, start_dialogue, type_dialogue,
+    Dialogue, DialogueBox, DialogueChoiceButton, DialogueFinished, DialogueLine, DialoguePlugin,
+    StartDialogue, StartDialogueTree, DIALOGUE_SPEED,
+};
+pub use dialogue_tree::{
+    load_map_dialogue, DialogueChoice, DialogueNode, DialogueTree, DialogueTreeLookup,
+    DialogueTreePlugin, DialogueTrees, DialogueTreesLoader, DialogueTreesLoaderError, MapDialogue,
+    CHOICES, DIALOGUE_PROPERTY,
+};
+pub use feedback::{button_feedback, ButtonFeedback, HOVER_SCALE, PRESS_OFFSET};
+pub use game_over::{
+    leave_game_over, open_game_over, run_game_over, GameOverButton, GameOverChoice,
+    GameOverPlugin, GameOverRoot, DARKEN_TIME, GAME_OVER_MUSIC,
+};
+pub use hud::{
+    key_color, spawn_hud, update_hearts, update_keys, update_run_timer, update_score, AreaBanner,
+    Heart, Hearts, HideHud, HudPlugin, HudRoot, KeyIcons, RunTimer, ScoreCounter,
+    AREA_BANNER_TIME, AREA_PROPERTY,
+};
+pub use main_menu::{ContinueAvailable, MainMenuButton, MainMenuPlugin, MainMenuRoot};
+pub use pause_menu::{PauseMenuButton, PauseMenuPlugin, PauseMenuRoot};
+pub use prompt::{
+    choose_prompt, interact, show_prompt, Interactable, Interacted, InteractionPrompt,
+    PromptPlugin, PromptText, PROMPT_HYSTERESIS,
+};
+pub use results::{open_results, ResultsButton, ResultsPlugin, ResultsRoot};
+pub use settings_menu::{
+    SettingsMenuPlugin, SettingsRoot, SettingsValue, SettingsWidget, VOLUME_STEP,
+};
+
+use crate::input::{Action, ActionState};
+use crate::locale::LocalizedText;
+use crate::sound::PlaySFX;
+use bevy::prelude::*;
+
+const BUTTON_COLOR: Color = Color::rgba(0.1, 0.1, 0.15, 0.85);
+const FOCUSED_COLOR: Color = Color::rgba(0.3, 0.38, 0.7, 0.95);
+const DISABLED_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.4);
+/// The border of the focused button while moving with the keyboard or a gamepad
+pub const FOCUS_RING_COLOR: Color = Color::rgb(1.0, 0.85, 0.3);
+
+///
+/// UiSounds
+///
+/// The SFX ButtonFeedback plays unless a button names its own, by their names in SoundResource
+/// * hover: a button hovered or focused
+/// * confirm: a button clicked or activated
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct UiSounds {
+    pub hover: String,
+    pub confirm: String,
+}
+
+impl Default for UiSounds {
+    fn default() -> Self {
+        UiSounds {
+            hover: "ui_hover".to_string(),
+            confirm: "ui_confirm".to_string(),
+        }
+    }
+}
+
+/// A button of a menu, moved through in the order of `index`. Disabled buttons can't be focused
+/// or activated.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MenuButton {
+    pub index: usize,
+    pub enabled: bool,
+}
+
+/// Lets a MenuButton be moved from in any direction. Each of the movement actions goes to its
+/// neighbor if it's set and enabled, and otherwise to the nearest button on screen that way.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Focusable {
+    pub up: Option<Entity>,
+    pub down: Option<Entity>,
+    pub left: Option<Entity>,
+    pub right: Option<Entity>,
+}
+
+impl Focusable {
+    /// The neighbor `direction` of the button, with y going down as on screen
+    fn neighbor(&self, direction: Vec2) -> Option<Entity> {
+        if direction.y < 0.0 {
+            self.up
+        } else if direction.y > 0.0 {
+            self.down
+        } else if direction.x < 0.0 {
+            self.left
+        } else {
+            self.right
+        }
+    }
+}
+
+///
+/// MenuFocus
+///
+/// The button keyboard and gamepad act on, if any
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MenuFocus(pub Option<Entity>);
+
+///
+/// MenuNavigation
+///
+/// * wrap: whether moving past the last button comes round to the first, and back
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MenuNavigation {
+    pub wrap: bool,
+}
+
+impl Default for MenuNavigation {
+    fn default() -> Self {
+        MenuNavigation { wrap: true }
+    }
+}
+
+///
+/// FocusVisible
+///
+/// Whether the focused button has its ring, on since the movement actions last moved focus and
+/// off once the mouse has
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FocusVisible(pub bool);
+
+/// Sent when a MenuButton is clicked, or Interact is pressed with it focused
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ButtonActivated(pub Entity);
+
+/// Asks for the settings screen, sent by the menus' Settings buttons
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct OpenSettings;
+
+/// Sent when the settings screen closes, for the menu that opened it to come back
+#[derive(Event, Debug, Default, Clone, Copy)]
+pub struct SettingsClosed;
+
+/// UiSounds, MenuButtons and ButtonFeedback, the menus, the settings screen, the dialogue box and
+/// its trees, the HUD, the interaction prompt, the results screen and the game over screen
+pub struct UiPlugin;
+
+impl Plugin for UiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UiSounds>()
+            .init_resource::<MenuFocus>()
+            .init_resource::<MenuNavigation>()
+            .init_resource::<FocusVisible>()
+            .add_event::<ButtonActivated>()
+            .add_event::<OpenSettings>()
+            .add_event::<SettingsClosed>()
+            .add_event::<PlaySFX>()
+            .add_plugins((
+                MainMenuPlugin,
+                PauseMenuPlugin,
+                SettingsMenuPlugin,
+                DialoguePlugin,
+                DialogueTreePlugin,
+                HudPlugin,
+                PromptPlugin,
+                ResultsPlugin,
+                GameOverPlugin,
+            ))
+            .add_systems(
+                Update,
+                (navigate_menus, (button_feedback, highlight_focus)).chain(),
+            );
+    }
+}
+
+/// Spawns a MenuButton with the default ButtonFeedback showing the text of `label` under `parent`
+pub fn spawn_menu_button(
+    parent: &mut ChildBuilder,
+    button: MenuButton,
+    label: &str,
+    bundle: impl Bundle,
+) -> Entity {
+    parent
+        .spawn((
+            button,
+            bundle,
+            ButtonFeedback::default(),
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(220.0),
+                    padding: UiRect::axes(Val::Px(16.0), Val::Px(10.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: BUTTON_COLOR.into(),
+                border_color: Color::NONE.into(),
+                ..default()
+            },
+        ))
+        .with_children(|button| {
+            button.spawn((
+                LocalizedText::new(label),
+                TextBundle::from_section(
+                    label.to_string(),
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+            ));
+        })
+        .id()
+}
+
+/// Despawns the menu whose root is marked with `M`, dropping the focus on its buttons
+pub fn despawn_menu<M: Component>(
+    mut commands: Commands,
+    root_query: Query<Entity, With<M>>,
+    mut focus: ResMut<MenuFocus>,
+) {
+    for entity in root_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    focus.0 = None;
+}
+
+///
+/// navigate_menus: Bevy system
+///
+/// Moves MenuFocus with the mouse and the movement actions, and sends ButtonActivated for clicks
+/// and Interact. Up and down go through a menu's buttons in order, or to a Focusable's
+/// neighbors; left and right only move from a Focusable. Past the ends focus wraps around if
+/// MenuNavigation::wrap is on. Focus on a disabled or removed button moves to the nearest one,
+/// and a menu opening without focus gets it on its first button.
+#[allow(clippy::type_complexity)]
+pub fn navigate_menus(
+    actions: Option<Res<ActionState>>,
+    navigation: Res<MenuNavigation>,
+    mut focus: ResMut<MenuFocus>,
+    mut visible: ResMut<FocusVisible>,
+    mut last_index: Local<Option<usize>>,
+    button_query: Query<(
+        Entity,
+        &MenuButton,
+        Ref<Interaction>,
+        Option<&Focusable>,
+        Option<&GlobalTransform>,
+    )>,
+    mut activations: EventWriter<ButtonActivated>,
+) {
+    let mut buttons: Vec<_> = button_query
+        .iter()
+        .filter(|(_, button, ..)| button.enabled)
+        .collect();
+    buttons.sort_by_key(|(_, button, ..)| button.index);
+    let mut focused = focus
+        .0
+        .filter(|focused| buttons.iter().any(|(entity, ..)| entity == focused));
+    let lost = focus.0.filter(|_| focused.is_none()).map(|lost| {
+        button_query
+            .get(lost)
+            .map_or(*last_index, |(_, button, ..)| Some(button.index))
+    });
+    match lost {
+        // a disabled or removed button hands focus to the nearest one, the one above on a tie
+        Some(Some(index)) => {
+            focused = buttons
+                .iter()
+                .min_by_key(|(_, button, ..)| (button.index.abs_diff(index), button.index))
+                .map(|(entity, ..)| *entity);
+        }
+        Some(None) => {}
+        None if focus.0.is_none() => {
+            focused = buttons
+                .iter()
+                .find(|(_, _, interaction, ..)| interaction.is_added())
+                .map(|(entity, ..)| *entity);
+        }
+        None => {}
+    }
+
+    let mut next = focused;
+    let mut activated = None;
+    for (entity, _, interaction, ..) in &buttons {
+        if !interaction.is_changed() || interaction.is_added() {
+            continue;
+        }
+        match **interaction {
+            Interaction::Hovered => next = Some(*entity),
+            Interaction::Pressed => {
+                next = Some(*entity);
+                activated = Some(*entity);
+            }
+            Interaction::None => continue,
+        }
+        visible.set_if_neq(FocusVisible(false));
+    }
+
+    if let Some(actions) = actions {
+        let direction = [
+            (Action::MoveUp, Vec2::NEG_Y),
+            (Action::MoveDown, Vec2::Y),
+            (Action::MoveLeft, Vec2::NEG_X),
+            (Action::MoveRight, Vec2::X),
+        ]
+        .iter()
+        .copied()
+        .find(|(action, _)| actions.just_pressed(*action))
+        .map(|(_, direction)| direction);
+        if let Some(direction) = direction.filter(|_| !buttons.is_empty()) {
+            let at = next.and_then(|next| buttons.iter().position(|(entity, ..)| *entity == next));
+            let position = |transform: Option<&GlobalTransform>| {
+                transform.map_or(Vec2::ZERO, |transform| transform.translation().truncate())
+            };
+            let moved = match at.map(|at| (at, &buttons[at])) {
+                Some((at, (_, _, _, Some(focusable), transform))) => focusable
+                    .neighbor(direction)
+                    .filter(|neighbor| buttons.iter().any(|(entity, ..)| entity == neighbor))
+                    .or_else(|| {
+                        let others = buttons
+                            .iter()
+                            .enumerate()
+                            .filter(|(index, _)| *index != at)
+                            .map(|(_, (entity, _, _, _, transform))| {
+                                (*entity, position(*transform))
+                            });
+                        nearest_in_direction(
+                            others,
+                            position(*transform),
+                            direction,
+                            navigation.wrap,
+                        )
+                    }),
+                // without a Focusable, through the buttons in order, leaving left and right to
+                // the button, like the settings' sliders
+                Some((at, _)) if direction.x == 0.0 => {
+                    let index = at as isize + direction.y as isize;
+                    let count = buttons.len() as isize;
+                    (navigation.wrap || (0..count).contains(&index))
+                        .then(|| buttons[index.rem_euclid(count) as usize].0)
+                }
+                Some(_) => None,
+                None if direction == Vec2::Y => Some(buttons[0].0),
+                None if direction == Vec2::NEG_Y => Some(buttons[buttons.len() - 1].0),
+                None => None,
+            };
+            if moved.is_some() {
+                next = moved;
+                visible.set_if_neq(FocusVisible(true));
+            }
+        }
+        if actions.just_pressed(Action::Interact) && activated.is_none() {
+            activated = next;
+        }
+    }
+
+    if focus.0 != next {
+        focus.0 = next;
+    }
+    *last_index = next
+        .and_then(|next| button_query.get(next).ok())
+        .map(|(_, button, ..)| button.index);
+    if let Some(button) = activated {
+        activations.send(ButtonActivated(button));
+    }
+}
+
+/// The nearest of `placed` from `from` going `direction`, favoring those straight ahead. With
+/// nothing that way and `wrap` on, the furthest the other way, as if coming round.
+fn nearest_in_direction(
+    placed: impl Iterator<Item = (Entity, Vec2)> + Clone,
+    from: Vec2,
+    direction: Vec2,
+    wrap: bool,
+) -> Option<Entity> {
+    let across = direction.perp();
+    let cost = |offset: Vec2| offset.dot(direction) + 2.0 * offset.dot(across).abs();
+    let ahead = placed
+        .clone()
+        .filter(|(_, position)| (*position - from).dot(direction) > 0.5)
+        .min_by(|(_, a), (_, b)| cost(*a - from).total_cmp(&cost(*b - from)))
+        .map(|(entity, _)| entity);
+    if ahead.is_some() || !wrap {
+        return ahead;
+    }
+    // coming round from the far side, the one furthest behind
+    placed
+        .filter(|(_, position)| (*position - from).dot(direction) < -0.5)
+        .min_by(|(_, a), (_, b)| cost(*a - from).total_cmp(&cost(*b - from)))
+        .map(|(entity, _)| entity)
+}
+
+///
+/// highlight_focus: Bevy system
+///
+/// Rings the focused MenuButton while FocusVisible is on, over ButtonFeedback's hover tint
+pub fn highlight_focus(
+    focus: Res<MenuFocus>,
+    visible: Res<FocusVisible>,
+    mut button_query: Query<(Entity, &mut BorderColor), With<MenuButton>>,
+) {
+    for (entity, mut border) in button_query.iter_mut() {
+        let color = if visible.0 && focus.0 == Some(entity) {
+            FOCUS_RING_COLOR
+        } else {
+            Color::NONE
+        };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! The pause menu, over the frozen world while in AppState::Paused. Pausing already stops
+//! virtual time and ducks the music (see AppStatePlugin), and resuming puts both back as they
+//! were, so the menu only has to change the state.
+
+use super::{
+    despawn_menu, navigate_menus, spawn_menu_button, ButtonActivated, MenuButton, MenuFocus,
+    OpenSettings, SettingsClosed,
+};
+use crate::helpers::tiled::{RestartMap, UnloadMap};
+use crate::locale::LocalizedText;
+use crate::state::{apply_state_changes, AppState, ChangeState};
+use bevy::prelude::*;
+
+/// How dark the world gets behind the menu
+const DIM: Color = Color::rgba(0.0, 0.0, 0.0, 0.55);
+
+/// A button of the pause menu
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseMenuButton {
+    Resume,
+    Settings,
+    RestartLevel,
+    QuitToMenu,
+}
+
+impl PauseMenuButton {
+    /// The buttons, top to bottom
+    pub const ALL: [PauseMenuButton; 4] = [
+        PauseMenuButton::Resume,
+        PauseMenuButton::Settings,
+        PauseMenuButton::RestartLevel,
+        PauseMenuButton::QuitToMenu,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PauseMenuButton::Resume => "pause.resume",
+            PauseMenuButton::Settings => "menu.settings",
+            PauseMenuButton::RestartLevel => "pause.restart",
+            PauseMenuButton::QuitToMenu => "pause.quit_to_menu",
+        }
+    }
+}
+
+/// Marks the pause menu's root node, which dims the world behind it
+#[derive(Component, Debug)]
+pub struct PauseMenuRoot;
+
+/// The pause menu, spawned on entering Paused and despawned on leaving it. It goes away while
+/// the settings are open and comes back on SettingsClosed.
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ChangeState>()
+            .add_event::<RestartMap>()
+            .add_event::<UnloadMap>()
+            .add_systems(OnEnter(AppState::Paused), open_pause_menu)
+            .add_systems(OnExit(AppState::Paused), despawn_menu::<PauseMenuRoot>)
+            .add_systems(
+                Update,
+                (
+                    run_pause_menu,
+                    reopen_pause_menu.run_if(on_event::<SettingsClosed>()),
+                )
+                    .chain()
+                    .after(navigate_menus)
+                    // so the change of state is entered on the next frame
+                    .before(apply_state_changes)
+                    .run_if(in_state(AppState::Paused)),
+            );
+    }
+}
+
+/// Spawns the pause menu with `focused` focused
+pub fn spawn_pause_menu(commands: &mut Commands, focus: &mut MenuFocus, focused: PauseMenuButton) {
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(12.0),
+            ..default()
+        },
+        background_color: DIM.into(),
+        // under the toasts
+        z_index: ZIndex::Global(50),
+        ..default()
+    };
+    commands.spawn((PauseMenuRoot, root)).with_children(|menu| {
+        menu.spawn((
+            LocalizedText::new("pause.title"),
+            TextBundle::from_section(
+                "pause.title",
+                TextStyle {
+                    font_size: 36.0,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                margin: UiRect::bottom(Val::Px(16.0)),
+                ..default()
+            }),
+        ));
+        for (index, button) in PauseMenuButton::ALL.iter().enumerate() {
+            let menu_button = MenuButton {
+                index,
+                enabled: true,
+            };
+            let entity = spawn_menu_button(menu, menu_button, button.label(), *button);
+            if *button == focused {
+                focus.0 = Some(entity);
+            }
+        }
+    });
+}
+
+fn open_pause_menu(mut commands: Commands, mut focus: ResMut<MenuFocus>) {
+    spawn_pause_menu(&mut commands, &mut focus, PauseMenuButton::Resume);
+}
+
+///
+/// run_pause_menu: Bevy system
+///
+/// Does what the activated button says. Restarting and quitting leave Paused as well, which
+/// resumes virtual time and the music first.
+#[allow(clippy::too_many_arguments)]
+pub fn run_pause_menu(
+    mut commands: Commands,
+    mut activations: EventReader<ButtonActivated>,
+    button_query: Query<&PauseMenuButton>,
+    root_query: Query<Entity, With<PauseMenuRoot>>,
+    mut change_state: EventWriter<ChangeState>,
+    mut open_settings: EventWriter<OpenSettings>,
+    mut restart: EventWriter<RestartMap>,
+    mut unload: EventWriter<UnloadMap>,
+) {
+    for ButtonActivated(entity) in activations.read() {
+        let Ok(button) = button_query.get(*entity) else {
+            continue;
+        };
+        match button {
+            PauseMenuButton::Resume => {
+                change_state.send(ChangeState(AppState::InGame));
+            }
+            PauseMenuButton::Settings => {
+                for root in root_query.iter() {
+                    commands.entity(root).despawn_recursive();
+                }
+                open_settings.send(OpenSettings);
+            }
+            // faded out and back in on the map's default spawn point
+            PauseMenuButton::RestartLevel => {
+                change_state.send(ChangeState(AppState::InGame));
+                restart.send(RestartMap);
+            }
+            // start_game loads the first map again on Play
+            PauseMenuButton::QuitToMenu => {
+                change_state.send(ChangeState(AppState::MainMenu));
+                unload.send(UnloadMap::default());
+            }
+        }
+    }
+}
+
+/// Brings the pause menu back when the settings it opened close
+fn reopen_pause_menu(
+    mut commands: Commands,
+    mut focus: ResMut<MenuFocus>,
+    root_query: Query<(), With<PauseMenuRoot>>,
+) {
+    if root_query.is_empty() {
+        spawn_pause_menu(&mut commands, &mut focus, PauseMenuButton::Settings);
+    }
+}
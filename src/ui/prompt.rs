@@ -0,0 +1,231 @@
+//! The interaction prompt: standing within reach of an Interactable, like a door, an NPC or a
+//! sign, puts "[E] Talk" at the bottom of the screen, with the glyph of the device the player
+//! used last. Pressing Interact while it's up sends Interacted for gameplay to handle.
+//!
+//! Only the nearest Interactable gets a prompt. Another has to be PROMPT_HYSTERESIS closer to
+//! take it over, so standing halfway between two doesn't flicker between them. There's no prompt
+//! while the dialogue box is open or gameplay doesn't get the actions.
+//!
+//! The player is the PlacedAtSpawn entity, as for doors.
+
+use super::Dialogue;
+use crate::helpers::tiled::PlacedAtSpawn;
+use crate::input::{Action, ActionState, ActiveDevice, GameplayInput, InputMap};
+use crate::locale::Locale;
+use crate::state::AppState;
+use bevy::prelude::*;
+
+/// How much closer than the prompted Interactable another has to be to take the prompt over
+pub const PROMPT_HYSTERESIS: f32 = 8.0;
+
+/// Can be interacted with from within `radius` of it, prompting with the text of `prompt_key`,
+/// e.g. "prompt.talk"
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Interactable {
+    pub prompt_key: String,
+    pub radius: f32,
+}
+
+/// Sent when Interact is pressed with the prompt up for `entity`
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interacted {
+    pub entity: Entity,
+}
+
+///
+/// InteractionPrompt
+///
+/// The Interactable prompted for, if any
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InteractionPrompt(pub Option<Entity>);
+
+/// Marks the prompt's text
+#[derive(Component, Debug)]
+pub struct PromptText;
+
+/// Interactable, Interacted and the prompt
+pub struct PromptPlugin;
+
+impl Plugin for PromptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InteractionPrompt>()
+            .init_resource::<Dialogue>()
+            .init_resource::<GameplayInput>()
+            .add_event::<Interacted>()
+            .add_systems(OnEnter(AppState::MainMenu), despawn_prompt)
+            .add_systems(
+                Update,
+                (
+                    spawn_prompt.run_if(not(any_with_component::<PromptText>)),
+                    // on the prompt that was up, so Interact closing the dialogue box doesn't
+                    // also start talking again
+                    interact,
+                    choose_prompt,
+                    show_prompt,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::InGame)),
+            );
+    }
+}
+
+/// Spawns the prompt, hidden
+fn spawn_prompt(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(48.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            // with the HUD
+            z_index: ZIndex::Global(30),
+            ..default()
+        })
+        .with_children(|root| {
+            root.spawn((
+                PromptText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                )
+                .with_background_color(Color::rgba(0.0, 0.0, 0.0, 0.6))
+                .with_style(Style {
+                    padding: UiRect::axes(Val::Px(10.0), Val::Px(4.0)),
+                    ..default()
+                }),
+            ));
+        });
+}
+
+/// Despawns the prompt and forgets what it was for
+fn despawn_prompt(
+    mut commands: Commands,
+    mut prompt: ResMut<InteractionPrompt>,
+    text_query: Query<&Parent, With<PromptText>>,
+) {
+    for parent in text_query.iter() {
+        commands.entity(parent.get()).despawn_recursive();
+    }
+    prompt.0 = None;
+}
+
+///
+/// interact: Bevy system
+///
+/// Sends Interacted for the prompted Interactable on Interact
+pub fn interact(
+    actions: Option<Res<ActionState>>,
+    prompt: Res<InteractionPrompt>,
+    mut interacted: EventWriter<Interacted>,
+) {
+    let pressed = actions.is_some_and(|actions| actions.just_pressed(Action::Interact));
+    if let Some(entity) = prompt.0.filter(|_| pressed) {
+        interacted.send(Interacted { entity });
+    }
+}
+
+///
+/// choose_prompt: Bevy system
+///
+/// Prompts for the nearest Interactable in reach of the player, keeping the one prompted for
+/// unless another is PROMPT_HYSTERESIS closer
+pub fn choose_prompt(
+    dialogue: Res<Dialogue>,
+    input: Res<GameplayInput>,
+    player_query: Query<&GlobalTransform, With<PlacedAtSpawn>>,
+    interactable_query: Query<(Entity, &Interactable, &GlobalTransform)>,
+    mut prompt: ResMut<InteractionPrompt>,
+) {
+    let player = player_query
+        .get_single()
+        .ok()
+        .filter(|_| input.0 && !dialogue.is_open())
+        .map(|transform| transform.translation().truncate());
+    let in_reach: Vec<(Entity, f32)> = player
+        .map(|player| {
+            interactable_query
+                .iter()
+                .map(|(entity, interactable, transform)| {
+                    let distance = transform.translation().truncate().distance(player);
+                    (entity, distance, interactable.radius)
+                })
+                .filter(|(_, distance, radius)| distance <= radius)
+                .map(|(entity, distance, _)| (entity, distance))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let nearest = in_reach
+        .iter()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .copied();
+    let current = prompt
+        .0
+        .and_then(|current| in_reach.iter().find(|(entity, _)| *entity == current))
+        .copied();
+    let chosen = match (current, nearest) {
+        (Some((current, distance)), Some((_, nearest)))
+            if nearest + PROMPT_HYSTERESIS > distance =>
+        {
+            Some(current)
+        }
+        _ => nearest.map(|(entity, _)| entity),
+    };
+    if prompt.0 != chosen {
+        prompt.0 = chosen;
+    }
+}
+
+///
+/// show_prompt: Bevy system
+///
+/// Shows the prompt for the prompted Interactable, with the glyph of Interact on the active
+/// device, and hides it when there's none
+pub fn show_prompt(
+    prompt: Res<InteractionPrompt>,
+    locale: Option<Res<Locale>>,
+    map: Option<Res<InputMap>>,
+    device: Option<Res<ActiveDevice>>,
+    interactable_query: Query<&Interactable>,
+    mut text_query: Query<(&mut Text, &mut Visibility), With<PromptText>>,
+) {
+    let interactable = prompt
+        .0
+        .and_then(|entity| interactable_query.get(entity).ok());
+    for (mut text, mut visibility) in text_query.iter_mut() {
+        let Some(interactable) = interactable else {
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+            continue;
+        };
+        let glyphs = device.as_deref().copied().unwrap_or_default().glyphs();
+        let button = map
+            .as_ref()
+            .and_then(|map| map.glyph(Action::Interact, glyphs))
+            .unwrap_or_default();
+        let label = match locale.as_deref() {
+            Some(locale) => locale.format(
+                "prompt.press",
+                &[
+                    ("button", button),
+                    ("action", locale.text(&interactable.prompt_key)),
+                ],
+            ),
+            None => format!("[{}] {}", button, interactable.prompt_key),
+        };
+        if text.sections[0].value != label {
+            text.sections[0].value = label;
+        }
+        if *visibility != Visibility::Inherited {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! The results screen, over the world once the level is complete: the run's score, time and
+//! pickups from RunStats, its rank by the map's thresholds, and the map's personal bests from
+//! BestResults, with the records the run just set called out. From there the level can be played
+//! again or left for the main menu, or, at an exit with a next_map, the next level played.
+
+use super::{
+    despawn_menu, navigate_menus, spawn_menu_button, ButtonActivated, MenuButton, MenuFocus,
+};
+use crate::bridge::LevelComplete;
+use crate::exits::ReachedExit;
+use crate::helpers::tiled::{ChangeMap, MapProperties, RestartMap, UnloadMap};
+use crate::locale::LocalizedText;
+use crate::run_stats::{finish_runs, format_time, rank, BestResults, NewRecord, Record, RunStats};
+use crate::state::{AppState, ChangeState};
+use bevy::prelude::*;
+
+/// How dark the world gets behind the results
+const DIM: Color = Color::rgba(0.0, 0.0, 0.0, 0.65);
+const RECORD_COLOR: Color = Color::rgb(1.0, 0.8, 0.2);
+
+/// A button of the results screen
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultsButton {
+    NextLevel,
+    RestartLevel,
+    QuitToMenu,
+}
+
+impl ResultsButton {
+    /// The buttons, top to bottom; NextLevel is only there when the exit reached has a next map
+    pub const ALL: [ResultsButton; 3] = [
+        ResultsButton::NextLevel,
+        ResultsButton::RestartLevel,
+        ResultsButton::QuitToMenu,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            ResultsButton::NextLevel => "results.next_level",
+            ResultsButton::RestartLevel => "pause.restart",
+            ResultsButton::QuitToMenu => "pause.quit_to_menu",
+        }
+    }
+}
+
+/// Marks the results screen's root node
+#[derive(Component, Debug)]
+pub struct ResultsRoot;
+
+/// The results screen, spawned on LevelComplete once the run is finished and despawned by its
+/// buttons, on restarting the level or on going back to the main menu
+pub struct ResultsPlugin;
+
+impl Plugin for ResultsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelComplete>()
+            .add_event::<NewRecord>()
+            .add_event::<ChangeState>()
+            .add_event::<RestartMap>()
+            .add_event::<ChangeMap>()
+            .add_event::<UnloadMap>()
+            .add_systems(OnEnter(AppState::MainMenu), despawn_menu::<ResultsRoot>)
+            .add_systems(
+                Update,
+                (
+                    despawn_menu::<ResultsRoot>.run_if(on_event::<RestartMap>()),
+                    open_results
+                        .after(finish_runs)
+                        .run_if(on_event::<LevelComplete>()),
+                    run_results
+                        .after(navigate_menus)
+                        .run_if(any_with_component::<ResultsRoot>),
+                ),
+            );
+    }
+}
+
+///
+/// open_results: Bevy system
+///
+/// Spawns the results screen for the finished run
+#[allow(clippy::too_many_arguments)]
+pub fn open_results(
+    mut commands: Commands,
+    mut focus: ResMut<MenuFocus>,
+    stats: Option<Res<RunStats>>,
+    bests: Option<Res<BestResults>>,
+    properties: Option<Res<MapProperties>>,
+    reached: Option<Res<ReachedExit>>,
+    mut records: EventReader<NewRecord>,
+    root_query: Query<(), With<ResultsRoot>>,
+) {
+    let records: Vec<Record> = records.read().map(|event| event.record).collect();
+    if !root_query.is_empty() {
+        return;
+    }
+    let next_level = reached
+        .as_ref()
+        .and_then(|reached| reached.0.as_ref())
+        .is_some_and(|exit| exit.next_map.is_some());
+    let stats = stats.as_deref().cloned().unwrap_or_default();
+    let best = bests.map(|bests| bests.get(&stats.map)).unwrap_or_default();
+    let rank = properties.and_then(|properties| rank(&properties, stats.score));
+    let new_time = records
+        .iter()
+        .any(|record| matches!(record, Record::Time(_)));
+    let new_score = records
+        .iter()
+        .any(|record| matches!(record, Record::Score(_)));
+
+    let mut lines = vec![
+        (
+            LocalizedText::new("results.score").with_arg("score", stats.score),
+            new_score,
+        ),
+        (
+            LocalizedText::new("results.time").with_arg("time", format_time(stats.time)),
+            new_time,
+        ),
+        (
+            LocalizedText::new("results.collected").with_arg("count", stats.collected),
+            false,
+        ),
+    ];
+    if let Some(rank) = rank {
+        lines.push((
+            LocalizedText::new("results.rank").with_arg("rank", rank),
+            false,
+        ));
+    }
+    if let (Some(time), Some(score)) = (best.time, best.score) {
+        lines.push((
+            LocalizedText::new("results.best")
+                .with_arg("time", format_time(time))
+                .with_arg("score", score),
+            false,
+        ));
+    }
+
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            row_gap: Val::Px(10.0),
+            ..default()
+        },
+        background_color: DIM.into(),
+        // over the HUD, under the toasts
+        z_index: ZIndex::Global(50),
+        ..default()
+    };
+    let text = |label: &LocalizedText, size: f32, color: Color| {
+        TextBundle::from_section(
+            label.key.clone(),
+            TextStyle {
+                font_size: size,
+                color,
+                ..default()
+            },
+        )
+    };
+    commands.spawn((ResultsRoot, root)).with_children(|screen| {
+        let title = LocalizedText::new("results.title");
+        screen.spawn((
+            text(&title, 36.0, Color::WHITE).with_style(Style {
+                margin: UiRect::bottom(Val::Px(12.0)),
+                ..default()
+            }),
+            title,
+        ));
+        for (line, record) in lines {
+            let color = if record { RECORD_COLOR } else { Color::WHITE };
+            screen.spawn((text(&line, 22.0, color), line));
+        }
+        if !records.is_empty() {
+            let record = LocalizedText::new("results.new_record");
+            screen.spawn((text(&record, 22.0, RECORD_COLOR), record));
+        }
+        screen
+            .spawn(NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(12.0),
+                    margin: UiRect::top(Val::Px(16.0)),
+                    ..default()
+                },
+                ..default()
+            })
+            .with_children(|buttons| {
+                let buttons_shown = ResultsButton::ALL
+                    .iter()
+                    .filter(|button| next_level || **button != ResultsButton::NextLevel);
+                for (index, button) in buttons_shown.enumerate() {
+                    let menu_button = MenuButton {
+                        index,
+                        enabled: true,
+                    };
+                    let entity = spawn_menu_button(buttons, menu_button, button.label(), *button);
+                    if index == 0 {
+                        focus.0 = Some(entity);
+                    }
+                }
+            });
+    });
+}
+
+///
+/// run_results: Bevy system
+///
+/// Does what the activated button says, closing the results screen
+#[allow(clippy::too_many_arguments)]
+pub fn run_results(
+    mut commands: Commands,
+    mut activations: EventReader<ButtonActivated>,
+    button_query: Query<&ResultsButton>,
+    root_query: Query<Entity, With<ResultsRoot>>,
+    reached: Option<Res<ReachedExit>>,
+    mut change_state: EventWriter<ChangeState>,
+    mut restart: EventWriter<RestartMap>,
+    mut change_map: EventWriter<ChangeMap>,
+    mut unload: EventWriter<UnloadMap>,
+) {
+    for ButtonActivated(entity) in activations.read() {
+        let Ok(button) = button_query.get(*entity) else {
+            continue;
+        };
+        for root in root_query.iter() {
+            commands.entity(root).despawn_recursive();
+        }
+        match button {
+            // and RunStats start over on arriving
+            ResultsButton::NextLevel => {
+                let next = reached
+                    .as_ref()
+                    .and_then(|reached| reached.0.as_ref())
+                    .and_then(|exit| exit.next_level());
+                if let Some(next) = next {
+                    change_map.send(next);
+                }
+            }
+            // and RunStats start over
+            ResultsButton::RestartLevel => {
+                restart.send(RestartMap);
+            }
+            ResultsButton::QuitToMenu => {
+                change_state.send(ChangeState(AppState::MainMenu));
+                unload.send(UnloadMap::default());
+            }
+        }
+    }
+}
@@ -0,0 +1,528 @@
+//! The settings screen, opened by OpenSettings from the main or the pause menu and closed with
+//! Back or the Pause action, after which SettingsClosed brings the menu back. Changes apply as
+//! they're made: volumes go through SetVolume, so the playing music follows the slider, and the
+//! rest is written to Settings, whose SettingsChanged the modules pick it up from. Settings then
+//! saves itself once the changes settle.
+//!
+//! Sliders and choices change with Action::MoveLeft and MoveRight while focused, and clicking
+//! them: a slider's bar takes the value under the cursor, a choice moves to the next one.
+
+use super::{
+    navigate_menus, ButtonActivated, ButtonFeedback, MenuButton, MenuFocus, OpenSettings,
+    SettingsClosed, BUTTON_COLOR,
+};
+use crate::fullscreen::{Fullscreen, ToggleFullscreen};
+use crate::gfx::{ColorFilter, Quality};
+use crate::input::{Action, ActionState, InputMap, Rebinding, ResolveRebind, StartRebind};
+use crate::locale::{Locale, LocalizedText};
+use crate::settings::Settings;
+use crate::sound::{AudioChannel, AudioChannels, SetVolume};
+use crate::state::{pause_on_key, PauseOnKey};
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+
+/// How much a slider moves for each press of MoveLeft or MoveRight
+pub const VOLUME_STEP: f32 = 0.1;
+
+/// Segments of a slider's bar
+const SLIDER_SEGMENTS: usize = 10;
+
+const QUALITIES: [Quality; 3] = [Quality::Low, Quality::Medium, Quality::High];
+const COLOR_FILTERS: [ColorFilter; 4] = [
+    ColorFilter::None,
+    ColorFilter::Protanopia,
+    ColorFilter::Deuteranopia,
+    ColorFilter::Tritanopia,
+];
+const CHANNELS: [AudioChannel; 3] = [
+    AudioChannel::Music,
+    AudioChannel::Sfx,
+    AudioChannel::Ambient,
+];
+
+/// A row of the settings screen, on its MenuButton. The value it shows is on a child marked with
+/// SettingsValue.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsWidget {
+    Volume(AudioChannel),
+    Fullscreen,
+    Quality,
+    ColorFilter,
+    Captions,
+    /// rebinds the first key of the action
+    Binding(Action),
+    /// answers a RebindConflict, only there while one is waiting
+    ResolveConflict(ResolveRebind),
+    Reset,
+    Back,
+}
+
+impl SettingsWidget {
+    fn label(self) -> &'static str {
+        match self {
+            SettingsWidget::Volume(AudioChannel::Music) => "settings.music",
+            SettingsWidget::Volume(AudioChannel::Sfx) => "settings.sfx",
+            SettingsWidget::Volume(AudioChannel::Ambient) => "settings.ambient",
+            SettingsWidget::Fullscreen => "settings.fullscreen",
+            SettingsWidget::Quality => "settings.quality",
+            SettingsWidget::ColorFilter => "settings.color_filter",
+            SettingsWidget::Captions => "settings.captions",
+            SettingsWidget::Binding(action) => action.label(),
+            SettingsWidget::ResolveConflict(ResolveRebind::Swap) => "settings.swap",
+            SettingsWidget::ResolveConflict(ResolveRebind::Take) => "settings.take",
+            SettingsWidget::ResolveConflict(ResolveRebind::Cancel) => "settings.cancel",
+            SettingsWidget::Reset => "settings.reset",
+            SettingsWidget::Back => "settings.back",
+        }
+    }
+}
+
+/// Marks the text showing a SettingsWidget's value
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SettingsValue(pub SettingsWidget);
+
+/// Marks the settings screen's root node
+#[derive(Component, Debug)]
+pub struct SettingsRoot;
+
+/// Marks the line saying which actions a captured binding is bound to already
+#[derive(Component, Debug)]
+pub struct ConflictText;
+
+/// The settings screen
+pub struct SettingsMenuPlugin;
+
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SetVolume>()
+            .add_event::<ToggleFullscreen>()
+            .add_event::<StartRebind>()
+            .add_event::<ResolveRebind>()
+            .add_systems(
+                Update,
+                (
+                    // after navigate_menus, which drops focus from rows it hasn't seen spawned yet
+                    open_settings_menu
+                        .after(navigate_menus)
+                        .run_if(on_event::<OpenSettings>()),
+                    // after pause_on_key, so closing with Pause doesn't also unpause
+                    (change_settings, show_settings)
+                        .chain()
+                        .after(navigate_menus)
+                        .after(pause_on_key)
+                        .run_if(any_with_component::<SettingsRoot>),
+                ),
+            );
+    }
+}
+
+///
+/// open_settings_menu: Bevy system
+///
+/// Spawns the settings screen, focused on its first row, and turns PauseOnKey off so the Pause
+/// action backs out of it instead
+pub fn open_settings_menu(
+    mut commands: Commands,
+    root_query: Query<(), With<SettingsRoot>>,
+    mut focus: ResMut<MenuFocus>,
+    pause_setting: Option<ResMut<PauseOnKey>>,
+) {
+    if !root_query.is_empty() {
+        return;
+    }
+    if let Some(mut pause_setting) = pause_setting {
+        pause_setting.0 = false;
+    }
+
+    let root = NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(32.0),
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.75).into(),
+        // over the pause menu, under the toasts
+        z_index: ZIndex::Global(60),
+        ..default()
+    };
+    let column = || NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(6.0),
+            ..default()
+        },
+        ..default()
+    };
+
+    let mut general: Vec<SettingsWidget> = CHANNELS
+        .iter()
+        .copied()
+        .map(SettingsWidget::Volume)
+        .collect();
+    general.extend(vec![
+        SettingsWidget::Fullscreen,
+        SettingsWidget::Quality,
+        SettingsWidget::ColorFilter,
+        SettingsWidget::Captions,
+        SettingsWidget::Reset,
+        SettingsWidget::Back,
+    ]);
+    let bindings: Vec<SettingsWidget> = Action::ALL
+        .iter()
+        .copied()
+        .map(SettingsWidget::Binding)
+        .collect();
+    let resolutions = [
+        ResolveRebind::Swap,
+        ResolveRebind::Take,
+        ResolveRebind::Cancel,
+    ];
+
+    commands
+        .spawn((SettingsRoot, root))
+        .with_children(|screen| {
+            screen.spawn(column()).with_children(|rows| {
+                for (index, widget) in general.iter().enumerate() {
+                    let entity = spawn_widget(rows, index, *widget);
+                    if index == 0 {
+                        focus.0 = Some(entity);
+                    }
+                }
+            });
+            screen.spawn(column()).with_children(|rows| {
+                for (index, widget) in bindings.iter().enumerate() {
+                    spawn_widget(rows, general.len() + index, *widget);
+                }
+                rows.spawn((
+                    ConflictText,
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: 16.0,
+                            color: Color::rgb(1.0, 0.8, 0.4),
+                            ..default()
+                        },
+                    ),
+                ));
+                for (index, resolution) in resolutions.iter().enumerate() {
+                    let index = general.len() + bindings.len() + index;
+                    spawn_widget(rows, index, SettingsWidget::ResolveConflict(*resolution));
+                }
+            });
+        });
+}
+
+/// A row of the settings screen: its label on the left, its value on the right
+fn spawn_widget(parent: &mut ChildBuilder, index: usize, widget: SettingsWidget) -> Entity {
+    let text = |text: &str| {
+        TextBundle::from_section(
+            text.to_string(),
+            TextStyle {
+                font_size: 18.0,
+                color: Color::WHITE,
+                ..default()
+            },
+        )
+    };
+    let enabled = !matches!(widget, SettingsWidget::ResolveConflict(_));
+    parent
+        .spawn((
+            MenuButton { index, enabled },
+            widget,
+            ButtonFeedback::default(),
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(360.0),
+                    padding: UiRect::axes(Val::Px(12.0), Val::Px(6.0)),
+                    border: UiRect::all(Val::Px(2.0)),
+                    justify_content: JustifyContent::SpaceBetween,
+                    display: if enabled {
+                        Display::Flex
+                    } else {
+                        Display::None
+                    },
+                    ..default()
+                },
+                background_color: BUTTON_COLOR.into(),
+                border_color: Color::NONE.into(),
+                ..default()
+            },
+        ))
+        .with_children(|row| {
+            match widget {
+                // action names aren't translated
+                SettingsWidget::Binding(action) => row.spawn(text(action.label())),
+                _ => row.spawn((LocalizedText::new(widget.label()), text(widget.label()))),
+            };
+            row.spawn((SettingsValue(widget), text("")));
+        })
+        .id()
+}
+
+/// The entry after (`step` 1) or before (-1) `current` in `all`, wrapping around
+fn cycle<T: Copy + PartialEq>(all: &[T], current: T, step: isize) -> T {
+    let at = all.iter().position(|item| *item == current).unwrap_or(0);
+    all[(at as isize + step).rem_euclid(all.len() as isize) as usize]
+}
+
+///
+/// change_settings: Bevy system
+///
+/// Applies activated rows, MoveLeft and MoveRight on the focused one and clicks on a slider's
+/// bar, and closes the screen on Back or the Pause action
+#[allow(clippy::too_many_arguments)]
+pub fn change_settings(
+    mut commands: Commands,
+    mut activations: EventReader<ButtonActivated>,
+    actions: Option<Res<ActionState>>,
+    focus: Res<MenuFocus>,
+    widget_query: Query<(&SettingsWidget, &Interaction)>,
+    value_query: Query<(&SettingsValue, &Node, &GlobalTransform)>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    root_query: Query<Entity, With<SettingsRoot>>,
+    rebinding: Option<Res<Rebinding>>,
+    channels: Option<Res<AudioChannels>>,
+    mut settings: ResMut<Settings>,
+    mut pause_setting: Option<ResMut<PauseOnKey>>,
+    mut events: SettingsEvents,
+) {
+    let volume = |channel| {
+        channels
+            .as_ref()
+            .map_or(1.0, |channels| channels.get(channel))
+    };
+    let mut changes: Vec<(SettingsWidget, isize)> = activations
+        .read()
+        .filter_map(|ButtonActivated(entity)| widget_query.get(*entity).ok())
+        .map(|(widget, _)| (*widget, 1))
+        .collect();
+
+    if let Some(actions) = actions.as_ref() {
+        let step = actions.just_pressed(Action::MoveRight) as isize
+            - actions.just_pressed(Action::MoveLeft) as isize;
+        let focused = focus.0.and_then(|entity| widget_query.get(entity).ok());
+        if let Some((widget, _)) = focused.filter(|_| step != 0) {
+            if !matches!(
+                widget,
+                SettingsWidget::Binding(_) | SettingsWidget::ResolveConflict(_)
+            ) {
+                changes.push((*widget, step));
+            }
+        }
+        if actions.just_pressed(Action::Pause) {
+            changes.push((SettingsWidget::Back, 1));
+        }
+    }
+
+    // held down on a slider's bar: the value under the cursor
+    let cursor = window_query
+        .get_single()
+        .ok()
+        .and_then(|window| window.cursor_position());
+    for (widget, interaction) in widget_query.iter() {
+        let SettingsWidget::Volume(channel) = *widget else {
+            continue;
+        };
+        let Some(cursor) = cursor.filter(|_| *interaction == Interaction::Pressed) else {
+            continue;
+        };
+        let bar = value_query
+            .iter()
+            .find(|(value, ..)| value.0 == *widget)
+            .map(|(_, node, transform)| node.logical_rect(transform));
+        if let Some(bar) = bar.filter(|bar| bar.width() > 0.0 && bar.contains(cursor)) {
+            let level = ((cursor.x - bar.min.x) / bar.width() * SLIDER_SEGMENTS as f32).ceil()
+                / SLIDER_SEGMENTS as f32;
+            changes.retain(|(changed, _)| changed != widget);
+            if level != volume(channel) {
+                events.volume.send(SetVolume {
+                    channel,
+                    volume: level,
+                });
+            }
+        }
+    }
+
+    for (widget, step) in changes {
+        match widget {
+            SettingsWidget::Volume(channel) => {
+                let level = (volume(channel) + step as f32 * VOLUME_STEP).clamp(0.0, 1.0);
+                // round off the steps' float error
+                let level = (level * 100.0).round() / 100.0;
+                events.volume.send(SetVolume {
+                    channel,
+                    volume: level,
+                });
+            }
+            SettingsWidget::Fullscreen => {
+                events.fullscreen.send(ToggleFullscreen);
+            }
+            SettingsWidget::Quality => {
+                settings.graphics.quality = cycle(&QUALITIES, settings.graphics.quality, step);
+            }
+            SettingsWidget::ColorFilter => {
+                let filter = settings.graphics.color_filter;
+                settings.graphics.color_filter = cycle(&COLOR_FILTERS, filter, step);
+            }
+            SettingsWidget::Captions => settings.captions = !settings.captions,
+            SettingsWidget::Binding(action) => {
+                events.rebind.send(StartRebind { action, slot: 0 });
+            }
+            SettingsWidget::ResolveConflict(resolution) => {
+                events.resolve.send(resolution);
+            }
+            SettingsWidget::Reset => settings.reset_to_defaults(),
+            SettingsWidget::Back => {
+                // the Pause action backs out of a rebind first
+                if rebinding
+                    .as_ref()
+                    .is_some_and(|rebinding| rebinding.is_active())
+                {
+                    events.resolve.send(ResolveRebind::Cancel);
+                    continue;
+                }
+                for root in root_query.iter() {
+                    commands.entity(root).despawn_recursive();
+                }
+                if let Some(pause_setting) = pause_setting.as_mut() {
+                    pause_setting.0 = true;
+                }
+                events.closed.send(SettingsClosed);
+                return;
+            }
+        }
+    }
+}
+
+/// The events change_settings sends
+#[derive(SystemParam)]
+pub struct SettingsEvents<'w> {
+    volume: EventWriter<'w, SetVolume>,
+    fullscreen: EventWriter<'w, ToggleFullscreen>,
+    rebind: EventWriter<'w, StartRebind>,
+    resolve: EventWriter<'w, ResolveRebind>,
+    closed: EventWriter<'w, SettingsClosed>,
+}
+
+///
+/// show_settings: Bevy system
+///
+/// Keeps the values shown up to date, and the conflict's line and buttons there only while a
+/// rebind waits on one, focused when it comes up
+#[allow(clippy::too_many_arguments)]
+pub fn show_settings(
+    settings: Res<Settings>,
+    channels: Option<Res<AudioChannels>>,
+    fullscreen: Option<Res<Fullscreen>>,
+    map: Option<Res<InputMap>>,
+    rebinding: Option<Res<Rebinding>>,
+    locale: Option<Res<Locale>>,
+    mut value_query: Query<(&SettingsValue, &mut Text)>,
+    mut conflict_query: Query<&mut Text, (With<ConflictText>, Without<SettingsValue>)>,
+    mut widget_query: Query<(Entity, &SettingsWidget, &mut MenuButton, &mut Style)>,
+    mut focus: ResMut<MenuFocus>,
+) {
+    let text = |key: &str| {
+        locale
+            .as_ref()
+            .map_or_else(|| key.to_string(), |locale| locale.text(key))
+    };
+    let on_off = |on: bool| text(if on { "settings.on" } else { "settings.off" });
+    let capturing = match rebinding.as_deref() {
+        Some(Rebinding::Capturing { action, .. }) => Some(*action),
+        _ => None,
+    };
+    let conflict = match rebinding.as_deref() {
+        Some(Rebinding::Conflict {
+            action, binding, ..
+        }) => map.as_ref().map(|map| {
+            let others: Vec<&str> = map
+                .conflicts(*action, *binding)
+                .into_iter()
+                .map(Action::label)
+                .collect();
+            format!("{} → {}", binding.label(), others.join(", "))
+        }),
+        _ => None,
+    };
+
+    for (SettingsValue(widget), mut shown) in value_query.iter_mut() {
+        let value = match widget {
+            SettingsWidget::Volume(channel) => {
+                let level = channels
+                    .as_ref()
+                    .map_or(1.0, |channels| channels.get(*channel));
+                let filled = (level * SLIDER_SEGMENTS as f32).round() as usize;
+                format!(
+                    "{}{} {:>3}%",
+                    "■".repeat(filled),
+                    "□".repeat(SLIDER_SEGMENTS - filled),
+                    (level * 100.0).round()
+                )
+            }
+            SettingsWidget::Fullscreen => on_off(
+                fullscreen
+                    .as_ref()
+                    .is_some_and(|fullscreen| fullscreen.is_active()),
+            ),
+            SettingsWidget::Quality => text(match settings.graphics.quality {
+                Quality::Low => "settings.quality.low",
+                Quality::Medium => "settings.quality.medium",
+                Quality::High => "settings.quality.high",
+            }),
+            SettingsWidget::ColorFilter => text(match settings.graphics.color_filter {
+                ColorFilter::None => "settings.color_filter.none",
+                ColorFilter::Protanopia => "settings.color_filter.protanopia",
+                ColorFilter::Deuteranopia => "settings.color_filter.deuteranopia",
+                ColorFilter::Tritanopia => "settings.color_filter.tritanopia",
+            }),
+            SettingsWidget::Captions => on_off(settings.captions),
+            SettingsWidget::Binding(action) if capturing == Some(*action) => {
+                text("settings.press_key")
+            }
+            SettingsWidget::Binding(action) => map
+                .as_ref()
+                .map(|map| {
+                    let labels: Vec<String> = map
+                        .bindings(*action)
+                        .into_iter()
+                        .map(|binding| binding.label())
+                        .collect();
+                    labels.join(", ")
+                })
+                .unwrap_or_default(),
+            _ => String::new(),
+        };
+        if let Some(section) = shown.sections.first_mut() {
+            if section.value != value {
+                section.value = value;
+            }
+        }
+    }
+
+    for mut shown in conflict_query.iter_mut() {
+        let value = conflict.clone().unwrap_or_default();
+        if let Some(section) = shown.sections.first_mut() {
+            if section.value != value {
+                section.value = value;
+            }
+        }
+    }
+    for (entity, widget, mut button, mut style) in widget_query.iter_mut() {
+        let SettingsWidget::ResolveConflict(resolution) = widget else {
+            continue;
+        };
+        let shown = conflict.is_some();
+        if button.enabled != shown {
+            button.enabled = shown;
+            style.display = if shown { Display::Flex } else { Display::None };
+            // the choice goes first, the focus going back once it's made
+            if shown && *resolution == ResolveRebind::Swap {
+                focus.0 = Some(entity);
+            }
+        }
+    }
+}
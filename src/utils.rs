@@ -1,10 +1,51 @@
+use bevy::log::warn;
+use js_sys::{Function, Promise, Reflect};
+use std::sync::Once;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+
+/// Logs panics with `console.error`, with their message and a backtrace, instead of wasm's
+/// "unreachable executed", then tells the player the game crashed (see the crash module). Safe
+/// to call more than once.
+///
+/// For more details see
+/// https://github.com/rustwasm/console_error_panic_hook#readme
 pub fn set_panic_hook() {
-    // When the `console_error_panic_hook` feature is enabled, we can call the
-    // `set_panic_hook` function at least once during initialization, and then
-    // we will get better error messages if our code ever panics.
-    //
-    // For more details see
-    // https://github.com/rustwasm/console_error_panic_hook#readme
-    #[cfg(feature = "console_error_panic_hook")]
-    console_error_panic_hook::set_once();
+    static SET: Once = Once::new();
+    SET.call_once(|| {
+        std::panic::set_hook(Box::new(|info| {
+            console_error_panic_hook::hook(info);
+            let payload = info.payload();
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "Box<dyn Any>".to_string());
+            crate::crash::on_panic(
+                message,
+                info.location().map(|location| location.to_string()),
+            );
+        }));
+    });
+}
+
+/// Calls the method `name` of `target`, logging if it throws or the promise it returns is
+/// rejected, as it is when the browser refuses a request. It goes through Reflect because web-sys
+/// drops the promises of requestFullscreen and friends.
+pub fn call_method(target: &JsValue, name: &'static str) {
+    let result = Reflect::get(target, &name.into())
+        .and_then(|method| method.dyn_into::<Function>())
+        .and_then(|method| method.call0(target));
+    match result {
+        Ok(value) => {
+            if let Ok(promise) = value.dyn_into::<Promise>() {
+                spawn_local(async move {
+                    if let Err(e) = JsFuture::from(promise).await {
+                        warn!("The browser refused {}: {:?}", name, e);
+                    }
+                });
+            }
+        }
+        Err(e) => warn!("Could not call {}: {:?}", name, e),
+    }
 }
@@ -0,0 +1,74 @@
+//! The JavaScript side of the game: what the hosting page calls.
+
+use crate::bridge;
+use crate::build_info::BuildInfo;
+use crate::fullscreen;
+use crate::lifecycle;
+use crate::options::{self, StartOptions};
+use crate::utils;
+use wasm_bindgen::prelude::*;
+
+/// Starts the game with the options in `options`, a JS object of StartOptions fields or undefined,
+/// overridden by the flags in the page's URL.
+/// Throws an Error if the options are invalid, the page can't run the game, or it's already
+/// running.
+#[wasm_bindgen]
+pub fn start(options: JsValue) -> Result<(), JsValue> {
+    utils::set_panic_hook();
+    let mut options = StartOptions::from_js(options)?;
+    for problem in options.apply_flags(options::url_flags()) {
+        web_sys::console::warn_1(&format!("URL flags: {}", problem).into());
+    }
+    options.check_page()?;
+    let lifecycle = lifecycle::begin(&mut options)?;
+    // winit takes the App from here, so this returns right away
+    crate::app(options, lifecycle).run();
+    Ok(())
+}
+
+/// Stops the running game: its sounds stop, it lets go of the canvas, and start() can be called
+/// again. Does nothing if no game is running.
+#[wasm_bindgen]
+pub fn stop() {
+    lifecycle::stop();
+}
+
+/// Starts the running game over from the initial map, without reloading the page or its assets.
+/// Does nothing if no game is running.
+#[wasm_bindgen]
+pub fn restart() {
+    lifecycle::restart();
+}
+
+/// Sends a command to the game, a JSON PageCommand such as
+/// `{"type": "set_muted", "muted": true}`. Commands sent before start() run once the game does.
+/// Throws an Error if the command is malformed or unknown.
+#[wasm_bindgen]
+pub fn send_command(json: &str) -> Result<(), JsValue> {
+    Ok(bridge::send_command(json)?)
+}
+
+/// Makes the game's canvas fullscreen (true), or leaves fullscreen (false). Browsers only allow
+/// going fullscreen from a click or key press, so call it from the page's handler of one; when
+/// the browser refuses, the game stays as it is and logs why. Does nothing if no game is running.
+#[wasm_bindgen]
+pub fn set_fullscreen(active: bool) {
+    if lifecycle::is_running() {
+        fullscreen::request(active, lifecycle::canvas_selector().as_deref());
+    }
+}
+
+/// Which build of the game this is, as `{ version, gitHash, built }`: the crate version, the
+/// short hash of the commit and when it was built, the last two null if unknown
+#[wasm_bindgen]
+pub fn get_version() -> JsValue {
+    serde_wasm_bindgen::to_value(&BuildInfo::current()).unwrap_or(JsValue::NULL)
+}
+
+/// Panics with `message`, to check panics reach the console readably
+#[cfg(feature = "dev")]
+#[wasm_bindgen]
+pub fn debug_panic(message: &str) {
+    utils::set_panic_hook();
+    panic!("debug_panic: {}", message);
+}
@@ -0,0 +1,200 @@
+//! Tests for the events sent to the hosting page.
+
+use bevy::prelude::*;
+use gamedevjam2024::bridge::{
+    BridgeEvent, BridgePlugin, CommandChannel, CommandError, LevelComplete, PageCommand,
+    PageTrigger,
+};
+use gamedevjam2024::helpers::tiled::LoadMap;
+use gamedevjam2024::options::StartOptions;
+use gamedevjam2024::save::{GameProgress, GameSaved};
+use gamedevjam2024::sound::{AudioChannel, SetMuted, SetVolume};
+use gamedevjam2024::state::{AppState, ChangeState};
+use gamedevjam2024::testing::{headless_app, run_frames};
+use serde_json::json;
+
+#[derive(Resource, Default)]
+struct Sent(Vec<BridgeEvent>);
+
+fn record_sent(mut events: EventReader<BridgeEvent>, mut sent: ResMut<Sent>) {
+    sent.0.extend(events.read().cloned());
+}
+
+fn bridge_app() -> App {
+    bridge_app_with(CommandChannel::default())
+}
+
+fn bridge_app_with(commands: CommandChannel) -> App {
+    let mut app = headless_app();
+    app.add_plugins(BridgePlugin { commands })
+        .init_state::<AppState>()
+        .init_resource::<GameProgress>()
+        .init_resource::<StartOptions>()
+        .init_resource::<Sent>()
+        .add_systems(Last, record_sent);
+    run_frames(&mut app, 1);
+    app
+}
+
+/// The events of type E sent in the last two frames
+fn read<E: Event, T>(app: &App, f: impl Fn(&E) -> T) -> Vec<T> {
+    let events = app.world.resource::<Events<E>>();
+    events.get_reader().read(events).map(f).collect()
+}
+
+fn set_state(app: &mut App, state: AppState) {
+    app.world.resource_mut::<NextState<AppState>>().set(state);
+    run_frames(app, 1);
+}
+
+fn take_sent(app: &mut App) -> Vec<BridgeEvent> {
+    std::mem::take(&mut app.world.resource_mut::<Sent>().0)
+}
+
+#[test]
+fn gameplay_events_reach_the_page() {
+    let mut app = bridge_app();
+    assert!(take_sent(&mut app).is_empty());
+
+    app.world.resource_mut::<GameProgress>().score = 120;
+    app.world.send_event(LevelComplete);
+    app.world.send_event(GameSaved {
+        map: "level3.tmx".to_string(),
+    });
+    run_frames(&mut app, 1);
+    let sent = take_sent(&mut app);
+    assert_eq!(
+        sent,
+        vec![
+            BridgeEvent::new(
+                "level_complete",
+                json!({ "map": null, "score": 120, "play_time": 0.0 })
+            ),
+            BridgeEvent::new("score_changed", json!({ "score": 120, "previous": 0 })),
+            BridgeEvent::new("save_written", json!({ "map": "level3.tmx" })),
+        ]
+    );
+    assert_eq!(sent[0].event_type(), "game:level_complete");
+
+    // touching the progress without changing the score isn't a change
+    app.world
+        .resource_mut::<GameProgress>()
+        .set_flag("met_the_baker");
+    run_frames(&mut app, 1);
+    assert!(take_sent(&mut app).is_empty());
+
+    app.world
+        .resource_mut::<NextState<AppState>>()
+        .set(AppState::GameOver);
+    run_frames(&mut app, 1);
+    assert_eq!(
+        take_sent(&mut app),
+        vec![BridgeEvent::new(
+            "game_over",
+            json!({ "score": 120, "play_time": 0.0 })
+        )]
+    );
+}
+
+#[test]
+fn gameplay_code_can_send_events_of_its_own() {
+    let mut app = bridge_app();
+    app.world.send_event(BridgeEvent::new(
+        "boss_defeated",
+        json!({ "boss": "slime king" }),
+    ));
+    app.world.send_event(BridgeEvent::named("tutorial_skipped"));
+    run_frames(&mut app, 1);
+    let sent = take_sent(&mut app);
+    assert_eq!(sent.len(), 2);
+    assert_eq!(sent[0].event_type(), "game:boss_defeated");
+    assert_eq!(sent[0].payload["boss"], "slime king");
+    assert_eq!(sent[1].payload, serde_json::Value::Null);
+}
+
+#[test]
+fn page_commands_sent_before_the_game_starts_wait_for_it() {
+    let commands = CommandChannel::default();
+    commands
+        .send_json(r#"{"type": "set_muted", "muted": true}"#)
+        .unwrap();
+    commands
+        .send_json(r#"{"type": "set_volume", "channel": "music", "volume": 0.25}"#)
+        .unwrap();
+    commands
+        .send_json(r#"{"type": "grant_item", "item": "promo_hat"}"#)
+        .unwrap();
+    commands
+        .send_json(r#"{"type": "trigger_event", "name": "open_gates"}"#)
+        .unwrap();
+
+    let app = bridge_app_with(commands.clone());
+    assert_eq!(read(&app, |SetMuted(muted): &SetMuted| *muted), vec![true]);
+    assert_eq!(
+        read(&app, |event: &SetVolume| (event.channel, event.volume)),
+        vec![(AudioChannel::Music, 0.25)]
+    );
+    assert_eq!(
+        app.world.resource::<GameProgress>().inventory,
+        vec!["promo_hat".to_string()]
+    );
+    assert_eq!(
+        read(&app, PageTrigger::clone),
+        vec![PageTrigger {
+            name: "open_gates".to_string()
+        }]
+    );
+    assert!(commands.drain().is_empty());
+}
+
+#[test]
+fn load_map_commands_switch_maps_in_game_and_start_one_from_the_menu() {
+    let commands = CommandChannel::default();
+    let mut app = bridge_app_with(commands.clone());
+
+    set_state(&mut app, AppState::MainMenu);
+    commands.send(PageCommand::LoadMap {
+        path: "level2.tmx".to_string(),
+        spawn: None,
+    });
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.resource::<StartOptions>().map, "level2.tmx");
+    assert_eq!(
+        read(&app, |ChangeState(state): &ChangeState| *state),
+        vec![AppState::InGame]
+    );
+
+    set_state(&mut app, AppState::InGame);
+    commands
+        .send_json(r#"{"type": "load_map", "path": "level3.tmx", "spawn": "west"}"#)
+        .unwrap();
+    run_frames(&mut app, 1);
+    assert_eq!(
+        read(&app, |event: &LoadMap| (
+            event.path.clone(),
+            event.spawn.clone()
+        )),
+        vec![("level3.tmx".to_string(), Some("west".to_string()))]
+    );
+    assert_eq!(app.world.resource::<StartOptions>().map, "level2.tmx");
+}
+
+#[test]
+fn malformed_and_unknown_commands_are_errors() {
+    let commands = CommandChannel::default();
+    for json in [
+        "mute please",
+        r#"{"type": "self_destruct"}"#,
+        r#"{"type": "set_muted"}"#,
+        r#"{"type": "set_volume", "channel": "voice", "volume": 1.0}"#,
+        r#"{"type": "grant_item", "item": "hat", "count": 2}"#,
+    ] {
+        let result = commands.send_json(json);
+        assert!(
+            matches!(result, Err(CommandError::Parse(_))),
+            "{} was accepted",
+            json
+        );
+    }
+    assert!(commands.drain().is_empty());
+}
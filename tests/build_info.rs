@@ -0,0 +1,37 @@
+use gamedevjam2024::build_info::BuildInfo;
+
+#[test]
+fn the_current_build_has_the_crate_version() {
+    let build = BuildInfo::current();
+    assert_eq!(build.version, env!("CARGO_PKG_VERSION"));
+    assert!(build
+        .to_string()
+        .starts_with(&format!("v{}", build.version)));
+}
+
+#[test]
+fn build_info_shows_what_is_known() {
+    let mut build = BuildInfo {
+        version: "1.2.3".to_string(),
+        git_hash: Some("abc1234".to_string()),
+        built: Some("2024-06-30 18:05 UTC".to_string()),
+    };
+    assert_eq!(build.to_string(), "v1.2.3 abc1234 (2024-06-30 18:05 UTC)");
+
+    build.git_hash = None;
+    build.built = None;
+    assert_eq!(build.to_string(), "v1.2.3");
+}
+
+#[test]
+fn build_info_is_camel_case_json() {
+    let build = BuildInfo {
+        version: "1.2.3".to_string(),
+        git_hash: Some("abc1234".to_string()),
+        built: None,
+    };
+    assert_eq!(
+        serde_json::to_value(&build).unwrap(),
+        serde_json::json!({ "version": "1.2.3", "gitHash": "abc1234", "built": null })
+    );
+}
@@ -0,0 +1,121 @@
+//! Tests for button hover and press feedback.
+
+use bevy::prelude::*;
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::state::AppStatePlugin;
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    ButtonFeedback, ContinueAvailable, MainMenuButton, MenuFocus, UiPlugin, HOVER_SCALE,
+    PRESS_OFFSET,
+};
+
+/// The game on the main menu, with no save to continue
+fn feedback_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.insert_resource(ContinueAvailable(false));
+    run_frames(&mut app, 1);
+    app
+}
+
+fn sounds(app: &mut App) -> Vec<String> {
+    app.world
+        .resource_mut::<Events<PlaySFX>>()
+        .drain()
+        .map(|sfx| sfx.name)
+        .collect()
+}
+
+fn menu_button(app: &mut App, which: MainMenuButton) -> Entity {
+    app.world
+        .query::<(Entity, &MainMenuButton)>()
+        .iter(&app.world)
+        .find(|(_, button)| **button == which)
+        .map(|(entity, _)| entity)
+        .unwrap()
+}
+
+fn interact(app: &mut App, button: Entity, interaction: Interaction) {
+    app.world.entity_mut(button).insert(interaction);
+    run_frames(app, 1);
+}
+
+#[test]
+fn focus_looks_and_sounds_like_hovering() {
+    let mut app = feedback_app();
+    // the menu opening on Play doesn't make a sound
+    assert!(sounds(&mut app).is_empty());
+    let play = menu_button(&mut app, MainMenuButton::Play);
+    let settings = menu_button(&mut app, MainMenuButton::Settings);
+    assert!(app.world.get::<ButtonFeedback>(play).unwrap().is_hovered());
+
+    app.world.resource_mut::<MenuFocus>().0 = Some(settings);
+    run_frames(&mut app, 1);
+    assert_eq!(sounds(&mut app), vec!["ui_hover".to_string()]);
+    let feedback = app.world.get::<ButtonFeedback>(settings).unwrap().clone();
+    assert!(feedback.is_hovered());
+    assert_eq!(
+        app.world.get::<BackgroundColor>(settings).unwrap().0,
+        feedback.hover_color
+    );
+    assert_eq!(
+        app.world.get::<Transform>(settings).unwrap().scale,
+        Vec3::splat(HOVER_SCALE)
+    );
+    assert_eq!(app.world.get::<Transform>(play).unwrap().scale, Vec3::ONE);
+}
+
+#[test]
+fn disabled_buttons_are_dimmed_and_quiet() {
+    let mut app = feedback_app();
+    let continue_button = menu_button(&mut app, MainMenuButton::Continue);
+    let feedback = app
+        .world
+        .get::<ButtonFeedback>(continue_button)
+        .unwrap()
+        .clone();
+    assert_eq!(
+        app.world.get::<BackgroundColor>(continue_button).unwrap().0,
+        feedback.disabled_color
+    );
+
+    sounds(&mut app);
+    interact(&mut app, continue_button, Interaction::Hovered);
+    interact(&mut app, continue_button, Interaction::Pressed);
+    assert!(sounds(&mut app).is_empty());
+    assert_eq!(
+        app.world.get::<Style>(continue_button).unwrap().top,
+        Val::Auto
+    );
+}
+
+#[test]
+fn any_button_plays_its_own_sounds_and_presses_down() {
+    let mut app = feedback_app();
+    let button = app
+        .world
+        .spawn((
+            ButtonBundle::default(),
+            ButtonFeedback::default().with_sounds("beep", "boop"),
+        ))
+        .id();
+    run_frames(&mut app, 1);
+    sounds(&mut app);
+
+    interact(&mut app, button, Interaction::Hovered);
+    assert_eq!(sounds(&mut app), vec!["beep".to_string()]);
+    interact(&mut app, button, Interaction::Pressed);
+    assert_eq!(sounds(&mut app), vec!["boop".to_string()]);
+    assert_eq!(
+        app.world.get::<Style>(button).unwrap().top,
+        Val::Px(PRESS_OFFSET)
+    );
+
+    interact(&mut app, button, Interaction::Hovered);
+    assert!(sounds(&mut app).is_empty());
+    assert_eq!(app.world.get::<Style>(button).unwrap().top, Val::Auto);
+    interact(&mut app, button, Interaction::None);
+    assert_eq!(app.world.get::<Transform>(button).unwrap().scale, Vec3::ONE);
+}
@@ -0,0 +1,73 @@
+//! Tests for fitting the canvas to its parent.
+
+use bevy::prelude::*;
+use gamedevjam2024::canvas::{CanvasFit, CanvasFitPlugin, CanvasSize, RESIZE_DEBOUNCE};
+use gamedevjam2024::testing::{headless_app, run_frames, spawn_window};
+
+fn size(width: f32, height: f32, scale_factor: f32) -> CanvasSize {
+    CanvasSize {
+        width,
+        height,
+        scale_factor,
+    }
+}
+
+#[test]
+fn sizes_settle_after_the_debounce() {
+    let mut fit = CanvasFit::default();
+    fit.observe(size(800.0, 600.0, 2.0), 0.0);
+    assert_eq!(fit.settled(RESIZE_DEBOUNCE / 2.0), None);
+
+    // still being dragged
+    fit.observe(size(820.0, 600.0, 2.0), RESIZE_DEBOUNCE / 2.0);
+    assert_eq!(fit.settled(RESIZE_DEBOUNCE), None);
+    assert_eq!(
+        fit.settled(RESIZE_DEBOUNCE * 2.0),
+        Some(size(820.0, 600.0, 2.0))
+    );
+    // applied once
+    assert_eq!(fit.settled(RESIZE_DEBOUNCE * 3.0), None);
+
+    // moved to a screen of another density
+    fit.observe(size(820.0, 600.0, 1.0), 1.0);
+    assert_eq!(
+        fit.settled(1.0 + RESIZE_DEBOUNCE),
+        Some(size(820.0, 600.0, 1.0))
+    );
+}
+
+#[test]
+fn hidden_parents_are_ignored() {
+    let mut fit = CanvasFit::default();
+    fit.observe(size(0.0, 600.0, 1.0), 0.0);
+    assert_eq!(fit.settled(10.0), None);
+}
+
+#[test]
+fn the_backbuffer_matches_the_screen_pixels() {
+    assert_eq!(size(390.0, 844.0, 3.0).physical(), UVec2::new(1170, 2532));
+}
+
+#[test]
+fn the_window_follows_the_settled_size() {
+    let mut app = headless_app();
+    app.add_plugins(CanvasFitPlugin::default());
+    let window = spawn_window(&mut app, 1.0);
+    app.update();
+    let now = app
+        .world
+        .resource::<Time<bevy::time::Real>>()
+        .elapsed_seconds_f64();
+    app.world
+        .resource_mut::<CanvasFit>()
+        .observe(size(640.0, 360.0, 2.0), now);
+
+    run_frames(&mut app, 20);
+    let resolution = &app.world.get::<Window>(window).unwrap().resolution;
+    assert_eq!((resolution.width(), resolution.height()), (640.0, 360.0));
+    assert_eq!(resolution.scale_factor(), 2.0);
+    assert_eq!(
+        (resolution.physical_width(), resolution.physical_height()),
+        (1280, 720)
+    );
+}
@@ -0,0 +1,185 @@
+//! Tests for checkpoints and coming back to them on dying.
+
+use bevy::prelude::*;
+use gamedevjam2024::checkpoints::{
+    Checkpoint, CheckpointsPlugin, PlayerRespawned, Respawn, RespawnSettings, CHECKPOINT_SOUND,
+};
+use gamedevjam2024::health::{Damage, Health, HealthPlugin, PLAYER_MAX_HEALTH};
+use gamedevjam2024::helpers::tiled::{
+    LoadMap, ObjectShape, TiledObject, TriggerEntered, TriggerRegion,
+};
+use gamedevjam2024::player::Player;
+use gamedevjam2024::save::{GameProgress, SavedCheckpoint};
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::state::ChangeState;
+use gamedevjam2024::testing::{game_app, run_frames};
+
+const MAP: &str = "level1.tmx";
+
+/// The game on MAP, with a player
+fn checkpoints_app() -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((CheckpointsPlugin, HealthPlugin));
+    app.world.send_event(LoadMap::new(MAP));
+    let player = app.world.spawn((Player, TransformBundle::default())).id();
+    run_frames(&mut app, 1);
+    (app, player)
+}
+
+fn spawn_checkpoint(app: &mut App, id: u32, x: f32, checkpoint: Checkpoint) -> Entity {
+    app.world
+        .spawn((
+            checkpoint,
+            TiledObject {
+                id,
+                name: String::new(),
+                object_type: "checkpoint".to_string(),
+                properties: tiled::Properties::new(),
+                shape: ObjectShape::Rect {
+                    size: Vec2::splat(16.0),
+                },
+            },
+            TriggerRegion {
+                name: String::new(),
+                properties: tiled::Properties::new(),
+                rect: Rect::from_center_size(Vec2::new(x, 0.0), Vec2::splat(16.0)),
+            },
+        ))
+        .id()
+}
+
+fn enter(app: &mut App, region: Entity, player: Entity) {
+    app.world.send_event(TriggerEntered {
+        region,
+        entity: player,
+    });
+    run_frames(app, 1);
+}
+
+fn active(app: &App) -> Option<u32> {
+    app.world
+        .resource::<GameProgress>()
+        .checkpoint
+        .as_ref()
+        .map(|checkpoint| checkpoint.id)
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+const PLAIN: Checkpoint = Checkpoint {
+    order: None,
+    reactivate: false,
+};
+
+#[test]
+fn checkpoints_light_up_and_become_the_active_one() {
+    let (mut app, player) = checkpoints_app();
+    let first = spawn_checkpoint(&mut app, 1, 0.0, PLAIN);
+    let second = spawn_checkpoint(&mut app, 2, 100.0, PLAIN);
+    drain::<PlaySFX>(&mut app);
+
+    enter(&mut app, first, player);
+    assert_eq!(active(&app), Some(1));
+    assert!(app.world.resource::<GameProgress>().has_lit(MAP, 1));
+    assert!(drain::<PlaySFX>(&mut app)
+        .iter()
+        .any(|sound| sound.name == CHECKPOINT_SOUND));
+
+    enter(&mut app, second, player);
+    assert_eq!(active(&app), Some(2));
+    assert_eq!(
+        app.world.resource::<GameProgress>().checkpoint,
+        Some(SavedCheckpoint {
+            map: MAP.to_string(),
+            id: 2,
+            position: Vec2::new(100.0, 0.0),
+            order: None,
+        })
+    );
+
+    // going back doesn't light it again or take the player back there
+    drain::<PlaySFX>(&mut app);
+    enter(&mut app, first, player);
+    assert_eq!(active(&app), Some(2));
+    assert!(drain::<PlaySFX>(&mut app).is_empty());
+}
+
+#[test]
+fn earlier_checkpoints_only_take_over_when_reactivated() {
+    let (mut app, player) = checkpoints_app();
+    let order = |order, reactivate| Checkpoint {
+        order: Some(order),
+        reactivate,
+    };
+    let later = spawn_checkpoint(&mut app, 1, 0.0, order(2, false));
+    let earlier = spawn_checkpoint(&mut app, 2, 50.0, order(1, false));
+    let shrine = spawn_checkpoint(&mut app, 3, 100.0, order(0, true));
+
+    enter(&mut app, later, player);
+    enter(&mut app, earlier, player);
+    assert_eq!(active(&app), Some(1));
+    assert!(app.world.resource::<GameProgress>().has_lit(MAP, 2));
+
+    enter(&mut app, shrine, player);
+    assert_eq!(active(&app), Some(3));
+}
+
+#[test]
+fn dying_brings_the_player_back_at_the_checkpoint() {
+    let (mut app, player) = checkpoints_app();
+    app.insert_resource(RespawnSettings {
+        reset_room: false,
+        fade: 0.0,
+    });
+    let checkpoint = spawn_checkpoint(&mut app, 1, 40.0, PLAIN);
+    enter(&mut app, checkpoint, player);
+    drain::<ChangeState>(&mut app);
+
+    // dying, fading out and spawning again
+    app.world.send_event(Damage::new(player, PLAYER_MAX_HEALTH));
+    run_frames(&mut app, 2);
+    assert!(drain::<ChangeState>(&mut app).is_empty());
+    assert!(app.world.get_entity(player).is_none());
+
+    let respawned = drain::<PlayerRespawned>(&mut app);
+    assert_eq!(respawned.len(), 1);
+    assert_eq!(respawned[0].position, Vec2::new(40.0, 0.0));
+    let new_player = respawned[0].entity;
+    assert!(app.world.entity(new_player).contains::<Player>());
+    assert_eq!(
+        app.world
+            .get::<Transform>(new_player)
+            .unwrap()
+            .translation
+            .truncate(),
+        Vec2::new(40.0, 0.0)
+    );
+
+    run_frames(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Health>(new_player),
+        Some(&Health::new(PLAYER_MAX_HEALTH))
+    );
+    assert_eq!(*app.world.resource::<Respawn>(), Respawn::Idle);
+}
+
+#[test]
+fn resetting_the_room_loads_the_checkpoint_map_again() {
+    let (mut app, player) = checkpoints_app();
+    app.world.resource_mut::<RespawnSettings>().fade = 0.0;
+    let checkpoint = spawn_checkpoint(&mut app, 1, 40.0, PLAIN);
+    enter(&mut app, checkpoint, player);
+    drain::<LoadMap>(&mut app);
+
+    app.world.send_event(Damage::new(player, PLAYER_MAX_HEALTH));
+    run_frames(&mut app, 2);
+    assert!(matches!(
+        *app.world.resource::<Respawn>(),
+        Respawn::Loading(_)
+    ));
+    let loads = drain::<LoadMap>(&mut app);
+    assert_eq!(loads.len(), 1);
+    assert_eq!(loads[0].path, MAP);
+}
@@ -0,0 +1,120 @@
+//! Tests for the dev console.
+#![cfg(feature = "dev")]
+
+use bevy::prelude::*;
+use gamedevjam2024::console::{
+    Console, ConsoleCommands, ConsoleError, ConsolePlugin, LineKind, RegisterConsoleCommand,
+};
+use gamedevjam2024::testing::{headless_app, run_frames};
+
+#[derive(Resource, Default)]
+struct Health(i32);
+
+fn console_app() -> App {
+    let mut app = headless_app();
+    app.add_plugins(ConsolePlugin)
+        .init_resource::<Health>()
+        .register_console_command("hurt <amount>", "Hurts the player", |args, world| {
+            let amount: i32 = args.required("amount")?;
+            world.resource_mut::<Health>().0 -= amount;
+            Ok(format!("Hurt by {}", amount))
+        });
+    app
+}
+
+fn run(app: &mut App, line: &str) -> Result<String, ConsoleError> {
+    app.world
+        .resource_scope(|world, commands: Mut<ConsoleCommands>| commands.run(line, world))
+}
+
+#[test]
+fn commands_take_their_arguments() {
+    let mut app = console_app();
+
+    assert_eq!(run(&mut app, "hurt 3"), Ok("Hurt by 3".to_string()));
+    assert_eq!(app.world.resource::<Health>().0, -3);
+    assert_eq!(
+        run(&mut app, "hurt 1 2"),
+        Ok("Hurt by 1 (ignored 2)".to_string())
+    );
+}
+
+#[test]
+fn bad_arguments_show_the_usage() {
+    let mut app = console_app();
+
+    assert_eq!(
+        run(&mut app, "hurt"),
+        Err(ConsoleError::Missing {
+            name: "amount",
+            usage: "hurt <amount>".to_string(),
+        })
+    );
+    assert_eq!(
+        run(&mut app, "hurt lots"),
+        Err(ConsoleError::Invalid {
+            name: "amount",
+            value: "lots".to_string(),
+            usage: "hurt <amount>".to_string(),
+        })
+    );
+    assert_eq!(app.world.resource::<Health>().0, 0);
+}
+
+#[test]
+fn unknown_commands_suggest_close_ones() {
+    let mut app = console_app();
+
+    let Err(ConsoleError::Unknown { suggestions, .. }) = run(&mut app, "hurd 1") else {
+        panic!("expected an unknown command");
+    };
+    assert_eq!(suggestions, vec!["hurt".to_string()]);
+    let Err(ConsoleError::Unknown { suggestions, .. }) = run(&mut app, "fly") else {
+        panic!("expected an unknown command");
+    };
+    assert!(suggestions.is_empty());
+}
+
+#[test]
+fn help_lists_the_commands() {
+    let mut app = console_app();
+
+    let help = run(&mut app, "help").unwrap();
+    assert!(help.contains("hurt <amount>: Hurts the player"));
+    assert!(help.contains("clear: Clears the console"));
+}
+
+#[test]
+fn submitted_lines_run_and_print() {
+    let mut app = console_app();
+    app.world.resource_mut::<Console>().submit("hurt 2");
+    app.world.resource_mut::<Console>().submit("jump");
+    run_frames(&mut app, 1);
+
+    let console = app.world.resource::<Console>();
+    let lines: Vec<(LineKind, &str)> = console.lines().collect();
+    assert_eq!(lines[0], (LineKind::Input, "> hurt 2"));
+    assert_eq!(lines[1], (LineKind::Output, "Hurt by 2"));
+    assert_eq!(lines[2], (LineKind::Input, "> jump"));
+    assert_eq!(lines[3].0, LineKind::Error);
+    assert_eq!(app.world.resource::<Health>().0, -2);
+}
+
+#[test]
+fn the_history_goes_back_and_forth() {
+    let mut console = Console::default();
+    console.submit("hurt 1");
+    console.submit("hurt 1");
+    console.submit("help");
+    assert_eq!(console.history(), ["hurt 1", "help"]);
+
+    console.history_back();
+    assert_eq!(console.input(), "help");
+    console.history_back();
+    console.history_back();
+    assert_eq!(console.input(), "hurt 1");
+    console.history_forward();
+    assert_eq!(console.input(), "help");
+    console.history_forward();
+    assert_eq!(console.input(), "");
+}
@@ -0,0 +1,45 @@
+use gamedevjam2024::build_info::BuildInfo;
+use gamedevjam2024::crash::{self, CrashReport, LogRing, LOG_RING_LINES};
+
+#[test]
+fn the_log_ring_keeps_the_latest_lines() {
+    let mut ring = LogRing::new();
+    for line in 0..LOG_RING_LINES + 5 {
+        ring.push(line.to_string());
+    }
+    let lines = ring.lines();
+    assert_eq!(lines.len(), LOG_RING_LINES);
+    assert_eq!(lines.first().map(String::as_str), Some("5"));
+    assert_eq!(lines.last(), Some(&(LOG_RING_LINES + 4).to_string()));
+}
+
+#[test]
+fn reports_are_only_made_with_a_url() {
+    crash::configure(None, 42);
+    assert!(crash::crash_report("boom", None).is_none());
+
+    crash::configure(Some("https://example.com/crash".to_string()), 42);
+    let (url, report) = crash::crash_report("boom", Some("src/lib.rs:1:1".to_string())).unwrap();
+    assert_eq!(url, "https://example.com/crash");
+    assert_eq!(report.message, "boom");
+    assert_eq!(report.location.as_deref(), Some("src/lib.rs:1:1"));
+    assert_eq!(report.seed, 42);
+    assert_eq!(report.build, BuildInfo::current());
+
+    crash::configure(None, 42);
+    assert!(crash::crash_report("boom", None).is_none());
+}
+
+#[test]
+fn reports_hold_only_game_state() {
+    let report = CrashReport::new("boom", None, 7, vec!["INFO game: hi".to_string()]);
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    let mut keys: Vec<&str> = json
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    keys.sort_unstable();
+    assert_eq!(keys, vec!["build", "location", "log", "message", "seed"]);
+}
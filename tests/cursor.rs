@@ -0,0 +1,117 @@
+//! Tests for the custom cursor.
+
+use bevy::prelude::*;
+use gamedevjam2024::cursor::{Aiming, CursorPlugin, CursorSettings, CursorVariant, CustomCursor};
+use gamedevjam2024::input::TouchControls;
+use gamedevjam2024::picking::PointerEntered;
+use gamedevjam2024::testing::{headless_app, run_frames, spawn_window};
+
+/// The cursor at a scale factor of 1.5, before its sheet has loaded
+fn cursor_app() -> (App, Entity) {
+    let mut app = headless_app();
+    app.add_plugins(CursorPlugin);
+    let window = spawn_window(&mut app, 1.5);
+    run_frames(&mut app, 1);
+    (app, window)
+}
+
+/// Stands a blank image in for the sheet
+fn load_sheet(app: &mut App) {
+    let sheet = app
+        .world
+        .query_filtered::<&UiImage, With<CustomCursor>>()
+        .single(&app.world)
+        .texture
+        .clone();
+    app.world
+        .resource_mut::<Assets<Image>>()
+        .insert(&sheet, Image::default());
+}
+
+fn move_cursor(app: &mut App, window: Entity, position: Option<Vec2>) {
+    app.world
+        .get_mut::<Window>(window)
+        .unwrap()
+        .set_cursor_position(position);
+    run_frames(app, 1);
+}
+
+fn cursor(app: &mut App) -> (usize, Style, Visibility) {
+    let (atlas, style, visibility) = app
+        .world
+        .query_filtered::<(&TextureAtlas, &Style, &Visibility), With<CustomCursor>>()
+        .single(&app.world);
+    (atlas.index, style.clone(), *visibility)
+}
+
+fn os_cursor_visible(app: &App, window: Entity) -> bool {
+    app.world.get::<Window>(window).unwrap().cursor.visible
+}
+
+#[test]
+fn the_os_cursor_stays_until_the_sheet_loads() {
+    let (mut app, window) = cursor_app();
+    move_cursor(&mut app, window, Some(Vec2::new(10.0, 10.0)));
+    assert!(os_cursor_visible(&app, window));
+    assert_eq!(cursor(&mut app).2, Visibility::Hidden);
+
+    load_sheet(&mut app);
+    run_frames(&mut app, 1);
+    assert!(!os_cursor_visible(&app, window));
+    assert_eq!(cursor(&mut app).2, Visibility::Inherited);
+
+    move_cursor(&mut app, window, None);
+    assert_eq!(cursor(&mut app).2, Visibility::Hidden);
+}
+
+#[test]
+fn texels_are_whole_physical_pixels_around_the_hotspot() {
+    let (mut app, window) = cursor_app();
+    load_sheet(&mut app);
+    move_cursor(&mut app, window, Some(Vec2::new(10.0, 10.0)));
+
+    let settings = app.world.resource::<CursorSettings>().clone();
+    let hotspot = settings.hotspot(CursorVariant::Default);
+    // 1.5 rounds to 2 physical pixels a texel
+    let texel = 2.0 / 1.5;
+    let (_, style, _) = cursor(&mut app);
+    assert_eq!(style.width, Val::Px(settings.cell_size.x * texel));
+    let Val::Px(left) = style.left else {
+        panic!("the cursor isn't placed in pixels");
+    };
+    assert!((left - (10.0 - hotspot.x * texel)).abs() <= 0.5 / 1.5);
+    let physical = left * 1.5;
+    assert!((physical - physical.round()).abs() < 1e-4);
+}
+
+#[test]
+fn variants_follow_picking_and_aiming() {
+    let (mut app, window) = cursor_app();
+    load_sheet(&mut app);
+    move_cursor(&mut app, window, Some(Vec2::new(10.0, 10.0)));
+    assert_eq!(cursor(&mut app).0, 0);
+
+    let chest = app.world.spawn_empty().id();
+    app.world.send_event(PointerEntered { entity: chest });
+    run_frames(&mut app, 1);
+    assert_eq!(cursor(&mut app).0, 1);
+
+    app.insert_resource(Aiming(true));
+    run_frames(&mut app, 1);
+    assert_eq!(cursor(&mut app).0, 2);
+}
+
+#[test]
+fn touch_controls_give_the_os_cursor_back() {
+    let (mut app, window) = cursor_app();
+    load_sheet(&mut app);
+    move_cursor(&mut app, window, Some(Vec2::new(10.0, 10.0)));
+    assert!(!os_cursor_visible(&app, window));
+
+    let mut touch = TouchControls::default();
+    touch.enabled = true;
+    app.insert_resource(touch);
+    run_frames(&mut app, 1);
+    assert!(os_cursor_visible(&app, window));
+    assert_eq!(cursor(&mut app).2, Visibility::Hidden);
+}
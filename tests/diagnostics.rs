@@ -0,0 +1,77 @@
+//! Tests for the diagnostics overlay.
+
+use bevy::prelude::*;
+use gamedevjam2024::diagnostics::{
+    DiagnosticsOverlay, DiagnosticsOverlayPlugin, DiagnosticsOverlayRoot, DiagnosticsText,
+    FrameHistory, DIAGNOSTICS_KEY, FRAME_HISTORY,
+};
+use gamedevjam2024::state::AppState;
+use gamedevjam2024::testing::{headless_app, run_frames};
+
+fn overlay_app() -> App {
+    let mut app = headless_app();
+    app.add_plugins(DiagnosticsOverlayPlugin)
+        .init_state::<AppState>()
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 1);
+    app
+}
+
+fn overlay_visibility(app: &mut App) -> Visibility {
+    *app.world
+        .query_filtered::<&Visibility, With<DiagnosticsOverlayRoot>>()
+        .single(&app.world)
+}
+
+fn overlay_text(app: &mut App) -> String {
+    app.world
+        .query_filtered::<&Text, With<DiagnosticsText>>()
+        .single(&app.world)
+        .sections[0]
+        .value
+        .clone()
+}
+
+fn press(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.release(key);
+    keys.clear();
+}
+
+#[test]
+fn the_overlay_is_hidden_until_toggled() {
+    let mut app = overlay_app();
+    assert_eq!(overlay_visibility(&mut app), Visibility::Hidden);
+    assert_eq!(overlay_text(&mut app), "");
+    assert!(app.world.resource::<FrameHistory>().average_fps().is_none());
+
+    press(&mut app, DIAGNOSTICS_KEY);
+    run_frames(&mut app, 3);
+    assert_eq!(overlay_visibility(&mut app), Visibility::Inherited);
+    let text = overlay_text(&mut app);
+    assert!(text.contains("FPS"), "{}", text);
+    assert!(text.contains("entities"), "{}", text);
+    assert!(text.contains("state Loading"), "{}", text);
+    // 64 frames a second on the test clock
+    let average = app.world.resource::<FrameHistory>().average_fps().unwrap();
+    assert!((average - 64.0).abs() < 0.5, "{}", average);
+
+    press(&mut app, DIAGNOSTICS_KEY);
+    assert_eq!(overlay_visibility(&mut app), Visibility::Hidden);
+    assert!(!app.world.resource::<DiagnosticsOverlay>().0);
+}
+
+#[test]
+fn one_percent_low_is_the_slowest_frames() {
+    let mut history = FrameHistory::default();
+    for frame in 0..FRAME_HISTORY + 100 {
+        // a 50 ms hitch every 100 frames
+        history.push(if frame % 100 == 0 { 50.0 } else { 10.0 });
+    }
+    assert_eq!(history.frame_times().count(), FRAME_HISTORY);
+    assert_eq!(history.one_percent_low(), Some(20.0));
+    let average = history.average_fps().unwrap();
+    assert!((average - 96.15).abs() < 0.01, "{}", average);
+}
@@ -0,0 +1,122 @@
+//! Tests for the dialogue box.
+
+use bevy::prelude::*;
+use gamedevjam2024::input::{Action, FixedActionState, GameplayInput};
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    Dialogue, DialogueBox, DialogueFinished, DialogueLine, StartDialogue, UiPlugin,
+};
+
+/// The game running, with nothing said yet
+fn dialogue_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    app
+}
+
+fn boxes(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<DialogueBox>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn shown(app: &App) -> String {
+    app.world.resource::<Dialogue>().shown_text()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+#[test]
+fn interact_skips_the_typing_then_advances() {
+    let mut app = dialogue_app();
+    app.world.send_event(StartDialogue {
+        lines: vec![
+            DialogueLine::new("menu.title", "menu.play"),
+            DialogueLine::new("menu.title", "menu.quit"),
+        ],
+    });
+    run_frames(&mut app, 1);
+    assert_eq!(boxes(&mut app), 1);
+    assert!(!app.world.resource::<GameplayInput>().0);
+    assert_ne!(shown(&app), "Play");
+
+    // from the locale, not the key
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(shown(&app), "Play");
+
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(
+        app.world
+            .resource::<Dialogue>()
+            .line()
+            .map(|line| line.text.as_str()),
+        Some("menu.quit")
+    );
+    assert!(drain::<DialogueFinished>(&mut app).is_empty());
+
+    tap(&mut app, KeyCode::KeyE);
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(drain::<DialogueFinished>(&mut app).len(), 1);
+    assert_eq!(boxes(&mut app), 0);
+    assert!(app.world.resource::<GameplayInput>().0);
+}
+
+#[test]
+fn typing_blips_the_voice() {
+    let mut app = dialogue_app();
+    app.world.send_event(StartDialogue {
+        lines: vec![DialogueLine::new("menu.title", "menu.continue").with_voice("blip")],
+    });
+    let mut blips = 0;
+    for _ in 0..64 {
+        run_frames(&mut app, 1);
+        blips += drain::<PlaySFX>(&mut app)
+            .into_iter()
+            .filter(|sfx| sfx.name == "blip")
+            .count();
+    }
+    assert_eq!(shown(&app), "Continue");
+    // "Continue" is 8 characters: one as it starts, then every third
+    assert_eq!(blips, 3);
+}
+
+#[test]
+fn gameplay_gets_no_actions_while_open() {
+    let mut app = dialogue_app();
+    app.world
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    run_frames(&mut app, 2);
+    assert!(app
+        .world
+        .resource::<FixedActionState>()
+        .pressed(Action::MoveRight));
+
+    app.world.send_event(StartDialogue {
+        lines: vec![DialogueLine::new("menu.title", "menu.play")],
+    });
+    run_frames(&mut app, 2);
+    assert!(!app
+        .world
+        .resource::<FixedActionState>()
+        .pressed(Action::MoveRight));
+}
@@ -0,0 +1,206 @@
+//! Tests for branching dialogue trees, their choices and the story flags they set.
+
+use bevy::prelude::*;
+use gamedevjam2024::save::{GameProgress, StoryFlags};
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    Dialogue, DialogueBox, DialogueFinished, DialogueTree, DialogueTrees, StartDialogueTree,
+    UiPlugin,
+};
+
+const TREES: &str = r#"{
+    "elder": (
+        speaker: Some("elder.speaker"),
+        start: ["again", "intro"],
+        nodes: {
+            "intro": (
+                lines: ["elder.intro.1", "elder.intro.2"],
+                set: ["heard_elder"],
+                choices: [
+                    (text: "elder.ask_cave", next: Some("cave")),
+                    (text: "elder.bye", set: ["said_bye"]),
+                    (text: "elder.secret", requires: ["knows_secret"], next: Some("secret")),
+                ],
+            ),
+            "again": (
+                requires: ["heard_elder"],
+                lines: ["elder.again"],
+                choices: [
+                    (text: "elder.ask_cave", next: Some("cave")),
+                    (text: "elder.bye"),
+                ],
+            ),
+            "cave": (lines: ["elder.cave"], next: Some("farewell")),
+            "farewell": (clear: ["said_bye"], next: Some("last")),
+            "last": (lines: ["elder.last"]),
+            "secret": (lines: ["elder.secret.1"]),
+        },
+    ),
+}"#;
+
+fn elder() -> DialogueTree {
+    DialogueTrees::from_ron(TREES).unwrap().0["elder"].clone()
+}
+
+/// The game running, with nothing said yet
+fn dialogue_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    app
+}
+
+fn talk(app: &mut App) {
+    app.world.send_event(StartDialogueTree {
+        tree: elder(),
+        speaker: "npc.speaker".to_string(),
+    });
+    run_frames(app, 1);
+}
+
+fn dialogue(app: &App) -> &Dialogue {
+    app.world.resource::<Dialogue>()
+}
+
+fn line(app: &App) -> Option<String> {
+    dialogue(app).line().map(|line| line.text.clone())
+}
+
+fn flags(app: &App) -> &StoryFlags {
+    &app.world.resource::<GameProgress>().flags
+}
+
+fn boxes(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<DialogueBox>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+#[test]
+fn trees_parse_and_report_their_problems() {
+    let trees = DialogueTrees::from_ron(TREES).unwrap();
+    assert_eq!(trees.problems(), Vec::<String>::new());
+    let tree = &trees.0["elder"];
+    assert_eq!(tree.start_node(&StoryFlags::default()), Some("intro"));
+    let mut flags = StoryFlags::default();
+    flags.set("heard_elder");
+    assert_eq!(tree.start_node(&flags), Some("again"));
+    assert_eq!(tree.nodes["intro"].offered(&flags), vec![0, 1]);
+    flags.set("knows_secret");
+    assert_eq!(tree.nodes["intro"].offered(&flags), vec![0, 1, 2]);
+
+    let broken = DialogueTrees::from_ron(
+        r#"{
+            "broken": (
+                start: ["nowhere"],
+                nodes: {
+                    "one": (lines: ["a"], choices: [(text: "b", next: Some("gone"))]),
+                },
+            ),
+            "empty": (),
+        }"#,
+    )
+    .unwrap();
+    let problems = broken.problems();
+    assert_eq!(problems.len(), 4, "{:?}", problems);
+    assert!(problems
+        .iter()
+        .any(|p| p.starts_with("broken:") && p.contains("nowhere")));
+    assert!(problems.iter().any(|p| p.contains("1 choices")));
+    assert!(problems.iter().any(|p| p.contains("gone")));
+    assert!(problems.iter().any(|p| p.starts_with("empty:")));
+}
+
+#[test]
+fn choices_branch_and_set_flags() {
+    let mut app = dialogue_app();
+    talk(&mut app);
+    assert_eq!(boxes(&mut app), 1);
+    assert_eq!(dialogue(&app).node(), Some("intro"));
+    assert!(flags(&app).has("heard_elder"));
+    assert_eq!(
+        dialogue(&app).line().map(|line| line.speaker.as_str()),
+        Some("elder.speaker")
+    );
+
+    // no choices until the last line is typed out, then only those the flags allow
+    tap(&mut app, KeyCode::KeyE);
+    assert!(dialogue(&app).choices().is_empty());
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(line(&app).as_deref(), Some("elder.intro.2"));
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(
+        dialogue(&app).choices(),
+        vec!["elder.ask_cave", "elder.bye"]
+    );
+
+    tap(&mut app, KeyCode::ArrowDown);
+    tap(&mut app, KeyCode::KeyE);
+    assert!(flags(&app).has("said_bye"));
+    assert_eq!(drain::<DialogueFinished>(&mut app).len(), 1);
+    assert_eq!(boxes(&mut app), 0);
+    assert_eq!(dialogue(&app).node(), None);
+
+    // talking again skips the introduction
+    talk(&mut app);
+    assert_eq!(dialogue(&app).node(), Some("again"));
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(dialogue(&app).choices().len(), 2);
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(line(&app).as_deref(), Some("elder.cave"));
+    assert_eq!(dialogue(&app).node(), Some("cave"));
+    assert!(drain::<DialogueFinished>(&mut app).is_empty());
+
+    // on through the node without lines, clearing its flag
+    tap(&mut app, KeyCode::KeyE);
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(line(&app).as_deref(), Some("elder.last"));
+    assert_eq!(dialogue(&app).node(), Some("last"));
+    assert!(!flags(&app).has("said_bye"));
+
+    tap(&mut app, KeyCode::KeyE);
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(drain::<DialogueFinished>(&mut app).len(), 1);
+    assert_eq!(boxes(&mut app), 0);
+}
+
+#[test]
+fn flags_unlock_hidden_choices() {
+    let mut app = dialogue_app();
+    app.world
+        .resource_mut::<GameProgress>()
+        .set_flag("knows_secret");
+    talk(&mut app);
+    tap(&mut app, KeyCode::KeyE);
+    tap(&mut app, KeyCode::KeyE);
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(
+        dialogue(&app).choices(),
+        vec!["elder.ask_cave", "elder.bye", "elder.secret"]
+    );
+    tap(&mut app, KeyCode::ArrowDown);
+    tap(&mut app, KeyCode::ArrowDown);
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(dialogue(&app).node(), Some("secret"));
+    assert_eq!(line(&app).as_deref(), Some("elder.secret.1"));
+    assert!(dialogue(&app).choices().is_empty());
+}
@@ -0,0 +1,216 @@
+//! Tests for playing maps dropped onto the game.
+
+use bevy::prelude::*;
+use gamedevjam2024::dropped_map::{
+    unpack_drop, DropError, DroppedFile, DroppedFiles, DroppedMapTransition,
+};
+use gamedevjam2024::options::StartOptions;
+use gamedevjam2024::state::{AppState, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::toast::{ShowToast, ToastLevel};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+
+const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="tiles" tilewidth="16" tileheight="16" tilecount="2" columns="2">
+  <image source="tiles.png" width="32" height="16"/>
+ </tileset>
+ <layer id="1" name="ground" width="2" height="1">
+  <data encoding="csv">
+1,2
+</data>
+ </layer>
+</map>
+"#;
+
+const MAP_WITH_EXTERNAL_TILESET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" source="tilesets/terrain.tsx"/>
+ <layer id="1" name="ground" width="2" height="1">
+  <data encoding="csv">
+1,2
+</data>
+ </layer>
+</map>
+"#;
+
+fn file(name: &str, bytes: &[u8]) -> DroppedFile {
+    DroppedFile {
+        name: name.to_string(),
+        bytes: bytes.to_vec(),
+    }
+}
+
+fn problems(error: DropError) -> Vec<String> {
+    let DropError::Invalid(report) = error else {
+        panic!("expected an invalid map, got {}", error);
+    };
+    report
+        .errors
+        .iter()
+        .map(|problem| problem.message.clone())
+        .collect()
+}
+
+#[test]
+fn a_map_dropped_with_its_images_unpacks() {
+    let map = unpack_drop(vec![
+        file("level.tmx", MAP.as_bytes()),
+        file("tiles.png", b"png"),
+    ])
+    .unwrap();
+
+    assert_eq!(map.map, PathBuf::from("level.tmx"));
+    assert_eq!(map.files.len(), 2);
+}
+
+#[test]
+fn missing_images_are_reported() {
+    let error = unpack_drop(vec![file("level.tmx", MAP.as_bytes())]).unwrap_err();
+
+    assert_eq!(
+        problems(error),
+        vec!["tileset tiles uses tiles.png, which doesn't exist".to_string()]
+    );
+}
+
+#[test]
+fn missing_tilesets_are_reported() {
+    let error = unpack_drop(vec![file(
+        "level.tmx",
+        MAP_WITH_EXTERNAL_TILESET.as_bytes(),
+    )])
+    .unwrap_err();
+
+    assert_eq!(
+        problems(error),
+        vec!["tileset tilesets/terrain.tsx is missing".to_string()]
+    );
+}
+
+#[test]
+fn a_drop_needs_exactly_one_map() {
+    let none = unpack_drop(vec![file("tiles.png", b"png")]).unwrap_err();
+    assert!(matches!(none, DropError::NoMap(_)));
+
+    let several = unpack_drop(vec![
+        file("b.tmx", MAP.as_bytes()),
+        file("a.tmx", MAP.as_bytes()),
+        file("tiles.png", b"png"),
+    ])
+    .unwrap_err();
+    let DropError::SeveralMaps(maps) = several else {
+        panic!("expected several maps, got {}", several);
+    };
+    assert_eq!(maps, vec!["a.tmx".to_string(), "b.tmx".to_string()]);
+}
+
+#[test]
+fn zipped_maps_unpack() {
+    let mut zipped = Cursor::new(Vec::new());
+    {
+        let mut writer = zip::ZipWriter::new(&mut zipped);
+        let options = zip::write::FileOptions::default();
+        writer.start_file("maps/level.tmx", options).unwrap();
+        writer.write_all(MAP.as_bytes()).unwrap();
+        writer.start_file("maps/tiles.png", options).unwrap();
+        writer.write_all(b"png").unwrap();
+        writer
+            .start_file("__MACOSX/maps/._level.tmx", options)
+            .unwrap();
+        writer.write_all(b"fork").unwrap();
+        writer.finish().unwrap();
+    }
+
+    let map = unpack_drop(vec![file("level.zip", zipped.get_ref())]).unwrap();
+
+    assert_eq!(map.map, PathBuf::from("maps/level.tmx"));
+    assert!(map.files.contains_key(Path::new("maps/tiles.png")));
+    assert_eq!(map.files.len(), 2);
+}
+
+#[test]
+fn a_broken_zip_is_reported() {
+    let error = unpack_drop(vec![file("level.zip", b"not a zip")]).unwrap_err();
+
+    assert!(matches!(error, DropError::Zip { .. }));
+}
+
+fn drop_app(state: AppState) -> App {
+    let mut app = game_app();
+    app.insert_state(state).init_resource::<StartOptions>();
+    app
+}
+
+fn drop_map(app: &mut App) {
+    app.world
+        .send_event_batch([file("level.tmx", MAP.as_bytes()), file("tiles.png", b"png")]);
+    run_frames(app, 1);
+}
+
+#[test]
+fn maps_dropped_on_the_menu_start_a_game() {
+    let mut app = drop_app(AppState::MainMenu);
+    drop_map(&mut app);
+
+    assert_eq!(
+        app.world.resource::<StartOptions>().map,
+        "dropped://1/level.tmx"
+    );
+    assert!(app
+        .world
+        .resource::<DroppedFiles>()
+        .get(Path::new("1/tiles.png"))
+        .is_some());
+    let changes: Vec<AppState> = app
+        .world
+        .resource_mut::<Events<ChangeState>>()
+        .drain()
+        .map(|change| change.0)
+        .collect();
+    assert_eq!(changes, vec![AppState::InGame]);
+}
+
+#[test]
+fn maps_dropped_in_game_replace_the_current_one() {
+    let mut app = drop_app(AppState::InGame);
+    drop_map(&mut app);
+
+    assert!(matches!(
+        app.world.resource::<DroppedMapTransition>(),
+        DroppedMapTransition::FadingOut { path, .. } if path == "dropped://1/level.tmx"
+    ));
+    // each drop gets a directory of its own
+    drop_map(&mut app);
+    assert!(app
+        .world
+        .resource::<DroppedFiles>()
+        .get(Path::new("2/level.tmx"))
+        .is_some());
+    assert!(app
+        .world
+        .resource::<DroppedFiles>()
+        .get(Path::new("1/level.tmx"))
+        .is_none());
+}
+
+#[test]
+fn invalid_drops_get_an_error_toast() {
+    let mut app = drop_app(AppState::InGame);
+    app.world.send_event(file("level.tmx", MAP.as_bytes()));
+    run_frames(&mut app, 1);
+
+    let toasts: Vec<ShowToast> = app
+        .world
+        .resource_mut::<Events<ShowToast>>()
+        .drain()
+        .collect();
+    assert_eq!(toasts.len(), 1);
+    assert_eq!(toasts[0].level, ToastLevel::Error);
+    assert!(toasts[0].text.contains("tiles.png"));
+    assert!(matches!(
+        app.world.resource::<DroppedMapTransition>(),
+        DroppedMapTransition::Idle
+    ));
+}
@@ -0,0 +1,221 @@
+//! Tests for enemies spawned from Tiled objects.
+
+use bevy::prelude::*;
+use gamedevjam2024::enemies::{
+    enemy_spawner, EnemiesPlugin, Enemy, EnemySpawner, LOSE_FACTOR, SLIME,
+};
+use gamedevjam2024::gfx::{Emote, ShowEmote};
+use gamedevjam2024::health::{Damage, Health, HealthPlugin};
+use gamedevjam2024::helpers::tiled::{CurrentMap, LoadMap, ObjectShape, TiledObject};
+use gamedevjam2024::physics::{PhysicsPlugin, Velocity};
+use gamedevjam2024::player::Player;
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::testing::{game_app, run_frames};
+
+/// Every ShowEmote sent
+#[derive(Resource, Default)]
+struct Emotes(Vec<ShowEmote>);
+
+fn collect_emotes(mut events: EventReader<ShowEmote>, mut emotes: ResMut<Emotes>) {
+    emotes.0.extend(events.read().copied());
+}
+
+/// The game running on "level1.tmx", with the player far away
+fn enemies_app() -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((TransformPlugin, PhysicsPlugin, EnemiesPlugin, HealthPlugin))
+        .init_resource::<Emotes>()
+        .add_systems(Update, collect_emotes);
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    let player = app
+        .world
+        .spawn((
+            Player,
+            TransformBundle::from_transform(Transform::from_xyz(1000.0, 0.0, 0.0)),
+        ))
+        .id();
+    run_frames(&mut app, 1);
+    (app, player)
+}
+
+fn object(id: u32, object_type: &str, shape: ObjectShape) -> TiledObject {
+    TiledObject {
+        id,
+        name: String::new(),
+        object_type: object_type.to_string(),
+        properties: tiled::Properties::new(),
+        shape,
+    }
+}
+
+/// An "enemy" object with the id 1 at the origin, patrolling the object `patrol`
+fn spawn_enemy_object(app: &mut App, patrol: Option<u32>, once: bool) {
+    app.world.spawn((
+        object(1, "enemy", ObjectShape::Point),
+        EnemySpawner {
+            kind: SLIME.to_string(),
+            patrol,
+            once,
+        },
+        TransformBundle::default(),
+    ));
+}
+
+/// A polyline path object with the id 2 from the origin to (64, 0)
+fn spawn_path(app: &mut App) {
+    let points = vec![Vec2::ZERO, Vec2::new(64.0, 0.0)];
+    app.world.spawn((
+        object(2, "", ObjectShape::Polyline { points }),
+        TransformBundle::default(),
+    ));
+}
+
+fn enemies(app: &mut App) -> Vec<Entity> {
+    app.world
+        .query_filtered::<Entity, With<Enemy>>()
+        .iter(&app.world)
+        .collect()
+}
+
+fn position(app: &App, entity: Entity) -> Vec2 {
+    app.world
+        .get::<Transform>(entity)
+        .unwrap()
+        .translation
+        .truncate()
+}
+
+#[test]
+fn enemy_objects_spawn_their_kind_under_the_map() {
+    let mut enemy = object(1, "enemy", ObjectShape::Point);
+    assert_eq!(enemy_spawner(&enemy), None);
+    enemy.properties.insert(
+        "kind".to_string(),
+        tiled::PropertyValue::StringValue(SLIME.to_string()),
+    );
+    enemy
+        .properties
+        .insert("patrol".to_string(), tiled::PropertyValue::ObjectValue(2));
+    enemy
+        .properties
+        .insert("once".to_string(), tiled::PropertyValue::BoolValue(true));
+    assert_eq!(
+        enemy_spawner(&enemy),
+        Some(EnemySpawner {
+            kind: SLIME.to_string(),
+            patrol: Some(2),
+            once: true,
+        })
+    );
+
+    let (mut app, _) = enemies_app();
+    spawn_enemy_object(&mut app, None, false);
+    app.world.spawn((
+        object(3, "enemy", ObjectShape::Point),
+        EnemySpawner {
+            kind: "dragon".to_string(),
+            patrol: None,
+            once: false,
+        },
+        TransformBundle::default(),
+    ));
+    run_frames(&mut app, 2);
+
+    let spawned = enemies(&mut app);
+    assert_eq!(spawned.len(), 1);
+    let map = app.world.resource::<CurrentMap>().entity().unwrap();
+    assert_eq!(app.world.get::<Parent>(spawned[0]).unwrap().get(), map);
+    assert_eq!(app.world.get::<Health>(spawned[0]), Some(&Health::new(3)));
+}
+
+#[test]
+fn enemies_walk_their_patrol_back_and_forth() {
+    let (mut app, _) = enemies_app();
+    spawn_path(&mut app);
+    spawn_enemy_object(&mut app, Some(2), false);
+    run_frames(&mut app, 2);
+    let enemy = enemies(&mut app)[0];
+
+    let farthest = (0..160)
+        .map(|_| {
+            run_frames(&mut app, 1);
+            position(&app, enemy).x
+        })
+        .fold(f32::MIN, f32::max);
+    assert!(farthest > 62.0, "got to {}", farthest);
+    assert!(position(&app, enemy).x < farthest);
+    assert!(app.world.get::<Velocity>(enemy).unwrap().0.x < 0.0);
+}
+
+#[test]
+fn enemies_chase_the_player_they_notice_until_they_get_away() {
+    let (mut app, player) = enemies_app();
+    spawn_enemy_object(&mut app, None, false);
+    run_frames(&mut app, 2);
+    let enemy = enemies(&mut app)[0];
+    let radius = app
+        .world
+        .get::<Enemy>(enemy)
+        .unwrap()
+        .archetype
+        .notice_radius;
+    assert_eq!(app.world.get::<Velocity>(enemy).unwrap().0, Vec2::ZERO);
+
+    app.world
+        .get_mut::<Transform>(player)
+        .unwrap()
+        .translation
+        .x = radius - 4.0;
+    run_frames(&mut app, 2);
+    assert!(app.world.get::<Enemy>(enemy).unwrap().chasing);
+    assert!(app.world.get::<Velocity>(enemy).unwrap().0.x > 0.0);
+
+    app.world
+        .get_mut::<Transform>(player)
+        .unwrap()
+        .translation
+        .x = radius * LOSE_FACTOR + 40.0;
+    run_frames(&mut app, 2);
+    assert!(!app.world.get::<Enemy>(enemy).unwrap().chasing);
+    assert_eq!(app.world.get::<Velocity>(enemy).unwrap().0, Vec2::ZERO);
+
+    let emotes: Vec<Emote> = app
+        .world
+        .resource::<Emotes>()
+        .0
+        .iter()
+        .map(|emote| {
+            assert_eq!(emote.entity, enemy);
+            emote.emote
+        })
+        .collect();
+    assert_eq!(emotes, vec![Emote::Alert, Emote::Lost]);
+}
+
+#[test]
+fn enemies_go_with_the_map_and_once_ones_stay_defeated() {
+    let (mut app, _) = enemies_app();
+    spawn_enemy_object(&mut app, None, true);
+    run_frames(&mut app, 2);
+    let first = enemies(&mut app)[0];
+
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    run_frames(&mut app, 1);
+    assert!(app.world.get_entity(first).is_none());
+
+    // back on the map, fresh
+    spawn_enemy_object(&mut app, None, true);
+    run_frames(&mut app, 2);
+    let second = enemies(&mut app)[0];
+    app.world.send_event(Damage::new(second, 3));
+    run_frames(&mut app, 2);
+    assert!(enemies(&mut app).is_empty());
+    assert!(app
+        .world
+        .resource::<GameProgress>()
+        .has_defeated("level1.tmx", 1));
+
+    spawn_enemy_object(&mut app, None, true);
+    run_frames(&mut app, 2);
+    assert!(enemies(&mut app).is_empty());
+}
@@ -0,0 +1,169 @@
+//! Tests for level exits and the results screen they put up.
+
+use bevy::prelude::*;
+use gamedevjam2024::bridge::LevelComplete;
+use gamedevjam2024::exits::{Exit, ExitsPlugin, ReachedExit, SCORE_NAME, VICTORY_STINGER};
+use gamedevjam2024::helpers::tiled::{
+    ChangeMap, LoadMap, MapBounds, MapLoaded, MapProperties, TriggerEntered,
+};
+use gamedevjam2024::input::GameplayInput;
+use gamedevjam2024::leaderboard::SubmitScore;
+use gamedevjam2024::options::StartOptions;
+use gamedevjam2024::player::Player;
+use gamedevjam2024::run_stats::{RunStats, RunStatsPlugin};
+use gamedevjam2024::sound::PlayStinger;
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{ButtonActivated, ResultsButton, ResultsRoot, UiPlugin};
+
+const MAP: &str = "level1.tmx";
+
+/// Every LevelComplete sent
+#[derive(Resource, Default)]
+struct Completed(usize);
+
+fn count_completed(mut events: EventReader<LevelComplete>, mut completed: ResMut<Completed>) {
+    completed.0 += events.read().count();
+}
+
+/// The game on MAP with a player, scoring 120 so far, and `leaderboard_url` in the options
+fn exits_app(leaderboard_url: Option<&str>) -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((RunStatsPlugin { persist: false }, UiPlugin, ExitsPlugin))
+        .insert_resource(StartOptions {
+            leaderboard_url: leaderboard_url.map(str::to_string),
+            ..StartOptions::default()
+        })
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<Completed>()
+        .add_systems(Update, count_completed);
+    app.world.send_event(LoadMap::new(MAP));
+    run_frames(&mut app, 1);
+    let mut stats = app.world.resource_mut::<RunStats>();
+    stats.reset(MAP);
+    stats.score = 120;
+    let player = app.world.spawn((Player, TransformBundle::default())).id();
+    (app, player)
+}
+
+fn spawn_exit(app: &mut App, next_map: Option<&str>) -> Entity {
+    app.world
+        .spawn(Exit {
+            next_map: next_map.map(str::to_string),
+            next_spawn: None,
+        })
+        .id()
+}
+
+fn enter(app: &mut App, region: Entity, entity: Entity) {
+    app.world.send_event(TriggerEntered { region, entity });
+    run_frames(app, 2);
+}
+
+fn buttons(app: &mut App) -> Vec<ResultsButton> {
+    app.world
+        .query::<&ResultsButton>()
+        .iter(&app.world)
+        .copied()
+        .collect()
+}
+
+fn activate(app: &mut App, which: ResultsButton) {
+    let button = app
+        .world
+        .query::<(Entity, &ResultsButton)>()
+        .iter(&app.world)
+        .find(|(_, button)| **button == which)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    app.world.send_event(ButtonActivated(button));
+    run_frames(app, 1);
+}
+
+fn results(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<ResultsRoot>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+#[test]
+fn reaching_an_exit_completes_the_level_once() {
+    let (mut app, player) = exits_app(None);
+    let exit = spawn_exit(&mut app, Some("level2.tmx"));
+    let other = app.world.spawn_empty().id();
+
+    // only the player completes it
+    enter(&mut app, exit, other);
+    assert_eq!(app.world.resource::<Completed>().0, 0);
+
+    enter(&mut app, exit, player);
+    assert_eq!(app.world.resource::<Completed>().0, 1);
+    assert!(app.world.resource::<RunStats>().finished);
+    assert!(!app.world.resource::<GameplayInput>().0);
+    assert!(drain::<PlayStinger>(&mut app)
+        .iter()
+        .any(|stinger| stinger.name == VICTORY_STINGER));
+    assert!(drain::<SubmitScore>(&mut app).is_empty());
+    assert_eq!(results(&mut app), 1);
+    assert_eq!(
+        buttons(&mut app).len(),
+        3,
+        "Next Level, Restart and Quit to menu"
+    );
+
+    // walking out and back in again changes nothing
+    enter(&mut app, exit, player);
+    assert_eq!(app.world.resource::<Completed>().0, 1);
+    assert_eq!(results(&mut app), 1);
+}
+
+#[test]
+fn next_level_goes_on_to_the_next_map_and_gives_input_back() {
+    let (mut app, player) = exits_app(None);
+    let exit = spawn_exit(&mut app, Some("level2.tmx"));
+    enter(&mut app, exit, player);
+
+    activate(&mut app, ResultsButton::NextLevel);
+    assert_eq!(results(&mut app), 0);
+    assert_eq!(
+        drain::<ChangeMap>(&mut app),
+        vec![ChangeMap::new("level2.tmx")]
+    );
+    assert!(!app.world.resource::<GameplayInput>().0);
+
+    let map = app.world.spawn_empty().id();
+    app.world.send_event(MapLoaded {
+        map,
+        path: "level2.tmx".to_string(),
+        size_in_tiles: UVec2::splat(4),
+        tile_size: Vec2::splat(16.0),
+        bounds: MapBounds::default(),
+        properties: MapProperties::default(),
+        reloaded: false,
+    });
+    run_frames(&mut app, 1);
+    assert!(app.world.resource::<GameplayInput>().0);
+    assert_eq!(app.world.resource::<ReachedExit>().0, None);
+}
+
+#[test]
+fn the_last_exit_only_offers_to_play_again_and_submits_when_configured() {
+    let (mut app, player) = exits_app(Some("https://scores.example/api"));
+    let exit = spawn_exit(&mut app, None);
+    enter(&mut app, exit, player);
+
+    let shown = buttons(&mut app);
+    assert!(!shown.contains(&ResultsButton::NextLevel));
+    assert_eq!(shown.len(), 2);
+    assert_eq!(
+        drain::<SubmitScore>(&mut app),
+        vec![SubmitScore {
+            name: SCORE_NAME.to_string(),
+            score: 120,
+        }]
+    );
+}
@@ -0,0 +1,136 @@
+//! Tests for moving focus between menu buttons.
+
+use bevy::prelude::*;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    Focusable, MenuButton, MenuFocus, MenuNavigation, UiPlugin, FOCUS_RING_COLOR,
+};
+
+/// The game running, with no menu of its own up
+fn focus_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    app
+}
+
+/// A button at `at` on screen, its index going by `at` too
+fn spawn_button(app: &mut App, at: Vec2, focusable: Option<Focusable>) -> Entity {
+    let index = (at.y * 10.0 + at.x) as usize;
+    let mut button = app.world.spawn((
+        MenuButton {
+            index,
+            enabled: true,
+        },
+        ButtonBundle {
+            transform: Transform::from_translation(at.extend(0.0) * 100.0),
+            global_transform: GlobalTransform::from_translation(at.extend(0.0) * 100.0),
+            ..default()
+        },
+    ));
+    if let Some(focusable) = focusable {
+        button.insert(focusable);
+    }
+    button.id()
+}
+
+/// A 2x2 grid of Focusable buttons, row by row
+fn spawn_grid(app: &mut App) -> [Entity; 4] {
+    let grid = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+        .map(|(x, y)| spawn_button(app, Vec2::new(x, y), Some(Focusable::default())));
+    app.world.resource_mut::<MenuFocus>().0 = Some(grid[0]);
+    run_frames(app, 1);
+    grid
+}
+
+fn focused(app: &App) -> Option<Entity> {
+    app.world.resource::<MenuFocus>().0
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+#[test]
+fn focusables_move_to_the_nearest_or_their_neighbor() {
+    let mut app = focus_app();
+    let [top_left, top_right, bottom_left, bottom_right] = spawn_grid(&mut app);
+
+    tap(&mut app, KeyCode::ArrowRight);
+    assert_eq!(focused(&app), Some(top_right));
+    tap(&mut app, KeyCode::ArrowDown);
+    assert_eq!(focused(&app), Some(bottom_right));
+    tap(&mut app, KeyCode::ArrowLeft);
+    assert_eq!(focused(&app), Some(bottom_left));
+
+    app.world.entity_mut(bottom_left).insert(Focusable {
+        up: Some(top_right),
+        ..default()
+    });
+    tap(&mut app, KeyCode::ArrowUp);
+    assert_eq!(focused(&app), Some(top_right));
+    // a disabled neighbor is passed over for the nearest
+    app.world.get_mut::<MenuButton>(top_right).unwrap().enabled = false;
+    app.world.resource_mut::<MenuFocus>().0 = Some(bottom_left);
+    tap(&mut app, KeyCode::ArrowUp);
+    assert_eq!(focused(&app), Some(top_left));
+}
+
+#[test]
+fn wrapping_at_the_ends_can_be_turned_off() {
+    let mut app = focus_app();
+    let [_, top_right, ..] = spawn_grid(&mut app);
+    tap(&mut app, KeyCode::ArrowLeft);
+    assert_eq!(focused(&app), Some(top_right));
+
+    app.insert_resource(MenuNavigation { wrap: false });
+    tap(&mut app, KeyCode::ArrowRight);
+    assert_eq!(focused(&app), Some(top_right));
+
+    // and buttons in a list stop at the last one
+    let list = [0.0, 1.0].map(|y| spawn_button(&mut app, Vec2::new(5.0, y), None));
+    app.world.resource_mut::<MenuFocus>().0 = Some(list[1]);
+    run_frames(&mut app, 1);
+    tap(&mut app, KeyCode::ArrowDown);
+    assert_eq!(focused(&app), Some(list[1]));
+}
+
+#[test]
+fn focus_survives_its_button_going_away() {
+    let mut app = focus_app();
+    let [top_left, top_right, ..] = spawn_grid(&mut app);
+    tap(&mut app, KeyCode::ArrowRight);
+    assert_eq!(focused(&app), Some(top_right));
+
+    app.world.despawn(top_right);
+    run_frames(&mut app, 1);
+    assert_eq!(focused(&app), Some(top_left));
+}
+
+#[test]
+fn the_ring_shows_for_the_keys_and_not_the_mouse() {
+    let mut app = focus_app();
+    let [top_left, top_right, ..] = spawn_grid(&mut app);
+    let border = |app: &App, button: Entity| app.world.get::<BorderColor>(button).unwrap().0;
+    assert_eq!(border(&app, top_left), Color::NONE);
+
+    tap(&mut app, KeyCode::ArrowRight);
+    assert_eq!(border(&app, top_right), FOCUS_RING_COLOR);
+    assert_eq!(border(&app, top_left), Color::NONE);
+
+    app.world.entity_mut(top_left).insert(Interaction::Hovered);
+    run_frames(&mut app, 1);
+    assert_eq!(focused(&app), Some(top_left));
+    assert_eq!(border(&app, top_left), Color::NONE);
+    assert_eq!(border(&app, top_right), Color::NONE);
+}
@@ -0,0 +1,94 @@
+//! Tests for toggling fullscreen, natively.
+
+use bevy::prelude::*;
+use bevy::window::WindowMode;
+use gamedevjam2024::fullscreen::{Fullscreen, FullscreenPlugin, ToggleFullscreen};
+use gamedevjam2024::input::{Action, InputMap, InputPlugin};
+use gamedevjam2024::testing::{headless_app, run_frames, spawn_window};
+
+fn fullscreen_app() -> (App, Entity) {
+    let mut app = headless_app();
+    app.add_plugins(FullscreenPlugin)
+        .init_resource::<ButtonInput<KeyCode>>();
+    let window = spawn_window(&mut app, 1.0);
+    run_frames(&mut app, 1);
+    (app, window)
+}
+
+fn mode(app: &App, window: Entity) -> WindowMode {
+    app.world.get::<Window>(window).unwrap().mode
+}
+
+fn is_active(app: &App) -> bool {
+    app.world.resource::<Fullscreen>().is_active()
+}
+
+fn press(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.release(key);
+    keys.clear();
+}
+
+#[test]
+fn toggling_goes_fullscreen_and_back() {
+    let (mut app, window) = fullscreen_app();
+    assert!(!is_active(&app));
+
+    app.world.send_event(ToggleFullscreen);
+    run_frames(&mut app, 2);
+    assert_eq!(mode(&app, window), WindowMode::BorderlessFullscreen);
+    assert!(is_active(&app));
+
+    app.world.send_event(ToggleFullscreen);
+    run_frames(&mut app, 2);
+    assert_eq!(mode(&app, window), WindowMode::Windowed);
+    assert!(!is_active(&app));
+}
+
+#[test]
+fn toggling_twice_in_a_frame_does_nothing() {
+    let (mut app, window) = fullscreen_app();
+    app.world.send_event(ToggleFullscreen);
+    app.world.send_event(ToggleFullscreen);
+    run_frames(&mut app, 2);
+    assert_eq!(mode(&app, window), WindowMode::Windowed);
+}
+
+#[test]
+fn the_state_follows_the_window() {
+    let (mut app, window) = fullscreen_app();
+    // made fullscreen some other way
+    app.world.get_mut::<Window>(window).unwrap().mode = WindowMode::Fullscreen;
+    run_frames(&mut app, 1);
+    assert!(is_active(&app));
+
+    app.world.get_mut::<Window>(window).unwrap().mode = WindowMode::Windowed;
+    run_frames(&mut app, 1);
+    assert!(!is_active(&app));
+}
+
+#[test]
+fn f11_toggles_fullscreen() {
+    let (mut app, window) = fullscreen_app();
+    press(&mut app, KeyCode::F11);
+    run_frames(&mut app, 1);
+    assert_eq!(mode(&app, window), WindowMode::BorderlessFullscreen);
+}
+
+#[test]
+fn the_key_can_be_rebound() {
+    let mut app = headless_app();
+    app.add_plugins((InputPlugin, FullscreenPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.world
+        .resource_mut::<InputMap>()
+        .bind(Action::ToggleFullscreen, vec![KeyCode::KeyF]);
+    let window = spawn_window(&mut app, 1.0);
+    run_frames(&mut app, 1);
+    press(&mut app, KeyCode::F11);
+    assert_eq!(mode(&app, window), WindowMode::Windowed);
+    press(&mut app, KeyCode::KeyF);
+    assert_eq!(mode(&app, window), WindowMode::BorderlessFullscreen);
+}
@@ -0,0 +1,182 @@
+//! Tests for the game over screen and playing on from it.
+
+use bevy::prelude::*;
+use gamedevjam2024::checkpoints::{CheckpointsPlugin, PlayerRespawned, RespawnSettings};
+use gamedevjam2024::gfx::ScreenFade;
+use gamedevjam2024::health::{Damage, Health, HealthPlugin, PLAYER_MAX_HEALTH};
+use gamedevjam2024::helpers::tiled::{CurrentMap, LoadMap, RestartMap};
+use gamedevjam2024::player::{Player, PlayerPlugin};
+use gamedevjam2024::save::{GameProgress, SavedCheckpoint};
+use gamedevjam2024::sound::PlayMusic;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    ButtonActivated, GameOverButton, GameOverRoot, MenuButton, MenuFocus, UiPlugin, DARKEN_TIME,
+    GAME_OVER_MUSIC,
+};
+
+const MAP: &str = "level1.tmx";
+
+/// The game InGame on MAP, with the player spawned for it
+fn in_game_app() -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((
+        AppStatePlugin,
+        UiPlugin,
+        PlayerPlugin,
+        HealthPlugin,
+        CheckpointsPlugin,
+    ))
+    .insert_resource(RespawnSettings {
+        reset_room: false,
+        fade: 0.0,
+    })
+    .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(LoadMap::new(MAP));
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 3);
+    let player = players(&mut app)[0];
+    (app, player)
+}
+
+/// in_game_app after the player died without a checkpoint
+fn game_over_app() -> (App, Entity) {
+    let (mut app, player) = in_game_app();
+    app.world.send_event(Damage::new(player, PLAYER_MAX_HEALTH));
+    run_frames(&mut app, 3);
+    (app, player)
+}
+
+fn state(app: &App) -> AppState {
+    *app.world.resource::<State<AppState>>().get()
+}
+
+fn players(app: &mut App) -> Vec<Entity> {
+    app.world
+        .query_filtered::<Entity, With<Player>>()
+        .iter(&app.world)
+        .collect()
+}
+
+fn screens(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<GameOverRoot>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn button(app: &mut App, which: GameOverButton) -> Entity {
+    app.world
+        .query::<(Entity, &GameOverButton)>()
+        .iter(&app.world)
+        .find(|(_, button)| **button == which)
+        .map(|(entity, _)| entity)
+        .unwrap()
+}
+
+fn activate(app: &mut App, which: GameOverButton) {
+    let button = button(app, which);
+    app.world.send_event(ButtonActivated(button));
+    run_frames(app, 3);
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+#[test]
+fn dying_without_a_checkpoint_darkens_into_the_game_over_screen() {
+    let (mut app, player) = game_over_app();
+    assert_eq!(state(&app), AppState::GameOver);
+    assert_eq!(screens(&mut app), 1);
+    let sting = drain::<PlayMusic>(&mut app);
+    assert!(sting
+        .iter()
+        .any(|music| music.name == GAME_OVER_MUSIC && !music.looping));
+
+    // nothing to retry from
+    let retry = button(&mut app, GameOverButton::RetryFromCheckpoint);
+    assert!(!app.world.get::<MenuButton>(retry).unwrap().enabled);
+    let restart = button(&mut app, GameOverButton::RestartLevel);
+    assert_eq!(app.world.resource::<MenuFocus>().0, Some(restart));
+
+    let darkness = |app: &mut App| {
+        app.world
+            .query_filtered::<&BackgroundColor, With<GameOverRoot>>()
+            .single(&app.world)
+            .0
+            .a()
+    };
+    let early = darkness(&mut app);
+    run_frames(&mut app, (DARKEN_TIME * 64.0) as u32 + 2);
+    let dark = darkness(&mut app);
+    assert!(early < dark && dark > 0.5, "{} then {}", early, dark);
+
+    // the world stays frozen, the player dead
+    assert_eq!(players(&mut app), vec![player]);
+    assert_eq!(app.world.get::<Health>(player).unwrap().current, 0);
+}
+
+#[test]
+fn restarting_brings_one_player_back_with_full_health() {
+    let (mut app, player) = game_over_app();
+    activate(&mut app, GameOverButton::RestartLevel);
+    assert_eq!(state(&app), AppState::InGame);
+    assert_eq!(screens(&mut app), 0);
+    assert_eq!(drain::<RestartMap>(&mut app).len(), 1);
+
+    let now = players(&mut app);
+    assert_eq!(now.len(), 1);
+    assert_ne!(now[0], player);
+    assert_eq!(
+        app.world.get::<Health>(now[0]),
+        Some(&Health::new(PLAYER_MAX_HEALTH))
+    );
+
+    // black under the screen, then back in once the map is
+    assert_eq!(app.world.resource::<ScreenFade>().alpha(), 1.0);
+    run_frames(&mut app, 120);
+    assert_eq!(app.world.resource::<ScreenFade>().alpha(), 0.0);
+}
+
+#[test]
+fn retrying_respawns_at_the_checkpoint() {
+    let (mut app, player) = in_game_app();
+    app.world.resource_mut::<GameProgress>().checkpoint = Some(SavedCheckpoint {
+        map: MAP.to_string(),
+        id: 1,
+        position: Vec2::new(40.0, 0.0),
+        order: None,
+    });
+    // as a run ending some other way would
+    app.world.send_event(ChangeState(AppState::GameOver));
+    run_frames(&mut app, 2);
+    let retry = button(&mut app, GameOverButton::RetryFromCheckpoint);
+    assert_eq!(app.world.resource::<MenuFocus>().0, Some(retry));
+
+    activate(&mut app, GameOverButton::RetryFromCheckpoint);
+    assert_eq!(state(&app), AppState::InGame);
+    let respawned = drain::<PlayerRespawned>(&mut app);
+    assert_eq!(respawned.len(), 1);
+    assert_eq!(respawned[0].position, Vec2::new(40.0, 0.0));
+    assert_eq!(players(&mut app), vec![respawned[0].entity]);
+    assert!(app.world.get_entity(player).is_none());
+
+    run_frames(&mut app, 2);
+    assert_eq!(app.world.resource::<ScreenFade>().alpha(), 0.0);
+}
+
+#[test]
+fn quitting_goes_back_to_the_main_menu_without_the_map() {
+    let (mut app, _) = game_over_app();
+    activate(&mut app, GameOverButton::QuitToMenu);
+    assert_eq!(state(&app), AppState::MainMenu);
+    // the UnloadMap event itself is gone by now
+    assert_eq!(app.world.resource::<CurrentMap>().path(), None);
+    assert_eq!(screens(&mut app), 0);
+    assert!(players(&mut app).is_empty());
+    // the main menu fades in from black over its MENU_FADE
+    run_frames(&mut app, 30);
+    assert_eq!(app.world.resource::<ScreenFade>().alpha(), 0.0);
+}
@@ -0,0 +1,224 @@
+//! Tests for the two-finger gestures.
+
+use bevy::input::touch::{TouchInput, TouchPhase};
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{apply_zoom, MainCamera, ZoomLimits};
+use gamedevjam2024::input::{
+    FreeLook, GesturePlugin, GestureSettings, InputPlugin, TouchControlsPlugin,
+};
+use gamedevjam2024::testing::{headless_app, run_frames, spawn_window};
+
+fn gesture_app() -> (App, Entity) {
+    let mut app = headless_app();
+    app.add_plugins((
+        bevy::input::InputPlugin,
+        InputPlugin,
+        TouchControlsPlugin { forced: Some(true) },
+        GesturePlugin,
+    ))
+    .init_resource::<ZoomLimits>()
+    .add_systems(Update, apply_zoom);
+    app.world.spawn((MainCamera {}, Camera2dBundle::default()));
+    let window = spawn_window(&mut app, 1.0);
+    run_frames(&mut app, 1);
+    (app, window)
+}
+
+fn touch(app: &mut App, window: Entity, id: u64, phase: TouchPhase, position: Vec2) {
+    app.world.send_event(TouchInput {
+        phase,
+        position,
+        window,
+        force: None,
+        id,
+    });
+}
+
+/// Puts two fingers down at `first` and `second` and lets a frame go by
+fn two_fingers(app: &mut App, window: Entity, first: Vec2, second: Vec2) {
+    touch(app, window, 1, TouchPhase::Started, first);
+    touch(app, window, 2, TouchPhase::Started, second);
+    run_frames(app, 2);
+}
+
+fn camera(app: &mut App) -> (Vec2, f32) {
+    let (transform, projection) = app
+        .world
+        .query_filtered::<(&Transform, &OrthographicProjection), With<MainCamera>>()
+        .single(&app.world);
+    (transform.translation.truncate(), projection.scale)
+}
+
+#[test]
+fn pinching_zooms_within_the_limits() {
+    let (mut app, window) = gesture_app();
+    app.world.resource_mut::<GestureSettings>().free_look = false;
+    two_fingers(
+        &mut app,
+        window,
+        Vec2::new(800.0, 300.0),
+        Vec2::new(1000.0, 300.0),
+    );
+
+    touch(
+        &mut app,
+        window,
+        2,
+        TouchPhase::Moved,
+        Vec2::new(1050.0, 300.0),
+    );
+    run_frames(&mut app, 1);
+    // 200 pixels apart to 250
+    assert!((camera(&mut app).1 - 0.8).abs() < 1e-4);
+
+    touch(
+        &mut app,
+        window,
+        2,
+        TouchPhase::Moved,
+        Vec2::new(1250.0, 300.0),
+    );
+    run_frames(&mut app, 1);
+    assert_eq!(camera(&mut app), (Vec2::ZERO, 0.5));
+}
+
+#[test]
+fn fingers_on_the_joystick_dont_pinch() {
+    let (mut app, window) = gesture_app();
+    two_fingers(
+        &mut app,
+        window,
+        Vec2::new(200.0, 300.0),
+        Vec2::new(900.0, 300.0),
+    );
+
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Moved,
+        Vec2::new(100.0, 300.0),
+    );
+    touch(
+        &mut app,
+        window,
+        2,
+        TouchPhase::Moved,
+        Vec2::new(1000.0, 300.0),
+    );
+    run_frames(&mut app, 1);
+    assert_eq!(camera(&mut app), (Vec2::ZERO, 1.0));
+}
+
+#[test]
+fn dragging_pans_until_a_tap() {
+    let (mut app, window) = gesture_app();
+    two_fingers(
+        &mut app,
+        window,
+        Vec2::new(700.0, 300.0),
+        Vec2::new(900.0, 300.0),
+    );
+
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Moved,
+        Vec2::new(860.0, 300.0),
+    );
+    touch(
+        &mut app,
+        window,
+        2,
+        TouchPhase::Moved,
+        Vec2::new(1060.0, 300.0),
+    );
+    run_frames(&mut app, 1);
+    // 160 pixels right at 16 pixels a unit drags the world 10 units with the fingers
+    assert_eq!(camera(&mut app), (Vec2::new(-10.0, 0.0), 1.0));
+    assert!(app.world.resource::<FreeLook>().is_active());
+
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Ended,
+        Vec2::new(860.0, 300.0),
+    );
+    touch(
+        &mut app,
+        window,
+        2,
+        TouchPhase::Ended,
+        Vec2::new(1060.0, 300.0),
+    );
+    run_frames(&mut app, 1);
+    assert_eq!(camera(&mut app).0, Vec2::new(-10.0, 0.0));
+
+    touch(
+        &mut app,
+        window,
+        3,
+        TouchPhase::Started,
+        Vec2::new(900.0, 200.0),
+    );
+    run_frames(&mut app, 1);
+    touch(
+        &mut app,
+        window,
+        3,
+        TouchPhase::Ended,
+        Vec2::new(902.0, 200.0),
+    );
+    run_frames(&mut app, 1);
+    assert_eq!(camera(&mut app).0, Vec2::ZERO);
+    assert!(!app.world.resource::<FreeLook>().is_active());
+}
+
+#[test]
+fn panning_times_out() {
+    let (mut app, window) = gesture_app();
+    app.world
+        .resource_mut::<GestureSettings>()
+        .free_look_timeout = 0.5;
+    two_fingers(
+        &mut app,
+        window,
+        Vec2::new(700.0, 300.0),
+        Vec2::new(900.0, 300.0),
+    );
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Moved,
+        Vec2::new(700.0, 332.0),
+    );
+    touch(
+        &mut app,
+        window,
+        2,
+        TouchPhase::Moved,
+        Vec2::new(900.0, 332.0),
+    );
+    run_frames(&mut app, 1);
+    assert_eq!(camera(&mut app).0, Vec2::new(0.0, 2.0));
+
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Ended,
+        Vec2::new(700.0, 332.0),
+    );
+    touch(
+        &mut app,
+        window,
+        2,
+        TouchPhase::Ended,
+        Vec2::new(900.0, 332.0),
+    );
+    run_frames(&mut app, 40);
+    assert_eq!(camera(&mut app).0, Vec2::ZERO);
+}
@@ -0,0 +1,101 @@
+//! Tests driving the game's plugins through the headless harness.
+
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{Animation, AnimationType, GFXPlugin, MainCamera};
+use gamedevjam2024::helpers::tiled::{CurrentMap, LoadMap};
+use gamedevjam2024::sound::{NowPlaying, PlayMusic, StopMusic};
+use gamedevjam2024::testing::{
+    game_app, headless_app, run_frames, spawn_window, stub_sound, FRAME,
+};
+
+fn spawn_animation(app: &mut App, animation_type: AnimationType) -> Entity {
+    let frame_time = FRAME.as_secs_f32();
+    app.world
+        .spawn((
+            TextureAtlas::default(),
+            Animation::new(Handle::default(), vec![0, 1, 2], frame_time, animation_type),
+        ))
+        .id()
+}
+
+#[test]
+fn animations_finish_on_their_last_frame() {
+    let mut app = headless_app();
+    app.add_plugins(GFXPlugin);
+    let once = spawn_animation(&mut app, AnimationType::Once);
+    let despawn = spawn_animation(&mut app, AnimationType::Despawn);
+    let repeat = spawn_animation(&mut app, AnimationType::Repeat);
+
+    // the first update only starts the clock
+    run_frames(&mut app, 2);
+    assert_eq!(app.world.get::<TextureAtlas>(once).unwrap().index, 1);
+    assert!(app.world.get::<Animation>(once).is_some());
+
+    run_frames(&mut app, 2);
+    assert_eq!(app.world.get::<TextureAtlas>(once).unwrap().index, 2);
+    assert!(app.world.get::<Animation>(once).is_none());
+    assert!(app.world.get_entity(despawn).is_none());
+    assert_eq!(app.world.get::<TextureAtlas>(repeat).unwrap().index, 0);
+    assert!(app.world.get::<Animation>(repeat).is_some());
+}
+
+#[test]
+fn music_events_start_and_stop_tracks() {
+    let mut app = game_app();
+    stub_sound(&mut app, "theme");
+
+    app.world.send_event(PlayMusic::new("theme"));
+    app.update();
+    let playing = app
+        .world
+        .query::<&NowPlaying>()
+        .iter(&app.world)
+        .map(|playing| playing.name.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(playing, vec!["theme".to_string()]);
+
+    app.world.send_event(StopMusic::default());
+    app.update();
+    assert_eq!(app.world.query::<&NowPlaying>().iter(&app.world).count(), 0);
+}
+
+#[test]
+fn sprites_scale_with_the_window() {
+    let mut app = headless_app();
+    app.add_plugins(GFXPlugin);
+    let sprite = app.world.spawn(Sprite::default()).id();
+    let window = spawn_window(&mut app, 2.0);
+
+    run_frames(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Sprite>(sprite).unwrap().custom_size,
+        Some(Vec2::splat(2.0))
+    );
+
+    app.world
+        .get_mut::<Window>(window)
+        .unwrap()
+        .resolution
+        .set_scale_factor_override(Some(3.0));
+    run_frames(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Sprite>(sprite).unwrap().custom_size,
+        Some(Vec2::splat(3.0))
+    );
+}
+
+#[test]
+fn the_game_app_spawns_its_camera_and_loads_maps() {
+    let mut app = game_app();
+    app.world.send_event(LoadMap::new("map.tmx"));
+    app.update();
+
+    assert_eq!(
+        app.world
+            .query_filtered::<Entity, With<MainCamera>>()
+            .iter(&app.world)
+            .count(),
+        1
+    );
+    assert_eq!(app.world.resource::<CurrentMap>().path(), Some("map.tmx"));
+}
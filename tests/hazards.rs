@@ -0,0 +1,200 @@
+//! Tests for hazard tiles and the damage they deal.
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use gamedevjam2024::gfx::{CameraShake, ShakeCamera};
+use gamedevjam2024::hazards::{HazardImmune, HazardsPlugin, SPIKE_KNOCKBACK};
+use gamedevjam2024::health::{Damage, Health};
+use gamedevjam2024::helpers::tiled::{LoadMap, TileLookup, TiledMap, TiledMapBundle, TmxExtras};
+use gamedevjam2024::physics::AabbCollider;
+use gamedevjam2024::player::Player;
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::testing::{game_app, run_frames};
+use std::io::Cursor;
+use std::path::Path;
+
+// spikes in the top right corner and lava in the bottom left one
+const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="hazards" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="hazards.png" width="32" height="32"/>
+  <tile id="0">
+   <properties>
+    <property name="damage" type="int" value="1"/>
+    <property name="damage_interval" type="float" value="0.25"/>
+    <property name="hazard_type" value="spikes"/>
+   </properties>
+  </tile>
+  <tile id="1">
+   <properties>
+    <property name="damage" type="int" value="2"/>
+    <property name="hazard_type" value="lava"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="hazards" width="4" height="2">
+  <data encoding="csv">
+0,0,0,1,
+2,0,0,0
+</data>
+ </layer>
+</map>
+"#;
+
+/// Every Damage sent
+#[derive(Resource, Default)]
+struct Damaged(Vec<Damage>);
+
+fn collect_damage(mut events: EventReader<Damage>, mut damaged: ResMut<Damaged>) {
+    damaged.0.extend(events.read().copied());
+}
+
+fn parse_map(tmx: &'static str) -> tiled::Map {
+    struct MemoryReader(&'static str);
+
+    impl tiled::ResourceReader for MemoryReader {
+        type Resource = Cursor<&'static [u8]>;
+        type Error = std::io::Error;
+
+        fn read_from(&mut self, path: &Path) -> Result<Self::Resource, Self::Error> {
+            if path == Path::new("test.tmx") {
+                return Ok(Cursor::new(self.0.as_bytes()));
+            }
+            Err(std::io::ErrorKind::NotFound.into())
+        }
+    }
+
+    tiled::Loader::with_cache_and_reader(tiled::DefaultResourceCache::new(), MemoryReader(tmx))
+        .load_tmx_map("test.tmx")
+        .expect("test map should parse")
+}
+
+/// The game with MAP loaded
+fn hazards_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((TransformPlugin, HazardsPlugin))
+        .init_resource::<Damaged>()
+        .add_systems(Update, collect_damage);
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    run_frames(&mut app, 1);
+
+    let map = TiledMap {
+        map: parse_map(MAP),
+        tilemap_textures: vec![(0, TilemapTexture::Single(Handle::default()))]
+            .into_iter()
+            .collect(),
+        tile_image_offsets: Default::default(),
+        image_layers: Default::default(),
+        tmx: TmxExtras::read(MAP),
+    };
+    let handle = app.world.resource_mut::<Assets<TiledMap>>().add(map);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle,
+        ..Default::default()
+    });
+    run_frames(&mut app, 2);
+    app
+}
+
+/// Where the middle of the tile at `pos` is, offset by `by`
+fn on_tile(app: &App, pos: TilePos, by: Vec2) -> Vec3 {
+    (app.world.resource::<TileLookup>().tile_to_world(pos) + by).extend(0.0)
+}
+
+/// Something with 10 health, 8x8, in the middle of the map, clear of the hazards
+fn spawn_target(app: &mut App) -> Entity {
+    app.world
+        .spawn((
+            Health::new(10),
+            AabbCollider::new(Vec2::splat(4.0)),
+            TransformBundle::default(),
+        ))
+        .id()
+}
+
+fn move_to(app: &mut App, entity: Entity, translation: Vec3) {
+    app.world.get_mut::<Transform>(entity).unwrap().translation = translation;
+}
+
+fn damage_to(app: &App, entity: Entity) -> Vec<Damage> {
+    app.world
+        .resource::<Damaged>()
+        .0
+        .iter()
+        .filter(|damage| damage.target == entity)
+        .copied()
+        .collect()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+#[test]
+fn standing_in_lava_hurts_every_interval() {
+    let mut app = hazards_app();
+    let target = spawn_target(&mut app);
+    run_frames(&mut app, 4);
+    assert!(damage_to(&app, target).is_empty());
+
+    let lava = on_tile(&app, TilePos { x: 0, y: 0 }, Vec2::ZERO);
+    move_to(&mut app, target, lava);
+    run_frames(&mut app, 2);
+    let hurt = damage_to(&app, target);
+    assert_eq!(hurt.len(), 1);
+    assert_eq!(hurt[0].amount, 2);
+    assert_eq!(hurt[0].knockback, None);
+
+    // half a second apart, not every step
+    run_frames(&mut app, 31);
+    assert_eq!(damage_to(&app, target).len(), 1);
+    run_frames(&mut app, 2);
+    assert_eq!(damage_to(&app, target).len(), 2);
+}
+
+#[test]
+fn spikes_push_the_player_away_and_shake_the_camera() {
+    let mut app = hazards_app();
+    let player = spawn_target(&mut app);
+    app.world.entity_mut(player).insert(Player);
+    run_frames(&mut app, 2);
+    drain::<PlaySFX>(&mut app);
+
+    // on the spikes' left edge
+    let spikes = on_tile(&app, TilePos { x: 3, y: 1 }, Vec2::new(-10.0, 0.0));
+    move_to(&mut app, player, spikes);
+    run_frames(&mut app, 2);
+    let hurt = damage_to(&app, player);
+    assert_eq!(hurt.len(), 1);
+    let knockback = hurt[0].knockback.unwrap();
+    assert!(knockback.x < 0.0 && knockback.y.abs() < 1e-3);
+    assert!((knockback.length() - SPIKE_KNOCKBACK).abs() < 1e-3);
+    assert!(drain::<PlaySFX>(&mut app)
+        .iter()
+        .any(|sfx| sfx.name == "spikes"));
+    assert!(app.world.resource::<CameraShake>().strength() > 0.0);
+
+    run_frames(&mut app, 17);
+    assert_eq!(damage_to(&app, player).len(), 2);
+}
+
+#[test]
+fn hazard_immune_things_are_not_hurt() {
+    let mut app = hazards_app();
+    let immune = spawn_target(&mut app);
+    app.world.entity_mut(immune).insert(HazardImmune);
+    let no_health = app
+        .world
+        .spawn((
+            AabbCollider::new(Vec2::splat(4.0)),
+            TransformBundle::default(),
+        ))
+        .id();
+    run_frames(&mut app, 1);
+    let spikes = on_tile(&app, TilePos { x: 3, y: 1 }, Vec2::ZERO);
+    move_to(&mut app, immune, spikes);
+    move_to(&mut app, no_health, spikes);
+    run_frames(&mut app, 20);
+    assert!(app.world.resource::<Damaged>().0.is_empty());
+    assert!(drain::<ShakeCamera>(&mut app).is_empty());
+}
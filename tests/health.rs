@@ -0,0 +1,172 @@
+//! Tests for health and damage.
+
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{FlashTint, SpawnFloatingText};
+use gamedevjam2024::health::{
+    Damage, Died, Dying, Health, HealthPlugin, Invulnerability, Invulnerable, HURT_SOUND,
+    PLAYER_MAX_HEALTH,
+};
+use gamedevjam2024::physics::{AabbCollider, Knockback, Velocity};
+use gamedevjam2024::player::Player;
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::state::{AppState, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+
+/// Every Died sent
+#[derive(Resource, Default)]
+struct Deaths(Vec<Entity>);
+
+fn collect_deaths(mut events: EventReader<Died>, mut deaths: ResMut<Deaths>) {
+    deaths.0.extend(events.read().map(|death| death.entity));
+}
+
+fn health_app() -> App {
+    let mut app = game_app();
+    app.add_plugins(HealthPlugin)
+        .init_resource::<Deaths>()
+        .add_systems(Update, collect_deaths);
+    run_frames(&mut app, 1);
+    app
+}
+
+fn spawn_target(app: &mut App, health: i32) -> Entity {
+    app.world
+        .spawn((
+            Health::new(health),
+            Velocity::default(),
+            AabbCollider::new(Vec2::splat(4.0)),
+            SpriteBundle::default(),
+        ))
+        .id()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+#[test]
+fn damage_takes_health_off_with_a_flash_a_sound_and_a_number() {
+    let mut app = health_app();
+    let target = spawn_target(&mut app, 3);
+    drain::<PlaySFX>(&mut app);
+    app.world.send_event(Damage::new(target, 1));
+    run_frames(&mut app, 1);
+
+    assert_eq!(
+        app.world.get::<Health>(target),
+        Some(&Health { current: 2, max: 3 })
+    );
+    assert!(app.world.entity(target).contains::<FlashTint>());
+    assert!(drain::<PlaySFX>(&mut app)
+        .iter()
+        .any(|sound| sound.name == HURT_SOUND));
+    assert!(drain::<SpawnFloatingText>(&mut app)
+        .iter()
+        .any(|text| text.text == "1"));
+    assert!(app.world.resource::<Deaths>().0.is_empty());
+
+    // the flash fades back to the sprite's colour
+    run_frames(&mut app, 30);
+    assert!(!app.world.entity(target).contains::<FlashTint>());
+    assert_eq!(app.world.get::<Sprite>(target).unwrap().color, Color::WHITE);
+}
+
+#[test]
+fn running_out_of_health_dies_once() {
+    let mut app = health_app();
+    let target = spawn_target(&mut app, 2);
+    // a double kill in one step
+    app.world.send_event(Damage::new(target, 2));
+    app.world.send_event(Damage::new(target, 5));
+    app.update();
+    assert_eq!(app.world.resource::<Deaths>().0, vec![target]);
+    assert!(app.world.get_entity(target).is_none());
+
+    let mut health = Health { current: 2, max: 4 };
+    health.heal(5);
+    assert_eq!(health.current, 4);
+}
+
+#[test]
+fn the_player_health_is_the_hud_health_and_dying_ends_the_game() {
+    let mut app = health_app();
+    let player = app.world.spawn((Player, SpriteBundle::default())).id();
+    run_frames(&mut app, 1);
+    assert_eq!(
+        app.world.get::<Health>(player),
+        Some(&Health::new(PLAYER_MAX_HEALTH))
+    );
+    assert_eq!(
+        app.world.resource::<GameProgress>().health,
+        Some(PLAYER_MAX_HEALTH)
+    );
+
+    app.world.send_event(Damage::new(player, 2));
+    run_frames(&mut app, 1);
+    assert_eq!(
+        app.world.resource::<GameProgress>().health,
+        Some(PLAYER_MAX_HEALTH - 2)
+    );
+
+    drain::<ChangeState>(&mut app);
+    app.world.send_event(Damage::new(player, PLAYER_MAX_HEALTH));
+    run_frames(&mut app, 2);
+    // the player stays, dying
+    assert!(app.world.entity(player).contains::<Dying>());
+    assert_eq!(app.world.resource::<GameProgress>().health, Some(0));
+    assert!(drain::<ChangeState>(&mut app).contains(&ChangeState(AppState::GameOver)));
+}
+
+#[test]
+fn invulnerability_ignores_damage_for_a_while_blinking_meanwhile() {
+    let mut app = health_app();
+    let target = spawn_target(&mut app, 5);
+    app.world
+        .entity_mut(target)
+        .insert(Invulnerability::new(0.5));
+    // a double hit in one step only counts once
+    app.world
+        .send_event(Damage::new(target, 1).with_knockback(Vec2::new(-100.0, 0.0)));
+    app.world.send_event(Damage::new(target, 1));
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.get::<Health>(target).unwrap().current, 4);
+    assert!(app.world.entity(target).contains::<Invulnerable>());
+    assert_eq!(
+        app.world.get::<Knockback>(target),
+        Some(&Knockback(Vec2::new(-100.0, 0.0)))
+    );
+
+    let mut alphas = Vec::new();
+    for _ in 0..20 {
+        app.world.send_event(Damage::new(target, 1));
+        run_frames(&mut app, 1);
+        alphas.push(app.world.get::<Sprite>(target).unwrap().color.a());
+    }
+    assert_eq!(app.world.get::<Health>(target).unwrap().current, 4);
+    assert!(
+        alphas.contains(&0.0) && alphas.contains(&1.0),
+        "{:?}",
+        alphas
+    );
+
+    // over, fully visible again and hurt by the next hit
+    run_frames(&mut app, 16);
+    assert!(!app.world.entity(target).contains::<Invulnerable>());
+    assert_eq!(app.world.get::<Sprite>(target).unwrap().color, Color::WHITE);
+    app.world.send_event(Damage::new(target, 1));
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.get::<Health>(target).unwrap().current, 3);
+}
+
+#[test]
+fn without_invulnerability_every_hit_counts() {
+    let mut app = health_app();
+    let target = spawn_target(&mut app, 5);
+    for _ in 0..3 {
+        app.world.send_event(Damage::new(target, 1));
+        run_frames(&mut app, 1);
+    }
+    assert_eq!(app.world.get::<Health>(target).unwrap().current, 2);
+    assert!(!app.world.entity(target).contains::<Invulnerable>());
+}
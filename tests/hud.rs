@@ -0,0 +1,143 @@
+//! Tests for the HUD.
+
+use bevy::prelude::*;
+use gamedevjam2024::helpers::tiled::{TriggerEntered, TriggerRegion};
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    AreaBanner, DialogueLine, Heart, Hearts, HudRoot, ScoreCounter, StartDialogue, UiPlugin,
+    AREA_BANNER_TIME, AREA_PROPERTY,
+};
+
+/// The game running with 3 of 5 hearts
+fn hud_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<GameProgress>();
+    run_frames(&mut app, 2);
+    {
+        let mut progress = app.world.resource_mut::<GameProgress>();
+        progress.health = Some(3);
+        progress.max_health = Some(5);
+    }
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 3);
+    app
+}
+
+/// The hearts colored like the first, which is full, and all of them
+fn full_hearts(app: &mut App) -> (usize, usize) {
+    let mut hearts: Vec<(i32, Color)> = app
+        .world
+        .query::<(&Heart, &BackgroundColor)>()
+        .iter(&app.world)
+        .map(|(heart, color)| (heart.0, color.0))
+        .collect();
+    hearts.sort_by_key(|(index, _)| *index);
+    let full = hearts
+        .iter()
+        .take_while(|(_, color)| *color == hearts[0].1)
+        .count();
+    assert!(hearts[full..]
+        .iter()
+        .all(|(_, color)| *color != hearts[0].1));
+    (full, hearts.len())
+}
+
+fn progress(app: &mut App) -> Mut<'_, GameProgress> {
+    app.world.resource_mut::<GameProgress>()
+}
+
+#[test]
+fn hearts_follow_health_and_shake_on_damage() {
+    let mut app = hud_app();
+    assert_eq!(full_hearts(&mut app), (3, 5));
+
+    progress(&mut app).health = Some(2);
+    run_frames(&mut app, 1);
+    assert_eq!(full_hearts(&mut app), (2, 5));
+    let hearts = app.world.query::<&Hearts>().single(&app.world).shake;
+    assert!(hearts > 0.0);
+
+    run_frames(&mut app, 32);
+    let (hearts, style) = app.world.query::<(&Hearts, &Style)>().single(&app.world);
+    assert_eq!(hearts.shake, 0.0);
+    assert_eq!(style.left, Val::Px(0.0));
+}
+
+#[test]
+fn the_score_counts_up_to_its_value() {
+    let mut app = hud_app();
+    progress(&mut app).score = 100;
+    run_frames(&mut app, 1);
+    let shown = app.world.query::<&ScoreCounter>().single(&app.world).shown;
+    assert!(shown > 0 && shown < 100);
+
+    run_frames(&mut app, 64);
+    let (counter, text, transform) = app
+        .world
+        .query::<(&ScoreCounter, &Text, &Transform)>()
+        .single(&app.world);
+    assert_eq!(counter.shown, 100);
+    assert_eq!(text.sections[0].value, "100");
+    assert_eq!(transform.scale, Vec3::ONE);
+}
+
+#[test]
+fn named_areas_get_a_banner() {
+    let mut app = hud_app();
+    let mut properties = tiled::Properties::new();
+    properties.insert(
+        AREA_PROPERTY.to_string(),
+        tiled::PropertyValue::StringValue("menu.title".to_string()),
+    );
+    let region = app
+        .world
+        .spawn(TriggerRegion {
+            name: "village".to_string(),
+            properties,
+            rect: Rect::new(0.0, 0.0, 10.0, 10.0),
+        })
+        .id();
+    let sensor = app.world.spawn_empty().id();
+    app.world.send_event(TriggerEntered {
+        region,
+        entity: sensor,
+    });
+    run_frames(&mut app, 16);
+
+    let (banner, text) = app.world.query::<(&AreaBanner, &Text)>().single(&app.world);
+    assert!(banner.remaining > 0.0);
+    assert_eq!(text.sections[0].value, "Gamedev Jam 2024");
+    assert!(text.sections[0].style.color.a() > 0.0);
+
+    run_frames(&mut app, (AREA_BANNER_TIME * 64.0) as u32);
+    let banner = app.world.query::<&AreaBanner>().single(&app.world);
+    assert_eq!(banner.remaining, 0.0);
+}
+
+#[test]
+fn hidden_during_dialogue_and_gone_on_the_main_menu() {
+    let mut app = hud_app();
+    app.world.send_event(StartDialogue {
+        lines: vec![DialogueLine::new("menu.title", "menu.play")],
+    });
+    run_frames(&mut app, 2);
+    let visibility = *app
+        .world
+        .query_filtered::<&Visibility, With<HudRoot>>()
+        .single(&app.world);
+    assert_eq!(visibility, Visibility::Hidden);
+
+    app.world.send_event(ChangeState(AppState::MainMenu));
+    run_frames(&mut app, 2);
+    assert_eq!(
+        app.world
+            .query_filtered::<(), With<HudRoot>>()
+            .iter(&app.world)
+            .count(),
+        0
+    );
+}
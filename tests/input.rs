@@ -0,0 +1,294 @@
+//! Tests for InputMap, ActionState, FixedActionState, input buffering and gamepads.
+
+use bevy::input::gamepad::{
+    GamepadAxisChangedEvent, GamepadButtonChangedEvent, GamepadConnection, GamepadConnectionEvent,
+    GamepadInfo,
+};
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use gamedevjam2024::input::{
+    read_actions, Action, ActionAxis, ActionState, ActiveDevice, FixedActionState, InputMap,
+    InputPlugin,
+};
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{headless_app, run_frames};
+use gamedevjam2024::toast::ShowToast;
+use std::collections::BTreeSet;
+
+fn input_app() -> App {
+    let mut app = headless_app();
+    app.add_plugins(InputPlugin)
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<ButtonInput<MouseButton>>();
+    // the clock doesn't move on the first frame, so FixedUpdate first runs on the second
+    run_frames(&mut app, 1);
+    app
+}
+
+fn actions(app: &App) -> &ActionState {
+    app.world.resource::<ActionState>()
+}
+
+/// Presses `key` for a frame, then releases it for another
+fn tap(app: &mut App, key: KeyCode) -> (ActionState, ActionState) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let pressed = actions(app).clone();
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+    (pressed, actions(app).clone())
+}
+
+#[test]
+fn every_binding_of_an_action_holds_it() {
+    let mut app = input_app();
+
+    for key in [KeyCode::KeyW, KeyCode::ArrowUp] {
+        let (pressed, released) = tap(&mut app, key);
+        assert!(pressed.pressed(Action::MoveUp));
+        assert!(pressed.just_pressed(Action::MoveUp));
+        assert!(!released.pressed(Action::MoveUp));
+        assert!(released.just_released(Action::MoveUp));
+    }
+
+    app.world
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .press(MouseButton::Left);
+    run_frames(&mut app, 1);
+    app.world.resource_mut::<ButtonInput<MouseButton>>().clear();
+    run_frames(&mut app, 1);
+    assert!(actions(&app).pressed(Action::Attack));
+    assert!(!actions(&app).just_pressed(Action::Attack));
+}
+
+#[test]
+fn rebinding_replaces_the_keys() {
+    let mut app = input_app();
+    app.world
+        .resource_mut::<InputMap>()
+        .bind(Action::Interact, vec![KeyCode::KeyF]);
+
+    assert!(!tap(&mut app, KeyCode::KeyE).0.pressed(Action::Interact));
+    assert!(tap(&mut app, KeyCode::KeyF).0.pressed(Action::Interact));
+}
+
+#[test]
+fn axes_follow_the_movement_keys() {
+    let mut app = input_app();
+    let fixed_x = |app: &App| {
+        app.world
+            .resource::<FixedActionState>()
+            .axis(ActionAxis::MoveX)
+    };
+    app.world
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::KeyD);
+    run_frames(&mut app, 1);
+    assert_eq!(actions(&app).axis(ActionAxis::MoveX), 1.0);
+    assert_eq!(actions(&app).axis(ActionAxis::MoveY), 0.0);
+    assert_eq!(fixed_x(&app), 1.0);
+
+    app.world
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::ArrowLeft);
+    run_frames(&mut app, 1);
+    assert_eq!(actions(&app).axis(ActionAxis::MoveX), 0.0);
+    assert_eq!(fixed_x(&app), 0.0);
+}
+
+fn pad_app() -> (App, Gamepad) {
+    let mut app = input_app();
+    app.add_plugins(bevy::input::InputPlugin);
+    let gamepad = Gamepad::new(0);
+    app.world.send_event(GamepadConnectionEvent::new(
+        gamepad,
+        GamepadConnection::Connected(GamepadInfo {
+            name: "pad".to_string(),
+        }),
+    ));
+    run_frames(&mut app, 1);
+    (app, gamepad)
+}
+
+fn push_stick(app: &mut App, gamepad: Gamepad, value: f32) {
+    app.world.send_event(GamepadAxisChangedEvent::new(
+        gamepad,
+        GamepadAxisType::LeftStickX,
+        value,
+    ));
+    run_frames(app, 1);
+}
+
+#[test]
+fn sticks_go_through_the_dead_zone_and_curve_and_hold_their_direction() {
+    let (mut app, gamepad) = pad_app();
+    {
+        let mut map = app.world.resource_mut::<InputMap>();
+        map.dead_zone = 0.2;
+        map.response_curve = 2.0;
+    }
+
+    push_stick(&mut app, gamepad, -0.6);
+    assert!((actions(&app).axis(ActionAxis::MoveX) + 0.25).abs() < 1e-5);
+    assert!(actions(&app).pressed(Action::MoveLeft));
+    assert!(app
+        .world
+        .resource::<FixedActionState>()
+        .pressed(Action::MoveLeft));
+
+    push_stick(&mut app, gamepad, -0.1);
+    assert_eq!(actions(&app).axis(ActionAxis::MoveX), 0.0);
+    assert!(!actions(&app).pressed(Action::MoveLeft));
+
+    push_stick(&mut app, gamepad, 1.0);
+    assert_eq!(actions(&app).axis(ActionAxis::MoveX), 1.0);
+}
+
+#[test]
+fn prompts_follow_the_device_used_last() {
+    let (mut app, gamepad) = pad_app();
+    let prompt = |app: &App| {
+        let glyphs = app.world.resource::<ActiveDevice>().glyphs();
+        let glyph = app
+            .world
+            .resource::<InputMap>()
+            .glyph(Action::Interact, glyphs);
+        format!("Press {}", glyph.unwrap())
+    };
+    assert_eq!(prompt(&app), "Press E");
+
+    app.world.send_event(GamepadButtonChangedEvent::new(
+        gamepad,
+        GamepadButtonType::South,
+        1.0,
+    ));
+    run_frames(&mut app, 1);
+    assert_eq!(
+        *app.world.resource::<ActiveDevice>(),
+        ActiveDevice::Gamepad(gamepad)
+    );
+    assert!(actions(&app).just_pressed(Action::Interact));
+    assert_eq!(prompt(&app), "Press Ⓐ");
+
+    // bevy's InputPlugin clears what's pressed by hand, so this goes through its events
+    app.world.send_event(KeyboardInput {
+        key_code: KeyCode::Space,
+        logical_key: Key::Space,
+        state: ButtonState::Pressed,
+        window: Entity::PLACEHOLDER,
+    });
+    run_frames(&mut app, 1);
+    assert_eq!(prompt(&app), "Press E");
+
+    push_stick(&mut app, gamepad, 0.1);
+    assert_eq!(prompt(&app), "Press E");
+    push_stick(&mut app, gamepad, 0.9);
+    assert_eq!(prompt(&app), "Press Ⓐ");
+}
+
+#[test]
+fn losing_the_active_pad_mid_play_pauses() {
+    let (mut app, gamepad) = pad_app();
+    app.add_plugins(AppStatePlugin);
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    push_stick(&mut app, gamepad, 0.9);
+    assert_eq!(
+        *app.world.resource::<State<AppState>>().get(),
+        AppState::InGame
+    );
+    app.world.resource_mut::<Events<ShowToast>>().clear();
+
+    app.world.send_event(GamepadConnectionEvent::new(
+        gamepad,
+        GamepadConnection::Disconnected,
+    ));
+    run_frames(&mut app, 2);
+
+    assert_eq!(
+        *app.world.resource::<State<AppState>>().get(),
+        AppState::Paused
+    );
+    assert_eq!(
+        *app.world.resource::<ActiveDevice>(),
+        ActiveDevice::KeyboardMouse
+    );
+    let toasts: Vec<String> = app
+        .world
+        .resource_mut::<Events<ShowToast>>()
+        .drain()
+        .map(|toast| toast.text)
+        .collect();
+    assert_eq!(toasts, vec!["Controller disconnected".to_string()]);
+}
+
+/// The actions held in each fixed step
+#[derive(Resource, Default)]
+struct Steps(Vec<bool>);
+
+fn note_interact(actions: Res<FixedActionState>, mut steps: ResMut<Steps>) {
+    steps.0.push(actions.pressed(Action::Interact));
+}
+
+#[test]
+fn a_tap_between_steps_is_held_for_the_next() {
+    let mut app = input_app();
+    // a step every 4 frames
+    app.insert_resource(Time::<Fixed>::from_hz(16.0))
+        .init_resource::<Steps>()
+        .add_systems(FixedUpdate, note_interact.after(read_actions));
+    run_frames(&mut app, 8);
+    app.world.resource_mut::<Steps>().0.clear();
+
+    tap(&mut app, KeyCode::KeyE);
+    run_frames(&mut app, 8);
+    let steps = &app.world.resource::<Steps>().0;
+    assert_eq!(steps.iter().filter(|held| **held).count(), 1);
+}
+
+#[test]
+fn buffered_presses_are_consumed_once_within_their_window() {
+    let step = 1.0 / 64.0;
+    let mut state = FixedActionState::default();
+    state.step(BTreeSet::from([Action::Attack, Action::MoveUp]), 0.0);
+    state.step(BTreeSet::new(), step);
+    assert!(state.buffered(Action::Attack));
+    assert!(!state.consume(Action::MoveUp));
+    assert!(state.consume(Action::Attack));
+    assert!(!state.consume(Action::Attack));
+
+    state.step(BTreeSet::from([Action::Attack]), 2.0 * step);
+    let window = state.buffer_window(Action::Attack) as f64;
+    let mut now = 2.0 * step;
+    while now - 2.0 * step <= window {
+        now += step;
+        state.step(BTreeSet::new(), now);
+    }
+    assert!(!state.consume(Action::Attack));
+
+    // a replay replacing the step's actions takes the press back
+    state.step(BTreeSet::from([Action::Interact]), now + step);
+    state.set_held(BTreeSet::new());
+    assert!(!state.buffered(Action::Interact));
+
+    state.set_buffer_window(Action::MoveUp, 0.1);
+    state.step(BTreeSet::from([Action::MoveUp]), now + 2.0 * step);
+    assert!(state.consume(Action::MoveUp));
+}
+
+#[test]
+fn held_for_times_the_hold_up_to_its_release() {
+    let mut state = FixedActionState::default();
+    state.step(BTreeSet::from([Action::Attack]), 1.0);
+    state.step(BTreeSet::from([Action::Attack]), 1.25);
+    assert_eq!(state.held_for(Action::Attack), 0.25);
+    state.step(BTreeSet::new(), 1.5);
+    assert!(state.just_released(Action::Attack));
+    assert_eq!(state.held_for(Action::Attack), 0.5);
+    state.step(BTreeSet::new(), 1.75);
+    assert_eq!(state.held_for(Action::Attack), 0.0);
+}
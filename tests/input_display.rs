@@ -0,0 +1,95 @@
+//! Tests for the on-screen input display.
+
+use bevy::prelude::*;
+use gamedevjam2024::input::{
+    Action, InputDisplay, InputDisplayDot, InputDisplayHeld, InputDisplayPlugin, InputDisplayRoot,
+    InputHistory, INPUT_HISTORY,
+};
+use gamedevjam2024::testing::{game_app, run_frames};
+
+fn display_app() -> App {
+    let mut app = game_app();
+    app.add_plugins(InputDisplayPlugin)
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 1);
+    app
+}
+
+fn shown(app: &mut App) -> bool {
+    app.world
+        .query_filtered::<(), With<InputDisplayRoot>>()
+        .iter(&app.world)
+        .count()
+        == 1
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+#[test]
+fn hidden_there_is_nothing_to_show() {
+    let mut app = display_app();
+    assert!(!shown(&mut app));
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(app.world.resource::<InputHistory>().presses().count(), 0);
+
+    app.insert_resource(InputDisplay(true));
+    run_frames(&mut app, 1);
+    assert!(shown(&mut app));
+    app.insert_resource(InputDisplay(false));
+    run_frames(&mut app, 1);
+    assert!(!shown(&mut app));
+}
+
+#[test]
+fn the_last_few_presses_are_listed_newest_first() {
+    let mut app = display_app();
+    app.insert_resource(InputDisplay(true));
+    run_frames(&mut app, 1);
+    for _ in 0..INPUT_HISTORY {
+        tap(&mut app, KeyCode::KeyE);
+    }
+    tap(&mut app, KeyCode::KeyW);
+
+    let presses: Vec<Action> = app.world.resource::<InputHistory>().presses().collect();
+    assert_eq!(presses.len(), INPUT_HISTORY);
+    assert_eq!(presses[0], Action::MoveUp);
+    assert_eq!(presses[1], Action::Interact);
+}
+
+#[test]
+fn held_actions_and_the_dial_follow_the_bindings() {
+    let mut app = display_app();
+    app.insert_resource(InputDisplay(true));
+    run_frames(&mut app, 1);
+    app.world
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::ArrowRight);
+    run_frames(&mut app, 2);
+
+    let held = app
+        .world
+        .query_filtered::<&Text, With<InputDisplayHeld>>()
+        .single(&app.world)
+        .sections[0]
+        .value
+        .clone();
+    // the first key bound to MoveRight, not the arrow held
+    assert_eq!(held, "D");
+    let dot = app
+        .world
+        .query_filtered::<&Style, With<InputDisplayDot>>()
+        .single(&app.world)
+        .clone();
+    let (Val::Px(left), Val::Px(top)) = (dot.left, dot.top) else {
+        panic!("the dot isn't placed in pixels");
+    };
+    assert!(left > top);
+}
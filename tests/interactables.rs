@@ -0,0 +1,161 @@
+//! Tests for signs, levers and NPCs spawned from Tiled objects.
+
+use bevy::prelude::*;
+use gamedevjam2024::helpers::tiled::{ObjectShape, TiledObject};
+use gamedevjam2024::interactables::{
+    dialogue_lines, object_interaction, InteractablesPlugin, LeverToggled, ObjectInteraction,
+    LEVER_SOUND, SIGN_SPEAKER,
+};
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{Dialogue, DialogueLine, Interacted, StartDialogue, UiPlugin};
+
+fn object(object_type: &str, properties: &[(&str, tiled::PropertyValue)]) -> TiledObject {
+    TiledObject {
+        id: 1,
+        name: String::new(),
+        object_type: object_type.to_string(),
+        properties: properties
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect(),
+        shape: ObjectShape::Point,
+    }
+}
+
+fn string(value: &str) -> tiled::PropertyValue {
+    tiled::PropertyValue::StringValue(value.to_string())
+}
+
+/// The game running in game, with the dialogue box and the interactables
+fn interactables_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin, InteractablesPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    app
+}
+
+fn spawn(app: &mut App, interaction: ObjectInteraction) -> Entity {
+    app.world
+        .spawn((
+            interaction,
+            TransformBundle::from_transform(Transform::from_xyz(32.0, 16.0, 0.0)),
+        ))
+        .id()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+fn interact(app: &mut App, entity: Entity) {
+    app.world.send_event(Interacted { entity });
+    run_frames(app, 1);
+}
+
+#[test]
+fn objects_are_set_up_from_their_properties() {
+    let (sign, prompt) = object_interaction(&object("sign", &[("text", string("menu.play"))]))
+        .expect("a sign with text");
+    assert_eq!(
+        sign,
+        ObjectInteraction::Sign {
+            text: "menu.play".to_string()
+        }
+    );
+    assert_eq!(prompt, "prompt.read");
+
+    let (npc, prompt) =
+        object_interaction(&object("npc", &[("dialogue", string("guide"))])).unwrap();
+    assert_eq!(
+        npc,
+        ObjectInteraction::Npc {
+            dialogue: "guide".to_string(),
+            speaker: "guide.speaker".to_string(),
+        }
+    );
+    assert_eq!(prompt, "prompt.talk");
+
+    // missing what they need, or not interactable at all
+    assert_eq!(object_interaction(&object("lever", &[])), None);
+    assert_eq!(
+        object_interaction(&object("torch", &[("text", string("menu.play"))])),
+        None
+    );
+}
+
+#[test]
+fn signs_and_npcs_open_the_dialogue_box() {
+    let mut app = interactables_app();
+    let sign = spawn(
+        &mut app,
+        ObjectInteraction::Sign {
+            text: "menu.play".to_string(),
+        },
+    );
+    interact(&mut app, sign);
+    let opened = drain::<StartDialogue>(&mut app);
+    assert_eq!(opened.len(), 1);
+    assert_eq!(
+        opened[0].lines,
+        vec![DialogueLine::new(SIGN_SPEAKER, "menu.play")]
+    );
+
+    assert_eq!(
+        dialogue_lines("guide", "guide.speaker", None),
+        vec![DialogueLine::new("guide.speaker", "guide")]
+    );
+}
+
+#[test]
+fn levers_toggle_their_flag() {
+    let mut app = interactables_app();
+    let lever = spawn(
+        &mut app,
+        ObjectInteraction::Lever {
+            target: "gate_open".to_string(),
+        },
+    );
+    drain::<PlaySFX>(&mut app);
+
+    interact(&mut app, lever);
+    assert!(app.world.resource::<GameProgress>().has_flag("gate_open"));
+    assert_eq!(
+        drain::<LeverToggled>(&mut app),
+        vec![LeverToggled {
+            name: "gate_open".to_string(),
+            state: true,
+        }]
+    );
+    let sounds = drain::<PlaySFX>(&mut app);
+    assert!(sounds.iter().any(|sound| sound.name == LEVER_SOUND));
+
+    interact(&mut app, lever);
+    assert!(!app.world.resource::<GameProgress>().has_flag("gate_open"));
+    assert!(!drain::<LeverToggled>(&mut app)[0].state);
+}
+
+#[test]
+fn nothing_happens_while_the_dialogue_box_is_open() {
+    let mut app = interactables_app();
+    let lever = spawn(
+        &mut app,
+        ObjectInteraction::Lever {
+            target: "gate_open".to_string(),
+        },
+    );
+    app.world.send_event(StartDialogue {
+        lines: vec![DialogueLine::new("menu.title", "menu.play")],
+    });
+    run_frames(&mut app, 1);
+    assert!(app.world.resource::<Dialogue>().is_open());
+
+    interact(&mut app, lever);
+    assert!(!app.world.resource::<GameProgress>().has_flag("gate_open"));
+    assert!(drain::<LeverToggled>(&mut app).is_empty());
+}
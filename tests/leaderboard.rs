@@ -0,0 +1,99 @@
+//! Tests for score submission, through the native stub.
+
+use bevy::prelude::*;
+use gamedevjam2024::leaderboard::{
+    LeaderboardPlugin, ScoreSubmissions, ScoreSubmitFailed, ScoreSubmitted, StubLeaderboard,
+    SubmitScore, SUBMIT_TIMEOUT,
+};
+use gamedevjam2024::options::StartOptions;
+use gamedevjam2024::testing::{headless_app, run_frames, FRAME};
+
+#[derive(Resource, Default)]
+struct Outcomes {
+    submitted: Vec<ScoreSubmitted>,
+    failed: Vec<ScoreSubmitFailed>,
+}
+
+fn record_outcomes(
+    mut submitted: EventReader<ScoreSubmitted>,
+    mut failed: EventReader<ScoreSubmitFailed>,
+    mut outcomes: ResMut<Outcomes>,
+) {
+    outcomes.submitted.extend(submitted.read().cloned());
+    outcomes.failed.extend(failed.read().cloned());
+}
+
+fn leaderboard_app(url: Option<&str>) -> App {
+    let mut app = headless_app();
+    app.add_plugins(LeaderboardPlugin)
+        .insert_resource(StartOptions {
+            leaderboard_url: url.map(str::to_string),
+            ..StartOptions::default()
+        })
+        .init_resource::<Outcomes>()
+        .add_systems(Last, record_outcomes);
+    app
+}
+
+fn submit(app: &mut App, score: u64) {
+    app.world.send_event(SubmitScore {
+        name: "kayla".to_string(),
+        score,
+    });
+    run_frames(app, 2);
+}
+
+#[test]
+fn the_stub_answers_like_the_score_api() {
+    let mut app = leaderboard_app(Some("https://scores.example/api"));
+    submit(&mut app, 1200);
+    app.world.resource_mut::<StubLeaderboard>().0 = Some(Ok(false));
+    submit(&mut app, 1300);
+    app.world.resource_mut::<StubLeaderboard>().0 = Some(Err("the server answered 500".into()));
+    submit(&mut app, 1400);
+
+    let outcomes = app.world.resource::<Outcomes>();
+    assert_eq!(
+        outcomes.submitted,
+        vec![
+            ScoreSubmitted { accepted: true },
+            ScoreSubmitted { accepted: false }
+        ]
+    );
+    assert_eq!(
+        outcomes.failed,
+        vec![ScoreSubmitFailed {
+            reason: "the server answered 500".to_string()
+        }]
+    );
+    assert_eq!(app.world.resource::<ScoreSubmissions>().in_flight(), 0);
+}
+
+#[test]
+fn submitting_without_a_leaderboard_url_fails() {
+    let mut app = leaderboard_app(None);
+    submit(&mut app, 1200);
+    let outcomes = app.world.resource::<Outcomes>();
+    assert!(outcomes.submitted.is_empty());
+    assert_eq!(outcomes.failed.len(), 1);
+    assert!(outcomes.failed[0].reason.contains("leaderboard_url"));
+}
+
+#[test]
+fn unanswered_submissions_time_out() {
+    let mut app = leaderboard_app(Some("https://scores.example/api"));
+    app.insert_resource(StubLeaderboard(None));
+    submit(&mut app, 1200);
+    assert_eq!(app.world.resource::<ScoreSubmissions>().in_flight(), 1);
+
+    let timeout_frames = (SUBMIT_TIMEOUT / FRAME.as_secs_f32()) as u32;
+    run_frames(&mut app, timeout_frames - 10);
+    assert!(app.world.resource::<Outcomes>().failed.is_empty());
+
+    run_frames(&mut app, 10);
+    let outcomes = app.world.resource::<Outcomes>();
+    assert!(outcomes.submitted.is_empty());
+    assert_eq!(outcomes.failed.len(), 1);
+    assert!(outcomes.failed[0].reason.contains("no answer"));
+    assert_eq!(app.world.resource::<ScoreSubmissions>().in_flight(), 0);
+}
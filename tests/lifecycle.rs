@@ -0,0 +1,107 @@
+//! Tests for stopping and restarting the game from the page.
+
+use bevy::{app::AppExit, audio::AudioSource, prelude::*};
+use gamedevjam2024::gfx::{CameraShakeOffset, MainCamera, ScreenFade};
+use gamedevjam2024::helpers::tiled::LoadMap;
+use gamedevjam2024::lifecycle::{
+    exit_on_stop, is_running, restart, stop, Gameplay, LifecyclePlugin, ResetGame, StopSignal,
+};
+use gamedevjam2024::options::StartOptions;
+use gamedevjam2024::sound::{StopAmbient, StopDucking, StopMusic};
+
+#[test]
+fn stop_without_a_running_game_does_nothing() {
+    assert!(!is_running());
+    stop();
+    stop();
+    restart();
+    assert!(!is_running());
+}
+
+#[test]
+fn a_raised_stop_signal_silences_every_sound_and_exits() {
+    let signal = StopSignal::default();
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_event::<AppExit>()
+        .insert_resource(signal.clone())
+        .add_systems(First, exit_on_stop);
+    let sound = app.world.spawn(Handle::<AudioSource>::default()).id();
+    let other = app.world.spawn(Transform::default()).id();
+
+    app.update();
+    assert!(app.world.get_entity(sound).is_some());
+    assert!(app.world.resource::<Events<AppExit>>().is_empty());
+
+    signal.stop();
+    app.update();
+    assert!(app.world.get_entity(sound).is_none());
+    assert!(app.world.get_entity(other).is_some());
+    assert_eq!(app.world.resource::<Events<AppExit>>().len(), 1);
+}
+
+#[test]
+fn restarting_resets_the_run_and_goes_back_to_the_initial_map() {
+    let plugin = LifecyclePlugin::default();
+    let restart = plugin.restart.clone();
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, plugin))
+        .add_event::<LoadMap>()
+        .add_event::<StopMusic>()
+        .add_event::<StopAmbient>()
+        .add_event::<StopDucking>()
+        .init_resource::<ScreenFade>()
+        .insert_resource(StartOptions {
+            map: "first.tmx".to_string(),
+            ..StartOptions::default()
+        });
+    let enemy = app.world.spawn(Gameplay).id();
+    let loot = app.world.spawn(Transform::default()).id();
+    app.world.entity_mut(enemy).add_child(loot);
+    let settings = app.world.spawn(Transform::default()).id();
+    let camera = app
+        .world
+        .spawn((
+            MainCamera {},
+            Transform::from_xyz(40.0, 12.0, 0.0),
+            CameraShakeOffset(Vec2::ONE),
+        ))
+        .id();
+    app.world.resource_mut::<ScreenFade>().fade_out(2.0);
+    app.world.resource_mut::<ScreenFade>().tick(1.0);
+
+    app.update();
+    assert!(app.world.get_entity(enemy).is_some());
+
+    restart.restart();
+    app.update();
+    assert!(app.world.get_entity(enemy).is_none());
+    assert!(app.world.get_entity(loot).is_none());
+    assert!(app.world.get_entity(settings).is_some());
+    assert_eq!(
+        *app.world.get::<Transform>(camera).unwrap(),
+        Transform::default()
+    );
+    assert_eq!(
+        app.world.get::<CameraShakeOffset>(camera).unwrap().0,
+        Vec2::ZERO
+    );
+    assert_eq!(app.world.resource::<ScreenFade>().alpha(), 0.0);
+    assert!(app.world.resource::<ScreenFade>().is_done());
+
+    let loads = app.world.resource::<Events<LoadMap>>();
+    let paths = loads
+        .get_reader()
+        .read(loads)
+        .map(|load| load.path.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["first.tmx".to_string()]);
+    let stops = app.world.resource::<Events<StopMusic>>();
+    assert_eq!(stops.get_reader().read(stops).count(), 1);
+
+    // Rust code resets the same way
+    let enemy = app.world.spawn(Gameplay).id();
+    app.world.send_event(ResetGame);
+    app.update();
+    assert!(app.world.get_entity(enemy).is_none());
+}
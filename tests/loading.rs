@@ -0,0 +1,71 @@
+//! Tests for the Loading state.
+
+use bevy::prelude::*;
+use gamedevjam2024::loading::{LoadProgress, LoadingScreen, LoadingSettings, PreloadAssets};
+use gamedevjam2024::state::{AppState, AppStatePlugin};
+use gamedevjam2024::testing::headless_app;
+use std::time::Duration;
+
+fn loading_app() -> App {
+    let mut app = headless_app();
+    app.add_plugins(AppStatePlugin);
+    app
+}
+
+fn state(app: &App) -> AppState {
+    *app.world.resource::<State<AppState>>().get()
+}
+
+fn preload(app: &mut App, path: &'static str) {
+    let handle = app.world.resource::<AssetServer>().load::<Image>(path);
+    app.world.resource_mut::<PreloadAssets>().add(handle);
+}
+
+fn loading_screens(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<Entity, With<LoadingScreen>>()
+        .iter(&app.world)
+        .count()
+}
+
+#[test]
+fn loading_waits_for_its_assets_and_counts_failures() {
+    let mut app = loading_app();
+    // no loader reads .png without a renderer, so it fails once the server gets to it
+    preload(&mut app, "missing.png");
+    app.update();
+    assert_eq!(loading_screens(&mut app), 1);
+
+    for _ in 0..200 {
+        if state(&app) != AppState::Loading {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+        app.update();
+    }
+    assert_eq!(state(&app), AppState::MainMenu);
+    assert_eq!(
+        *app.world.resource::<LoadProgress>(),
+        LoadProgress {
+            loaded: 0,
+            total: 1,
+            failed: 1,
+        }
+    );
+    assert_eq!(loading_screens(&mut app), 0);
+}
+
+#[test]
+fn loading_gives_up_after_its_timeout() {
+    let mut app = loading_app();
+    app.insert_resource(LoadingSettings {
+        timeout: 0.0,
+        next: AppState::InGame,
+    });
+    preload(&mut app, "missing.png");
+
+    app.update();
+    app.update();
+    assert_eq!(state(&app), AppState::InGame);
+    assert_eq!(app.world.resource::<LoadProgress>().total, 1);
+}
@@ -0,0 +1,125 @@
+#![cfg(feature = "test-harness")]
+
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{FloatingText, SpawnFloatingText};
+use gamedevjam2024::locale::{
+    interpolate, Locale, LocaleChanged, LocalizedText, SetLanguage, FALLBACK_LANGUAGE, LANGUAGES,
+};
+use gamedevjam2024::settings::Settings;
+use gamedevjam2024::t;
+use gamedevjam2024::testing::game_app;
+use std::collections::HashMap;
+
+#[test]
+fn language_tags_match_their_translation() {
+    assert_eq!(Locale::supported("de"), Some("de"));
+    assert_eq!(Locale::supported("de-AT"), Some("de"));
+    assert_eq!(Locale::supported("EN_us"), Some("en"));
+    assert_eq!(Locale::supported("fr-FR"), None);
+    assert_eq!(Locale::new(Some("fr")).language(), FALLBACK_LANGUAGE);
+    assert_eq!(Locale::new(None).language(), FALLBACK_LANGUAGE);
+}
+
+#[test]
+fn placeholders_are_filled_in() {
+    let english = Locale::new(Some("en"));
+    assert_eq!(
+        t!(english, "hud.coins_collected", count = 3),
+        "Collected 3 coins"
+    );
+    let german = Locale::new(Some("de"));
+    assert_eq!(
+        t!(german, "hud.coins_collected", count = 3),
+        "3 Münzen gesammelt"
+    );
+    assert_eq!(
+        interpolate("{a} and {b}", &[("a", "1")]),
+        "1 and {b}".to_string()
+    );
+}
+
+#[test]
+fn missing_keys_fall_back_to_english_then_the_key() {
+    let german = Locale::new(Some("de"));
+    assert_eq!(german.get("no.such.key"), None);
+    assert_eq!(t!(german, "no.such.key"), "no.such.key");
+}
+
+#[test]
+fn every_translation_parses_and_only_has_english_keys() {
+    let tables: HashMap<&str, HashMap<String, String>> = LANGUAGES
+        .iter()
+        .map(|(language, text)| (*language, ron::from_str(text).unwrap()))
+        .collect();
+    let english = &tables[FALLBACK_LANGUAGE];
+    for (language, table) in &tables {
+        for key in table.keys() {
+            assert!(
+                english.contains_key(key),
+                "{} has {}, en doesn't",
+                language,
+                key
+            );
+        }
+    }
+}
+
+#[test]
+fn switching_language_refreshes_localized_text() {
+    let mut app = game_app();
+    let label = app
+        .world
+        .spawn((
+            Text::from_section("", TextStyle::default()),
+            LocalizedText::new("menu.build").with_arg("build", "v1"),
+        ))
+        .id();
+    app.update();
+    assert_eq!(
+        app.world.get::<Text>(label).unwrap().sections[0].value,
+        "Build v1"
+    );
+
+    app.world.send_event(SetLanguage("de-DE".to_string()));
+    app.update();
+    assert_eq!(app.world.resource::<Locale>().language(), "de");
+    assert_eq!(
+        app.world.get::<Text>(label).unwrap().sections[0].value,
+        "Version v1"
+    );
+    assert_eq!(
+        app.world.resource::<Settings>().language.as_deref(),
+        Some("de")
+    );
+    let events = app.world.resource::<Events<LocaleChanged>>();
+    let changed: Vec<_> = events
+        .get_reader()
+        .read(events)
+        .map(|e| e.language)
+        .collect();
+    assert_eq!(changed, vec!["de"]);
+}
+
+#[test]
+fn unknown_languages_are_ignored() {
+    let mut app = game_app();
+    app.world.send_event(SetLanguage("xx".to_string()));
+    app.update();
+    assert_eq!(app.world.resource::<Locale>().language(), FALLBACK_LANGUAGE);
+    assert_eq!(app.world.resource::<Settings>().language, None);
+}
+
+#[test]
+fn floating_text_can_be_localized() {
+    let mut app = game_app();
+    app.insert_resource(Locale::new(Some("de")));
+    app.world.send_event(
+        SpawnFloatingText::localized("hud.coins_collected", Vec2::ZERO).with_arg("count", 5),
+    );
+    app.update();
+    let text = app
+        .world
+        .query_filtered::<&Text, With<FloatingText>>()
+        .single(&app.world);
+    assert_eq!(text.sections[0].value, "5 Münzen gesammelt");
+}
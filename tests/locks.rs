@@ -0,0 +1,193 @@
+//! Tests for locked doors and the keys opening them.
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use gamedevjam2024::helpers::tiled::{CollisionMap, LoadMap, TiledMap, TiledMapBundle, TmxExtras};
+use gamedevjam2024::locks::{DoorUnlocked, LockedDoor, LocksPlugin, UNLOCK_SOUND};
+use gamedevjam2024::physics::AabbCollider;
+use gamedevjam2024::pickups::PickupsPlugin;
+use gamedevjam2024::player::Player;
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::toast::ShowToast;
+use gamedevjam2024::ui::Interacted;
+use std::io::Cursor;
+use std::path::Path;
+
+const MAP_PATH: &str = "level1.tmx";
+const DOOR: u32 = 1;
+
+// a wall with a red door in the middle, and a red key to its right
+const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="3">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="collision" width="3" height="2">
+  <data encoding="csv">
+0,1,0,
+1,1,1
+</data>
+ </layer>
+ <objectgroup id="2" name="objects">
+  <object id="1" type="locked_door" x="16" y="0" width="16" height="32">
+   <properties>
+    <property name="lock" value="red"/>
+   </properties>
+  </object>
+  <object id="2" type="key" x="40" y="8">
+   <properties>
+    <property name="key" value="red"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+fn parse_map(tmx: &'static str) -> tiled::Map {
+    struct MemoryReader(&'static str);
+
+    impl tiled::ResourceReader for MemoryReader {
+        type Resource = Cursor<&'static [u8]>;
+        type Error = std::io::Error;
+
+        fn read_from(&mut self, path: &Path) -> Result<Self::Resource, Self::Error> {
+            if path == Path::new("test.tmx") {
+                return Ok(Cursor::new(self.0.as_bytes()));
+            }
+            Err(std::io::ErrorKind::NotFound.into())
+        }
+    }
+
+    tiled::Loader::with_cache_and_reader(tiled::DefaultResourceCache::new(), MemoryReader(tmx))
+        .load_tmx_map("test.tmx")
+        .expect("test map should parse")
+}
+
+/// The game with MAP as the current map and the player left of the door, after `progress`
+fn locks_app(progress: GameProgress) -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((TransformPlugin, PickupsPlugin, LocksPlugin))
+        .insert_resource(progress);
+    app.world.send_event(LoadMap::new(MAP_PATH));
+    run_frames(&mut app, 1);
+
+    let map = TiledMap {
+        map: parse_map(MAP),
+        tilemap_textures: vec![(0, TilemapTexture::Single(Handle::default()))]
+            .into_iter()
+            .collect(),
+        tile_image_offsets: Default::default(),
+        image_layers: Default::default(),
+        tmx: TmxExtras::read(MAP),
+    };
+    let handle = app.world.resource_mut::<Assets<TiledMap>>().add(map);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle,
+        ..Default::default()
+    });
+    let player = app
+        .world
+        .spawn((
+            Player,
+            AabbCollider::new(Vec2::splat(4.0)),
+            TransformBundle::from_transform(Transform::from_xyz(-40.0, 0.0, 0.0)),
+        ))
+        .id();
+    run_frames(&mut app, 2);
+    (app, player)
+}
+
+fn move_player(app: &mut App, player: Entity, x: f32, y: f32) {
+    app.world.get_mut::<Transform>(player).unwrap().translation = Vec3::new(x, y, 0.0);
+    run_frames(app, 2);
+}
+
+fn doors(app: &mut App) -> Vec<Entity> {
+    app.world
+        .query_filtered::<Entity, With<LockedDoor>>()
+        .iter(&app.world)
+        .collect()
+}
+
+/// Whether the door's two tiles are solid
+fn door_closed(app: &App) -> [bool; 2] {
+    let collision = app.world.resource::<CollisionMap>();
+    [collision.is_solid(1, 0), collision.is_solid(1, 1)]
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+fn progress(app: &App) -> &GameProgress {
+    app.world.resource::<GameProgress>()
+}
+
+#[test]
+fn keys_open_their_doors_for_good() {
+    let (mut app, player) = locks_app(GameProgress::default());
+    let door = doors(&mut app)[0];
+    assert_eq!(door_closed(&app), [true, true]);
+
+    // walking into it without the key does nothing, interacting says it's locked
+    move_player(&mut app, player, -13.0, 0.0);
+    assert_eq!(doors(&mut app), vec![door]);
+    drain::<ShowToast>(&mut app);
+    app.world.send_event(Interacted { entity: door });
+    run_frames(&mut app, 1);
+    assert!(drain::<ShowToast>(&mut app)
+        .iter()
+        .any(|toast| toast.text == "It's locked."));
+    assert_eq!(door_closed(&app), [true, true]);
+
+    // the key, over on the right
+    move_player(&mut app, player, 16.0, 8.0);
+    assert!(progress(&app).has_key("red"));
+    drain::<PlaySFX>(&mut app);
+
+    move_player(&mut app, player, 13.0, 0.0);
+    assert!(doors(&mut app).is_empty());
+    assert_eq!(door_closed(&app), [false, false]);
+    // the wall on either side stays
+    assert!(app.world.resource::<CollisionMap>().is_solid(0, 0));
+    assert!(app.world.resource::<CollisionMap>().is_solid(2, 0));
+    assert!(!progress(&app).has_key("red"));
+    assert!(progress(&app).has_unlocked(MAP_PATH, DOOR));
+    assert_eq!(
+        drain::<DoorUnlocked>(&mut app),
+        vec![DoorUnlocked {
+            entity: door,
+            lock: "red".to_string(),
+        }]
+    );
+    assert!(drain::<PlaySFX>(&mut app)
+        .iter()
+        .any(|sfx| sfx.name == UNLOCK_SOUND));
+}
+
+#[test]
+fn doors_opened_before_spawn_open() {
+    let mut progress = GameProgress::default();
+    progress.unlock(MAP_PATH, DOOR);
+    let (mut app, _) = locks_app(progress);
+    assert!(doors(&mut app).is_empty());
+    assert_eq!(door_closed(&app), [false, false]);
+    assert!(drain::<DoorUnlocked>(&mut app).is_empty());
+}
+
+#[test]
+fn keys_are_counted_per_lock() {
+    let mut progress = GameProgress::default();
+    assert!(!progress.use_key("red"));
+    progress.add_key("red");
+    progress.add_key("red");
+    progress.add_key("blue");
+    assert!(progress.use_key("red"));
+    assert!(progress.has_key("red"));
+    assert!(progress.use_key("red"));
+    assert!(!progress.has_key("red"));
+    assert!(progress.has_key("blue"));
+    assert_eq!(progress.keys.len(), 1);
+}
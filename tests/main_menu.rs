@@ -0,0 +1,145 @@
+//! Tests for the main menu and menu navigation.
+
+use bevy::prelude::*;
+use gamedevjam2024::save::LoadGame;
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    ContinueAvailable, MainMenuButton, MainMenuRoot, MenuButton, MenuFocus, OpenSettings, UiPlugin,
+};
+
+/// The game on the main menu, with no save to continue
+fn menu_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.insert_resource(ContinueAvailable(false));
+    run_frames(&mut app, 1);
+    app
+}
+
+fn state(app: &App) -> AppState {
+    *app.world.resource::<State<AppState>>().get()
+}
+
+fn button(app: &mut App, which: MainMenuButton) -> Entity {
+    app.world
+        .query::<(Entity, &MainMenuButton)>()
+        .iter(&app.world)
+        .find(|(_, button)| **button == which)
+        .map(|(entity, _)| entity)
+        .unwrap()
+}
+
+fn focused(app: &mut App) -> Option<MainMenuButton> {
+    let focus = app.world.resource::<MenuFocus>().0?;
+    app.world.get::<MainMenuButton>(focus).copied()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+#[test]
+fn the_menu_comes_and_goes_with_its_state() {
+    let mut app = menu_app();
+    assert_eq!(state(&app), AppState::MainMenu);
+    let count = |app: &mut App| {
+        app.world
+            .query_filtered::<(), With<MainMenuRoot>>()
+            .iter(&app.world)
+            .count()
+    };
+    assert_eq!(count(&mut app), 1);
+    let buttons = app
+        .world
+        .query::<&MainMenuButton>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(buttons, MainMenuButton::SHOWN.len());
+
+    app.world.send_event(ChangeState(AppState::GameOver));
+    run_frames(&mut app, 2);
+    assert_eq!(count(&mut app), 0);
+    // the game over screen has the focus now
+    assert_eq!(focused(&mut app), None);
+
+    // back from game over, there's still just the one
+    app.world.send_event(ChangeState(AppState::MainMenu));
+    run_frames(&mut app, 2);
+    assert_eq!(count(&mut app), 1);
+}
+
+#[test]
+fn the_keys_move_past_continue_without_a_save_and_play_starts_the_game() {
+    let mut app = menu_app();
+    let continue_button = button(&mut app, MainMenuButton::Continue);
+    assert!(
+        !app.world
+            .get::<MenuButton>(continue_button)
+            .unwrap()
+            .enabled
+    );
+    assert_eq!(focused(&mut app), Some(MainMenuButton::Play));
+    drain::<PlaySFX>(&mut app);
+
+    tap(&mut app, KeyCode::KeyS);
+    assert_eq!(focused(&mut app), Some(MainMenuButton::Settings));
+    let sounds: Vec<String> = drain::<PlaySFX>(&mut app)
+        .into_iter()
+        .map(|sfx| sfx.name)
+        .collect();
+    assert_eq!(sounds, vec!["ui_hover".to_string()]);
+
+    tap(&mut app, KeyCode::KeyE);
+    assert_eq!(drain::<OpenSettings>(&mut app).len(), 1);
+    // backing out of the settings, which the menu made way for
+    tap(&mut app, KeyCode::Escape);
+    assert_eq!(focused(&mut app), Some(MainMenuButton::Settings));
+
+    tap(&mut app, KeyCode::ArrowUp);
+    assert_eq!(focused(&mut app), Some(MainMenuButton::Play));
+    // wrapping around
+    tap(&mut app, KeyCode::ArrowUp);
+    assert_ne!(focused(&mut app), Some(MainMenuButton::Play));
+    tap(&mut app, KeyCode::ArrowDown);
+
+    tap(&mut app, KeyCode::Space);
+    run_frames(&mut app, 1);
+    assert_eq!(state(&app), AppState::InGame);
+}
+
+#[test]
+fn clicking_continue_loads_the_save_once_there_is_one() {
+    let mut app = menu_app();
+    let continue_button = button(&mut app, MainMenuButton::Continue);
+    app.world
+        .entity_mut(continue_button)
+        .insert(Interaction::Pressed);
+    run_frames(&mut app, 1);
+    assert!(drain::<LoadGame>(&mut app).is_empty());
+
+    app.insert_resource(ContinueAvailable(true));
+    app.world
+        .entity_mut(continue_button)
+        .insert(Interaction::Hovered);
+    run_frames(&mut app, 1);
+    assert_eq!(focused(&mut app), Some(MainMenuButton::Continue));
+    app.world
+        .entity_mut(continue_button)
+        .insert(Interaction::Pressed);
+    run_frames(&mut app, 1);
+    assert_eq!(drain::<LoadGame>(&mut app).len(), 1);
+}
@@ -0,0 +1,130 @@
+//! Tests for the .assets.ron manifest.
+
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{AnimationResource, AnimationType};
+use gamedevjam2024::manifest::{
+    patch_animations, AssetManifest, AssetManifestPlugin, AssetManifests, AtlasEntry,
+};
+use gamedevjam2024::testing::headless_app;
+
+const MANIFEST: &str = r#"(
+    images: [
+        (
+            path: "graphics/hero.png",
+            atlas: Some((tile_size: (16.0, 16.0), columns: 4, rows: 2)),
+            animations: [
+                (name: "walking", first: 0, last: 3, frame_time: 0.1, kind: Repeat),
+                (name: "dying", first: 4, last: 7, frame_time: 0.2, kind: Despawn),
+            ],
+        ),
+        (atlas: None),
+        (path: "graphics/sky.png", animations: [(name: "twinkle", first: 0, last: 1, frame_time: 0.5, kind: Repeat)]),
+    ],
+    sounds: ["sounds/game.sounds.ron", 3],
+    shaders: ["glow.wgsl"],
+)"#;
+
+#[test]
+fn bad_entries_are_reported_and_skipped() {
+    let (manifest, errors) = AssetManifest::parse(MANIFEST.as_bytes()).unwrap();
+
+    assert_eq!(manifest.images.len(), 2);
+    assert_eq!(manifest.images[0].path, "graphics/hero.png");
+    assert_eq!(
+        manifest.images[0].atlas,
+        Some(AtlasEntry {
+            tile_size: (16.0, 16.0),
+            columns: 4,
+            rows: 2,
+            padding: None,
+            offset: None,
+        })
+    );
+    assert_eq!(
+        manifest.images[0].animations[1].kind,
+        AnimationType::Despawn
+    );
+    assert_eq!(manifest.sounds, vec!["sounds/game.sounds.ron".to_string()]);
+
+    assert_eq!(errors.len(), 3, "{:?}", errors);
+    assert!(errors.iter().any(|e| e.starts_with("skipping images #1")));
+    assert!(errors.iter().any(|e| e.starts_with("skipping sounds #1")));
+    assert!(errors.contains(&"unknown asset kind shaders".to_string()));
+
+    assert!(AssetManifest::parse(b"(images: [").is_err());
+}
+
+#[test]
+fn the_game_manifest_parses() {
+    let (manifest, errors) =
+        AssetManifest::parse(include_bytes!("../assets/game.assets.ron")).unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(manifest.sounds, vec!["sounds/game.sounds.ron".to_string()]);
+}
+
+#[test]
+fn applied_manifests_register_their_animations() {
+    let mut app = headless_app();
+    app.add_plugins(AssetManifestPlugin);
+    let (manifest, _) = AssetManifest::parse(MANIFEST.as_bytes()).unwrap();
+    let handle = app
+        .world
+        .resource_mut::<Assets<AssetManifest>>()
+        .add(manifest);
+    app.world.resource_mut::<AssetManifests>().add(handle);
+    app.update();
+
+    assert!(app.world.resource::<AssetManifests>().is_applied());
+    let animations = app.world.resource::<AnimationResource>();
+    let walking = animations.get("walking").unwrap();
+    assert_eq!(
+        walking.texture().path().map(|path| path.to_string()),
+        Some("graphics/hero.png".to_string())
+    );
+    let layouts = app.world.resource::<Assets<TextureAtlasLayout>>();
+    assert_eq!(layouts.get(walking.atlas()).unwrap().len(), 8);
+    assert!(animations.get("dying").is_some());
+    // sky.png has no atlas to cut frames from
+    assert!(animations.get("twinkle").is_none());
+}
+
+fn patch(app: &mut App, old: &AssetManifest, new: &AssetManifest) -> Vec<String> {
+    app.world
+        .resource_scope(|world, mut animations: Mut<AnimationResource>| {
+            world.resource_scope(|world, mut layouts: Mut<Assets<TextureAtlasLayout>>| {
+                let asset_server = world.resource::<AssetServer>();
+                patch_animations(asset_server, &mut layouts, &mut animations, None, old, new)
+            })
+        })
+}
+
+#[test]
+fn patching_in_a_changed_manifest_updates_its_animations() {
+    let mut app = headless_app();
+    app.add_plugins(AssetManifestPlugin);
+    let (old, _) = AssetManifest::parse(MANIFEST.as_bytes()).unwrap();
+    let handle = app
+        .world
+        .resource_mut::<Assets<AssetManifest>>()
+        .add(old.clone());
+    app.world.resource_mut::<AssetManifests>().add(handle);
+    app.update();
+    let changed = MANIFEST
+        .replace("frame_time: 0.1", "frame_time: 0.05")
+        .replace("name: \"dying\"", "name: \"falling\"");
+    let (new, _) = AssetManifest::parse(changed.as_bytes()).unwrap();
+
+    assert_eq!(
+        patch(&mut app, &old, &new),
+        vec![
+            "animation dying removed".to_string(),
+            "animation falling added".to_string(),
+            "animation walking changed".to_string(),
+        ]
+    );
+    let animations = app.world.resource::<AnimationResource>();
+    assert!(animations.get("dying").is_none());
+    assert!(animations.get("falling").is_some());
+    assert!(animations.get("walking").is_some());
+    assert!(patch(&mut app, &new, &new).is_empty());
+}
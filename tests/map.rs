@@ -0,0 +1,2980 @@
+//! Tests for the map data built from Tiled maps, run without loading any assets.
+
+use bevy::{
+    asset::LoadState, prelude::*, sprite::Anchor, text::Text2dBounds, time::TimeUpdateStrategy,
+};
+use bevy_ecs_tilemap::prelude::*;
+use gamedevjam2024::destructible::{DamageTile, DestructiblePlugin, TileDestroyed, TileHealth};
+use gamedevjam2024::gfx::{CameraShakeOffset, MainCamera, SpriteLayer};
+use gamedevjam2024::helpers::tiled::{
+    animate_tiles, chunked_tiles_by_tileset, register_tile_animations, tiles_by_tileset,
+    validate_map, ColliderShape, CollisionMap, Connectivity, CurrentMap, Door, DoorTransition,
+    HiddenLayers, ImageLayerTexture, LayerDepths, LayerName, LayerParallax, LoadMap, MapBounds,
+    MapLoaded, MapMarkers, MapProperties, MapSpawnBudget, MapSpawning, MapUnloaded,
+    MinimapSettings, MinimapTexture, NavGrid, ObjectShape, PathBlocked, PathCompleted, PathFollow,
+    PathOptions, PlacedAtSpawn, PlacedTile, RegisterLayerMarker, RegisterTileProperty,
+    RegisterTiledObject, SetLayerTint, SetLayerVisibility, SetTile, ShapeCollider, SpawnPointName,
+    SpawnPointReady, TileAnimation, TileCollider, TileEditLog, TileFrame, TileLookup,
+    TileProperties, TileStreaming, TiledImageLayer, TiledLayer, TiledMap, TiledMapBundle,
+    TiledMapPlugin, TiledObject, TiledText, TiledWorld, TiledWorldBundle, TilemapAnimations,
+    TilesetTile, TmxExtras, TriggerEntered, TriggerExited, TriggerOccupancy, TriggerRegion,
+    TriggerSensor, UnloadMap, WorldMap, WorldMembers,
+};
+use gamedevjam2024::sound::PlaySFX;
+use std::io::Cursor;
+use std::path::Path;
+use std::time::Duration;
+
+fn collision_map() -> CollisionMap {
+    let mut collision = CollisionMap::default();
+    // a 10×4 map of 16px tiles centred on the origin
+    collision.reset(
+        UVec2::new(10, 4),
+        Vec2::splat(16.0),
+        Vec2::new(-80.0, -32.0),
+    );
+    collision
+}
+
+#[test]
+fn collision_map_tracks_solid_tiles() {
+    let mut collision = collision_map();
+    collision.set_solid(0, 0, true);
+    collision.set_solid(9, 3, true);
+    collision.set_solid(12, 0, true);
+
+    assert!(collision.is_solid(0, 0));
+    assert!(collision.is_solid(9, 3));
+    assert!(!collision.is_solid(1, 0));
+    assert!(!collision.is_solid(-1, 0));
+    assert_eq!(collision.solid_tiles().count(), 2);
+
+    collision.set_solid(0, 0, false);
+    assert!(!collision.is_solid(0, 0));
+}
+
+#[test]
+fn collision_map_converts_world_positions() {
+    let mut collision = collision_map();
+    collision.set_solid(0, 0, true);
+
+    assert_eq!(collision.tile_at(Vec2::new(-79.0, -31.0)), IVec2::new(0, 0));
+    assert_eq!(collision.tile_at(Vec2::new(-81.0, 0.0)), IVec2::new(-1, 2));
+    assert!(collision.is_solid_at(Vec2::new(-72.0, -24.0)));
+    assert_eq!(
+        collision.tile_rect(0, 0),
+        Rect::new(-80.0, -32.0, -64.0, -16.0)
+    );
+}
+
+#[test]
+fn collision_shapes_share_the_tile_queries() {
+    let mut collision = collision_map();
+    collision.set_solid(0, 0, true);
+    collision.add_shape(ColliderShape::Aabb(Rect::new(0.0, 0.0, 8.0, 8.0)));
+    // a slope rising to the right across tile (6, 1)
+    collision.add_shape(ColliderShape::Polygon(vec![
+        Vec2::new(16.0, -16.0),
+        Vec2::new(32.0, -16.0),
+        Vec2::new(32.0, 0.0),
+    ]));
+
+    assert!(collision.is_solid_at(Vec2::new(4.0, 4.0)));
+    assert!(collision.is_solid_at(Vec2::new(30.0, -14.0)));
+    assert!(!collision.is_solid_at(Vec2::new(18.0, -2.0)));
+
+    // ends exactly where tile (0, 0) begins
+    assert!(!collision.overlaps(Rect::new(-100.0, -50.0, -80.0, -40.0)));
+    assert!(collision.overlaps(Rect::new(-90.0, -40.0, -79.0, -31.0)));
+    assert!(collision.overlaps(Rect::new(6.0, 6.0, 12.0, 12.0)));
+    assert!(collision.overlaps(Rect::new(28.0, -12.0, 40.0, -8.0)));
+    assert!(!collision.overlaps(Rect::new(16.0, -4.0, 20.0, -1.0)));
+}
+
+// parses a TMX from memory, with any external file it references missing
+fn parse_map(tmx: &'static str) -> tiled::Map {
+    parse_map_with(tmx, &[])
+}
+
+// parses a TMX from memory, along with the external files it references by path
+fn parse_map_with(tmx: &'static str, files: &'static [(&'static str, &'static str)]) -> tiled::Map {
+    struct MemoryReader {
+        tmx: &'static str,
+        files: &'static [(&'static str, &'static str)],
+    }
+
+    impl tiled::ResourceReader for MemoryReader {
+        type Resource = Cursor<&'static [u8]>;
+        type Error = std::io::Error;
+
+        fn read_from(&mut self, path: &Path) -> Result<Self::Resource, Self::Error> {
+            if path == Path::new("test.tmx") {
+                return Ok(Cursor::new(self.tmx.as_bytes()));
+            }
+            self.files
+                .iter()
+                .find(|(name, _)| path == Path::new(name))
+                .map(|(_, contents)| Cursor::new(contents.as_bytes()))
+                .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+        }
+    }
+
+    let reader = MemoryReader { tmx, files };
+    tiled::Loader::with_cache_and_reader(tiled::DefaultResourceCache::new(), reader)
+        .load_tmx_map("test.tmx")
+        .expect("test map should parse")
+}
+
+const TWO_TILESETS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <tileset firstgid="5" name="decor" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="decor.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="2" height="2">
+  <data encoding="csv">
+1,6,
+8,0
+</data>
+ </layer>
+</map>
+"#;
+
+#[test]
+fn layers_mixing_tilesets_are_split_with_local_indices() {
+    let map = parse_map(TWO_TILESETS);
+    let layer = map.get_layer(0).unwrap();
+    let Some(tiled::TileLayer::Finite(layer)) = layer.as_tile_layer() else {
+        panic!("expected a finite tile layer");
+    };
+
+    let tiles: Vec<Vec<(usize, u32, u32, u32)>> = tiles_by_tileset(&layer)
+        .into_values()
+        .map(|tiles| {
+            tiles
+                .iter()
+                .map(|tile: &PlacedTile| (tile.tileset_index, tile.id, tile.pos.x, tile.pos.y))
+                .collect()
+        })
+        .collect();
+    // (tileset, local index, x, y) with y up: the first TMX row is y = 1
+    assert_eq!(
+        tiles,
+        vec![vec![(0, 0, 0, 1)], vec![(1, 3, 0, 0), (1, 1, 1, 1)]]
+    );
+}
+
+// chunks up and left of the origin, one down and right, and one left empty
+const INFINITE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="30" height="20" tilewidth="16" tileheight="16" infinite="1" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="30" height="20">
+  <data encoding="csv">
+   <chunk x="-3" y="-1" width="2" height="1">
+1,0
+</chunk>
+   <chunk x="4" y="2" width="1" height="1">
+2
+</chunk>
+   <chunk x="40" y="40" width="1" height="1">
+0
+</chunk>
+  </data>
+ </layer>
+</map>
+"#;
+
+#[test]
+fn infinite_maps_cover_the_union_of_their_chunks() {
+    let asset = tiled_map(INFINITE);
+    let bounds = MapBounds::of(&asset);
+    let map = &asset.map;
+    assert_eq!(bounds.min, IVec2::new(-16, -16));
+    assert_eq!(bounds.size, UVec2::new(32, 32));
+    assert_eq!(bounds.world_rect(), Rect::new(-256.0, -256.0, 256.0, 256.0));
+    // the top left corner of TMX tile (0, 0) is the centre of the bounds
+    assert_eq!(bounds.tmx_to_world(0.0, 0.0), Vec2::ZERO);
+
+    let layer = map.get_layer(0).unwrap();
+    let Some(tiled::TileLayer::Infinite(layer)) = layer.as_tile_layer() else {
+        panic!("expected an infinite tile layer");
+    };
+    let mut tiles: Vec<(u32, u32, u32)> = chunked_tiles_by_tileset(&layer, &bounds)
+        .into_values()
+        .flatten()
+        .map(|tile| (tile.id, tile.pos.x, tile.pos.y))
+        .collect();
+    tiles.sort();
+    // TMX (-3, -1) and (4, 2), with y up from the bottom of the bounds
+    assert_eq!(tiles, vec![(0, 13, 16), (1, 20, 13)]);
+}
+
+#[test]
+fn finite_maps_cover_their_declared_size() {
+    let bounds = MapBounds::of(&tiled_map(TWO_TILESETS));
+    assert_eq!(bounds.min, IVec2::ZERO);
+    assert_eq!(bounds.size, UVec2::new(2, 2));
+    assert_eq!(bounds.tmx_to_world(0.0, 0.0), Vec2::new(-16.0, 16.0));
+}
+
+fn frames(frames: &[(u32, f32)]) -> TileAnimation {
+    TileAnimation::new(
+        frames
+            .iter()
+            .map(|(texture_index, duration)| TileFrame {
+                texture_index: *texture_index,
+                duration: *duration,
+            })
+            .collect(),
+    )
+}
+
+#[test]
+fn tile_animations_use_each_frame_duration() {
+    let animation = frames(&[(4, 0.1), (5, 0.3)]);
+    assert_eq!(animation.frame_at(0.0), 4);
+    assert_eq!(animation.frame_at(0.15), 5);
+    assert_eq!(animation.frame_at(0.35), 5);
+    // loops after 0.4s
+    assert_eq!(animation.frame_at(0.45), 4);
+    assert_eq!(animation.frame_at(40.15), 5);
+}
+
+fn texture_index(app: &App, tile: Entity) -> u32 {
+    app.world.get::<TileTextureIndex>(tile).unwrap().0
+}
+
+#[test]
+fn animated_tiles_stay_in_phase_and_follow_tile_swaps() {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            50,
+        )))
+        .add_systems(Update, (register_tile_animations, animate_tiles).chain());
+
+    let mut animations = TilemapAnimations::default();
+    animations.insert(2, frames(&[(10, 0.1), (11, 0.1)]));
+    animations.insert(3, frames(&[(20, 0.1), (21, 0.1)]));
+    let tilemap = app.world.spawn(animations).id();
+    let spawn_tile = |app: &mut App, id| {
+        app.world
+            .spawn((TilesetTile(id), TilemapId(tilemap), TileTextureIndex(0)))
+            .id()
+    };
+
+    let first = spawn_tile(&mut app, 2);
+    for _ in 0..3 {
+        app.update();
+    }
+    let second = spawn_tile(&mut app, 2);
+    for _ in 0..4 {
+        app.update();
+        assert!([10, 11].contains(&texture_index(&app, first)));
+        assert_eq!(texture_index(&app, first), texture_index(&app, second));
+    }
+
+    app.world.get_mut::<TilesetTile>(second).unwrap().0 = 3;
+    app.update();
+    assert!([20, 21].contains(&texture_index(&app, second)));
+
+    // a static tile keeps whatever index it was given
+    app.world.get_mut::<TilesetTile>(second).unwrap().0 = 1;
+    app.world.get_mut::<TileTextureIndex>(second).unwrap().0 = 7;
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(texture_index(&app, second), 7);
+}
+
+fn map_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), TiledMapPlugin));
+    app
+}
+
+fn map_entities(app: &mut App) -> Vec<Entity> {
+    app.world
+        .query_filtered::<Entity, With<Handle<TiledMap>>>()
+        .iter(&app.world)
+        .collect()
+}
+
+#[test]
+fn only_the_last_loaded_map_stays_live() {
+    let mut app = map_app();
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    app.update();
+    let first = app.world.resource::<CurrentMap>().entity().unwrap();
+
+    app.world.send_event(LoadMap::new("level2.tmx"));
+    app.world.send_event(LoadMap::new("level3.tmx"));
+    app.update();
+
+    let current = app.world.resource::<CurrentMap>();
+    assert_eq!(current.path(), Some("level3.tmx"));
+    let live = current.entity().unwrap();
+    assert_eq!(map_entities(&mut app), vec![live]);
+    assert!(app.world.get_entity(first).is_none());
+}
+
+#[test]
+fn unloading_takes_down_what_is_parented_to_the_map() {
+    let mut app = map_app();
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    app.update();
+    let map = app.world.resource::<CurrentMap>().entity().unwrap();
+    let child = app.world.spawn_empty().id();
+    app.world.entity_mut(map).add_child(child);
+    let player = app.world.spawn_empty().id();
+
+    app.world.send_event(UnloadMap::default());
+    app.update();
+
+    assert!(map_entities(&mut app).is_empty());
+    assert!(app.world.get_entity(child).is_none());
+    assert!(app.world.get_entity(player).is_some());
+    assert_eq!(app.world.resource::<CurrentMap>().entity(), None);
+    assert_eq!(app.world.resource::<MapBounds>(), &MapBounds::default());
+}
+
+const SOLID: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="collision" width="3" height="2">
+  <data encoding="csv">
+0,0,0,
+1,0,0
+</data>
+ </layer>
+</map>
+"#;
+
+// adds a parsed map straight to the assets, with a placeholder texture for its first tileset
+fn spawn_map(app: &mut App, tmx: &'static str) {
+    spawn_parsed_map(app, parse_map(tmx), tmx);
+}
+
+fn spawn_parsed_map(app: &mut App, map: tiled::Map, tmx: &str) {
+    let map = TiledMap {
+        map,
+        tilemap_textures: vec![(0, TilemapTexture::Single(Handle::default()))]
+            .into_iter()
+            .collect(),
+        tile_image_offsets: Default::default(),
+        image_layers: Default::default(),
+        tmx: TmxExtras::read(tmx),
+    };
+    let handle = app.world.resource_mut::<Assets<TiledMap>>().add(map);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle,
+        ..Default::default()
+    });
+}
+
+// what a MapLoaded reader could see when the event arrived
+#[derive(Resource, Default)]
+struct SeenOnLoad(Vec<(UVec2, bool, usize)>);
+
+fn record_map_loaded(
+    mut events: EventReader<MapLoaded>,
+    collision: Res<CollisionMap>,
+    tilemaps: Query<&TileStorage>,
+    mut seen: ResMut<SeenOnLoad>,
+) {
+    for event in events.read() {
+        seen.0.push((
+            event.size_in_tiles,
+            collision.is_solid(0, 0),
+            tilemaps.iter().count(),
+        ));
+    }
+}
+
+#[test]
+fn map_loaded_readers_see_the_spawned_map() {
+    let mut app = map_app();
+    app.init_resource::<SeenOnLoad>()
+        .add_systems(Update, record_map_loaded);
+
+    spawn_map(&mut app, SOLID);
+    for _ in 0..3 {
+        app.update();
+    }
+
+    assert_eq!(
+        app.world.resource::<SeenOnLoad>().0,
+        vec![(UVec2::new(3, 2), true, 1)]
+    );
+}
+
+#[test]
+fn map_bounds_give_the_world_extents() {
+    let mut app = map_app();
+    spawn_map(&mut app, SOLID);
+    app.update();
+
+    let loaded: Vec<MapBounds> = app
+        .world
+        .resource_mut::<Events<MapLoaded>>()
+        .drain()
+        .map(|event| event.bounds)
+        .collect();
+    assert_eq!(loaded, vec![*app.world.resource::<MapBounds>()]);
+    let bounds = loaded[0];
+    assert_eq!(bounds.world_rect(), Rect::new(-24.0, -16.0, 24.0, 16.0));
+    assert!(bounds.contains(Vec2::new(24.0, -16.0)));
+    assert!(!bounds.contains(Vec2::new(24.5, 0.0)));
+    assert_eq!(bounds.clamp(Vec2::new(100.0, -3.0)), Vec2::new(24.0, -3.0));
+
+    let map = map_entities(&mut app)[0];
+    app.world.despawn(map);
+    app.update();
+    let bounds = app.world.resource::<MapBounds>();
+    assert!(bounds.is_empty());
+    assert!(!bounds.contains(Vec2::ZERO));
+    assert_eq!(bounds.clamp(Vec2::new(100.0, -3.0)), Vec2::new(100.0, -3.0));
+}
+
+#[test]
+fn map_unloaded_follows_the_teardown() {
+    let mut app = map_app();
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    app.update();
+    app.world.send_event(UnloadMap::default());
+    app.update();
+
+    let unloaded: Vec<String> = app
+        .world
+        .resource_mut::<Events<MapUnloaded>>()
+        .drain()
+        .map(|event| event.path)
+        .collect();
+    assert_eq!(unloaded, vec!["level1.tmx".to_string()]);
+    assert!(map_entities(&mut app).is_empty());
+}
+
+const LAYERED: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="overlay" width="2" height="1" opacity="0.5" tintcolor="#ff0000">
+  <data encoding="csv">
+1,2
+</data>
+ </layer>
+ <layer id="2" name="notes" width="2" height="1" visible="0">
+  <data encoding="csv">
+3,0
+</data>
+ </layer>
+</map>
+"##;
+
+fn layer_visibility(app: &mut App, name: &str) -> Option<Visibility> {
+    app.world
+        .query::<(&TiledLayer, &Visibility)>()
+        .iter(&app.world)
+        .find(|(layer, _)| layer.name == name)
+        .map(|(_, visibility)| *visibility)
+}
+
+#[test]
+fn layers_keep_their_tiled_visibility_and_opacity() {
+    let mut app = map_app();
+    spawn_map(&mut app, LAYERED);
+    app.update();
+
+    assert_eq!(
+        layer_visibility(&mut app, "overlay"),
+        Some(Visibility::Inherited)
+    );
+    assert_eq!(
+        layer_visibility(&mut app, "notes"),
+        Some(Visibility::Hidden)
+    );
+    let alphas: Vec<f32> = app
+        .world
+        .query::<&TileColor>()
+        .iter(&app.world)
+        .map(|color| color.0.a())
+        .collect();
+    assert_eq!(alphas.iter().filter(|alpha| **alpha == 0.5).count(), 2);
+
+    app.world.send_event(SetLayerVisibility::new("notes", true));
+    app.update();
+    assert_eq!(
+        layer_visibility(&mut app, "notes"),
+        Some(Visibility::Inherited)
+    );
+}
+
+#[test]
+fn hidden_layers_can_be_skipped() {
+    let mut app = map_app();
+    app.insert_resource(HiddenLayers::Skip);
+    spawn_map(&mut app, LAYERED);
+    app.update();
+
+    assert!(layer_visibility(&mut app, "overlay").is_some());
+    assert_eq!(layer_visibility(&mut app, "notes"), None);
+}
+
+fn tile_colors(app: &mut App) -> Vec<Color> {
+    app.world
+        .query::<&TileColor>()
+        .iter(&app.world)
+        .map(|color| color.0)
+        .collect()
+}
+
+#[test]
+fn layer_tints_combine_with_opacity() {
+    let mut app = map_app();
+    spawn_map(&mut app, LAYERED);
+    app.update();
+    let faded_red = Color::rgba(1.0, 0.0, 0.0, 0.5);
+    assert_eq!(
+        tile_colors(&mut app)
+            .iter()
+            .filter(|color| **color == faded_red)
+            .count(),
+        2
+    );
+
+    app.world
+        .send_event(SetLayerTint::new("overlay", Color::rgb(0.0, 0.0, 1.0)));
+    app.update();
+    let faded_blue = Color::rgba(0.0, 0.0, 1.0, 0.5);
+    assert_eq!(
+        tile_colors(&mut app)
+            .iter()
+            .filter(|color| **color == faded_blue)
+            .count(),
+        2
+    );
+    // the notes layer has no tint
+    assert!(tile_colors(&mut app).contains(&Color::WHITE));
+}
+
+const NESTED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="5" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <group id="1" name="gameplay" offsetx="8" opacity="0.5">
+  <group id="2" name="inner" opacity="0.5">
+   <layer id="3" name="ground" width="2" height="1" offsety="4">
+    <data encoding="csv">
+1,2
+</data>
+   </layer>
+  </group>
+ </group>
+ <layer id="4" name="top" width="2" height="1">
+  <data encoding="csv">
+3,0
+</data>
+ </layer>
+</map>
+"#;
+
+const DISSOLVED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="2" height="1" offsetx="8" offsety="4" opacity="0.25">
+  <data encoding="csv">
+1,2
+</data>
+ </layer>
+ <layer id="2" name="top" width="2" height="1">
+  <data encoding="csv">
+3,0
+</data>
+ </layer>
+</map>
+"#;
+
+// (layer name, translation, tile colors) of every tilemap, bottom layer first
+fn rendered_layers(tmx: &'static str) -> Vec<(String, Vec3, Vec<Color>)> {
+    let mut app = map_app();
+    spawn_map(&mut app, tmx);
+    app.update();
+
+    let mut layers: Vec<(String, Vec3, Vec<Color>)> = app
+        .world
+        .query::<(&TiledLayer, &Transform, &TileStorage)>()
+        .iter(&app.world)
+        .map(|(layer, transform, storage)| {
+            let colors = storage
+                .iter()
+                .flatten()
+                .map(|tile| app.world.get::<TileColor>(*tile).unwrap().0)
+                .collect();
+            (layer.name.clone(), transform.translation, colors)
+        })
+        .collect();
+    layers.sort_by(|a, b| a.1.z.total_cmp(&b.1.z));
+    layers
+}
+
+#[test]
+fn nested_groups_render_like_dissolved_ones() {
+    assert_eq!(rendered_layers(NESTED), rendered_layers(DISSOLVED));
+
+    let mut app = map_app();
+    spawn_map(&mut app, NESTED);
+    app.update();
+    let paths: Vec<String> = app
+        .world
+        .query::<&TiledLayer>()
+        .iter(&app.world)
+        .map(|layer| layer.path.clone())
+        .collect();
+    assert!(paths.contains(&"gameplay/inner/ground".to_string()));
+    assert!(paths.contains(&"top".to_string()));
+}
+
+const BACKDROP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="4" height="2">
+  <data encoding="csv">
+1,1,1,1,
+1,1,1,1
+</data>
+ </layer>
+ <imagelayer id="2" name="clouds" offsetx="8" repeatx="1" opacity="0.5">
+  <image source="clouds.png" width="20" height="10"/>
+ </imagelayer>
+</map>
+"#;
+
+#[test]
+fn repeating_image_layers_cover_the_map() {
+    let mut app = map_app();
+    let map = TiledMap {
+        map: parse_map(BACKDROP),
+        tilemap_textures: vec![(0, TilemapTexture::Single(Handle::default()))]
+            .into_iter()
+            .collect(),
+        tile_image_offsets: Default::default(),
+        image_layers: vec![(
+            2,
+            ImageLayerTexture {
+                texture: Handle::default(),
+                size: Vec2::new(20.0, 10.0),
+                repeat: BVec2::new(true, false),
+            },
+        )]
+        .into_iter()
+        .collect(),
+        tmx: TmxExtras::read(BACKDROP),
+    };
+    let handle = app.world.resource_mut::<Assets<TiledMap>>().add(map);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle,
+        ..Default::default()
+    });
+    app.update();
+
+    let (sprite, transform) = app
+        .world
+        .query_filtered::<(&Sprite, &Transform), With<TiledImageLayer>>()
+        .single(&app.world);
+    // starts left of the map on the 20px grid through the offset, and runs past its right edge
+    assert_eq!(sprite.custom_size, Some(Vec2::new(80.0, 10.0)));
+    assert_eq!(transform.translation, Vec3::new(-44.0, 16.0, 1.0));
+    assert_eq!(sprite.color.a(), 0.5);
+}
+
+const PARALLAX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="4" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="far" width="2" height="2" parallaxx="0.5" parallaxy="0.5">
+  <data encoding="csv">
+1,1,
+1,1
+</data>
+ </layer>
+ <layer id="2" name="ground" width="2" height="2">
+  <data encoding="csv">
+1,1,
+1,1
+</data>
+ </layer>
+ <layer id="3" name="near" width="2" height="2" parallaxx="2">
+  <data encoding="csv">
+1,1,
+1,1
+</data>
+ </layer>
+</map>
+"#;
+
+fn layer_translation(app: &mut App, name: &str) -> Vec3 {
+    app.world
+        .query::<(&TiledLayer, &Transform)>()
+        .iter(&app.world)
+        .find(|(layer, _)| layer.name == name)
+        .map(|(_, transform)| transform.translation)
+        .unwrap()
+}
+
+#[test]
+fn parallax_layers_follow_the_steady_camera() {
+    let mut app = map_app();
+    let camera = app
+        .world
+        .spawn((
+            MainCamera {},
+            Transform::default(),
+            CameraShakeOffset::default(),
+        ))
+        .id();
+    spawn_map(&mut app, PARALLAX);
+    app.update();
+    app.update();
+    let far = layer_translation(&mut app, "far");
+    let ground = layer_translation(&mut app, "ground");
+    let near = layer_translation(&mut app, "near");
+
+    let ground_has_parallax = app
+        .world
+        .query::<(&TiledLayer, &LayerParallax)>()
+        .iter(&app.world)
+        .any(|(layer, _)| layer.name == "ground");
+    assert!(!ground_has_parallax);
+
+    // 10 units of camera movement, 3 of them screen shake
+    app.world.get_mut::<Transform>(camera).unwrap().translation = Vec3::new(10.0, 0.0, 0.0);
+    app.world.get_mut::<CameraShakeOffset>(camera).unwrap().0 = Vec2::new(3.0, 0.0);
+    app.update();
+
+    assert_eq!(layer_translation(&mut app, "ground"), ground);
+    assert_eq!(
+        layer_translation(&mut app, "far") - far,
+        Vec3::new(3.5, 0.0, 0.0)
+    );
+    assert_eq!(
+        layer_translation(&mut app, "near") - near,
+        Vec3::new(-7.0, 0.0, 0.0)
+    );
+}
+
+const ISOMETRIC: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="isometric" renderorder="right-down" width="3" height="2" tilewidth="64" tileheight="32" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="64" tileheight="32" tilecount="4" columns="2">
+  <image source="terrain.png" width="128" height="64"/>
+ </tileset>
+ <layer id="1" name="collision" width="3" height="2">
+  <data encoding="csv">
+0,0,0,
+1,0,0
+</data>
+ </layer>
+</map>
+"#;
+
+#[test]
+fn isometric_maps_project_onto_the_diamond() {
+    let bounds = MapBounds::of(&tiled_map(ISOMETRIC));
+    assert!(bounds.is_isometric());
+    // the box around the diamond, not 3×2 tiles
+    assert_eq!(bounds.world_rect(), Rect::new(-80.0, -40.0, 80.0, 40.0));
+    // the top corner of the diamond, then the centre of TMX tile (0, 1)
+    assert_eq!(bounds.tmx_to_world(0.0, 0.0), Vec2::new(-16.0, 40.0));
+    let center = bounds.tmx_to_world(16.0, 48.0);
+    assert_eq!(center, Vec2::new(-48.0, 8.0));
+    assert_eq!(bounds.tile_center(TilePos { x: 0, y: 0 }), center);
+
+    let mut app = map_app();
+    spawn_map(&mut app, ISOMETRIC);
+    app.update();
+    let collision = app.world.resource::<CollisionMap>();
+    assert!(collision.is_isometric());
+    assert_eq!(collision.tile_at(center), IVec2::new(0, 0));
+    assert!(collision.is_solid_at(center + Vec2::new(20.0, 0.0)));
+    // inside the tile's box but past the edge of its diamond
+    assert_eq!(
+        collision.tile_at(center + Vec2::new(30.0, 10.0)),
+        IVec2::new(0, 1)
+    );
+    assert!(!collision.overlaps(Rect::from_center_size(
+        center + Vec2::new(26.0, 12.0),
+        Vec2::splat(4.0)
+    )));
+    assert!(collision.overlaps(Rect::from_center_size(center, Vec2::splat(4.0))));
+}
+
+const POINTY_HEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="hexagonal" renderorder="right-down" width="3" height="2" tilewidth="28" tileheight="32" infinite="0" hexsidelength="16" staggeraxis="y" staggerindex="odd" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="28" tileheight="32" tilecount="4" columns="2">
+  <image source="terrain.png" width="56" height="64"/>
+ </tileset>
+ <layer id="1" name="collision" width="3" height="2">
+  <data encoding="csv">
+1,0,0,
+0,0,0
+</data>
+ </layer>
+</map>
+"#;
+
+const FLAT_HEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="hexagonal" renderorder="right-down" width="3" height="2" tilewidth="32" tileheight="28" infinite="0" hexsidelength="16" staggeraxis="x" staggerindex="even" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="32" tileheight="28" tilecount="4" columns="2">
+  <image source="terrain.png" width="64" height="56"/>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,1,1,
+1,1,1
+</data>
+ </layer>
+</map>
+"#;
+
+fn assert_near(a: Vec2, b: Vec2) {
+    assert!(a.distance(b) < 1e-3, "{} != {}", a, b);
+}
+
+#[test]
+fn hexagonal_maps_follow_the_stagger_settings() {
+    let bounds = MapBounds::of(&tiled_map(POINTY_HEX));
+    assert!(bounds.is_hexagonal());
+    // 3 tiles and half a shift across, 2 rows stepping by (32 + 16) / 2
+    assert_near(bounds.world_rect().size(), Vec2::new(98.0, 56.0));
+    // the centre of TMX tile (0, 0), in the unshifted top row
+    let center = bounds.tmx_to_world(14.0, 16.0);
+    assert_near(center, bounds.tile_center(TilePos { x: 0, y: 1 }));
+    assert_eq!(bounds.tile_at(center), Some(TilePos { x: 0, y: 1 }));
+
+    // TMX (1, 0): both sides in its row, and the two below it in the shifted row
+    let mut neighbors: Vec<(u32, u32)> = bounds
+        .neighbors(TilePos { x: 1, y: 1 })
+        .into_iter()
+        .map(|pos| (pos.x, pos.y))
+        .collect();
+    neighbors.sort();
+    assert_eq!(neighbors, vec![(0, 0), (0, 1), (1, 0), (2, 1)]);
+
+    let mut app = map_app();
+    spawn_map(&mut app, POINTY_HEX);
+    app.update();
+    let collision = app.world.resource::<CollisionMap>();
+    assert!(collision.is_hexagonal());
+    assert_eq!(collision.tile_at(center), IVec2::new(0, 1));
+    assert!(collision.is_solid_at(center));
+    assert_eq!(collision.tile_outline(0, 1).len(), 6);
+}
+
+#[test]
+fn flat_topped_hexagons_round_trip() {
+    let bounds = MapBounds::of(&tiled_map(FLAT_HEX));
+    // columns step by (32 + 16) / 2, and the shifted columns stick out half a tile
+    assert_near(bounds.world_rect().size(), Vec2::new(80.0, 70.0));
+    // TMX tile (0, 0) is in a column Tiled shifts down
+    assert_near(
+        bounds.tmx_to_world(16.0, 28.0),
+        bounds.tile_center(TilePos { x: 0, y: 1 }),
+    );
+    for y in 0..2 {
+        for x in 0..3 {
+            let pos = TilePos { x, y };
+            assert_eq!(bounds.tile_at(bounds.tile_center(pos)), Some(pos));
+        }
+    }
+    assert_eq!(bounds.neighbors(TilePos { x: 1, y: 0 }).len(), 5);
+}
+
+// tile 0 in all eight flip combinations: none, H, V, D, HV, HD, VD, HVD
+const FLIPPED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="8" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+  <tile id="0">
+   <objectgroup draworder="index" id="2">
+    <object id="1" x="0" y="0" width="8" height="4"/>
+   </objectgroup>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="8" height="1">
+  <data encoding="csv">
+1,2147483649,1073741825,536870913,3221225473,2684354561,1610612737,3758096385
+</data>
+ </layer>
+</map>
+"#;
+
+#[test]
+fn flipped_tiles_keep_their_flips_and_shapes() {
+    let combinations = [
+        (false, false, false),
+        (true, false, false),
+        (false, true, false),
+        (false, false, true),
+        (true, true, false),
+        (true, false, true),
+        (false, true, true),
+        (true, true, true),
+    ];
+
+    let mut app = map_app();
+    spawn_map(&mut app, FLIPPED);
+    app.update();
+    let mut tiles: Vec<(u32, TileFlip, TileTextureIndex, Rect)> = app
+        .world
+        .query::<(&TilePos, &TileFlip, &TileTextureIndex, &ShapeCollider)>()
+        .iter(&app.world)
+        .map(|(pos, flip, index, collider)| (pos.x, *flip, *index, collider.shapes[0].bounds()))
+        .collect();
+    tiles.sort_by_key(|(x, ..)| *x);
+    assert_eq!(tiles.len(), 8);
+
+    let collision = app.world.resource::<CollisionMap>();
+    for ((x, flip, index, shape), (h, v, d)) in tiles.into_iter().zip(combinations) {
+        assert_eq!(index.0, 0, "tile {} should be tile 0", x);
+        assert_eq!((flip.x, flip.y, flip.d), (h, v, d), "tile {}", x);
+
+        // the 8×4 box in the top left corner, swapped along the diagonal, then mirrored
+        let size = if d {
+            Vec2::new(4.0, 8.0)
+        } else {
+            Vec2::new(8.0, 4.0)
+        };
+        let left = if h { 16.0 - size.x } else { 0.0 };
+        let top = if v { 16.0 - size.y } else { 0.0 };
+        let tile = collision.tile_rect(x as i32, 0);
+        let expected = Rect::new(
+            tile.min.x + left,
+            tile.max.y - top - size.y,
+            tile.min.x + left + size.x,
+            tile.max.y - top,
+        );
+        assert_eq!(shape, expected, "tile {}", x);
+    }
+}
+
+// the same 2×2 layer (tiles 1, 2, 3, 4) in every encoding tiled decodes without extra features
+const ENCODED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="5" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="csv" width="2" height="2">
+  <data encoding="csv">
+1,2,
+3,4
+</data>
+ </layer>
+ <layer id="2" name="base64" width="2" height="2">
+  <data encoding="base64">AQAAAAIAAAADAAAABAAAAA==</data>
+ </layer>
+ <layer id="3" name="zlib" width="2" height="2">
+  <data encoding="base64" compression="zlib">eJxjZGBgYAJiZiBmAWIAAGAACw==</data>
+ </layer>
+ <layer id="4" name="gzip" width="2" height="2">
+  <data encoding="base64" compression="gzip">H4sIAAAAAAACA2NkYGBgAmJmIGYBYgDv1AWvEAAAAA==</data>
+ </layer>
+</map>
+"#;
+
+const ENCODED_CHUNKS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="30" height="20" tilewidth="16" tileheight="16" infinite="1" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="30" height="20">
+  <data encoding="base64" compression="zlib">
+   <chunk x="-3" y="-1" width="2" height="1">eJxjZIAAAAAQAAI=</chunk>
+  </data>
+ </layer>
+</map>
+"#;
+
+#[test]
+fn compressed_layer_data_decodes_like_csv() {
+    let map = parse_map(ENCODED);
+    let layers: Vec<(String, Vec<_>)> = map
+        .layers()
+        .map(|layer| {
+            let Some(tiled::TileLayer::Finite(tiles)) = layer.as_tile_layer() else {
+                panic!("expected a finite tile layer");
+            };
+            let mut placed: Vec<(u32, u32, u32)> = tiles_by_tileset(&tiles)
+                .into_values()
+                .flatten()
+                .map(|tile| (tile.id, tile.pos.x, tile.pos.y))
+                .collect();
+            placed.sort();
+            (layer.name.clone(), placed)
+        })
+        .collect();
+    assert_eq!(layers.len(), 4);
+    for (name, placed) in layers.iter() {
+        assert_eq!(placed, &layers[0].1, "layer {}", name);
+    }
+
+    let asset = tiled_map(ENCODED_CHUNKS);
+    let bounds = MapBounds::of(&asset);
+    let map = &asset.map;
+    let Some(tiled::TileLayer::Infinite(layer)) = map.get_layer(0).unwrap().as_tile_layer() else {
+        panic!("expected an infinite tile layer");
+    };
+    let tiles: Vec<(u32, u32, u32)> = chunked_tiles_by_tileset(&layer, &bounds)
+        .into_values()
+        .flatten()
+        .map(|tile| (tile.id, tile.pos.x, tile.pos.y))
+        .collect();
+    // TMX (-3, -1) in the single 16×16 chunk around it
+    assert_eq!(tiles, vec![(0, 13, 0)]);
+}
+
+const TORCH_TEMPLATE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<template>
+ <object type="torch" width="16" height="16">
+  <properties>
+   <property name="lit" type="bool" value="true"/>
+   <property name="radius" type="int" value="48"/>
+  </properties>
+ </object>
+</template>
+"#;
+
+// a templated torch overriding one property, and the same torch written out in full
+const TEMPLATED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="3">
+ <objectgroup id="1" name="objects">
+  <object id="1" template="torch.tx" x="0" y="0">
+   <properties>
+    <property name="radius" type="int" value="96"/>
+   </properties>
+  </object>
+  <object id="2" type="torch" x="32" y="0" width="16" height="16">
+   <properties>
+    <property name="lit" type="bool" value="true"/>
+    <property name="radius" type="int" value="96"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+#[test]
+fn templated_objects_look_like_plain_ones() {
+    let mut app = map_app();
+    app.register_tiled_object("torch", |entity, _| {
+        entity.insert(Torch);
+    });
+    spawn_parsed_map(
+        &mut app,
+        parse_map_with(TEMPLATED, &[("torch.tx", TORCH_TEMPLATE)]),
+        TEMPLATED,
+    );
+    app.update();
+
+    let mut torches: Vec<TiledObject> = app
+        .world
+        .query_filtered::<&TiledObject, With<Torch>>()
+        .iter(&app.world)
+        .cloned()
+        .collect();
+    torches.sort_by_key(|object| object.id);
+    assert_eq!(torches.len(), 2);
+    let (templated, plain) = (&torches[0], &torches[1]);
+    assert_eq!(templated.object_type, plain.object_type);
+    assert_eq!(templated.properties, plain.properties);
+    assert_eq!(templated.shape, plain.shape);
+}
+
+#[test]
+fn the_loader_reads_the_templates_a_map_uses() {
+    let path = temp_map("templated", TEMPLATED);
+    std::fs::write(path.with_file_name("torch.tx"), TORCH_TEMPLATE).unwrap();
+    let mut app = map_app();
+    app.register_tiled_object("torch", |entity, _| {
+        entity.insert(Torch);
+    });
+    app.world
+        .send_event(LoadMap::new(path.to_string_lossy().into_owned()));
+
+    let mut torches = Vec::new();
+    for _ in 0..200 {
+        app.update();
+        torches = app
+            .world
+            .query_filtered::<&TiledObject, With<Torch>>()
+            .iter(&app.world)
+            .map(|object| (object.id, object.properties.clone()))
+            .collect();
+        if !torches.is_empty() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    torches.sort_by_key(|(id, _)| *id);
+    assert_eq!(torches.len(), 2);
+    assert_eq!(
+        torches[0].1.get("lit"),
+        Some(&tiled::PropertyValue::BoolValue(true))
+    );
+    assert_eq!(torches[0].1, torches[1].1);
+}
+
+#[derive(Component)]
+struct Torch;
+
+const EXTERNAL_TILESET: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <tileset firstgid="5" source="tilesets/decor.tsx"/>
+ <layer id="1" name="ground" width="2" height="2">
+  <data encoding="csv">
+1,6,
+8,0
+</data>
+ </layer>
+</map>
+"#;
+
+const DECOR_TSX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" name="decor" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+ <image source="../images/decor.png" width="32" height="32"/>
+</tileset>
+"#;
+
+/// Loads a map from disk, updating until the load has succeeded or failed
+fn load_from_disk(app: &mut App, path: &Path) -> (Handle<TiledMap>, LoadState) {
+    // the loader asks for the tileset images, which map_app doesn't otherwise need
+    app.init_asset::<Image>();
+    let handle: Handle<TiledMap> = app
+        .world
+        .resource::<AssetServer>()
+        .load(path.to_string_lossy().into_owned());
+    let mut state = LoadState::Loading;
+    for _ in 0..200 {
+        app.update();
+        state = app.world.resource::<AssetServer>().load_state(&handle);
+        if matches!(state, LoadState::Loaded | LoadState::Failed) {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    (handle, state)
+}
+
+#[test]
+fn the_loader_reads_external_tilesets_next_to_embedded_ones() {
+    let path = temp_map("external_tileset", EXTERNAL_TILESET);
+    let dir = path.parent().unwrap();
+    std::fs::create_dir_all(dir.join("tilesets")).unwrap();
+    std::fs::write(dir.join("tilesets/decor.tsx"), DECOR_TSX).unwrap();
+    let mut app = map_app();
+
+    let (handle, state) = load_from_disk(&mut app, &path);
+    assert_eq!(state, LoadState::Loaded);
+    let maps = app.world.resource::<Assets<TiledMap>>();
+    let map = maps.get(&handle).unwrap();
+    let tilesets = map.map.tilesets();
+    let names: Vec<&str> = tilesets
+        .iter()
+        .map(|tileset| tileset.name.as_str())
+        .collect();
+    assert_eq!(names, vec!["terrain", "decor"]);
+    // the image is relative to the tileset, not to the map
+    let TilemapTexture::Single(texture) = &map.tilemap_textures[&1] else {
+        panic!("the decor tileset should have one image");
+    };
+    assert_eq!(
+        texture.path().map(|path| path.path().to_path_buf()),
+        Some(dir.join("images/decor.png"))
+    );
+}
+
+#[test]
+fn a_missing_or_broken_external_tileset_fails_the_load() {
+    let path = temp_map("missing_tileset", EXTERNAL_TILESET);
+    let _ = std::fs::remove_dir_all(path.with_file_name("tilesets"));
+    let mut app = map_app();
+    let (_, state) = load_from_disk(&mut app, &path);
+    assert_eq!(state, LoadState::Failed);
+
+    let path = temp_map("broken_tileset", EXTERNAL_TILESET);
+    std::fs::create_dir_all(path.with_file_name("tilesets")).unwrap();
+    std::fs::write(
+        path.with_file_name("tilesets").join("decor.tsx"),
+        "<tileset",
+    )
+    .unwrap();
+    let mut app = map_app();
+    let (_, state) = load_from_disk(&mut app, &path);
+    assert_eq!(state, LoadState::Failed);
+}
+
+const SHAPES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="5">
+ <objectgroup id="1" name="objects">
+  <object id="1" name="box" type="torch" x="0" y="0" width="32" height="16"/>
+  <object id="2" name="round" type="torch" x="32" y="0" width="16" height="16">
+   <ellipse/>
+  </object>
+  <object id="3" name="spot" type="torch" x="48" y="24">
+   <point/>
+  </object>
+  <object id="4" name="plain" x="0" y="16" width="16" height="16"/>
+ </objectgroup>
+</map>
+"#;
+
+#[test]
+fn registered_spawners_run_for_their_objects_where_tiled_put_them() {
+    let mut app = map_app();
+    app.register_tiled_object("torch", |entity, object| {
+        assert_eq!(object.object_type, "torch");
+        entity.insert(Torch);
+    });
+    spawn_map(&mut app, SHAPES);
+    app.update();
+
+    let mut torches: Vec<(String, Vec2, TiledObject)> = app
+        .world
+        .query_filtered::<(&Name, &Transform, &TiledObject), With<Torch>>()
+        .iter(&app.world)
+        .map(|(name, transform, object)| {
+            (
+                name.to_string(),
+                transform.translation.truncate(),
+                object.clone(),
+            )
+        })
+        .collect();
+    torches.sort_by_key(|(_, _, object)| object.id);
+    let names: Vec<&str> = torches.iter().map(|(name, _, _)| name.as_str()).collect();
+    // the untyped object gets no spawner
+    assert_eq!(names, vec!["box", "round", "spot"]);
+
+    // the 64×32 map is centred on the origin; rectangles and ellipses on their middle
+    assert_eq!(torches[0].1, Vec2::new(-16.0, 8.0));
+    assert_eq!(
+        torches[0].2.shape,
+        ObjectShape::Rect {
+            size: Vec2::new(32.0, 16.0)
+        }
+    );
+    assert_eq!(torches[1].1, Vec2::new(8.0, 8.0));
+    assert_eq!(
+        torches[1].2.shape,
+        ObjectShape::Ellipse {
+            size: Vec2::splat(16.0)
+        }
+    );
+    assert_eq!(torches[2].1, Vec2::new(16.0, -8.0));
+    assert_eq!(torches[2].2.shape, ObjectShape::Point);
+}
+
+const MARKED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="5">
+ <objectgroup id="1" name="markers" visible="0" offsetx="8" offsety="0">
+  <object id="1" name="player_spawn" x="8" y="8">
+   <point/>
+  </object>
+  <object id="2" name="patrol_point" x="0" y="16">
+   <properties>
+    <property name="index" type="int" value="1"/>
+   </properties>
+   <point/>
+  </object>
+  <object id="3" name="patrol_point" x="48" y="16">
+   <properties>
+    <property name="index" type="int" value="0"/>
+   </properties>
+   <point/>
+  </object>
+  <object id="4" name="crate" x="0" y="0" width="16" height="16"/>
+ </objectgroup>
+</map>
+"#;
+
+#[test]
+fn point_objects_become_markers() {
+    let mut app = map_app();
+    app.insert_resource(HiddenLayers::Skip);
+    spawn_map(&mut app, MARKED);
+    app.update();
+
+    let markers = app.world.resource::<MapMarkers>();
+    // 8px in from the map's top left corner, plus the layer offset
+    assert_eq!(markers.get_one("player_spawn"), Some(Vec2::new(-16.0, 8.0)));
+    assert_eq!(markers.get_one("crate"), None);
+    let patrol: Vec<(Vec2, Option<&tiled::PropertyValue>)> = markers
+        .get("patrol_point")
+        .iter()
+        .map(|marker| (marker.position, marker.properties.get("index")))
+        .collect();
+    assert_eq!(
+        patrol,
+        vec![
+            (
+                Vec2::new(-24.0, 0.0),
+                Some(&tiled::PropertyValue::IntValue(1))
+            ),
+            (
+                Vec2::new(24.0, 0.0),
+                Some(&tiled::PropertyValue::IntValue(0))
+            ),
+        ]
+    );
+
+    let map = map_entities(&mut app)[0];
+    app.world.entity_mut(map).despawn_recursive();
+    app.update();
+    assert!(app.world.resource::<MapMarkers>().is_empty());
+}
+
+const SIGNPOST: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="2">
+ <objectgroup id="2" name="labels">
+  <object id="1" name="sign" x="0" y="0" width="48" height="24">
+   <text pixelsize="12" wrap="1" color="#ff00ff00" halign="right" valign="bottom">Keep out</text>
+  </object>
+ </objectgroup>
+</map>
+"##;
+
+#[test]
+fn text_objects_draw_inside_their_rectangle() {
+    let mut app = map_app();
+    spawn_map(&mut app, SIGNPOST);
+    app.update();
+
+    let (text, anchor, text_bounds, transform, parent) = app
+        .world
+        .query_filtered::<(&Text, &Anchor, &Text2dBounds, &Transform, &Parent), With<TiledText>>()
+        .single(&app.world);
+    assert_eq!(text.sections[0].value, "Keep out");
+    assert_eq!(text.sections[0].style.font_size, 12.0);
+    assert_eq!(text.sections[0].style.color, Color::rgba_u8(0, 255, 0, 255));
+    assert_eq!(text.justify, JustifyText::Right);
+    // the bottom right corner of the 48×24 rectangle the object is centred on
+    assert_eq!(*anchor, Anchor::Custom(Vec2::new(0.5, -0.5)));
+    assert_eq!(transform.translation, Vec3::new(24.0, -12.0, 0.0));
+    assert_eq!(text_bounds.size.x, 48.0);
+    let sign = app.world.get::<TiledObject>(parent.get()).unwrap();
+    assert_eq!(sign.name, "sign");
+
+    // changing the map takes the text along with the rest of the old map
+    let map = map_entities(&mut app)[0];
+    let handle = app.world.get::<Handle<TiledMap>>(map).unwrap().id();
+    app.world
+        .resource_mut::<Assets<TiledMap>>()
+        .insert(handle, tiled_map(SOLID));
+    app.update();
+    app.update();
+    assert_eq!(
+        app.world
+            .query_filtered::<(), With<TiledText>>()
+            .iter(&app.world)
+            .count(),
+        0
+    );
+}
+
+const SPAWNS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="3">
+ <objectgroup id="1" name="spawns" visible="0">
+  <object id="1" name="spawn" x="8" y="8">
+   <properties>
+    <property name="facing" value="left"/>
+   </properties>
+   <point/>
+  </object>
+  <object id="2" name="spawn_from_cave" x="56" y="24">
+   <point/>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+fn spawn_app(spawn: Option<&str>) -> (App, Entity, Entity) {
+    let mut app = map_app();
+    let player = app
+        .world
+        .spawn((PlacedAtSpawn, Transform::from_xyz(500.0, 500.0, 3.0)))
+        .id();
+    let camera = app
+        .world
+        .spawn((MainCamera {}, Transform::from_xyz(500.0, 500.0, 999.0)))
+        .id();
+    spawn_map(&mut app, SPAWNS);
+    if let Some(spawn) = spawn {
+        let map = map_entities(&mut app)[0];
+        app.world.entity_mut(map).insert(SpawnPointName::new(spawn));
+    }
+    app.update();
+    (app, player, camera)
+}
+
+#[test]
+fn players_start_at_the_spawn_point() {
+    let (mut app, player, camera) = spawn_app(None);
+
+    assert_eq!(
+        app.world.get::<Transform>(player).unwrap().translation,
+        Vec3::new(-24.0, 8.0, 3.0)
+    );
+    // snapped, not lerped over from the old position
+    assert_eq!(
+        app.world.get::<Transform>(camera).unwrap().translation,
+        Vec3::new(-24.0, 8.0, 999.0)
+    );
+    let ready: Vec<SpawnPointReady> = app
+        .world
+        .resource_mut::<Events<SpawnPointReady>>()
+        .drain()
+        .collect();
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].name, "spawn");
+    assert_eq!(ready[0].position, Vec2::new(-24.0, 8.0));
+    assert_eq!(
+        ready[0].properties.get("facing"),
+        Some(&tiled::PropertyValue::StringValue("left".to_string()))
+    );
+}
+
+#[test]
+fn maps_can_start_at_another_spawn_point() {
+    let (app, player, _) = spawn_app(Some("spawn_from_cave"));
+    assert_eq!(
+        app.world.get::<Transform>(player).unwrap().translation,
+        Vec3::new(24.0, -8.0, 3.0)
+    );
+
+    // unknown names fall back to "spawn"
+    let (app, player, _) = spawn_app(Some("spawn_from_nowhere"));
+    assert_eq!(
+        app.world.get::<Transform>(player).unwrap().translation,
+        Vec3::new(-24.0, 8.0, 3.0)
+    );
+}
+
+const PROPERTIED: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="1" nextobjectid="1">
+ <properties>
+  <property name="music" value="caves"/>
+  <property name="dark" type="bool" value="true"/>
+  <property name="enemies" type="int" value="3"/>
+  <property name="gravity" type="float" value="9.5"/>
+  <property name="tint" type="color" value="#80ff0000"/>
+ </properties>
+</map>
+"##;
+
+#[test]
+fn map_properties_are_published_as_a_resource() {
+    let mut app = map_app();
+    spawn_map(&mut app, PROPERTIED);
+    app.update();
+
+    let properties = app.world.resource::<MapProperties>();
+    assert_eq!(properties.get_string("music"), Some("caves"));
+    assert_eq!(properties.get_bool("dark"), Some(true));
+    assert_eq!(properties.get_int("enemies"), Some(3));
+    assert_eq!(properties.get_float("gravity"), Some(9.5));
+    assert_eq!(properties.get_float("enemies"), Some(3.0));
+    assert_eq!(
+        properties.get_color("tint"),
+        Some(Color::rgba_u8(255, 0, 0, 128))
+    );
+    // the wrong type is a miss, not a panic
+    assert_eq!(properties.get_int("music"), None);
+    assert_eq!(properties.get_int("music"), None);
+    assert_eq!(properties.get_bool("missing"), None);
+
+    let map = map_entities(&mut app)[0];
+    app.world.entity_mut(map).despawn_recursive();
+    app.update();
+    assert!(app.world.resource::<MapProperties>().is_empty());
+}
+
+const PROPERTY_TILES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+  <tile id="0">
+   <properties>
+    <property name="water" type="bool" value="true"/>
+    <property name="damage" type="int" value="2"/>
+   </properties>
+  </tile>
+  <tile id="1">
+   <properties>
+    <property name="water" type="bool" value="false"/>
+    <property name="surface" value="ice"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="1">
+  <data encoding="csv">
+1,2,3
+</data>
+ </layer>
+</map>
+"#;
+
+#[derive(Component, Debug, PartialEq)]
+struct Water;
+
+#[derive(Component, Debug, PartialEq)]
+struct Damage(i32);
+
+fn tile_at_x(app: &mut App, x: u32) -> Entity {
+    app.world
+        .query::<(Entity, &TilePos)>()
+        .iter(&app.world)
+        .find(|(_, pos)| pos.x == x)
+        .map(|(entity, _)| entity)
+        .unwrap()
+}
+
+#[test]
+fn tile_properties_become_registered_components() {
+    let mut app = map_app();
+    app.register_tile_property_value("water", tiled::PropertyValue::BoolValue(true), |_| Water)
+        .register_tile_property("damage", |value| match value {
+            tiled::PropertyValue::IntValue(damage) => Damage(*damage),
+            _ => Damage(0),
+        });
+    spawn_map(&mut app, PROPERTY_TILES);
+    app.update();
+
+    let pool = tile_at_x(&mut app, 0);
+    assert!(app.world.get::<Water>(pool).is_some());
+    assert_eq!(app.world.get::<Damage>(pool), Some(&Damage(2)));
+    let ice = tile_at_x(&mut app, 1);
+    assert!(app.world.get::<Water>(ice).is_none());
+    // unregistered properties are still there
+    let properties = app.world.get::<TileProperties>(ice).unwrap();
+    assert_eq!(properties.get_string("surface"), Some("ice"));
+    let plain = tile_at_x(&mut app, 2);
+    assert!(app.world.get::<TileProperties>(plain).is_none());
+
+    // swapping tiles at runtime swaps their components
+    app.world.get_mut::<TilesetTile>(pool).unwrap().0 = 1;
+    app.world.get_mut::<TilesetTile>(plain).unwrap().0 = 0;
+    app.update();
+    assert!(app.world.get::<Water>(pool).is_none());
+    assert!(app.world.get::<Damage>(pool).is_none());
+    assert_eq!(
+        app.world
+            .get::<TileProperties>(pool)
+            .unwrap()
+            .get_string("surface"),
+        Some("ice")
+    );
+    assert!(app.world.get::<Water>(plain).is_some());
+    assert_eq!(app.world.get::<Damage>(plain), Some(&Damage(2)));
+}
+
+const TRIGGERS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="2">
+ <objectgroup id="1" name="triggers">
+  <object id="1" name="cave_mouth" x="0" y="0" width="16" height="16">
+   <properties>
+    <property name="area" value="Old Cave"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+fn trigger_events(app: &mut App) -> (Vec<TriggerEntered>, Vec<TriggerExited>) {
+    let entered = app
+        .world
+        .resource_mut::<Events<TriggerEntered>>()
+        .drain()
+        .collect();
+    let exited = app
+        .world
+        .resource_mut::<Events<TriggerExited>>()
+        .drain()
+        .collect();
+    (entered, exited)
+}
+
+#[test]
+fn sensors_enter_and_leave_trigger_regions() {
+    let mut app = map_app();
+    app.add_plugins(TransformPlugin);
+    // already inside when the map spawns
+    let sensor = app
+        .world
+        .spawn((
+            TriggerSensor,
+            TransformBundle::from_transform(Transform::from_xyz(-24.0, 8.0, 0.0)),
+        ))
+        .id();
+    spawn_map(&mut app, TRIGGERS);
+    app.update();
+
+    let (region, trigger) = app
+        .world
+        .query::<(Entity, &TriggerRegion)>()
+        .single(&app.world);
+    assert_eq!(trigger.name, "cave_mouth");
+    assert_eq!(trigger.rect, Rect::new(-32.0, 0.0, -16.0, 16.0));
+    let entered = TriggerEntered {
+        region,
+        entity: sensor,
+    };
+    assert_eq!(trigger_events(&mut app), (vec![entered], vec![]));
+
+    // staying inside sends nothing more
+    app.update();
+    assert_eq!(trigger_events(&mut app), (vec![], vec![]));
+
+    app.world
+        .get_mut::<Transform>(sensor)
+        .unwrap()
+        .translation
+        .x = 8.0;
+    app.update();
+    let exited = TriggerExited {
+        region,
+        entity: sensor,
+    };
+    assert_eq!(trigger_events(&mut app), (vec![], vec![exited]));
+
+    app.world
+        .get_mut::<Transform>(sensor)
+        .unwrap()
+        .translation
+        .x = -24.0;
+    app.update();
+    assert_eq!(trigger_events(&mut app), (vec![entered], vec![]));
+    app.world.entity_mut(sensor).despawn();
+    app.update();
+    assert_eq!(trigger_events(&mut app), (vec![], vec![]));
+    assert!(!app
+        .world
+        .resource::<TriggerOccupancy>()
+        .contains(region, sensor));
+}
+
+const DOORS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="3">
+ <objectgroup id="1" name="objects">
+  <object id="1" name="cellar" type="door" x="0" y="0" width="16" height="16">
+   <properties>
+    <property name="target_map" value="missing.tmx"/>
+    <property name="target_spawn" value="spawn_from_house"/>
+   </properties>
+  </object>
+  <object id="2" name="hatch" type="door" x="48" y="0" width="16" height="16">
+   <properties>
+    <property name="target_map" value="attic.tmx"/>
+    <property name="requires_interact" type="bool" value="true"/>
+   </properties>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+#[test]
+fn doors_read_their_targets() {
+    let mut app = map_app();
+    spawn_map(&mut app, DOORS);
+    app.update();
+
+    let mut doors: Vec<(Door, Rect)> = app
+        .world
+        .query::<(&Door, &TriggerRegion)>()
+        .iter(&app.world)
+        .map(|(door, region)| (door.clone(), region.rect))
+        .collect();
+    doors.sort_by(|a, b| a.0.target_map.cmp(&b.0.target_map));
+    assert_eq!(
+        doors,
+        vec![
+            (
+                Door {
+                    target_map: "attic.tmx".to_string(),
+                    target_spawn: None,
+                    requires_interact: true,
+                },
+                Rect::new(16.0, 0.0, 32.0, 16.0)
+            ),
+            (
+                Door {
+                    target_map: "missing.tmx".to_string(),
+                    target_spawn: Some("spawn_from_house".to_string()),
+                    requires_interact: false,
+                },
+                Rect::new(-32.0, 0.0, -16.0, 16.0)
+            ),
+        ]
+    );
+}
+
+#[test]
+fn doors_to_missing_maps_leave_the_player_be() {
+    let mut app = map_app();
+    app.add_plugins(TransformPlugin);
+    let player = app
+        .world
+        .spawn((
+            PlacedAtSpawn,
+            TriggerSensor,
+            TransformBundle::from_transform(Transform::from_xyz(0.0, 8.0, 0.0)),
+        ))
+        .id();
+    spawn_map(&mut app, DOORS);
+    app.update();
+
+    app.world
+        .get_mut::<Transform>(player)
+        .unwrap()
+        .translation
+        .x = -24.0;
+    app.update();
+    app.update();
+    assert!(matches!(
+        app.world.resource::<DoorTransition>(),
+        DoorTransition::FadingOut { .. }
+    ));
+
+    for _ in 0..200 {
+        if app.world.resource::<DoorTransition>().is_idle() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+        app.update();
+    }
+    assert!(app.world.resource::<DoorTransition>().is_idle());
+    assert_eq!(
+        app.world.get::<Transform>(player).unwrap().translation,
+        Vec3::new(-24.0, 8.0, 0.0)
+    );
+    assert_eq!(map_entities(&mut app).len(), 1);
+}
+
+#[test]
+fn tile_lookup_converts_between_tiles_and_the_world() {
+    let mut app = map_app();
+    spawn_map(&mut app, SOLID);
+    app.update();
+
+    let lookup = app.world.resource::<TileLookup>();
+    let corner = TilePos { x: 0, y: 0 };
+    // the solid tile in the bottom left corner of the 48×32 map
+    assert_eq!(lookup.world_to_tile(Vec2::new(-20.0, -10.0)), Some(corner));
+    assert_eq!(lookup.tile_to_world(corner), Vec2::new(-16.0, -8.0));
+    assert_eq!(lookup.world_to_tile(Vec2::new(-25.0, 0.0)), None);
+    assert_eq!(lookup.world_to_tile(Vec2::new(0.0, 17.0)), None);
+
+    let tile = lookup.tile_entity("collision", corner).unwrap();
+    assert_eq!(app.world.get::<TilePos>(tile), Some(&corner));
+    assert_eq!(
+        lookup.tile_entity("collision", TilePos { x: 1, y: 0 }),
+        None
+    );
+    assert_eq!(lookup.tile_entity("decor", corner), None);
+
+    let tiles: Vec<TilePos> = lookup
+        .tiles_in_rect(Rect::new(-20.0, -10.0, -4.0, 4.0))
+        .collect();
+    assert_eq!(tiles.len(), 4);
+    // touching edges don't count
+    let tiles: Vec<TilePos> = lookup
+        .tiles_in_rect(Rect::new(-24.0, -16.0, -8.0, 0.0))
+        .collect();
+    assert_eq!(tiles, vec![corner]);
+    assert_eq!(
+        lookup
+            .tiles_in_rect(Rect::new(100.0, 100.0, 120.0, 120.0))
+            .count(),
+        0
+    );
+
+    // removing the tile at runtime
+    let mut storage = app
+        .world
+        .query::<&mut TileStorage>()
+        .single_mut(&mut app.world);
+    storage.remove(&corner);
+    app.world.entity_mut(tile).despawn();
+    app.update();
+    let lookup = app.world.resource::<TileLookup>();
+    assert_eq!(lookup.tile_entity("collision", corner), None);
+}
+
+#[test]
+fn set_tile_changes_tiles_after_load() {
+    let mut app = map_app();
+    spawn_map(&mut app, SOLID);
+    app.update();
+
+    let corner = TilePos { x: 0, y: 0 };
+    let far = TilePos { x: 2, y: 1 };
+    app.world.send_event(SetTile::remove("collision", corner));
+    app.world.send_event(SetTile::new("collision", far, 1));
+    // the last edit of a tile in a frame wins
+    app.world
+        .send_event(SetTile::new("collision", TilePos { x: 1, y: 0 }, 2));
+    app.world
+        .send_event(SetTile::remove("collision", TilePos { x: 1, y: 0 }));
+    // these only warn
+    app.world.send_event(SetTile::new("decor", corner, 1));
+    app.world
+        .send_event(SetTile::new("collision", TilePos { x: 3, y: 0 }, 1));
+    app.world
+        .send_event(SetTile::new("collision", TilePos { x: 1, y: 1 }, 40));
+    app.update();
+
+    let collision = app.world.resource::<CollisionMap>();
+    assert!(!collision.is_solid(0, 0));
+    assert!(collision.is_solid(2, 1));
+    assert_eq!(collision.solid_tiles().count(), 1);
+    let lookup = app.world.resource::<TileLookup>();
+    assert_eq!(lookup.tile_entity("collision", corner), None);
+    assert_eq!(
+        lookup.tile_entity("collision", TilePos { x: 1, y: 0 }),
+        None
+    );
+    let placed = lookup.tile_entity("collision", far).unwrap();
+    assert_eq!(app.world.get::<TileTextureIndex>(placed).unwrap().0, 1);
+    assert!(app.world.get::<TileCollider>(placed).is_some());
+
+    // swapping a tile keeps its entity
+    let flip = TileFlip {
+        x: true,
+        ..Default::default()
+    };
+    app.world
+        .send_event(SetTile::new("collision", far, 3).with_flip(flip));
+    app.update();
+    let lookup = app.world.resource::<TileLookup>();
+    assert_eq!(lookup.tile_entity("collision", far), Some(placed));
+    assert_eq!(app.world.get::<TileTextureIndex>(placed).unwrap().0, 3);
+    assert!(app.world.get::<TileFlip>(placed).unwrap().x);
+    assert_eq!(app.world.get::<TilesetTile>(placed), Some(&TilesetTile(3)));
+}
+
+const CRATES: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="crates" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="crates.png" width="32" height="32"/>
+  <tile id="0">
+   <properties>
+    <property name="hp" type="int" value="3"/>
+    <property name="cracked_at" type="int" value="1"/>
+    <property name="cracked_tile" type="int" value="1"/>
+   </properties>
+  </tile>
+  <tile id="1">
+   <properties>
+    <property name="hp" type="int" value="3"/>
+    <property name="breaks_into" type="int" value="2"/>
+    <property name="break_sound" value="crate_break"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="props" width="2" height="1">
+  <data encoding="csv">
+1,0
+</data>
+ </layer>
+</map>
+"#;
+
+#[test]
+fn damaged_tiles_crack_and_break() {
+    let mut app = map_app();
+    app.add_plugins(DestructiblePlugin);
+    spawn_map(&mut app, CRATES);
+    app.update();
+
+    let pos = TilePos { x: 0, y: 0 };
+    let tile = app
+        .world
+        .resource::<TileLookup>()
+        .tile_entity("props", pos)
+        .unwrap();
+    // two hits in a frame add up, leaving 1 hp: cracked
+    app.world.send_event(DamageTile::new(pos, 1));
+    app.world.send_event(DamageTile::new(pos, 1));
+    // nothing to damage there
+    app.world
+        .send_event(DamageTile::new(TilePos { x: 1, y: 0 }, 1));
+    app.update();
+    assert_eq!(app.world.get::<TileHealth>(tile), Some(&TileHealth(1)));
+    assert_eq!(app.world.get::<TilesetTile>(tile), Some(&TilesetTile(1)));
+
+    app.world.send_event(DamageTile::new(pos, 1));
+    app.update();
+    assert_eq!(app.world.get::<TilesetTile>(tile), Some(&TilesetTile(2)));
+    assert!(app.world.get::<TileHealth>(tile).is_none());
+    let destroyed: Vec<TileDestroyed> = app
+        .world
+        .resource_mut::<Events<TileDestroyed>>()
+        .drain()
+        .collect();
+    assert_eq!(destroyed, vec![TileDestroyed { pos, tile_id: 1 }]);
+    let sounds: Vec<(String, Option<Vec2>)> = app
+        .world
+        .resource_mut::<Events<PlaySFX>>()
+        .drain()
+        .map(|sfx| (sfx.name, sfx.position))
+        .collect();
+    assert_eq!(
+        sounds,
+        vec![("crate_break".to_string(), Some(Vec2::new(-8.0, 0.0)))]
+    );
+}
+
+const MAZE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+  <tile id="1">
+   <properties>
+    <property name="cost" type="float" value="3"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+0,2,0,
+0,0,0
+</data>
+ </layer>
+ <layer id="2" name="collision" width="3" height="2">
+  <data encoding="csv">
+0,0,0,
+0,1,0
+</data>
+ </layer>
+</map>
+"#;
+
+#[test]
+fn nav_grid_follows_the_collision_map() {
+    let mut app = map_app();
+    spawn_map(&mut app, MAZE);
+    app.update();
+
+    let origin = TilePos { x: 0, y: 0 };
+    let wall = TilePos { x: 1, y: 0 };
+    let mud = TilePos { x: 1, y: 1 };
+    let nav = app.world.resource::<NavGrid>();
+    assert_eq!(nav.size(), UVec2::new(3, 2));
+    assert!(nav.is_walkable(origin));
+    assert!(!nav.is_walkable(wall));
+    assert!(!nav.is_walkable(TilePos { x: 5, y: 5 }));
+    assert_eq!(nav.cost(origin), Some(1.0));
+    assert_eq!(nav.cost(mud), Some(3.0));
+    assert_eq!(nav.cost(wall), None);
+
+    let up = TilePos { x: 0, y: 1 };
+    assert_eq!(nav.neighbors(origin, Connectivity::Four), vec![up]);
+    // the diagonal step would squeeze past the wall
+    assert_eq!(
+        nav.neighbors(origin, Connectivity::Eight { cut_corners: false }),
+        vec![up]
+    );
+    assert_eq!(
+        nav.neighbors(origin, Connectivity::Eight { cut_corners: true }),
+        vec![up, mud]
+    );
+
+    app.world.send_event(SetTile::remove("collision", wall));
+    app.world
+        .send_event(SetTile::new("collision", TilePos { x: 2, y: 0 }, 0));
+    app.world.send_event(SetTile::new("ground", mud, 0));
+    app.update();
+    let nav = app.world.resource::<NavGrid>();
+    assert!(nav.is_walkable(wall));
+    assert!(!nav.is_walkable(TilePos { x: 2, y: 0 }));
+    assert_eq!(nav.cost(mud), Some(1.0));
+    assert_eq!(
+        nav.neighbors(origin, Connectivity::Eight { cut_corners: false }),
+        vec![wall, up, mud]
+    );
+}
+
+// a NavGrid drawn top row first: '#' is blocked, a digit is a cell of that cost, anything else
+// costs 1
+fn nav_grid(rows: &[&str]) -> NavGrid {
+    let height = rows.len() as u32;
+    let mut nav = NavGrid::new(UVec2::new(rows[0].len() as u32, height));
+    for (row, line) in rows.iter().enumerate() {
+        for (x, cell) in line.chars().enumerate() {
+            let pos = TilePos::new(x as u32, height - 1 - row as u32);
+            match cell {
+                '#' => nav.set_walkable(pos, false),
+                digit if digit.is_ascii_digit() => {
+                    nav.set_cost(pos, digit.to_digit(10).unwrap() as f32)
+                }
+                _ => {}
+            }
+        }
+    }
+    nav
+}
+
+fn four_way() -> PathOptions {
+    PathOptions {
+        connectivity: Connectivity::Four,
+        ..Default::default()
+    }
+}
+
+fn tiles(cells: &[(u32, u32)]) -> Vec<TilePos> {
+    cells.iter().map(|&(x, y)| TilePos::new(x, y)).collect()
+}
+
+#[test]
+fn paths_go_around_walls() {
+    let nav = nav_grid(&[
+        "....", //
+        ".##.", //
+        "....", //
+    ]);
+    let path = nav
+        .find_path(TilePos::new(0, 1), TilePos::new(3, 1), four_way())
+        .unwrap();
+    assert_eq!(path.len(), 6);
+    assert_eq!(path.first(), Some(&TilePos::new(0, 1)));
+    assert_eq!(path.last(), Some(&TilePos::new(3, 1)));
+    assert!(path.iter().all(|pos| nav.is_walkable(*pos)));
+
+    // diagonals shorten it, without cutting past the wall's corners
+    let path = nav
+        .find_path(
+            TilePos::new(0, 0),
+            TilePos::new(3, 2),
+            PathOptions::default(),
+        )
+        .unwrap();
+    assert_eq!(path.len(), 6);
+    assert!(path.iter().all(|pos| nav.is_walkable(*pos)));
+    let path = nav
+        .find_path(
+            TilePos::new(0, 0),
+            TilePos::new(3, 2),
+            PathOptions {
+                connectivity: Connectivity::Eight { cut_corners: true },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(path.len(), 5);
+
+    let same = nav.find_path(TilePos::new(0, 0), TilePos::new(0, 0), four_way());
+    assert_eq!(same, Some(tiles(&[(0, 0)])));
+}
+
+#[test]
+fn paths_avoid_costly_cells() {
+    let nav = nav_grid(&[
+        "....", //
+        ".99.", //
+    ]);
+    let path = nav
+        .find_path(TilePos::new(0, 0), TilePos::new(3, 0), four_way())
+        .unwrap();
+    assert_eq!(
+        path,
+        tiles(&[(0, 0), (0, 1), (1, 1), (2, 1), (3, 1), (3, 0)])
+    );
+
+    // through the mud is cheaper than the long way round
+    let nav = nav_grid(&[
+        "......", //
+        ".####.", //
+        "..2...", //
+    ]);
+    let path = nav
+        .find_path(TilePos::new(0, 0), TilePos::new(5, 0), four_way())
+        .unwrap();
+    assert_eq!(path.len(), 6);
+}
+
+#[test]
+fn unreachable_goals_give_up_or_settle_for_the_nearest_cell() {
+    let nav = nav_grid(&[
+        "..#.", //
+        "..#.", //
+        "..##", //
+    ]);
+    let from = TilePos::new(0, 0);
+    let walled_off = TilePos::new(3, 2);
+    assert_eq!(nav.find_path(from, walled_off, four_way()), None);
+    assert_eq!(nav.find_path(from, TilePos::new(2, 0), four_way()), None);
+
+    let nearest = PathOptions {
+        nearest: true,
+        ..four_way()
+    };
+    let path = nav.find_path(from, walled_off, nearest).unwrap();
+    assert_eq!(path.last(), Some(&TilePos::new(1, 2)));
+    let path = nav.find_path(from, TilePos::new(2, 0), nearest).unwrap();
+    assert_eq!(path, tiles(&[(0, 0), (1, 0)]));
+
+    // too far for the cap
+    let capped = PathOptions {
+        max_expanded: 2,
+        ..four_way()
+    };
+    assert_eq!(nav.find_path(from, TilePos::new(1, 2), capped), None);
+}
+
+fn path_events(app: &mut App) -> (Vec<PathCompleted>, Vec<PathBlocked>) {
+    let completed = app
+        .world
+        .resource_mut::<Events<PathCompleted>>()
+        .drain()
+        .collect();
+    let blocked = app
+        .world
+        .resource_mut::<Events<PathBlocked>>()
+        .drain()
+        .collect();
+    (completed, blocked)
+}
+
+#[test]
+fn followers_walk_their_path_until_it_is_blocked() {
+    let mut app = map_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )));
+    spawn_map(&mut app, MAZE);
+    app.update();
+
+    let bounds = *app.world.resource::<MapBounds>();
+    let path = tiles(&[(0, 0), (0, 1), (1, 1), (2, 1)]);
+    let start = bounds.tile_center(path[0]).extend(5.0);
+    let spawn_walker = |app: &mut App| {
+        app.world
+            .spawn((
+                PathFollow::new(path.clone(), 80.0),
+                Transform::from_translation(start),
+            ))
+            .id()
+    };
+    let walker = spawn_walker(&mut app);
+    let other = spawn_walker(&mut app);
+    app.update();
+    // half a tile towards the second tile of the path
+    let halfway = (bounds.tile_center(path[0]) + Vec2::new(0.0, 8.0)).extend(5.0);
+    let translation = app.world.get::<Transform>(walker).unwrap().translation;
+    assert!(translation.abs_diff_eq(halfway, 0.001));
+
+    app.world
+        .send_event(SetTile::new("collision", TilePos::new(2, 1), 0));
+    app.update();
+    let (completed, blocked) = path_events(&mut app);
+    assert!(completed.is_empty());
+    assert_eq!(blocked.len(), 2);
+    for entity in [walker, other] {
+        assert!(blocked.contains(&PathBlocked {
+            entity,
+            at: TilePos::new(2, 1)
+        }));
+        assert!(app.world.get::<PathFollow>(entity).is_none());
+    }
+    // stopped where it stood
+    let translation = app.world.get::<Transform>(walker).unwrap().translation;
+    assert!(translation.abs_diff_eq(halfway, 0.001));
+
+    app.world
+        .entity_mut(walker)
+        .insert(PathFollow::new(tiles(&[(0, 0), (0, 1), (1, 1)]), 400.0));
+    app.update();
+    let (completed, blocked) = path_events(&mut app);
+    assert_eq!(completed, vec![PathCompleted { entity: walker }]);
+    assert!(blocked.is_empty());
+    let translation = app.world.get::<Transform>(walker).unwrap().translation;
+    assert_eq!(
+        translation,
+        bounds.tile_center(TilePos::new(1, 1)).extend(5.0)
+    );
+    assert!(app.world.get::<PathFollow>(walker).is_none());
+}
+
+const WEST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="collision" width="2" height="1">
+  <data encoding="csv">
+1,0
+</data>
+ </layer>
+</map>
+"#;
+
+const EAST: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="2" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="2">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="collision" width="2" height="1">
+  <data encoding="csv">
+0,1
+</data>
+ </layer>
+ <objectgroup id="2" name="markers">
+  <object id="1" name="spawn" x="8" y="8">
+   <point/>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+fn tiled_map(tmx: &'static str) -> TiledMap {
+    TiledMap {
+        map: parse_map(tmx),
+        tilemap_textures: vec![(0, TilemapTexture::Single(Handle::default()))]
+            .into_iter()
+            .collect(),
+        tile_image_offsets: Default::default(),
+        image_layers: Default::default(),
+        tmx: TmxExtras::read(tmx),
+    }
+}
+
+fn add_map(app: &mut App, tmx: &'static str) -> Handle<TiledMap> {
+    let map = tiled_map(tmx);
+    app.world.resource_mut::<Assets<TiledMap>>().add(map)
+}
+
+// west.tmx with east.tmx to its right
+fn world_maps(app: &mut App) -> Vec<WorldMap> {
+    vec![
+        WorldMap {
+            name: "west".to_string(),
+            offset: IVec2::ZERO,
+            map: add_map(app, WEST),
+        },
+        WorldMap {
+            name: "east".to_string(),
+            offset: IVec2::new(32, 0),
+            map: add_map(app, EAST),
+        },
+    ]
+}
+
+fn world_members(app: &App, world: Entity) -> usize {
+    app.world
+        .get::<WorldMembers>(world)
+        .map_or(0, |members| members.entities().len())
+}
+
+#[test]
+fn world_maps_share_the_map_resources() {
+    let mut app = map_app();
+    let maps = world_maps(&mut app);
+    let world = app
+        .world
+        .resource_mut::<Assets<TiledWorld>>()
+        .add(TiledWorld { maps });
+    let world = app
+        .world
+        .spawn(TiledWorldBundle {
+            world,
+            ..Default::default()
+        })
+        .id();
+    app.update();
+
+    let loaded: Vec<(Entity, UVec2)> = app
+        .world
+        .resource_mut::<Events<MapLoaded>>()
+        .drain()
+        .map(|event| (event.map, event.bounds.size))
+        .collect();
+    assert_eq!(loaded, vec![(world, UVec2::new(4, 1))]);
+    assert_eq!(world_members(&app, world), 2);
+
+    let bounds = *app.world.resource::<MapBounds>();
+    assert_eq!(bounds.world_rect(), Rect::new(-32.0, -8.0, 32.0, 8.0));
+    let collision = app.world.resource::<CollisionMap>();
+    assert_eq!(
+        collision.solid_tiles().collect::<Vec<_>>(),
+        vec![IVec2::new(0, 0), IVec2::new(3, 0)]
+    );
+    let lookup = app.world.resource::<TileLookup>();
+    assert!(lookup
+        .tile_entity("collision", TilePos::new(3, 0))
+        .is_some());
+    assert_eq!(lookup.tile_entity("collision", TilePos::new(2, 0)), None);
+    // across the seam between the maps
+    let path = app.world.resource::<NavGrid>().find_path(
+        TilePos::new(1, 0),
+        TilePos::new(2, 0),
+        PathOptions::default(),
+    );
+    assert_eq!(path, Some(vec![TilePos::new(1, 0), TilePos::new(2, 0)]));
+
+    let markers = app.world.resource::<MapMarkers>();
+    assert!(markers.get("spawn").is_empty());
+    assert_eq!(markers.get_one("east/spawn"), Some(Vec2::new(8.0, 0.0)));
+}
+
+#[test]
+fn world_maps_unload_one_at_a_time() {
+    let mut app = map_app();
+    app.world.send_event(LoadMap::new("overworld.world"));
+    app.update();
+    // there's no overworld.world on disk, so the world is filled in by hand
+    let world = app.world.resource::<CurrentMap>().entity().unwrap();
+    let handle = app.world.get::<Handle<TiledWorld>>(world).unwrap().clone();
+    let maps = world_maps(&mut app);
+    app.world
+        .resource_mut::<Assets<TiledWorld>>()
+        .insert(handle.id(), TiledWorld { maps });
+    app.update();
+    assert_eq!(world_members(&app, world), 2);
+
+    app.world.send_event(UnloadMap::member("west"));
+    // only warns
+    app.world.send_event(UnloadMap::member("north"));
+    app.update();
+    assert_eq!(world_members(&app, world), 1);
+    assert_eq!(
+        app.world
+            .resource::<CollisionMap>()
+            .solid_tiles()
+            .collect::<Vec<_>>(),
+        vec![IVec2::new(3, 0)]
+    );
+    assert_eq!(
+        app.world
+            .resource::<TileLookup>()
+            .tile_entity("collision", TilePos::new(0, 0)),
+        None
+    );
+    let markers = app.world.resource::<MapMarkers>();
+    assert_eq!(markers.get_one("east/spawn"), Some(Vec2::new(8.0, 0.0)));
+    // the world keeps its size
+    assert_eq!(app.world.resource::<MapBounds>().size, UVec2::new(4, 1));
+
+    app.world.send_event(UnloadMap::default());
+    app.update();
+    assert!(app.world.get_entity(world).is_none());
+    assert!(map_entities(&mut app).is_empty());
+    assert!(app.world.resource::<MapBounds>().is_empty());
+}
+
+const LONG: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="8" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="8" height="1">
+  <data encoding="csv">
+1,1,1,1,1,1,1,1
+</data>
+ </layer>
+ <layer id="2" name="collision" width="8" height="1">
+  <data encoding="csv">
+0,0,0,0,0,0,0,1
+</data>
+ </layer>
+</map>
+"#;
+
+// the tileset tile shown at `pos` on a layer, None without a tile entity
+fn layer_tile(app: &mut App, layer_name: &str, pos: TilePos) -> Option<u32> {
+    let entity = app
+        .world
+        .query::<(&TiledLayer, &TileStorage)>()
+        .iter(&app.world)
+        .find(|(layer, _)| layer.name == layer_name)
+        .and_then(|(_, storage)| storage.get(&pos))?;
+    app.world.get::<TilesetTile>(entity).map(|tile| tile.0)
+}
+
+fn ground_tile(app: &mut App, pos: TilePos) -> Option<u32> {
+    layer_tile(app, "ground", pos)
+}
+
+// the camera shows 16×16 pixels around its position
+fn move_camera(app: &mut App, camera: Entity, x: f32) {
+    app.world
+        .get_mut::<Transform>(camera)
+        .unwrap()
+        .translation
+        .x = x;
+    app.update();
+}
+
+#[test]
+fn tile_chunks_stream_around_the_camera() {
+    let mut app = map_app();
+    let handle = add_map(&mut app, LONG);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle,
+        // chunks of two tiles, 32px wide, kept up to 32px out of view
+        streaming: TileStreaming::Chunks {
+            chunk_size: UVec2::new(2, 1),
+            spawn_margin: 0.0,
+            despawn_margin: 1.0,
+        },
+        ..Default::default()
+    });
+    let camera = app
+        .world
+        .spawn((
+            MainCamera {},
+            Transform::from_xyz(-48.0, 0.0, 999.0),
+            OrthographicProjection {
+                area: Rect::new(-8.0, -8.0, 8.0, 8.0),
+                ..Default::default()
+            },
+        ))
+        .id();
+    app.update();
+
+    // the map spans -64..64, so only the first chunk is in view
+    let pos = |x| TilePos { x, y: 0 };
+    assert_eq!(ground_tile(&mut app, pos(0)), Some(0));
+    assert_eq!(ground_tile(&mut app, pos(1)), Some(0));
+    assert_eq!(ground_tile(&mut app, pos(2)), None);
+    let lookup = app.world.resource::<TileLookup>();
+    assert!(lookup.tile_entity("ground", pos(0)).is_some());
+    assert!(lookup.tile_entity("ground", pos(7)).is_none());
+    // the collision data doesn't stream
+    assert!(app.world.resource::<CollisionMap>().is_solid(7, 0));
+    assert!(!app.world.resource::<NavGrid>().is_walkable(pos(7)));
+
+    move_camera(&mut app, camera, -16.0);
+    assert_eq!(ground_tile(&mut app, pos(2)), Some(0));
+    // out of view but within the despawn margin
+    assert_eq!(ground_tile(&mut app, pos(0)), Some(0));
+
+    app.world.send_event(SetTile::new("ground", pos(0), 2));
+    app.update();
+    move_camera(&mut app, camera, 44.0);
+    assert_eq!(ground_tile(&mut app, pos(0)), None);
+    assert_eq!(ground_tile(&mut app, pos(3)), None);
+    assert_eq!(ground_tile(&mut app, pos(6)), Some(0));
+    assert_eq!(ground_tile(&mut app, pos(4)), None);
+
+    // edits spawn the chunk they land in
+    app.world.send_event(SetTile::new("ground", pos(4), 3));
+    app.update();
+    assert_eq!(ground_tile(&mut app, pos(4)), Some(3));
+    assert_eq!(ground_tile(&mut app, pos(5)), Some(0));
+
+    // chunks spawn again as they were left
+    move_camera(&mut app, camera, -48.0);
+    assert_eq!(ground_tile(&mut app, pos(0)), Some(2));
+    assert_eq!(ground_tile(&mut app, pos(1)), Some(0));
+    assert_eq!(ground_tile(&mut app, pos(6)), None);
+}
+
+fn tile_entities(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<TilesetTile>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn tilemaps_hidden(app: &mut App) -> bool {
+    app.world
+        .query_filtered::<&Visibility, With<TileStorage>>()
+        .iter(&app.world)
+        .all(|visibility| *visibility == Visibility::Hidden)
+}
+
+#[test]
+fn budgeted_maps_spawn_over_several_frames() {
+    let mut app = map_app();
+    app.insert_resource(MapSpawnBudget::Entities(4));
+    spawn_map(&mut app, LONG);
+    let map = map_entities(&mut app)[0];
+
+    // nine tiles, four a frame
+    for spawned in [4, 8] {
+        app.update();
+        assert_eq!(tile_entities(&mut app), spawned);
+        assert!(tilemaps_hidden(&mut app));
+        assert!(app.world.get::<MapSpawning>(map).is_some());
+        assert!(app.world.resource::<Events<MapLoaded>>().is_empty());
+        // the resources are all there from the start
+        assert!(app.world.resource::<CollisionMap>().is_solid(7, 0));
+    }
+
+    app.update();
+    assert_eq!(tile_entities(&mut app), 9);
+    assert!(!tilemaps_hidden(&mut app));
+    assert!(app.world.get::<MapSpawning>(map).is_none());
+    let loaded: Vec<Entity> = app
+        .world
+        .resource_mut::<Events<MapLoaded>>()
+        .drain()
+        .map(|event| event.map)
+        .collect();
+    assert_eq!(loaded, vec![map]);
+    let lookup = app.world.resource::<TileLookup>();
+    assert!(lookup
+        .tile_entity("collision", TilePos::new(7, 0))
+        .is_some());
+}
+
+#[test]
+fn loading_another_map_cancels_a_partly_spawned_one() {
+    let mut app = map_app();
+    app.insert_resource(MapSpawnBudget::Entities(4));
+    app.world.send_event(LoadMap::new("long.tmx"));
+    app.update();
+    // there's no long.tmx on disk, so it's filled in by hand
+    let map = app.world.resource::<CurrentMap>().entity().unwrap();
+    let handle = app.world.get::<Handle<TiledMap>>(map).unwrap().clone();
+    app.world
+        .resource_mut::<Assets<TiledMap>>()
+        .insert(handle.id(), tiled_map(LONG));
+    for _ in 0..3 {
+        app.update();
+        if app.world.get::<MapSpawning>(map).is_some() {
+            break;
+        }
+    }
+    assert!(app.world.get::<MapSpawning>(map).is_some());
+    assert_eq!(tile_entities(&mut app), 4);
+
+    app.world.send_event(LoadMap::new("other.tmx"));
+    app.update();
+    assert!(app.world.get_entity(map).is_none());
+    assert_eq!(tile_entities(&mut app), 0);
+    assert!(app
+        .world
+        .query_filtered::<(), With<TileStorage>>()
+        .iter(&app.world)
+        .next()
+        .is_none());
+    app.update();
+    assert!(app.world.resource::<Events<MapLoaded>>().is_empty());
+}
+
+// east.tmx cut down to its first column, made solid
+const EAST_NARROWED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="1" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="2">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="collision" width="1" height="1">
+  <data encoding="csv">
+1
+</data>
+ </layer>
+ <objectgroup id="2" name="markers">
+  <object id="1" name="spawn" x="8" y="8">
+   <point/>
+  </object>
+ </objectgroup>
+</map>
+"#;
+
+#[test]
+fn reloaded_maps_keep_the_player_and_the_tile_edits_that_fit() {
+    let mut app = map_app();
+    let handle = add_map(&mut app, EAST);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle.clone(),
+        ..Default::default()
+    });
+    let player = app
+        .world
+        .spawn((PlacedAtSpawn, Transform::from_xyz(100.0, 100.0, 1.0)))
+        .id();
+    app.update();
+    assert_eq!(
+        app.world.get::<Transform>(player).unwrap().translation,
+        Vec3::new(-8.0, 0.0, 1.0)
+    );
+
+    app.world
+        .get_mut::<Transform>(player)
+        .unwrap()
+        .translation
+        .x = 100.0;
+    app.world
+        .send_event(SetTile::new("collision", TilePos::new(0, 0), 2));
+    app.world
+        .send_event(SetTile::new("collision", TilePos::new(1, 0), 3));
+    app.update();
+    assert_eq!(app.world.resource::<TileEditLog>().edits().len(), 2);
+    // the first load's event is still buffered, since no fixed update has run
+    app.world.resource_mut::<Events<MapLoaded>>().clear();
+
+    app.world
+        .resource_mut::<Assets<TiledMap>>()
+        .insert(handle.id(), tiled_map(EAST_NARROWED));
+    app.update();
+    app.update();
+    let reloaded: Vec<bool> = app
+        .world
+        .resource_mut::<Events<MapLoaded>>()
+        .drain()
+        .map(|event| event.reloaded)
+        .collect();
+    assert_eq!(reloaded, vec![true]);
+    assert_eq!(
+        app.world.get::<Transform>(player).unwrap().translation,
+        Vec3::new(100.0, 0.0, 1.0)
+    );
+    assert_eq!(
+        layer_tile(&mut app, "collision", TilePos::new(0, 0)),
+        Some(2)
+    );
+    // the second column is gone
+    let edits = app.world.resource::<TileEditLog>().edits();
+    assert_eq!(edits.len(), 1);
+    assert_eq!(edits[0].pos, TilePos::new(0, 0));
+
+    app.world.send_event(UnloadMap::default());
+    app.update();
+    assert!(app.world.resource::<TileEditLog>().edits().is_empty());
+}
+
+const BROKEN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="4" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="3" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="missing.png" width="32" height="32"/>
+  <tile id="0">
+   <properties>
+    <property name="Collides" type="bool" value="true"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,9,1,
+1,1,1
+</data>
+ </layer>
+ <objectgroup id="2" name="markers"/>
+</map>
+"#;
+
+const LZMA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="1" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <layer id="1" name="ground" width="1" height="1">
+  <data encoding="base64" compression="lzma">AAAAAA==</data>
+ </layer>
+</map>
+"#;
+
+// writes a map to its own temporary directory, so the files it names are missing
+fn temp_map(name: &str, tmx: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("gamedevjam2024-{}", name));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(format!("{}.tmx", name));
+    std::fs::write(&path, tmx).unwrap();
+    path
+}
+
+#[test]
+fn validation_reports_every_problem_with_where_it_is() {
+    let path = temp_map("broken", BROKEN);
+    let report = validate_map(&path);
+    assert!(!report.is_ok());
+    assert_eq!(report.file, path);
+
+    let messages: Vec<String> = report.errors.iter().map(|e| e.message.clone()).collect();
+    assert_eq!(report.errors.len(), 3, "{:?}", messages);
+    assert!(report
+        .errors
+        .iter()
+        .any(|error| error.layer.as_deref() == Some("ground")
+            && error.tile.is_none()
+            && error.message.contains("3×2")));
+    let gid = report
+        .errors
+        .iter()
+        .find(|error| error.tile.is_some())
+        .expect("the tile with GID 9");
+    assert_eq!(gid.tile, Some(UVec2::new(1, 0)));
+    assert_eq!(gid.layer.as_deref(), Some("ground"));
+    assert!(report
+        .errors
+        .iter()
+        .any(|error| error.layer.is_none() && error.message.contains("missing.png")));
+    assert!(report.to_string().contains("layer ground, tile (1, 0)"));
+
+    let warnings: Vec<(Option<&str>, &str)> = report
+        .warnings
+        .iter()
+        .map(|warning| (warning.layer.as_deref(), warning.message.as_str()))
+        .collect();
+    assert!(warnings.contains(&(Some("markers"), "the layer is empty")));
+    assert!(warnings
+        .iter()
+        .any(|(_, message)| message.contains("did you mean collides?")));
+}
+
+#[test]
+fn unsupported_compression_is_reported_before_parsing() {
+    let report = validate_map(temp_map("lzma", LZMA));
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].layer.as_deref(), Some("ground"));
+    assert_eq!(report.errors[0].message, "unsupported compression lzma");
+}
+
+#[test]
+fn every_map_in_assets_validates() {
+    fn maps_in(dir: &Path, maps: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                maps_in(&path, maps);
+            } else if path.extension().is_some_and(|extension| extension == "tmx") {
+                maps.push(path);
+            }
+        }
+    }
+
+    let mut maps = Vec::new();
+    maps_in(
+        &Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"),
+        &mut maps,
+    );
+    for map in maps {
+        let report = validate_map(&map);
+        assert!(report.is_ok(), "{}", report);
+    }
+}
+
+const DEPTHS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="1" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="5" nextobjectid="2">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="1" height="1">
+  <data encoding="csv">1</data>
+ </layer>
+ <layer id="2" name="canopy" width="1" height="1">
+  <properties>
+   <property name="z_band" value="above_actors"/>
+  </properties>
+  <data encoding="csv">2</data>
+ </layer>
+ <objectgroup id="3" name="props">
+  <object id="1" name="crate" x="0" y="0" width="16" height="16"/>
+ </objectgroup>
+ <layer id="4" name="roof" width="1" height="1">
+  <properties>
+   <property name="z" type="float" value="500"/>
+  </properties>
+  <data encoding="csv">3</data>
+ </layer>
+</map>
+"#;
+
+fn layer_z(app: &mut App, layer_name: &str) -> f32 {
+    app.world
+        .query::<(&TiledLayer, &Transform)>()
+        .iter(&app.world)
+        .find(|(layer, _)| layer.name == layer_name)
+        .map(|(_, transform)| transform.translation.z)
+        .unwrap()
+}
+
+#[test]
+fn layers_are_placed_in_z_by_the_bundle_then_their_properties() {
+    let mut app = map_app();
+    let handle = add_map(&mut app, DEPTHS);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle,
+        depths: LayerDepths::default()
+            .with_z("props", 250.0)
+            .with_band("roof", SpriteLayer::BelowActors),
+        ..Default::default()
+    });
+    app.update();
+
+    // unnamed and without properties: its index
+    assert_eq!(layer_z(&mut app, "ground"), 0.0);
+    assert_eq!(
+        layer_z(&mut app, "canopy"),
+        SpriteLayer::AboveActors.z() + 1.0
+    );
+    assert_eq!(layer_z(&mut app, "props"), 250.0);
+    // the bundle wins over the layer's own z
+    assert_eq!(
+        layer_z(&mut app, "roof"),
+        SpriteLayer::BelowActors.z() + 3.0
+    );
+    assert!(layer_z(&mut app, "canopy") > SpriteLayer::Actors.z());
+}
+
+const POND: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="1" tilewidth="16" tileheight="16" infinite="0" nextlayerid="4" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="1">
+  <data encoding="csv">
+1,1,1
+</data>
+ </layer>
+ <group id="2" name="gameplay">
+  <layer id="3" name="water" width="3" height="1">
+   <data encoding="csv">
+2,2,0
+</data>
+  </layer>
+ </group>
+</map>
+"#;
+
+#[derive(Component, Default)]
+struct WaterLayer;
+
+#[derive(Component, Default)]
+struct GameplayWater;
+
+#[derive(Component, Default)]
+struct GroundLayer;
+
+fn marked<C: Component>(app: &mut App) -> Vec<(u32, bool)> {
+    let mut marked: Vec<(u32, bool)> = app
+        .world
+        .query_filtered::<(Option<&TilePos>, Has<TileStorage>), With<C>>()
+        .iter(&app.world)
+        .map(|(pos, tilemap)| (pos.map_or(u32::MAX, |pos| pos.x), tilemap))
+        .collect();
+    marked.sort();
+    marked
+}
+
+#[test]
+fn registered_layer_markers_tag_the_tiles_and_tilemaps_of_their_layer() {
+    let mut app = map_app();
+    app.register_layer_marker::<WaterLayer>("water")
+        .register_layer_marker::<GameplayWater>("gameplay/water");
+    spawn_map(&mut app, POND);
+    app.update();
+
+    let water = vec![(0, false), (1, false), (u32::MAX, true)];
+    assert_eq!(marked::<WaterLayer>(&mut app), water);
+    assert_eq!(marked::<GameplayWater>(&mut app), water);
+    let names: Vec<String> = app
+        .world
+        .query_filtered::<&LayerName, With<WaterLayer>>()
+        .iter(&app.world)
+        .map(|name| name.path().to_string())
+        .collect();
+    assert!(names.iter().all(|name| name == "gameplay/water"));
+
+    // placed where the layer had no tile
+    app.world
+        .send_event(SetTile::new("water", TilePos { x: 2, y: 0 }, 1));
+    app.update();
+    assert_eq!(marked::<WaterLayer>(&mut app).len(), 4);
+
+    // registered once the map is there
+    assert!(marked::<GroundLayer>(&mut app).is_empty());
+    app.register_layer_marker::<GroundLayer>("ground");
+    app.update();
+    assert_eq!(
+        marked::<GroundLayer>(&mut app),
+        vec![(0, false), (1, false), (2, false), (u32::MAX, true)]
+    );
+}
+
+const MINI: &str = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+  <tile id="0">
+   <properties>
+    <property name="minimap_color" type="color" value="#ff3070c0"/>
+   </properties>
+  </tile>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,2,0,
+2,2,1
+</data>
+ </layer>
+</map>
+"##;
+
+fn minimap_pixels(app: &App) -> (UVec2, Vec<[u8; 4]>) {
+    let handle = &app.world.resource::<MinimapTexture>().0;
+    let image = app.world.resource::<Assets<Image>>().get(handle).unwrap();
+    let size = image.texture_descriptor.size;
+    let pixels = image
+        .data
+        .chunks(4)
+        .map(|pixel| [pixel[0], pixel[1], pixel[2], pixel[3]])
+        .collect();
+    (UVec2::new(size.width, size.height), pixels)
+}
+
+#[test]
+fn the_minimap_is_drawn_from_the_tiles_and_patched_by_set_tile() {
+    const EMPTY: [u8; 4] = [0; 4];
+    // through Color, like the minimap
+    let blue = Color::rgba_u8(0x30, 0x70, 0xc0, 0xff).as_rgba_u8();
+    let green = Color::rgb_u8(10, 200, 10).as_rgba_u8();
+    let mut app = map_app();
+    app.init_asset::<Image>().insert_resource(
+        MinimapSettings::default().with_tileset_color("terrain", Color::rgb_u8(10, 200, 10)),
+    );
+    spawn_map(&mut app, MINI);
+    app.update();
+
+    // laid out as in Tiled, top row first
+    let (size, pixels) = minimap_pixels(&app);
+    assert_eq!(size, UVec2::new(3, 2));
+    assert_eq!(pixels, vec![blue, green, EMPTY, green, green, blue]);
+
+    // the top right tile, and the bottom left one
+    app.world
+        .send_event(SetTile::new("ground", TilePos { x: 2, y: 1 }, 0));
+    app.world
+        .send_event(SetTile::remove("ground", TilePos { x: 0, y: 0 }));
+    app.update();
+    let (_, pixels) = minimap_pixels(&app);
+    assert_eq!(pixels, vec![blue, green, blue, EMPTY, green, blue]);
+}
+
+#[test]
+fn big_maps_get_a_sampled_minimap() {
+    let mut app = map_app();
+    app.init_asset::<Image>().insert_resource(MinimapSettings {
+        max_size: 2,
+        ..Default::default()
+    });
+    spawn_map(&mut app, MINI);
+    app.update();
+
+    // every other tile of the top row
+    let (size, pixels) = minimap_pixels(&app);
+    assert_eq!(size, UVec2::new(2, 1));
+    let blue = Color::rgba_u8(0x30, 0x70, 0xc0, 0xff).as_rgba_u8();
+    assert_eq!(pixels, vec![blue, [0; 4]]);
+}
@@ -0,0 +1,49 @@
+//! Tests for the memory stats and the tracking allocator.
+
+use gamedevjam2024::memory::{
+    allocated, live_allocations, peak_allocated, MemoryStats, TrackingAllocator, MEMORY_LOG_STEP,
+};
+use std::alloc::{GlobalAlloc, Layout, System};
+
+#[test]
+fn the_tracking_allocator_counts_live_bytes() {
+    let allocator = TrackingAllocator::new(System);
+    let layout = Layout::from_size_align(4096, 8).unwrap();
+    let (before, allocations) = (allocated(), live_allocations());
+
+    unsafe {
+        let ptr = allocator.alloc(layout);
+        assert!(!ptr.is_null());
+        assert_eq!(allocated(), before + 4096);
+        assert_eq!(live_allocations(), allocations + 1);
+        assert!(peak_allocated() >= before + 4096);
+
+        let ptr = allocator.realloc(ptr, layout, 8192);
+        assert_eq!(allocated(), before + 8192);
+        allocator.dealloc(ptr, Layout::from_size_align(8192, 8).unwrap());
+    }
+    assert_eq!(allocated(), before);
+    assert_eq!(live_allocations(), allocations);
+    assert!(peak_allocated() >= before + 8192);
+}
+
+fn sample(linear_memory: usize) -> MemoryStats {
+    MemoryStats {
+        linear_memory: Some(linear_memory),
+        ..MemoryStats::default()
+    }
+}
+
+#[test]
+fn growth_is_reported_once_per_step() {
+    let mut logged_step = 0;
+    let mut passed = |linear_memory| sample(linear_memory).passed_step(&mut logged_step);
+    assert_eq!(passed(MEMORY_LOG_STEP / 2), None);
+    assert_eq!(passed(MEMORY_LOG_STEP + 1), Some(MEMORY_LOG_STEP));
+    assert_eq!(passed(MEMORY_LOG_STEP + 2), None);
+    // shrinking and growing back isn't news
+    assert_eq!(passed(MEMORY_LOG_STEP / 2), None);
+    assert_eq!(passed(MEMORY_LOG_STEP + 1), None);
+    assert_eq!(passed(3 * MEMORY_LOG_STEP), Some(3 * MEMORY_LOG_STEP));
+    assert_eq!(MemoryStats::default().passed_step(&mut 0), None);
+}
@@ -0,0 +1,75 @@
+//! Tests for StartOptions flags.
+
+use gamedevjam2024::options::StartOptions;
+use gamedevjam2024::state::AppState;
+
+#[test]
+fn flags_override_the_options() {
+    let mut options = StartOptions::default();
+    let problems = options.apply_flags(vec![
+        ("map", "level3.tmx"),
+        ("mute", "1"),
+        ("seed", "42"),
+        ("debug", ""),
+        ("state", "in_game"),
+    ]);
+    assert!(problems.is_empty(), "{:?}", problems);
+    assert_eq!(
+        options,
+        StartOptions {
+            map: "level3.tmx".to_string(),
+            muted: true,
+            seed: Some(42),
+            debug: true,
+            skip_to: Some(AppState::InGame),
+            ..StartOptions::default()
+        }
+    );
+}
+
+#[test]
+fn invalid_and_unknown_flags_are_reported_and_ignored() {
+    let mut options = StartOptions::default();
+    let problems = options.apply_flags(vec![
+        ("seed", "forty-two"),
+        ("mute", "loud"),
+        ("state", "paused"),
+        ("utm_source", "discord"),
+        ("fps", "1"),
+    ]);
+    assert_eq!(options, StartOptions::default());
+    assert_eq!(problems.len(), 4);
+    assert!(problems[0].contains("seeding at random"));
+    assert_eq!(problems[3], "ignoring unknown flags: utm_source, fps");
+}
+
+#[test]
+fn flags_record_and_replay_sessions() {
+    let mut options = StartOptions::default();
+    let problems = options.apply_flags(vec![("record", "1"), ("check", "64"), ("replay", "no")]);
+    assert_eq!(problems, vec!["replay=no isn't 0 or 1".to_string()]);
+    assert!(options.record);
+    assert!(!options.replay);
+    assert_eq!(options.check_every, Some(64));
+
+    let problems = options.apply_flags(vec![("check", "0")]);
+    assert_eq!(problems.len(), 1);
+    assert_eq!(options.check_every, Some(64));
+}
+
+#[test]
+fn the_touch_flag_forces_the_touch_controls() {
+    let mut options = StartOptions::default();
+    assert_eq!(options.touch_controls, None);
+    assert!(options.apply_flags(vec![("touch", "0")]).is_empty());
+    assert_eq!(options.touch_controls, Some(false));
+}
+
+#[test]
+fn the_inputoverlay_flag_shows_the_input_display() {
+    let mut options = StartOptions::default();
+    assert!(options.apply_flags(vec![("inputoverlay", "1")]).is_empty());
+    assert!(options.input_overlay);
+    assert_eq!(options.apply_flags(vec![("inputoverlay", "on")]).len(), 1);
+    assert!(options.input_overlay);
+}
@@ -0,0 +1,116 @@
+//! Tests for the pause menu.
+
+use bevy::prelude::*;
+use gamedevjam2024::helpers::tiled::{RestartMap, UnloadMap};
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState, SetTimeScale};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    ButtonActivated, MenuFocus, OpenSettings, PauseMenuButton, PauseMenuRoot, SettingsClosed,
+    UiPlugin,
+};
+
+/// The game paused with the Pause action, in slow motion
+fn paused_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    app.world.send_event(SetTimeScale::new(0.5));
+    run_frames(&mut app, 2);
+    tap(&mut app, KeyCode::Escape);
+    run_frames(&mut app, 1);
+    app
+}
+
+fn state(app: &App) -> AppState {
+    *app.world.resource::<State<AppState>>().get()
+}
+
+fn menus(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<PauseMenuRoot>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn focused(app: &mut App) -> Option<PauseMenuButton> {
+    let focus = app.world.resource::<MenuFocus>().0?;
+    app.world.get::<PauseMenuButton>(focus).copied()
+}
+
+fn activate(app: &mut App, which: PauseMenuButton) {
+    let button = app
+        .world
+        .query::<(Entity, &PauseMenuButton)>()
+        .iter(&app.world)
+        .find(|(_, button)| **button == which)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    app.world.send_event(ButtonActivated(button));
+    run_frames(app, 2);
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+fn speed(app: &App) -> f32 {
+    app.world.resource::<Time<Virtual>>().relative_speed()
+}
+
+#[test]
+fn resuming_puts_the_game_back_as_it_was() {
+    let mut app = paused_app();
+    assert_eq!(state(&app), AppState::Paused);
+    assert_eq!(menus(&mut app), 1);
+    assert_eq!(focused(&mut app), Some(PauseMenuButton::Resume));
+    assert_eq!(speed(&app), 0.0);
+
+    tap(&mut app, KeyCode::KeyE);
+    run_frames(&mut app, 1);
+    assert_eq!(state(&app), AppState::InGame);
+    assert_eq!(menus(&mut app), 0);
+    assert_eq!(speed(&app), 0.5);
+}
+
+#[test]
+fn settings_come_back_to_the_pause_menu() {
+    let mut app = paused_app();
+    activate(&mut app, PauseMenuButton::Settings);
+    assert_eq!(drain::<OpenSettings>(&mut app).len(), 1);
+    assert_eq!(menus(&mut app), 0);
+
+    app.world.send_event(SettingsClosed);
+    run_frames(&mut app, 1);
+    assert_eq!(state(&app), AppState::Paused);
+    assert_eq!(menus(&mut app), 1);
+    assert_eq!(focused(&mut app), Some(PauseMenuButton::Settings));
+}
+
+#[test]
+fn restarting_resumes_and_restarts_the_map() {
+    let mut app = paused_app();
+    activate(&mut app, PauseMenuButton::RestartLevel);
+    assert_eq!(drain::<RestartMap>(&mut app).len(), 1);
+    assert_eq!(state(&app), AppState::InGame);
+}
+
+#[test]
+fn quitting_goes_back_to_the_main_menu_without_the_map() {
+    let mut app = paused_app();
+    activate(&mut app, PauseMenuButton::QuitToMenu);
+    assert_eq!(drain::<UnloadMap>(&mut app).len(), 1);
+    assert_eq!(state(&app), AppState::MainMenu);
+    assert_eq!(menus(&mut app), 0);
+}
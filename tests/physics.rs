@@ -0,0 +1,153 @@
+//! Tests for moving entities and their collisions with walls.
+
+use bevy::prelude::*;
+use gamedevjam2024::helpers::tiled::CollisionMap;
+use gamedevjam2024::physics::{AabbCollider, HitWall, Knockback, PhysicsPlugin, Velocity};
+use gamedevjam2024::testing::{headless_app, run_frames};
+
+/// Every HitWall sent
+#[derive(Resource, Default)]
+struct Hits(Vec<HitWall>);
+
+fn collect_hits(mut events: EventReader<HitWall>, mut hits: ResMut<Hits>) {
+    hits.0.extend(events.read().copied());
+}
+
+/// A 10x10 map of 16 unit tiles with its bottom left corner at the origin and `solid` tiles
+fn physics_app(solid: &[(i32, i32)]) -> App {
+    let mut map = CollisionMap::default();
+    map.reset(UVec2::splat(10), Vec2::splat(16.0), Vec2::ZERO);
+    for (x, y) in solid {
+        map.set_solid(*x, *y, true);
+    }
+    let mut app = headless_app();
+    app.add_plugins(PhysicsPlugin)
+        .insert_resource(map)
+        .init_resource::<Hits>()
+        .add_systems(Update, collect_hits);
+    // the clock doesn't move on the first frame, so each frame after runs one step
+    run_frames(&mut app, 1);
+    app
+}
+
+/// The wall along the column from x = 80 to 96
+fn wall() -> Vec<(i32, i32)> {
+    (0..10).map(|y| (5, y)).collect()
+}
+
+fn spawn_mover(app: &mut App, at: Vec2, half_extents: Vec2, velocity: Vec2) -> Entity {
+    app.world
+        .spawn((
+            AabbCollider::new(half_extents),
+            Velocity(velocity),
+            TransformBundle::from_transform(Transform::from_translation(at.extend(0.0))),
+        ))
+        .id()
+}
+
+fn position(app: &App, entity: Entity) -> Vec2 {
+    app.world
+        .get::<Transform>(entity)
+        .unwrap()
+        .translation
+        .truncate()
+}
+
+#[test]
+fn movers_stop_at_walls() {
+    let mut app = physics_app(&wall());
+    let mover = spawn_mover(
+        &mut app,
+        Vec2::new(40.0, 40.0),
+        Vec2::splat(4.0),
+        Vec2::new(200.0, 0.0),
+    );
+    run_frames(&mut app, 32);
+    assert!((position(&app, mover).x - 76.0).abs() < 0.1);
+    assert_eq!(app.world.get::<Velocity>(mover).unwrap().0, Vec2::ZERO);
+    assert_eq!(
+        app.world.resource::<Hits>().0,
+        vec![HitWall {
+            entity: mover,
+            normal: Vec2::NEG_X
+        }]
+    );
+}
+
+#[test]
+fn movers_slide_along_walls() {
+    let mut app = physics_app(&wall());
+    let mover = spawn_mover(
+        &mut app,
+        Vec2::new(70.0, 20.0),
+        Vec2::splat(4.0),
+        Vec2::new(100.0, 64.0),
+    );
+    run_frames(&mut app, 4);
+    let stopped = position(&app, mover);
+    assert!((stopped.x - 76.0).abs() < 0.1);
+    run_frames(&mut app, 4);
+    // a step of 1 up each frame
+    assert!((position(&app, mover).y - stopped.y - 4.0).abs() < 1e-3);
+}
+
+#[test]
+fn fast_movers_do_not_tunnel_through_thin_walls() {
+    let mut app = physics_app(&wall());
+    // 100 units a step, past the whole wall
+    let mover = spawn_mover(
+        &mut app,
+        Vec2::new(40.0, 40.0),
+        Vec2::splat(2.0),
+        Vec2::new(6400.0, 0.0),
+    );
+    run_frames(&mut app, 1);
+    assert!((position(&app, mover).x - 78.0).abs() < 0.5);
+}
+
+#[test]
+fn colliders_bigger_than_a_tile_collide_all_along_them() {
+    // a single tile at x 80..96, y 48..64
+    let mut app = physics_app(&[(5, 3)]);
+    let mover = spawn_mover(
+        &mut app,
+        Vec2::new(40.0, 70.0),
+        Vec2::splat(20.0),
+        Vec2::new(200.0, 0.0),
+    );
+    run_frames(&mut app, 32);
+    assert!((position(&app, mover).x - 60.0).abs() < 0.1);
+}
+
+#[test]
+fn knockback_dies_down_and_stops_at_walls() {
+    let mut app = physics_app(&wall());
+    let pushed = spawn_mover(
+        &mut app,
+        Vec2::new(20.0, 40.0),
+        Vec2::splat(4.0),
+        Vec2::ZERO,
+    );
+    let knocked = spawn_mover(
+        &mut app,
+        Vec2::new(40.0, 80.0),
+        Vec2::splat(4.0),
+        Vec2::ZERO,
+    );
+    app.world
+        .entity_mut(pushed)
+        .insert(Knockback(Vec2::new(100.0, 0.0)));
+    // hard enough to go through the wall, undamped
+    app.world
+        .entity_mut(knocked)
+        .insert(Knockback(Vec2::new(6400.0, 0.0)));
+    run_frames(&mut app, 64);
+
+    // 100 units a second, damped, goes about 9
+    let moved = position(&app, pushed).x - 20.0;
+    assert!(moved > 6.0 && moved < 10.0, "{}", moved);
+    assert!(!app.world.entity(pushed).contains::<Knockback>());
+    assert!((position(&app, knocked).x - 76.0).abs() < 0.1);
+    assert!(!app.world.entity(knocked).contains::<Knockback>());
+    assert_eq!(app.world.get::<Velocity>(knocked).unwrap().0, Vec2::ZERO);
+}
@@ -0,0 +1,122 @@
+//! Tests for picking world sprites with the mouse.
+
+use bevy::prelude::*;
+use gamedevjam2024::picking::{
+    Clicked, Highlight, Pickable, PickingPlugin, PointedAt, PointerEntered, PointerExited,
+};
+use gamedevjam2024::pointer::CursorWorldPosition;
+use gamedevjam2024::testing::{headless_app, run_frames};
+
+fn picking_app() -> App {
+    let mut app = headless_app();
+    app.add_plugins((TransformPlugin, PickingPlugin))
+        .init_resource::<ButtonInput<MouseButton>>();
+    run_frames(&mut app, 1);
+    app
+}
+
+fn spawn_pickable(app: &mut App, at: Vec3) -> Entity {
+    app.world
+        .spawn((
+            Pickable {
+                size: Vec2::splat(16.0),
+            },
+            SpriteBundle {
+                transform: Transform::from_translation(at),
+                ..default()
+            },
+        ))
+        .id()
+}
+
+fn point_at(app: &mut App, position: Option<Vec2>) {
+    app.world.resource_mut::<CursorWorldPosition>().0 = position;
+    run_frames(app, 1);
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+#[test]
+fn the_topmost_under_the_cursor_is_picked() {
+    let mut app = picking_app();
+    let back = spawn_pickable(&mut app, Vec3::new(0.0, 0.0, 1.0));
+    let front = spawn_pickable(&mut app, Vec3::new(10.0, 0.0, 2.0));
+    run_frames(&mut app, 1);
+
+    point_at(&mut app, Some(Vec2::new(-5.0, 0.0)));
+    assert_eq!(app.world.resource::<PointedAt>().0, Some(back));
+    point_at(&mut app, Some(Vec2::new(5.0, 0.0)));
+    assert_eq!(app.world.resource::<PointedAt>().0, Some(front));
+    assert_eq!(
+        drain::<PointerEntered>(&mut app),
+        vec![
+            PointerEntered { entity: back },
+            PointerEntered { entity: front }
+        ]
+    );
+    assert_eq!(
+        drain::<PointerExited>(&mut app),
+        vec![PointerExited { entity: back }]
+    );
+
+    point_at(&mut app, None);
+    assert_eq!(app.world.resource::<PointedAt>().0, None);
+    assert_eq!(
+        drain::<PointerExited>(&mut app),
+        vec![PointerExited { entity: front }]
+    );
+}
+
+#[test]
+fn clicks_go_to_what_is_pointed_at() {
+    let mut app = picking_app();
+    let chest = spawn_pickable(&mut app, Vec3::ZERO);
+    run_frames(&mut app, 1);
+    point_at(&mut app, Some(Vec2::ZERO));
+    app.world
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .press(MouseButton::Right);
+    run_frames(&mut app, 1);
+    assert_eq!(
+        drain::<Clicked>(&mut app),
+        vec![Clicked {
+            entity: chest,
+            button: MouseButton::Right
+        }]
+    );
+}
+
+#[test]
+fn the_ui_swallows_the_pointer() {
+    let mut app = picking_app();
+    let enemy = spawn_pickable(&mut app, Vec3::ZERO);
+    let button = app.world.spawn(ButtonBundle::default()).id();
+    run_frames(&mut app, 1);
+    point_at(&mut app, Some(Vec2::ZERO));
+    assert_eq!(app.world.resource::<PointedAt>().0, Some(enemy));
+
+    app.world.entity_mut(button).insert(Interaction::Pressed);
+    app.world
+        .resource_mut::<ButtonInput<MouseButton>>()
+        .press(MouseButton::Left);
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.resource::<PointedAt>().0, None);
+    assert!(drain::<Clicked>(&mut app).is_empty());
+}
+
+#[test]
+fn highlights_tint_while_pointed_at() {
+    let mut app = picking_app();
+    let enemy = spawn_pickable(&mut app, Vec3::ZERO);
+    app.world
+        .entity_mut(enemy)
+        .insert(Highlight::new(Color::YELLOW));
+    run_frames(&mut app, 1);
+
+    point_at(&mut app, Some(Vec2::ZERO));
+    assert_eq!(app.world.get::<Sprite>(enemy).unwrap().color, Color::YELLOW);
+    point_at(&mut app, Some(Vec2::new(100.0, 0.0)));
+    assert_eq!(app.world.get::<Sprite>(enemy).unwrap().color, Color::WHITE);
+}
@@ -0,0 +1,150 @@
+//! Tests for coins and hearts.
+
+use bevy::prelude::*;
+use gamedevjam2024::helpers::tiled::{LoadMap, ObjectShape, TiledObject};
+use gamedevjam2024::physics::AabbCollider;
+use gamedevjam2024::pickups::{
+    spawn_pickup, Pickup, PickupCollected, PickupKind, PickupsPlugin, COIN_TYPE,
+};
+use gamedevjam2024::player::Player;
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::testing::{game_app, run_frames};
+
+/// Every PickupCollected sent
+#[derive(Resource, Default)]
+struct Collected(Vec<PickupCollected>);
+
+fn collect(mut events: EventReader<PickupCollected>, mut collected: ResMut<Collected>) {
+    collected.0.extend(events.read().copied());
+}
+
+/// The game running on "level1.tmx", with the player at the origin
+fn pickups_app() -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((TransformPlugin, PickupsPlugin))
+        .init_resource::<Collected>()
+        .add_systems(Update, collect);
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    let player = app
+        .world
+        .spawn((
+            Player,
+            AabbCollider::new(Vec2::splat(4.0)),
+            TransformBundle::default(),
+        ))
+        .id();
+    run_frames(&mut app, 1);
+    (app, player)
+}
+
+/// A pickup placed on the map as the object `id`, at `x`
+fn spawn_object_pickup(app: &mut App, id: u32, x: f32) -> Entity {
+    let object = TiledObject {
+        id,
+        name: String::new(),
+        object_type: COIN_TYPE.to_string(),
+        properties: Default::default(),
+        shape: ObjectShape::Point,
+    };
+    app.world
+        .spawn((
+            object,
+            Pickup {
+                kind: PickupKind::Coin,
+                amount: 1,
+            },
+            AabbCollider::new(Vec2::splat(5.0)),
+            TransformBundle::from_transform(Transform::from_xyz(x, 0.0, 0.0)),
+        ))
+        .id()
+}
+
+fn spawn_drop(app: &mut App, kind: PickupKind, amount: i32, x: f32) -> Entity {
+    let mut queue = bevy::ecs::system::CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, &app.world);
+    let pickup = spawn_pickup(&mut commands, kind, amount, Vec3::new(x, 0.0, 0.0));
+    queue.apply(&mut app.world);
+    pickup
+}
+
+fn walk_to(app: &mut App, player: Entity, x: f32) {
+    app.world
+        .get_mut::<Transform>(player)
+        .unwrap()
+        .translation
+        .x = x;
+    run_frames(app, 3);
+}
+
+#[test]
+fn touching_a_pickup_picks_it_up() {
+    let (mut app, player) = pickups_app();
+    app.world.resource_mut::<GameProgress>().score = 10;
+    let coin = spawn_drop(&mut app, PickupKind::Coin, 5, 40.0);
+    run_frames(&mut app, 3);
+    assert!(app.world.get_entity(coin).is_some());
+
+    app.world.resource_mut::<Events<PlaySFX>>().clear();
+    walk_to(&mut app, player, 36.0);
+    assert!(app.world.get_entity(coin).is_none());
+    assert_eq!(app.world.resource::<GameProgress>().score, 15);
+    assert_eq!(
+        app.world.resource::<Collected>().0,
+        vec![PickupCollected {
+            kind: PickupKind::Coin,
+            amount: 5,
+        }]
+    );
+    let sounds: Vec<String> = app
+        .world
+        .resource_mut::<Events<PlaySFX>>()
+        .drain()
+        .map(|sound| sound.name)
+        .collect();
+    assert!(sounds.contains(&COIN_TYPE.to_string()));
+}
+
+#[test]
+fn hearts_heal_up_to_max_health() {
+    let (mut app, player) = pickups_app();
+    {
+        let mut progress = app.world.resource_mut::<GameProgress>();
+        progress.health = Some(2);
+        progress.max_health = Some(3);
+    }
+    spawn_drop(&mut app, PickupKind::Heart, 2, 40.0);
+    run_frames(&mut app, 1);
+    walk_to(&mut app, player, 40.0);
+    assert_eq!(app.world.resource::<GameProgress>().health, Some(3));
+}
+
+#[test]
+fn map_pickups_stay_picked_up() {
+    let (mut app, player) = pickups_app();
+    spawn_object_pickup(&mut app, 7, 40.0);
+    run_frames(&mut app, 1);
+    walk_to(&mut app, player, 40.0);
+    assert!(app
+        .world
+        .resource::<GameProgress>()
+        .has_collected("level1.tmx", 7));
+
+    // coming back to the map
+    walk_to(&mut app, player, -100.0);
+    let again = spawn_object_pickup(&mut app, 7, 40.0);
+    let other = spawn_object_pickup(&mut app, 8, 80.0);
+    run_frames(&mut app, 1);
+    assert!(app.world.get_entity(again).is_none());
+    assert!(app.world.get_entity(other).is_some());
+
+    // while drops come back every time
+    let first = spawn_drop(&mut app, PickupKind::Coin, 1, 40.0);
+    walk_to(&mut app, player, 40.0);
+    assert!(app.world.get_entity(first).is_none());
+    walk_to(&mut app, player, -100.0);
+    let second = spawn_drop(&mut app, PickupKind::Coin, 1, 40.0);
+    run_frames(&mut app, 1);
+    assert!(app.world.get_entity(second).is_some());
+    assert_eq!(app.world.resource::<GameProgress>().score, 2);
+}
@@ -0,0 +1,210 @@
+//! Tests for side-view movement: gravity, jumping and one-way platforms.
+
+use bevy::prelude::*;
+use gamedevjam2024::helpers::tiled::{CollisionMap, MapProperties};
+use gamedevjam2024::physics::{AabbCollider, PhysicsPlugin, Velocity};
+use gamedevjam2024::platformer::{
+    Gravity, PlatformerController, PlatformerPlugin, DEFAULT_GRAVITY, GRAVITY_PROPERTY, LAND_SOUND,
+};
+use gamedevjam2024::player::{MovementStats, Player};
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::testing::{game_app, run_frames};
+
+/// Every PlaySFX sent
+#[derive(Resource, Default)]
+struct Sounds(Vec<String>);
+
+fn collect_sounds(mut events: EventReader<PlaySFX>, mut sounds: ResMut<Sounds>) {
+    sounds
+        .0
+        .extend(events.read().map(|sound| sound.name.clone()));
+}
+
+fn side_view(gravity: f32) -> MapProperties {
+    let mut properties = tiled::Properties::new();
+    properties.insert(
+        GRAVITY_PROPERTY.to_string(),
+        tiled::PropertyValue::FloatValue(gravity),
+    );
+    MapProperties(properties)
+}
+
+/// A side-view map of 16 unit tiles with its bottom left corner at the origin: a floor 10 tiles
+/// long, its top at y = 16, and a one-way platform over its first 5 tiles, its top at y = 48
+fn platformer_app() -> App {
+    let mut map = CollisionMap::default();
+    map.reset(UVec2::new(20, 10), Vec2::splat(16.0), Vec2::ZERO);
+    for x in 0..10 {
+        map.set_solid(x, 0, true);
+    }
+    for x in 0..5 {
+        map.set_one_way(x, 2, true);
+    }
+    let mut app = game_app();
+    app.add_plugins((PhysicsPlugin, PlatformerPlugin))
+        .init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<Sounds>()
+        .add_systems(Update, collect_sounds);
+    app.insert_resource(map)
+        .insert_resource(side_view(DEFAULT_GRAVITY));
+    run_frames(&mut app, 1);
+    app
+}
+
+/// A player platformer 8 units square, centred on `at`
+fn spawn_platformer(app: &mut App, at: Vec2) -> Entity {
+    app.world
+        .spawn((
+            Player,
+            MovementStats::default(),
+            PlatformerController::default(),
+            Velocity::default(),
+            AabbCollider::new(Vec2::splat(4.0)),
+            TransformBundle::from_transform(Transform::from_translation(at.extend(0.0))),
+        ))
+        .id()
+}
+
+fn position(app: &App, entity: Entity) -> Vec2 {
+    app.world
+        .get::<Transform>(entity)
+        .unwrap()
+        .translation
+        .truncate()
+}
+
+fn velocity(app: &App, entity: Entity) -> Vec2 {
+    app.world.get::<Velocity>(entity).unwrap().0
+}
+
+fn hold(app: &mut App, keys: &[KeyCode]) {
+    let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    input.release_all();
+    input.clear();
+    for key in keys {
+        input.press(*key);
+    }
+}
+
+/// The highest the entity gets over `frames` frames
+fn highest(app: &mut App, entity: Entity, frames: usize) -> f32 {
+    (0..frames)
+        .map(|_| {
+            run_frames(app, 1);
+            position(app, entity).y
+        })
+        .fold(f32::MIN, f32::max)
+}
+
+#[test]
+fn platformers_fall_and_land_on_the_ground() {
+    let mut app = platformer_app();
+    let platformer = spawn_platformer(&mut app, Vec2::new(120.0, 60.0));
+    run_frames(&mut app, 64);
+
+    assert!((position(&app, platformer).y - 20.0).abs() < 0.1);
+    assert_eq!(velocity(&app, platformer), Vec2::ZERO);
+    assert!(app
+        .world
+        .get::<PlatformerController>(platformer)
+        .unwrap()
+        .grounded());
+    assert_eq!(
+        app.world.resource::<Sounds>().0,
+        vec![LAND_SOUND.to_string()]
+    );
+}
+
+#[test]
+fn letting_go_of_jump_cuts_it_short() {
+    let mut app = platformer_app();
+    let platformer = spawn_platformer(&mut app, Vec2::new(120.0, 20.0));
+    run_frames(&mut app, 2);
+
+    hold(&mut app, &[KeyCode::KeyL]);
+    let full = highest(&mut app, platformer, 48) - 20.0;
+    hold(&mut app, &[]);
+    run_frames(&mut app, 32);
+    assert!((position(&app, platformer).y - 20.0).abs() < 0.1);
+
+    hold(&mut app, &[KeyCode::KeyL]);
+    run_frames(&mut app, 2);
+    hold(&mut app, &[]);
+    let hop = highest(&mut app, platformer, 48) - 20.0;
+    assert!(full > 30.0, "jumped {}", full);
+    assert!(hop < full / 2.0, "hopped {} of {}", hop, full);
+}
+
+#[test]
+fn jumps_only_work_from_the_ground_or_just_off_it() {
+    let mut app = platformer_app();
+    // about to walk off the end of the floor
+    let platformer = spawn_platformer(&mut app, Vec2::new(163.0, 20.0));
+    run_frames(&mut app, 2);
+    hold(&mut app, &[KeyCode::KeyD]);
+    run_frames(&mut app, 4);
+    assert!(velocity(&app, platformer).y < 0.0);
+    hold(&mut app, &[KeyCode::KeyD, KeyCode::KeyL]);
+    run_frames(&mut app, 1);
+    assert!(velocity(&app, platformer).y > 0.0);
+
+    // and not again in the air
+    hold(&mut app, &[]);
+    run_frames(&mut app, 24);
+    assert!(velocity(&app, platformer).y < 0.0);
+    hold(&mut app, &[KeyCode::KeyL]);
+    run_frames(&mut app, 2);
+    assert!(velocity(&app, platformer).y < 0.0);
+}
+
+#[test]
+fn jumps_pressed_just_before_landing_are_kept() {
+    let mut app = platformer_app();
+    let platformer = spawn_platformer(&mut app, Vec2::new(120.0, 60.0));
+    // 3 frames from landing
+    run_frames(&mut app, 18);
+    hold(&mut app, &[KeyCode::KeyL]);
+    run_frames(&mut app, 1);
+    hold(&mut app, &[]);
+    assert!(velocity(&app, platformer).y < 0.0);
+    let jumped = (0..8).any(|_| {
+        run_frames(&mut app, 1);
+        velocity(&app, platformer).y > 0.0
+    });
+    assert!(jumped);
+}
+
+#[test]
+fn one_way_platforms_are_jumped_through_and_landed_on() {
+    let mut app = platformer_app();
+    let platformer = spawn_platformer(&mut app, Vec2::new(40.0, 20.0));
+    run_frames(&mut app, 2);
+    hold(&mut app, &[KeyCode::KeyL]);
+    run_frames(&mut app, 64);
+
+    assert!((position(&app, platformer).y - 52.0).abs() < 0.1);
+    assert!(app
+        .world
+        .get::<PlatformerController>(platformer)
+        .unwrap()
+        .grounded());
+}
+
+#[test]
+fn maps_with_gravity_make_the_player_a_platformer() {
+    let mut app = game_app();
+    app.add_plugins(PlatformerPlugin);
+    let player = app.world.spawn(Player).id();
+    run_frames(&mut app, 1);
+    assert!(!app.world.entity(player).contains::<PlatformerController>());
+
+    app.insert_resource(side_view(500.0));
+    run_frames(&mut app, 1);
+    assert!(app.world.entity(player).contains::<PlatformerController>());
+    assert_eq!(*app.world.resource::<Gravity>(), Gravity(500.0));
+
+    app.world.resource_mut::<MapProperties>().clear();
+    run_frames(&mut app, 1);
+    assert!(!app.world.entity(player).contains::<PlatformerController>());
+    assert_eq!(app.world.resource::<Gravity>().0, DEFAULT_GRAVITY);
+}
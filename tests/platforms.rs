@@ -0,0 +1,195 @@
+//! Tests for moving platforms and what stands on them.
+
+use bevy::prelude::*;
+use gamedevjam2024::health::Damage;
+use gamedevjam2024::helpers::tiled::{CollisionMap, LoadMap, ObjectShape, TiledObject};
+use gamedevjam2024::physics::{AabbCollider, KinematicCollider, PhysicsPlugin, Velocity};
+use gamedevjam2024::platforms::{MovingPlatform, PlatformSpawner, PlatformsPlugin};
+use gamedevjam2024::testing::{game_app, run_frames};
+
+/// Every Damage sent
+#[derive(Resource, Default)]
+struct Damaged(Vec<Damage>);
+
+fn collect_damage(mut events: EventReader<Damage>, mut damaged: ResMut<Damaged>) {
+    damaged.0.extend(events.read().copied());
+}
+
+/// A 10x10 map of 16 unit tiles with its bottom left corner at the origin and `solid` tiles
+fn platforms_app(solid: &[(i32, i32)]) -> App {
+    let mut app = game_app();
+    app.add_plugins((TransformPlugin, PhysicsPlugin, PlatformsPlugin))
+        .init_resource::<Damaged>()
+        .add_systems(Update, collect_damage);
+    app.world.send_event(LoadMap::new("level1.tmx"));
+    run_frames(&mut app, 1);
+    let mut map = CollisionMap::default();
+    map.reset(UVec2::splat(10), Vec2::splat(16.0), Vec2::ZERO);
+    for (x, y) in solid {
+        map.set_solid(*x, *y, true);
+    }
+    app.insert_resource(map);
+    app
+}
+
+fn object(id: u32, object_type: &str, shape: ObjectShape) -> TiledObject {
+    TiledObject {
+        id,
+        name: String::new(),
+        object_type: object_type.to_string(),
+        properties: tiled::Properties::new(),
+        shape,
+    }
+}
+
+/// A 32x8 platform at (40, 20) following a polyline to `to` from there, where it starts out;
+/// `crushes` ones do 2 damage
+fn spawn_platform(app: &mut App, to: Vec2, crushes: bool) -> Entity {
+    let at = Transform::from_xyz(40.0, 20.0, 0.0);
+    let points = vec![Vec2::ZERO, to];
+    app.world.spawn((
+        object(2, "", ObjectShape::Polyline { points }),
+        TransformBundle::from_transform(at),
+    ));
+    let mut platform = object(
+        1,
+        "platform",
+        ObjectShape::Rect {
+            size: Vec2::new(32.0, 8.0),
+        },
+    );
+    for (name, value) in [
+        ("crushes", tiled::PropertyValue::BoolValue(crushes)),
+        ("damage", tiled::PropertyValue::IntValue(2)),
+    ] {
+        platform.properties.insert(name.to_string(), value);
+    }
+    app.world.spawn((
+        platform,
+        PlatformSpawner { path: Some(2) },
+        TransformBundle::from_transform(at),
+    ));
+    run_frames(app, 1);
+    app.world
+        .query_filtered::<Entity, With<MovingPlatform>>()
+        .single(&app.world)
+}
+
+/// Something 8x8 standing on the middle of `platform` of spawn_platform
+fn spawn_rider(app: &mut App, platform: Entity) -> Entity {
+    let at = position(app, platform) + Vec2::new(0.0, 8.0);
+    app.world
+        .spawn((
+            AabbCollider::new(Vec2::splat(4.0)),
+            Velocity::default(),
+            TransformBundle::from_transform(Transform::from_translation(at.extend(0.0))),
+        ))
+        .id()
+}
+
+fn position(app: &App, entity: Entity) -> Vec2 {
+    app.world
+        .get::<Transform>(entity)
+        .unwrap()
+        .translation
+        .truncate()
+}
+
+#[test]
+fn platforms_go_back_and_forth_by_the_clock() {
+    let platform =
+        MovingPlatform::new(vec![Vec2::ZERO, Vec2::new(40.0, 0.0)], false, 40.0).with_pause(0.5);
+    for (seconds, x) in [
+        (0.0, 0.0),
+        (0.5, 20.0),
+        (1.0, 40.0),
+        (1.4, 40.0),
+        (2.0, 20.0),
+        (2.6, 0.0),
+        (3.0, 0.0),
+        (3.5, 20.0),
+        (300.5, 20.0),
+    ] {
+        let at = platform.position_at(seconds);
+        assert!(
+            (at - Vec2::new(x, 0.0)).length() < 1e-3,
+            "{}: {}",
+            seconds,
+            at
+        );
+    }
+
+    // round a square, pausing at the start
+    let square = MovingPlatform::new(
+        vec![
+            Vec2::ZERO,
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(0.0, 10.0),
+        ],
+        true,
+        10.0,
+    )
+    .with_pause(1.0);
+    assert_eq!(square.length(), 40.0);
+    assert!((square.position_at(1.5) - Vec2::new(10.0, 5.0)).length() < 1e-3);
+    assert!((square.position_at(3.5) - Vec2::new(0.0, 5.0)).length() < 1e-3);
+    assert!((square.position_at(4.5) - Vec2::ZERO).length() < 1e-3);
+    assert!((square.position_at(5.5) - Vec2::new(5.0, 0.0)).length() < 1e-3);
+
+    let still = MovingPlatform::new(vec![Vec2::new(3.0, 4.0)], false, 40.0);
+    assert_eq!(still.position_at(12.0), Vec2::new(3.0, 4.0));
+}
+
+#[test]
+fn riders_are_carried_up_to_walls() {
+    // a wall along the column from x = 96 to 112
+    let wall: Vec<(i32, i32)> = (0..10).map(|y| (6, y)).collect();
+    let mut app = platforms_app(&wall);
+    let platform = spawn_platform(&mut app, Vec2::new(64.0, 0.0), false);
+    assert_eq!(
+        app.world.get::<KinematicCollider>(platform),
+        Some(&KinematicCollider::new(Vec2::new(16.0, 4.0)))
+    );
+    let rider = spawn_rider(&mut app, platform);
+    let (platform_start, rider_start) = (position(&app, platform), position(&app, rider));
+
+    run_frames(&mut app, 20);
+    let carried = position(&app, platform) - platform_start;
+    assert!(carried.x > 5.0);
+    assert!((position(&app, rider) - rider_start - carried).length() < 1e-3);
+
+    // the platform goes on into the wall, the rider stops at it
+    run_frames(&mut app, 70);
+    assert!(position(&app, platform).x > 95.0);
+    let rider_at = position(&app, rider);
+    assert!(rider_at.x <= 92.0 && rider_at.x > 91.0, "{}", rider_at);
+    assert_eq!(rider_at.y, 28.0);
+}
+
+#[test]
+fn only_crushing_platforms_hurt_what_they_carry_into_ceilings() {
+    // a ceiling along the row from y = 48 to 64
+    let ceiling: Vec<(i32, i32)> = (0..10).map(|x| (x, 3)).collect();
+    for crushes in [false, true] {
+        let mut app = platforms_app(&ceiling);
+        let platform = spawn_platform(&mut app, Vec2::new(0.0, 40.0), crushes);
+        let rider = spawn_rider(&mut app, platform);
+
+        // riding up until the top of the rider meets the ceiling at 48
+        run_frames(&mut app, 80);
+        let rider_at = position(&app, rider);
+        assert!(rider_at.y <= 44.0 && rider_at.y > 43.0, "{}", rider_at);
+        assert!(position(&app, platform).y > 40.0);
+
+        let crushed = &app.world.resource::<Damaged>().0;
+        if crushes {
+            assert_eq!(crushed.len(), 1);
+            assert_eq!(crushed[0].target, rider);
+            assert_eq!(crushed[0].amount, 2);
+            assert_eq!(crushed[0].source, Some(platform));
+        } else {
+            assert!(crushed.is_empty());
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Tests for the player's movement and animations.
+
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{
+    Animation, AnimationController, AnimationResource, AnimationType, CameraTarget, MainCamera,
+};
+use gamedevjam2024::physics::PhysicsPlugin;
+use gamedevjam2024::player::{
+    spawn_player, Facing, Player, PlayerPlugin, IDLE, PLAYER_IDLE_ANIMATION, PLAYER_SPEED,
+    PLAYER_WALK_ANIMATION, WALK,
+};
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+
+/// Idle on frames 0 and 1, walking on 2 and 3
+fn animations() -> AnimationResource {
+    let mut animations = AnimationResource::new();
+    let clip = |frames| Animation::new(Handle::default(), frames, 0.1, AnimationType::Repeat);
+    animations.insert(PLAYER_IDLE_ANIMATION.to_string(), clip(vec![0, 1]));
+    animations.insert(PLAYER_WALK_ANIMATION.to_string(), clip(vec![2, 3]));
+    animations
+}
+
+/// The game with a player at the origin
+fn player_app() -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((TransformPlugin, PhysicsPlugin, PlayerPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 1);
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, &app.world);
+    let player = spawn_player(&mut commands, &animations(), Vec2::ZERO);
+    queue.apply(&mut app.world);
+    run_frames(&mut app, 1);
+    (app, player)
+}
+
+fn position(app: &App, player: Entity) -> Vec2 {
+    app.world
+        .get::<Transform>(player)
+        .unwrap()
+        .translation
+        .truncate()
+}
+
+fn hold(app: &mut App, keys: &[KeyCode]) {
+    let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    input.release_all();
+    input.clear();
+    for key in keys {
+        input.press(*key);
+    }
+}
+
+#[test]
+fn diagonals_are_as_fast_as_straight_lines() {
+    let (mut app, player) = player_app();
+    hold(&mut app, &[KeyCode::KeyD, KeyCode::KeyW]);
+    run_frames(&mut app, 2);
+    let from = position(&app, player);
+    // a fixed step a frame
+    run_frames(&mut app, 8);
+    let moved = position(&app, player) - from;
+    assert!((moved.length() - PLAYER_SPEED * 8.0 / 64.0).abs() < 1e-3);
+    assert!((moved.x - moved.y).abs() < 1e-3);
+
+    hold(&mut app, &[]);
+    run_frames(&mut app, 2);
+    let stopped = position(&app, player);
+    run_frames(&mut app, 4);
+    assert_eq!(position(&app, player), stopped);
+}
+
+#[test]
+fn walking_switches_clips_and_faces_the_way_moved() {
+    let (mut app, player) = player_app();
+    assert_eq!(
+        app.world
+            .get::<AnimationController>(player)
+            .unwrap()
+            .state(),
+        IDLE
+    );
+
+    hold(&mut app, &[KeyCode::KeyA]);
+    run_frames(&mut app, 3);
+    assert_eq!(
+        app.world
+            .get::<AnimationController>(player)
+            .unwrap()
+            .state(),
+        WALK
+    );
+    assert!([2, 3].contains(&app.world.get::<TextureAtlas>(player).unwrap().index));
+    assert_eq!(*app.world.get::<Facing>(player).unwrap(), Facing::Left);
+    assert!(app.world.get::<Sprite>(player).unwrap().flip_x);
+
+    // still facing left once stopped
+    hold(&mut app, &[]);
+    run_frames(&mut app, 3);
+    assert_eq!(
+        app.world
+            .get::<AnimationController>(player)
+            .unwrap()
+            .state(),
+        IDLE
+    );
+    assert!([0, 1].contains(&app.world.get::<TextureAtlas>(player).unwrap().index));
+    assert!(app.world.get::<Sprite>(player).unwrap().flip_x);
+}
+
+#[test]
+fn the_camera_follows_the_player_from_the_start() {
+    let (mut app, player) = player_app();
+    assert_eq!(app.world.resource::<CameraTarget>().0, Some(player));
+
+    app.world
+        .get_mut::<Transform>(player)
+        .unwrap()
+        .translation
+        .x = 100.0;
+    run_frames(&mut app, 16);
+    let camera = app
+        .world
+        .query_filtered::<&Transform, With<MainCamera>>()
+        .single(&app.world)
+        .translation;
+    assert!(camera.x > 50.0 && camera.x < 100.0);
+}
+
+#[test]
+fn a_player_comes_with_the_game_and_goes_with_it() {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, PhysicsPlugin, PlayerPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    let players = |app: &mut App| {
+        app.world
+            .query_filtered::<(), With<Player>>()
+            .iter(&app.world)
+            .count()
+    };
+    assert_eq!(players(&mut app), 0);
+
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    assert_eq!(players(&mut app), 1);
+    app.world.send_event(ChangeState(AppState::MainMenu));
+    run_frames(&mut app, 2);
+    assert_eq!(players(&mut app), 0);
+}
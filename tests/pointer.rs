@@ -0,0 +1,103 @@
+//! Tests for pointer lock, natively.
+
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy::window::CursorGrabMode;
+use gamedevjam2024::pointer::{
+    CursorWorldPosition, PointerDelta, PointerLock, PointerPlugin, ReleasePointerLock,
+    RequestPointerLock, NATIVE_GRAB_MODE,
+};
+use gamedevjam2024::testing::{headless_app, run_frames, spawn_window};
+
+fn pointer_app() -> (App, Entity) {
+    let mut app = headless_app();
+    app.add_plugins(PointerPlugin);
+    let window = spawn_window(&mut app, 1.0);
+    run_frames(&mut app, 1);
+    (app, window)
+}
+
+fn is_locked(app: &App) -> bool {
+    app.world.resource::<PointerLock>().is_locked()
+}
+
+fn move_mouse(app: &mut App, delta: Vec2) {
+    app.world.send_event(MouseMotion { delta });
+}
+
+#[test]
+fn requesting_grabs_and_hides_the_cursor() {
+    let (mut app, window) = pointer_app();
+    app.world.send_event(RequestPointerLock);
+    run_frames(&mut app, 2);
+    let cursor = &app.world.get::<Window>(window).unwrap().cursor;
+    assert_eq!(cursor.grab_mode, NATIVE_GRAB_MODE);
+    assert!(!cursor.visible);
+    assert!(is_locked(&app));
+
+    app.world.send_event(ReleasePointerLock);
+    run_frames(&mut app, 2);
+    let cursor = &app.world.get::<Window>(window).unwrap().cursor;
+    assert_eq!(cursor.grab_mode, CursorGrabMode::None);
+    assert!(cursor.visible);
+    assert!(!is_locked(&app));
+}
+
+#[test]
+fn releasing_wins_in_the_same_frame() {
+    let (mut app, window) = pointer_app();
+    app.world.send_event(RequestPointerLock);
+    app.world.send_event(ReleasePointerLock);
+    run_frames(&mut app, 2);
+    assert_eq!(
+        app.world.get::<Window>(window).unwrap().cursor.grab_mode,
+        CursorGrabMode::None
+    );
+    assert!(!is_locked(&app));
+}
+
+#[test]
+fn losing_the_grab_unlocks() {
+    let (mut app, window) = pointer_app();
+    app.world.send_event(RequestPointerLock);
+    run_frames(&mut app, 2);
+    // as if the platform took it away
+    app.world
+        .get_mut::<Window>(window)
+        .unwrap()
+        .cursor
+        .grab_mode = CursorGrabMode::None;
+    run_frames(&mut app, 1);
+    assert!(!is_locked(&app));
+}
+
+#[test]
+fn mouse_motion_is_a_delta_while_locked() {
+    let (mut app, _) = pointer_app();
+    move_mouse(&mut app, Vec2::new(3.0, 4.0));
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.resource::<PointerDelta>().0, Vec2::ZERO);
+
+    app.world.send_event(RequestPointerLock);
+    run_frames(&mut app, 2);
+    move_mouse(&mut app, Vec2::new(3.0, 4.0));
+    move_mouse(&mut app, Vec2::new(-1.0, 1.0));
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.resource::<PointerDelta>().0, Vec2::new(2.0, 5.0));
+
+    // no motion, no delta
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.resource::<PointerDelta>().0, Vec2::ZERO);
+}
+
+#[test]
+fn the_cursor_has_no_world_position_while_locked() {
+    let (mut app, window) = pointer_app();
+    app.world
+        .get_mut::<Window>(window)
+        .unwrap()
+        .set_cursor_position(Some(Vec2::new(10.0, 10.0)));
+    app.world.send_event(RequestPointerLock);
+    run_frames(&mut app, 2);
+    assert_eq!(app.world.resource::<CursorWorldPosition>().0, None);
+}
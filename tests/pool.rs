@@ -0,0 +1,143 @@
+#![cfg(feature = "test-harness")]
+
+use bevy::audio::{AudioSource, PlaybackMode};
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{FloatingText, SpawnFloatingText, FLOATING_TEXT_TIME};
+use gamedevjam2024::helpers::tiled::MapUnloaded;
+use gamedevjam2024::pool::{InPool, Pool, PoolStats};
+use gamedevjam2024::sound::{OneShotSfx, PlaySFX, SfxVoice};
+use gamedevjam2024::testing::{game_app, run_frames, stub_sound, FRAME};
+
+fn frames_to_float() -> u32 {
+    (FLOATING_TEXT_TIME / FRAME.as_secs_f32()).ceil() as u32 + 2
+}
+
+fn floating_texts(app: &mut App) -> Vec<Entity> {
+    app.world
+        .query_filtered::<Entity, With<FloatingText>>()
+        .iter(&app.world)
+        .collect()
+}
+
+#[test]
+fn finished_floating_text_is_reused() {
+    let mut app = game_app();
+    app.world
+        .send_event(SpawnFloatingText::new("12", Vec2::ZERO));
+    app.update();
+    let texts = floating_texts(&mut app);
+    assert_eq!(texts.len(), 1);
+    let text = texts[0];
+
+    run_frames(&mut app, frames_to_float());
+    assert!(app.world.get::<InPool>(text).is_some());
+    assert_eq!(app.world.get::<Visibility>(text), Some(&Visibility::Hidden));
+    assert_eq!(app.world.resource::<Pool<FloatingText>>().free(), 1);
+
+    app.world
+        .send_event(SpawnFloatingText::new("7", Vec2::new(1.0, 2.0)));
+    app.update();
+    assert_eq!(floating_texts(&mut app), vec![text]);
+    assert!(app.world.get::<InPool>(text).is_none());
+    assert_eq!(
+        app.world.get::<Visibility>(text),
+        Some(&Visibility::Inherited)
+    );
+    assert_eq!(app.world.get::<Text>(text).unwrap().sections[0].value, "7");
+    assert!(app.world.get::<FloatingText>(text).unwrap().elapsed < FLOATING_TEXT_TIME);
+
+    let stats = app.world.resource::<Pool<FloatingText>>().stats();
+    assert_eq!((stats.spawned, stats.reused, stats.released), (1, 1, 1));
+    assert_eq!(stats.reuse_rate(), Some(0.5));
+}
+
+#[test]
+fn a_full_pool_despawns() {
+    let mut app = game_app();
+    app.insert_resource(Pool::<FloatingText>::with_max_size(1));
+    for _ in 0..3 {
+        app.world
+            .send_event(SpawnFloatingText::new("1", Vec2::ZERO));
+    }
+    app.update();
+    assert_eq!(floating_texts(&mut app).len(), 3);
+
+    run_frames(&mut app, frames_to_float());
+    assert_eq!(floating_texts(&mut app).len(), 1);
+    let pool = app.world.resource::<Pool<FloatingText>>();
+    assert_eq!(pool.free(), 1);
+    assert_eq!(pool.stats().despawned, 2);
+}
+
+#[test]
+fn unloading_a_map_drains_the_pools() {
+    let mut app = game_app();
+    app.world
+        .send_event(SpawnFloatingText::new("1", Vec2::ZERO));
+    run_frames(&mut app, frames_to_float());
+    assert_eq!(app.world.resource::<Pool<FloatingText>>().free(), 1);
+
+    app.world.send_event(MapUnloaded {
+        path: "map.tmx".to_string(),
+    });
+    app.update();
+    assert_eq!(app.world.resource::<Pool<FloatingText>>().free(), 0);
+    assert!(floating_texts(&mut app).is_empty());
+}
+
+#[test]
+fn finished_one_shot_sfx_go_back_to_the_pool() {
+    let mut app = game_app();
+    stub_sound(&mut app, "hit");
+    app.world.send_event(PlaySFX::new("hit"));
+    app.update();
+    let (sound, mode) = app
+        .world
+        .query_filtered::<(Entity, &PlaybackSettings), With<OneShotSfx>>()
+        .single(&app.world);
+    assert!(matches!(mode.mode, PlaybackMode::Remove));
+
+    // what bevy does once the sound has played out
+    app.world
+        .entity_mut(sound)
+        .remove::<(Handle<AudioSource>, PlaybackSettings)>();
+    app.update();
+    assert!(app.world.get::<InPool>(sound).is_some());
+    assert!(app.world.get::<SfxVoice>(sound).is_none());
+
+    // past the rate limit
+    run_frames(&mut app, 4);
+    app.world.send_event(PlaySFX::new("hit"));
+    app.update();
+    assert!(app.world.get::<InPool>(sound).is_none());
+    assert!(app.world.get::<SfxVoice>(sound).is_some());
+    assert_eq!(app.world.resource::<Pool<OneShotSfx>>().stats().reused, 1);
+}
+
+#[test]
+fn looping_sfx_are_not_pooled() {
+    let mut app = game_app();
+    stub_sound(&mut app, "engine");
+    app.world.send_event(PlaySFX {
+        looping: true,
+        ..PlaySFX::new("engine")
+    });
+    app.update();
+    let playing = app
+        .world
+        .query_filtered::<&SfxVoice, With<OneShotSfx>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(playing, 0);
+}
+
+#[test]
+fn reuse_rate_needs_a_spawn() {
+    assert_eq!(PoolStats::default().reuse_rate(), None);
+    let stats = PoolStats {
+        spawned: 1,
+        reused: 3,
+        ..default()
+    };
+    assert_eq!(stats.reuse_rate(), Some(0.75));
+}
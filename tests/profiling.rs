@@ -0,0 +1,61 @@
+//! Tests for the system timings.
+
+use gamedevjam2024::profiling::{SystemTimings, TIMING_WINDOW};
+
+#[test]
+fn timings_average_over_the_frames_each_system_ran_in() {
+    let mut timings = SystemTimings::default();
+    timings.add_frame(vec![("follow_paths", 1.0), ("animate_tiles", 0.5)]);
+    // a system running twice in a frame adds up
+    timings.add_frame(vec![
+        ("follow_paths", 2.0),
+        ("follow_paths", 1.0),
+        ("spawn_map_batches", 8.0),
+    ]);
+
+    assert_eq!(timings.average("follow_paths"), Some(2.0));
+    assert_eq!(timings.max("follow_paths"), Some(3.0));
+    assert_eq!(timings.average("animate_tiles"), Some(0.5));
+    assert_eq!(timings.average("update_animations"), None);
+    assert_eq!(
+        timings.top(2),
+        vec![("spawn_map_batches", 8.0), ("follow_paths", 2.0)]
+    );
+
+    let table = timings.table();
+    let rows: Vec<&str> = table.lines().collect();
+    assert_eq!(rows.len(), 4);
+    assert!(rows[1].starts_with("spawn_map_batches"));
+    assert!(rows[3].starts_with("animate_tiles"));
+}
+
+#[test]
+fn old_frames_leave_the_window() {
+    let mut timings = SystemTimings::default();
+    timings.add_frame(vec![("follow_paths", 100.0)]);
+    for _ in 0..TIMING_WINDOW {
+        timings.add_frame(vec![("follow_paths", 1.0)]);
+    }
+    assert_eq!(timings.average("follow_paths"), Some(1.0));
+    assert_eq!(timings.max("follow_paths"), Some(1.0));
+}
+
+#[cfg(feature = "profiling")]
+#[test]
+fn spans_are_collected_each_frame() {
+    use bevy::prelude::*;
+    use gamedevjam2024::profiling::{span, ProfilingPlugin};
+    use gamedevjam2024::testing::{headless_app, run_frames};
+
+    fn slow_system() {
+        let _span = span("slow_system");
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+
+    let mut app = headless_app();
+    app.add_plugins(ProfilingPlugin)
+        .add_systems(Update, slow_system);
+    run_frames(&mut app, 3);
+    let timings = app.world.resource::<SystemTimings>();
+    assert!(timings.average("slow_system").unwrap() >= 2.0);
+}
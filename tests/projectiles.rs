@@ -0,0 +1,167 @@
+//! Tests for projectiles hitting walls and who they're shot at.
+
+use bevy::ecs::system::CommandQueue;
+use bevy::prelude::*;
+use gamedevjam2024::health::{Damage, Faction, Health, HealthPlugin};
+use gamedevjam2024::helpers::tiled::CollisionMap;
+use gamedevjam2024::physics::{AabbCollider, PhysicsPlugin, Velocity};
+use gamedevjam2024::pool::{InPool, Pool};
+use gamedevjam2024::projectiles::{
+    spawn_projectile, Projectile, ProjectileConfig, ProjectilesPlugin,
+};
+use gamedevjam2024::sound::PlaySFX;
+use gamedevjam2024::testing::{game_app, run_frames};
+
+/// Every Damage sent
+#[derive(Resource, Default)]
+struct Damages(Vec<Damage>);
+
+fn collect_damage(mut events: EventReader<Damage>, mut damages: ResMut<Damages>) {
+    damages.0.extend(events.read().copied());
+}
+
+/// The name of every PlaySFX sent
+#[derive(Resource, Default)]
+struct Sounds(Vec<String>);
+
+fn collect_sounds(mut events: EventReader<PlaySFX>, mut sounds: ResMut<Sounds>) {
+    sounds
+        .0
+        .extend(events.read().map(|sound| sound.name.clone()));
+}
+
+/// The game with a 10x10 map of 16 unit tiles from the origin, with a wall along x = 5
+fn projectiles_app() -> App {
+    let mut map = CollisionMap::default();
+    map.reset(UVec2::splat(10), Vec2::splat(16.0), Vec2::ZERO);
+    for y in 0..10 {
+        map.set_solid(5, y, true);
+    }
+    let mut app = game_app();
+    app.add_plugins((
+        TransformPlugin,
+        PhysicsPlugin,
+        HealthPlugin,
+        ProjectilesPlugin,
+    ))
+    .insert_resource(map)
+    .init_resource::<Damages>()
+    .init_resource::<Sounds>()
+    .add_systems(Update, (collect_damage, collect_sounds));
+    app
+}
+
+fn spawn_target(app: &mut App, x: f32, faction: Faction) -> Entity {
+    app.world
+        .spawn((
+            Health::new(3),
+            faction,
+            AabbCollider::new(Vec2::splat(4.0)),
+            TransformBundle::from_transform(Transform::from_xyz(x, 40.0, 0.0)),
+        ))
+        .id()
+}
+
+/// Shoots `config` to the right from (8, 40), pooled
+fn shoot(app: &mut App, owner: Option<Entity>, config: &ProjectileConfig) -> Entity {
+    app.world
+        .resource_scope(|world, mut pool: Mut<Pool<Projectile>>| {
+            let mut queue = CommandQueue::default();
+            let mut commands = Commands::new(&mut queue, world);
+            let projectile = spawn_projectile(
+                &mut commands,
+                Some(&mut pool),
+                owner,
+                Vec2::new(8.0, 40.0),
+                Vec2::X,
+                config,
+            );
+            queue.apply(world);
+            projectile
+        })
+}
+
+fn gone(app: &App, projectile: Entity) -> bool {
+    app.world
+        .get_entity(projectile)
+        .is_none_or(|entity| entity.contains::<InPool>())
+}
+
+#[test]
+fn projectiles_hurt_the_other_side_but_not_their_own() {
+    let mut app = projectiles_app();
+    let shooter = spawn_target(&mut app, 8.0, Faction::Player);
+    let friend = spawn_target(&mut app, 30.0, Faction::Player);
+    let enemy = spawn_target(&mut app, 50.0, Faction::Enemy);
+    run_frames(&mut app, 1);
+    let config = ProjectileConfig {
+        faction: Faction::Player,
+        impact_sound: Some("thud".to_string()),
+        ..default()
+    };
+    let projectile = shoot(&mut app, Some(shooter), &config);
+
+    run_frames(&mut app, 30);
+    assert!(gone(&app, projectile));
+    let damages = &app.world.resource::<Damages>().0;
+    assert_eq!(damages.len(), 1);
+    assert_eq!(damages[0].target, enemy);
+    assert_eq!(damages[0].source, Some(shooter));
+    assert_eq!(app.world.get::<Health>(enemy).unwrap().current, 2);
+    assert_eq!(app.world.get::<Health>(friend).unwrap().current, 3);
+    assert_eq!(app.world.get::<Health>(shooter).unwrap().current, 3);
+    let sounds = &app.world.resource::<Sounds>().0;
+    assert!(sounds.iter().any(|sound| sound == "thud"));
+}
+
+#[test]
+fn piercing_projectiles_go_through_and_bouncing_ones_come_back() {
+    let mut app = projectiles_app();
+    let first = spawn_target(&mut app, 30.0, Faction::Enemy);
+    let second = spawn_target(&mut app, 50.0, Faction::Enemy);
+    run_frames(&mut app, 1);
+    let config = ProjectileConfig {
+        faction: Faction::Player,
+        piercing: true,
+        max_bounces: 1,
+        ..default()
+    };
+    let projectile = shoot(&mut app, None, &config);
+
+    // through both to the wall, and back through them
+    run_frames(&mut app, 40);
+    assert!(!gone(&app, projectile));
+    assert!(app.world.get::<Velocity>(projectile).unwrap().0.x < 0.0);
+    assert_eq!(app.world.get::<Projectile>(projectile).unwrap().bounces, 0);
+    let targets: Vec<Entity> = app
+        .world
+        .resource::<Damages>()
+        .0
+        .iter()
+        .map(|damage| damage.target)
+        .collect();
+    assert_eq!(targets, vec![first, second]);
+}
+
+#[test]
+fn projectiles_run_out_and_are_reused() {
+    let mut app = projectiles_app();
+    let config = ProjectileConfig {
+        speed: 0.0,
+        lifetime: 0.25,
+        ..default()
+    };
+    let projectile = shoot(&mut app, None, &config);
+    run_frames(&mut app, 8);
+    assert!(!gone(&app, projectile));
+    run_frames(&mut app, 10);
+    assert!(gone(&app, projectile));
+    assert_eq!(app.world.resource::<Pool<Projectile>>().free(), 1);
+
+    assert_eq!(shoot(&mut app, None, &config), projectile);
+    assert!(!gone(&app, projectile));
+    assert_eq!(
+        app.world.get::<Projectile>(projectile).unwrap().lifetime,
+        0.25
+    );
+}
@@ -0,0 +1,119 @@
+//! Tests for the interaction prompt.
+
+use bevy::prelude::*;
+use gamedevjam2024::helpers::tiled::PlacedAtSpawn;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    DialogueLine, Interactable, Interacted, InteractionPrompt, PromptText, StartDialogue, UiPlugin,
+};
+
+/// The game running with the player at the origin
+fn prompt_app() -> (App, Entity) {
+    let mut app = game_app();
+    app.add_plugins((TransformPlugin, AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    let player = app
+        .world
+        .spawn((PlacedAtSpawn, TransformBundle::default()))
+        .id();
+    run_frames(&mut app, 2);
+    (app, player)
+}
+
+fn spawn_interactable(app: &mut App, x: f32, prompt_key: &str, radius: f32) -> Entity {
+    app.world
+        .spawn((
+            Interactable {
+                prompt_key: prompt_key.to_string(),
+                radius,
+            },
+            TransformBundle::from_transform(Transform::from_xyz(x, 0.0, 0.0)),
+        ))
+        .id()
+}
+
+fn move_player(app: &mut App, player: Entity, x: f32) {
+    app.world
+        .get_mut::<Transform>(player)
+        .unwrap()
+        .translation
+        .x = x;
+    run_frames(app, 2);
+}
+
+fn prompted(app: &App) -> Option<Entity> {
+    app.world.resource::<InteractionPrompt>().0
+}
+
+fn prompt_text(app: &mut App) -> Option<String> {
+    let (text, visibility) = app
+        .world
+        .query_filtered::<(&Text, &Visibility), With<PromptText>>()
+        .single(&app.world);
+    (*visibility != Visibility::Hidden).then(|| text.sections[0].value.clone())
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+#[test]
+fn interact_in_reach_of_the_prompt() {
+    let (mut app, player) = prompt_app();
+    let sign = spawn_interactable(&mut app, 20.0, "prompt.read", 32.0);
+    run_frames(&mut app, 2);
+    assert_eq!(prompted(&app), Some(sign));
+    assert_eq!(prompt_text(&mut app).as_deref(), Some("[E] Read"));
+
+    tap(&mut app, KeyCode::KeyE);
+    let interacted: Vec<Interacted> = app
+        .world
+        .resource_mut::<Events<Interacted>>()
+        .drain()
+        .collect();
+    assert_eq!(interacted, vec![Interacted { entity: sign }]);
+
+    move_player(&mut app, player, -20.0);
+    assert_eq!(prompted(&app), None);
+    assert_eq!(prompt_text(&mut app), None);
+}
+
+#[test]
+fn the_nearest_wins_only_by_a_margin() {
+    let (mut app, player) = prompt_app();
+    let sign = spawn_interactable(&mut app, 20.0, "prompt.read", 64.0);
+    let door = spawn_interactable(&mut app, 40.0, "prompt.open", 64.0);
+    run_frames(&mut app, 2);
+    assert_eq!(prompted(&app), Some(sign));
+
+    // a little nearer the door, but not enough to take the prompt over
+    move_player(&mut app, player, 31.0);
+    assert_eq!(prompted(&app), Some(sign));
+    move_player(&mut app, player, 36.0);
+    assert_eq!(prompted(&app), Some(door));
+    assert_eq!(prompt_text(&mut app).as_deref(), Some("[E] Open"));
+}
+
+#[test]
+fn no_prompt_during_dialogue() {
+    let (mut app, _) = prompt_app();
+    spawn_interactable(&mut app, 0.0, "prompt.talk", 32.0);
+    run_frames(&mut app, 2);
+    assert!(prompt_text(&mut app).is_some());
+
+    app.world.send_event(StartDialogue {
+        lines: vec![DialogueLine::new("menu.title", "menu.play")],
+    });
+    run_frames(&mut app, 2);
+    assert_eq!(prompted(&app), None);
+    assert_eq!(prompt_text(&mut app), None);
+}
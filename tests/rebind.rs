@@ -0,0 +1,124 @@
+//! Tests for rebinding actions from the settings menu.
+
+use bevy::prelude::*;
+use gamedevjam2024::input::{
+    Action, ActionState, Binding, BindingsChanged, InputMap, RebindConflict, RebindRejected,
+    Rebinding, ResolveRebind, StartRebind,
+};
+use gamedevjam2024::settings::Settings;
+use gamedevjam2024::testing::{game_app, run_frames};
+
+fn rebind_app() -> App {
+    let mut app = game_app();
+    app.init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 1);
+    app
+}
+
+/// Starts rebinding `slot` of `action`, then presses `key`
+fn rebind(app: &mut App, action: Action, slot: usize, key: KeyCode) {
+    app.world.send_event(StartRebind { action, slot });
+    run_frames(app, 1);
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+#[test]
+fn the_next_key_pressed_is_bound_and_saved_with_the_settings() {
+    let mut app = rebind_app();
+    drain::<BindingsChanged>(&mut app);
+    rebind(&mut app, Action::MoveUp, 0, KeyCode::KeyZ);
+
+    let up = [KeyCode::KeyZ, KeyCode::ArrowUp];
+    assert_eq!(app.world.resource::<InputMap>().keys(Action::MoveUp), &up);
+    assert_eq!(
+        app.world
+            .resource::<Settings>()
+            .bindings
+            .keys(Action::MoveUp),
+        &up
+    );
+    assert_eq!(*app.world.resource::<Rebinding>(), Rebinding::Idle);
+    assert_eq!(drain::<BindingsChanged>(&mut app).len(), 1);
+    // the press went to the rebind
+    assert!(!app.world.resource::<ActionState>().pressed(Action::MoveUp));
+
+    rebind(&mut app, Action::MoveUp, 5, KeyCode::KeyK);
+    assert_eq!(
+        app.world.resource::<InputMap>().keys(Action::MoveUp),
+        &[KeyCode::KeyZ, KeyCode::ArrowUp, KeyCode::KeyK]
+    );
+}
+
+#[test]
+fn reserved_keys_end_the_rebind_unbound() {
+    let mut app = rebind_app();
+    rebind(&mut app, Action::Interact, 0, KeyCode::Escape);
+
+    assert_eq!(
+        app.world.resource::<InputMap>().keys(Action::Interact),
+        InputMap::default().keys(Action::Interact)
+    );
+    assert_eq!(*app.world.resource::<Rebinding>(), Rebinding::Idle);
+    assert_eq!(
+        drain::<RebindRejected>(&mut app),
+        vec![RebindRejected {
+            action: Action::Interact,
+            key: KeyCode::Escape,
+        }]
+    );
+}
+
+#[test]
+fn conflicts_wait_for_a_choice_and_can_swap() {
+    let mut app = rebind_app();
+    rebind(&mut app, Action::MoveUp, 0, KeyCode::KeyS);
+
+    let conflicts = drain::<RebindConflict>(&mut app);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].others, vec![Action::MoveDown]);
+    assert!(app.world.resource::<Rebinding>().is_active());
+    assert_eq!(
+        app.world.resource::<InputMap>().keys(Action::MoveUp)[0],
+        KeyCode::KeyW
+    );
+
+    app.world.send_event(ResolveRebind::Swap);
+    run_frames(&mut app, 1);
+    let map = app.world.resource::<InputMap>();
+    assert_eq!(map.keys(Action::MoveUp), &[KeyCode::KeyS, KeyCode::ArrowUp]);
+    assert_eq!(
+        map.keys(Action::MoveDown),
+        &[KeyCode::KeyW, KeyCode::ArrowDown]
+    );
+}
+
+#[test]
+fn cancelled_conflicts_leave_the_bindings() {
+    let mut app = rebind_app();
+    rebind(&mut app, Action::Attack, 0, KeyCode::KeyE);
+    app.world.send_event(ResolveRebind::Cancel);
+    run_frames(&mut app, 1);
+
+    assert_eq!(*app.world.resource::<InputMap>(), InputMap::default());
+    assert_eq!(*app.world.resource::<Rebinding>(), Rebinding::Idle);
+}
+
+#[test]
+fn bindings_reset_and_show_as_labels() {
+    let mut map = InputMap::default();
+    map.set_binding(Action::Interact, 0, Binding::Key(KeyCode::KeyF), false);
+    map.dead_zone = 0.4;
+    let labels = map.labels();
+    assert_eq!(labels[4].0, Action::Interact);
+    assert_eq!(labels[4].1, vec!["F", "Space", "Ⓐ"]);
+    assert_eq!(labels[0].1, vec!["W", "↑", "✚↑"]);
+
+    map.reset_bindings();
+    assert_eq!(map.keys(Action::Interact), &[KeyCode::KeyE, KeyCode::Space]);
+    assert_eq!(map.dead_zone, 0.4);
+}
@@ -0,0 +1,206 @@
+//! Tests for recording input and replaying it.
+
+use bevy::prelude::*;
+use gamedevjam2024::input::{Action, FixedActionState};
+use gamedevjam2024::options::StartOptions;
+use gamedevjam2024::replay::{
+    state_hash, Replay, ReplayData, ReplayDiverged, ReplayError, ReplayFinished, ReplayTick,
+};
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::timestep::FixedSet;
+
+/// The actions held on each tick, as gameplay saw them
+#[derive(Resource, Default)]
+struct Seen(Vec<(u64, Vec<Action>)>);
+
+fn note_actions(tick: Res<ReplayTick>, actions: Res<FixedActionState>, mut seen: ResMut<Seen>) {
+    seen.0
+        .push((tick.0, actions.held().iter().copied().collect()));
+}
+
+fn score_on_interact(actions: Res<FixedActionState>, mut progress: ResMut<GameProgress>) {
+    if actions.just_pressed(Action::Interact) {
+        progress.score += 1;
+    }
+}
+
+fn replay_app(replay: Replay) -> App {
+    let mut app = game_app();
+    app.init_resource::<ButtonInput<KeyCode>>()
+        .init_resource::<GameProgress>()
+        .init_resource::<Seen>()
+        .insert_resource(replay)
+        .add_systems(
+            FixedUpdate,
+            (score_on_interact, note_actions).in_set(FixedSet::Gameplay),
+        );
+    // the clock doesn't move on the first frame, so each frame after runs one tick
+    run_frames(&mut app, 1);
+    app
+}
+
+fn keys(app: &mut App) -> Mut<'_, ButtonInput<KeyCode>> {
+    app.world.resource_mut::<ButtonInput<KeyCode>>()
+}
+
+fn record_session() -> (ReplayData, Vec<(u64, Vec<Action>)>) {
+    let options = StartOptions {
+        seed: Some(7),
+        record: true,
+        check_every: Some(1),
+        ..StartOptions::default()
+    };
+    let mut app = replay_app(Replay::Recording(ReplayData::new(&options)));
+    run_frames(&mut app, 3);
+    keys(&mut app).press(KeyCode::KeyD);
+    run_frames(&mut app, 5);
+    keys(&mut app).press(KeyCode::KeyE);
+    run_frames(&mut app, 2);
+    // without bevy's InputPlugin to clear them, the presses would read as taps every frame
+    keys(&mut app).clear();
+    keys(&mut app).release(KeyCode::KeyE);
+    keys(&mut app).release(KeyCode::KeyD);
+    run_frames(&mut app, 4);
+
+    assert_eq!(app.world.resource::<GameProgress>().score, 1);
+    let data = app.world.resource::<Replay>().recording().unwrap().clone();
+    let seen = std::mem::take(&mut app.world.resource_mut::<Seen>().0);
+    (data, seen)
+}
+
+#[test]
+fn recordings_hold_the_changes_to_the_actions() {
+    let (data, seen) = record_session();
+
+    assert_eq!(data.seed, 7);
+    assert_eq!(data.ticks, seen.len() as u64);
+    let changes: Vec<(Action, bool)> = data
+        .actions
+        .iter()
+        .map(|change| (change.action, change.pressed))
+        .collect();
+    assert_eq!(
+        changes,
+        vec![
+            (Action::MoveRight, true),
+            (Action::Interact, true),
+            (Action::MoveRight, false),
+            (Action::Interact, false),
+        ]
+    );
+    assert_eq!(data.checks.len() as u64, data.ticks);
+}
+
+#[test]
+fn replays_feed_the_recorded_actions_whatever_keys_are_held() {
+    let (data, recorded) = record_session();
+    let mut app = replay_app(Replay::play(data.clone()));
+    keys(&mut app).press(KeyCode::KeyS);
+    run_frames(&mut app, data.ticks as u32);
+
+    assert_eq!(app.world.resource::<Seen>().0, recorded);
+    assert_eq!(app.world.resource::<GameProgress>().score, 1);
+    assert!(app.world.resource::<Events<ReplayDiverged>>().is_empty());
+
+    run_frames(&mut app, 1);
+    assert_eq!(*app.world.resource::<Replay>(), Replay::Finished);
+    let finished: Vec<ReplayFinished> = app
+        .world
+        .resource_mut::<Events<ReplayFinished>>()
+        .drain()
+        .collect();
+    assert_eq!(finished, vec![ReplayFinished { ticks: data.ticks }]);
+    // the keys work again
+    assert!(app
+        .world
+        .resource::<FixedActionState>()
+        .pressed(Action::MoveDown));
+}
+
+fn cheat_on_tick_3(tick: Res<ReplayTick>, mut progress: ResMut<GameProgress>) {
+    if tick.0 == 3 {
+        progress.score += 100;
+    }
+}
+
+#[test]
+fn replays_stop_where_the_state_diverges() {
+    let (data, _) = record_session();
+    let mut app = replay_app(Replay::play(data));
+    app.add_systems(FixedUpdate, cheat_on_tick_3.in_set(FixedSet::Gameplay));
+    run_frames(&mut app, 4);
+
+    assert_eq!(
+        *app.world.resource::<Replay>(),
+        Replay::Diverged { tick: 3 }
+    );
+    let diverged: Vec<u64> = app
+        .world
+        .resource_mut::<Events<ReplayDiverged>>()
+        .drain()
+        .map(|event| event.tick)
+        .collect();
+    assert_eq!(diverged, vec![3]);
+}
+
+#[test]
+fn recordings_round_trip_and_refuse_other_versions() {
+    let (data, _) = record_session();
+    let text = data.to_ron().unwrap();
+    assert_eq!(ReplayData::from_ron(&text).unwrap(), data);
+
+    let future = ReplayData {
+        version: 99,
+        ..data
+    };
+    let error = ReplayData::from_ron(&future.to_ron().unwrap()).unwrap_err();
+    assert!(matches!(error, ReplayError::Version { found: 99, .. }));
+}
+
+#[test]
+fn replays_take_the_recorded_options_that_decide_the_game() {
+    let recorded = StartOptions {
+        seed: Some(42),
+        map: "level2.tmx".to_string(),
+        record: true,
+        ..StartOptions::default()
+    };
+    let data = ReplayData::new(&recorded);
+    let mut options = StartOptions {
+        canvas: Some("#game".to_string()),
+        replay: true,
+        ..StartOptions::default()
+    };
+    data.apply_to(&mut options);
+
+    assert_eq!(options.seed, Some(42));
+    assert_eq!(options.map, "level2.tmx");
+    assert_eq!(options.fixed_hz, Some(64.0));
+    assert_eq!(options.canvas.as_deref(), Some("#game"));
+    assert!(!options.record);
+}
+
+#[test]
+fn state_hashes_ignore_play_time_and_spawn_order() {
+    let progress = GameProgress {
+        score: 10,
+        ..GameProgress::default()
+    };
+    let positions = [Vec3::new(1.0, 2.0, 0.0), Vec3::new(-5.0, 0.5, 1.0)];
+    let hash = state_hash(Some(&progress), positions.iter().copied());
+
+    let later = GameProgress {
+        play_time: 12.5,
+        ..progress.clone()
+    };
+    assert_eq!(
+        state_hash(Some(&later), positions.iter().rev().copied()),
+        hash
+    );
+    let scored = GameProgress {
+        score: 11,
+        ..progress
+    };
+    assert_ne!(state_hash(Some(&scored), positions.iter().copied()), hash);
+}
@@ -0,0 +1,34 @@
+//! Tests for GameRng.
+
+use gamedevjam2024::rng::GameRng;
+use gamedevjam2024::testing::{game_app, TEST_SEED};
+use rand::Rng;
+
+fn draws(rng: &mut GameRng) -> Vec<u32> {
+    (0..8).map(|_| rng.gen()).collect()
+}
+
+#[test]
+fn the_same_seed_gives_the_same_numbers() {
+    assert_eq!(draws(&mut GameRng::new(7)), draws(&mut GameRng::new(7)));
+    assert_ne!(draws(&mut GameRng::new(7)), draws(&mut GameRng::new(8)));
+}
+
+#[test]
+fn forks_depend_only_on_the_seed_and_their_name() {
+    let mut root = GameRng::new(7);
+    let before = draws(&mut root.fork("sfx"));
+    // drawing from the root or another stream doesn't shift the sfx stream
+    draws(&mut root);
+    draws(&mut root.fork("loot"));
+    assert_eq!(draws(&mut root.fork("sfx")), before);
+
+    assert_ne!(draws(&mut root.fork("loot")), before);
+    assert_eq!(root.fork("sfx").seed(), GameRng::new(7).fork("sfx").seed());
+}
+
+#[test]
+fn the_game_app_is_seeded_for_repeatable_runs() {
+    let app = game_app();
+    assert_eq!(app.world.resource::<GameRng>().seed(), TEST_SEED);
+}
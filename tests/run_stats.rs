@@ -0,0 +1,169 @@
+//! Tests for the stats of a run, the bests kept from them and the results screen.
+
+use bevy::prelude::*;
+use gamedevjam2024::bridge::LevelComplete;
+use gamedevjam2024::enemies::{Enemy, EnemyArchetype};
+use gamedevjam2024::health::Died;
+use gamedevjam2024::helpers::tiled::{LoadMap, MapProperties, RestartMap};
+use gamedevjam2024::pickups::{PickupCollected, PickupKind};
+use gamedevjam2024::run_stats::{
+    format_time, rank, BestResults, MapBest, NewRecord, Rank, Record, RunStats, RunStatsPlugin,
+    FANFARE_STINGER, KILL_SCORE,
+};
+use gamedevjam2024::save::GameProgress;
+use gamedevjam2024::sound::PlayStinger;
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::toast::ShowToast;
+use gamedevjam2024::ui::{ResultsRoot, UiPlugin};
+
+const MAP: &str = "level1.tmx";
+
+/// The game on MAP, keeping bests in memory only
+fn run_stats_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((RunStatsPlugin { persist: false }, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    app.world.send_event(LoadMap::new(MAP));
+    run_frames(&mut app, 1);
+    app.world.resource_mut::<RunStats>().reset(MAP);
+    app
+}
+
+fn stats(app: &App) -> RunStats {
+    app.world.resource::<RunStats>().clone()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+fn collect_coins(app: &mut App, amount: i32) {
+    app.world.send_event(PickupCollected {
+        kind: PickupKind::Coin,
+        amount,
+    });
+    run_frames(app, 1);
+}
+
+fn finish(app: &mut App) {
+    app.world.send_event(LevelComplete);
+    run_frames(app, 1);
+}
+
+#[test]
+fn runs_count_pickups_kills_and_time_until_finished() {
+    let mut app = run_stats_app();
+    collect_coins(&mut app, 5);
+    app.world.send_event(PickupCollected {
+        kind: PickupKind::Heart,
+        amount: 1,
+    });
+    let enemy = app
+        .world
+        .spawn(Enemy {
+            kind: "slime".to_string(),
+            archetype: EnemyArchetype::default(),
+            object: 1,
+            once: false,
+            chasing: false,
+        })
+        .id();
+    app.world.send_event(Died {
+        entity: enemy,
+        position: Vec2::ZERO,
+    });
+    run_frames(&mut app, 64);
+
+    let run = stats(&app);
+    assert_eq!(run.score, 5 + KILL_SCORE);
+    assert_eq!(run.collected, 2);
+    assert!((run.time - 1.0).abs() < 0.1, "{}", run.time);
+    assert_eq!(app.world.resource::<GameProgress>().score, KILL_SCORE);
+
+    // frozen once the level is complete
+    finish(&mut app);
+    let finished = stats(&app);
+    assert!(finished.finished);
+    collect_coins(&mut app, 5);
+    run_frames(&mut app, 10);
+    assert_eq!(stats(&app).score, finished.score);
+    assert_eq!(stats(&app).time, finished.time);
+    assert_eq!(
+        app.world.resource::<BestResults>().get(MAP),
+        MapBest {
+            time: Some(finished.time),
+            score: Some(finished.score),
+        }
+    );
+    assert_eq!(
+        app.world
+            .query_filtered::<(), With<ResultsRoot>>()
+            .iter(&app.world)
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn beating_a_best_is_a_new_record_and_restarting_keeps_the_bests() {
+    let mut app = run_stats_app();
+    collect_coins(&mut app, 3);
+    run_frames(&mut app, 32);
+    finish(&mut app);
+    // the first run sets the bests without records
+    assert!(drain::<NewRecord>(&mut app).is_empty());
+    let first = app.world.resource::<BestResults>().get(MAP);
+
+    app.world.send_event(RestartMap);
+    run_frames(&mut app, 1);
+    let restarted = stats(&app);
+    assert_eq!((restarted.score, restarted.collected), (0, 0));
+    assert!(!restarted.finished);
+    assert_eq!(restarted.map, MAP);
+    assert_eq!(app.world.resource::<BestResults>().get(MAP), first);
+    drain::<PlayStinger>(&mut app);
+    drain::<ShowToast>(&mut app);
+
+    // faster, but not more coins
+    collect_coins(&mut app, 2);
+    finish(&mut app);
+    let records: Vec<Record> = drain::<NewRecord>(&mut app)
+        .iter()
+        .map(|event| event.record)
+        .collect();
+    let time = stats(&app).time;
+    assert_eq!(records, vec![Record::Time(time)]);
+    assert_eq!(
+        app.world.resource::<BestResults>().get(MAP),
+        MapBest {
+            time: Some(time),
+            score: first.score,
+        }
+    );
+    run_frames(&mut app, 1);
+    assert!(drain::<PlayStinger>(&mut app)
+        .iter()
+        .any(|stinger| stinger.name == FANFARE_STINGER));
+    let text = format_time(time);
+    assert!(drain::<ShowToast>(&mut app)
+        .iter()
+        .any(|toast| toast.text.contains(&text)));
+}
+
+#[test]
+fn ranks_come_from_map_properties() {
+    let mut properties = MapProperties::default();
+    assert_eq!(rank(&properties, 100), None);
+    for (name, least) in [("rank_s", 100), ("rank_a", 50), ("rank_b", 20)] {
+        properties
+            .0
+            .insert(name.to_string(), tiled::PropertyValue::IntValue(least));
+    }
+    assert_eq!(rank(&properties, 120), Some(Rank::S));
+    assert_eq!(rank(&properties, 50), Some(Rank::A));
+    assert_eq!(rank(&properties, 21), Some(Rank::B));
+    assert_eq!(rank(&properties, 3), Some(Rank::C));
+
+    assert_eq!(format_time(0.0), "0:00.0");
+    assert_eq!(format_time(65.37), "1:05.3");
+}
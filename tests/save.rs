@@ -0,0 +1,197 @@
+//! Tests for saving and restoring games.
+
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::*;
+use gamedevjam2024::helpers::tiled::{
+    PlacedAtSpawn, SetTile, TileEditLog, TiledMap, TiledMapBundle, TiledMapPlugin, TmxExtras,
+};
+use gamedevjam2024::save::{
+    GameLoaded, GameProgress, PendingLoad, SaveData, SaveError, SavePlugin, SaveSlot,
+    SavedTileEdit, SavesRead, SAVE_VERSION,
+};
+use std::io::Cursor;
+use std::path::Path;
+
+const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" renderorder="right-down" width="3" height="2" tilewidth="16" tileheight="16" infinite="0" nextlayerid="2" nextobjectid="1">
+ <tileset firstgid="1" name="terrain" tilewidth="16" tileheight="16" tilecount="4" columns="2">
+  <image source="terrain.png" width="32" height="32"/>
+ </tileset>
+ <layer id="1" name="ground" width="3" height="2">
+  <data encoding="csv">
+1,1,1,
+1,1,1
+</data>
+ </layer>
+</map>
+"#;
+
+fn parse_map(tmx: &'static str) -> tiled::Map {
+    struct MemoryReader(&'static str);
+
+    impl tiled::ResourceReader for MemoryReader {
+        type Resource = Cursor<&'static [u8]>;
+        type Error = std::io::Error;
+
+        fn read_from(&mut self, path: &Path) -> Result<Self::Resource, Self::Error> {
+            if path == Path::new("test.tmx") {
+                return Ok(Cursor::new(self.0.as_bytes()));
+            }
+            Err(std::io::ErrorKind::NotFound.into())
+        }
+    }
+
+    tiled::Loader::with_cache_and_reader(tiled::DefaultResourceCache::new(), MemoryReader(tmx))
+        .load_tmx_map("test.tmx")
+        .expect("test map should parse")
+}
+
+fn save_data() -> SaveData {
+    let mut progress = GameProgress {
+        health: Some(3),
+        inventory: vec!["key".to_string()],
+        score: 120,
+        play_time: 65.5,
+        ..GameProgress::default()
+    };
+    progress.set_flag("met_the_mayor");
+    SaveData {
+        version: SAVE_VERSION,
+        // maps added to the assets directly have no path
+        map: String::new(),
+        player: Some(Vec2::new(40.0, 20.0)),
+        progress,
+        tile_edits: vec![SavedTileEdit::from(&SetTile::remove(
+            "ground",
+            TilePos { x: 1, y: 0 },
+        ))],
+        saved_at: 1_700_000_000.0,
+    }
+}
+
+#[test]
+fn saves_read_back_as_written() {
+    let save = save_data();
+    let text = save.to_ron().unwrap();
+    assert_eq!(SaveData::from_ron(&text).unwrap(), save);
+}
+
+#[test]
+fn saves_of_other_versions_are_rejected() {
+    let mut save = save_data();
+    save.version = SAVE_VERSION + 1;
+    let text = save.to_ron().unwrap();
+    assert!(matches!(
+        SaveData::from_ron(&text),
+        Err(SaveError::Version { found, expected })
+            if found == SAVE_VERSION + 1 && expected == SAVE_VERSION
+    ));
+
+    // from before saves had a version
+    assert!(matches!(
+        SaveData::from_ron("(map: \"map.tmx\")"),
+        Err(SaveError::Version { found: 0, .. })
+    ));
+    assert!(matches!(
+        SaveData::from_ron("not a save"),
+        Err(SaveError::Parse(_))
+    ));
+}
+
+#[test]
+fn loaded_games_put_the_player_and_tiles_back() {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        TiledMapPlugin,
+        SavePlugin,
+    ));
+    let player = app
+        .world
+        .spawn((PlacedAtSpawn, Transform::from_xyz(500.0, 500.0, 3.0)))
+        .id();
+    app.insert_resource(PendingLoad(save_data()));
+
+    let map = TiledMap {
+        map: parse_map(MAP),
+        tilemap_textures: vec![(0, TilemapTexture::Single(Handle::default()))]
+            .into_iter()
+            .collect(),
+        tile_image_offsets: Default::default(),
+        image_layers: Default::default(),
+        tmx: TmxExtras::read(MAP),
+    };
+    let handle = app.world.resource_mut::<Assets<TiledMap>>().add(map);
+    app.world.spawn(TiledMapBundle {
+        tiled_map: handle,
+        ..Default::default()
+    });
+    app.update();
+    app.update();
+
+    assert_eq!(
+        app.world.get::<Transform>(player).unwrap().translation,
+        Vec3::new(40.0, 20.0, 3.0)
+    );
+    let storage = app
+        .world
+        .query::<&TileStorage>()
+        .iter(&app.world)
+        .next()
+        .unwrap();
+    assert!(storage.get(&TilePos { x: 1, y: 0 }).is_none());
+    assert!(storage.get(&TilePos { x: 0, y: 0 }).is_some());
+    // replayed edits count as edits of the map, so saving again keeps them
+    assert_eq!(app.world.resource::<TileEditLog>().edits().len(), 1);
+    assert!(app.world.get_resource::<PendingLoad>().is_none());
+    assert_eq!(app.world.resource::<Events<GameLoaded>>().len(), 1);
+}
+
+#[test]
+fn the_newest_readable_save_is_loaded() {
+    let older = save_data();
+    let newer = SaveData {
+        saved_at: older.saved_at + 60.0,
+        ..save_data()
+    };
+
+    let read = SavesRead::from_slots([
+        (SaveSlot::Manual, Ok(older.clone())),
+        (SaveSlot::Autosave, Ok(newer.clone())),
+    ]);
+    assert_eq!(read.newest, Some((SaveSlot::Autosave, newer)));
+    assert!(read.unreadable.is_empty());
+
+    let read = SavesRead::from_slots([
+        (SaveSlot::Manual, Ok(older.clone())),
+        (
+            SaveSlot::Autosave,
+            SaveData::from_ron("(version: 1, map: \"cut"),
+        ),
+    ]);
+    assert_eq!(read.newest, Some((SaveSlot::Manual, older)));
+    assert_eq!(read.unreadable.len(), 1);
+    assert_eq!(read.unreadable[0].0, SaveSlot::Autosave);
+}
+
+#[test]
+fn empty_slots_are_not_unreadable() {
+    let read = SavesRead::from_slots([
+        (SaveSlot::Manual, Err(SaveError::NoSave)),
+        (SaveSlot::Autosave, Err(SaveError::NoSave)),
+    ]);
+    assert!(read.newest.is_none());
+    assert!(read.unreadable.is_empty());
+}
+
+#[test]
+fn saves_from_before_the_time_was_kept_read_as_oldest() {
+    let mut save = save_data();
+    let text = save.to_ron().unwrap();
+    // saved_at is the last field
+    let text = format!("{})", &text[..text.find(",saved_at:").unwrap()]);
+
+    save.saved_at = 0.0;
+    assert_eq!(SaveData::from_ron(&text).unwrap(), save);
+}
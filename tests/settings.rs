@@ -0,0 +1,90 @@
+//! Tests for the Settings blob and the modules following it.
+
+use bevy::prelude::*;
+use gamedevjam2024::gfx::{ColorFilter, GraphicsSettings, Quality};
+use gamedevjam2024::input::{Action, InputMap};
+use gamedevjam2024::settings::{parse_settings, Settings};
+use gamedevjam2024::sound::{AudioChannel, AudioChannels, SetVolume};
+use gamedevjam2024::testing::{game_app, run_frames};
+
+#[test]
+fn changed_settings_reach_every_module() {
+    let mut app = game_app();
+    run_frames(&mut app, 1);
+
+    {
+        let mut settings = app.world.resource_mut::<Settings>();
+        settings.audio.music = 0.25;
+        settings.graphics.quality = Quality::Low;
+        settings
+            .bindings
+            .bind(Action::ToggleMute, vec![KeyCode::KeyN]);
+    }
+    run_frames(&mut app, 1);
+
+    assert_eq!(app.world.resource::<AudioChannels>().music, 0.25);
+    assert_eq!(
+        app.world.resource::<GraphicsSettings>().quality,
+        Quality::Low
+    );
+    assert_eq!(
+        app.world.resource::<InputMap>().keys(Action::ToggleMute),
+        &[KeyCode::KeyN]
+    );
+}
+
+#[test]
+fn volume_events_are_kept_in_the_settings() {
+    let mut app = game_app();
+    run_frames(&mut app, 1);
+
+    app.world.send_event(SetVolume {
+        channel: AudioChannel::Sfx,
+        volume: 0.5,
+    });
+    run_frames(&mut app, 2);
+
+    assert_eq!(app.world.resource::<Settings>().audio.sfx, 0.5);
+    assert_eq!(app.world.resource::<AudioChannels>().sfx, 0.5);
+}
+
+#[test]
+fn reset_to_defaults_undoes_every_change() {
+    let mut app = game_app();
+    run_frames(&mut app, 1);
+    {
+        let mut settings = app.world.resource_mut::<Settings>();
+        settings.audio.muted = true;
+        settings.graphics.color_filter = ColorFilter::Tritanopia;
+    }
+    run_frames(&mut app, 1);
+
+    app.world.resource_mut::<Settings>().reset_to_defaults();
+    run_frames(&mut app, 1);
+
+    assert_eq!(*app.world.resource::<Settings>(), Settings::default());
+    assert!(!app.world.resource::<AudioChannels>().muted);
+    assert_eq!(
+        *app.world.resource::<GraphicsSettings>(),
+        GraphicsSettings::default()
+    );
+}
+
+#[test]
+fn unreadable_settings_fall_back_to_the_defaults() {
+    assert_eq!(parse_settings("(audio: (music: "), Settings::default());
+    assert_eq!(parse_settings("not settings at all"), Settings::default());
+}
+
+#[test]
+fn missing_fields_keep_their_defaults() {
+    let settings = parse_settings("(audio: (music: 3.0, muted: true))");
+    assert_eq!(settings.audio.music, 1.0);
+    assert!(settings.audio.muted);
+    assert_eq!(settings.audio.sfx, 1.0);
+    assert_eq!(settings.graphics, GraphicsSettings::default());
+    assert_eq!(settings.bindings, InputMap::default());
+
+    let saved = ron::to_string(&settings).unwrap();
+    assert_eq!(parse_settings(&saved), settings);
+}
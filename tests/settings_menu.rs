@@ -0,0 +1,136 @@
+//! Tests for the settings screen.
+
+use bevy::prelude::*;
+use gamedevjam2024::gfx::Quality;
+use gamedevjam2024::settings::Settings;
+use gamedevjam2024::sound::{AudioChannel, AudioChannels};
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::ui::{
+    ButtonActivated, MainMenuButton, MainMenuRoot, MenuFocus, PauseMenuButton, PauseMenuRoot,
+    SettingsClosed, SettingsRoot, SettingsWidget, UiPlugin, VOLUME_STEP,
+};
+
+/// The settings screen, opened from the main menu
+fn settings_app() -> App {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    activate::<MainMenuButton>(&mut app, MainMenuButton::Settings);
+    app
+}
+
+fn state(app: &App) -> AppState {
+    *app.world.resource::<State<AppState>>().get()
+}
+
+fn count<M: Component>(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<(), With<M>>()
+        .iter(&app.world)
+        .count()
+}
+
+fn activate<B: Component + PartialEq>(app: &mut App, which: B) {
+    let button = app
+        .world
+        .query::<(Entity, &B)>()
+        .iter(&app.world)
+        .find(|(_, button)| **button == which)
+        .map(|(entity, _)| entity)
+        .unwrap();
+    app.world.send_event(ButtonActivated(button));
+    run_frames(app, 2);
+}
+
+fn focused<B: Component + Copy>(app: &mut App) -> Option<B> {
+    let focus = app.world.resource::<MenuFocus>().0?;
+    app.world.get::<B>(focus).copied()
+}
+
+fn drain<E: Event>(app: &mut App) -> Vec<E> {
+    app.world.resource_mut::<Events<E>>().drain().collect()
+}
+
+/// Presses `key` for a frame, then releases it
+fn tap(app: &mut App, key: KeyCode) {
+    app.world.resource_mut::<ButtonInput<KeyCode>>().press(key);
+    run_frames(app, 1);
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.clear();
+    keys.release(key);
+    run_frames(app, 1);
+}
+
+#[test]
+fn back_returns_to_the_main_menu() {
+    let mut app = settings_app();
+    assert_eq!(count::<SettingsRoot>(&mut app), 1);
+    assert_eq!(count::<MainMenuRoot>(&mut app), 0);
+    assert_eq!(
+        focused::<SettingsWidget>(&mut app),
+        Some(SettingsWidget::Volume(AudioChannel::Music))
+    );
+
+    activate(&mut app, SettingsWidget::Back);
+    assert_eq!(drain::<SettingsClosed>(&mut app).len(), 1);
+    assert_eq!(count::<SettingsRoot>(&mut app), 0);
+    assert_eq!(count::<MainMenuRoot>(&mut app), 1);
+    assert_eq!(
+        focused::<MainMenuButton>(&mut app),
+        Some(MainMenuButton::Settings)
+    );
+}
+
+#[test]
+fn sliders_move_with_left_and_right() {
+    let mut app = settings_app();
+    app.world.resource_mut::<AudioChannels>().music = 0.5;
+
+    tap(&mut app, KeyCode::ArrowRight);
+    let music = app.world.resource::<AudioChannels>().music;
+    assert!((music - (0.5 + VOLUME_STEP)).abs() < 1e-4);
+
+    tap(&mut app, KeyCode::ArrowLeft);
+    tap(&mut app, KeyCode::ArrowLeft);
+    let music = app.world.resource::<AudioChannels>().music;
+    assert!((music - (0.5 - VOLUME_STEP)).abs() < 1e-4);
+}
+
+#[test]
+fn choices_and_reset_change_settings() {
+    let mut app = settings_app();
+    activate(&mut app, SettingsWidget::Quality);
+    // from High, wrapping around
+    assert_eq!(
+        app.world.resource::<Settings>().graphics.quality,
+        Quality::Low
+    );
+    activate(&mut app, SettingsWidget::Captions);
+    assert!(app.world.resource::<Settings>().captions);
+
+    activate(&mut app, SettingsWidget::Reset);
+    assert_eq!(*app.world.resource::<Settings>(), Settings::default());
+}
+
+#[test]
+fn pause_backs_out_to_the_pause_menu_without_resuming() {
+    let mut app = game_app();
+    app.add_plugins((AppStatePlugin, UiPlugin))
+        .init_resource::<ButtonInput<KeyCode>>();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    tap(&mut app, KeyCode::Escape);
+    activate(&mut app, PauseMenuButton::Settings);
+    assert_eq!(count::<SettingsRoot>(&mut app), 1);
+
+    tap(&mut app, KeyCode::Escape);
+    assert_eq!(state(&app), AppState::Paused);
+    assert_eq!(count::<SettingsRoot>(&mut app), 0);
+    assert_eq!(count::<PauseMenuRoot>(&mut app), 1);
+
+    tap(&mut app, KeyCode::Escape);
+    assert_eq!(state(&app), AppState::InGame);
+}
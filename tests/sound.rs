@@ -0,0 +1,1390 @@
+//! Tests for the sound module's event handling, run without an audio device.
+
+use bevy::{
+    asset::io::{AssetReader, AssetReaderError, AssetSource, PathStream, Reader},
+    audio::{AudioSource, PlaybackMode},
+    core::FrameCount,
+    prelude::*,
+    time::TimeUpdateStrategy,
+    utils::BoxedFuture,
+    window::WindowFocused,
+};
+use bevy_ecs_tilemap::prelude::*;
+use gamedevjam2024::gfx::{Animation, AnimationType, MainCamera};
+use gamedevjam2024::helpers::tiled::{MapBounds, MapLoaded, MapProperties, TileProperties};
+use gamedevjam2024::rng::GameRng;
+use gamedevjam2024::sound::{
+    AllSoundsLoaded, AmbientOneShot, AmbientPlaying, AmbientScheduler, AudioChannel, AudioChannels,
+    AudioEmitter, AudioUnlocked, BeatClock, Caption, CaptionImportance, CaptionsEnabled,
+    CurrentMusic, DuckMusic, EmitterSound, FootstepSurfaces, Footsteps, LoadingAudio, MusicDucking,
+    MusicIntro, MusicOnce, MusicTempo, NextTrack, NowPlaying, OnBeat, PlayAmbient, PlayMusic,
+    PlayPlaylist, PlaySFX, PlayStinger, Playlist, PreviousTrack, ResumeAfter, SetMuted, SetVolume,
+    SfxPriority, SfxTag, SoundAction, SoundCaption, SoundConfig, SoundDefaults, SoundLoadProgress,
+    SoundLog, SoundOutcome, SoundPlugin, SoundResource, Stinger, Stingers, StopAmbient,
+    StopDucking, StopMusic, StopSFX, ToggleMute, TweenVolume, VolumeTweens,
+};
+use std::path::Path;
+use std::time::Duration;
+
+/// An asset source whose files never finish loading, as in "slow://theme.ogg"
+struct NeverLoads;
+
+impl AssetReader for NeverLoads {
+    fn read<'a>(
+        &'a self,
+        _path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+        Box::pin(std::future::pending())
+    }
+
+    fn read_meta<'a>(
+        &'a self,
+        _path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<Reader<'a>>, AssetReaderError>> {
+        Box::pin(std::future::pending())
+    }
+
+    fn read_directory<'a>(
+        &'a self,
+        _path: &'a Path,
+    ) -> BoxedFuture<'a, Result<Box<PathStream>, AssetReaderError>> {
+        Box::pin(std::future::pending())
+    }
+
+    fn is_directory<'a>(
+        &'a self,
+        _path: &'a Path,
+    ) -> BoxedFuture<'a, Result<bool, AssetReaderError>> {
+        Box::pin(std::future::pending())
+    }
+}
+
+fn sound_app() -> App {
+    let mut app = App::new();
+    app.register_asset_source(
+        "slow",
+        AssetSource::build().with_reader(|| Box::new(NeverLoads)),
+    )
+    .add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        SoundPlugin {
+            persist_settings: false,
+            manifests: Vec::new(),
+            ..default()
+        },
+    ))
+    .init_asset::<AudioSource>();
+    register_sounds(&mut app, &["overworld", "battle"]);
+    app
+}
+
+/// Registers an empty sound under each name, returning their handles
+fn register_sounds(app: &mut App, names: &[&str]) -> Vec<Handle<AudioSource>> {
+    let mut handles = Vec::new();
+    for name in names {
+        let handle = app
+            .world
+            .resource_mut::<Assets<AudioSource>>()
+            .add(AudioSource {
+                bytes: Vec::new().into(),
+            });
+        app.world
+            .resource_mut::<SoundResource>()
+            .insert(name.to_string(), handle.clone());
+        handles.push(handle);
+    }
+    handles
+}
+
+fn now_playing_count(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .iter(&app.world)
+        .count()
+}
+
+/// Names of the music entities, fading ones included
+fn now_playing(app: &mut App) -> Vec<String> {
+    app.world
+        .query::<&NowPlaying>()
+        .iter(&app.world)
+        .map(|playing| playing.name.clone())
+        .collect()
+}
+
+#[test]
+fn two_play_music_events_in_one_frame_leave_one_track() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.world.send_event(PlayMusic::new("battle"));
+    app.update();
+
+    assert_eq!(now_playing_count(&mut app), 1);
+}
+
+#[test]
+fn play_music_replaces_the_current_track() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    app.world.send_event(PlayMusic::new("battle"));
+    app.update();
+
+    assert_eq!(now_playing_count(&mut app), 1);
+}
+
+#[test]
+fn requesting_the_playing_track_does_not_restart_it() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    let first = app
+        .world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .single(&app.world);
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    let second = app
+        .world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .single(&app.world);
+
+    assert_eq!(first, second);
+}
+
+#[test]
+fn muting_suppresses_new_sfx() {
+    let mut app = sound_app();
+
+    app.world.send_event(SetMuted(true));
+    app.world.send_event(PlaySFX::new("battle"));
+    app.update();
+
+    let sfx_count = app
+        .world
+        .query_filtered::<Entity, With<SfxTag>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(sfx_count, 0);
+}
+
+#[test]
+fn toggling_mute_twice_keeps_the_channel_levels() {
+    let mut app = sound_app();
+    app.world.resource_mut::<AudioChannels>().music = 0.4;
+
+    app.world.send_event(ToggleMute);
+    app.update();
+    assert!(app.world.resource::<AudioChannels>().muted);
+
+    app.world.send_event(ToggleMute);
+    app.update();
+    let channels = app.world.resource::<AudioChannels>();
+    assert!(!channels.muted);
+    assert_eq!(channels.music, 0.4);
+}
+
+#[test]
+fn music_requested_while_locked_plays_once_unlocked() {
+    let mut app = sound_app();
+    app.world.insert_resource(AudioUnlocked(false));
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.world.send_event(PlayMusic::new("battle"));
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 0);
+
+    app.world.insert_resource(AudioUnlocked(true));
+    app.update();
+    let playing = app
+        .world
+        .query::<&NowPlaying>()
+        .single(&app.world)
+        .name
+        .clone();
+    assert_eq!(playing, "battle");
+}
+
+#[test]
+fn current_music_follows_play_and_stop() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    assert_eq!(
+        app.world.resource::<CurrentMusic>().name(),
+        Some("overworld")
+    );
+
+    app.world
+        .send_event(PlayMusic::new("battle").with_crossfade(1.0));
+    app.update();
+    assert_eq!(app.world.resource::<CurrentMusic>().name(), Some("battle"));
+
+    app.world.send_event(StopMusic::default());
+    app.update();
+    assert_eq!(app.world.resource::<CurrentMusic>().name(), None);
+}
+
+#[test]
+fn a_crossfade_keeps_the_old_track_until_it_has_faded() {
+    let mut app = sound_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )));
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    app.world
+        .send_event(PlayMusic::new("battle").with_crossfade(0.5));
+    app.update();
+    let mut playing = now_playing(&mut app);
+    playing.sort();
+    assert_eq!(playing, vec!["battle".to_string(), "overworld".to_string()]);
+
+    for _ in 0..6 {
+        app.update();
+    }
+    assert_eq!(now_playing(&mut app), vec!["battle".to_string()]);
+}
+
+#[test]
+fn a_faded_stop_despawns_the_track_after_the_fade() {
+    let mut app = sound_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )));
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    app.world.send_event(StopMusic::with_fade_out(0.5));
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 1);
+    assert_eq!(app.world.resource::<CurrentMusic>().name(), None);
+
+    for _ in 0..6 {
+        app.update();
+    }
+    assert_eq!(now_playing_count(&mut app), 0);
+}
+
+#[test]
+fn a_stop_during_a_fade_in_wins() {
+    let mut app = sound_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )));
+
+    app.world
+        .send_event(PlayMusic::new("overworld").with_fade_in(2.0));
+    app.update();
+    app.world.send_event(StopMusic::default());
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 0);
+
+    // a faded stop takes over from the fade in, rather than waiting for it
+    app.world
+        .send_event(PlayMusic::new("overworld").with_fade_in(2.0));
+    app.update();
+    app.world.send_event(StopMusic::with_fade_out(0.5));
+    for _ in 0..7 {
+        app.update();
+    }
+    assert_eq!(now_playing_count(&mut app), 0);
+}
+
+#[test]
+fn only_the_last_music_request_of_a_frame_is_logged() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("battle"));
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+
+    // FrameCount has already moved on to the next frame
+    let frame = app.world.resource::<FrameCount>().0.wrapping_sub(1);
+    let played: Vec<_> = app
+        .world
+        .resource::<SoundLog>()
+        .in_frame(frame)
+        .filter(|entry| entry.action == SoundAction::PlayMusic)
+        .collect();
+    assert_eq!(played.len(), 1);
+    assert_eq!(played[0].name, "overworld");
+    assert_eq!(played[0].outcome, SoundOutcome::Played);
+}
+
+fn ambient_count(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<Entity, With<AmbientPlaying>>()
+        .iter(&app.world)
+        .count()
+}
+
+#[test]
+fn stop_music_leaves_ambient_loops_running() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.world.send_event(PlayAmbient::new("battle"));
+    app.update();
+    app.world.send_event(StopMusic::default());
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 0);
+    assert_eq!(ambient_count(&mut app), 1);
+
+    app.world.send_event(StopAmbient::all());
+    app.update();
+    assert_eq!(ambient_count(&mut app), 0);
+}
+
+#[test]
+fn volume_tweens_land_exactly_on_their_target() {
+    let mut app = sound_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )));
+
+    app.world
+        .send_event(TweenVolume::new(AudioChannel::Music, 0.2, 0.5));
+    app.update();
+    app.update();
+    let music = app
+        .world
+        .resource::<VolumeTweens>()
+        .level(AudioChannel::Music);
+    assert!(music < 1.0 && music > 0.2);
+
+    // replaces the first tween, starting from wherever it got to
+    app.world
+        .send_event(TweenVolume::new(AudioChannel::Music, 0.7, 0.3));
+    for _ in 0..10 {
+        app.update();
+    }
+    assert_eq!(
+        app.world
+            .resource::<VolumeTweens>()
+            .level(AudioChannel::Music),
+        0.7
+    );
+}
+
+#[test]
+fn volume_tweens_scale_the_players_volume_without_changing_it() {
+    let mut app = sound_app();
+    app.world.send_event(SetVolume {
+        channel: AudioChannel::Music,
+        volume: 0.5,
+    });
+    app.update();
+    let saved_at = app.world.resource_ref::<AudioChannels>().last_changed();
+
+    app.world
+        .send_event(TweenVolume::new(AudioChannel::Music, 0.4, 0.0));
+    app.update();
+    app.update();
+
+    let channels = app.world.resource_ref::<AudioChannels>();
+    // nothing for the settings to save, so the tween can't overwrite the player's choice
+    assert_eq!(channels.last_changed(), saved_at);
+    assert_eq!(channels.get(AudioChannel::Music), 0.5);
+    let level = channels.effective(AudioChannel::Music, app.world.resource::<VolumeTweens>());
+    assert!((level - 0.2).abs() < 1e-6);
+}
+
+#[test]
+fn stingers_queue_instead_of_stacking() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.world.send_event(PlayStinger::new("battle"));
+    app.world.send_event(PlayStinger::new("battle"));
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 1);
+    assert_eq!(stinger_count(&mut app), 1);
+    assert_eq!(app.world.resource::<Stingers>().queued(), 1);
+
+    // the first stinger finishing lets the next one start
+    let first = app
+        .world
+        .query_filtered::<Entity, With<Stinger>>()
+        .single(&app.world);
+    app.world.despawn(first);
+    app.update();
+    assert_eq!(stinger_count(&mut app), 1);
+    assert_eq!(app.world.resource::<Stingers>().queued(), 0);
+}
+
+fn stinger_count(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<Entity, With<Stinger>>()
+        .iter(&app.world)
+        .count()
+}
+
+#[test]
+fn ambient_scheduler_fires_its_one_shots() {
+    let mut app = sound_app();
+    let mut scheduler = app.world.resource_mut::<AmbientScheduler>();
+    scheduler.add(AmbientOneShot::new("battle", 0.0, 0.0));
+    scheduler.add(AmbientOneShot::new("overworld", 0.0, 0.0).with_chance(0.0));
+    app.update();
+
+    let frame = app.world.resource::<FrameCount>().0.wrapping_sub(1);
+    let played: Vec<_> = app
+        .world
+        .resource::<SoundLog>()
+        .in_frame(frame)
+        .filter(|entry| entry.action == SoundAction::PlaySfx)
+        .map(|entry| entry.name.clone())
+        .collect();
+    assert_eq!(played, vec!["battle".to_string()]);
+}
+
+fn sfx_count(app: &mut App) -> usize {
+    app.world
+        .query_filtered::<Entity, With<SfxTag>>()
+        .iter(&app.world)
+        .count()
+}
+
+#[test]
+fn sfx_requested_in_the_background_are_dropped() {
+    let mut app = sound_app();
+    app.world.send_event(WindowFocused {
+        window: Entity::PLACEHOLDER,
+        focused: false,
+    });
+    app.world.send_event(PlaySFX::new("battle"));
+    app.update();
+    assert_eq!(sfx_count(&mut app), 0);
+
+    app.world.send_event(WindowFocused {
+        window: Entity::PLACEHOLDER,
+        focused: true,
+    });
+    app.update();
+    app.world.send_event(PlaySFX::new("battle"));
+    app.update();
+    assert_eq!(sfx_count(&mut app), 1);
+}
+
+#[test]
+fn load_progress_counts_registered_sounds() {
+    let mut app = sound_app();
+    let mut reader = app.world.resource::<Events<AllSoundsLoaded>>().get_reader();
+
+    app.update();
+    let progress = app.world.resource::<SoundLoadProgress>();
+    assert_eq!((progress.loaded, progress.total), (2, 2));
+    assert!(progress.failed.is_empty());
+    let events = app.world.resource::<Events<AllSoundsLoaded>>();
+    assert_eq!(reader.read(events).count(), 1);
+
+    app.update();
+    app.update();
+    let events = app.world.resource::<Events<AllSoundsLoaded>>();
+    assert_eq!(reader.read(events).count(), 0);
+}
+
+#[test]
+fn emitter_sounds_last_as_long_as_their_entity() {
+    let mut app = sound_app();
+    let emitter_sounds = |app: &mut App| {
+        app.world
+            .query_filtered::<Entity, With<EmitterSound>>()
+            .iter(&app.world)
+            .count()
+    };
+
+    let campfire = app.world.spawn(AudioEmitter::new("battle")).id();
+    let torch = app.world.spawn(AudioEmitter::new("overworld")).id();
+    app.update();
+    assert_eq!(emitter_sounds(&mut app), 2);
+
+    app.world.entity_mut(campfire).remove::<AudioEmitter>();
+    app.world.despawn(torch);
+    app.update();
+    assert_eq!(emitter_sounds(&mut app), 0);
+}
+
+#[test]
+fn repeated_captions_are_coalesced() {
+    let mut app = sound_app();
+    app.world.insert_resource(CaptionsEnabled(true));
+    for name in ["overworld", "battle"] {
+        app.world.resource_mut::<SoundResource>().set_caption(
+            name.to_string(),
+            Caption {
+                text: "[footstep]".to_string(),
+                importance: CaptionImportance::Low,
+            },
+        );
+    }
+
+    app.world.send_event(PlaySFX::new("overworld"));
+    app.world.send_event(PlaySFX::new("battle"));
+    app.update();
+
+    let captions = app.world.resource::<Events<SoundCaption>>();
+    assert_eq!(captions.get_reader().read(captions).count(), 1);
+}
+
+#[test]
+fn handle_based_requests_use_the_same_pipeline() {
+    let mut app = sound_app();
+    let handle = app
+        .world
+        .resource::<SoundResource>()
+        .handle("battle")
+        .unwrap();
+
+    app.world.send_event(PlaySFX::from_handle(handle.clone()));
+    app.world.send_event(PlaySFX::from_handle(handle.clone()));
+    app.world.send_event(PlayMusic::from_handle(handle));
+    app.update();
+
+    // the second SFX falls within the default rate limit
+    let sfx_count = app
+        .world
+        .query_filtered::<Entity, With<SfxTag>>()
+        .iter(&app.world)
+        .count();
+    assert_eq!(sfx_count, 1);
+    assert_eq!(now_playing_count(&mut app), 1);
+}
+
+const TRACKS: [&str; 4] = ["credits_1", "credits_2", "credits_3", "credits_4"];
+
+fn play_playlist(app: &mut App, shuffle: bool, repeat: bool) {
+    register_sounds(app, &TRACKS);
+    app.world.send_event(PlayPlaylist {
+        names: TRACKS.iter().map(|name| name.to_string()).collect(),
+        shuffle,
+        repeat,
+    });
+    app.update();
+}
+
+/// The tracks a playlist goes through, skipping on from the first with NextTrack
+fn skip_through(app: &mut App, tracks: usize) -> Vec<String> {
+    let mut played = now_playing(app);
+    for _ in 1..tracks {
+        app.world.send_event(NextTrack);
+        app.update();
+        played.extend(now_playing(app));
+    }
+    played
+}
+
+#[test]
+fn a_playlist_starts_on_its_first_track_and_plays_in_order() {
+    let mut app = sound_app();
+    play_playlist(&mut app, false, false);
+    assert_eq!(now_playing(&mut app), vec![TRACKS[0].to_string()]);
+
+    // nothing has finished in between
+    app.update();
+    assert_eq!(now_playing(&mut app), vec![TRACKS[0].to_string()]);
+    assert_eq!(app.world.resource::<Playlist>().position(), 0);
+
+    assert_eq!(skip_through(&mut app, TRACKS.len()), TRACKS.to_vec());
+
+    // a list that doesn't repeat ends after its last track
+    app.world.send_event(NextTrack);
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 0);
+    assert!(!app.world.resource::<Playlist>().is_active());
+}
+
+#[test]
+fn a_shuffled_playlist_plays_every_track_once_in_the_seeded_order() {
+    let shuffled = || {
+        let mut app = sound_app();
+        app.insert_resource(GameRng::new(7));
+        play_playlist(&mut app, true, false);
+        skip_through(&mut app, TRACKS.len())
+    };
+    let order = shuffled();
+    let mut sorted = order.clone();
+    sorted.sort();
+    assert_eq!(sorted, TRACKS.to_vec());
+    assert_eq!(shuffled(), order);
+}
+
+#[test]
+fn next_and_previous_tracks_wrap_around_a_repeating_playlist() {
+    let mut app = sound_app();
+    play_playlist(&mut app, false, true);
+
+    app.world.send_event(PreviousTrack);
+    app.update();
+    assert_eq!(now_playing(&mut app), vec![TRACKS[3].to_string()]);
+
+    app.world.send_event(NextTrack);
+    app.update();
+    assert_eq!(now_playing(&mut app), vec![TRACKS[0].to_string()]);
+
+    // skips sent together add up
+    app.world.send_event(NextTrack);
+    app.world.send_event(NextTrack);
+    app.world.send_event(PreviousTrack);
+    app.update();
+    assert_eq!(now_playing(&mut app), vec![TRACKS[1].to_string()]);
+}
+
+#[test]
+fn previous_track_on_the_first_track_restarts_it() {
+    let mut app = sound_app();
+    play_playlist(&mut app, false, false);
+    let first = app
+        .world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .single(&app.world);
+
+    app.world.send_event(PreviousTrack);
+    app.update();
+    let restarted = app
+        .world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .single(&app.world);
+    assert_ne!(restarted, first);
+    assert_eq!(now_playing(&mut app), vec![TRACKS[0].to_string()]);
+}
+
+#[test]
+fn stop_music_clears_the_whole_playlist() {
+    let mut app = sound_app();
+    play_playlist(&mut app, false, true);
+
+    app.world.send_event(StopMusic::default());
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 0);
+    assert!(!app.world.resource::<Playlist>().is_active());
+
+    // nothing left to skip to
+    app.world.send_event(NextTrack);
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 0);
+}
+
+/// Registers "theme" as an intro/loop pair, returning the intro and loop handles
+fn register_intro_loop(app: &mut App) -> (Handle<AudioSource>, Handle<AudioSource>) {
+    let handles = register_sounds(app, &["theme_intro", "theme_loop"]);
+    app.world.resource_mut::<SoundResource>().insert_intro_loop(
+        "theme".to_string(),
+        "theme_intro".to_string(),
+        "theme_loop".to_string(),
+    );
+    (handles[0].clone(), handles[1].clone())
+}
+
+#[test]
+fn an_intro_loop_entry_starts_on_its_intro_under_the_entry_name() {
+    let mut app = sound_app();
+    let (intro, _) = register_intro_loop(&mut app);
+
+    app.world.send_event(PlayMusic::new("theme"));
+    app.update();
+
+    let (entity, playing, source, settings) = app
+        .world
+        .query_filtered::<(
+            Entity,
+            &NowPlaying,
+            &Handle<AudioSource>,
+            &PlaybackSettings,
+        ), With<MusicIntro>>()
+        .single(&app.world);
+    assert_eq!(playing.name, "theme");
+    assert_eq!(source.id(), intro.id());
+    // the intro plays once; the loop takes over on the same entity
+    assert!(matches!(settings.mode, PlaybackMode::Once));
+
+    app.world.send_event(PlayMusic::new("theme"));
+    app.update();
+    let again = app
+        .world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .single(&app.world);
+    assert_eq!(again, entity);
+
+    app.world.send_event(StopMusic::default());
+    app.update();
+    assert_eq!(now_playing_count(&mut app), 0);
+}
+
+/// A MainCamera at the origin whose view is 32 units wide
+fn spawn_listener(app: &mut App) {
+    app.world.spawn((
+        MainCamera {},
+        GlobalTransform::IDENTITY,
+        OrthographicProjection {
+            area: Rect::new(-16.0, -9.0, 16.0, 9.0),
+            ..default()
+        },
+    ));
+}
+
+/// The SFX entity playing `name`, with its settings and position
+fn sfx_playing(app: &mut App, name: &str) -> Option<(PlaybackSettings, Option<Vec3>)> {
+    app.world
+        .query::<(&SfxTag, &PlaybackSettings, Option<&Transform>)>()
+        .iter(&app.world)
+        .find(|(tag, _, _)| tag.0 == name)
+        .map(|(_, settings, transform)| {
+            (
+                settings.clone(),
+                transform.map(|transform| transform.translation),
+            )
+        })
+}
+
+#[test]
+fn positional_sfx_are_attenuated_and_kept_within_half_a_view() {
+    let mut app = sound_app();
+    register_sounds(&mut app, &["near", "far", "flat"]);
+    spawn_listener(&mut app);
+
+    // half of the default 64 unit range away, beyond the 16 unit half view
+    app.world
+        .send_event(PlaySFX::at("near", Vec2::new(32.0, 0.0)));
+    app.world
+        .send_event(PlaySFX::at("far", Vec2::new(0.0, 80.0)));
+    app.world.send_event(PlaySFX::new("flat"));
+    app.update();
+
+    let (settings, position) = sfx_playing(&mut app, "near").unwrap();
+    assert!(settings.spatial);
+    assert!((settings.volume.get() - 0.5).abs() < 1e-6);
+    assert_eq!(position, Some(Vec3::new(16.0, 0.0, 0.0)));
+
+    assert!(sfx_playing(&mut app, "far").is_none());
+    let skipped =
+        app.world.resource::<SoundLog>().entries().any(|entry| {
+            entry.name == "far" && entry.outcome == SoundOutcome::Skipped("out of range")
+        });
+    assert!(skipped);
+
+    let (settings, position) = sfx_playing(&mut app, "flat").unwrap();
+    assert!(!settings.spatial);
+    assert_eq!(settings.volume.get(), 1.0);
+    assert_eq!(position, None);
+}
+
+#[test]
+fn sounds_marked_non_spatial_ignore_their_position() {
+    let mut app = sound_app();
+    register_sounds(&mut app, &["ui"]);
+    app.world.resource_mut::<SoundResource>().set_defaults(
+        "ui".to_string(),
+        SoundDefaults {
+            spatial: false,
+            ..default()
+        },
+    );
+    spawn_listener(&mut app);
+
+    app.world
+        .send_event(PlaySFX::at("ui", Vec2::new(32.0, 0.0)));
+    app.update();
+
+    let (settings, position) = sfx_playing(&mut app, "ui").unwrap();
+    assert!(!settings.spatial);
+    assert_eq!(settings.volume.get(), 1.0);
+    assert_eq!(position, None);
+}
+
+#[test]
+fn sfx_start_from_their_defaults_before_the_event_overrides() {
+    let mut app = sound_app();
+    let handles = register_sounds(&mut app, &["tick"]);
+    app.world
+        .resource_mut::<SoundResource>()
+        .insert_with_defaults(
+            "tick".to_string(),
+            handles[0].clone(),
+            SoundDefaults {
+                volume: 0.25,
+                speed: 1.5,
+                min_interval: Some(0.0),
+                ..default()
+            },
+        );
+
+    app.world.send_event(PlaySFX::new("tick"));
+    app.update();
+    let (settings, _) = sfx_playing(&mut app, "tick").unwrap();
+    assert_eq!(settings.volume.get(), 0.25);
+    assert_eq!(settings.speed, 1.5);
+    let entity = app
+        .world
+        .query_filtered::<Entity, With<SfxTag>>()
+        .single(&app.world);
+    app.world.despawn(entity);
+
+    // adjusted after registration, with the event's own volume on top
+    app.world.resource_mut::<SoundResource>().set_defaults(
+        "tick".to_string(),
+        SoundDefaults {
+            volume: 0.5,
+            speed: 0.75,
+            min_interval: Some(0.0),
+            ..default()
+        },
+    );
+    app.world.send_event(PlaySFX::new("tick").with_volume(0.8));
+    app.update();
+    let (settings, _) = sfx_playing(&mut app, "tick").unwrap();
+    assert_eq!(settings.volume.get(), 0.8);
+    assert_eq!(settings.speed, 0.75);
+}
+
+#[test]
+fn a_group_never_picks_the_same_member_twice_in_a_row() {
+    let mut app = sound_app();
+    let members = register_sounds(&mut app, &["hit_1", "hit_2"]);
+    let mut sound_resource = app.world.resource_mut::<SoundResource>();
+    sound_resource.insert_group("hit".to_string(), members.clone());
+    sound_resource.set_defaults(
+        "hit".to_string(),
+        SoundDefaults {
+            min_interval: Some(0.0),
+            ..default()
+        },
+    );
+
+    let mut picks = Vec::new();
+    for _ in 0..8 {
+        app.world.send_event(PlaySFX::new("hit"));
+        app.update();
+        let (entity, source) = app
+            .world
+            .query_filtered::<(Entity, &Handle<AudioSource>), With<SfxTag>>()
+            .single(&app.world);
+        picks.push(source.id());
+        app.world.despawn(entity);
+    }
+
+    assert!(picks.windows(2).all(|pair| pair[0] != pair[1]));
+    assert!(members.iter().all(|member| picks.contains(&member.id())));
+}
+
+#[test]
+fn getting_a_group_picks_members_in_the_seeded_order() {
+    let picks = || {
+        let mut app = sound_app();
+        app.insert_resource(GameRng::new(7));
+        let members = register_sounds(&mut app, &["step_1", "step_2", "step_3", "step_4"]);
+        app.world
+            .resource_mut::<SoundResource>()
+            .insert_group("step".to_string(), members);
+        app.update();
+
+        let sound_resource = app.world.resource::<SoundResource>();
+        (0..16)
+            .map(|_| sound_resource.get("step").unwrap().id())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(picks(), picks());
+}
+
+/// Plays `event` `times` times, one a frame, returning the speed each one started at
+fn sfx_speeds(app: &mut App, event: PlaySFX, times: usize) -> Vec<f32> {
+    let mut speeds = Vec::new();
+    for _ in 0..times {
+        app.world.send_event(event.clone());
+        app.update();
+        let (entity, settings) = app
+            .world
+            .query_filtered::<(Entity, &PlaybackSettings), With<SfxTag>>()
+            .single(&app.world);
+        speeds.push(settings.speed);
+        app.world.despawn(entity);
+    }
+    speeds
+}
+
+#[test]
+fn sfx_without_jitter_play_at_exactly_their_speed() {
+    let mut app = sound_app();
+    app.world.resource_mut::<SoundResource>().set_defaults(
+        "battle".to_string(),
+        SoundDefaults {
+            min_interval: Some(0.0),
+            ..default()
+        },
+    );
+
+    let speeds = sfx_speeds(&mut app, PlaySFX::new("battle"), 4);
+    assert!(speeds.iter().all(|speed| *speed == 1.0));
+}
+
+#[test]
+fn jittered_speeds_stay_within_the_jitter() {
+    let mut app = sound_app();
+    app.insert_resource(GameRng::new(7));
+    app.world.resource_mut::<SoundResource>().set_defaults(
+        "battle".to_string(),
+        SoundDefaults {
+            speed_jitter: 0.1,
+            min_interval: Some(0.0),
+            ..default()
+        },
+    );
+
+    let speeds = sfx_speeds(&mut app, PlaySFX::new("battle"), 16);
+    assert!(speeds.iter().all(|speed| (0.9..=1.1).contains(speed)));
+    assert!(speeds.iter().any(|speed| *speed != 1.0));
+
+    // the jitter scales an overridden speed too
+    let event = PlaySFX::new("battle")
+        .with_speed(2.0)
+        .with_speed_jitter(0.1);
+    let speeds = sfx_speeds(&mut app, event, 16);
+    assert!(speeds.iter().all(|speed| (1.8..=2.2).contains(speed)));
+}
+
+fn sfx_tags(app: &mut App) -> Vec<String> {
+    let mut tags: Vec<_> = app
+        .world
+        .query::<&SfxTag>()
+        .iter(&app.world)
+        .map(|tag| tag.0.clone())
+        .collect();
+    tags.sort();
+    tags
+}
+
+#[test]
+fn higher_priority_sfx_replace_the_lowest_priority_voice_at_the_cap() {
+    let mut app = sound_app();
+    register_sounds(&mut app, &["a", "b", "c", "d", "e", "f"]);
+    app.world.resource_mut::<SoundConfig>().max_sfx_voices = 2;
+
+    app.world
+        .send_event(PlaySFX::new("a").with_priority(SfxPriority::Low));
+    app.world.send_event(PlaySFX::new("b"));
+    app.update();
+    app.world
+        .send_event(PlaySFX::new("c").with_priority(SfxPriority::High));
+    app.update();
+    assert_eq!(sfx_tags(&mut app), vec!["b", "c"]);
+
+    // nothing playing gives way to an equal or lower priority
+    app.world
+        .send_event(PlaySFX::new("d").with_priority(SfxPriority::Low));
+    app.world.send_event(PlaySFX::new("e"));
+    app.update();
+    assert_eq!(sfx_tags(&mut app), vec!["b", "c"]);
+
+    app.world
+        .send_event(PlaySFX::new("f").with_priority(SfxPriority::High));
+    app.update();
+    assert_eq!(sfx_tags(&mut app), vec!["c", "f"]);
+}
+
+#[test]
+fn looping_sfx_play_until_stopped() {
+    let mut app = sound_app();
+    register_sounds(&mut app, &["engine", "wind"]);
+
+    app.world.send_event(PlaySFX::new("engine").looping());
+    app.world.send_event(PlaySFX::new("wind").looping());
+    app.world.send_event(PlaySFX::new("battle"));
+    app.update();
+    for (tag, settings) in app
+        .world
+        .query::<(&SfxTag, &PlaybackSettings)>()
+        .iter(&app.world)
+    {
+        match tag.0.as_str() {
+            "battle" => assert!(matches!(settings.mode, PlaybackMode::Despawn)),
+            _ => assert!(matches!(settings.mode, PlaybackMode::Loop)),
+        }
+    }
+
+    app.world.send_event(StopSFX::by_name("engine"));
+    app.update();
+    assert_eq!(sfx_tags(&mut app), vec!["battle", "wind"]);
+
+    // never started playing here, so the fade has nothing to ramp and ends right away
+    app.world.send_event(StopSFX::all().with_fade_out(0.5));
+    app.update();
+    app.update();
+    assert!(sfx_tags(&mut app).is_empty());
+}
+
+fn ducking_level(app: &App) -> f32 {
+    app.world.resource::<MusicDucking>().level()
+}
+
+#[test]
+fn the_deepest_duck_wins_until_it_releases() {
+    let mut app = sound_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+        100,
+    )));
+
+    app.world
+        .send_event(DuckMusic::new(0.5).with_attack(0.0).with_release(0.25));
+    app.world.send_event(
+        DuckMusic::new(0.2)
+            .with_attack(0.0)
+            .with_release(0.0)
+            .with_hold(0.3),
+    );
+    app.update();
+    assert_eq!(ducking_level(&app), 0.2);
+
+    // the held duck expires, leaving the shallower one
+    for _ in 0..6 {
+        app.update();
+    }
+    assert_eq!(ducking_level(&app), 0.5);
+    assert!(app.world.resource::<MusicDucking>().is_ducking());
+
+    app.world.send_event(StopDucking);
+    for _ in 0..3 {
+        app.update();
+    }
+    assert_eq!(ducking_level(&app), 1.0);
+    assert!(!app.world.resource::<MusicDucking>().is_ducking());
+}
+
+#[test]
+fn set_muted_wins_over_toggles_sent_the_same_frame() {
+    let mut app = sound_app();
+
+    app.world.send_event(SetMuted(true));
+    app.world.send_event(ToggleMute);
+    app.update();
+    assert!(app.world.resource::<AudioChannels>().muted);
+
+    app.world.send_event(ToggleMute);
+    app.world.send_event(SetMuted(false));
+    app.world.send_event(ToggleMute);
+    app.update();
+    assert!(!app.world.resource::<AudioChannels>().muted);
+
+    // on their own, toggles still flip it
+    app.world.send_event(ToggleMute);
+    app.update();
+    assert!(app.world.resource::<AudioChannels>().muted);
+}
+
+/// Registers `name` as a sound whose file is still loading
+fn register_loading_sound(app: &mut App, name: &str) {
+    let handle = app
+        .world
+        .resource::<AssetServer>()
+        .load::<AudioSource>(format!("slow://{}.ogg", name));
+    app.world
+        .resource_mut::<SoundResource>()
+        .insert(name.to_string(), handle);
+}
+
+fn logged(app: &App, name: &str, outcome: SoundOutcome) -> bool {
+    app.world
+        .resource::<SoundLog>()
+        .entries()
+        .any(|entry| entry.name == name && entry.outcome == outcome)
+}
+
+#[test]
+fn music_waits_for_its_file_while_the_current_track_keeps_playing() {
+    let mut app = sound_app();
+    register_loading_sound(&mut app, "theme");
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    app.world.send_event(PlayMusic::new("theme"));
+    for _ in 0..5 {
+        app.update();
+    }
+
+    assert!(logged(&app, "theme", SoundOutcome::Queued));
+    assert!(app.world.resource::<LoadingAudio>().is_waiting());
+    assert_eq!(now_playing(&mut app), vec!["overworld".to_string()]);
+}
+
+#[test]
+fn sfx_still_loading_are_dropped_after_the_load_wait() {
+    let mut app = sound_app();
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)));
+    register_loading_sound(&mut app, "thud");
+
+    app.world.send_event(PlaySFX::new("thud"));
+    app.update();
+    assert!(logged(&app, "thud", SoundOutcome::Queued));
+    assert!(app.world.resource::<LoadingAudio>().is_waiting());
+
+    // past the default three second wait
+    for _ in 0..5 {
+        app.update();
+    }
+    assert!(!app.world.resource::<LoadingAudio>().is_waiting());
+    assert_eq!(sfx_count(&mut app), 0);
+}
+
+/// What the one-shot track `name` hands back to once it ends
+fn resume_after(app: &mut App, name: &str) -> Option<ResumeAfter> {
+    let (_, once, settings) = app
+        .world
+        .query::<(&NowPlaying, &MusicOnce, &PlaybackSettings)>()
+        .iter(&app.world)
+        .find(|(playing, _, _)| playing.name == name)
+        .expect("one-shot track is playing");
+    assert!(matches!(settings.mode, PlaybackMode::Once));
+    once.resume.clone()
+}
+
+#[test]
+fn a_jingle_remembers_the_track_it_interrupted() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    app.world
+        .send_event(PlayMusic::new("battle").with_resume_previous());
+    app.update();
+
+    assert_eq!(now_playing(&mut app), vec!["battle".to_string()]);
+    assert!(matches!(
+        resume_after(&mut app, "battle"),
+        Some(ResumeAfter::Track(name)) if name == "overworld"
+    ));
+}
+
+#[test]
+fn a_jingle_over_a_playlist_holds_it_instead_of_clearing_it() {
+    let mut app = sound_app();
+    play_playlist(&mut app, false, true);
+
+    app.world
+        .send_event(PlayMusic::new("battle").with_resume_previous());
+    for _ in 0..3 {
+        app.update();
+    }
+
+    assert!(matches!(
+        resume_after(&mut app, "battle"),
+        Some(ResumeAfter::Playlist)
+    ));
+    let playlist = app.world.resource::<Playlist>();
+    assert!(playlist.is_active());
+    assert_eq!(playlist.current(), Some(TRACKS[0]));
+    assert_eq!(now_playing(&mut app), vec!["battle".to_string()]);
+}
+
+#[test]
+fn a_track_played_once_without_resume_hands_back_to_nothing() {
+    let mut app = sound_app();
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    app.world
+        .send_event(PlayMusic::new("battle").with_looping(false));
+    app.update();
+
+    assert!(resume_after(&mut app, "battle").is_none());
+}
+
+#[test]
+fn the_beat_clock_follows_the_current_tracks_tempo() {
+    let mut app = sound_app();
+    let tempo = MusicTempo::new(120.0).with_beats_per_bar(3);
+    app.world
+        .resource_mut::<SoundResource>()
+        .insert_tempo("battle".to_string(), tempo);
+
+    app.world.send_event(PlayMusic::new("battle"));
+    for _ in 0..3 {
+        app.update();
+    }
+    let clock = app.world.resource::<BeatClock>();
+    assert_eq!(clock.tempo(), Some(tempo));
+    // without an audio device the track never becomes audible, so no beat has gone by
+    assert_eq!(clock.beat(), None);
+    assert_eq!(clock.progress(), 0.0);
+    assert!(app.world.resource::<Events<OnBeat>>().is_empty());
+
+    app.world.send_event(PlayMusic::new("overworld"));
+    app.update();
+    app.update();
+    assert_eq!(app.world.resource::<BeatClock>().tempo(), None);
+}
+
+/// A two tile layer of 32 unit tiles centred on the origin: grass, then lava. Only grass has
+/// a footstep group of its own.
+fn spawn_surfaces(app: &mut App) {
+    let mut storage = TileStorage::empty(TilemapSize { x: 2, y: 1 });
+    for (x, surface) in ["grass", "lava"].iter().enumerate() {
+        let mut properties = tiled::Properties::new();
+        properties.insert(
+            "surface".to_string(),
+            tiled::PropertyValue::StringValue(surface.to_string()),
+        );
+        let tile = app.world.spawn(TileProperties(properties)).id();
+        storage.set(&TilePos { x: x as u32, y: 0 }, tile);
+    }
+    app.world.spawn((
+        storage,
+        TilemapSize { x: 2, y: 1 },
+        TilemapGridSize { x: 32.0, y: 32.0 },
+        TilemapType::Square,
+        GlobalTransform::IDENTITY,
+    ));
+    app.world
+        .resource_mut::<FootstepSurfaces>()
+        .insert("grass".to_string(), "grass_steps".to_string());
+}
+
+/// Runs a frame and returns the footsteps it sent
+fn steps_this_frame(app: &mut App) -> Vec<PlaySFX> {
+    app.update();
+    app.world
+        .resource_mut::<Events<PlaySFX>>()
+        .drain()
+        .collect()
+}
+
+#[test]
+fn distance_footsteps_carry_over_the_stride_and_ignore_teleports() {
+    let mut app = sound_app();
+    spawn_surfaces(&mut app);
+    let actor = app
+        .world
+        .spawn((
+            Footsteps::every(10.0).with_foot_offset(Vec2::new(0.0, -2.0)),
+            GlobalTransform::IDENTITY,
+        ))
+        .id();
+
+    // 52 units in one frame is a teleport; the last steps are off the map, then on lava
+    let walk = [2.0, 8.0, 14.0, 8.0, 2.0, 8.0, 60.0, 66.0, 72.0, 40.0];
+    let expected = [
+        None,
+        None,
+        Some("grass_steps"),
+        None,
+        Some("grass_steps"),
+        Some("grass_steps"),
+        None,
+        None,
+        Some("footstep"),
+        Some("footstep"),
+    ];
+    for (x, expected) in walk.iter().zip(expected.iter()) {
+        *app.world.get_mut::<GlobalTransform>(actor).unwrap() =
+            GlobalTransform::from_xyz(*x, 4.0, 0.0);
+        let steps = steps_this_frame(&mut app);
+        match expected {
+            Some(group) => {
+                assert_eq!(steps.len(), 1, "one step at {}", x);
+                assert_eq!(steps[0].name, *group);
+                assert_eq!(steps[0].position, Some(Vec2::new(*x, 2.0)));
+            }
+            None => assert!(steps.is_empty(), "no step at {}", x),
+        }
+    }
+}
+
+#[test]
+fn frame_footsteps_land_once_per_frame_entered() {
+    let mut app = sound_app();
+    spawn_surfaces(&mut app);
+    let actor = app
+        .world
+        .spawn((
+            Footsteps::on_frames(vec![1, 3]),
+            Animation::new(
+                Handle::default(),
+                vec![0, 1, 2, 3],
+                1.0,
+                AnimationType::Repeat,
+            ),
+            GlobalTransform::from_xyz(2.0, 2.0, 0.0),
+        ))
+        .id();
+
+    // whether to move the animation on a frame first, and whether a step lands
+    let frames = [
+        (false, false),
+        (true, true),
+        (false, false),
+        (true, false),
+        (true, true),
+        (true, false),
+        (true, true),
+    ];
+    for (advance, lands) in frames.iter() {
+        if *advance {
+            app.world.get_mut::<Animation>(actor).unwrap().tick(1.0);
+        }
+        let steps = steps_this_frame(&mut app);
+        assert_eq!(steps.len(), usize::from(*lands));
+        assert!(steps.iter().all(|step| step.name == "grass_steps"));
+    }
+}
+
+fn load_map(app: &mut App, properties: &[(&str, &str)]) {
+    let mut map_properties = MapProperties::default();
+    for (name, value) in properties {
+        map_properties.0.insert(
+            name.to_string(),
+            tiled::PropertyValue::StringValue(value.to_string()),
+        );
+    }
+    let map = app.world.spawn_empty().id();
+    app.world.send_event(MapLoaded {
+        map,
+        path: "level.tmx".to_string(),
+        size_in_tiles: UVec2::splat(4),
+        tile_size: Vec2::splat(16.0),
+        bounds: MapBounds::default(),
+        properties: map_properties,
+        reloaded: false,
+    });
+    app.update();
+    app.update();
+}
+
+#[test]
+fn maps_start_the_music_and_ambience_they_name() {
+    let mut app = sound_app();
+    register_sounds(&mut app, &["wind"]);
+
+    load_map(&mut app, &[("music", "battle"), ("ambient", "wind")]);
+    assert_eq!(now_playing(&mut app), vec!["battle".to_string()]);
+    assert_eq!(ambient_count(&mut app), 1);
+    let track = app
+        .world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .single(&app.world);
+
+    // the same music carries on into the next map without being requested again
+    load_map(&mut app, &[("music", "battle")]);
+    let again = app
+        .world
+        .query_filtered::<Entity, With<NowPlaying>>()
+        .single(&app.world);
+    assert_eq!(again, track);
+    let requested = app
+        .world
+        .resource::<SoundLog>()
+        .entries()
+        .filter(|entry| entry.action == SoundAction::PlayMusic)
+        .count();
+    assert_eq!(requested, 1);
+
+    // a map that names nothing leaves both playing
+    load_map(&mut app, &[]);
+    assert_eq!(now_playing(&mut app), vec!["battle".to_string()]);
+    assert_eq!(ambient_count(&mut app), 1);
+}
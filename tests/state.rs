@@ -0,0 +1,250 @@
+//! Tests for AppState and the events changing it.
+
+use bevy::prelude::*;
+use bevy::window::WindowOccluded;
+use gamedevjam2024::state::{
+    AppState, AppStatePlugin, ChangeState, GameplaySet, Hitstop, PauseOnHidden, SetTimeScale,
+    TogglePause, MAX_TIME_SCALE, TIME_SCALE_RAMP,
+};
+use gamedevjam2024::testing::{headless_app, run_frames, FRAME};
+
+#[derive(Resource, Default)]
+struct GameplayFrames(u32);
+
+fn count_gameplay_frames(mut frames: ResMut<GameplayFrames>) {
+    frames.0 += 1;
+}
+
+fn state_app() -> App {
+    let mut app = headless_app();
+    app.add_plugins(AppStatePlugin)
+        .init_resource::<GameplayFrames>()
+        .add_systems(Update, count_gameplay_frames.in_set(GameplaySet));
+    app
+}
+
+fn state(app: &App) -> AppState {
+    *app.world.resource::<State<AppState>>().get()
+}
+
+fn time_stopped(app: &App) -> bool {
+    app.world.resource::<Time<Virtual>>().relative_speed() == 0.0
+}
+
+#[test]
+fn events_move_the_game_through_its_states() {
+    let mut app = state_app();
+    assert_eq!(state(&app), AppState::Loading);
+
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::MainMenu);
+    assert_eq!(app.world.resource::<GameplayFrames>().0, 0);
+
+    // only a running game can pause
+    app.world.send_event(ChangeState(AppState::Paused));
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::MainMenu);
+
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::InGame);
+    assert!(app.world.resource::<GameplayFrames>().0 > 0);
+
+    app.world.send_event(ChangeState(AppState::GameOver));
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::GameOver);
+}
+
+#[test]
+fn pausing_freezes_gameplay_and_virtual_time() {
+    let mut app = state_app();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::InGame);
+
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::Paused);
+    assert!(time_stopped(&app));
+    let frames = app.world.resource::<GameplayFrames>().0;
+    let elapsed = app.world.resource::<Time<Virtual>>().elapsed();
+    run_frames(&mut app, 5);
+    assert_eq!(app.world.resource::<GameplayFrames>().0, frames);
+    assert_eq!(app.world.resource::<Time<Virtual>>().elapsed(), elapsed);
+
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::InGame);
+    assert!(!time_stopped(&app));
+    run_frames(&mut app, 1);
+    assert!(app.world.resource::<GameplayFrames>().0 > frames);
+}
+
+fn in_game_app() -> App {
+    let mut app = state_app();
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::InGame);
+    app
+}
+
+fn set_hidden(app: &mut App, hidden: bool) {
+    app.world.send_event(WindowOccluded {
+        window: Entity::PLACEHOLDER,
+        occluded: hidden,
+    });
+}
+
+#[test]
+fn hiding_the_window_freezes_gameplay_until_it_is_shown() {
+    let mut app = in_game_app();
+
+    set_hidden(&mut app, true);
+    run_frames(&mut app, 1);
+    assert!(time_stopped(&app));
+    let frames = app.world.resource::<GameplayFrames>().0;
+    run_frames(&mut app, 5);
+    assert_eq!(app.world.resource::<GameplayFrames>().0, frames);
+    // hidden isn't a state of its own
+    assert_eq!(state(&app), AppState::InGame);
+
+    set_hidden(&mut app, false);
+    run_frames(&mut app, 2);
+    assert!(!time_stopped(&app));
+    assert!(app.world.resource::<GameplayFrames>().0 > frames);
+}
+
+#[test]
+fn showing_the_window_keeps_a_pause_the_player_asked_for() {
+    let mut app = in_game_app();
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+
+    set_hidden(&mut app, true);
+    run_frames(&mut app, 2);
+    set_hidden(&mut app, false);
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::Paused);
+    assert!(time_stopped(&app));
+
+    // and unpausing while hidden waits for the window
+    set_hidden(&mut app, true);
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert_eq!(state(&app), AppState::InGame);
+    assert!(time_stopped(&app));
+}
+
+#[test]
+fn the_demo_keeps_playing_in_the_background() {
+    let mut app = in_game_app();
+    app.insert_resource(PauseOnHidden(false));
+
+    set_hidden(&mut app, true);
+    let frames = app.world.resource::<GameplayFrames>().0;
+    run_frames(&mut app, 2);
+    assert!(!time_stopped(&app));
+    assert!(app.world.resource::<GameplayFrames>().0 > frames);
+}
+
+#[test]
+fn escape_pauses_and_resumes() {
+    let mut app = in_game_app();
+    app.init_resource::<ButtonInput<KeyCode>>();
+
+    // without bevy's InputPlugin nothing ends just_pressed, so each press is cleared by hand
+    app.world
+        .resource_mut::<ButtonInput<KeyCode>>()
+        .press(KeyCode::Escape);
+    run_frames(&mut app, 1);
+    app.world.resource_mut::<ButtonInput<KeyCode>>().clear();
+    run_frames(&mut app, 1);
+    assert_eq!(state(&app), AppState::Paused);
+
+    let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+    keys.release(KeyCode::Escape);
+    keys.press(KeyCode::Escape);
+    run_frames(&mut app, 1);
+    app.world.resource_mut::<ButtonInput<KeyCode>>().clear();
+    run_frames(&mut app, 1);
+    assert_eq!(state(&app), AppState::InGame);
+    assert!(!time_stopped(&app));
+}
+
+#[test]
+fn resuming_does_not_catch_up_on_the_paused_time() {
+    let mut app = in_game_app();
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    let elapsed = app.world.resource::<Time<Virtual>>().elapsed();
+    run_frames(&mut app, 20);
+
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert!(app.world.resource::<Time<Virtual>>().elapsed() - elapsed <= 2 * FRAME);
+    assert!(app.world.resource::<Time<Fixed>>().overstep() < FRAME);
+}
+
+#[test]
+fn a_hitstop_ending_keeps_the_menu_pause() {
+    let mut app = in_game_app();
+    // the clock moves a FRAME of real time per update
+    app.world.send_event(Hitstop(3.5 * FRAME.as_secs_f32()));
+    run_frames(&mut app, 1);
+    assert!(time_stopped(&app));
+    assert_eq!(state(&app), AppState::InGame);
+
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 5);
+    assert_eq!(state(&app), AppState::Paused);
+    assert!(time_stopped(&app));
+
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert!(!time_stopped(&app));
+}
+
+fn speed(app: &App) -> f32 {
+    app.world.resource::<Time<Virtual>>().relative_speed()
+}
+
+#[test]
+fn timed_slow_motion_eases_back_to_full_speed() {
+    let mut app = in_game_app();
+    app.world
+        .send_event(SetTimeScale::for_seconds(0.25, 4.0 * FRAME.as_secs_f32()));
+    run_frames(&mut app, 1);
+    assert_eq!(speed(&app), 0.25);
+
+    run_frames(&mut app, 5);
+    let easing = speed(&app);
+    assert!(easing > 0.25 && easing < 1.0);
+
+    let ramp_frames = (TIME_SCALE_RAMP / FRAME.as_secs_f32()).ceil() as u32;
+    run_frames(&mut app, ramp_frames);
+    assert_eq!(speed(&app), 1.0);
+}
+
+#[test]
+fn time_scales_are_clamped_and_zero_is_refused() {
+    let mut app = in_game_app();
+    app.world.send_event(SetTimeScale::new(100.0));
+    run_frames(&mut app, 1);
+    assert_eq!(speed(&app), MAX_TIME_SCALE);
+
+    app.world.send_event(SetTimeScale::new(0.0));
+    app.world.send_event(SetTimeScale::new(-1.0));
+    run_frames(&mut app, 1);
+    assert_eq!(speed(&app), MAX_TIME_SCALE);
+
+    // pausing in fast-forward comes back to it
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert!(time_stopped(&app));
+    app.world.send_event(TogglePause);
+    run_frames(&mut app, 2);
+    assert_eq!(speed(&app), MAX_TIME_SCALE);
+}
@@ -0,0 +1,75 @@
+//! Tests for the fixed timestep and the order of its sets.
+
+use bevy::prelude::*;
+use gamedevjam2024::testing::{headless_app, run_frames};
+use gamedevjam2024::timestep::{
+    FixedSet, FixedStepConfig, FixedStepPlugin, SetFixedRate, DEFAULT_FIXED_HZ, MAX_FIXED_HZ,
+};
+use std::time::Duration;
+
+#[derive(Resource, Default)]
+struct Order(Vec<FixedSet>);
+
+fn ran(set: FixedSet) -> impl FnMut(ResMut<Order>) {
+    move |mut order: ResMut<Order>| order.0.push(set)
+}
+
+fn timestep(app: &App) -> Duration {
+    app.world.resource::<Time<Fixed>>().timestep()
+}
+
+#[test]
+fn fixed_sets_run_in_order() {
+    let mut app = headless_app();
+    app.add_plugins(FixedStepPlugin)
+        .init_resource::<Order>()
+        // added backwards, run in order
+        .add_systems(
+            FixedUpdate,
+            (
+                ran(FixedSet::PostPhysics).in_set(FixedSet::PostPhysics),
+                ran(FixedSet::Physics).in_set(FixedSet::Physics),
+                ran(FixedSet::Gameplay).in_set(FixedSet::Gameplay),
+                ran(FixedSet::Input).in_set(FixedSet::Input),
+            ),
+        );
+    run_frames(&mut app, 3);
+    let order = &app.world.resource::<Order>().0;
+    assert!(!order.is_empty());
+    for step in order.chunks(4) {
+        assert_eq!(
+            step,
+            [
+                FixedSet::Input,
+                FixedSet::Gameplay,
+                FixedSet::Physics,
+                FixedSet::PostPhysics
+            ]
+        );
+    }
+}
+
+#[test]
+fn the_fixed_rate_follows_the_config() {
+    let mut app = headless_app();
+    app.add_plugins(FixedStepPlugin)
+        .insert_resource(FixedStepConfig::new(30.0).unwrap());
+    run_frames(&mut app, 1);
+    assert_eq!(timestep(&app), Duration::from_secs_f64(1.0 / 30.0));
+
+    app.world.send_event(SetFixedRate(120.0));
+    run_frames(&mut app, 1);
+    assert_eq!(timestep(&app), Duration::from_secs_f64(1.0 / 120.0));
+
+    // out of range rates are clamped, and what isn't a rate is ignored
+    app.world.send_event(SetFixedRate(10_000.0));
+    run_frames(&mut app, 1);
+    assert_eq!(timestep(&app), Duration::from_secs_f64(1.0 / MAX_FIXED_HZ));
+    app.world.send_event(SetFixedRate(f64::NAN));
+    app.world.send_event(SetFixedRate(-5.0));
+    run_frames(&mut app, 1);
+    assert_eq!(app.world.resource::<FixedStepConfig>().hz, MAX_FIXED_HZ);
+
+    assert_eq!(FixedStepConfig::default().hz, DEFAULT_FIXED_HZ);
+    assert_eq!(FixedStepConfig::new(0.0), None);
+}
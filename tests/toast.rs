@@ -0,0 +1,92 @@
+//! Tests for toasts.
+
+use bevy::prelude::*;
+use gamedevjam2024::state::{AppState, AppStatePlugin, ChangeState};
+use gamedevjam2024::testing::{game_app, run_frames};
+use gamedevjam2024::toast::{ShowToast, Toast, ToastLevel, MAX_TOASTS};
+
+fn toasts(app: &mut App) -> Vec<Toast> {
+    app.world
+        .query::<&Toast>()
+        .iter(&app.world)
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn repeats_count_up_on_one_toast() {
+    let mut app = game_app();
+    run_frames(&mut app, 1);
+    for _ in 0..3 {
+        app.world.send_event(ShowToast::info("Game saved"));
+    }
+    run_frames(&mut app, 1);
+    app.world.send_event(ShowToast::info("Game saved"));
+    // the same text at another level is another toast
+    app.world.send_event(ShowToast::error("Game saved"));
+    run_frames(&mut app, 1);
+
+    let shown = toasts(&mut app);
+    assert_eq!(shown.len(), 2);
+    let info = shown
+        .iter()
+        .find(|toast| toast.level == ToastLevel::Info)
+        .unwrap();
+    assert_eq!(info.label(), "Game saved ×4");
+}
+
+#[test]
+fn the_oldest_make_room() {
+    let mut app = game_app();
+    run_frames(&mut app, 1);
+    for i in 0..MAX_TOASTS + 2 {
+        app.world
+            .send_event(ShowToast::info(format!("Toast {}", i)));
+    }
+    run_frames(&mut app, 1);
+
+    let mut texts: Vec<String> = toasts(&mut app)
+        .into_iter()
+        .map(|toast| toast.text)
+        .collect();
+    texts.sort();
+    assert_eq!(texts.len(), MAX_TOASTS);
+    assert!(!texts.contains(&"Toast 0".to_string()));
+}
+
+#[test]
+fn toasts_fade_out_and_go() {
+    let mut app = game_app();
+    run_frames(&mut app, 1);
+    app.world
+        .send_event(ShowToast::warning("Missing sound: boss_theme").with_duration(1.0));
+    run_frames(&mut app, 48);
+
+    let border = app
+        .world
+        .query_filtered::<&BorderColor, With<Toast>>()
+        .single(&app.world)
+        .0;
+    assert!(border.a() < 1.0);
+    assert_eq!(border.with_a(1.0), ToastLevel::Warning.accent());
+
+    run_frames(&mut app, 20);
+    assert!(toasts(&mut app).is_empty());
+}
+
+#[test]
+fn toasts_stay_up_across_states() {
+    let mut app = game_app();
+    app.add_plugins(AppStatePlugin);
+    run_frames(&mut app, 1);
+    app.world.send_event(ShowToast::info("Loading"));
+    run_frames(&mut app, 2);
+    app.world.send_event(ChangeState(AppState::InGame));
+    run_frames(&mut app, 2);
+
+    assert_eq!(
+        *app.world.resource::<State<AppState>>().get(),
+        AppState::InGame
+    );
+    assert_eq!(toasts(&mut app).len(), 1);
+}
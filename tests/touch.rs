@@ -0,0 +1,129 @@
+//! Tests for the on-screen touch controls.
+
+use bevy::input::touch::{TouchInput, TouchPhase};
+use bevy::prelude::*;
+use gamedevjam2024::input::{
+    Action, ActionAxis, ActionState, InputMap, InputPlugin, TouchControls, TouchControlsPlugin,
+};
+use gamedevjam2024::testing::{headless_app, run_frames, spawn_window};
+
+fn touch_app(forced: Option<bool>) -> (App, Entity) {
+    let mut app = headless_app();
+    app.add_plugins((
+        bevy::input::InputPlugin,
+        InputPlugin,
+        TouchControlsPlugin { forced },
+    ));
+    let window = spawn_window(&mut app, 1.0);
+    run_frames(&mut app, 1);
+    (app, window)
+}
+
+fn touch(app: &mut App, window: Entity, id: u64, phase: TouchPhase, position: Vec2) {
+    app.world.send_event(TouchInput {
+        phase,
+        position,
+        window,
+        force: None,
+        id,
+    });
+}
+
+fn actions(app: &App) -> &ActionState {
+    app.world.resource::<ActionState>()
+}
+
+/// The size of spawn_window's window
+const WINDOW: Vec2 = Vec2::new(1280.0, 720.0);
+
+fn button(app: &App, index: usize) -> Vec2 {
+    app.world
+        .resource::<TouchControls>()
+        .button_center(index, WINDOW)
+}
+
+#[test]
+fn the_first_touch_turns_the_controls_on() {
+    let (mut app, window) = touch_app(None);
+    assert!(!app.world.resource::<TouchControls>().enabled);
+
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Started,
+        Vec2::new(900.0, 100.0),
+    );
+    run_frames(&mut app, 1);
+    assert!(app.world.resource::<TouchControls>().enabled);
+
+    let (mut app, window) = touch_app(Some(false));
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Started,
+        Vec2::new(200.0, 500.0),
+    );
+    run_frames(&mut app, 1);
+    assert!(!app.world.resource::<TouchControls>().enabled);
+    assert_eq!(actions(&app).axis(ActionAxis::MoveX), 0.0);
+}
+
+#[test]
+fn moving_and_pressing_a_button_work_together() {
+    let (mut app, window) = touch_app(Some(true));
+    {
+        let mut map = app.world.resource_mut::<InputMap>();
+        map.dead_zone = 0.0;
+        map.response_curve = 1.0;
+    }
+    let origin = Vec2::new(200.0, 500.0);
+    touch(&mut app, window, 1, TouchPhase::Started, origin);
+    run_frames(&mut app, 1);
+    // the stick is 0.12 of the 720 pixel side: 86.4 pixels
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Moved,
+        origin + Vec2::new(43.2, 0.0),
+    );
+    let attack = button(&app, 0);
+    touch(&mut app, window, 2, TouchPhase::Started, attack);
+    run_frames(&mut app, 1);
+
+    assert!((actions(&app).axis(ActionAxis::MoveX) - 0.5).abs() < 1e-4);
+    assert!(actions(&app).pressed(Action::MoveRight));
+    assert!(actions(&app).just_pressed(Action::Attack));
+
+    touch(
+        &mut app,
+        window,
+        1,
+        TouchPhase::Moved,
+        origin + Vec2::new(0.0, -200.0),
+    );
+    run_frames(&mut app, 1);
+    assert!((actions(&app).axis(ActionAxis::MoveY) - 1.0).abs() < 1e-4);
+    assert!(actions(&app).pressed(Action::Attack));
+
+    touch(&mut app, window, 2, TouchPhase::Ended, attack);
+    touch(&mut app, window, 1, TouchPhase::Ended, origin);
+    run_frames(&mut app, 1);
+    assert!(actions(&app).held().is_empty());
+    assert!(actions(&app).just_released(Action::Attack));
+}
+
+#[test]
+fn a_tap_holds_its_button_for_a_frame() {
+    let (mut app, window) = touch_app(Some(true));
+    let interact = button(&app, 1);
+    touch(&mut app, window, 3, TouchPhase::Started, interact);
+    touch(&mut app, window, 3, TouchPhase::Ended, interact);
+    run_frames(&mut app, 1);
+    assert!(actions(&app).just_pressed(Action::Interact));
+
+    run_frames(&mut app, 1);
+    assert!(!actions(&app).pressed(Action::Interact));
+}
@@ -3,6 +3,7 @@
 #![cfg(target_arch = "wasm32")]
 
 extern crate wasm_bindgen_test;
+use wasm_bindgen::JsCast;
 use wasm_bindgen_test::*;
 
 wasm_bindgen_test_configure!(run_in_browser);
@@ -11,3 +12,59 @@ wasm_bindgen_test_configure!(run_in_browser);
 fn pass() {
     assert_eq!(1 + 1, 2);
 }
+
+#[wasm_bindgen_test]
+fn start_options_default_every_missing_field() {
+    use gamedevjam2024::options::StartOptions;
+    use wasm_bindgen::JsValue;
+
+    assert_eq!(
+        StartOptions::from_js(JsValue::UNDEFINED).unwrap(),
+        StartOptions::default()
+    );
+    assert_eq!(
+        StartOptions::from_js(js_sys::JSON::parse("{}").unwrap()).unwrap(),
+        StartOptions::default()
+    );
+
+    let options = js_sys::JSON::parse(
+        r##"{ "canvas": "#game", "width": 640, "assetPath": "cdn/assets", "muted": true }"##,
+    )
+    .unwrap();
+    assert_eq!(
+        StartOptions::from_js(options).unwrap(),
+        StartOptions {
+            canvas: Some("#game".to_string()),
+            width: 640.0,
+            asset_path: "cdn/assets".to_string(),
+            muted: true,
+            ..StartOptions::default()
+        }
+    );
+
+    let wrong = js_sys::JSON::parse(r#"{ "width": "wide" }"#).unwrap();
+    assert!(StartOptions::from_js(wrong).is_err());
+}
+
+#[wasm_bindgen_test]
+fn start_throws_instead_of_panicking_on_a_missing_canvas() {
+    let options = js_sys::JSON::parse(r##"{ "canvas": "#not-on-the-page" }"##).unwrap();
+    let error = gamedevjam2024::start(options).unwrap_err();
+    let error = error.dyn_into::<js_sys::Error>().unwrap();
+    assert_eq!(
+        String::from(error.message()),
+        "no canvas matches #not-on-the-page"
+    );
+
+    let options = js_sys::JSON::parse(r#"{ "muted": "yes" }"#).unwrap();
+    assert!(gamedevjam2024::start(options).is_err());
+}
+
+#[wasm_bindgen_test]
+fn get_version_has_the_crate_version() {
+    let version = js_sys::Reflect::get(&gamedevjam2024::get_version(), &"version".into()).unwrap();
+    assert_eq!(
+        version.as_string().as_deref(),
+        Some(env!("CARGO_PKG_VERSION"))
+    );
+}